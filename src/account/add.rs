@@ -1,4 +1,6 @@
 use crate::app_config;
+use crate::common::api_stats::StatsDelegate;
+use crate::common::hub_helper;
 use crate::hub;
 use std::error;
 use std::fmt::Display;
@@ -29,11 +31,13 @@ pub async fn add() -> Result<(), Error> {
     .await
     .map_err(Error::AccessToken)?;
 
-    let hub = hub::Hub::new(auth).await;
+    let http_config = hub_helper::http_config();
+    let hub = hub::Hub::new(auth, &http_config).await;
     let (_, about) = hub
         .about()
         .get()
         .param("fields", "user")
+        .delegate(&mut StatsDelegate)
         .doit()
         .await
         .map_err(Error::About)?;
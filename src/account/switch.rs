@@ -1,5 +1,6 @@
 use crate::app_config;
 use crate::app_config::AppConfig;
+use crate::config::settings;
 use std::error;
 use std::fmt::Display;
 use std::fmt::Formatter;
@@ -17,6 +18,15 @@ pub fn switch(config: Config) -> Result<(), Error> {
     app_config::switch_account(&app_cfg).map_err(Error::AppConfig)?;
     println!("Switched to account '{}'", &config.account_name);
 
+    if let Some(pinned) = &settings::current().account {
+        if pinned != &config.account_name {
+            eprintln!(
+                "Warning: The account setting selects '{}', which takes precedence over the switch. Remove it from config.toml, GDRIVE_ACCOUNT or --account to use '{}'",
+                pinned, config.account_name
+            );
+        }
+    }
+
     Ok(())
 }
 
@@ -0,0 +1,313 @@
+use crate::common::drive_file;
+use crate::common::hub_helper;
+use crate::common::table;
+use crate::common::table::Table;
+use crate::files;
+use crate::hub;
+use crate::hub::Hub;
+use google_drive3::chrono;
+use google_drive3::hyper;
+use serde::Deserialize;
+use serde_json::json;
+use serde_json::Value;
+use std::cmp::min;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::io;
+
+const ACTIVITY_QUERY_URL: &str = "https://driveactivity.googleapis.com/v2/activity:query";
+const ACTIVITY_SCOPE: &str = "https://www.googleapis.com/auth/drive.activity.readonly";
+const MAX_PAGE_SIZE: usize = 100;
+
+pub struct Config {
+    pub file_id: String,
+    pub max_activities: usize,
+    pub json: bool,
+    pub skip_header: bool,
+    pub field_separator: String,
+}
+
+pub async fn activity(config: Config) -> Result<(), Error> {
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+
+    let file = files::info::get_file(&hub, &config.file_id)
+        .await
+        .map_err(Error::GetFile)?;
+
+    // Activity of a folder includes everything in it
+    let target = if drive_file::is_directory(&file) {
+        Target::Ancestor(config.file_id.clone())
+    } else {
+        Target::Item(config.file_id.clone())
+    };
+
+    let activities = query_activities(&hub, &target, config.max_activities).await?;
+
+    if config.json {
+        let json = serde_json::to_string_pretty(&activities).map_err(Error::Serialize)?;
+        println!("{}", json);
+        return Ok(());
+    }
+
+    let mut values: Vec<[String; 5]> = vec![];
+
+    for value in activities {
+        let activity: Activity = serde_json::from_value(value).map_err(Error::Parse)?;
+        values.push([
+            activity.time().unwrap_or_default(),
+            activity.actor(),
+            activity.action(),
+            activity.item(),
+            activity.details(),
+        ])
+    }
+
+    let table = Table {
+        header: ["Time", "Actor", "Action", "Item", "Details"],
+        values,
+    };
+
+    let _ = table::write(
+        io::stdout(),
+        table,
+        &table::DisplayConfig {
+            skip_header: config.skip_header,
+            separator: config.field_separator,
+        },
+    );
+
+    Ok(())
+}
+
+pub enum Target {
+    Item(String),
+    Ancestor(String),
+}
+
+// Activities are returned newest first
+pub async fn query_activities(
+    hub: &Hub,
+    target: &Target,
+    max_activities: usize,
+) -> Result<Vec<Value>, Error> {
+    let mut collected: Vec<Value> = vec![];
+    let mut next_page_token: Option<String> = None;
+
+    loop {
+        let mut body = match target {
+            Target::Item(id) => json!({ "itemName": format!("items/{}", id) }),
+            Target::Ancestor(id) => json!({ "ancestorName": format!("items/{}", id) }),
+        };
+        body["pageSize"] = json!(min(MAX_PAGE_SIZE, max_activities - collected.len()));
+        if let Some(token) = next_page_token {
+            body["pageToken"] = json!(token);
+        }
+
+        let request = hyper::Request::post(ACTIVITY_QUERY_URL)
+            .header(hyper::header::CONTENT_TYPE, "application/json");
+        let response = hub
+            .send_with_scope(request, hyper::Body::from(body.to_string()), ACTIVITY_SCOPE)
+            .await
+            .map_err(Error::Query)?;
+
+        let status = response.status();
+        let bytes = hyper::body::to_bytes(response.into_body())
+            .await
+            .map_err(|err| Error::Query(hub::RequestError::Http(err)))?;
+
+        if !status.is_success() {
+            return Err(Error::Query(hub::RequestError::Status(
+                status,
+                String::from_utf8_lossy(&bytes).to_string(),
+            )));
+        }
+
+        let mut page: QueryResponse = serde_json::from_slice(&bytes).map_err(Error::Parse)?;
+        collected.append(&mut page.activities);
+        next_page_token = page.next_page_token;
+
+        if collected.len() >= max_activities || next_page_token.is_none() {
+            break;
+        }
+    }
+
+    collected.truncate(max_activities);
+    Ok(collected)
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct QueryResponse {
+    activities: Vec<Value>,
+    next_page_token: Option<String>,
+}
+
+// The parts of an activity that are shown in the table, the json output has all of it.
+// See https://developers.google.com/drive/activity/v2/reference/rest/v2/activity/query
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct Activity {
+    primary_action_detail: serde_json::Map<String, Value>,
+    actors: Vec<Value>,
+    targets: Vec<Value>,
+    timestamp: Option<String>,
+    time_range: Option<TimeRange>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct TimeRange {
+    end_time: Option<String>,
+}
+
+impl Activity {
+    fn time(&self) -> Option<String> {
+        let time = self
+            .timestamp
+            .as_ref()
+            .or_else(|| self.time_range.as_ref()?.end_time.as_ref())?;
+
+        chrono::DateTime::parse_from_rfc3339(time)
+            .ok()
+            .map(|time| files::info::format_date_time(time.with_timezone(&chrono::Utc)))
+    }
+
+    // The api only returns an opaque person id for other users, i.e. people/1234
+    fn actor(&self) -> String {
+        let actor = match self.actors.first() {
+            Some(actor) => actor,
+            None => return String::new(),
+        };
+
+        let known_user = &actor["user"]["knownUser"];
+        if known_user["isCurrentUser"].as_bool() == Some(true) {
+            String::from("you")
+        } else if let Some(person) = known_user["personName"].as_str() {
+            person.to_string()
+        } else {
+            first_key(actor).map(to_kebab_case).unwrap_or_default()
+        }
+    }
+
+    fn action(&self) -> String {
+        self.primary_action_detail
+            .keys()
+            .next()
+            .map(|key| to_kebab_case(key))
+            .unwrap_or_default()
+    }
+
+    fn item(&self) -> String {
+        self.targets
+            .first()
+            .and_then(|target| target["driveItem"]["title"].as_str())
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    fn details(&self) -> String {
+        let (action, detail) = match self.primary_action_detail.iter().next() {
+            Some(entry) => entry,
+            None => return String::new(),
+        };
+
+        match action.as_str() {
+            "rename" => format!(
+                "'{}' -> '{}'",
+                detail["oldTitle"].as_str().unwrap_or_default(),
+                detail["newTitle"].as_str().unwrap_or_default()
+            ),
+            "move" => {
+                let from = item_titles(&detail["removedParents"]);
+                let to = item_titles(&detail["addedParents"]);
+                format!("'{}' -> '{}'", from, to)
+            }
+            "permissionChange" => {
+                let added = roles(&detail["addedPermissions"]);
+                let removed = roles(&detail["removedPermissions"]);
+                [("added", added), ("removed", removed)]
+                    .into_iter()
+                    .filter(|(_, roles)| !roles.is_empty())
+                    .map(|(change, roles)| format!("{} {}", change, roles))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }
+            "create" | "delete" | "restore" => detail["type"]
+                .as_str()
+                .map(|kind| kind.to_lowercase())
+                .or_else(|| first_key(detail).map(to_kebab_case))
+                .unwrap_or_default(),
+            _ => String::new(),
+        }
+    }
+}
+
+fn first_key(value: &Value) -> Option<&str> {
+    value.as_object()?.keys().next().map(|key| key.as_str())
+}
+
+fn item_titles(targets: &Value) -> String {
+    targets
+        .as_array()
+        .map(|targets| {
+            targets
+                .iter()
+                .filter_map(|target| target["driveItem"]["title"].as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .unwrap_or_default()
+}
+
+// i.e. `editor, viewer`
+fn roles(permissions: &Value) -> String {
+    permissions
+        .as_array()
+        .map(|permissions| {
+            permissions
+                .iter()
+                .filter_map(|permission| permission["role"].as_str())
+                .map(|role| role.to_lowercase())
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .unwrap_or_default()
+}
+
+// i.e. permissionChange -> permission-change
+fn to_kebab_case(s: &str) -> String {
+    let mut kebab = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c.is_ascii_uppercase() {
+            kebab.push('-');
+            kebab.push(c.to_ascii_lowercase());
+        } else {
+            kebab.push(c);
+        }
+    }
+    kebab
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    GetFile(google_drive3::Error),
+    Query(hub::RequestError),
+    Parse(serde_json::Error),
+    Serialize(serde_json::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::GetFile(err) => write!(f, "Failed getting file: {}", err),
+            Error::Query(err) => write!(f, "Failed to query activity: {}", err),
+            Error::Parse(err) => write!(f, "Failed to parse activity: {}", err),
+            Error::Serialize(err) => write!(f, "Failed to serialize activity: {}", err),
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::config::settings;
 use serde::Deserialize;
 use serde::Serialize;
 use std::error;
@@ -61,10 +62,17 @@ impl AppConfig {
         }
     }
 
+    // The account from the settings, i.e. --account, takes precedence over the
+    // one selected with `gdrive account switch`
     pub fn load_current_account() -> Result<AppConfig, Error> {
         let base_path = AppConfig::default_base_path()?;
-        let account_config = AppConfig::load_account_config()?;
-        let account = Account::new(&account_config.current);
+        let account = match &settings::current().account {
+            Some(name) if base_path.join(name).join(TOKENS_CONFIG_NAME).exists() => {
+                Account::new(name)
+            }
+            Some(name) => return Err(Error::AccountNotFound(name.clone())),
+            None => Account::new(&AppConfig::load_account_config()?.current),
+        };
         let config = AppConfig { base_path, account };
         Ok(config)
     }
@@ -189,6 +197,74 @@ impl Account {
     }
 }
 
+// Tuning for networks where long transfers get reset, timeouts are in seconds.
+// Set with the http.* settings, see settings::HttpSettings
+#[derive(Debug, Clone, Default)]
+pub struct HttpConfig {
+    pub connect_timeout: Option<u64>,
+    pub read_timeout: Option<u64>,
+    pub keep_alive: Option<u64>,
+    pub http1_only: bool,
+    // Only connect over this address family, some isps route Google's ipv6 poorly
+    pub ip_family: Option<IpFamily>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IpFamily {
+    Ipv4,
+    Ipv6,
+}
+
+impl Display for IpFamily {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IpFamily::Ipv4 => write!(f, "ipv4"),
+            IpFamily::Ipv6 => write!(f, "ipv6"),
+        }
+    }
+}
+
+// How sizes and counts are shown, i.e. "1.5 MiB" and "1,204". Set with the
+// display.* settings, see settings::DisplaySettings
+#[derive(Debug, Clone)]
+pub struct NumberFormat {
+    pub size_unit: SizeUnit,
+    // Sizes get up to one decimal without trailing zeros if not set
+    pub decimals: Option<usize>,
+    pub thousands_separator: String,
+    pub decimal_separator: String,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        NumberFormat {
+            size_unit: SizeUnit::Binary,
+            decimals: None,
+            thousands_separator: String::from(","),
+            decimal_separator: String::from("."),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SizeUnit {
+    // Powers of 1024, i.e. KiB and MiB
+    Binary,
+    // Powers of 1000, i.e. kB and MB
+    Decimal,
+}
+
+impl Display for SizeUnit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SizeUnit::Binary => write!(f, "binary"),
+            SizeUnit::Decimal => write!(f, "decimal"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Secret {
     pub client_id: String,
@@ -211,6 +287,7 @@ pub enum Error {
     CreateConfigDir(io::Error),
     ReadAccountConfig(io::Error),
     AccountConfigMissing,
+    AccountNotFound(String),
     ParseAccountConfig(serde_json::Error),
     SerializeAccountConfig(serde_json::Error),
     WriteAccountConfig(io::Error),
@@ -253,6 +330,12 @@ impl Display for Error {
                 write!(f, "Use `gdrive account switch` to select an account.")
             }
 
+            Error::AccountNotFound(name) => {
+                // fmt
+                writeln!(f, "Account '{}' not found", name)?;
+                write!(f, "Use `gdrive account list` to show all accounts.")
+            }
+
             Error::ParseAccountConfig(err) => {
                 // fmt
                 write!(f, "Failed to parse account config: {}", err)
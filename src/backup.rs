@@ -0,0 +1,299 @@
+use crate::app_config;
+use crate::app_config::AppConfig;
+use crate::common::delegate::UploadDelegateConfig;
+use crate::common::file_tree;
+use crate::common::file_tree::FileTree;
+use crate::common::hash_writer;
+use crate::common::hub_helper;
+use crate::common::id_gen::IdGen;
+use crate::config::settings;
+use crate::files::mkdir;
+use crate::files::shortcut;
+use crate::files::upload;
+use google_drive3::chrono;
+use human_bytes::human_bytes;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+const SNAPSHOTS_DIR_NAME: &str = "backups";
+
+pub struct Config {
+    pub path: PathBuf,
+    pub parent_id: String,
+    pub full: bool,
+}
+
+// State of the last snapshot of a directory, keyed by path relative to the
+// backed up directory. `file_id` always points at the uploaded content, never at
+// a shortcut, so shortcuts don't chain across snapshots
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Snapshot {
+    folder_id: String,
+    time: String,
+    files: HashMap<String, SnapshotFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SnapshotFile {
+    file_id: String,
+    md5: String,
+    size: u64,
+    modified: u64,
+}
+
+pub async fn backup(config: Config) -> Result<(), Error> {
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+    let delegate_config = UploadDelegateConfig {
+        backoff_config: settings::current().backoff(),
+        ..UploadDelegateConfig::default()
+    };
+
+    let snapshot_path = snapshot_path(&config)?;
+    let previous = if config.full {
+        Snapshot::default()
+    } else {
+        load_snapshot(&snapshot_path)?
+    };
+
+    let mut ids = IdGen::new(&hub, &delegate_config);
+    let tree = FileTree::from_path(&config.path, &mut ids)
+        .await
+        .map_err(Error::CreateFileTree)?;
+
+    let time = chrono::Local::now();
+    let snapshot_name = format!("{} {}", tree.root.name, time.format("%Y-%m-%d %H.%M.%S"));
+    println!("Creating snapshot '{}'", snapshot_name);
+
+    let mut snapshot = Snapshot {
+        folder_id: tree.root.drive_id.clone(),
+        time: time.to_rfc3339(),
+        files: HashMap::new(),
+    };
+    let mut uploaded = Totals::default();
+    let mut linked = Totals::default();
+
+    for folder in &tree.folders() {
+        let (name, parents) = match &folder.parent {
+            Some(parent) => (folder.name.clone(), vec![parent.drive_id.clone()]),
+            None => (snapshot_name.clone(), vec![config.parent_id.clone()]),
+        };
+
+        mkdir::create_directory(
+            &hub,
+            &mkdir::Config {
+                id: Some(folder.drive_id.clone()),
+                name,
+                parents: Some(parents),
+                print_only_id: false,
+            },
+            delegate_config.clone(),
+        )
+        .await
+        .map_err(Error::Mkdir)?;
+
+        for file in folder.files() {
+            let key = relative_key(&tree, &file.path);
+            let modified = modified_secs(&file.path);
+            let parents = Some(vec![folder.drive_id.clone()]);
+
+            let unchanged = match previous.files.get(&key) {
+                Some(prev) if prev.size == file.size && prev.modified == modified => {
+                    Some(prev.clone())
+                }
+                Some(prev) if prev.size == file.size => {
+                    let md5 = hash_writer::md5_of_file(&file.path)
+                        .map_err(|err| Error::OpenFile(file.path.clone(), err))?;
+                    (md5 == prev.md5).then(|| prev.clone())
+                }
+                _ => None,
+            };
+
+            let entry = match unchanged {
+                Some(prev) => {
+                    shortcut::create_shortcut(
+                        &hub,
+                        &shortcut::Config {
+                            id: Some(file.drive_id.clone()),
+                            name: file.name.clone(),
+                            parents,
+                            target_id: prev.file_id.clone(),
+                        },
+                        delegate_config.clone(),
+                    )
+                    .await
+                    .map_err(Error::Shortcut)?;
+
+                    linked.add(file.size);
+                    SnapshotFile { modified, ..prev }
+                }
+
+                None => {
+                    println!("Uploading '{}'", key);
+                    let os_file = fs::File::open(&file.path)
+                        .map_err(|err| Error::OpenFile(file.path.clone(), err))?;
+                    let drive_file = upload::upload_file(
+                        &hub,
+                        os_file,
+                        Some(file.drive_id.clone()),
+                        file.info(parents),
+                        delegate_config.clone(),
+                    )
+                    .await
+                    .map_err(Error::Upload)?;
+
+                    uploaded.add(file.size);
+                    SnapshotFile {
+                        file_id: file.drive_id.clone(),
+                        md5: drive_file.md5_checksum.unwrap_or_default(),
+                        size: file.size,
+                        modified,
+                    }
+                }
+            };
+
+            snapshot.files.insert(key, entry);
+        }
+    }
+
+    save_snapshot(&snapshot_path, &snapshot)?;
+
+    println!(
+        "Uploaded {} files ({}), linked {} unchanged files ({}) to earlier snapshots",
+        uploaded.count,
+        human_bytes(uploaded.bytes as f64),
+        linked.count,
+        human_bytes(linked.bytes as f64)
+    );
+    println!("Snapshot folder id: {}", snapshot.folder_id);
+
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+struct Totals {
+    count: u64,
+    bytes: u64,
+}
+
+impl Totals {
+    fn add(&mut self, size: u64) {
+        self.count += 1;
+        self.bytes += size;
+    }
+}
+
+fn relative_key(tree: &FileTree, path: &Path) -> String {
+    path.strip_prefix(&tree.root.path)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .to_string()
+}
+
+fn modified_secs(path: &Path) -> u64 {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+// One snapshot state per backed up directory and destination folder
+fn snapshot_path(config: &Config) -> Result<PathBuf, Error> {
+    let canonical_path = config
+        .path
+        .canonicalize()
+        .map_err(|err| Error::OpenFile(config.path.clone(), err))?;
+    let key = md5::compute(format!(
+        "{}\n{}",
+        canonical_path.display(),
+        config.parent_id
+    ));
+
+    let app_cfg = AppConfig::load_current_account().map_err(Error::AppConfig)?;
+    Ok(app_cfg
+        .account_base_path()
+        .join(SNAPSHOTS_DIR_NAME)
+        .join(format!("{:x}.json", key)))
+}
+
+fn load_snapshot(path: &Path) -> Result<Snapshot, Error> {
+    if !path.exists() {
+        return Ok(Snapshot::default());
+    }
+
+    let content =
+        fs::read_to_string(path).map_err(|err| Error::ReadSnapshot(path.to_path_buf(), err))?;
+    serde_json::from_str(&content).map_err(Error::ParseSnapshot)
+}
+
+fn save_snapshot(path: &Path, snapshot: &Snapshot) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| Error::WriteSnapshot(dir.to_path_buf(), err))?;
+    }
+
+    let content = serde_json::to_string(snapshot).map_err(Error::SerializeSnapshot)?;
+    fs::write(path, content).map_err(|err| Error::WriteSnapshot(path.to_path_buf(), err))
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    AppConfig(app_config::Error),
+    CreateFileTree(file_tree::Error),
+    OpenFile(PathBuf, io::Error),
+    Mkdir(google_drive3::Error),
+    Upload(google_drive3::Error),
+    Shortcut(google_drive3::Error),
+    ReadSnapshot(PathBuf, io::Error),
+    WriteSnapshot(PathBuf, io::Error),
+    ParseSnapshot(serde_json::Error),
+    SerializeSnapshot(serde_json::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::AppConfig(err) => write!(f, "{}", err),
+            Error::CreateFileTree(err) => write!(f, "Failed to create file tree: {}", err),
+            Error::OpenFile(path, err) => {
+                write!(f, "Failed to open file '{}': {}", path.display(), err)
+            }
+            Error::Mkdir(err) => write!(f, "Failed to create directory: {}", err),
+            Error::Upload(err) => write!(f, "Failed to upload file: {}", err),
+            Error::Shortcut(err) => write!(f, "Failed to create shortcut: {}", err),
+            Error::ReadSnapshot(path, err) => {
+                write!(
+                    f,
+                    "Failed to read snapshot state '{}': {}",
+                    path.display(),
+                    err
+                )
+            }
+            Error::WriteSnapshot(path, err) => {
+                write!(
+                    f,
+                    "Failed to write snapshot state '{}': {}",
+                    path.display(),
+                    err
+                )
+            }
+            Error::ParseSnapshot(err) => write!(f, "Failed to parse snapshot state: {}", err),
+            Error::SerializeSnapshot(err) => {
+                write!(f, "Failed to serialize snapshot state: {}", err)
+            }
+        }
+    }
+}
@@ -0,0 +1,459 @@
+use crate::common::delegate::UploadDelegateConfig;
+use crate::common::drive_file;
+use crate::common::drive_url;
+use crate::common::file_info;
+use crate::common::file_info::FileInfo;
+use crate::common::hub_helper;
+use crate::common::permission;
+use crate::common::quota;
+use crate::config::settings;
+use crate::files;
+use crate::files::delete;
+use crate::files::mkdir;
+use crate::files::mv;
+use crate::files::upload;
+use crate::hub::Hub;
+use crate::permissions::share;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+pub struct Config {
+    pub plan_path: PathBuf,
+    pub continue_on_error: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Op {
+    Upload,
+    Mkdir,
+    Move,
+    Permission,
+    Delete,
+}
+
+impl Display for Op {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Op::Upload => write!(f, "upload"),
+            Op::Mkdir => write!(f, "mkdir"),
+            Op::Move => write!(f, "move"),
+            Op::Permission => write!(f, "permission"),
+            Op::Delete => write!(f, "delete"),
+        }
+    }
+}
+
+// One operation of a plan. The same fields are used for json and the columns of
+// csv, each operation only reads the fields it needs. Ids can refer to what an
+// earlier step created with `$<label>`, where the label is that step's `as`
+#[derive(Debug, Clone, Deserialize)]
+pub struct Step {
+    pub op: Op,
+    #[serde(rename = "as")]
+    pub label: Option<String>,
+    pub id: Option<String>,
+    pub path: Option<PathBuf>,
+    pub name: Option<String>,
+    pub parent: Option<String>,
+    pub to: Option<String>,
+    pub role: Option<String>,
+    #[serde(rename = "type")]
+    pub type_: Option<String>,
+    pub email: Option<String>,
+    pub domain: Option<String>,
+    pub permanent: Option<bool>,
+    pub recursive: Option<bool>,
+}
+
+impl Step {
+    fn describe(&self) -> String {
+        let subject = match self.op {
+            Op::Upload => self.path.as_ref().map(|path| path.display().to_string()),
+            Op::Mkdir => self.name.clone(),
+            Op::Move | Op::Permission | Op::Delete => self.id.clone(),
+        };
+
+        format!("{} '{}'", self.op, subject.unwrap_or_default())
+    }
+
+    fn required_fields(&self) -> Vec<(&'static str, bool)> {
+        match self.op {
+            Op::Upload => vec![("path", self.path.is_some())],
+            Op::Mkdir => vec![("name", self.name.is_some())],
+            Op::Move => vec![("id", self.id.is_some()), ("to", self.to.is_some())],
+            Op::Permission => vec![
+                ("id", self.id.is_some()),
+                ("role", self.role.is_some()),
+                ("type", self.type_.is_some()),
+            ],
+            Op::Delete => vec![("id", self.id.is_some())],
+        }
+    }
+
+    fn references(&self) -> Vec<&str> {
+        [&self.id, &self.parent, &self.to]
+            .into_iter()
+            .flatten()
+            .filter_map(|value| value.strip_prefix('$'))
+            .collect()
+    }
+}
+
+// Steps run in order. A failed step stops the plan unless `continue_on_error`
+// is set, the plan fails at the end then
+pub async fn run(config: Config) -> Result<(), Error> {
+    let steps = read_plan(&config.plan_path)?;
+    validate(&steps)?;
+
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+
+    let delegate_config = UploadDelegateConfig {
+        backoff_config: settings::current().backoff(),
+        ..UploadDelegateConfig::default()
+    };
+
+    let total = steps.len();
+    let mut labels: HashMap<String, String> = HashMap::new();
+    let mut failed = 0;
+
+    for (index, step) in steps.iter().enumerate() {
+        let prefix = format!("[{}/{}] {}", index + 1, total, step.describe());
+
+        match run_step(&hub, &delegate_config, step, &labels).await {
+            Ok(file_id) => {
+                println!("{}: ok {}", prefix, file_id);
+                if let Some(label) = &step.label {
+                    labels.insert(label.clone(), file_id);
+                }
+            }
+
+            Err(err) => {
+                failed += 1;
+                println!("{}: failed: {}", prefix, err);
+
+                // The next steps would fail the same way until the quota is back
+                if !config.continue_on_error || quota::exhausted().is_some() {
+                    return Err(Error::StepFailed(index + 1));
+                }
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(Error::StepsFailed(failed, total));
+    }
+
+    println!("All {} steps succeeded", total);
+
+    Ok(())
+}
+
+fn read_plan(path: &PathBuf) -> Result<Vec<Step>, Error> {
+    let content = fs::read_to_string(path).map_err(|err| Error::ReadPlan(path.clone(), err))?;
+
+    let is_csv = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+
+    let steps: Vec<Step> = if is_csv {
+        csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(content.as_bytes())
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .map_err(Error::ParseCsv)?
+    } else {
+        serde_json::from_str(&content).map_err(Error::ParseJson)?
+    };
+
+    if steps.is_empty() {
+        return Err(Error::EmptyPlan);
+    }
+
+    Ok(steps)
+}
+
+// Checks the whole plan before anything is changed, so a typo in a late step
+// doesn't leave a migration half done
+fn validate(steps: &[Step]) -> Result<(), Error> {
+    let mut labels: Vec<&str> = vec![];
+
+    for (index, step) in steps.iter().enumerate() {
+        let invalid = |err| Error::InvalidStep(index + 1, err);
+
+        if let Some((field, _)) = step
+            .required_fields()
+            .into_iter()
+            .find(|(_, present)| !present)
+        {
+            return Err(invalid(StepError::MissingField(field)));
+        }
+
+        if let Some(label) = step.references().into_iter().find(|l| !labels.contains(l)) {
+            return Err(invalid(StepError::UnknownLabel(label.to_string())));
+        }
+
+        if step.op == Op::Permission {
+            step.role
+                .as_deref()
+                .unwrap_or_default()
+                .parse::<permission::Role>()
+                .map_err(|err| invalid(StepError::InvalidValue(err)))?;
+            step.type_
+                .as_deref()
+                .unwrap_or_default()
+                .parse::<permission::Type>()
+                .map_err(|err| invalid(StepError::InvalidValue(err)))?;
+        }
+
+        if let Some(label) = &step.label {
+            labels.push(label);
+        }
+    }
+
+    Ok(())
+}
+
+// Returns the id of the file the step created or changed
+async fn run_step(
+    hub: &Hub,
+    delegate_config: &UploadDelegateConfig,
+    step: &Step,
+    labels: &HashMap<String, String>,
+) -> Result<String, StepError> {
+    let resolve = |value: &Option<String>| -> Result<Option<String>, StepError> {
+        match value.as_deref() {
+            Some(value) => match value.strip_prefix('$') {
+                Some(label) => labels
+                    .get(label)
+                    .cloned()
+                    .map(Some)
+                    .ok_or(StepError::UnknownLabel(label.to_string())),
+                None => Ok(Some(
+                    drive_url::extract_id(value).unwrap_or_else(|| value.to_string()),
+                )),
+            },
+            None => Ok(None),
+        }
+    };
+
+    let id = resolve(&step.id)?.unwrap_or_default();
+    let parent = resolve(&step.parent)?;
+
+    match step.op {
+        Op::Upload => {
+            let path = step.path.clone().unwrap_or_default();
+            if path.is_dir() {
+                return Err(StepError::IsDirectory(path));
+            }
+
+            let file =
+                fs::File::open(&path).map_err(|err| StepError::OpenFile(path.clone(), err))?;
+            let mut file_info = FileInfo::from_file(
+                &file,
+                &file_info::Config {
+                    file_path: path.clone(),
+                    mime_type: None,
+                    parents: parent.map(|parent| vec![parent]),
+                },
+            )
+            .map_err(StepError::FileInfo)?;
+
+            if let Some(name) = &step.name {
+                file_info.name = name.clone();
+            }
+
+            let reader = io::BufReader::new(file);
+            let file = upload::upload_file(hub, reader, None, file_info, delegate_config.clone())
+                .await
+                .map_err(StepError::Drive)?;
+
+            Ok(file.id.unwrap_or_default())
+        }
+
+        Op::Mkdir => {
+            let file = mkdir::create_directory(
+                hub,
+                &mkdir::Config {
+                    id: None,
+                    name: step.name.clone().unwrap_or_default(),
+                    parents: parent.map(|parent| vec![parent]),
+                    print_only_id: false,
+                },
+                delegate_config.clone(),
+            )
+            .await
+            .map_err(StepError::Drive)?;
+
+            Ok(file.id.unwrap_or_default())
+        }
+
+        Op::Move => {
+            let to = resolve(&step.to)?.unwrap_or_default();
+            let file = files::info::get_file(hub, &id)
+                .await
+                .map_err(StepError::Drive)?;
+
+            let old_parent_id = match file.parents.unwrap_or_default().as_slice() {
+                [parent] => parent.clone(),
+                [] => return Err(StepError::NoParents),
+                _ => return Err(StepError::MultipleParents),
+            };
+
+            mv::change_parent(
+                hub,
+                delegate_config.clone(),
+                &mv::ChangeParentConfig {
+                    file_id: id.clone(),
+                    old_parent_id,
+                    new_parent_id: to,
+                },
+            )
+            .await
+            .map_err(StepError::Drive)?;
+
+            Ok(id)
+        }
+
+        Op::Permission => {
+            let role = step.role.as_deref().unwrap_or_default().parse();
+            let type_ = step.type_.as_deref().unwrap_or_default().parse();
+
+            share::create_permission(
+                hub,
+                delegate_config.clone(),
+                &share::Config {
+                    file_id: id.clone(),
+                    role: role.map_err(StepError::InvalidValue)?,
+                    type_: type_.map_err(StepError::InvalidValue)?,
+                    discoverable: false,
+                    email: step.email.clone(),
+                    domain: step.domain.clone(),
+                },
+            )
+            .await
+            .map_err(StepError::Drive)?;
+
+            Ok(id)
+        }
+
+        Op::Delete => {
+            let file = files::info::get_file(hub, &id)
+                .await
+                .map_err(StepError::Drive)?;
+
+            if drive_file::is_directory(&file) && !step.recursive.unwrap_or_default() {
+                return Err(StepError::RecursiveRequired);
+            }
+
+            let operation = if step.permanent.unwrap_or_default() {
+                delete::Operation::DeletePermanent
+            } else {
+                delete::Operation::Trash
+            };
+
+            let name = file.name.unwrap_or_default();
+            let parents = file.parents.unwrap_or_default();
+            let journal_error = delete::apply_and_record(hub, &id, &name, &parents, operation)
+                .await
+                .map_err(StepError::Drive)?;
+            if let Some(err) = journal_error {
+                eprintln!("Warning: {}", err);
+            }
+
+            Ok(id)
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum StepError {
+    MissingField(&'static str),
+    UnknownLabel(String),
+    InvalidValue(String),
+    IsDirectory(PathBuf),
+    OpenFile(PathBuf, io::Error),
+    FileInfo(file_info::Error),
+    NoParents,
+    MultipleParents,
+    RecursiveRequired,
+    Drive(google_drive3::Error),
+}
+
+impl error::Error for StepError {}
+
+impl Display for StepError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            StepError::MissingField(field) => write!(f, "'{}' is required", field),
+            StepError::UnknownLabel(label) => write!(
+                f,
+                "'${}' doesn't refer to the `as` of an earlier step",
+                label
+            ),
+            StepError::InvalidValue(err) => write!(f, "{}", err),
+            StepError::IsDirectory(path) => write!(
+                f,
+                "'{}' is a directory, use mkdir and upload its files",
+                path.display()
+            ),
+            StepError::OpenFile(path, err) => {
+                write!(f, "Failed to open file '{}': {}", path.display(), err)
+            }
+            StepError::FileInfo(err) => write!(f, "{}", err),
+            StepError::NoParents => write!(f, "File has no parent to move it from"),
+            StepError::MultipleParents => write!(f, "Files with multiple parents can't be moved"),
+            StepError::RecursiveRequired => write!(
+                f,
+                "File is a folder, set recursive to delete it with everything in it"
+            ),
+            StepError::Drive(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    ReadPlan(PathBuf, io::Error),
+    ParseJson(serde_json::Error),
+    ParseCsv(csv::Error),
+    EmptyPlan,
+    InvalidStep(usize, StepError),
+    StepFailed(usize),
+    StepsFailed(usize, usize),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::ReadPlan(path, err) => {
+                write!(f, "Failed to read plan '{}': {}", path.display(), err)
+            }
+            Error::ParseJson(err) => write!(f, "Failed to parse plan: {}", err),
+            Error::ParseCsv(err) => write!(f, "Failed to parse plan: {}", err),
+            Error::EmptyPlan => write!(f, "The plan has no steps"),
+            Error::InvalidStep(number, err) => {
+                write!(f, "Step {} is invalid, nothing was run: {}", number, err)
+            }
+            Error::StepFailed(number) => write!(
+                f,
+                "Stopped at step {}, use --continue-on-error to run the remaining steps anyway",
+                number
+            ),
+            Error::StepsFailed(failed, total) => {
+                write!(f, "{} of {} steps failed", failed, total)
+            }
+        }
+    }
+}
@@ -0,0 +1,322 @@
+// A facade for using gdrive from other Rust programs without running the
+// binary. It uses the same accounts and config.toml as the command line, and
+// nothing here prints to stdout.
+//
+//     let client = gdrive::client::Client::new().await?;
+//     let query = Query::folder("root").not_trashed().mime_contains("image/");
+//     let files = client.list(query, 100).await?;
+//
+// The functions behind the commands change with them, this module is what
+// stays compatible between releases
+use crate::common::batch_request;
+use crate::common::delegate::UploadDelegateConfig;
+use crate::common::drive_file;
+use crate::common::file_info;
+use crate::common::file_info::FileInfo;
+use crate::common::hub_helper;
+use crate::files;
+use crate::files::delete::Operation;
+use crate::files::list::ListFilesConfig;
+use crate::hub;
+use crate::hub::Hub;
+use crate::permissions;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+pub use crate::common::delegate::Chunk;
+pub use crate::common::delegate::ChunkError;
+pub use crate::common::delegate::UploadObserver;
+pub use crate::common::drive_query::Query;
+pub use crate::common::permission::Role;
+pub use crate::common::permission::Type;
+pub use crate::files::list::Corpora;
+pub use crate::files::list::ListQuery;
+pub use crate::files::list::ListScope;
+pub use crate::files::list::ListSortOrder;
+pub use crate::permissions::share::Config as ShareConfig;
+pub use google_drive3::api::File;
+pub use google_drive3::api::Permission;
+
+// Clones share the connections and the token, so a clone can be moved into
+// each task of parallel transfers
+#[derive(Clone)]
+pub struct Client {
+    hub: Arc<Hub>,
+}
+
+impl Client {
+    // Uses the current account, or the service account and refresh token
+    // environment variables like the command line does. Clients made with new
+    // in the same process share one hub
+    pub async fn new() -> Result<Client, Error> {
+        let hub = hub_helper::shared_hub().await.map_err(Error::Hub)?;
+        Ok(Client { hub })
+    }
+
+    pub async fn for_account(account_name: &str) -> Result<Client, Error> {
+        let hub = hub_helper::get_hub_for_account(account_name)
+            .await
+            .map_err(Error::Hub)?;
+        Ok(Client::from_hub(hub))
+    }
+
+    // I.e. a hub for a mock server in tests
+    pub fn from_hub(hub: Hub) -> Client {
+        Client { hub: Arc::new(hub) }
+    }
+
+    pub fn hub(&self) -> &Hub {
+        &self.hub
+    }
+
+    // Takes a Query or a ListQuery
+    pub async fn list(
+        &self,
+        query: impl Into<ListQuery>,
+        max_files: usize,
+    ) -> Result<Vec<File>, Error> {
+        self.list_in(query, ListScope::default(), max_files).await
+    }
+
+    // Like list, but searches the given corpora, i.e. ListScope::drive(id)
+    // for only the files of a shared drive
+    pub async fn list_in(
+        &self,
+        query: impl Into<ListQuery>,
+        scope: ListScope,
+        max_files: usize,
+    ) -> Result<Vec<File>, Error> {
+        files::list::list_files(
+            &self.hub,
+            &ListFilesConfig {
+                query: query.into(),
+                order_by: ListSortOrder::default(),
+                scope,
+                max_files,
+            },
+        )
+        .await
+        .map_err(Error::List)
+    }
+
+    // Lists the files in any of the folders that match the query. Many folders
+    // don't fit in one query, it is split into as many as needed
+    pub async fn list_in_folders(
+        &self,
+        query: Query,
+        folder_ids: &[String],
+        max_files: usize,
+    ) -> Result<Vec<File>, Error> {
+        let mut files = vec![];
+
+        for query in query.in_any_parents(folder_ids) {
+            if files.len() >= max_files {
+                break;
+            }
+            files.extend(self.list(query, max_files - files.len()).await?);
+        }
+
+        Ok(files)
+    }
+
+    pub async fn info(&self, file_id: &str) -> Result<File, Error> {
+        files::info::get_file_details(&self.hub, file_id, false)
+            .await
+            .map_err(Error::GetFile)
+    }
+
+    // Like info for many files, in batches of 100 instead of one request each.
+    // The results are in the order of the ids, a missing file doesn't fail the
+    // others
+    pub async fn info_many(&self, file_ids: &[String]) -> Result<Vec<Result<File, Error>>, Error> {
+        let requests: Vec<batch_request::Request> = file_ids
+            .iter()
+            .map(|file_id| batch_request::Request::get(&files::info::details_url(file_id)))
+            .collect();
+        let responses = batch_request::send(&self.hub, &requests)
+            .await
+            .map_err(Error::GetFile)?;
+
+        Ok(responses
+            .iter()
+            .map(|response| response.json().map_err(Error::GetFile))
+            .collect())
+    }
+
+    // Uploads a regular file, the mime type is guessed from the extension
+    pub async fn upload(
+        &self,
+        file_path: &Path,
+        parents: Option<Vec<String>>,
+    ) -> Result<File, Error> {
+        self.upload_observed(file_path, parents, None).await
+    }
+
+    // Like upload, the observer is told about each chunk and retry, i.e. for a
+    // progress bar
+    pub async fn upload_with_observer(
+        &self,
+        file_path: &Path,
+        parents: Option<Vec<String>>,
+        observer: Arc<dyn UploadObserver>,
+    ) -> Result<File, Error> {
+        self.upload_observed(file_path, parents, Some(observer))
+            .await
+    }
+
+    async fn upload_observed(
+        &self,
+        file_path: &Path,
+        parents: Option<Vec<String>>,
+        observer: Option<Arc<dyn UploadObserver>>,
+    ) -> Result<File, Error> {
+        let file = fs::File::open(file_path)
+            .map_err(|err| Error::OpenFile(file_path.to_path_buf(), err))?;
+
+        let file_info = FileInfo::from_file(
+            &file,
+            &file_info::Config {
+                file_path: file_path.to_path_buf(),
+                mime_type: None,
+                parents,
+            },
+        )
+        .map_err(Error::FileInfo)?;
+
+        let reader = std::io::BufReader::new(file);
+        files::upload::upload_file(
+            &self.hub,
+            reader,
+            None,
+            file_info,
+            UploadDelegateConfig {
+                observer,
+                ..UploadDelegateConfig::default()
+            },
+        )
+        .await
+        .map_err(Error::Upload)
+    }
+
+    // Downloads a file into a directory, or to the given path when it is not a
+    // directory. Existing files are not overwritten. Returns the path written
+    pub async fn download(&self, file_id: &str, destination: &Path) -> Result<PathBuf, Error> {
+        let file = self.info(file_id).await?;
+        let name = file.name.clone().unwrap_or_default();
+
+        if drive_file::is_directory(&file) {
+            return Err(Error::IsDirectory(name));
+        }
+
+        let file_path = if destination.is_dir() {
+            destination.join(&name)
+        } else {
+            destination.to_path_buf()
+        };
+
+        let body = files::download::download_file(&self.hub, file_id)
+            .await
+            .map_err(Error::Download)?;
+
+        files::download::save_body_to_file(body, &file_path, file.md5_checksum)
+            .await
+            .map_err(Error::Save)?;
+
+        Ok(file_path)
+    }
+
+    pub async fn trash(&self, file_id: &str) -> Result<(), Error> {
+        self.apply(file_id, Operation::Trash).await
+    }
+
+    pub async fn untrash(&self, file_id: &str) -> Result<(), Error> {
+        self.apply(file_id, Operation::Untrash).await
+    }
+
+    // Deletes the file permanently, the content of folders included
+    pub async fn delete(&self, file_id: &str) -> Result<(), Error> {
+        self.apply(file_id, Operation::DeletePermanent).await
+    }
+
+    pub async fn permissions(&self, file_id: &str) -> Result<Vec<Permission>, Error> {
+        permissions::list::list_permissions(&self.hub, UploadDelegateConfig::default(), file_id)
+            .await
+            .map_err(Error::Permissions)
+    }
+
+    pub async fn share(&self, config: &ShareConfig) -> Result<Permission, Error> {
+        permissions::share::create_permission(&self.hub, UploadDelegateConfig::default(), config)
+            .await
+            .map_err(Error::Permissions)
+    }
+
+    pub async fn revoke(&self, file_id: &str, permission_id: &str) -> Result<(), Error> {
+        permissions::revoke::delete_permission(
+            &self.hub,
+            UploadDelegateConfig::default(),
+            file_id,
+            permission_id,
+        )
+        .await
+        .map_err(Error::Permissions)
+    }
+
+    // Recorded in the deletion journal like the command line does, so
+    // `gdrive files undelete-last` can restore it
+    async fn apply(&self, file_id: &str, operation: Operation) -> Result<(), Error> {
+        let file = self.info(file_id).await?;
+        let name = file.name.unwrap_or_default();
+        let parents = file.parents.unwrap_or_default();
+
+        let journal_error =
+            files::delete::apply_and_record(&self.hub, file_id, &name, &parents, operation)
+                .await
+                .map_err(Error::Delete)?;
+        if let Some(err) = journal_error {
+            eprintln!("Warning: {}", err);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    List(files::list::Error),
+    GetFile(hub::RequestError),
+    OpenFile(PathBuf, std::io::Error),
+    FileInfo(file_info::Error),
+    Upload(google_drive3::Error),
+    IsDirectory(String),
+    Download(google_drive3::Error),
+    Save(files::download::Error),
+    Delete(google_drive3::Error),
+    Permissions(google_drive3::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::List(err) => write!(f, "{}", err),
+            Error::GetFile(err) => write!(f, "Failed to get file: {}", err),
+            Error::OpenFile(path, err) => {
+                write!(f, "Failed to open file '{}': {}", path.display(), err)
+            }
+            Error::FileInfo(err) => write!(f, "{}", err),
+            Error::Upload(err) => write!(f, "Failed to upload file: {}", err),
+            Error::IsDirectory(name) => write!(f, "'{}' is a directory", name),
+            Error::Download(err) => write!(f, "Failed to download file: {}", err),
+            Error::Save(err) => write!(f, "{}", err),
+            Error::Delete(err) => write!(f, "Failed to delete file: {}", err),
+            Error::Permissions(err) => write!(f, "Permissions request failed: {}", err),
+        }
+    }
+}
@@ -0,0 +1,414 @@
+use crate::common::api_stats::StatsDelegate;
+use crate::common::hub_helper;
+use crate::common::number_format;
+use crate::config::settings;
+use crate::hub;
+use crate::hub::Hub;
+use google_drive3::hyper;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::Deserialize;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+// Alerts for unattended runs of `gdrive backup` and `gdrive sync run`, sent to
+// every backend in the [alerts] table of config.toml when a run fails or the
+// storage quota is nearly full:
+//
+// [alerts]
+// quota-percent = 90
+// ntfy = "https://ntfy.sh/my-backups"
+// webhook = "https://example.com/hooks/gdrive"
+//
+// [alerts.smtp]
+// server = "smtp.example.com"
+// username = "me@example.com"
+// password = "app password"
+// from = "gdrive <me@example.com>"
+// to = ["me@example.com"]
+//
+// An alert that can't be sent is a warning, it doesn't change the result of the run
+const DEFAULT_QUOTA_PERCENT: u8 = 90;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct AlertsConfig {
+    // Usage of the storage quota that is alerted on, 0 turns the check off
+    pub quota_percent: Option<u8>,
+    // Url of an ntfy topic
+    pub ntfy: Option<String>,
+    // Url that gets the alert posted as json
+    pub webhook: Option<String>,
+    pub smtp: Option<SmtpConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct SmtpConfig {
+    pub server: String,
+    // Defaults to the port of the security, 587 for starttls and 465 for tls
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SmtpSecurity {
+    #[default]
+    Starttls,
+    Tls,
+    // Plain text, i.e. for a relay on localhost
+    None,
+}
+
+impl AlertsConfig {
+    fn has_backends(&self) -> bool {
+        self.ntfy.is_some() || self.webhook.is_some() || self.smtp.is_some()
+    }
+
+    fn quota_percent(&self) -> u8 {
+        self.quota_percent.unwrap_or(DEFAULT_QUOTA_PERCENT)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.quota_percent() > 100 {
+            return Err(String::from("alerts.quota-percent must be at most 100"));
+        }
+
+        for (key, url) in [
+            ("alerts.ntfy", &self.ntfy),
+            ("alerts.webhook", &self.webhook),
+        ] {
+            if let Some(url) = url {
+                if !url.starts_with("https://") && !url.starts_with("http://") {
+                    return Err(format!("{} must be an http or https url", key));
+                }
+            }
+        }
+
+        if let Some(smtp) = &self.smtp {
+            for address in std::iter::once(&smtp.from).chain(&smtp.to) {
+                address
+                    .parse::<lettre::message::Mailbox>()
+                    .map_err(|err| format!("Invalid email address '{}': {}", address, err))?;
+            }
+            if smtp.to.is_empty() {
+                return Err(String::from("alerts.smtp.to must have an address"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alert {
+    pub title: String,
+    pub message: String,
+}
+
+// Sends an alert when the run failed and checks the quota either way, a run
+// can fail because the quota is full
+pub async fn report<T, E: Display>(what: &str, result: Result<T, E>) -> Result<T, E> {
+    if let Err(err) = &result {
+        failure(what, err).await;
+    }
+    check_quota().await;
+    result
+}
+
+pub async fn failure(what: &str, err: &dyn Display) {
+    let config = configured();
+    if config.has_backends() {
+        let alert = Alert {
+            title: format!("{} failed", what),
+            message: err.to_string(),
+        };
+        send(&config, &alert).await;
+    }
+}
+
+pub async fn check_quota() {
+    let config = configured();
+    if !config.has_backends() || config.quota_percent() == 0 {
+        return;
+    }
+
+    let alert = match hub_helper::get_hub().await {
+        Ok(hub) => quota_alert(&hub, config.quota_percent()).await,
+        Err(err) => Err(err.to_string()),
+    };
+
+    match alert {
+        Ok(Some(alert)) => send(&config, &alert).await,
+        Ok(None) => {}
+        Err(err) => eprintln!("Warning: Failed to check the storage quota: {}", err),
+    }
+}
+
+// Unlimited accounts have no limit and are never full
+async fn quota_alert(hub: &Hub, percent: u8) -> Result<Option<Alert>, String> {
+    let (_, about) = hub
+        .about()
+        .get()
+        .param("fields", "storageQuota")
+        .add_scope(google_drive3::api::Scope::Full)
+        .delegate(&mut StatsDelegate)
+        .doit()
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let quota = about.storage_quota.unwrap_or_default();
+    let (usage, limit) = match (quota.usage, quota.limit) {
+        (Some(usage), Some(limit)) if limit > 0 => (usage.max(0) as u64, limit as u64),
+        _ => return Ok(None),
+    };
+
+    let used_percent = usage * 100 / limit;
+    if used_percent < percent as u64 {
+        return Ok(None);
+    }
+
+    let format = number_format::current();
+    Ok(Some(Alert {
+        title: format!("Drive storage is {}% full", used_percent),
+        message: format!(
+            "{} of {} used",
+            number_format::format_bytes(usage, format),
+            number_format::format_bytes(limit, format)
+        ),
+    }))
+}
+
+pub async fn send(config: &AlertsConfig, alert: &Alert) {
+    let mut results = Vec::new();
+    if let Some(url) = &config.ntfy {
+        results.push(send_ntfy(url, alert).await);
+    }
+    if let Some(url) = &config.webhook {
+        results.push(send_webhook(url, alert).await);
+    }
+    if let Some(smtp) = &config.smtp {
+        results.push(send_email(smtp, alert).await);
+    }
+
+    for err in results.into_iter().filter_map(Result::err) {
+        eprintln!("Warning: Failed to send alert: {}", err);
+    }
+}
+
+// The title is sent as a parameter, headers can't have the non-ascii
+// characters of file names
+async fn send_ntfy(url: &str, alert: &Alert) -> Result<(), Error> {
+    let separator = if url.contains('?') { '&' } else { '?' };
+    let topic_url = format!(
+        "{}{}title={}&priority=high&tags=warning",
+        url,
+        separator,
+        utf8_percent_encode(&alert.title, NON_ALPHANUMERIC)
+    );
+    let request = hyper::Request::post(&topic_url);
+    post(url, request, alert.message.clone()).await
+}
+
+async fn send_webhook(url: &str, alert: &Alert) -> Result<(), Error> {
+    let body = serde_json::json!({
+        "title": alert.title,
+        "message": alert.message,
+    });
+    let request = hyper::Request::post(url).header(hyper::header::CONTENT_TYPE, "application/json");
+    post(url, request, body.to_string()).await
+}
+
+async fn post(
+    url: &str,
+    request: hyper::http::request::Builder,
+    body: String,
+) -> Result<(), Error> {
+    let http_config = hub_helper::http_config();
+    let request = request
+        .body(hyper::Body::from(body))
+        .map_err(|err| Error::Build(url.to_string(), err))?;
+    let response = hub::http_client(&http_config)
+        .request(request)
+        .await
+        .map_err(|err| Error::Http(url.to_string(), err))?;
+
+    if !response.status().is_success() {
+        return Err(Error::Status(url.to_string(), response.status()));
+    }
+    Ok(())
+}
+
+async fn send_email(config: &SmtpConfig, alert: &Alert) -> Result<(), Error> {
+    let mut builder = Message::builder()
+        .from(parse_address(&config.from)?)
+        .subject(alert.title.clone());
+    for address in &config.to {
+        builder = builder.to(parse_address(address)?);
+    }
+    let message = builder
+        .body(alert.message.clone())
+        .map_err(Error::Message)?;
+
+    let mut transport = match config.security {
+        SmtpSecurity::Starttls => {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.server)
+                .map_err(Error::Smtp)?
+        }
+        SmtpSecurity::Tls => {
+            AsyncSmtpTransport::<Tokio1Executor>::relay(&config.server).map_err(Error::Smtp)?
+        }
+        SmtpSecurity::None => {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.server)
+        }
+    };
+    if let Some(port) = config.port {
+        transport = transport.port(port);
+    }
+    if let (Some(username), Some(password)) = (&config.username, &config.password) {
+        transport = transport.credentials(Credentials::new(username.clone(), password.clone()));
+    }
+
+    transport
+        .build()
+        .send(message)
+        .await
+        .map(|_| ())
+        .map_err(Error::Smtp)
+}
+
+fn parse_address(address: &str) -> Result<lettre::message::Mailbox, Error> {
+    address
+        .parse()
+        .map_err(|err| Error::Address(address.to_string(), err))
+}
+
+fn configured() -> AlertsConfig {
+    settings::current().alerts.clone().unwrap_or_default()
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Build(String, hyper::http::Error),
+    Http(String, hyper::Error),
+    Status(String, hyper::StatusCode),
+    Address(String, lettre::address::AddressError),
+    Message(lettre::error::Error),
+    Smtp(lettre::transport::smtp::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Build(url, err) => write!(f, "Invalid request for '{}': {}", url, err),
+            Error::Http(url, err) => write!(f, "Request to '{}' failed: {}", url, err),
+            Error::Status(url, status) => write!(f, "'{}' answered with {}", url, status),
+            Error::Address(address, err) => {
+                write!(f, "Invalid email address '{}': {}", address, err)
+            }
+            Error::Message(err) => write!(f, "Failed to build email: {}", err),
+            Error::Smtp(err) => write!(f, "Failed to send email: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockServer;
+
+    fn alert() -> Alert {
+        Alert {
+            title: "Backup of 'photos' failed".to_string(),
+            message: "Failed to upload file".to_string(),
+        }
+    }
+
+    #[test]
+    fn config_is_read_from_the_alerts_table() {
+        let config: AlertsConfig = toml::from_str(
+            r#"
+            quota-percent = 95
+            ntfy = "https://ntfy.sh/backups"
+
+            [smtp]
+            server = "smtp.example.com"
+            security = "tls"
+            from = "gdrive <me@example.com>"
+            to = ["me@example.com"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.quota_percent(), 95);
+        assert_eq!(config.smtp.as_ref().unwrap().security, SmtpSecurity::Tls);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_bad_urls_and_addresses() {
+        let config = AlertsConfig {
+            webhook: Some("example.com/hook".to_string()),
+            ..AlertsConfig::default()
+        };
+        assert_eq!(
+            config.validate().unwrap_err(),
+            "alerts.webhook must be an http or https url"
+        );
+
+        let config = AlertsConfig {
+            smtp: Some(SmtpConfig {
+                server: "smtp.example.com".to_string(),
+                port: None,
+                security: SmtpSecurity::default(),
+                username: None,
+                password: None,
+                from: "not an address".to_string(),
+                to: vec!["me@example.com".to_string()],
+            }),
+            ..AlertsConfig::default()
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn ntfy_and_webhook_get_the_alert() {
+        let server = MockServer::start().await;
+        server.respond("POST", "/backups", 200, "");
+        server.respond("POST", "/hook", 204, "");
+
+        let ntfy = format!("{}backups", server.url());
+        let webhook = format!("{}hook", server.url());
+        send_ntfy(&ntfy, &alert()).await.unwrap();
+        send_webhook(&webhook, &alert()).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(
+            requests[0].query_param("title").as_deref(),
+            Some("Backup of 'photos' failed")
+        );
+        assert_eq!(requests[0].body_text(), "Failed to upload file");
+        let body: serde_json::Value = serde_json::from_slice(&requests[1].body).unwrap();
+        assert_eq!(body["message"], "Failed to upload file");
+    }
+
+    #[tokio::test]
+    async fn failed_post_is_an_error() {
+        let server = MockServer::start().await;
+        let url = format!("{}missing", server.url());
+
+        let err = send_webhook(&url, &alert()).await.unwrap_err();
+
+        assert!(matches!(err, Error::Status(_, status) if status.as_u16() == 404));
+    }
+}
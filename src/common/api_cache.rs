@@ -0,0 +1,127 @@
+use crate::app_config::AppConfig;
+use crate::hub;
+use crate::hub::Hub;
+use google_drive3::hyper;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+const CACHE_DIR_NAME: &str = "api_cache";
+
+// The oldest responses are removed when there are more, a cache entry is one
+// page of a listing or the metadata of one file
+const MAX_ENTRIES: usize = 1000;
+
+// A response with the etag Drive sent for it
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    etag: String,
+    body: String,
+}
+
+// Fetches a metadata url. Responses that come with an etag are kept in the
+// account directory, and the next request for the same url sends it in
+// If-None-Match. Drive then answers with 304 and no body when nothing changed,
+// so repeated calls in a loop are fast. Responses without an etag are not cached.
+// The cache is best effort, failing to read or write it only means a full request
+pub async fn get_json<T: DeserializeOwned>(
+    hub: &Hub,
+    url: &str,
+    use_cache: bool,
+) -> Result<T, hub::RequestError> {
+    let path = if use_cache { entry_path(url) } else { None };
+    let cached = path.as_deref().and_then(load_entry);
+
+    let mut request = hyper::Request::get(url);
+    if let Some(entry) = &cached {
+        request = request.header(hyper::header::IF_NONE_MATCH, entry.etag.as_str());
+    }
+
+    let response = hub.send(request, hyper::Body::empty()).await?;
+    let status = response.status();
+    let etag = response
+        .headers()
+        .get(hyper::header::ETAG)
+        .and_then(|etag| etag.to_str().ok())
+        .map(String::from);
+
+    if let (hyper::StatusCode::NOT_MODIFIED, Some(entry)) = (status, &cached) {
+        return serde_json::from_str(&entry.body).map_err(hub::RequestError::Parse);
+    }
+
+    let body = hyper::body::to_bytes(response.into_body())
+        .await
+        .map_err(hub::RequestError::Http)?;
+    let body = String::from_utf8_lossy(&body).to_string();
+
+    if !status.is_success() {
+        return Err(hub::RequestError::Status(status, body));
+    }
+
+    let value = serde_json::from_str(&body).map_err(hub::RequestError::Parse)?;
+
+    if let (Some(path), Some(etag)) = (path, etag) {
+        save_entry(&path, &Entry { etag, body });
+    }
+
+    Ok(value)
+}
+
+// The cache is per account, the same url returns different files for each
+fn entry_path(url: &str) -> Option<PathBuf> {
+    let app_cfg = AppConfig::load_current_account().ok()?;
+    let key = md5::compute(url);
+    Some(
+        app_cfg
+            .account_base_path()
+            .join(CACHE_DIR_NAME)
+            .join(format!("{:x}.json", key)),
+    )
+}
+
+fn load_entry(path: &Path) -> Option<Entry> {
+    let content = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn save_entry(path: &Path, entry: &Entry) {
+    let dir = match path.parent() {
+        Some(dir) => dir,
+        None => return,
+    };
+
+    let saved = fs::create_dir_all(dir)
+        .ok()
+        .and_then(|_| serde_json::to_string(entry).ok())
+        .and_then(|content| fs::write(path, content).ok());
+
+    if saved.is_some() {
+        prune(dir);
+    }
+}
+
+fn prune(dir: &Path) {
+    let mut entries: Vec<(std::time::SystemTime, PathBuf)> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let modified = entry.metadata().ok()?.modified().ok()?;
+                Some((modified, entry.path()))
+            })
+            .collect(),
+        Err(_) => return,
+    };
+
+    if entries.len() <= MAX_ENTRIES {
+        return;
+    }
+
+    entries.sort();
+    let excess = entries.len() - MAX_ENTRIES;
+    for (_, path) in entries.into_iter().take(excess) {
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,183 @@
+use crate::app_config;
+use crate::app_config::AppConfig;
+use crate::common::quota;
+use crate::common::table;
+use crate::common::table::Table;
+use google_drive3::chrono;
+use google_drive3::hyper;
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+
+const USAGE_FILE_NAME: &str = "api_usage.json";
+const KEEP_DAYS: usize = 30;
+
+// Calls made by this process, by endpoint
+static CALLS: Mutex<BTreeMap<String, u64>> = Mutex::new(BTreeMap::new());
+static RETRIES: AtomicU64 = AtomicU64::new(0);
+static PRINT_SUMMARY: AtomicBool = AtomicBool::new(false);
+
+// Endpoint -> number of calls, per day
+type DailyUsage = BTreeMap<String, BTreeMap<String, u64>>;
+
+pub fn record(endpoint: &str) {
+    if let Ok(mut calls) = CALLS.lock() {
+        *calls.entry(endpoint.to_string()).or_default() += 1;
+    }
+}
+
+// A retried request is recorded as a call as well
+pub fn record_retry() {
+    RETRIES.fetch_add(1, Ordering::Relaxed);
+}
+
+// The calls made by this process so far, by endpoint
+pub fn calls() -> BTreeMap<String, u64> {
+    CALLS.lock().map(|calls| calls.clone()).unwrap_or_default()
+}
+
+pub fn retries() -> u64 {
+    RETRIES.load(Ordering::Relaxed)
+}
+
+pub fn enable_summary() {
+    PRINT_SUMMARY.store(true, Ordering::Relaxed);
+}
+
+// Called once before the process exits, also when the command failed since
+// that is when the numbers are most interesting
+pub fn finish() {
+    let calls = calls();
+    if calls.is_empty() {
+        return;
+    }
+
+    let today = match add_to_daily_usage(&calls) {
+        Ok(today) => Some(today),
+        Err(err) => {
+            eprintln!("Warning: Failed to update daily API usage: {}", err);
+            None
+        }
+    };
+
+    if PRINT_SUMMARY.load(Ordering::Relaxed) {
+        print_summary(&calls, today);
+    }
+}
+
+// Goes to stderr so the output of the command can still be piped
+fn print_summary(calls: &BTreeMap<String, u64>, today: Option<u64>) {
+    let total: u64 = calls.values().sum();
+    let values = calls
+        .iter()
+        .map(|(endpoint, count)| [endpoint.clone(), count.to_string()])
+        .collect();
+
+    eprintln!();
+    let _ = table::write(
+        io::stderr(),
+        Table {
+            header: ["Endpoint", "Calls"],
+            values,
+        },
+        &table::DisplayConfig::default(),
+    );
+
+    match today {
+        Some(today) => eprintln!("Total: {} API calls, {} today", total, today),
+        None => eprintln!("Total: {} API calls", total),
+    }
+}
+
+// Returns the number of calls made today, including this run
+fn add_to_daily_usage(calls: &BTreeMap<String, u64>) -> Result<u64, Error> {
+    let path = usage_path()?;
+    let mut usage = load_usage(&path)?;
+    let date = chrono::Local::now().format("%Y-%m-%d").to_string();
+
+    let day = usage.entry(date.clone()).or_default();
+    for (endpoint, count) in calls {
+        *day.entry(endpoint.clone()).or_default() += count;
+    }
+    let today = day.values().sum();
+
+    // Dates sort chronologically, so the oldest days come first
+    while usage.len() > KEEP_DAYS {
+        let oldest = usage.keys().next().cloned().unwrap_or_default();
+        usage.remove(&oldest);
+    }
+
+    let content = serde_json::to_string_pretty(&usage).map_err(Error::Serialize)?;
+    fs::write(&path, content).map_err(|err| Error::Write(path, err))?;
+
+    Ok(today)
+}
+
+fn load_usage(path: &PathBuf) -> Result<DailyUsage, Error> {
+    if !path.exists() {
+        return Ok(DailyUsage::new());
+    }
+
+    let content = fs::read_to_string(path).map_err(|err| Error::Read(path.clone(), err))?;
+    serde_json::from_str(&content).map_err(Error::Deserialize)
+}
+
+// Shared by all accounts, they usually use the same OAuth client and quota
+fn usage_path() -> Result<PathBuf, Error> {
+    let base_path = AppConfig::default_base_path().map_err(Error::AppConfig)?;
+    fs::create_dir_all(&base_path).map_err(|err| Error::Write(base_path.clone(), err))?;
+    Ok(base_path.join(USAGE_FILE_NAME))
+}
+
+// Counts the calls of requests that don't need any other delegate behavior
+pub struct StatsDelegate;
+
+impl google_drive3::client::Delegate for StatsDelegate {
+    fn begin(&mut self, info: google_drive3::client::MethodInfo) {
+        record(info.id)
+    }
+
+    fn http_failure(
+        &mut self,
+        _res: &hyper::http::response::Response<hyper::body::Body>,
+        err: Option<serde_json::Value>,
+    ) -> google_drive3::client::Retry {
+        quota::record_error(err.as_ref());
+        google_drive3::client::Retry::Abort
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    AppConfig(app_config::Error),
+    Serialize(serde_json::Error),
+    Deserialize(serde_json::Error),
+    Read(PathBuf, io::Error),
+    Write(PathBuf, io::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::AppConfig(err) => write!(f, "{}", err),
+            Error::Serialize(err) => write!(f, "Failed to serialize API usage: {}", err),
+            Error::Deserialize(err) => write!(f, "Failed to parse API usage: {}", err),
+            Error::Read(path, err) => {
+                write!(f, "Failed to read '{}': {}", path.display(), err)
+            }
+            Error::Write(path, err) => {
+                write!(f, "Failed to write '{}': {}", path.display(), err)
+            }
+        }
+    }
+}
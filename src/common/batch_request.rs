@@ -0,0 +1,351 @@
+use crate::common::delegate::Backoff;
+use crate::common::delegate::BackoffConfig;
+use crate::hub;
+use crate::hub::Hub;
+use google_drive3::hyper;
+use serde::de::DeserializeOwned;
+
+const BATCH_URL: &str = "https://www.googleapis.com/batch/drive/v3";
+const HOST_URL: &str = "https://www.googleapis.com";
+const BOUNDARY: &str = "gdrive_batch";
+
+// Drive takes at most 100 requests in one batch, longer lists are sent in
+// several
+pub const MAX_BATCH_SIZE: usize = 100;
+
+// One request of a batch, made from the same urls as single requests, i.e.
+// hub::metadata_url
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    method: hyper::Method,
+    // Relative to googleapis.com, i.e. /drive/v3/files/<id>?fields=name
+    path: String,
+    body: Option<String>,
+}
+
+impl Request {
+    pub fn get(url: &str) -> Request {
+        Request::new(hyper::Method::GET, url, None)
+    }
+
+    pub fn delete(url: &str) -> Request {
+        Request::new(hyper::Method::DELETE, url, None)
+    }
+
+    pub fn patch(url: &str, body: &serde_json::Value) -> Request {
+        Request::new(hyper::Method::PATCH, url, Some(body.to_string()))
+    }
+
+    fn new(method: hyper::Method, url: &str, body: Option<String>) -> Request {
+        Request {
+            method,
+            path: url.strip_prefix(HOST_URL).unwrap_or(url).to_string(),
+            body,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: hyper::StatusCode,
+    pub body: String,
+}
+
+impl Response {
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, hub::RequestError> {
+        self.check()?;
+        serde_json::from_str(&self.body).map_err(hub::RequestError::Parse)
+    }
+
+    // Rate limited or a server error, the request can be sent again
+    fn is_retryable(&self) -> bool {
+        self.status == hyper::StatusCode::TOO_MANY_REQUESTS || self.status.is_server_error()
+    }
+
+    // For requests that answer without a body, i.e. deletes
+    pub fn check(&self) -> Result<(), hub::RequestError> {
+        if self.status.is_success() {
+            Ok(())
+        } else {
+            Err(hub::RequestError::Status(self.status, self.body.clone()))
+        }
+    }
+}
+
+// Sends the requests to the batch endpoint of Drive, up to 100 in one round
+// trip instead of one each. Returns the responses in the order of the
+// requests. A failed request only fails its own response, the others are done
+// anyway. Each request still counts against the rate limit of Drive
+pub async fn send(hub: &Hub, requests: &[Request]) -> Result<Vec<Response>, hub::RequestError> {
+    let mut responses = Vec::with_capacity(requests.len());
+
+    for batch in requests.chunks(MAX_BATCH_SIZE) {
+        responses.extend(send_batch(hub, batch).await?);
+    }
+
+    Ok(responses)
+}
+
+// Like send, but requests that failed with 429 or a server error are sent
+// again in a new batch after the wait of the backoff. Whatever still fails
+// when the retries are used up is returned as it is
+pub async fn send_with_retries(
+    hub: &Hub,
+    requests: &[Request],
+    backoff_config: BackoffConfig,
+) -> Result<Vec<Response>, hub::RequestError> {
+    let mut responses = send(hub, requests).await?;
+    let mut backoff = Backoff::new(backoff_config);
+
+    loop {
+        let failed: Vec<usize> = (0..responses.len())
+            .filter(|index| responses[*index].is_retryable())
+            .collect();
+        if failed.is_empty() {
+            break;
+        }
+        let delay = match backoff.next_delay() {
+            Some(delay) => delay,
+            None => break,
+        };
+        tokio::time::sleep(delay).await;
+
+        let retried: Vec<Request> = failed
+            .iter()
+            .map(|index| requests[*index].clone())
+            .collect();
+        for (index, response) in failed.into_iter().zip(send(hub, &retried).await?) {
+            responses[index] = response;
+        }
+    }
+
+    Ok(responses)
+}
+
+async fn send_batch(hub: &Hub, requests: &[Request]) -> Result<Vec<Response>, hub::RequestError> {
+    let request = hyper::Request::post(BATCH_URL).header(
+        hyper::header::CONTENT_TYPE,
+        format!("multipart/mixed; boundary={}", BOUNDARY),
+    );
+    let response = hub
+        .send(request, hyper::Body::from(build_body(requests)))
+        .await?;
+
+    let status = response.status();
+    let boundary = response
+        .headers()
+        .get(hyper::header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .and_then(boundary_of);
+    let body = hyper::body::to_bytes(response.into_body())
+        .await
+        .map_err(hub::RequestError::Http)?;
+    let body = String::from_utf8_lossy(&body).to_string();
+
+    if !status.is_success() {
+        return Err(hub::RequestError::Status(status, body));
+    }
+
+    let boundary = boundary
+        .ok_or_else(|| hub::RequestError::Batch("missing multipart boundary".to_string()))?;
+    parse_body(&body, &boundary, requests.len())
+}
+
+// Each request is a part of a multipart/mixed body, the Content-ID is its
+// index so the responses can be matched with it
+fn build_body(requests: &[Request]) -> String {
+    let mut body = String::new();
+
+    for (index, request) in requests.iter().enumerate() {
+        body.push_str(&format!("--{}\r\n", BOUNDARY));
+        body.push_str("Content-Type: application/http\r\n");
+        body.push_str(&format!("Content-ID: <item{}>\r\n\r\n", index));
+        body.push_str(&format!("{} {} HTTP/1.1\r\n", request.method, request.path));
+
+        match &request.body {
+            Some(json) => body.push_str(&format!(
+                "Content-Type: application/json; charset=UTF-8\r\n\r\n{}\r\n",
+                json
+            )),
+            None => body.push_str("\r\n"),
+        }
+    }
+
+    body.push_str(&format!("--{}--\r\n", BOUNDARY));
+    body
+}
+
+// The parts of the answer have the Content-ID of their request with a
+// `response-` prefix, they can come in any order
+fn parse_body(
+    body: &str,
+    boundary: &str,
+    count: usize,
+) -> Result<Vec<Response>, hub::RequestError> {
+    let body = body.replace("\r\n", "\n");
+    let delimiter = format!("--{}", boundary);
+    let mut responses: Vec<Option<Response>> = vec![None; count];
+
+    for part in body.split(delimiter.as_str()).skip(1) {
+        if part.starts_with("--") {
+            break;
+        }
+
+        let (headers, message) = part
+            .trim_start_matches('\n')
+            .split_once("\n\n")
+            .ok_or_else(|| hub::RequestError::Batch("part without a response".to_string()))?;
+        let index = response_index(headers)
+            .filter(|index| *index < count)
+            .ok_or_else(|| {
+                hub::RequestError::Batch("part without a known Content-ID".to_string())
+            })?;
+        responses[index] = Some(parse_message(message)?);
+    }
+
+    responses
+        .into_iter()
+        .enumerate()
+        .map(|(index, response)| {
+            response.ok_or_else(|| {
+                hub::RequestError::Batch(format!("no response for request {}", index + 1))
+            })
+        })
+        .collect()
+}
+
+fn response_index(headers: &str) -> Option<usize> {
+    headers.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        if !name.trim().eq_ignore_ascii_case("content-id") {
+            return None;
+        }
+
+        value
+            .trim()
+            .trim_start_matches('<')
+            .trim_end_matches('>')
+            .strip_prefix("response-item")?
+            .parse()
+            .ok()
+    })
+}
+
+// A response like `HTTP/1.1 404 Not Found`, its headers and the body
+fn parse_message(message: &str) -> Result<Response, hub::RequestError> {
+    let (head, body) = message.split_once("\n\n").unwrap_or((message, ""));
+    let status = head
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse::<u16>().ok())
+        .and_then(|code| hyper::StatusCode::from_u16(code).ok())
+        .ok_or_else(|| hub::RequestError::Batch(format!("invalid status line in '{}'", head)))?;
+
+    Ok(Response {
+        status,
+        body: body.trim_end().to_string(),
+    })
+}
+
+fn boundary_of(content_type: &str) -> Option<String> {
+    content_type
+        .split(';')
+        .find_map(|param| param.trim().strip_prefix("boundary="))
+        .map(|boundary| boundary.trim_matches('"').to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockServer;
+    use std::time::Duration;
+
+    #[test]
+    fn requests_are_parts_of_the_body() {
+        let body = build_body(&[
+            Request::get(&hub::metadata_url("abc", "name")),
+            Request::patch(
+                "https://www.googleapis.com/drive/v3/files/def",
+                &serde_json::json!({"starred": true}),
+            ),
+        ]);
+
+        assert_eq!(
+            body,
+            "--gdrive_batch\r\n\
+             Content-Type: application/http\r\n\
+             Content-ID: <item0>\r\n\r\n\
+             GET /drive/v3/files/abc?fields=name&supportsAllDrives=true HTTP/1.1\r\n\r\n\
+             --gdrive_batch\r\n\
+             Content-Type: application/http\r\n\
+             Content-ID: <item1>\r\n\r\n\
+             PATCH /drive/v3/files/def HTTP/1.1\r\n\
+             Content-Type: application/json; charset=UTF-8\r\n\r\n\
+             {\"starred\":true}\r\n\
+             --gdrive_batch--\r\n"
+        );
+    }
+
+    #[test]
+    fn responses_are_matched_by_content_id() {
+        let body = "--batch_xyz\r\n\
+                    Content-Type: application/http\r\n\
+                    Content-ID: <response-item1>\r\n\r\n\
+                    HTTP/1.1 404 Not Found\r\n\
+                    Content-Type: application/json\r\n\r\n\
+                    {\"error\":{\"code\":404}}\r\n\
+                    --batch_xyz\r\n\
+                    Content-Type: application/http\r\n\
+                    Content-ID: <response-item0>\r\n\r\n\
+                    HTTP/1.1 204 No Content\r\n\r\n\r\n\
+                    --batch_xyz--\r\n";
+
+        let responses = parse_body(body, "batch_xyz", 2).unwrap();
+
+        assert_eq!(responses[0].status, hyper::StatusCode::NO_CONTENT);
+        assert!(responses[0].check().is_ok());
+        assert_eq!(responses[1].status, hyper::StatusCode::NOT_FOUND);
+        assert_eq!(responses[1].body, "{\"error\":{\"code\":404}}");
+        assert!(matches!(
+            parse_body(body, "batch_xyz", 3),
+            Err(hub::RequestError::Batch(_))
+        ));
+        assert_eq!(
+            boundary_of("multipart/mixed; boundary=batch_xyz").as_deref(),
+            Some("batch_xyz")
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_parts_are_sent_again() {
+        let server = MockServer::start().await;
+        server.respond_with_headers(
+            "POST",
+            "/batch/drive/v3",
+            200,
+            &[("content-type", "multipart/mixed; boundary=batch_xyz")],
+            "--batch_xyz\r\n\
+             Content-Type: application/http\r\n\
+             Content-ID: <response-item0>\r\n\r\n\
+             HTTP/1.1 503 Service Unavailable\r\n\r\n\r\n\
+             --batch_xyz--\r\n",
+        );
+        let backoff_config = BackoffConfig {
+            max_retries: 3,
+            min_sleep: Duration::from_millis(1),
+            max_sleep: Duration::from_millis(1),
+            max_elapsed: None,
+            jitter: 0.0,
+        };
+
+        let requests = [Request::delete(&hub::permission_url("abc", "123"))];
+        let responses = send_with_retries(&server.hub().await, &requests, backoff_config)
+            .await
+            .unwrap();
+
+        // Still failing when the retries are used up
+        assert_eq!(responses[0].status, hyper::StatusCode::SERVICE_UNAVAILABLE);
+        assert!(server.requests().len() > 1);
+    }
+}
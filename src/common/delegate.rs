@@ -1,16 +1,45 @@
+use crate::common::api_stats;
+use crate::common::quota;
+use google_drive3::chrono::Utc;
 use google_drive3::hyper;
 use google_drive3::hyper::http;
 use human_bytes::human_bytes;
 use std::fmt::Display;
+use std::future::Future;
 use std::str::FromStr;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
 use std::time::Duration;
+use std::time::Instant;
+
+// Files up to this size are always sent in a single multipart request,
+// a resumable session costs an extra round trip before any data is sent
+pub const MULTIPART_MAX_SIZE: u64 = 5 * 1024 * 1024;
+
+// A resumable upload is restarted when no chunk has been acknowledged for this
+// long, dead connections otherwise hang forever
+pub const DEFAULT_STALL_TIMEOUT: Duration = Duration::from_secs(300);
+pub const MAX_STALL_RETRIES: u32 = 10;
+
+// How often a paused upload checks whether it can go on
+const PAUSE_INTERVAL: Duration = Duration::from_millis(200);
 
 #[derive(Debug, Clone)]
 pub struct UploadDelegateConfig {
     pub chunk_size: ChunkSize,
     pub backoff_config: BackoffConfig,
-    pub print_chunk_errors: bool,
-    pub print_chunk_info: bool,
+    // Told about the chunks and retries of the upload, i.e. PrintObserver for
+    // --print-chunk-info and --print-chunk-errors
+    pub observer: Option<Arc<dyn UploadObserver>>,
+    pub stall_timeout: Option<Duration>,
+    // Set while a stalled upload is being restarted, for callers that show
+    // progress themselves. A warning is printed when this is None
+    pub stalled: Option<Arc<AtomicBool>>,
+    // A resumable upload waits before its next chunk while this is set. The
+    // wait blocks the thread, so only uploads on a thread of their own use it
+    pub paused: Option<Arc<AtomicBool>>,
 }
 
 impl Default for UploadDelegateConfig {
@@ -18,8 +47,115 @@ impl Default for UploadDelegateConfig {
         UploadDelegateConfig {
             chunk_size: ChunkSize::default(),
             backoff_config: BackoffConfig::default(),
-            print_chunk_errors: false,
-            print_chunk_info: false,
+            observer: None,
+            stall_timeout: Some(DEFAULT_STALL_TIMEOUT),
+            stalled: None,
+            paused: None,
+        }
+    }
+}
+
+impl UploadDelegateConfig {
+    // Files that fit in a single chunk gain nothing from a resumable upload
+    pub fn use_resumable_upload(&self, file_size: u64) -> bool {
+        file_size > MULTIPART_MAX_SIZE && file_size > self.chunk_size.in_bytes()
+    }
+}
+
+// A part of the file that is sent in one request of a resumable upload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chunk {
+    pub first: u64,
+    pub last: u64,
+    pub total: u64,
+}
+
+impl Chunk {
+    fn from_content_range(chunk: &google_drive3::client::ContentRange) -> Option<Chunk> {
+        chunk.range.as_ref().map(|range| Chunk {
+            first: range.first,
+            last: range.last,
+            total: chunk.total_length,
+        })
+    }
+
+    pub fn size(&self) -> u64 {
+        self.last - self.first + 1
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChunkError {
+    Http(String),
+    Status(http::StatusCode),
+}
+
+impl Display for ChunkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChunkError::Http(err) => write!(f, "{}", err),
+            ChunkError::Status(status) => write!(f, "Status code: {}", status),
+        }
+    }
+}
+
+// Events of an upload for progress bars, logs or library users. The methods
+// do nothing by default. They are called on the thread of the upload, so
+// they should return quickly
+pub trait UploadObserver: Send + Sync {
+    // `retry` is set when the chunk is sent again after it failed
+    fn chunk_started(&self, _chunk: &Chunk, _retry: bool) {}
+
+    fn chunk_completed(&self, _chunk: &Chunk) {}
+
+    // A request of the upload failed, it is retried when retry_scheduled follows
+    fn chunk_failed(&self, _error: &ChunkError) {}
+
+    fn retry_scheduled(&self, _attempt: u32, _delay: Duration) {}
+}
+
+impl std::fmt::Debug for dyn UploadObserver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "UploadObserver")
+    }
+}
+
+// Prints the chunks and the failed requests of uploads to the terminal
+#[derive(Debug)]
+pub struct PrintObserver {
+    pub chunks: bool,
+    pub errors: bool,
+}
+
+impl PrintObserver {
+    // None when there is nothing to print
+    pub fn new(chunks: bool, errors: bool) -> Option<Arc<dyn UploadObserver>> {
+        if chunks || errors {
+            Some(Arc::new(PrintObserver { chunks, errors }))
+        } else {
+            None
+        }
+    }
+}
+
+impl UploadObserver for PrintObserver {
+    fn chunk_started(&self, chunk: &Chunk, retry: bool) {
+        if self.chunks {
+            let action = if retry { "Retrying" } else { "Uploading" };
+            println!(
+                "Info: {} {} chunk ({}-{} of {})",
+                action,
+                human_bytes(chunk.size() as f64),
+                chunk.first,
+                chunk.last,
+                chunk.total
+            )
+        }
+    }
+
+    fn chunk_failed(&self, error: &ChunkError) {
+        if self.errors {
+            eprintln!("Warning: Failed attempt to upload chunk: {}", error);
         }
     }
 }
@@ -29,56 +165,105 @@ pub struct UploadDelegate {
     backoff: Backoff,
     resumable_upload_url: Option<String>,
     previous_chunk: Option<google_drive3::client::ContentRange>,
+    stall_watch: StallWatch,
+    // Retries are counted against the same endpoint as the call itself
+    method: Option<&'static str>,
 }
 
 impl UploadDelegate {
     pub fn new(config: UploadDelegateConfig) -> UploadDelegate {
         let backoff_config = config.backoff_config.clone();
+        let stall_watch = StallWatch::new(config.stall_timeout, config.stalled.clone());
 
         UploadDelegate {
             config,
             backoff: Backoff::new(backoff_config),
             resumable_upload_url: None,
             previous_chunk: None,
+            stall_watch,
+            method: None,
         }
     }
 
-    fn print_chunk_info(&self, chunk: &google_drive3::client::ContentRange) {
-        if self.config.print_chunk_info {
-            if let Some(range) = &chunk.range {
-                let chunk_size = range.last - range.first + 1;
+    // The watch sees progress as long as this delegate is used for the upload
+    pub fn stall_watch(&self) -> StallWatch {
+        self.stall_watch.clone()
+    }
 
-                let action = if Some(chunk) == self.previous_chunk.as_ref() {
-                    "Retrying"
-                } else {
-                    "Uploading"
-                };
+    fn retry(&mut self, retry_after: Option<Duration>) -> google_drive3::client::Retry {
+        let retry = self.backoff.retry(retry_after);
+        if let (google_drive3::client::Retry::After(_), Some(method)) = (&retry, self.method) {
+            api_stats::record(method);
+            api_stats::record_retry();
+        }
+        if let (google_drive3::client::Retry::After(delay), Some(observer)) =
+            (&retry, &self.config.observer)
+        {
+            observer.retry_scheduled(self.backoff.attempts, *delay);
+        }
+        retry
+    }
 
-                println!(
-                    "Info: {} {} chunk ({}-{} of {})",
-                    action,
-                    human_bytes(chunk_size as f64),
-                    range.first,
-                    range.last,
-                    chunk.total_length
-                )
-            }
+    fn chunk_failed(&self, error: ChunkError) {
+        if let Some(observer) = &self.config.observer {
+            observer.chunk_failed(&error);
+        }
+    }
+
+    fn previous_chunk_completed(&self) {
+        let previous = self
+            .previous_chunk
+            .as_ref()
+            .and_then(Chunk::from_content_range);
+        if let (Some(previous), Some(observer)) = (previous, &self.config.observer) {
+            observer.chunk_completed(&previous);
         }
     }
 }
 
 impl google_drive3::client::Delegate for UploadDelegate {
+    fn begin(&mut self, info: google_drive3::client::MethodInfo) {
+        api_stats::record(info.id);
+        self.method = Some(info.id);
+    }
+
     fn chunk_size(&mut self) -> u64 {
         self.config.chunk_size.in_bytes()
     }
 
+    // Called before each chunk is sent, so the previous one was acknowledged
     fn cancel_chunk_upload(&mut self, chunk: &google_drive3::client::ContentRange) -> bool {
-        self.print_chunk_info(chunk);
+        if let Some(paused) = &self.config.paused {
+            while paused.load(Ordering::SeqCst) {
+                std::thread::sleep(PAUSE_INTERVAL);
+            }
+        }
+
+        // Also after a pause, which is not a stall
+        self.stall_watch.progress();
+
+        let retry = self.previous_chunk.as_ref() == Some(chunk);
+        if !retry {
+            self.backoff.reset();
+            self.previous_chunk_completed();
+        }
+        if let (Some(current), Some(observer)) =
+            (Chunk::from_content_range(chunk), &self.config.observer)
+        {
+            observer.chunk_started(&current, retry);
+        }
         self.previous_chunk = Some(chunk.clone());
 
         false
     }
 
+    // The last chunk has no next chunk that would report it as completed
+    fn finished(&mut self, is_success: bool) {
+        if is_success {
+            self.previous_chunk_completed();
+        }
+    }
+
     fn store_upload_url(&mut self, url: Option<&str>) {
         self.resumable_upload_url = url.map(|s| s.to_string())
     }
@@ -88,80 +273,201 @@ impl google_drive3::client::Delegate for UploadDelegate {
     }
 
     fn http_error(&mut self, err: &hyper::Error) -> google_drive3::client::Retry {
-        if self.config.print_chunk_errors {
-            eprintln!("Warning: Failed attempt to upload chunk: {}", err);
-        }
-        self.backoff.retry()
+        self.chunk_failed(ChunkError::Http(err.to_string()));
+        self.retry(None)
     }
 
     fn http_failure(
         &mut self,
         res: &http::response::Response<hyper::body::Body>,
-        _err: Option<serde_json::Value>,
+        err: Option<serde_json::Value>,
     ) -> google_drive3::client::Retry {
         let status = res.status();
+        self.chunk_failed(ChunkError::Status(status));
 
-        if should_retry(status) {
-            if self.config.print_chunk_errors {
-                eprintln!(
-                    "Warning: Failed attempt to upload chunk. Status code: {}, body: {:?}",
-                    status,
-                    res.body()
-                );
-            }
-            self.backoff.retry()
+        // Retrying a used up daily or storage quota only uses up more of it
+        if quota::record_error(err.as_ref()).is_some() {
+            return self.backoff.abort();
+        }
+
+        let reason = err.as_ref().and_then(quota::error_reason);
+        if should_retry(status, reason) {
+            self.retry(retry_after(res))
         } else {
             self.backoff.abort()
         }
     }
 }
 
-fn should_retry(status: http::StatusCode) -> bool {
-    status.is_server_error() || status == http::StatusCode::TOO_MANY_REQUESTS
+#[derive(Debug, Clone)]
+pub struct StallWatch {
+    timeout: Option<Duration>,
+    last_progress: Arc<Mutex<Instant>>,
+    stalled: Option<Arc<AtomicBool>>,
 }
 
+impl StallWatch {
+    fn new(timeout: Option<Duration>, stalled: Option<Arc<AtomicBool>>) -> StallWatch {
+        StallWatch {
+            timeout,
+            last_progress: Arc::new(Mutex::new(Instant::now())),
+            stalled,
+        }
+    }
+
+    fn progress(&self) {
+        if let Ok(mut last_progress) = self.last_progress.lock() {
+            *last_progress = Instant::now();
+        }
+        if let Some(stalled) = &self.stalled {
+            stalled.store(false, Ordering::SeqCst);
+        }
+    }
+
+    fn last_progress(&self) -> Instant {
+        self.last_progress
+            .lock()
+            .map(|last_progress| *last_progress)
+            .unwrap_or_else(|err| *err.into_inner())
+    }
+
+    // Runs the upload to completion, or returns None when it has made no
+    // progress for the stall timeout
+    pub async fn watch<F: Future>(&self, upload: F) -> Option<F::Output> {
+        let timeout = match self.timeout {
+            Some(timeout) => timeout,
+            None => return Some(upload.await),
+        };
+
+        self.progress();
+        tokio::pin!(upload);
+
+        loop {
+            let deadline = self.last_progress() + timeout;
+
+            tokio::select! {
+                output = &mut upload => return Some(output),
+                _ = tokio::time::sleep_until(deadline.into()) => {
+                    if self.last_progress().elapsed() >= timeout {
+                        break;
+                    }
+                }
+            }
+        }
+
+        match &self.stalled {
+            Some(stalled) => stalled.store(true, Ordering::SeqCst),
+            None => eprintln!(
+                "Warning: No progress for {} seconds, retrying upload",
+                timeout.as_secs()
+            ),
+        }
+
+        None
+    }
+}
+
+fn should_retry(status: http::StatusCode, reason: Option<&str>) -> bool {
+    status.is_server_error()
+        || status == http::StatusCode::TOO_MANY_REQUESTS
+        || (status == http::StatusCode::FORBIDDEN && reason.map_or(false, quota::is_rate_limit))
+}
+
+fn retry_after(res: &http::response::Response<hyper::body::Body>) -> Option<Duration> {
+    res.headers()
+        .get(http::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| quota::parse_retry_after(value, Utc::now()))
+}
+
+pub const DEFAULT_MAX_RETRIES: u32 = 100;
+pub const DEFAULT_MAX_RETRY_TIME: Duration = Duration::from_secs(30 * 60);
+pub const DEFAULT_RETRY_JITTER: f32 = 0.3;
+
+// Set in config.toml, see Settings::backoff
 #[derive(Debug, Clone)]
 pub struct BackoffConfig {
     pub max_retries: u32,
     pub min_sleep: Duration,
     pub max_sleep: Duration,
+    // Give up when the retries of a request have taken this long
+    pub max_elapsed: Option<Duration>,
+    // Fraction of each wait that is random, so parallel transfers that failed
+    // together don't retry together
+    pub jitter: f32,
 }
 
 impl Default for BackoffConfig {
     fn default() -> Self {
         BackoffConfig {
-            max_retries: 100,
+            max_retries: DEFAULT_MAX_RETRIES,
             min_sleep: Duration::from_secs(1),
             max_sleep: Duration::from_secs(60),
+            max_elapsed: Some(DEFAULT_MAX_RETRY_TIME),
+            jitter: DEFAULT_RETRY_JITTER,
         }
     }
 }
 
 pub struct Backoff {
     attempts: u32,
+    first_retry: Option<Instant>,
+    max_elapsed: Option<Duration>,
     backoff: exponential_backoff::Backoff,
 }
 
 impl Backoff {
     pub fn new(config: BackoffConfig) -> Backoff {
+        let mut backoff = exponential_backoff::Backoff::new(
+            config.max_retries,
+            config.min_sleep,
+            config.max_sleep,
+        );
+        backoff.set_jitter(config.jitter);
+
         Backoff {
             attempts: 0,
-            backoff: exponential_backoff::Backoff::new(
-                config.max_retries,
-                config.min_sleep,
-                config.max_sleep,
-            ),
+            first_retry: None,
+            max_elapsed: config.max_elapsed,
+            backoff,
         }
     }
 
-    fn retry(&mut self) -> google_drive3::client::Retry {
+    // The retries start over after an upload made progress, so a long upload
+    // doesn't use them up over hours of occasional errors
+    fn reset(&mut self) {
+        self.attempts = 0;
+        self.first_retry = None;
+    }
+
+    // The wait Drive asked for in Retry-After replaces the exponential one,
+    // the number of retries is limited either way
+    fn retry(&mut self, retry_after: Option<Duration>) -> google_drive3::client::Retry {
+        let first_retry = *self.first_retry.get_or_insert_with(Instant::now);
+        if self
+            .max_elapsed
+            .is_some_and(|max_elapsed| first_retry.elapsed() >= max_elapsed)
+        {
+            return google_drive3::client::Retry::Abort;
+        }
+
         self.attempts += 1;
         self.backoff
             .next(self.attempts)
+            .map(|delay| retry_after.unwrap_or(delay))
             .map(google_drive3::client::Retry::After)
             .unwrap_or(google_drive3::client::Retry::Abort)
     }
 
+    // For retries outside a delegate, i.e. of the failed parts of a batch
+    // request. None once the retries are used up
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        match self.retry(None) {
+            google_drive3::client::Retry::After(delay) => Some(delay),
+            google_drive3::client::Retry::Abort => None,
+        }
+    }
+
     fn abort(&mut self) -> google_drive3::client::Retry {
         google_drive3::client::Retry::Abort
     }
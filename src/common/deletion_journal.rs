@@ -0,0 +1,148 @@
+use crate::app_config;
+use crate::app_config::AppConfig;
+use crate::files::delete::Operation;
+use google_drive3::chrono;
+use serde::Deserialize;
+use serde::Serialize;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+const JOURNAL_FILE_NAME: &str = "deletions.jsonl";
+// Past this the oldest half of the entries is dropped, a few thousand are kept
+const MAX_JOURNAL_BYTES: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub file_id: String,
+    pub name: String,
+    pub parents: Vec<String>,
+    pub operation: Operation,
+    pub time: String,
+}
+
+impl Entry {
+    pub fn new(file_id: &str, name: &str, parents: &[String], operation: Operation) -> Entry {
+        Entry {
+            file_id: file_id.to_string(),
+            name: name.to_string(),
+            parents: parents.to_vec(),
+            operation,
+            time: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+// Appends to a per-account JSON lines file, one entry per trash/untrash/delete
+pub fn record(entry: &Entry) -> Result<(), Error> {
+    let path = journal_path()?;
+    let line = serde_json::to_string(entry).map_err(Error::Serialize)?;
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|err| Error::Write(path.clone(), err))?;
+    writeln!(file, "{}", line).map_err(|err| Error::Write(path.clone(), err))?;
+    trim(&path, MAX_JOURNAL_BYTES).map_err(|err| Error::Write(path, err))
+}
+
+fn trim(path: &Path, max_bytes: u64) -> Result<(), io::Error> {
+    if fs::metadata(path)?.len() <= max_bytes {
+        return Ok(());
+    }
+
+    let content = fs::read_to_string(path)?;
+    let lines: Vec<&str> = content.lines().collect();
+    let mut kept = lines[lines.len() / 2..].join("\n");
+    kept.push('\n');
+    fs::write(path, kept)
+}
+
+pub fn entries() -> Result<Vec<Entry>, Error> {
+    let path = journal_path()?;
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+
+    let content = fs::read_to_string(&path).map_err(|err| Error::Read(path.clone(), err))?;
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(Error::Deserialize))
+        .collect()
+}
+
+// The most recent trashed file that hasn't been restored since
+pub fn last_trashed() -> Result<Option<Entry>, Error> {
+    let mut restored: Vec<String> = vec![];
+
+    for entry in entries()?.into_iter().rev() {
+        match entry.operation {
+            Operation::Untrash | Operation::DeletePermanent => {
+                restored.push(entry.file_id);
+            }
+            Operation::Trash => {
+                if !restored.contains(&entry.file_id) {
+                    return Ok(Some(entry));
+                }
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+fn journal_path() -> Result<PathBuf, Error> {
+    let app_cfg = AppConfig::load_current_account().map_err(Error::AppConfig)?;
+    Ok(app_cfg.account_base_path().join(JOURNAL_FILE_NAME))
+}
+
+#[derive(Debug)]
+pub enum Error {
+    AppConfig(app_config::Error),
+    Serialize(serde_json::Error),
+    Deserialize(serde_json::Error),
+    Read(PathBuf, io::Error),
+    Write(PathBuf, io::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::AppConfig(err) => write!(f, "{}", err),
+            Error::Serialize(err) => write!(f, "Failed to serialize journal entry: {}", err),
+            Error::Deserialize(err) => write!(f, "Failed to parse deletion journal: {}", err),
+            Error::Read(path, err) => {
+                write!(f, "Failed to read journal '{}': {}", path.display(), err)
+            }
+            Error::Write(path, err) => {
+                write!(f, "Failed to write journal '{}': {}", path.display(), err)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oldest_entries_are_dropped_when_the_journal_is_too_big() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(JOURNAL_FILE_NAME);
+        fs::write(&path, "1\n2\n3\n4\n").unwrap();
+
+        trim(&path, 8).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "1\n2\n3\n4\n");
+
+        trim(&path, 7).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "3\n4\n");
+    }
+}
@@ -1,6 +1,7 @@
 use mime::Mime;
 use std::fmt;
 use std::path::PathBuf;
+use std::str::FromStr;
 
 pub const MIME_TYPE_DRIVE_FOLDER: &str = "application/vnd.google-apps.folder";
 pub const MIME_TYPE_DRIVE_DOCUMENT: &str = "application/vnd.google-apps.document";
@@ -223,10 +224,23 @@ impl fmt::Display for FileExtension {
     }
 }
 
+impl FromStr for FileExtension {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let extension = s.trim_start_matches('.').to_lowercase();
+        FileExtension::from_extension(&extension)
+            .ok_or_else(|| format!("'{}' is not a supported file type", s))
+    }
+}
+
 impl FileExtension {
     pub fn from_path(path: &PathBuf) -> Option<FileExtension> {
         let extension = path.extension()?.to_str()?;
+        FileExtension::from_extension(extension)
+    }
 
+    pub fn from_extension(extension: &str) -> Option<FileExtension> {
         match extension {
             EXTENSION_DOC => Some(FileExtension::Doc),
             EXTENSION_DOCX => Some(FileExtension::Docx),
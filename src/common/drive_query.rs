@@ -0,0 +1,280 @@
+use crate::common::drive_file;
+use google_drive3::chrono::DateTime;
+use google_drive3::chrono::SecondsFormat;
+use google_drive3::chrono::Utc;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+// Drive answers with an error when the url of a request gets too long. The
+// limit isn't documented, this leaves room for the other parameters and for
+// the percent encoding of the query
+pub const MAX_QUERY_LENGTH: usize = 4000;
+
+// Builds the `q` parameter of files.list, i.e.
+// Query::folder(id).not_trashed().mime_contains("image/"). Values are quoted
+// and escaped, so names with quotes can't change the query. The conditions
+// are joined with `and`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Query {
+    conditions: Vec<String>,
+}
+
+impl Query {
+    pub fn new() -> Query {
+        Query::default()
+    }
+
+    // Files with the folder as one of their parents, `root` is My Drive
+    pub fn folder(folder_id: &str) -> Query {
+        Query::new().in_parents(folder_id)
+    }
+
+    pub fn in_parents(self, folder_id: &str) -> Query {
+        self.with(format!("{} in parents", quote(folder_id)))
+    }
+
+    pub fn not_trashed(self) -> Query {
+        self.with("trashed = false".to_string())
+    }
+
+    pub fn trashed(self) -> Query {
+        self.with("trashed = true".to_string())
+    }
+
+    pub fn name_is(self, name: &str) -> Query {
+        self.with(format!("name = {}", quote(name)))
+    }
+
+    // Drive matches the prefix of the words in the name, not any substring
+    pub fn name_contains(self, pattern: &str) -> Query {
+        self.with(format!("name contains {}", quote(pattern)))
+    }
+
+    // Matches the name, description and content of the files
+    pub fn full_text_contains(self, text: &str) -> Query {
+        self.with(format!("fullText contains {}", quote(text)))
+    }
+
+    pub fn mime_type(self, mime_type: &str) -> Query {
+        self.with(format!("mimeType = {}", quote(mime_type)))
+    }
+
+    pub fn not_mime_type(self, mime_type: &str) -> Query {
+        self.with(format!("mimeType != {}", quote(mime_type)))
+    }
+
+    pub fn mime_contains(self, pattern: &str) -> Query {
+        self.with(format!("mimeType contains {}", quote(pattern)))
+    }
+
+    pub fn folders_only(self) -> Query {
+        self.mime_type(drive_file::MIME_TYPE_DRIVE_FOLDER)
+    }
+
+    pub fn no_folders(self) -> Query {
+        self.not_mime_type(drive_file::MIME_TYPE_DRIVE_FOLDER)
+    }
+
+    // `me` or an email address
+    pub fn owned_by(self, owner: &str) -> Query {
+        self.with(format!("{} in owners", quote(owner)))
+    }
+
+    pub fn modified_after(self, time: DateTime<Utc>) -> Query {
+        self.with(format!("modifiedTime > {}", quote(&format_time(time))))
+    }
+
+    pub fn modified_before(self, time: DateTime<Utc>) -> Query {
+        self.with(format!("modifiedTime < {}", quote(&format_time(time))))
+    }
+
+    pub fn starred(self) -> Query {
+        self.with("starred = true".to_string())
+    }
+
+    pub fn shared_with_me(self) -> Query {
+        self.with("sharedWithMe = true".to_string())
+    }
+
+    // Files in any of the folders, i.e. to list the children of many folders
+    // with few requests. Thousands of folders don't fit in one query, so they
+    // are split over as many queries as needed, each with the conditions of
+    // self. Together the queries match the files of all the folders
+    pub fn in_any_parents(self, folder_ids: &[String]) -> Vec<Query> {
+        let conditions = folder_ids
+            .iter()
+            .map(|folder_id| format!("{} in parents", quote(folder_id)))
+            .collect();
+        self.any_of(conditions, MAX_QUERY_LENGTH)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.conditions.is_empty()
+    }
+
+    // A condition that is too long on its own still gets a query, Drive
+    // reports the error then
+    fn any_of(self, conditions: Vec<String>, max_length: usize) -> Vec<Query> {
+        // The group is joined to the other conditions with ` and (...)`
+        let base_length = self.to_string().len() + " and ()".len();
+        let mut groups: Vec<Vec<String>> = vec![];
+        let mut length = 0;
+
+        for condition in conditions {
+            let added = " or ".len() + condition.len();
+            match groups.last_mut() {
+                Some(group) if base_length + length + added <= max_length => {
+                    length += added;
+                    group.push(condition);
+                }
+                _ => {
+                    length = condition.len();
+                    groups.push(vec![condition]);
+                }
+            }
+        }
+
+        groups
+            .into_iter()
+            .map(|group| self.clone().with(format!("({})", group.join(" or "))))
+            .collect()
+    }
+
+    fn with(mut self, condition: String) -> Query {
+        self.conditions.push(condition);
+        self
+    }
+}
+
+impl Display for Query {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.conditions.join(" and "))
+    }
+}
+
+// Escapes a value for a single quoted string in a query
+pub fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+fn quote(value: &str) -> String {
+    format!("'{}'", escape(value))
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use google_drive3::chrono::TimeZone;
+
+    // Reads the quoted value at the start of the input like Drive does and
+    // returns it with the rest of the query
+    fn unquote(input: &str) -> Option<(String, &str)> {
+        let mut chars = input.strip_prefix('\'')?.char_indices();
+        let mut value = String::new();
+
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => value.push(chars.next()?.1),
+                '\'' => return Some((value, &input[i + 2..])),
+                _ => value.push(c),
+            }
+        }
+
+        None
+    }
+
+    #[test]
+    fn escape_quotes_and_backslashes() {
+        assert_eq!(escape("plain name.txt"), "plain name.txt");
+        assert_eq!(escape("it's"), "it\\'s");
+        assert_eq!(escape("C:\\temp"), "C:\\\\temp");
+        assert_eq!(escape("\\'"), "\\\\\\'");
+    }
+
+    #[test]
+    fn hostile_names_stay_one_value() {
+        let names = [
+            "it's",
+            "'",
+            "\\",
+            "\\'",
+            "ends with \\",
+            "x' or trashed = true or name = 'y",
+            "x\\' and '1' = '1",
+            "日本語 'quoted'",
+        ];
+
+        for name in names {
+            let query = Query::new().name_is(name).not_trashed().to_string();
+            let value = query.strip_prefix("name = ").unwrap();
+            let (unquoted, rest) = unquote(value).unwrap();
+            assert_eq!(unquoted, name);
+            assert_eq!(rest, " and trashed = false");
+        }
+    }
+
+    #[test]
+    fn conditions_are_joined_in_order() {
+        let query = Query::folder("abc").not_trashed().mime_contains("image/");
+        assert_eq!(
+            query.to_string(),
+            "'abc' in parents and trashed = false and mimeType contains 'image/'"
+        );
+    }
+
+    #[test]
+    fn many_folders_are_split_over_queries() {
+        let folder_ids: Vec<String> = (0..1000).map(|i| format!("folder{:04}", i)).collect();
+
+        let queries = Query::new().not_trashed().in_any_parents(&folder_ids);
+
+        assert!(queries.len() > 1);
+        for query in &queries {
+            let query = query.to_string();
+            assert!(query.len() <= MAX_QUERY_LENGTH);
+            assert!(query.starts_with("trashed = false and ('folder"));
+        }
+        let folders: usize = queries
+            .iter()
+            .map(|query| query.to_string().matches(" in parents").count())
+            .sum();
+        assert_eq!(folders, 1000);
+
+        assert_eq!(
+            Query::new().in_any_parents(&["a".to_string(), "b".to_string()])[0].to_string(),
+            "('a' in parents or 'b' in parents)"
+        );
+    }
+
+    #[test]
+    fn empty_query() {
+        assert!(Query::new().is_empty());
+        assert_eq!(Query::new().to_string(), "");
+    }
+
+    #[test]
+    fn folders_and_owners() {
+        assert_eq!(
+            Query::new().folders_only().owned_by("me").to_string(),
+            "mimeType = 'application/vnd.google-apps.folder' and 'me' in owners"
+        );
+        assert_eq!(
+            Query::new().no_folders().to_string(),
+            "mimeType != 'application/vnd.google-apps.folder'"
+        );
+    }
+
+    #[test]
+    fn modified_time_is_rfc3339() {
+        let time = Utc.with_ymd_and_hms(2023, 1, 2, 3, 4, 5).unwrap();
+        assert_eq!(
+            Query::new().modified_after(time).to_string(),
+            "modifiedTime > '2023-01-02T03:04:05Z'"
+        );
+    }
+}
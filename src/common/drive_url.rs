@@ -0,0 +1,80 @@
+// Path segments that are followed by the file id, i.e. /file/d/<ID> and /drive/folders/<ID>
+const ID_PATH_MARKERS: [&str; 2] = ["d", "folders"];
+
+// Extracts the file id from a Drive or Docs url, i.e.
+//
+// https://drive.google.com/file/d/<ID>/view
+// https://drive.google.com/open?id=<ID>
+// https://drive.google.com/drive/u/0/folders/<ID>
+// https://docs.google.com/spreadsheets/d/<ID>/edit#gid=0
+//
+// Anything that isn't a url is returned as is if it looks like an id
+pub fn extract_id(input: &str) -> Option<String> {
+    let input = input.trim();
+
+    let rest = match input
+        .strip_prefix("https://")
+        .or_else(|| input.strip_prefix("http://"))
+    {
+        Some(rest) => rest,
+        None => return Some(input.to_string()).filter(|id| is_id(id)),
+    };
+
+    let without_fragment = rest.split('#').next().unwrap_or(rest);
+    let (path, query) = without_fragment
+        .split_once('?')
+        .unwrap_or((without_fragment, ""));
+
+    let query_id = query
+        .split('&')
+        .find_map(|pair| match pair.split_once('=') {
+            Some(("id", value)) => Some(value.to_string()),
+            _ => None,
+        });
+
+    let segments: Vec<&str> = path.split('/').skip(1).collect();
+    let path_id = segments
+        .windows(2)
+        .find(|pair| ID_PATH_MARKERS.contains(&pair[0]))
+        .map(|pair| pair[1].to_string());
+
+    path_id.or(query_id).filter(|id| is_id(id))
+}
+
+fn is_id(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+// Value parser for command line arguments that take a file or folder id
+pub fn parse_id(input: &str) -> Result<String, String> {
+    extract_id(input).ok_or_else(|| format!("'{}' is not a Drive id or url", input))
+}
+
+// Link that downloads the file instead of opening it in the Drive viewer
+pub fn download_url(file_id: &str) -> String {
+    format!("https://drive.google.com/uc?export=download&id={}", file_id)
+}
+
+// Large files can't be virus scanned, the plain download link shows a warning
+// page for them instead. The confirmation from that page skips it
+pub fn confirmed_download_url(file_id: &str, confirm: &str) -> String {
+    format!(
+        "https://drive.usercontent.google.com/download?id={}&export=download&confirm={}",
+        file_id, confirm
+    )
+}
+
+// Reads the confirmation from the form on the warning page
+pub fn confirm_from_warning_page(html: &str) -> Option<String> {
+    let marker = "name=\"confirm\" value=\"";
+    let start = html.find(marker)? + marker.len();
+    let end = html[start..].find('"')?;
+    Some(html[start..start + end].to_string()).filter(|value| !value.is_empty())
+}
+
+// Link that opens the file in the Drive viewer
+pub fn view_url(file_id: &str) -> String {
+    format!("https://drive.google.com/file/d/{}/view", file_id)
+}
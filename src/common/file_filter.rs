@@ -0,0 +1,129 @@
+use crate::common::drive_file;
+use serde::Deserialize;
+use serde::Serialize;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+const UNITS: [(&str, u64); 4] = [
+    ("T", 1024 * 1024 * 1024 * 1024),
+    ("G", 1024 * 1024 * 1024),
+    ("M", 1024 * 1024),
+    ("K", 1024),
+];
+
+// Excludes files by size and mime type. Folders always match so that filtering
+// never hides the files inside them
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct FileFilter {
+    pub min_size: Option<ByteSize>,
+    pub max_size: Option<ByteSize>,
+    pub mime_include: Vec<String>,
+    pub mime_exclude: Vec<String>,
+}
+
+impl FileFilter {
+    pub fn is_empty(&self) -> bool {
+        self == &FileFilter::default()
+    }
+
+    pub fn matches(&self, size: u64, mime_type: &str) -> bool {
+        let too_small = self.min_size.map(|min| size < min.0).unwrap_or(false);
+        let too_large = self.max_size.map(|max| size > max.0).unwrap_or(false);
+        let included = self.mime_include.is_empty()
+            || self
+                .mime_include
+                .iter()
+                .any(|pattern| mime_matches(pattern, mime_type));
+        let excluded = self
+            .mime_exclude
+            .iter()
+            .any(|pattern| mime_matches(pattern, mime_type));
+
+        !too_small && !too_large && included && !excluded
+    }
+
+    pub fn matches_drive_file(&self, file: &google_drive3::api::File) -> bool {
+        if drive_file::is_directory(file) {
+            return true;
+        }
+
+        let size = file.size.unwrap_or(0).max(0) as u64;
+        self.matches(size, file.mime_type.as_deref().unwrap_or_default())
+    }
+}
+
+// Patterns are either a full mime type or a type with a wildcard subtype, i.e. `video/*`
+fn mime_matches(pattern: &str, mime_type: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some(type_) => mime_type
+            .split('/')
+            .next()
+            .map(|t| t.eq_ignore_ascii_case(type_))
+            .unwrap_or(false),
+        None => pattern.eq_ignore_ascii_case(mime_type),
+    }
+}
+
+// Size in bytes, parsed from values like `512`, `100K`, `1.5G` or `2GB`
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ByteSize(pub u64);
+
+impl FromStr for ByteSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let upper = s.trim().to_uppercase();
+        let value = upper
+            .strip_suffix("IB")
+            .or_else(|| upper.strip_suffix('B'))
+            .unwrap_or(&upper);
+
+        let (number, multiplier) = UNITS
+            .iter()
+            .find_map(|(suffix, multiplier)| {
+                value
+                    .strip_suffix(suffix)
+                    .map(|number| (number, *multiplier))
+            })
+            .unwrap_or((value, 1));
+
+        match number.trim().parse::<f64>() {
+            Ok(number) if number >= 0.0 => Ok(ByteSize((number * multiplier as f64) as u64)),
+            _ => Err(format!(
+                "'{}' is not a valid size, use a number of bytes or a number followed by K, M, G or T",
+                s
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for ByteSize {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<ByteSize> for String {
+    fn from(size: ByteSize) -> Self {
+        size.to_string()
+    }
+}
+
+impl Display for ByteSize {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let unit = UNITS
+            .iter()
+            .find(|(_, multiplier)| self.0 >= *multiplier && self.0 % multiplier == 0);
+
+        match unit {
+            Some((suffix, multiplier)) => write!(f, "{}{}", self.0 / multiplier, suffix),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
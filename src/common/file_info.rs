@@ -2,8 +2,19 @@ use std::error;
 use std::fmt::Display;
 use std::fmt::Formatter;
 use std::fs;
+use std::io::Read;
+use std::path::Path;
 use std::path::PathBuf;
 
+// Bytes read from the start of a file to detect its type from the content
+const SNIFF_LENGTH: u64 = 8192;
+
+// Text formats mime_guess doesn't know. Drive shows text/plain files in the
+// preview, octet-stream files only as a download
+const TEXT_EXTENSIONS: [&str; 12] = [
+    "log", "conf", "cfg", "ini", "env", "toml", "rs", "go", "kt", "swift", "gradle", "lock",
+];
+
 pub struct FileInfo {
     pub name: String,
     pub mime_type: mime::Mime,
@@ -27,11 +38,10 @@ impl FileInfo {
 
         let file_size = file.metadata().map(|m| m.len()).unwrap_or(0);
 
-        let mime_type = config.mime_type.clone().unwrap_or_else(|| {
-            mime_guess::from_path(&config.file_path)
-                .first()
-                .unwrap_or(mime::APPLICATION_OCTET_STREAM)
-        });
+        let mime_type = config
+            .mime_type
+            .clone()
+            .unwrap_or_else(|| detect_mime_type(&config.file_path));
 
         Ok(FileInfo {
             name: file_name,
@@ -42,6 +52,55 @@ impl FileInfo {
     }
 }
 
+// Guesses the mime type from the extension first and from the content when the
+// extension is missing or unknown, i.e. for uploads from stdin
+pub fn detect_mime_type(path: &Path) -> mime::Mime {
+    mime_guess::from_path(path)
+        .first()
+        .or_else(|| mime_from_extension(path))
+        .or_else(|| sniff_mime_type(path))
+        .unwrap_or(mime::APPLICATION_OCTET_STREAM)
+}
+
+fn mime_from_extension(path: &Path) -> Option<mime::Mime> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+
+    match extension.as_str() {
+        "heic" => "image/heic".parse().ok(),
+        "heif" => "image/heif".parse().ok(),
+        ext if TEXT_EXTENSIONS.contains(&ext) => Some(mime::TEXT_PLAIN),
+        _ => None,
+    }
+}
+
+fn sniff_mime_type(path: &Path) -> Option<mime::Mime> {
+    let file = fs::File::open(path).ok()?;
+    let mut head = Vec::new();
+    file.take(SNIFF_LENGTH).read_to_end(&mut head).ok()?;
+
+    if let Some(kind) = infer::get(&head) {
+        return kind.mime_type().parse().ok();
+    }
+
+    if is_text(&head) {
+        Some(mime::TEXT_PLAIN)
+    } else {
+        None
+    }
+}
+
+// Valid UTF-8 without NUL bytes, the head may end in the middle of a character
+fn is_text(head: &[u8]) -> bool {
+    if head.is_empty() || head.contains(&0) {
+        return false;
+    }
+
+    match std::str::from_utf8(head) {
+        Ok(_) => true,
+        Err(err) => err.error_len().is_none(),
+    }
+}
+
 #[derive(Debug)]
 pub enum Error {
     InvalidFilePath(PathBuf),
@@ -56,3 +115,40 @@ impl Display for Error {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(name: &str, content: &[u8]) -> mime::Mime {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(name);
+        fs::write(&path, content).unwrap();
+        detect_mime_type(&path)
+    }
+
+    #[test]
+    fn extension_is_used_before_content() {
+        assert_eq!(detect("notes.md", b"\x89PNG\r\n\x1a\n"), "text/markdown");
+        assert_eq!(detect("server.log", b"\x00\x01"), mime::TEXT_PLAIN);
+    }
+
+    #[test]
+    fn content_is_sniffed_without_extension() {
+        assert_eq!(
+            detect("stdin", b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR"),
+            mime::IMAGE_PNG
+        );
+        assert_eq!(detect("stdin", "sne\u{f6}".as_bytes()), mime::TEXT_PLAIN);
+        assert_eq!(
+            detect("stdin", b"\x00\xfe\xfd"),
+            mime::APPLICATION_OCTET_STREAM
+        );
+    }
+
+    #[test]
+    fn text_may_end_in_a_split_character() {
+        assert!(is_text(&"\u{f6}".as_bytes()[..1]));
+        assert!(!is_text(b"\xff text"));
+    }
+}
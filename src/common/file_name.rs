@@ -0,0 +1,212 @@
+use crate::common::local_path;
+use google_drive3::chrono;
+use google_drive3::chrono::format::Item;
+use google_drive3::chrono::format::StrftimeItems;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::Path;
+
+pub const DEFAULT_REPLACEMENT: &str = "_";
+
+// Enough of the id to tell files with the same name apart
+const SHORT_ID_LENGTH: usize = 8;
+
+// Characters Windows doesn't allow in file names, in addition to path separators
+const WINDOWS_INVALID_CHARS: [char; 7] = ['<', '>', ':', '"', '|', '?', '*'];
+
+// Drive allows any character in a name, including path separators. Invalid
+// characters are replaced so the file is saved under the target directory with
+// a name the platform accepts
+pub fn sanitize(name: &str, replacement: &str) -> String {
+    let mut sanitized = String::with_capacity(name.len());
+    for c in name.chars() {
+        if is_invalid_char(c) {
+            sanitized.push_str(replacement);
+        } else {
+            sanitized.push(c);
+        }
+    }
+
+    // Windows drops trailing dots and spaces, which could make two names collide
+    if cfg!(windows) {
+        let trimmed_len = sanitized.trim_end_matches(['.', ' ']).len();
+        if trimmed_len < sanitized.len() {
+            sanitized.truncate(trimmed_len);
+            sanitized.push_str(replacement);
+        }
+    }
+
+    if sanitized.is_empty() || sanitized == "." || sanitized == ".." {
+        sanitized = sanitized.replace('.', DEFAULT_REPLACEMENT);
+        if sanitized.is_empty() {
+            sanitized = DEFAULT_REPLACEMENT.to_string();
+        }
+    }
+
+    local_path::safe_name(&sanitized)
+}
+
+// Drive allows several files with the same name in one folder, a local folder
+// doesn't. Names are compared case-insensitively since many filesystems are
+pub fn duplicate_names<'a>(names: impl Iterator<Item = &'a str>) -> HashSet<String> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for name in names {
+        *counts.entry(name.to_lowercase()).or_default() += 1;
+    }
+
+    counts
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .map(|(name, _)| name)
+        .collect()
+}
+
+pub fn is_duplicate(duplicates: &HashSet<String>, name: &str) -> bool {
+    duplicates.contains(&name.to_lowercase())
+}
+
+// Adds the start of the id before the extension, i.e. "report [1a2B3c4D].pdf"
+pub fn with_id_suffix(name: &str, id: &str) -> String {
+    let short_id: String = id.chars().take(SHORT_ID_LENGTH).collect();
+    let path = Path::new(name);
+
+    match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(extension)) => format!(
+            "{} [{}].{}",
+            stem.to_string_lossy(),
+            short_id,
+            extension.to_string_lossy()
+        ),
+        _ => format!("{} [{}]", name, short_id),
+    }
+}
+
+// The replacement must not contain characters that would be replaced
+pub fn is_valid_replacement(replacement: &str) -> bool {
+    !replacement.chars().any(is_invalid_char)
+}
+
+fn is_invalid_char(c: char) -> bool {
+    c == '/' || c == '\\' || c.is_control() || (cfg!(windows) && WINDOWS_INVALID_CHARS.contains(&c))
+}
+
+// Name from a template like `backup-{date}.tar.gz` or `logs-%Y-%m-%d`. {date}
+// is YYYY-MM-DD and {time} HH.MM.SS in local time, {hostname} the name of this
+// machine, {uuid} a random uuid and {name} the local file name of an upload.
+// strftime sequences are expanded in the rest of the template. Use {{, }} and
+// %% for literal braces and percent signs
+pub fn render_template(
+    template: &str,
+    local_name: Option<&str>,
+    time: chrono::DateTime<chrono::Local>,
+) -> Result<String, String> {
+    let mut name = String::new();
+    // Text since the last placeholder, the placeholder values are not expanded
+    let mut literal = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '{' => {
+                name.push_str(&format_time(&literal, time)?);
+                literal.clear();
+
+                let placeholder: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                let value = match placeholder.as_str() {
+                    "date" => time.format("%Y-%m-%d").to_string(),
+                    "time" => time.format("%H.%M.%S").to_string(),
+                    "hostname" => hostname::get()
+                        .map_err(|err| format!("Failed to get the hostname: {}", err))?
+                        .to_string_lossy()
+                        .to_string(),
+                    "uuid" => uuid::Uuid::new_v4().to_string(),
+                    "name" => local_name
+                        .ok_or("{name} is only available for uploads")?
+                        .to_string(),
+                    _ => {
+                        return Err(format!(
+                            "Unknown placeholder '{{{}}}', use {{date}}, {{time}}, {{hostname}}, {{uuid}} or {{name}}",
+                            placeholder
+                        ))
+                    }
+                };
+                name.push_str(&value);
+            }
+            c => literal.push(c),
+        }
+    }
+
+    name.push_str(&format_time(&literal, time)?);
+
+    if name.is_empty() {
+        return Err("The name is empty".to_string());
+    }
+
+    Ok(name)
+}
+
+fn format_time(text: &str, time: chrono::DateTime<chrono::Local>) -> Result<String, String> {
+    let items: Vec<Item> = StrftimeItems::new(text).collect();
+    if items.iter().any(|item| matches!(item, Item::Error)) {
+        return Err(format!(
+            "Invalid % sequence in '{}', use %% for a literal %",
+            text
+        ));
+    }
+
+    Ok(time.format_with_items(items.into_iter()).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use google_drive3::chrono::TimeZone;
+
+    fn time() -> chrono::DateTime<chrono::Local> {
+        chrono::Local.with_ymd_and_hms(2024, 3, 9, 7, 5, 0).unwrap()
+    }
+
+    #[test]
+    fn template_placeholders_are_replaced() {
+        let name = render_template("{name}-{date}_{time}.tar.gz", Some("db"), time()).unwrap();
+        assert_eq!(name, "db-2024-03-09_07.05.00.tar.gz");
+
+        let name = render_template("{{date}}", Some("db"), time()).unwrap();
+        assert_eq!(name, "{date}");
+    }
+
+    #[test]
+    fn strftime_is_not_applied_to_placeholder_values() {
+        let name = render_template("logs-%Y-%m-%d/{name} 100%%", Some("%H"), time()).unwrap();
+        assert_eq!(name, "logs-2024-03-09/%H 100%");
+
+        let name = render_template("{uuid}", None, time()).unwrap();
+        assert_eq!(name.len(), 36);
+    }
+
+    #[test]
+    fn unknown_placeholder_is_an_error() {
+        let err = render_template("backup-{day}", Some("db"), time()).unwrap_err();
+        assert_eq!(
+            err,
+            "Unknown placeholder '{day}', use {date}, {time}, {hostname}, {uuid} or {name}"
+        );
+
+        let err = render_template("logs-{name}", None, time()).unwrap_err();
+        assert_eq!(err, "{name} is only available for uploads");
+
+        let err = render_template("50% off", None, time()).unwrap_err();
+        assert_eq!(
+            err,
+            "Invalid % sequence in '50% off', use %% for a literal %"
+        );
+    }
+}
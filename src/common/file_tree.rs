@@ -1,6 +1,8 @@
+use crate::common::file_info;
 use crate::common::file_info::FileInfo;
 use crate::common::id_gen;
 use crate::common::id_gen::IdGen;
+use crate::common::local_path;
 use async_recursion::async_recursion;
 use std::error;
 use std::fmt::Display;
@@ -8,12 +10,117 @@ use std::fmt::Formatter;
 use std::fs;
 use std::io;
 use std::path::PathBuf;
+use tokio::sync::mpsc;
+
+// Entries that walk can get ahead of the consumer
+pub const WALK_BUFFER: usize = 1000;
 
 #[derive(Debug, Clone)]
 pub struct FileTree {
     pub root: Folder,
 }
 
+// Number of folders and files found so far while walking
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ScanProgress {
+    pub folders: u64,
+    pub files: u64,
+}
+
+pub type OnScanProgress<'a> = &'a mut (dyn FnMut(ScanProgress) + Send);
+
+#[derive(Debug, Clone)]
+pub enum Entry {
+    Folder(Folder),
+    File(File),
+}
+
+// Sends the folders and files under `path` as they are found, so they can be
+// uploaded while the rest of the tree is still being walked. A folder is always
+// sent before anything in it. Folders have no children, their files and
+// subfolders are sent as separate entries. Stops early if the receiver is dropped.
+// Hidden files and folders under `path` are skipped unless `include_hidden` is
+// set, `path` itself is always walked
+pub async fn walk<'a, 'b>(
+    path: &PathBuf,
+    include_hidden: bool,
+    ids: &mut IdGen<'a>,
+    sender: mpsc::Sender<Entry>,
+    on_progress: OnScanProgress<'b>,
+) -> Result<(), Error> {
+    let canonical_path = path
+        .canonicalize()
+        .map_err(|err| Error::CanonicalizePath(path.clone(), err))?;
+
+    let mut progress = ScanProgress::default();
+    let mut pending: Vec<(PathBuf, Option<Folder>)> = vec![(canonical_path, None)];
+
+    while let Some((path, parent)) = pending.pop() {
+        let name = local_path::name(&path).ok_or(Error::InvalidPath(path.clone()))?;
+        let drive_id = ids.next().await.map_err(Error::GetId)?;
+
+        let folder = Folder {
+            name,
+            path: path.clone(),
+            parent: parent.map(Box::new),
+            children: Vec::new(),
+            drive_id,
+        };
+
+        progress.folders += 1;
+        on_progress(progress);
+        if sender.send(Entry::Folder(folder.clone())).await.is_err() {
+            return Ok(());
+        }
+
+        let mut subfolders = Vec::new();
+
+        for e in fs::read_dir(&path).map_err(Error::ReadDir)? {
+            let entry = e.map_err(Error::ReadDirEntry)?;
+            let path = entry.path();
+
+            if !include_hidden && local_path::is_hidden(&path) {
+                continue;
+            }
+
+            if path.is_dir() {
+                subfolders.push(path);
+            } else if path.is_file() {
+                let file = File::from_path(&path, &folder, ids).await?;
+                progress.files += 1;
+                on_progress(progress);
+                if sender.send(Entry::File(file)).await.is_err() {
+                    return Ok(());
+                }
+            } else {
+                return Err(Error::UnknownFileType(path.clone()));
+            }
+        }
+
+        // Reversed so subfolders are walked in directory order
+        for subfolder in subfolders.into_iter().rev() {
+            pending.push((subfolder, Some(folder.clone())));
+        }
+    }
+
+    Ok(())
+}
+
+// Waits for the next entry and takes whatever else the walk has already sent,
+// so the consumer can work on many entries at once. Empty when the walk is done
+pub async fn recv_batch(receiver: &mut mpsc::Receiver<Entry>) -> Vec<Entry> {
+    let mut entries = vec![];
+
+    if let Some(entry) = receiver.recv().await {
+        entries.push(entry);
+        while let Ok(entry) = receiver.try_recv() {
+            entries.push(entry);
+        }
+    }
+
+    entries
+}
+
 impl FileTree {
     pub async fn from_path<'a>(path: &PathBuf, ids: &mut IdGen<'a>) -> Result<FileTree, Error> {
         let canonical_path = path
@@ -96,10 +203,8 @@ impl Folder {
         parent: Option<&'async_recursion Folder>,
         ids: &mut IdGen<'a>,
     ) -> Result<Folder, Error> {
-        let name = path
-            .file_name()
-            .map(|s| s.to_string_lossy().to_string())
-            .ok_or(Error::InvalidPath(path.clone()))?;
+        // Drive and share roots have no file name, they are named after the drive letter or share
+        let name = local_path::name(path).ok_or(Error::InvalidPath(path.clone()))?;
 
         let drive_id = ids.next().await.map_err(Error::GetId)?;
 
@@ -212,9 +317,7 @@ impl File {
 
         let os_file = fs::File::open(path).map_err(|err| Error::OpenFile(path.clone(), err))?;
         let size = os_file.metadata().map(|m| m.len()).unwrap_or(0);
-        let mime_type = mime_guess::from_path(path)
-            .first()
-            .unwrap_or(mime::APPLICATION_OCTET_STREAM);
+        let mime_type = file_info::detect_mime_type(path);
         let drive_id = ids.next().await.map_err(Error::GetId)?;
 
         let file = File {
@@ -1,4 +1,6 @@
 use crate::common::drive_file;
+use crate::common::file_filter::FileFilter;
+use crate::common::file_name;
 use crate::files::list;
 use crate::files::list::ListQuery;
 use crate::hub::Hub;
@@ -17,8 +19,10 @@ impl FileTreeDrive {
     pub async fn from_file(
         hub: &Hub,
         file: &google_drive3::api::File,
+        filter: &FileFilter,
     ) -> Result<FileTreeDrive, Error> {
-        let root = Folder::from_file(hub, file, None).await?;
+        let name = file.name.clone().ok_or(Error::MissingFileName)?;
+        let root = Folder::from_file(hub, file, name, None, filter).await?;
         Ok(FileTreeDrive { root })
     }
 
@@ -81,6 +85,8 @@ pub enum Node {
 #[derive(Debug, Clone)]
 pub struct Folder {
     pub name: String,
+    // Name of the local folder, see `local_names`
+    pub local_name: String,
     pub parent: Option<Box<Folder>>,
     pub children: Vec<Node>,
     pub drive_id: String,
@@ -91,7 +97,9 @@ impl Folder {
     pub async fn from_file(
         hub: &Hub,
         file: &google_drive3::api::File,
+        local_name: String,
         parent: Option<&'async_recursion Folder>,
+        filter: &FileFilter,
     ) -> Result<Folder, Error> {
         err_if_not_directory(&file)?;
 
@@ -100,6 +108,7 @@ impl Folder {
 
         let mut folder = Folder {
             name,
+            local_name,
             parent: parent.map(|folder| Box::new(folder.clone())),
             children: Vec::new(),
             drive_id: file_id.clone(),
@@ -110,6 +119,7 @@ impl Folder {
             &list::ListFilesConfig {
                 query: ListQuery::FilesInFolder { folder_id: file_id },
                 order_by: Default::default(),
+                scope: Default::default(),
                 max_files: usize::MAX,
             },
         )
@@ -117,14 +127,19 @@ impl Folder {
         .map_err(Error::ListFiles)?;
 
         let mut children = Vec::new();
+        let local_names = local_names(&folder, &files);
 
-        for file in files {
+        for (file, local_name) in files.iter().zip(local_names) {
             if drive_file::is_directory(&file) {
-                let folder = Folder::from_file(hub, &file, Some(&folder)).await?;
+                let folder =
+                    Folder::from_file(hub, &file, local_name, Some(&folder), filter).await?;
                 let node = Node::FolderNode(folder);
                 children.push(node);
             } else if drive_file::is_binary(&file) {
-                let f = File::from_file(&file, &folder).await?;
+                if !filter.matches_drive_file(&file) {
+                    continue;
+                }
+                let f = File::from_file(&file, local_name, &folder).await?;
                 let node = Node::FileNode(f);
                 children.push(node);
             } else {
@@ -151,14 +166,15 @@ impl Folder {
         files
     }
 
-    pub fn relative_path(&self) -> PathBuf {
+    // Local path relative to the download root, names are sanitized with `replacement`
+    pub fn relative_path(&self, replacement: &str) -> PathBuf {
         let mut path = PathBuf::new();
 
         for folder in get_ancestors(self) {
-            path.push(&folder.name);
+            path.push(file_name::sanitize(&folder.local_name, replacement));
         }
 
-        path.join(&self.name)
+        path.join(file_name::sanitize(&self.local_name, replacement))
     }
 
     pub fn folders_recursive(&self) -> Vec<Folder> {
@@ -195,6 +211,8 @@ impl Folder {
 #[derive(Debug, Clone)]
 pub struct File {
     pub name: String,
+    // Name of the local file, see `local_names`
+    pub local_name: String,
     pub size: u64,
     pub parent: Folder,
     pub drive_id: String,
@@ -204,6 +222,7 @@ pub struct File {
 impl File {
     pub async fn from_file(
         file: &google_drive3::api::File,
+        local_name: String,
         parent: &Folder,
     ) -> Result<File, Error> {
         let name = file.name.clone().ok_or(Error::MissingFileName)?;
@@ -213,6 +232,7 @@ impl File {
 
         let file = File {
             name,
+            local_name,
             size,
             parent: parent.clone(),
             drive_id: file_id,
@@ -222,11 +242,48 @@ impl File {
         Ok(file)
     }
 
-    pub fn relative_path(&self) -> PathBuf {
-        self.parent.relative_path().join(&self.name)
+    pub fn relative_path(&self, replacement: &str) -> PathBuf {
+        self.parent
+            .relative_path(replacement)
+            .join(file_name::sanitize(&self.local_name, replacement))
     }
 }
 
+// Local names for the files in `folder`. Files that share their name with
+// another file or folder get the start of their id added, so one doesn't
+// overwrite the other. Google Docs aren't downloaded and can keep their name
+fn local_names(folder: &Folder, files: &[google_drive3::api::File]) -> Vec<String> {
+    let is_downloaded = |file: &&google_drive3::api::File| {
+        drive_file::is_directory(file) || drive_file::is_binary(file)
+    };
+
+    let duplicates = file_name::duplicate_names(
+        files
+            .iter()
+            .filter(is_downloaded)
+            .filter_map(|file| file.name.as_deref()),
+    );
+
+    for name in &duplicates {
+        eprintln!(
+            "Warning: '{}' has more than one file named like '{}', their id is added to the local name",
+            folder.name, name
+        );
+    }
+
+    files
+        .iter()
+        .map(|file| {
+            let name = file.name.clone().unwrap_or_default();
+            if is_downloaded(&file) && file_name::is_duplicate(&duplicates, &name) {
+                file_name::with_id_suffix(&name, &file.id.clone().unwrap_or_default())
+            } else {
+                name
+            }
+        })
+        .collect()
+}
+
 #[derive(Debug)]
 pub enum Error {
     NotADirectory(String),
@@ -0,0 +1,98 @@
+use serde::Deserialize;
+use sha2::Digest;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+// Computes the checksums Drive keeps for a file while it is written
+pub struct HashWriter<T> {
+    writer: T,
+    md5: md5::Context,
+    sha1: sha1::Sha1,
+    sha256: sha2::Sha256,
+}
+
+impl<T: Write> HashWriter<T> {
+    pub fn new(writer: T) -> Self {
+        Self {
+            writer,
+            md5: md5::Context::new(),
+            sha1: sha1::Sha1::new(),
+            sha256: sha2::Sha256::new(),
+        }
+    }
+
+    pub fn hashes(self) -> Hashes {
+        Hashes {
+            md5: format!("{:x}", self.md5.compute()),
+            sha1: format!("{:x}", self.sha1.finalize()),
+            sha256: format!("{:x}", self.sha256.finalize()),
+        }
+    }
+}
+
+impl<T: Write> Write for HashWriter<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let byte_count = self.writer.write(buf)?;
+        let written = &buf[..byte_count];
+        self.md5.consume(written);
+        self.sha1.update(written);
+        self.sha256.update(written);
+        Ok(byte_count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hashes {
+    pub md5: String,
+    pub sha1: String,
+    pub sha256: String,
+}
+
+// Checksums as reported by Drive. Only files with content have them, and
+// older files may lack sha1 and sha256
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Checksums {
+    pub md5_checksum: Option<String>,
+    pub sha1_checksum: Option<String>,
+    pub sha256_checksum: Option<String>,
+}
+
+impl Checksums {
+    pub fn md5(md5: Option<String>) -> Checksums {
+        Checksums {
+            md5_checksum: md5,
+            ..Checksums::default()
+        }
+    }
+
+    // The first checksum that doesn't match, as (algorithm, expected, actual)
+    pub fn mismatch(&self, actual: &Hashes) -> Option<(&'static str, String, String)> {
+        [
+            ("md5", &self.md5_checksum, &actual.md5),
+            ("sha1", &self.sha1_checksum, &actual.sha1),
+            ("sha256", &self.sha256_checksum, &actual.sha256),
+        ]
+        .into_iter()
+        .find_map(|(algorithm, expected, actual)| match expected {
+            Some(expected) if expected != actual => {
+                Some((algorithm, expected.clone(), actual.clone()))
+            }
+            _ => None,
+        })
+    }
+}
+
+// Only md5, which is all sync and backup compare against
+pub fn md5_of_file(path: &Path) -> io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut context = md5::Context::new();
+    io::copy(&mut file, &mut context)?;
+    Ok(format!("{:x}", context.compute()))
+}
@@ -1,29 +1,126 @@
 use crate::app_config;
 use crate::app_config::AppConfig;
+use crate::app_config::HttpConfig;
+use crate::app_config::Secret;
+use crate::config::settings;
 use crate::hub::Auth;
 use crate::hub::Hub;
+use std::env;
 use std::error;
 use std::fmt;
 use std::fmt::Display;
 use std::fmt::Formatter;
 use std::io;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+// Credentials for CI, they are used instead of the current account when set
+pub const SA_KEY_ENV: &str = "GDRIVE_SA_KEY_JSON";
+pub const REFRESH_TOKEN_ENV: &str = "GDRIVE_REFRESH_TOKEN";
+pub const CLIENT_ID_ENV: &str = "GDRIVE_CLIENT_ID";
+pub const CLIENT_SECRET_ENV: &str = "GDRIVE_CLIENT_SECRET";
+
+// The hub of the current account, built once for the process. Its clones use
+// the same connection pool and token cache, so jobs running at the same time
+// don't each do the TLS handshake and refresh the token
+static SHARED_HUB: Mutex<Option<Arc<Hub>>> = Mutex::new(None);
+
+// From the http.* settings, the command line flags are their top layer
+pub fn http_config() -> HttpConfig {
+    settings::current().http_config()
+}
 
 pub async fn get_hub() -> Result<Hub, Error> {
+    let auth = get_auth().await?;
+    let hub = Hub::new(auth, &http_config()).await;
+
+    Ok(hub)
+}
+
+// Like get_hub, but hands out the same hub until set_shared_hub replaces it
+pub async fn shared_hub() -> Result<Arc<Hub>, Error> {
+    if let Some(hub) = lock_shared_hub().clone() {
+        return Ok(hub);
+    }
+
+    // Not locked while the hub is built. If another job built one meanwhile,
+    // that one is kept so all of them share it
+    let hub = Arc::new(get_hub().await?);
+    let hub = lock_shared_hub().get_or_insert(hub).clone();
+    Ok(hub)
+}
+
+// After switching the account, shared_hub hands out the hub of the new one.
+// Jobs that still hold the old hub finish with it
+pub fn set_shared_hub(hub: Arc<Hub>) {
+    *lock_shared_hub() = Some(hub);
+}
+
+fn lock_shared_hub() -> std::sync::MutexGuard<'static, Option<Arc<Hub>>> {
+    SHARED_HUB
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+async fn get_auth() -> Result<Auth, Error> {
+    if let Some(key_json) = env_var(SA_KEY_ENV) {
+        return Auth::from_service_account_key(&key_json)
+            .await
+            .map_err(Error::Auth);
+    }
+
+    if let Some(refresh_token) = env_var(REFRESH_TOKEN_ENV) {
+        let secret = env_secret().map_or_else(load_current_secret, Ok)?;
+        return Auth::from_refresh_token(&secret, &refresh_token)
+            .await
+            .map_err(Error::Auth);
+    }
+
     let app_cfg = AppConfig::load_current_account().map_err(Error::AppConfig)?;
+    account_auth(&app_cfg).await
+}
+
+// Hub for an account other than the current one, i.e. to move files between
+// accounts. The environment variables don't apply here
+pub async fn get_hub_for_account(account_name: &str) -> Result<Hub, Error> {
+    let app_cfg = AppConfig::load_account(account_name).map_err(Error::AppConfig)?;
+    let auth = account_auth(&app_cfg).await?;
+    let hub = Hub::new(auth, &http_config()).await;
+
+    Ok(hub)
+}
+
+async fn account_auth(app_cfg: &AppConfig) -> Result<Auth, Error> {
     let secret = app_cfg.load_secret().map_err(Error::AppConfig)?;
-    let auth = Auth::new(&secret, &app_cfg.tokens_path())
+    Auth::new(&secret, &app_cfg.tokens_path())
         .await
-        .map_err(Error::Auth)?;
+        .map_err(Error::Auth)
+}
 
-    let hub = Hub::new(auth).await;
+// The refresh token only works with the client it was issued to
+pub fn env_secret() -> Option<Secret> {
+    Some(Secret {
+        client_id: env_var(CLIENT_ID_ENV)?,
+        client_secret: env_var(CLIENT_SECRET_ENV)?,
+    })
+}
 
-    Ok(hub)
+fn load_current_secret() -> Result<Secret, Error> {
+    AppConfig::load_current_account()
+        .and_then(|app_cfg| app_cfg.load_secret())
+        .map_err(|_| Error::MissingClientSecret)
+}
+
+// Empty variables are treated as unset, CI systems often define them that way
+pub fn env_var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.trim().is_empty())
 }
 
 #[derive(Debug)]
 pub enum Error {
     AppConfig(app_config::Error),
     Auth(io::Error),
+    MissingClientSecret,
 }
 
 impl error::Error for Error {}
@@ -33,6 +130,11 @@ impl Display for Error {
         match self {
             Error::AppConfig(err) => write!(f, "{}", err),
             Error::Auth(err) => write!(f, "Auth error: {}", err),
+            Error::MissingClientSecret => write!(
+                f,
+                "{} needs the client it was issued to, set {} and {} or add an account with the same client",
+                REFRESH_TOKEN_ENV, CLIENT_ID_ENV, CLIENT_SECRET_ENV
+            ),
         }
     }
 }
@@ -5,6 +5,11 @@ use std::error;
 use std::fmt::Display;
 use std::fmt::Formatter;
 
+// The most ids files.generateIds returns in one request
+const BATCH_SIZE: i32 = 1000;
+
+// Hands out drive ids for files that are yet to be created. Ids are fetched
+// BATCH_SIZE at a time, so large uploads only need a request per batch
 pub struct IdGen<'a> {
     hub: &'a Hub,
     delegate_config: UploadDelegateConfig,
@@ -36,7 +41,7 @@ impl<'a> IdGen<'a> {
     }
 
     async fn generate_ids(&self) -> Result<Vec<String>, Error> {
-        generate_ids::generate_ids(self.hub, 1000, self.delegate_config.clone())
+        generate_ids::generate_ids(self.hub, BATCH_SIZE, self.delegate_config.clone())
             .await
             .map_err(Error::GenerateIds)
     }
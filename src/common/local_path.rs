@@ -0,0 +1,261 @@
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+// Paths this long need the \\?\ prefix on Windows, or most file APIs fail
+const MAX_PATH: usize = 260;
+
+const VERBATIM_PREFIX: &str = r"\\?\";
+const VERBATIM_UNC_PREFIX: &str = r"\\?\UNC\";
+
+// Device names that can't be used as file names on Windows, with or without an extension
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+// The parent directory, or None at a drive root, UNC share root or `/`.
+// Unlike Path::parent this never returns an empty path for relative names
+pub fn parent(path: &Path) -> Option<PathBuf> {
+    path.parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .map(|parent| parent.to_path_buf())
+}
+
+// The file name, or the drive letter or share name for the root of a drive or share
+pub fn name(path: &Path) -> Option<String> {
+    if let Some(name) = path.file_name() {
+        return Some(name.to_string_lossy().to_string());
+    }
+
+    match path.components().next() {
+        Some(Component::Prefix(prefix)) => {
+            let prefix = simplify(&prefix.as_os_str().to_string_lossy());
+            let name = prefix
+                .trim_start_matches('\\')
+                .trim_end_matches(['\\', ':'])
+                .rsplit('\\')
+                .next()
+                .unwrap_or_default()
+                .to_string();
+            Some(name).filter(|name| !name.is_empty())
+        }
+        _ => None,
+    }
+}
+
+pub fn is_reserved_name(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or(name).trim_end();
+    RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+}
+
+// Reserved names get a trailing underscore on Windows, i.e. `CON.txt` is saved as `CON_.txt`
+pub fn safe_name(name: &str) -> String {
+    if cfg!(windows) && is_reserved_name(name) {
+        match name.split_once('.') {
+            Some((stem, extension)) => format!("{}_.{}", stem.trim_end(), extension),
+            None => format!("{}_", name.trim_end()),
+        }
+    } else {
+        name.to_string()
+    }
+}
+
+// Adds the \\?\ prefix to long absolute paths on Windows so they can be opened.
+// Paths from canonicalize already have it
+pub fn extended(path: &Path) -> PathBuf {
+    let s = path.to_string_lossy();
+    if cfg!(windows) && path.is_absolute() && s.len() >= MAX_PATH {
+        PathBuf::from(extend(&s))
+    } else {
+        path.to_path_buf()
+    }
+}
+
+// Removes the \\?\ prefix that canonicalize adds on Windows, for display
+pub fn simplified(path: &Path) -> PathBuf {
+    if cfg!(windows) {
+        PathBuf::from(simplify(&path.to_string_lossy()))
+    } else {
+        path.to_path_buf()
+    }
+}
+
+// Dot files and folders, and on Windows also the ones with the hidden attribute
+pub fn is_hidden(path: &Path) -> bool {
+    let dot_file = path
+        .file_name()
+        .map(|name| name.to_string_lossy().starts_with('.'))
+        .unwrap_or(false);
+
+    dot_file || has_hidden_attribute(path)
+}
+
+#[cfg(windows)]
+fn has_hidden_attribute(path: &Path) -> bool {
+    use std::os::windows::fs::MetadataExt;
+
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+    std::fs::symlink_metadata(path)
+        .map(|metadata| metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(windows))]
+fn has_hidden_attribute(_path: &Path) -> bool {
+    false
+}
+
+// Drive roots that exist, used to navigate above a drive root on Windows
+pub fn drive_roots() -> Vec<PathBuf> {
+    if !cfg!(windows) {
+        return vec![PathBuf::from("/")];
+    }
+
+    (b'A'..=b'Z')
+        .map(|letter| PathBuf::from(format!(r"{}:\", letter as char)))
+        .filter(|root| root.exists())
+        .collect()
+}
+
+fn extend(path: &str) -> String {
+    let path = path.replace('/', r"\");
+    if path.starts_with(VERBATIM_PREFIX) {
+        path
+    } else if let Some(unc) = path.strip_prefix(r"\\") {
+        format!("{}{}", VERBATIM_UNC_PREFIX, unc)
+    } else {
+        format!("{}{}", VERBATIM_PREFIX, path)
+    }
+}
+
+fn simplify(path: &str) -> String {
+    if let Some(unc) = path.strip_prefix(VERBATIM_UNC_PREFIX) {
+        format!(r"\\{}", unc)
+    } else {
+        path.strip_prefix(VERBATIM_PREFIX)
+            .unwrap_or(path)
+            .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn parent_of_relative_name_is_none() {
+        assert_eq!(parent(Path::new("file.txt")), None);
+        assert_eq!(
+            parent(Path::new("dir/file.txt")),
+            Some(PathBuf::from("dir"))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn parent_of_root_is_none() {
+        assert_eq!(parent(Path::new("/")), None);
+        assert_eq!(parent(Path::new("/home")), Some(PathBuf::from("/")));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn parent_of_drive_root_is_none() {
+        assert_eq!(parent(Path::new(r"C:\")), None);
+        assert_eq!(parent(Path::new(r"\\?\C:\")), None);
+        assert_eq!(parent(Path::new(r"\\server\share\")), None);
+        assert_eq!(parent(Path::new(r"C:\Users")), Some(PathBuf::from(r"C:\")));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn name_of_drive_and_share_roots() {
+        assert_eq!(name(Path::new(r"C:\")), Some("C".to_string()));
+        assert_eq!(name(Path::new(r"\\?\C:\")), Some("C".to_string()));
+        assert_eq!(
+            name(Path::new(r"\\server\share")),
+            Some("share".to_string())
+        );
+        assert_eq!(
+            name(Path::new(r"\\?\UNC\server\share")),
+            Some("share".to_string())
+        );
+    }
+
+    #[test]
+    fn name_of_regular_path() {
+        assert_eq!(
+            name(Path::new("dir/file.txt")),
+            Some("file.txt".to_string())
+        );
+    }
+
+    #[test]
+    fn dot_files_are_hidden() {
+        assert!(is_hidden(Path::new("home/.ssh")));
+        assert!(is_hidden(Path::new(".bashrc")));
+        assert!(!is_hidden(Path::new(".config/notes.txt")));
+        assert!(!is_hidden(Path::new("..")));
+    }
+
+    #[test]
+    fn reserved_names() {
+        assert!(is_reserved_name("CON"));
+        assert!(is_reserved_name("nul"));
+        assert!(is_reserved_name("Com1.txt"));
+        assert!(is_reserved_name("LPT9.tar.gz"));
+        assert!(is_reserved_name("AUX .log"));
+        assert!(!is_reserved_name("CONSOLE"));
+        assert!(!is_reserved_name("COM10"));
+        assert!(!is_reserved_name("icon.png"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn safe_name_renames_reserved_names() {
+        assert_eq!(safe_name("CON"), "CON_");
+        assert_eq!(safe_name("nul.txt"), "nul_.txt");
+        assert_eq!(safe_name("notes.txt"), "notes.txt");
+    }
+
+    #[test]
+    fn extend_adds_verbatim_prefix() {
+        assert_eq!(extend(r"C:\Users\me"), r"\\?\C:\Users\me");
+        assert_eq!(extend("C:/Users/me"), r"\\?\C:\Users\me");
+        assert_eq!(extend(r"\\server\share\dir"), r"\\?\UNC\server\share\dir");
+        assert_eq!(extend(r"\\?\C:\Users"), r"\\?\C:\Users");
+    }
+
+    #[test]
+    fn simplify_removes_verbatim_prefix() {
+        assert_eq!(simplify(r"\\?\C:\Users\me"), r"C:\Users\me");
+        assert_eq!(simplify(r"\\?\UNC\server\share\dir"), r"\\server\share\dir");
+        assert_eq!(simplify(r"C:\Users"), r"C:\Users");
+    }
+
+    #[test]
+    fn short_paths_are_not_extended() {
+        let path = std::env::temp_dir().join("short");
+        assert_eq!(extended(&path), path);
+    }
+
+    #[test]
+    fn long_paths_can_be_written_and_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut path = dir.path().to_path_buf();
+        while path.to_string_lossy().len() < MAX_PATH + 40 {
+            path.push("a-fairly-long-directory-name");
+        }
+
+        let long_path = extended(&path);
+        fs::create_dir_all(&long_path).unwrap();
+        fs::write(long_path.join("file.txt"), "content").unwrap();
+
+        let content = fs::read_to_string(extended(&path.join("file.txt"))).unwrap();
+        assert_eq!(content, "content");
+    }
+}
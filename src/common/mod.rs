@@ -1,13 +1,28 @@
 pub mod account_archive;
+pub mod alerts;
+pub mod api_cache;
+pub mod api_stats;
+pub mod batch_request;
 pub mod delegate;
+pub mod deletion_journal;
 pub mod drive_file;
+pub mod drive_query;
+pub mod drive_url;
 pub mod empty_file;
+pub mod file_filter;
 pub mod file_info;
+pub mod file_name;
 pub mod file_tree;
 pub mod file_tree_drive;
+pub mod hash_writer;
 pub mod hub_helper;
 pub mod id_gen;
-pub mod md5_writer;
+pub mod local_path;
+pub mod number_format;
+pub mod path_resolver;
 pub mod permission;
+pub mod quota;
+pub mod read_timeout;
 pub mod table;
+pub mod tee_writer;
 pub mod file_helper;
@@ -0,0 +1,68 @@
+use crate::app_config::NumberFormat;
+use crate::app_config::SizeUnit;
+use crate::config::settings;
+use std::sync::OnceLock;
+
+static CURRENT: OnceLock<NumberFormat> = OnceLock::new();
+
+// The format from the display.* settings, built once
+pub fn current() -> &'static NumberFormat {
+    CURRENT.get_or_init(|| settings::current().number_format())
+}
+
+pub fn format_bytes(bytes: u64, format: &NumberFormat) -> String {
+    let (base, units) = match format.size_unit {
+        SizeUnit::Binary => (1024.0, ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"]),
+        SizeUnit::Decimal => (1000.0, ["B", "kB", "MB", "GB", "TB", "PB", "EB"]),
+    };
+
+    if (bytes as f64) < base {
+        return format!("{} {}", bytes, units[0]);
+    }
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= base && unit < units.len() - 1 {
+        size /= base;
+        unit += 1;
+    }
+
+    let number = match format.decimals {
+        Some(decimals) => format!("{:.*}", decimals, size),
+        None => format!("{:.1}", size)
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_string(),
+    };
+
+    format!("{} {}", localize(&number, format), units[unit])
+}
+
+// Groups digits by thousands, i.e. 1204 -> 1,204
+pub fn format_count(count: u64, format: &NumberFormat) -> String {
+    group_digits(&count.to_string(), &format.thousands_separator)
+}
+
+// Takes a number formatted by rust, i.e. 1204.5
+fn localize(number: &str, format: &NumberFormat) -> String {
+    match number.split_once('.') {
+        Some((integer, fraction)) => format!(
+            "{}{}{}",
+            group_digits(integer, &format.thousands_separator),
+            format.decimal_separator,
+            fraction
+        ),
+        None => group_digits(number, &format.thousands_separator),
+    }
+}
+
+fn group_digits(digits: &str, separator: &str) -> String {
+    let mut grouped = String::new();
+    for (index, ch) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index) % 3 == 0 {
+            grouped.push_str(separator);
+        }
+        grouped.push(ch);
+    }
+    grouped
+}
@@ -0,0 +1,148 @@
+use crate::common::api_stats::StatsDelegate;
+use crate::common::drive_file;
+use crate::common::drive_query::Query;
+use crate::files::list;
+use crate::files::list::{ListFilesConfig, ListScope, ListSortOrder};
+use crate::hub::Hub;
+
+// Resolves a slash separated path below `root_id` by listing one folder per
+// component. Returns None if a component doesn't exist or isn't a folder
+pub async fn resolve(
+    hub: &Hub,
+    root_id: &str,
+    path: &str,
+) -> Result<Option<google_drive3::api::File>, list::Error> {
+    let mut current = google_drive3::api::File {
+        id: Some(root_id.to_string()),
+        mime_type: Some(drive_file::MIME_TYPE_DRIVE_FOLDER.to_string()),
+        ..google_drive3::api::File::default()
+    };
+
+    for name in components(path) {
+        if !drive_file::is_directory(&current) {
+            return Ok(None);
+        }
+
+        let parent_id = current.id.clone().unwrap_or_default();
+        match find_child(hub, &parent_id, name).await? {
+            Some(file) => current = file,
+            None => return Ok(None),
+        }
+    }
+
+    Ok(Some(current))
+}
+
+pub async fn find_child(
+    hub: &Hub,
+    parent_id: &str,
+    name: &str,
+) -> Result<Option<google_drive3::api::File>, list::Error> {
+    let files = list::list_files(
+        hub,
+        &ListFilesConfig {
+            query: child_query(parent_id, name).into(),
+            order_by: ListSortOrder::default(),
+            scope: ListScope::default(),
+            max_files: 1,
+        },
+    )
+    .await?;
+
+    Ok(files.into_iter().next())
+}
+
+// Enough for any real folder tree, Drive could report a cycle
+const MAX_DEPTH: usize = 100;
+
+// The folders above a file, starting with the top most folder the user has
+// access to. Files in My Drive end at the root folder, which has no parents
+pub async fn ancestors(
+    hub: &Hub,
+    file: &google_drive3::api::File,
+) -> Result<Vec<google_drive3::api::File>, google_drive3::Error> {
+    let mut ancestors = vec![];
+    let mut parent_id = first_parent(file);
+
+    while let Some(id) = parent_id {
+        if ancestors.len() >= MAX_DEPTH {
+            break;
+        }
+
+        let parent = match get_folder(hub, &id).await {
+            Ok(parent) => parent,
+            Err(err) if ancestors.is_empty() => return Err(err),
+            // A folder higher up that isn't shared with the user
+            Err(_) => break,
+        };
+
+        parent_id = first_parent(&parent);
+        ancestors.push(parent);
+    }
+
+    ancestors.reverse();
+    Ok(ancestors)
+}
+
+async fn get_folder(
+    hub: &Hub,
+    folder_id: &str,
+) -> Result<google_drive3::api::File, google_drive3::Error> {
+    let (_, folder) = hub
+        .files()
+        .get(folder_id)
+        .param("fields", "id,name,parents,driveId")
+        .supports_all_drives(true)
+        .add_scope(google_drive3::api::Scope::Full)
+        .delegate(&mut StatsDelegate)
+        .doit()
+        .await?;
+
+    Ok(folder)
+}
+
+fn first_parent(file: &google_drive3::api::File) -> Option<String> {
+    file.parents
+        .as_ref()
+        .and_then(|parents| parents.first().cloned())
+}
+
+// Splits a path into its non-empty components, so leading, trailing and
+// repeated slashes are ignored
+pub fn components(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|name| !name.is_empty())
+}
+
+fn child_query(parent_id: &str, name: &str) -> Query {
+    Query::new()
+        .name_is(name)
+        .in_parents(parent_id)
+        .not_trashed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn child_query_escapes_name() {
+        assert_eq!(
+            child_query("folderId", "it's a \\ test").to_string(),
+            "name = 'it\\'s a \\\\ test' and 'folderId' in parents and trashed = false"
+        );
+    }
+
+    #[test]
+    fn child_query_keeps_injected_conditions_in_the_name() {
+        assert_eq!(
+            child_query("folderId", "x' or name != '").to_string(),
+            "name = 'x\\' or name != \\'' and 'folderId' in parents and trashed = false"
+        );
+    }
+
+    #[test]
+    fn components_skip_empty_names() {
+        let names: Vec<&str> = components("/a//b c/it's/").collect();
+        assert_eq!(names, vec!["a", "b c", "it's"]);
+    }
+}
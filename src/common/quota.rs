@@ -0,0 +1,166 @@
+use google_drive3::chrono;
+use google_drive3::chrono::DateTime;
+use google_drive3::chrono::TimeZone;
+use google_drive3::chrono::Utc;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::sync::Mutex;
+use std::time::Duration;
+
+// Drive resets the daily quota at midnight Pacific Time. Standard time is
+// used, during daylight saving time the quota is back an hour earlier
+const PACIFIC_OFFSET_HOURS: i64 = -8;
+const SECONDS_PER_DAY: i64 = 24 * 3600;
+
+// Set by the delegates when Drive said a quota is used up, so the error
+// printed at exit can explain it
+static EXHAUSTED: Mutex<Option<Exhausted>> = Mutex::new(None);
+
+// Quotas that retrying doesn't help with, it only uses up more of them. The
+// per minute rate limits are not here, they are retried
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exhausted {
+    DailyLimit { reset_at: DateTime<Utc> },
+    StorageQuota,
+}
+
+impl Exhausted {
+    pub fn from_reason(reason: &str, now: DateTime<Utc>) -> Option<Exhausted> {
+        match reason {
+            "dailyLimitExceeded" => Some(Exhausted::DailyLimit {
+                reset_at: next_daily_reset(now),
+            }),
+            "storageQuotaExceeded" => Some(Exhausted::StorageQuota),
+            _ => None,
+        }
+    }
+}
+
+impl error::Error for Exhausted {}
+
+impl Display for Exhausted {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Exhausted::DailyLimit { reset_at } => write!(
+                f,
+                "The daily API quota of the OAuth client is used up, retrying won't help until it resets at midnight Pacific Time. Try again after {}",
+                reset_at
+                    .with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M")
+            ),
+            Exhausted::StorageQuota => write!(
+                f,
+                "The Drive storage of the account is full. Delete files, empty the trash or get more storage, then try again"
+            ),
+        }
+    }
+}
+
+pub fn record(exhausted: Exhausted) {
+    if let Ok(mut current) = EXHAUSTED.lock() {
+        *current = Some(exhausted);
+    }
+}
+
+pub fn exhausted() -> Option<Exhausted> {
+    EXHAUSTED.lock().ok().and_then(|current| *current)
+}
+
+// Remembers a used up quota from the error body of a failed request
+pub fn record_error(body: Option<&serde_json::Value>) -> Option<Exhausted> {
+    let exhausted = Exhausted::from_reason(error_reason(body?)?, Utc::now())?;
+    record(exhausted);
+    Some(exhausted)
+}
+
+// The reason of the first error in a Drive error body, i.e.
+// {"error": {"errors": [{"reason": "dailyLimitExceeded", ...}], ...}}
+pub fn error_reason(body: &serde_json::Value) -> Option<&str> {
+    body.pointer("/error/errors/0/reason")
+        .and_then(|reason| reason.as_str())
+}
+
+// Drive answers 403 instead of 429 for some of its rate limits
+pub fn is_rate_limit(reason: &str) -> bool {
+    matches!(reason, "rateLimitExceeded" | "userRateLimitExceeded")
+}
+
+// A Retry-After header is either a number of seconds or an http date
+pub fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let time = DateTime::parse_from_rfc2822(value).ok()?;
+    let wait = time.with_timezone(&Utc) - now;
+    Some(wait.to_std().unwrap_or_default())
+}
+
+fn next_daily_reset(now: DateTime<Utc>) -> DateTime<Utc> {
+    let offset = PACIFIC_OFFSET_HOURS * 3600;
+    let pacific_seconds = now.timestamp() + offset;
+    let next_midnight = (pacific_seconds.div_euclid(SECONDS_PER_DAY) + 1) * SECONDS_PER_DAY;
+
+    Utc.timestamp_opt(next_midnight - offset, 0)
+        .single()
+        .unwrap_or(now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 9, hour, 30, 0).unwrap()
+    }
+
+    #[test]
+    fn daily_limit_resets_at_pacific_midnight() {
+        let body = serde_json::json!({
+            "error": {
+                "code": 403,
+                "errors": [{"domain": "usageLimits", "reason": "dailyLimitExceeded"}],
+            }
+        });
+        let reason = error_reason(&body).unwrap();
+
+        assert_eq!(
+            Exhausted::from_reason(reason, time(7)),
+            Some(Exhausted::DailyLimit {
+                reset_at: Utc.with_ymd_and_hms(2024, 3, 9, 8, 0, 0).unwrap()
+            })
+        );
+        assert_eq!(
+            Exhausted::from_reason(reason, time(9)),
+            Some(Exhausted::DailyLimit {
+                reset_at: Utc.with_ymd_and_hms(2024, 3, 10, 8, 0, 0).unwrap()
+            })
+        );
+        assert!(is_rate_limit("userRateLimitExceeded"));
+        assert_eq!(
+            Exhausted::from_reason("userRateLimitExceeded", time(7)),
+            None
+        );
+    }
+
+    #[test]
+    fn retry_after_is_seconds_or_a_date() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 9, 7, 0, 0).unwrap();
+
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Sat, 09 Mar 2024 07:00:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Sat, 09 Mar 2024 06:00:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+}
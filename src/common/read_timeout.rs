@@ -0,0 +1,147 @@
+use google_drive3::hyper::client::connect::Connected;
+use google_drive3::hyper::client::connect::Connection;
+use google_drive3::hyper::service::Service;
+use google_drive3::hyper::Uri;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Duration;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+use tokio::io::ReadBuf;
+use tokio::time::Sleep;
+
+// Wraps a connector so reads fail when a connection has been silent for longer
+// than the timeout. Hyper waits forever on a connection that was silently dropped
+#[derive(Debug, Clone)]
+pub struct ReadTimeoutConnector<C> {
+    inner: C,
+    timeout: Option<Duration>,
+}
+
+impl<C> ReadTimeoutConnector<C> {
+    pub fn new(inner: C, timeout: Option<Duration>) -> Self {
+        Self { inner, timeout }
+    }
+}
+
+impl<C> Service<Uri> for ReadTimeoutConnector<C>
+where
+    C: Service<Uri>,
+    C::Response: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    C::Future: Send + 'static,
+{
+    type Response = ReadTimeoutStream<C::Response>;
+    type Error = C::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let timeout = self.timeout;
+        let connecting = self.inner.call(uri);
+
+        Box::pin(async move {
+            let io = connecting.await?;
+            Ok(ReadTimeoutStream::new(io, timeout))
+        })
+    }
+}
+
+pub struct ReadTimeoutStream<T> {
+    io: T,
+    timeout: Option<Duration>,
+    // Started when a read has to wait, cleared when data arrives
+    deadline: Option<Pin<Box<Sleep>>>,
+}
+
+impl<T> ReadTimeoutStream<T> {
+    fn new(io: T, timeout: Option<Duration>) -> Self {
+        Self {
+            io,
+            timeout,
+            deadline: None,
+        }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for ReadTimeoutStream<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+
+        match Pin::new(&mut this.io).poll_read(cx, buf) {
+            Poll::Ready(result) => {
+                this.deadline = None;
+                Poll::Ready(result)
+            }
+
+            Poll::Pending => {
+                let timeout = match this.timeout {
+                    Some(timeout) => timeout,
+                    None => return Poll::Pending,
+                };
+
+                let deadline = this
+                    .deadline
+                    .get_or_insert_with(|| Box::pin(tokio::time::sleep(timeout)));
+
+                match deadline.as_mut().poll(cx) {
+                    Poll::Ready(()) => {
+                        this.deadline = None;
+                        Poll::Ready(Err(io::Error::new(
+                            io::ErrorKind::TimedOut,
+                            format!("no data received for {} seconds", timeout.as_secs()),
+                        )))
+                    }
+
+                    Poll::Pending => Poll::Pending,
+                }
+            }
+        }
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for ReadTimeoutStream<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.io).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.io).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.io.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_shutdown(cx)
+    }
+}
+
+// Keeps the protocol negotiated by the inner connection, i.e. http2 over tls
+impl<T: Connection> Connection for ReadTimeoutStream<T> {
+    fn connected(&self) -> Connected {
+        self.io.connected()
+    }
+}
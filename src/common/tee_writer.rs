@@ -0,0 +1,29 @@
+use std::io;
+use std::io::Write;
+
+// Writes everything to all the writers, i.e. to save a download in several places at once
+pub struct TeeWriter<T> {
+    writers: Vec<T>,
+}
+
+impl<T: Write> TeeWriter<T> {
+    pub fn new(writers: Vec<T>) -> Self {
+        Self { writers }
+    }
+}
+
+impl<T: Write> Write for TeeWriter<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for writer in &mut self.writers {
+            writer.write_all(buf)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        for writer in &mut self.writers {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,10 @@
+pub mod doctor;
+pub mod get;
+pub mod list;
+pub mod set;
+pub mod settings;
+
+pub use doctor::doctor;
+pub use get::get;
+pub use list::list;
+pub use set::set;
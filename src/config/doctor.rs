@@ -0,0 +1,534 @@
+use crate::app_config::AppConfig;
+use crate::app_config::HttpConfig;
+use crate::app_config::Secret;
+use crate::common::api_stats;
+use crate::common::hub_helper;
+use crate::config::settings;
+use crate::hub;
+use crate::hub::HttpClient;
+use google_drive3::chrono;
+use google_drive3::hyper;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use std::time::Instant;
+
+const TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+const ABOUT_URL: &str = "https://www.googleapis.com/drive/v3/about?fields=user";
+const FULL_SCOPE: &str = "https://www.googleapis.com/auth/drive";
+
+// Any response counts, most of them answer an anonymous request with an error
+const ENDPOINTS: [(&str, &str); 3] = [
+    ("Drive API", "https://www.googleapis.com/drive/v3/about"),
+    (
+        "Drive uploads",
+        "https://www.googleapis.com/upload/drive/v3/files",
+    ),
+    ("Google sign-in", TOKEN_URL),
+];
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+// Service account tokens are signed with the local time and rejected by Google
+// when it is off by more than a few minutes
+const MAX_CLOCK_SKEW_SECS: i64 = 60;
+
+const NETWORK_FIX: &str =
+    "Check the network, proxy and firewall. If only some networks fail, try --ipv4 or --http1";
+const ADD_ACCOUNT_FIX: &str = "Sign in again with `gdrive account add`";
+
+enum Credentials {
+    ServiceAccount(String),
+    RefreshToken {
+        secret: Secret,
+        refresh_token: String,
+    },
+}
+
+#[derive(Default)]
+struct Report {
+    warnings: usize,
+    failures: usize,
+}
+
+impl Report {
+    fn ok(&mut self, message: impl Display) {
+        println!("[ok]   {}", message);
+    }
+
+    fn warn(&mut self, message: impl Display, fix: impl Display) {
+        self.warnings += 1;
+        println!("[warn] {}", message);
+        println!("       Fix: {}", fix);
+    }
+
+    fn fail(&mut self, message: impl Display, fix: impl Display) {
+        self.failures += 1;
+        println!("[fail] {}", message);
+        println!("       Fix: {}", fix);
+    }
+}
+
+// Checks the things that commonly make every command fail, from the config files
+// to a request to the Drive API, and prints how to fix what is wrong. The token
+// is refreshed directly, the authenticator would open the sign in flow when it fails
+pub async fn doctor() -> Result<(), Error> {
+    let mut report = Report::default();
+
+    println!("Config");
+    let http_config = check_config(&mut report);
+
+    println!();
+    println!("Credentials");
+    let credentials = check_credentials(&mut report);
+
+    println!();
+    println!("Network");
+    let client = hub::http_client(&http_config);
+    let reachable = check_network(&mut report, &client).await;
+
+    if let (Some(credentials), true) = (credentials, reachable) {
+        println!();
+        println!("Authorization");
+        if let Some(access_token) = check_authorization(&mut report, &client, credentials).await {
+            check_api(&mut report, &client, &access_token).await;
+        }
+    }
+
+    println!();
+    match (report.failures, report.warnings) {
+        (0, 0) => println!("All checks passed"),
+        (0, warnings) => println!("All checks passed with {} warning(s)", warnings),
+        (failures, _) => return Err(Error::ChecksFailed(failures)),
+    }
+
+    Ok(())
+}
+
+fn check_config(report: &mut Report) -> HttpConfig {
+    match settings::check() {
+        Ok(()) => report.ok("Settings are valid"),
+        Err(err) => report.fail(
+            err,
+            "Fix or remove the setting, `gdrive config list` shows where each value comes from",
+        ),
+    }
+
+    hub_helper::http_config()
+}
+
+// The same credentials as the other commands use, see hub_helper
+fn check_credentials(report: &mut Report) -> Option<Credentials> {
+    if let Some(key_json) = hub_helper::env_var(hub_helper::SA_KEY_ENV) {
+        let email = serde_json::from_str::<serde_json::Value>(&key_json)
+            .ok()
+            .and_then(|key| key["client_email"].as_str().map(String::from));
+
+        return match email {
+            Some(email) => {
+                report.ok(format!(
+                    "Using service account {} from {}",
+                    email,
+                    hub_helper::SA_KEY_ENV
+                ));
+                Some(Credentials::ServiceAccount(key_json))
+            }
+            None => {
+                report.fail(
+                    format!("{} is not a service account key", hub_helper::SA_KEY_ENV),
+                    "Set it to the content of the json key downloaded from the Google Cloud console",
+                );
+                None
+            }
+        };
+    }
+
+    if let Some(refresh_token) = hub_helper::env_var(hub_helper::REFRESH_TOKEN_ENV) {
+        let secret = match hub_helper::env_secret() {
+            Some(secret) => secret,
+            None => match AppConfig::load_current_account().and_then(|cfg| cfg.load_secret()) {
+                Ok(secret) => secret,
+                Err(_) => {
+                    report.fail(
+                        format!("{} is set without a client", hub_helper::REFRESH_TOKEN_ENV),
+                        format!(
+                            "Set {} and {} to the client the token was issued to",
+                            hub_helper::CLIENT_ID_ENV,
+                            hub_helper::CLIENT_SECRET_ENV
+                        ),
+                    );
+                    return None;
+                }
+            },
+        };
+
+        report.ok(format!(
+            "Using the refresh token from {}",
+            hub_helper::REFRESH_TOKEN_ENV
+        ));
+        return Some(Credentials::RefreshToken {
+            secret,
+            refresh_token,
+        });
+    }
+
+    let app_cfg = match AppConfig::load_current_account() {
+        Ok(app_cfg) => app_cfg,
+        Err(err) => {
+            report.fail(
+                err,
+                "Add an account with `gdrive account add` or select one with `gdrive account switch`",
+            );
+            return None;
+        }
+    };
+    report.ok(format!("Using account '{}'", app_cfg.account.name));
+
+    let secret = match app_cfg.load_secret() {
+        Ok(secret) => secret,
+        Err(err) => {
+            report.fail(err, ADD_ACCOUNT_FIX);
+            return None;
+        }
+    };
+    check_permissions(report, &app_cfg.secret_path());
+
+    let refresh_token = match read_refresh_token(&app_cfg.tokens_path()) {
+        Ok(refresh_token) => refresh_token,
+        Err(err) => {
+            report.fail(err, ADD_ACCOUNT_FIX);
+            return None;
+        }
+    };
+    check_permissions(report, &app_cfg.tokens_path());
+
+    Some(Credentials::RefreshToken {
+        secret,
+        refresh_token,
+    })
+}
+
+// Tokens are stored by scope, the Drive scope is the one gdrive asks for when
+// an account is added
+fn read_refresh_token(path: &Path) -> Result<String, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|err| format!("Failed to read '{}': {}", path.display(), err))?;
+    let tokens: Vec<serde_json::Value> = serde_json::from_str(&content)
+        .map_err(|err| format!("Failed to parse '{}': {}", path.display(), err))?;
+
+    let has_full_scope = |token: &&serde_json::Value| {
+        token["scopes"]
+            .as_array()
+            .map(|scopes| scopes.iter().any(|scope| scope == FULL_SCOPE))
+            .unwrap_or(false)
+    };
+
+    tokens
+        .iter()
+        .find(has_full_scope)
+        .or_else(|| tokens.first())
+        .and_then(|token| token["token"]["refresh_token"].as_str())
+        .map(String::from)
+        .ok_or_else(|| format!("'{}' has no refresh token", path.display()))
+}
+
+fn check_permissions(report: &mut Report, path: &Path) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        if let Ok(metadata) = fs::metadata(path) {
+            if metadata.permissions().mode() & 0o077 != 0 {
+                report.warn(
+                    format!("'{}' can be read by other users", path.display()),
+                    format!("chmod 600 '{}'", path.display()),
+                );
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = (report, path);
+    }
+}
+
+// Returns false when none of the endpoints could be reached
+async fn check_network(report: &mut Report, client: &HttpClient) -> bool {
+    let mut reachable = false;
+    let mut server_time = None;
+
+    for (name, url) in ENDPOINTS {
+        let request = match hyper::Request::get(url).body(hyper::Body::empty()) {
+            Ok(request) => request,
+            Err(_) => continue,
+        };
+
+        let started = Instant::now();
+        match tokio::time::timeout(REQUEST_TIMEOUT, client.request(request)).await {
+            Ok(Ok(response)) => {
+                reachable = true;
+                report.ok(format!(
+                    "{} is reachable ({} ms)",
+                    name,
+                    started.elapsed().as_millis()
+                ));
+
+                if server_time.is_none() {
+                    server_time = response
+                        .headers()
+                        .get(hyper::header::DATE)
+                        .and_then(|date| date.to_str().ok())
+                        .and_then(|date| chrono::DateTime::parse_from_rfc2822(date).ok());
+                }
+            }
+            Ok(Err(err)) => report.fail(format!("{} ({}): {}", name, url, err), NETWORK_FIX),
+            Err(_) => report.fail(
+                format!(
+                    "{} ({}) didn't answer within {} seconds",
+                    name,
+                    url,
+                    REQUEST_TIMEOUT.as_secs()
+                ),
+                NETWORK_FIX,
+            ),
+        }
+    }
+
+    if let Some(server_time) = server_time {
+        let skew = (chrono::Utc::now().timestamp() - server_time.timestamp()).abs();
+        if skew > MAX_CLOCK_SKEW_SECS {
+            report.warn(
+                format!("The clock is off by {} seconds", skew),
+                "Sync the system clock, i.e. enable NTP",
+            );
+        } else {
+            report.ok("The clock is in sync with Google");
+        }
+    }
+
+    reachable
+}
+
+// Returns an access token for the API check
+async fn check_authorization(
+    report: &mut Report,
+    client: &HttpClient,
+    credentials: Credentials,
+) -> Option<String> {
+    match credentials {
+        Credentials::ServiceAccount(key_json) => check_service_account(report, &key_json).await,
+        Credentials::RefreshToken {
+            secret,
+            refresh_token,
+        } => check_refresh_token(report, client, &secret, &refresh_token).await,
+    }
+}
+
+async fn check_service_account(report: &mut Report, key_json: &str) -> Option<String> {
+    let fix = "Check that the key hasn't been deleted in the Google Cloud console";
+
+    let auth = match hub::Auth::from_service_account_key(key_json).await {
+        Ok(auth) => auth,
+        Err(err) => {
+            report.fail(format!("Invalid service account key: {}", err), fix);
+            return None;
+        }
+    };
+
+    match auth.token(&[FULL_SCOPE]).await {
+        Ok(token) => {
+            report.ok("Got an access token for the service account");
+            token.token().map(String::from)
+        }
+        Err(err) => {
+            report.fail(format!("Failed to get an access token: {}", err), fix);
+            None
+        }
+    }
+}
+
+async fn check_refresh_token(
+    report: &mut Report,
+    client: &HttpClient,
+    secret: &Secret,
+    refresh_token: &str,
+) -> Option<String> {
+    let body = [
+        ("client_id", secret.client_id.as_str()),
+        ("client_secret", secret.client_secret.as_str()),
+        ("refresh_token", refresh_token),
+        ("grant_type", "refresh_token"),
+    ]
+    .iter()
+    .map(|(name, value)| format!("{}={}", name, utf8_percent_encode(value, NON_ALPHANUMERIC)))
+    .collect::<Vec<_>>()
+    .join("&");
+
+    let request = hyper::Request::post(TOKEN_URL)
+        .header(
+            hyper::header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded",
+        )
+        .body(hyper::Body::from(body))
+        .ok()?;
+
+    let response = match send(client, request).await {
+        Ok(response) => response,
+        Err(err) => {
+            report.fail(format!("Failed to refresh the token: {}", err), NETWORK_FIX);
+            return None;
+        }
+    };
+
+    let error = response["error"].as_str().unwrap_or_default();
+    let description = response["error_description"]
+        .as_str()
+        .unwrap_or(error)
+        .to_string();
+
+    match error {
+        "" => {}
+        "invalid_grant" => {
+            report.fail(
+                format!(
+                    "The refresh token was revoked or has expired: {}",
+                    description
+                ),
+                ADD_ACCOUNT_FIX,
+            );
+            return None;
+        }
+        "invalid_client" | "unauthorized_client" => {
+            report.fail(
+                format!("The client was rejected: {}", description),
+                "Check that the client still exists in the Google Cloud console, then add the account again",
+            );
+            return None;
+        }
+        _ => {
+            report.fail(
+                format!("Failed to refresh the token: {}", description),
+                ADD_ACCOUNT_FIX,
+            );
+            return None;
+        }
+    }
+
+    let expires_in = response["expires_in"].as_i64().unwrap_or_default();
+    report.ok(format!(
+        "The refresh token is valid, access tokens last {} minutes",
+        expires_in / 60
+    ));
+
+    // Only sent for clients whose consent screen is in testing mode
+    if let Some(seconds) = response["refresh_token_expires_in"].as_i64() {
+        report.warn(
+            format!("The refresh token expires in {} days", seconds / 86400),
+            "Publish the app on the OAuth consent screen in the Google Cloud console, then add the account again",
+        );
+    }
+
+    let scopes = response["scope"].as_str().unwrap_or_default();
+    if scopes.split_whitespace().any(|scope| scope == FULL_SCOPE) {
+        report.ok("The token has access to all Drive files");
+    } else {
+        report.fail(
+            format!("The token doesn't have the Drive scope, it has: {}", scopes),
+            "Add the account again and allow access to all Drive files when asked",
+        );
+    }
+
+    response["access_token"].as_str().map(String::from)
+}
+
+async fn check_api(report: &mut Report, client: &HttpClient, access_token: &str) {
+    let request = match hyper::Request::get(ABOUT_URL)
+        .header(
+            hyper::header::AUTHORIZATION,
+            format!("Bearer {}", access_token),
+        )
+        .body(hyper::Body::empty())
+    {
+        Ok(request) => request,
+        Err(_) => return,
+    };
+
+    api_stats::record("GET www.googleapis.com");
+    let response = match send(client, request).await {
+        Ok(response) => response,
+        Err(err) => {
+            report.fail(
+                format!("Failed to call the Drive API: {}", err),
+                NETWORK_FIX,
+            );
+            return;
+        }
+    };
+
+    if let Some(email) = response["user"]["emailAddress"].as_str() {
+        report.ok(format!("The Drive API works, signed in as {}", email));
+        return;
+    }
+
+    let message = response["error"]["message"]
+        .as_str()
+        .unwrap_or("unknown error")
+        .to_string();
+    let disabled = response["error"]["errors"]
+        .as_array()
+        .map(|errors| {
+            errors
+                .iter()
+                .any(|err| err["reason"] == "accessNotConfigured")
+        })
+        .unwrap_or(false);
+
+    if disabled {
+        report.fail(
+            "The Drive API isn't enabled for the project of the client",
+            "Enable the Google Drive API in the Google Cloud console, in the project the client belongs to",
+        );
+    } else {
+        report.fail(
+            format!("The Drive API returned an error: {}", message),
+            ADD_ACCOUNT_FIX,
+        );
+    }
+}
+
+// Google answers errors with json too, so the body is returned for any status
+async fn send(
+    client: &HttpClient,
+    request: hyper::Request<hyper::Body>,
+) -> Result<serde_json::Value, String> {
+    let response = tokio::time::timeout(REQUEST_TIMEOUT, client.request(request))
+        .await
+        .map_err(|_| String::from("timed out"))?
+        .map_err(|err| err.to_string())?;
+
+    let body = hyper::body::to_bytes(response.into_body())
+        .await
+        .map_err(|err| err.to_string())?;
+
+    serde_json::from_slice(&body).map_err(|err| format!("unexpected response: {}", err))
+}
+
+#[derive(Debug)]
+pub enum Error {
+    ChecksFailed(usize),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::ChecksFailed(count) => write!(f, "{} check(s) failed", count),
+        }
+    }
+}
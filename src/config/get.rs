@@ -0,0 +1,36 @@
+use crate::config::settings;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+pub struct Config {
+    pub key: String,
+}
+
+// Prints the value that is in effect, nothing for keys that aren't set
+pub fn get(config: Config) -> Result<(), Error> {
+    if !settings::is_known_key(&config.key) {
+        return Err(Error::UnknownKey(config.key));
+    }
+
+    if let Some((value, _)) = settings::layers().lookup(&config.key) {
+        println!("{}", value);
+    }
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum Error {
+    UnknownKey(String),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::UnknownKey(key) => write!(f, "Unknown key '{}'", key),
+        }
+    }
+}
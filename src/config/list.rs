@@ -0,0 +1,48 @@
+use crate::common::table;
+use crate::common::table::Table;
+use crate::config::settings;
+use std::collections::BTreeSet;
+use std::io;
+
+pub struct Config {
+    pub skip_header: bool,
+    pub field_separator: String,
+}
+
+// Shows every key with the value in effect and where it comes from. Keymap
+// keys are only shown when they are changed
+pub fn list(config: Config) {
+    let layers = settings::layers();
+
+    let keymap_keys: BTreeSet<String> = [&layers.file, &layers.env, &layers.flags]
+        .iter()
+        .flat_map(|layer| layer.keymap.keys())
+        .map(|name| format!("keymap.{}", name))
+        .collect();
+
+    let keys = settings::KEYS
+        .iter()
+        .map(|key| key.to_string())
+        .chain(keymap_keys);
+
+    let values: Vec<[String; 3]> = keys
+        .map(|key| match layers.lookup(&key) {
+            Some((value, source)) => [key, value, source.to_string()],
+            None => [key, String::new(), String::new()],
+        })
+        .collect();
+
+    let table = Table {
+        header: ["Key", "Value", "Source"],
+        values,
+    };
+
+    let _ = table::write(
+        io::stdout(),
+        table,
+        &table::DisplayConfig {
+            skip_header: config.skip_header,
+            separator: config.field_separator,
+        },
+    );
+}
@@ -0,0 +1,65 @@
+use crate::config::settings;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+pub struct Config {
+    pub key: String,
+    // An empty value removes the key from the file
+    pub value: String,
+}
+
+// Only changes config.toml, values from the environment and flags still take
+// precedence over it
+pub fn set(config: Config) -> Result<(), Error> {
+    if !settings::is_known_key(&config.key) {
+        return Err(Error::UnknownKey(config.key));
+    }
+
+    let mut table = settings::read_table().map_err(Error::Settings)?;
+
+    let (table_key, section) = match config.key.split_once('.') {
+        Some((section, name)) => (name.to_string(), Some(section.to_string())),
+        None => (config.key.clone(), None),
+    };
+
+    let target = match &section {
+        Some(section) => table
+            .entry(section.clone())
+            .or_insert_with(|| toml::Value::Table(toml::value::Table::new()))
+            .as_table_mut()
+            .ok_or_else(|| Error::NotATable(section.clone()))?,
+        None => &mut table,
+    };
+
+    if config.value.is_empty() {
+        target.remove(&table_key);
+    } else {
+        let value = settings::parse_value(&config.key, &config.value)
+            .map_err(|err| Error::InvalidValue(config.key.clone(), err))?;
+        target.insert(table_key, value);
+    }
+
+    settings::write_table(table).map_err(Error::Settings)
+}
+
+#[derive(Debug)]
+pub enum Error {
+    UnknownKey(String),
+    InvalidValue(String, String),
+    NotATable(String),
+    Settings(settings::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::UnknownKey(key) => write!(f, "Unknown key '{}'", key),
+            Error::InvalidValue(key, err) => write!(f, "Invalid value for '{}': {}", key, err),
+            Error::NotATable(section) => write!(f, "'{}' in the config is not a table", section),
+            Error::Settings(err) => write!(f, "{}", err),
+        }
+    }
+}
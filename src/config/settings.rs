@@ -0,0 +1,644 @@
+use crate::app_config;
+use crate::app_config::AppConfig;
+use crate::app_config::HttpConfig;
+use crate::app_config::IpFamily;
+use crate::app_config::NumberFormat;
+use crate::app_config::SizeUnit;
+use crate::common::alerts::AlertsConfig;
+use crate::common::delegate;
+use crate::common::delegate::BackoffConfig;
+use crate::common::delegate::ChunkSize;
+use crate::common::drive_url;
+use crate::navigate::keymap::Action;
+use crate::navigate::keymap::Keymap;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+const CONFIG_FILE_NAME: &str = "config.toml";
+const ENV_PREFIX: &str = "GDRIVE_";
+const KEYMAP_PREFIX: &str = "keymap.";
+const LEGACY_HTTP_FILE_NAME: &str = "http.json";
+const LEGACY_DISPLAY_FILE_NAME: &str = "display.json";
+
+// Keys that can be set in the file, the environment and with `gdrive config set`.
+// The keys of the keymap are set as keymap.<action>, they can't be set in the environment
+pub const KEYS: [&str; 17] = [
+    "account",
+    "concurrency",
+    "chunk-size",
+    "colors",
+    "default-parent",
+    "max-retries",
+    "max-retry-time",
+    "retry-jitter",
+    "http.connect-timeout",
+    "http.read-timeout",
+    "http.keep-alive",
+    "http.http1-only",
+    "http.ip-family",
+    "display.size-unit",
+    "display.decimals",
+    "display.thousands-separator",
+    "display.decimal-separator",
+];
+
+const DEFAULT_CONCURRENCY: usize = 4;
+
+static LAYERS: OnceLock<Layers> = OnceLock::new();
+
+// Settings shared by all accounts, i.e.
+//
+// account = "work"
+// chunk-size = 64
+// colors = false
+//
+// [keymap]
+// download = ["d", "Ctrl-d"]
+//
+// [http]
+// read-timeout = 120
+//
+// [alerts]
+// ntfy = "https://ntfy.sh/my-backups"
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Settings {
+    // Used instead of the account selected with `gdrive account switch`
+    pub account: Option<String>,
+    // Number of files transferred at the same time by commands that do several at once
+    pub concurrency: Option<usize>,
+    // Upload chunk size in MB
+    pub chunk_size: Option<u64>,
+    // Colors in `gdrive navigate`
+    pub colors: Option<bool>,
+    // Folder that files are uploaded to and folders are created in when no parent is given
+    pub default_parent: Option<String>,
+    // Retries of a failed request before giving up, see BackoffConfig
+    pub max_retries: Option<u32>,
+    // Seconds of retrying a request before giving up, 0 for no limit
+    pub max_retry_time: Option<u64>,
+    // Random fraction of the wait between retries, from 0 to 1
+    pub retry_jitter: Option<f64>,
+    // Keys by action name, they replace the default keys of the action
+    pub keymap: BTreeMap<String, Vec<String>>,
+    // Tuning of the http client, the http flags of the command line are its top layer
+    pub http: HttpSettings,
+    // How sizes and counts are shown
+    pub display: DisplaySettings,
+    // Where backup and sync failures are sent, see alerts.rs. Only read from the file
+    pub alerts: Option<AlertsConfig>,
+}
+
+impl Settings {
+    // Values set in `overrides` take precedence
+    pub fn merge(self, overrides: Settings) -> Settings {
+        let mut keymap = self.keymap;
+        keymap.extend(overrides.keymap);
+
+        Settings {
+            account: overrides.account.or(self.account),
+            concurrency: overrides.concurrency.or(self.concurrency),
+            chunk_size: overrides.chunk_size.or(self.chunk_size),
+            colors: overrides.colors.or(self.colors),
+            default_parent: overrides.default_parent.or(self.default_parent),
+            max_retries: overrides.max_retries.or(self.max_retries),
+            max_retry_time: overrides.max_retry_time.or(self.max_retry_time),
+            retry_jitter: overrides.retry_jitter.or(self.retry_jitter),
+            keymap,
+            http: self.http.merge(overrides.http),
+            display: self.display.merge(overrides.display),
+            alerts: overrides.alerts.or(self.alerts),
+        }
+    }
+
+    // Used by every http client, nothing is set by default
+    pub fn http_config(&self) -> HttpConfig {
+        HttpConfig {
+            connect_timeout: self.http.connect_timeout,
+            read_timeout: self.http.read_timeout,
+            keep_alive: self.http.keep_alive,
+            http1_only: self.http.http1_only.unwrap_or(false),
+            ip_family: self.http.ip_family,
+        }
+    }
+
+    pub fn number_format(&self) -> NumberFormat {
+        let default = NumberFormat::default();
+
+        NumberFormat {
+            size_unit: self.display.size_unit.unwrap_or(default.size_unit),
+            decimals: self.display.decimals.or(default.decimals),
+            thousands_separator: self
+                .display
+                .thousands_separator
+                .clone()
+                .unwrap_or(default.thousands_separator),
+            decimal_separator: self
+                .display
+                .decimal_separator
+                .clone()
+                .unwrap_or(default.decimal_separator),
+        }
+    }
+
+    pub fn concurrency(&self) -> usize {
+        self.concurrency.unwrap_or(DEFAULT_CONCURRENCY)
+    }
+
+    pub fn chunk_size(&self) -> ChunkSize {
+        self.chunk_size
+            .and_then(|mb| mb.to_string().parse().ok())
+            .unwrap_or_default()
+    }
+
+    pub fn colors(&self) -> bool {
+        self.colors.unwrap_or(true)
+    }
+
+    // The parent can be given as a url in the file
+    pub fn default_parents(&self) -> Option<Vec<String>> {
+        self.default_parent
+            .as_deref()
+            .and_then(|id| drive_url::parse_id(id).ok())
+            .map(|id| vec![id])
+    }
+
+    // Used by everything that retries, the TUI included
+    pub fn backoff(&self) -> BackoffConfig {
+        let default = BackoffConfig::default();
+
+        BackoffConfig {
+            max_retries: self.max_retries.unwrap_or(default.max_retries),
+            max_elapsed: match self.max_retry_time {
+                Some(0) => None,
+                Some(seconds) => Some(Duration::from_secs(seconds)),
+                None => default.max_elapsed,
+            },
+            jitter: self
+                .retry_jitter
+                .map(|jitter| jitter as f32)
+                .unwrap_or(default.jitter),
+            ..default
+        }
+    }
+
+    // The keys are checked when the settings are loaded
+    pub fn keymap(&self) -> Keymap {
+        Keymap::with_overrides(&self.keymap).unwrap_or_default()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.concurrency == Some(0) {
+            return Err(String::from("concurrency must be at least 1"));
+        }
+
+        if let Some(mb) = self.chunk_size {
+            mb.to_string().parse::<ChunkSize>()?;
+        }
+
+        if let Some(id) = &self.default_parent {
+            drive_url::parse_id(id)?;
+        }
+
+        if self
+            .retry_jitter
+            .is_some_and(|jitter| !(0.0..=1.0).contains(&jitter))
+        {
+            return Err(String::from("retry-jitter must be between 0 and 1"));
+        }
+
+        Keymap::with_overrides(&self.keymap)?;
+
+        if let Some(alerts) = &self.alerts {
+            alerts.validate()?;
+        }
+
+        Ok(())
+    }
+
+    // The value of a key as it is shown by `gdrive config get`
+    pub fn value(&self, key: &str) -> Option<String> {
+        if let Some(name) = key.strip_prefix(KEYMAP_PREFIX) {
+            return self.keymap.get(name).map(|keys| keys.join(" "));
+        }
+
+        match key {
+            "account" => self.account.clone(),
+            "concurrency" => self.concurrency.map(|n| n.to_string()),
+            "chunk-size" => self.chunk_size.map(|mb| mb.to_string()),
+            "colors" => self.colors.map(|colors| colors.to_string()),
+            "default-parent" => self.default_parent.clone(),
+            "max-retries" => self.max_retries.map(|n| n.to_string()),
+            "max-retry-time" => self.max_retry_time.map(|seconds| seconds.to_string()),
+            "retry-jitter" => self.retry_jitter.map(|jitter| jitter.to_string()),
+            "http.connect-timeout" => self.http.connect_timeout.map(|s| s.to_string()),
+            "http.read-timeout" => self.http.read_timeout.map(|s| s.to_string()),
+            "http.keep-alive" => self.http.keep_alive.map(|s| s.to_string()),
+            "http.http1-only" => self.http.http1_only.map(|only| only.to_string()),
+            "http.ip-family" => self.http.ip_family.map(|family| family.to_string()),
+            "display.size-unit" => self.display.size_unit.map(|unit| unit.to_string()),
+            "display.decimals" => self.display.decimals.map(|n| n.to_string()),
+            "display.thousands-separator" => self.display.thousands_separator.clone(),
+            "display.decimal-separator" => self.display.decimal_separator.clone(),
+            _ => None,
+        }
+    }
+}
+
+// Tuning for networks where long transfers get reset, timeouts are in seconds.
+// The aliases are the names used in http.json
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct HttpSettings {
+    #[serde(alias = "connect_timeout")]
+    pub connect_timeout: Option<u64>,
+    #[serde(alias = "read_timeout")]
+    pub read_timeout: Option<u64>,
+    #[serde(alias = "keep_alive")]
+    pub keep_alive: Option<u64>,
+    #[serde(alias = "http1_only")]
+    pub http1_only: Option<bool>,
+    #[serde(alias = "ip_family")]
+    pub ip_family: Option<IpFamily>,
+}
+
+impl HttpSettings {
+    fn merge(self, overrides: HttpSettings) -> HttpSettings {
+        HttpSettings {
+            connect_timeout: overrides.connect_timeout.or(self.connect_timeout),
+            read_timeout: overrides.read_timeout.or(self.read_timeout),
+            keep_alive: overrides.keep_alive.or(self.keep_alive),
+            http1_only: overrides.http1_only.or(self.http1_only),
+            ip_family: overrides.ip_family.or(self.ip_family),
+        }
+    }
+}
+
+// How sizes and counts are shown, see NumberFormat. The aliases are the names
+// used in display.json
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct DisplaySettings {
+    #[serde(alias = "size_unit")]
+    pub size_unit: Option<SizeUnit>,
+    pub decimals: Option<usize>,
+    #[serde(alias = "thousands_separator")]
+    pub thousands_separator: Option<String>,
+    #[serde(alias = "decimal_separator")]
+    pub decimal_separator: Option<String>,
+}
+
+impl DisplaySettings {
+    fn merge(self, overrides: DisplaySettings) -> DisplaySettings {
+        DisplaySettings {
+            size_unit: overrides.size_unit.or(self.size_unit),
+            decimals: overrides.decimals.or(self.decimals),
+            thousands_separator: overrides.thousands_separator.or(self.thousands_separator),
+            decimal_separator: overrides.decimal_separator.or(self.decimal_separator),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    File,
+    Env,
+    Flag,
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::File => write!(f, "file"),
+            Source::Env => write!(f, "env"),
+            Source::Flag => write!(f, "flag"),
+        }
+    }
+}
+
+// Each layer takes precedence over the ones before it:
+// defaults < config.toml < GDRIVE_* environment variables < flags
+#[derive(Debug, Default)]
+pub struct Layers {
+    pub file: Settings,
+    pub env: Settings,
+    pub flags: Settings,
+    pub merged: Settings,
+}
+
+impl Layers {
+    // The value of a key and the layer it comes from
+    pub fn lookup(&self, key: &str) -> Option<(String, Source)> {
+        let layers = [
+            (&self.flags, Source::Flag),
+            (&self.env, Source::Env),
+            (&self.file, Source::File),
+        ];
+
+        layers
+            .into_iter()
+            .find_map(|(settings, source)| settings.value(key).map(|value| (value, source)))
+            .or_else(|| default_value(key).map(|value| (value, Source::Default)))
+    }
+}
+
+// Loads the layers, called once at startup with the flags of the command.
+// When loading fails only the flags are used, for commands that can run
+// without the settings like `gdrive config doctor`
+pub fn init(flags: Settings) -> Result<(), Error> {
+    match load(flags.clone()) {
+        Ok(layers) => {
+            let _ = LAYERS.set(layers);
+            Ok(())
+        }
+        Err(err) => {
+            let _ = LAYERS.set(Layers {
+                merged: flags.clone(),
+                flags,
+                ..Layers::default()
+            });
+            Err(err)
+        }
+    }
+}
+
+// Loads the settings again to report what is wrong with them
+pub fn check() -> Result<(), Error> {
+    load(layers().flags.clone()).map(|_| ())
+}
+
+pub fn layers() -> &'static Layers {
+    LAYERS.get_or_init(|| {
+        load(Settings::default()).unwrap_or_else(|err| {
+            eprintln!("Warning: {}", err);
+            Layers::default()
+        })
+    })
+}
+
+pub fn current() -> &'static Settings {
+    &layers().merged
+}
+
+fn load(flags: Settings) -> Result<Layers, Error> {
+    let file = load_file()?;
+    let env = load_env()?;
+    flags.validate().map_err(Error::InvalidFlags)?;
+
+    let merged = file.clone().merge(env.clone()).merge(flags.clone());
+
+    Ok(Layers {
+        file,
+        env,
+        flags,
+        merged,
+    })
+}
+
+fn load_file() -> Result<Settings, Error> {
+    let path = config_path()?;
+    let table = read_table()?;
+    let settings: Settings = toml::Value::Table(table)
+        .try_into()
+        .map_err(|err| Error::Parse(path.clone(), err))?;
+    settings
+        .validate()
+        .map_err(|err| Error::InvalidFile(path, err))?;
+
+    // http.json and display.json are from before these settings moved to
+    // config.toml, they are used where config.toml doesn't set a value
+    let http = read_legacy::<HttpSettings>(LEGACY_HTTP_FILE_NAME)?;
+    let display = read_legacy::<DisplaySettings>(LEGACY_DISPLAY_FILE_NAME)?;
+    Ok(Settings {
+        http: http.merge(settings.http),
+        display: display.merge(settings.display),
+        ..settings
+    })
+}
+
+fn read_legacy<T: DeserializeOwned + Default>(name: &str) -> Result<T, Error> {
+    let path = AppConfig::default_base_path()
+        .map_err(Error::AppConfig)?
+        .join(name);
+    if !path.exists() {
+        return Ok(T::default());
+    }
+
+    let content = fs::read_to_string(&path).map_err(|err| Error::Read(path.clone(), err))?;
+    serde_json::from_str(&content).map_err(|err| Error::ParseLegacy(path, err))
+}
+
+fn load_env() -> Result<Settings, Error> {
+    let mut table = toml::value::Table::new();
+
+    for key in KEYS {
+        let name = env_name(key);
+        if let Ok(value) = env::var(&name) {
+            let value =
+                parse_value(key, &value).map_err(|err| Error::InvalidEnv(name.clone(), err))?;
+            match key.split_once('.') {
+                Some((section, key)) => {
+                    if let toml::Value::Table(section) = table
+                        .entry(section.to_string())
+                        .or_insert_with(|| toml::Value::Table(toml::value::Table::new()))
+                    {
+                        section.insert(key.to_string(), value);
+                    }
+                }
+                None => {
+                    table.insert(key.to_string(), value);
+                }
+            }
+        }
+    }
+
+    let settings: Settings = toml::Value::Table(table)
+        .try_into()
+        .map_err(|err| Error::InvalidEnv(String::from(ENV_PREFIX), err.to_string()))?;
+    settings
+        .validate()
+        .map_err(|err| Error::InvalidEnv(String::from(ENV_PREFIX), err))?;
+    Ok(settings)
+}
+
+// i.e. chunk-size -> GDRIVE_CHUNK_SIZE and http.read-timeout -> GDRIVE_HTTP_READ_TIMEOUT
+pub fn env_name(key: &str) -> String {
+    format!(
+        "{}{}",
+        ENV_PREFIX,
+        key.to_uppercase().replace(['-', '.'], "_")
+    )
+}
+
+// Values are given as strings on the command line and in the environment
+pub fn parse_value(key: &str, value: &str) -> Result<toml::Value, String> {
+    if key.starts_with(KEYMAP_PREFIX) {
+        let keys = value
+            .split_whitespace()
+            .map(|key| toml::Value::String(key.to_string()))
+            .collect();
+        return Ok(toml::Value::Array(keys));
+    }
+
+    match key {
+        "account" | "display.thousands-separator" | "display.decimal-separator" => {
+            Ok(toml::Value::String(value.to_string()))
+        }
+        "default-parent" => drive_url::parse_id(value).map(toml::Value::String),
+        "concurrency"
+        | "chunk-size"
+        | "max-retries"
+        | "max-retry-time"
+        | "http.connect-timeout"
+        | "http.read-timeout"
+        | "http.keep-alive"
+        | "display.decimals" => value
+            .parse::<i64>()
+            .map(toml::Value::Integer)
+            .map_err(|_| format!("'{}' is not a number", value)),
+        "http.ip-family" => match value {
+            "ipv4" | "ipv6" => Ok(toml::Value::String(value.to_string())),
+            _ => Err(format!("'{}' is not ipv4 or ipv6", value)),
+        },
+        "display.size-unit" => match value {
+            "binary" | "decimal" => Ok(toml::Value::String(value.to_string())),
+            _ => Err(format!("'{}' is not binary or decimal", value)),
+        },
+        "retry-jitter" => value
+            .parse::<f64>()
+            .map(toml::Value::Float)
+            .map_err(|_| format!("'{}' is not a number", value)),
+        "colors" | "http.http1-only" => value
+            .parse::<bool>()
+            .map(toml::Value::Boolean)
+            .map_err(|_| format!("'{}' is not true or false", value)),
+        _ => Err(format!("Unknown key '{}'", key)),
+    }
+}
+
+pub fn default_value(key: &str) -> Option<String> {
+    if let Some(name) = key.strip_prefix(KEYMAP_PREFIX) {
+        let action = Action::from_name(name)?;
+        let keys = Keymap::default()
+            .keys_for(action)
+            .iter()
+            .map(|key| key.to_string())
+            .collect::<Vec<_>>();
+        return Some(keys.join(" "));
+    }
+
+    match key {
+        "account" => AppConfig::load_account_config()
+            .ok()
+            .map(|account_config| account_config.current),
+        "concurrency" => Some(DEFAULT_CONCURRENCY.to_string()),
+        "chunk-size" => Some(ChunkSize::default().to_string()),
+        "colors" => Some(true.to_string()),
+        "max-retries" => Some(delegate::DEFAULT_MAX_RETRIES.to_string()),
+        "max-retry-time" => Some(delegate::DEFAULT_MAX_RETRY_TIME.as_secs().to_string()),
+        "retry-jitter" => Some(delegate::DEFAULT_RETRY_JITTER.to_string()),
+        "http.http1-only" => Some(false.to_string()),
+        "display.size-unit" => Some(NumberFormat::default().size_unit.to_string()),
+        "display.thousands-separator" => Some(NumberFormat::default().thousands_separator),
+        "display.decimal-separator" => Some(NumberFormat::default().decimal_separator),
+        _ => None,
+    }
+}
+
+pub fn is_known_key(key: &str) -> bool {
+    match key.strip_prefix(KEYMAP_PREFIX) {
+        Some(name) => Action::from_name(name).is_some(),
+        None => KEYS.contains(&key),
+    }
+}
+
+// The config file as a table, so `gdrive config set` keeps what it doesn't change
+pub fn read_table() -> Result<toml::value::Table, Error> {
+    let path = config_path()?;
+    if !path.exists() {
+        return Ok(toml::value::Table::new());
+    }
+
+    let content = fs::read_to_string(&path).map_err(|err| Error::Read(path.clone(), err))?;
+    toml::from_str(&content).map_err(|err| Error::Parse(path, err))
+}
+
+pub fn write_table(table: toml::value::Table) -> Result<(), Error> {
+    let path = config_path()?;
+
+    let settings: Settings = toml::Value::Table(table.clone())
+        .try_into()
+        .map_err(|err| Error::Parse(path.clone(), err))?;
+    settings
+        .validate()
+        .map_err(|err| Error::InvalidFile(path.clone(), err))?;
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| Error::Write(dir.to_path_buf(), err))?;
+    }
+
+    // Serialized as a value, which puts plain keys before the keymap table
+    let content = toml::to_string(&toml::Value::Table(table)).map_err(Error::Serialize)?;
+    fs::write(&path, content).map_err(|err| Error::Write(path, err))
+}
+
+pub fn config_path() -> Result<PathBuf, Error> {
+    let base_path = AppConfig::default_base_path().map_err(Error::AppConfig)?;
+    Ok(base_path.join(CONFIG_FILE_NAME))
+}
+
+#[derive(Debug)]
+pub enum Error {
+    AppConfig(app_config::Error),
+    Read(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    ParseLegacy(PathBuf, serde_json::Error),
+    InvalidFile(PathBuf, String),
+    InvalidEnv(String, String),
+    InvalidFlags(String),
+    Serialize(toml::ser::Error),
+    Write(PathBuf, io::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::AppConfig(err) => write!(f, "{}", err),
+            Error::Read(path, err) => {
+                write!(f, "Failed to read config '{}': {}", path.display(), err)
+            }
+            Error::Parse(path, err) => {
+                write!(f, "Failed to parse config '{}': {}", path.display(), err)
+            }
+            Error::ParseLegacy(path, err) => write!(
+                f,
+                "Failed to parse '{}': {}, its settings can be moved to config.toml",
+                path.display(),
+                err
+            ),
+            Error::InvalidFile(path, err) => {
+                write!(f, "Invalid config '{}': {}", path.display(), err)
+            }
+            Error::InvalidEnv(name, err) => write!(f, "Invalid {}: {}", name, err),
+            Error::InvalidFlags(err) => write!(f, "{}", err),
+            Error::Serialize(err) => write!(f, "Failed to serialize config: {}", err),
+            Error::Write(path, err) => {
+                write!(f, "Failed to write config '{}': {}", path.display(), err)
+            }
+        }
+    }
+}
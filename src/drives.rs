@@ -1,3 +1,12 @@
+pub mod create;
+pub mod delete;
+pub mod hide;
 pub mod list;
+pub mod members;
+pub mod rename;
 
+pub use create::create;
+pub use delete::delete;
+pub use hide::hide;
 pub use list::list;
+pub use rename::rename;
@@ -0,0 +1,88 @@
+use crate::common::delegate::UploadDelegate;
+use crate::common::delegate::UploadDelegateConfig;
+use crate::common::hub_helper;
+use crate::hub::Hub;
+use std::error;
+use std::fmt;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+pub struct Config {
+    pub name: String,
+    pub print_only_id: bool,
+}
+
+pub async fn create(config: Config) -> Result<(), Error> {
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+    let delegate_config = UploadDelegateConfig::default();
+
+    let drive = create_drive(&hub, delegate_config, &config.name)
+        .await
+        .map_err(Error::CreateDrive)?;
+
+    if config.print_only_id {
+        print!("{}", drive.id.unwrap_or_default())
+    } else {
+        println!(
+            "Created drive '{}' with id: {}",
+            config.name,
+            drive.id.unwrap_or_default()
+        );
+    }
+
+    Ok(())
+}
+
+pub async fn create_drive(
+    hub: &Hub,
+    delegate_config: UploadDelegateConfig,
+    name: &str,
+) -> Result<google_drive3::api::Drive, google_drive3::Error> {
+    let mut delegate = UploadDelegate::new(delegate_config);
+
+    let drive = google_drive3::api::Drive {
+        name: Some(name.to_string()),
+        ..google_drive3::api::Drive::default()
+    };
+
+    // Drive uses the request id to not create the drive twice when a request
+    // is retried, so it has to be unique per drive
+    let request_id = format!(
+        "{:x}",
+        md5::compute(format!(
+            "{}\n{}",
+            name,
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|time| time.as_nanos())
+                .unwrap_or_default()
+        ))
+    );
+
+    let (_, drive) = hub
+        .drives()
+        .create(drive, &request_id)
+        .add_scope(google_drive3::api::Scope::Full)
+        .delegate(&mut delegate)
+        .doit()
+        .await?;
+
+    Ok(drive)
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    CreateDrive(google_drive3::Error),
+}
+
+impl error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::CreateDrive(err) => write!(f, "Failed to create drive: {}", err),
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use crate::common::delegate::UploadDelegate;
+use crate::common::delegate::UploadDelegateConfig;
+use crate::common::hub_helper;
+use std::error;
+use std::fmt;
+
+pub struct Config {
+    pub drive_id: String,
+    // Act as a Workspace admin, for drives the user is not a member of
+    pub use_domain_admin_access: bool,
+}
+
+// Drive only deletes shared drives that are empty, including their trash
+pub async fn delete(config: Config) -> Result<(), Error> {
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+    let mut delegate = UploadDelegate::new(UploadDelegateConfig::default());
+
+    hub.drives()
+        .delete(&config.drive_id)
+        .use_domain_admin_access(config.use_domain_admin_access)
+        .add_scope(google_drive3::api::Scope::Full)
+        .delegate(&mut delegate)
+        .doit()
+        .await
+        .map_err(Error::DeleteDrive)?;
+
+    println!("Deleted drive {}", config.drive_id);
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    DeleteDrive(google_drive3::Error),
+}
+
+impl error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::DeleteDrive(err) => write!(
+                f,
+                "Failed to delete drive, only empty drives can be deleted: {}",
+                err
+            ),
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use crate::common::delegate::UploadDelegate;
+use crate::common::delegate::UploadDelegateConfig;
+use crate::common::hub_helper;
+use std::error;
+use std::fmt;
+
+pub struct Config {
+    pub drive_id: String,
+    pub hidden: bool,
+}
+
+// Hidden drives are left out of the default view of the user, other members
+// still see them
+pub async fn hide(config: Config) -> Result<(), Error> {
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+    let mut delegate = UploadDelegate::new(UploadDelegateConfig::default());
+
+    let (_, drive) = if config.hidden {
+        hub.drives()
+            .hide(&config.drive_id)
+            .add_scope(google_drive3::api::Scope::Full)
+            .delegate(&mut delegate)
+            .doit()
+            .await
+    } else {
+        hub.drives()
+            .unhide(&config.drive_id)
+            .add_scope(google_drive3::api::Scope::Full)
+            .delegate(&mut delegate)
+            .doit()
+            .await
+    }
+    .map_err(Error::Hide)?;
+
+    let action = if config.hidden { "Hid" } else { "Unhid" };
+    println!("{} drive '{}'", action, drive.name.unwrap_or_default());
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    Hide(google_drive3::Error),
+}
+
+impl error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::Hide(err) => write!(f, "Failed to change drive visibility: {}", err),
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use crate::common::api_stats::StatsDelegate;
+use crate::common::delegate::UploadDelegate;
+use crate::common::delegate::UploadDelegateConfig;
+use crate::common::hub_helper;
+use crate::common::permission;
+use crate::common::table;
+use crate::common::table::Table;
+use crate::hub::Hub;
+use std::error;
+use std::fmt;
+use std::io;
+
+// Members of a shared drive are the permissions of the drive, the drive id
+// works as a file id for the permissions api
+
+pub struct ListConfig {
+    pub drive_id: String,
+    pub use_domain_admin_access: bool,
+    pub skip_header: bool,
+    pub field_separator: String,
+}
+
+pub async fn list(config: ListConfig) -> Result<(), Error> {
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+
+    let members = list_members(&hub, &config.drive_id, config.use_domain_admin_access)
+        .await
+        .map_err(Error::ListMembers)?;
+
+    let mut values: Vec<[String; 5]> = vec![];
+
+    for member in members {
+        values.push([
+            member.id.unwrap_or_default(),
+            member.type_.unwrap_or_default(),
+            member.role.unwrap_or_default(),
+            member.email_address.unwrap_or_default(),
+            member.display_name.unwrap_or_default(),
+        ])
+    }
+
+    let table = Table {
+        header: ["Id", "Type", "Role", "Email", "Name"],
+        values,
+    };
+
+    let _ = table::write(
+        io::stdout(),
+        table,
+        &table::DisplayConfig {
+            skip_header: config.skip_header,
+            separator: config.field_separator,
+        },
+    );
+
+    Ok(())
+}
+
+pub struct AddConfig {
+    pub drive_id: String,
+    pub email: String,
+    pub role: permission::Role,
+    pub type_: permission::Type,
+    pub notify: bool,
+    pub use_domain_admin_access: bool,
+}
+
+pub async fn add(config: AddConfig) -> Result<(), Error> {
+    err_if_not_member_type(&config.type_)?;
+
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+    let mut delegate = UploadDelegate::new(UploadDelegateConfig::default());
+
+    let member = google_drive3::api::Permission {
+        role: Some(config.role.to_string()),
+        type_: Some(config.type_.to_string()),
+        email_address: Some(config.email.clone()),
+        ..google_drive3::api::Permission::default()
+    };
+
+    hub.permissions()
+        .create(member, &config.drive_id)
+        .param("fields", "id")
+        .send_notification_email(config.notify)
+        .use_domain_admin_access(config.use_domain_admin_access)
+        .add_scope(google_drive3::api::Scope::Full)
+        .delegate(&mut delegate)
+        .supports_all_drives(true)
+        .doit()
+        .await
+        .map_err(Error::AddMember)?;
+
+    println!(
+        "Added {} to drive {} as {}",
+        config.email, config.drive_id, config.role
+    );
+
+    Ok(())
+}
+
+pub struct RemoveConfig {
+    pub drive_id: String,
+    pub email: String,
+    pub use_domain_admin_access: bool,
+}
+
+pub async fn remove(config: RemoveConfig) -> Result<(), Error> {
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+
+    let members = list_members(&hub, &config.drive_id, config.use_domain_admin_access)
+        .await
+        .map_err(Error::ListMembers)?;
+
+    let member = members
+        .into_iter()
+        .find(|member| {
+            member
+                .email_address
+                .as_deref()
+                .is_some_and(|email| email.eq_ignore_ascii_case(&config.email))
+        })
+        .ok_or(Error::NotAMember(config.email.clone()))?;
+
+    let mut delegate = UploadDelegate::new(UploadDelegateConfig::default());
+
+    hub.permissions()
+        .delete(&config.drive_id, &member.id.unwrap_or_default())
+        .use_domain_admin_access(config.use_domain_admin_access)
+        .add_scope(google_drive3::api::Scope::Full)
+        .delegate(&mut delegate)
+        .supports_all_drives(true)
+        .doit()
+        .await
+        .map_err(Error::RemoveMember)?;
+
+    println!("Removed {} from drive {}", config.email, config.drive_id);
+
+    Ok(())
+}
+
+pub async fn list_members(
+    hub: &Hub,
+    drive_id: &str,
+    use_domain_admin_access: bool,
+) -> Result<Vec<google_drive3::api::Permission>, google_drive3::Error> {
+    let mut members = vec![];
+    let mut page_token: Option<String> = None;
+
+    loop {
+        let mut req = hub
+            .permissions()
+            .list(drive_id)
+            .param(
+                "fields",
+                "nextPageToken,permissions(id,role,type,emailAddress,displayName)",
+            )
+            .use_domain_admin_access(use_domain_admin_access)
+            .add_scope(google_drive3::api::Scope::Full)
+            .supports_all_drives(true)
+            .page_size(100);
+
+        if let Some(token) = &page_token {
+            req = req.page_token(token);
+        }
+
+        let (_, permission_list) = req.delegate(&mut StatsDelegate).doit().await?;
+        members.extend(permission_list.permissions.unwrap_or_default());
+
+        page_token = permission_list.next_page_token;
+        if page_token.is_none() {
+            break;
+        }
+    }
+
+    Ok(members)
+}
+
+fn err_if_not_member_type(type_: &permission::Type) -> Result<(), Error> {
+    match type_ {
+        permission::Type::User | permission::Type::Group => Ok(()),
+        _ => Err(Error::InvalidMemberType(type_.clone())),
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    ListMembers(google_drive3::Error),
+    AddMember(google_drive3::Error),
+    RemoveMember(google_drive3::Error),
+    NotAMember(String),
+    InvalidMemberType(permission::Type),
+}
+
+impl error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::ListMembers(err) => write!(f, "Failed to list members: {}", err),
+            Error::AddMember(err) => write!(f, "Failed to add member: {}", err),
+            Error::RemoveMember(err) => write!(f, "Failed to remove member: {}", err),
+            Error::NotAMember(email) => write!(f, "{} is not a member of the drive", email),
+            Error::InvalidMemberType(type_) => {
+                write!(f, "Members of a drive are users or groups, not '{}'", type_)
+            }
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use crate::common::delegate::UploadDelegate;
+use crate::common::delegate::UploadDelegateConfig;
+use crate::common::hub_helper;
+use std::error;
+use std::fmt;
+
+pub struct Config {
+    pub drive_id: String,
+    pub name: String,
+    // Act as a Workspace admin, for drives the user is not a member of
+    pub use_domain_admin_access: bool,
+}
+
+pub async fn rename(config: Config) -> Result<(), Error> {
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+    let mut delegate = UploadDelegate::new(UploadDelegateConfig::default());
+
+    let drive = google_drive3::api::Drive {
+        name: Some(config.name.clone()),
+        ..google_drive3::api::Drive::default()
+    };
+
+    hub.drives()
+        .update(drive, &config.drive_id)
+        .use_domain_admin_access(config.use_domain_admin_access)
+        .add_scope(google_drive3::api::Scope::Full)
+        .delegate(&mut delegate)
+        .doit()
+        .await
+        .map_err(Error::UpdateDrive)?;
+
+    println!("Renamed drive {} to '{}'", config.drive_id, config.name);
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    UpdateDrive(google_drive3::Error),
+}
+
+impl error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::UpdateDrive(err) => write!(f, "Failed to rename drive: {}", err),
+        }
+    }
+}
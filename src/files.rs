@@ -1,27 +1,54 @@
+pub mod adopt;
+pub mod convert;
 pub mod copy;
 pub mod delete;
 pub mod download;
+pub mod download_list;
 pub mod export;
+pub mod export_all;
 pub mod generate_ids;
 pub mod import;
+pub mod index;
 pub mod info;
 pub mod list;
+pub mod meta;
 pub mod mkdir;
 pub mod mv;
+pub mod pull;
 pub mod rename;
+pub mod search;
+pub mod shortcut;
+pub mod undelete_last;
+pub mod unshare_me;
 pub mod update;
 pub mod upload;
+pub mod upload_defaults;
+pub mod upload_list;
+pub mod url;
 
+pub use adopt::adopt;
+pub use convert::convert;
 pub use copy::copy;
 pub use delete::delete;
 pub use download::download;
+pub use download_list::download_list;
 pub use export::export;
+pub use export_all::export_all;
 pub use generate_ids::generate_ids;
 pub use import::import;
+pub use index::index;
 pub use info::info;
 pub use list::list;
 pub use mkdir::mkdir;
 pub use mv::mv;
+pub use pull::pull;
 pub use rename::rename;
+pub use search::search;
+pub use shortcut::shortcut;
+pub use undelete_last::undelete_last;
+pub use unshare_me::unshare_me;
 pub use update::update;
 pub use upload::upload;
+pub use upload_defaults::upload_defaults;
+pub use upload_list::upload_list;
+pub use url::url;
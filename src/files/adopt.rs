@@ -0,0 +1,111 @@
+use crate::common::delegate::UploadDelegate;
+use crate::common::delegate::UploadDelegateConfig;
+use crate::common::drive_file;
+use crate::common::hub_helper;
+use crate::files;
+use crate::hub::Hub;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub file_id: String,
+    pub folder_id: String,
+}
+
+// Gives a file without parents, i.e. one whose folder was deleted by someone
+// else, a parent folder so it shows up in Drive again
+pub async fn adopt(config: Config) -> Result<(), Error> {
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+
+    let file = files::info::get_file(&hub, &config.file_id)
+        .await
+        .map_err(Error::GetFile)?;
+
+    err_if_has_parents(&file)?;
+
+    let folder = files::info::get_file(&hub, &config.folder_id)
+        .await
+        .map_err(Error::GetFolder)?;
+
+    err_if_not_directory(&folder)?;
+
+    println!(
+        "Adding '{}' to '{}'",
+        file.name.unwrap_or_default(),
+        folder.name.unwrap_or_default()
+    );
+
+    add_parent(&hub, &config.file_id, &config.folder_id)
+        .await
+        .map_err(Error::Adopt)?;
+
+    Ok(())
+}
+
+pub async fn add_parent(
+    hub: &Hub,
+    file_id: &str,
+    folder_id: &str,
+) -> Result<google_drive3::api::File, google_drive3::Error> {
+    let mut delegate = UploadDelegate::new(UploadDelegateConfig::default());
+
+    let (_, file) = hub
+        .files()
+        .update(google_drive3::api::File::default(), file_id)
+        .add_parents(folder_id)
+        .param("fields", "id,name,parents")
+        .add_scope(google_drive3::api::Scope::Full)
+        .delegate(&mut delegate)
+        .supports_all_drives(true)
+        .doit_without_upload()
+        .await?;
+
+    Ok(file)
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    GetFile(google_drive3::Error),
+    GetFolder(google_drive3::Error),
+    HasParents,
+    NotADirectory,
+    Adopt(google_drive3::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::GetFile(err) => write!(f, "Failed to get file: {}", err),
+            Error::GetFolder(err) => write!(f, "Failed to get folder: {}", err),
+            Error::HasParents => {
+                write!(
+                    f,
+                    "File already has a parent, use `gdrive files move` instead"
+                )
+            }
+            Error::NotADirectory => write!(f, "Parent is not a directory"),
+            Error::Adopt(err) => write!(f, "Failed to add parent: {}", err),
+        }
+    }
+}
+
+fn err_if_has_parents(file: &google_drive3::api::File) -> Result<(), Error> {
+    match &file.parents {
+        Some(parents) if !parents.is_empty() => Err(Error::HasParents),
+        _ => Ok(()),
+    }
+}
+
+fn err_if_not_directory(file: &google_drive3::api::File) -> Result<(), Error> {
+    if !drive_file::is_directory(file) {
+        Err(Error::NotADirectory)
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,231 @@
+use crate::common::api_stats::StatsDelegate;
+use crate::common::delegate::UploadDelegateConfig;
+use crate::common::drive_file;
+use crate::common::drive_file::DocType;
+use crate::common::drive_file::FileExtension;
+use crate::common::file_info::FileInfo;
+use crate::common::hub_helper;
+use crate::files;
+use crate::files::info::DisplayConfig;
+use crate::hub::Hub;
+use std::error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::io::Cursor;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub file_id: String,
+    pub to: Format,
+}
+
+// The original file is left as is, the converted file is created next to it
+pub async fn convert(config: Config) -> Result<(), Error> {
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+
+    let file = files::info::get_file(&hub, &config.file_id)
+        .await
+        .map_err(Error::GetFile)?;
+
+    let name = file.name.clone().unwrap_or_default();
+    let mime_type = file.mime_type.clone().unwrap_or_default();
+
+    let new_file = match &config.to {
+        Format::Google(doc_type) => {
+            let source_type = DocType::from_file_path(&PathBuf::from(&name))
+                .filter(|_| drive_file::is_binary(&file))
+                .ok_or(Error::NotConvertible(mime_type))?;
+
+            if &source_type != doc_type {
+                return Err(Error::WrongDocType(source_type));
+            }
+
+            println!("Converting '{}' to a Google {}", name, doc_type);
+            convert_to_google(&hub, &file, doc_type).await?
+        }
+
+        Format::File(extension) => {
+            let doc_type =
+                DocType::from_mime_type(&mime_type).ok_or(Error::NotConvertible(mime_type))?;
+
+            if !doc_type.can_export_to(extension) {
+                return Err(Error::UnsupportedExport(doc_type));
+            }
+
+            println!("Converting '{}' to {}", name, extension);
+            convert_to_file(&hub, &file, extension).await?
+        }
+    };
+
+    println!("File successfully converted");
+    let fields = files::info::prepare_fields(&new_file, &DisplayConfig::default());
+    files::info::print_fields(&fields);
+
+    Ok(())
+}
+
+// Drive converts the file when it is copied with a Google mime type
+async fn convert_to_google(
+    hub: &Hub,
+    file: &google_drive3::api::File,
+    doc_type: &DocType,
+) -> Result<google_drive3::api::File, Error> {
+    let name = file.name.clone().unwrap_or_default();
+    let stem = PathBuf::from(&name)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or(name);
+
+    let dst_file = google_drive3::api::File {
+        name: Some(stem),
+        mime_type: doc_type.mime().map(|mime| mime.to_string()),
+        parents: file.parents.clone(),
+        ..google_drive3::api::File::default()
+    };
+
+    let (_, new_file) = hub
+        .files()
+        .copy(dst_file, &file.id.clone().unwrap_or_default())
+        .param("fields", "id,name,size,createdTime,modifiedTime,md5Checksum,mimeType,parents,shared,description,webContentLink,webViewLink")
+        .add_scope(google_drive3::api::Scope::Full)
+        .supports_all_drives(true)
+        .delegate(&mut StatsDelegate)
+        .doit()
+        .await
+        .map_err(Error::Copy)?;
+
+    Ok(new_file)
+}
+
+// Drive can't copy a Google document to another format, so it is exported and
+// uploaded again. Exports are limited to 10 MB, so the content is kept in memory
+async fn convert_to_file(
+    hub: &Hub,
+    file: &google_drive3::api::File,
+    extension: &FileExtension,
+) -> Result<google_drive3::api::File, Error> {
+    let mime_type = extension
+        .get_export_mime()
+        .ok_or(Error::GetFileExtensionMime(extension.clone()))?;
+
+    let body = files::export::export_file(hub, &file.id.clone().unwrap_or_default(), &mime_type)
+        .await
+        .map_err(Error::Export)?;
+
+    let bytes = hyper::body::to_bytes(body)
+        .await
+        .map_err(Error::ReadExport)?
+        .to_vec();
+
+    let file_info = FileInfo {
+        name: format!("{}.{}", file.name.clone().unwrap_or_default(), extension),
+        mime_type,
+        parents: file.parents.clone(),
+        size: bytes.len() as u64,
+    };
+
+    files::upload::upload_file(
+        hub,
+        Cursor::new(bytes),
+        None,
+        file_info,
+        UploadDelegateConfig::default(),
+    )
+    .await
+    .map_err(Error::Upload)
+}
+
+#[derive(Debug, Clone)]
+pub enum Format {
+    Google(DocType),
+    File(FileExtension),
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gdoc" => Ok(Format::Google(DocType::Document)),
+            "gsheet" => Ok(Format::Google(DocType::Spreadsheet)),
+            "gslides" => Ok(Format::Google(DocType::Presentation)),
+            _ => FileExtension::from_str(s).map(Format::File).map_err(|_| {
+                format!(
+                    "'{}' is not a supported format, use gdoc, gsheet, gslides or a file type like docx",
+                    s
+                )
+            }),
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Format::Google(DocType::Document) => write!(f, "gdoc"),
+            Format::Google(DocType::Spreadsheet) => write!(f, "gsheet"),
+            Format::Google(DocType::Presentation) => write!(f, "gslides"),
+            Format::File(extension) => write!(f, "{}", extension),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    GetFile(google_drive3::Error),
+    NotConvertible(String),
+    WrongDocType(DocType),
+    UnsupportedExport(DocType),
+    GetFileExtensionMime(FileExtension),
+    Copy(google_drive3::Error),
+    Export(google_drive3::Error),
+    ReadExport(hyper::Error),
+    Upload(google_drive3::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::GetFile(err) => write!(f, "Failed to get file: {}", err),
+            Error::NotConvertible(mime) => {
+                write!(f, "Files of type '{}' can't be converted", mime)
+            }
+            Error::WrongDocType(doc_type) => write!(
+                f,
+                "This file can only be converted to a Google {}, use --to {}",
+                doc_type,
+                Format::Google(doc_type.clone())
+            ),
+            Error::UnsupportedExport(doc_type) => {
+                let supported_types = doc_type
+                    .supported_export_types()
+                    .iter()
+                    .map(|ext| ext.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                write!(
+                    f,
+                    "A {} can't be converted to this file type, supported file types are: {}",
+                    doc_type, supported_types
+                )
+            }
+            Error::GetFileExtensionMime(extension) => write!(
+                f,
+                "Failed to get mime type from file extension: {}",
+                extension
+            ),
+            Error::Copy(err) => write!(f, "Failed to convert file: {}", err),
+            Error::Export(err) => write!(f, "Failed to export file: {}", err),
+            Error::ReadExport(err) => write!(f, "Failed to read exported file: {}", err),
+            Error::Upload(err) => write!(f, "Failed to upload converted file: {}", err),
+        }
+    }
+}
@@ -13,6 +13,7 @@ use std::fmt::Formatter;
 pub struct Config {
     pub file_id: String,
     pub to_folder_id: String,
+    pub print_only_id: bool,
 }
 
 pub async fn copy(config: Config) -> Result<(), Error> {
@@ -31,11 +32,13 @@ pub async fn copy(config: Config) -> Result<(), Error> {
 
     err_if_not_directory(&to_parent)?;
 
-    println!(
-        "Copying '{}' to '{}'",
-        file.name.unwrap_or_default(),
-        to_parent.name.unwrap_or_default()
-    );
+    if !config.print_only_id {
+        println!(
+            "Copying '{}' to '{}'",
+            file.name.unwrap_or_default(),
+            to_parent.name.unwrap_or_default()
+        );
+    }
 
     let copy_config = CopyConfig {
         file_id: config.file_id,
@@ -46,8 +49,12 @@ pub async fn copy(config: Config) -> Result<(), Error> {
         .await
         .map_err(Error::Copy)?;
 
-    let fields = files::info::prepare_fields(&new_file, &DisplayConfig::default());
-    files::info::print_fields(&fields);
+    if config.print_only_id {
+        print!("{}", new_file.id.unwrap_or_default())
+    } else {
+        let fields = files::info::prepare_fields(&new_file, &DisplayConfig::default());
+        files::info::print_fields(&fields);
+    }
 
     Ok(())
 }
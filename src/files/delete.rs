@@ -1,14 +1,70 @@
+use crate::common::api_stats::StatsDelegate;
 use std::error;
 use std::fmt::Display;
 use std::fmt::Formatter;
 
+use crate::common::delegate::UploadDelegateConfig;
+use crate::common::deletion_journal;
 use crate::common::drive_file;
 use crate::common::hub_helper;
 use crate::files;
+use crate::files::info::DisplayConfig;
+use crate::files::list::{ListFilesConfig, ListQuery, ListScope, ListSortOrder};
+use crate::files::update::PatchFile;
+use crate::hub::Hub;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::atomic::AtomicBool;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Operation {
+    Trash,
+    Untrash,
+    DeletePermanent,
+}
+
+impl Operation {
+    // Untrashing a folder restores its content, so only the other operations need
+    // the caller to confirm that descendants are affected
+    pub fn requires_recursive_confirmation(&self) -> bool {
+        match self {
+            Operation::Trash => true,
+            Operation::Untrash => false,
+            Operation::DeletePermanent => true,
+        }
+    }
+
+    pub fn past_tense(&self) -> &'static str {
+        match self {
+            Operation::Trash => "Trashed",
+            Operation::Untrash => "Restored",
+            Operation::DeletePermanent => "Deleted",
+        }
+    }
+}
 
 pub struct Config {
     pub file_id: String,
-    pub delete_directories: bool,
+    pub operation: Operation,
+    pub recursive: Recursive,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Recursive {
+    Refuse,
+    Confirmed,
+}
+
+impl From<bool> for Recursive {
+    fn from(confirmed: bool) -> Self {
+        if confirmed {
+            Recursive::Confirmed
+        } else {
+            Recursive::Refuse
+        }
+    }
 }
 
 pub async fn delete(config: Config) -> Result<(), Error> {
@@ -18,27 +74,155 @@ pub async fn delete(config: Config) -> Result<(), Error> {
         .await
         .map_err(Error::GetFile)?;
 
-    err_if_directory(&file, &config)?;
+    // Counting lists the whole tree, it is only done for the error that asks
+    // for --recursive
+    if drive_file::is_directory(&file) && needs_confirmation(&config) {
+        let descendants = count_descendants(&hub, &config.file_id, &AtomicBool::new(false))
+            .await
+            .map_err(Error::CountDescendants)?;
+        let name = file.name.unwrap_or_default();
+        return Err(Error::IsDirectory(name, descendants));
+    }
 
-    hub.files()
-        .delete(&config.file_id)
-        .supports_all_drives(true)
-        .add_scope(google_drive3::api::Scope::Full)
-        .doit()
+    let name = file.name.unwrap_or_default();
+    let parents = file.parents.unwrap_or_default();
+    let journal_error = apply_and_record(&hub, &config.file_id, &name, &parents, config.operation)
         .await
-        .map_err(Error::DeleteFile)?;
+        .map_err(|err| Error::Operation(config.operation, err))?;
+    if let Some(err) = journal_error {
+        eprintln!("Warning: {}", err);
+    }
+
+    println!("{} '{}'", config.operation.past_tense(), name);
 
-    println!("Deleted '{}'", file.name.unwrap_or_default());
+    Ok(())
+}
+
+pub async fn apply(
+    hub: &Hub,
+    file_id: &str,
+    operation: Operation,
+) -> Result<(), google_drive3::Error> {
+    match operation {
+        Operation::Trash | Operation::Untrash => {
+            let patch_file =
+                PatchFile::new(file_id.to_string()).with_trashed(operation == Operation::Trash);
+            files::update::update_metadata(hub, UploadDelegateConfig::default(), patch_file)
+                .await?;
+        }
+
+        Operation::DeletePermanent => {
+            hub.files()
+                .delete(file_id)
+                .supports_all_drives(true)
+                .add_scope(google_drive3::api::Scope::Full)
+                .delegate(&mut StatsDelegate)
+                .doit()
+                .await?;
+        }
+    }
 
     Ok(())
 }
 
+// Applies the operation and records it in the deletion journal that
+// `undelete-last` restores from. The journal is a safety net, failing to write
+// it doesn't fail the operation, the error is returned for the caller to show
+// as a warning
+pub async fn apply_and_record(
+    hub: &Hub,
+    file_id: &str,
+    name: &str,
+    parents: &[String],
+    operation: Operation,
+) -> Result<Option<deletion_journal::Error>, google_drive3::Error> {
+    apply(hub, file_id, operation).await?;
+
+    let entry = deletion_journal::Entry::new(file_id, name, parents, operation);
+    Ok(deletion_journal::record(&entry).err())
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Descendants {
+    pub files: u64,
+    pub folders: u64,
+    pub bytes: u64,
+}
+
+impl Descendants {
+    pub fn total(&self) -> u64 {
+        self.files + self.folders
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total() == 0
+    }
+}
+
+impl Display for Descendants {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} files, {} folders ({})",
+            files::info::format_count(self.files),
+            files::info::format_count(self.folders),
+            files::info::format_bytes(self.bytes as i64, &DisplayConfig::default())
+        )
+    }
+}
+
+// Stops with files::list::Error::Cancelled once cancel is set
+pub async fn count_descendants(
+    hub: &Hub,
+    folder_id: &str,
+    cancel: &AtomicBool,
+) -> Result<Descendants, files::list::Error> {
+    let mut descendants = Descendants::default();
+    let mut pending = vec![folder_id.to_string()];
+    // A folder can have several parents, it is only counted once
+    let mut visited = HashSet::from([folder_id.to_string()]);
+
+    while let Some(current_id) = pending.pop() {
+        let children = files::list::list_files_until_cancelled(
+            hub,
+            &ListFilesConfig {
+                query: ListQuery::FilesInFolder {
+                    folder_id: current_id,
+                },
+                order_by: ListSortOrder::default(),
+                scope: ListScope::default(),
+                max_files: usize::MAX,
+            },
+            cancel,
+        )
+        .await?;
+
+        for child in children {
+            if drive_file::is_directory(&child) {
+                if let Some(id) = child.id {
+                    if !visited.insert(id.clone()) {
+                        continue;
+                    }
+                    pending.push(id);
+                }
+                descendants.folders += 1;
+            } else {
+                descendants.files += 1;
+                descendants.bytes += child.size.unwrap_or(0).max(0) as u64;
+            }
+        }
+    }
+
+    Ok(descendants)
+}
+
 #[derive(Debug)]
 pub enum Error {
     Hub(hub_helper::Error),
     GetFile(google_drive3::Error),
-    DeleteFile(google_drive3::Error),
-    IsDirectory(String),
+    CountDescendants(files::list::Error),
+    Operation(Operation, google_drive3::Error),
+    IsDirectory(String, Descendants),
 }
 
 impl error::Error for Error {}
@@ -48,25 +232,57 @@ impl Display for Error {
         match self {
             Error::Hub(err) => write!(f, "{}", err),
             Error::GetFile(err) => write!(f, "Failed getting file: {}", err),
-            Error::DeleteFile(err) => write!(f, "Failed to delete file: {}", err),
-            Error::IsDirectory(name) => write!(
+            Error::CountDescendants(err) => {
+                write!(f, "Failed to count directory content: {}", err)
+            }
+            Error::Operation(Operation::Trash, err) => {
+                write!(f, "Failed to trash file: {}", err)
+            }
+            Error::Operation(Operation::Untrash, err) => {
+                write!(f, "Failed to restore file: {}", err)
+            }
+            Error::Operation(Operation::DeletePermanent, err) => {
+                write!(f, "Failed to delete file: {}", err)
+            }
+            Error::IsDirectory(name, descendants) => write!(
                 f,
-                "'{}' is a directory, use --recursive to delete directories",
-                name
+                "'{}' is a directory containing {}, use --recursive to include all of it",
+                name, descendants
             ),
         }
     }
 }
 
-fn err_if_directory(file: &google_drive3::api::File, config: &Config) -> Result<(), Error> {
-    if drive_file::is_directory(file) && !config.delete_directories {
-        let name = file
-            .name
-            .as_ref()
-            .map(|s| s.to_string())
-            .unwrap_or_default();
-        Err(Error::IsDirectory(name))
-    } else {
-        Ok(())
+fn needs_confirmation(config: &Config) -> bool {
+    config.operation.requires_recursive_confirmation() && config.recursive == Recursive::Refuse
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockServer;
+
+    #[tokio::test]
+    async fn folders_are_counted_once_until_cancelled() {
+        let server = MockServer::start().await;
+        // Every folder lists Photos again, like a folder that is its own
+        // descendant through a second parent
+        server.respond_fixture("GET", "/drive/v3/files", "files_list.json");
+        let hub = server.hub().await;
+
+        let descendants = count_descendants(&hub, "0AAbCdEfGhIjKUk9PVA", &AtomicBool::new(false))
+            .await
+            .unwrap();
+
+        assert_eq!(descendants.folders, 1);
+        assert_eq!(descendants.files, 2);
+        assert_eq!(server.requests().len(), 2);
+
+        let cancelled = count_descendants(&hub, "0AAbCdEfGhIjKUk9PVA", &AtomicBool::new(true));
+        assert!(matches!(
+            cancelled.await,
+            Err(files::list::Error::Cancelled)
+        ));
+        assert_eq!(server.requests().len(), 2);
     }
 }
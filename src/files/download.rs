@@ -1,14 +1,26 @@
+use crate::common::api_stats::StatsDelegate;
 use crate::common::drive_file;
+use crate::common::drive_url;
+use crate::common::file_filter::FileFilter;
+use crate::common::file_name;
 use crate::common::file_tree_drive;
 use crate::common::file_tree_drive::FileTreeDrive;
+use crate::common::hash_writer;
+use crate::common::hash_writer::Checksums;
+use crate::common::hash_writer::HashWriter;
+use crate::common::hash_writer::Hashes;
 use crate::common::hub_helper;
-use crate::common::md5_writer::Md5Writer;
+use crate::common::local_path;
+use crate::common::tee_writer::TeeWriter;
 use crate::files;
+use crate::hub;
 use crate::hub::Hub;
 use async_recursion::async_recursion;
 use futures::stream::StreamExt;
 use google_drive3::hyper;
 use human_bytes::human_bytes;
+use percent_encoding::percent_decode_str;
+use std::collections::HashSet;
 use std::error;
 use std::fmt::Display;
 use std::fmt::Formatter;
@@ -16,19 +28,43 @@ use std::fs;
 use std::fs::File;
 use std::io;
 use std::io::BufReader;
+use std::io::IsTerminal;
 use std::io::Read;
 use std::io::Write;
 use std::path::PathBuf;
 
+// Redirects followed before giving up on a public download, Drive normally
+// needs two
+const MAX_PUBLIC_REDIRECTS: usize = 10;
+
+#[derive(Clone)]
 pub struct Config {
     pub file_id: String,
     pub existing_file_action: ExistingFileAction,
     pub follow_shortcuts: bool,
     pub download_directories: bool,
     pub destination: Destination,
+    pub filter: FileFilter,
+    // Replaces characters in Drive names that aren't valid in local file names
+    pub name_replacement: String,
+    // Directories that get a copy of everything that is downloaded
+    pub mirrors: Vec<PathBuf>,
+    // Read files back from disk after writing them and compare their md5
+    pub verify_on_disk: bool,
+    // Check that the destination has room for the download before starting it
+    pub check_free_space: bool,
+    // Where the incomplete files are written, next to the destination if not set
+    pub temp_dir: Option<PathBuf>,
+    pub layout: Layout,
+    // Download a publicly shared file without an account
+    pub no_auth: bool,
 }
 
 impl Config {
+    fn canonical_mirror_roots(&self) -> Result<Vec<PathBuf>, Error> {
+        self.mirrors.iter().map(canonical_directory).collect()
+    }
+
     fn canonical_destination_root(&self) -> Result<PathBuf, Error> {
         match &self.destination {
             Destination::CurrentDir => {
@@ -40,14 +76,8 @@ impl Config {
             }
 
             Destination::Path(path) => {
-                if !path.exists() {
-                    Err(Error::DestinationPathDoesNotExist(path.clone()))
-                } else if !path.is_dir() {
-                    Err(Error::DestinationPathNotADirectory(path.clone()))
-                } else {
-                    path.canonicalize()
-                        .map_err(|err| Error::CanonicalizeDestinationPath(path.clone(), err))
-                }
+                // fmt
+                canonical_directory(path)
             }
 
             Destination::Stdout => {
@@ -58,6 +88,54 @@ impl Config {
     }
 }
 
+fn canonical_directory(path: &PathBuf) -> Result<PathBuf, Error> {
+    if !path.exists() {
+        Err(Error::DestinationPathDoesNotExist(path.clone()))
+    } else if !path.is_dir() {
+        Err(Error::DestinationPathNotADirectory(path.clone()))
+    } else {
+        path.canonicalize()
+            .map_err(|err| Error::CanonicalizeDestinationPath(path.clone(), err))
+    }
+}
+
+// How the Drive folders of a recursive download are recreated locally
+#[derive(Debug, Clone, Default)]
+pub struct Layout {
+    // Put every file directly in the destination
+    pub flatten: bool,
+    // Leave out this many leading folders, 1 leaves out the downloaded folder itself
+    pub strip_prefix: usize,
+}
+
+impl Layout {
+    // None if nothing is left of the folder
+    fn folder_path(&self, path: &PathBuf) -> Option<PathBuf> {
+        if self.flatten {
+            return None;
+        }
+
+        let stripped: PathBuf = path.components().skip(self.strip_prefix).collect();
+        if stripped.as_os_str().is_empty() {
+            None
+        } else {
+            Some(stripped)
+        }
+    }
+
+    // The file name is always kept, even if it has fewer folders than are stripped
+    fn file_path(&self, path: &PathBuf) -> PathBuf {
+        let folder_count = path.components().count().saturating_sub(1);
+        let skip = if self.flatten {
+            folder_count
+        } else {
+            self.strip_prefix.min(folder_count)
+        };
+
+        path.components().skip(skip).collect()
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Destination {
     CurrentDir,
@@ -71,11 +149,28 @@ pub enum ExistingFileAction {
     Overwrite,
 }
 
-#[async_recursion]
 pub async fn download(config: Config) -> Result<(), Error> {
+    err_if_invalid_config(&config)?;
+
+    if config.no_auth {
+        return download_public(&config).await;
+    }
+
     let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+    download_with_hub(&hub, config).await
+}
 
-    let file = files::info::get_file(&hub, &config.file_id)
+pub fn err_if_invalid_config(config: &Config) -> Result<(), Error> {
+    err_if_invalid_name_replacement(config)?;
+    err_if_mirrors_with_stdout(config)?;
+    err_if_invalid_temp_dir(config)
+}
+
+// Downloads like `download` with a hub that already exists, so downloading
+// many files doesn't load the account for each of them
+#[async_recursion]
+pub async fn download_with_hub(hub: &Hub, config: Config) -> Result<(), Error> {
+    let file = files::info::get_file(hub, &config.file_id)
         .await
         .map_err(Error::GetFile)?;
 
@@ -88,15 +183,18 @@ pub async fn download(config: Config) -> Result<(), Error> {
 
         err_if_shortcut_target_is_missing(&target_file_id)?;
 
-        download(Config {
-            file_id: target_file_id.unwrap_or_default(),
-            ..config
-        })
+        download_with_hub(
+            hub,
+            Config {
+                file_id: target_file_id.unwrap_or_default(),
+                ..config
+            },
+        )
         .await?;
     } else if drive_file::is_directory(&file) {
-        download_directory(&hub, &file, &config).await?;
+        download_directory(hub, &file, &config).await?;
     } else {
-        download_regular(&hub, &file, &config).await?;
+        download_regular(hub, &file, &config).await?;
     }
 
     Ok(())
@@ -107,10 +205,181 @@ pub async fn download_regular(
     file: &google_drive3::api::File,
     config: &Config,
 ) -> Result<(), Error> {
+    err_if_not_enough_space_for(file, config)?;
+
+    let checksums = match config.destination {
+        Destination::Stdout => Checksums::default(),
+        _ => checksums_of(hub, &config.file_id, file.md5_checksum.clone()).await,
+    };
     let body = download_file(&hub, &config.file_id)
         .await
         .map_err(Error::DownloadFile)?;
 
+    save_download(body, file, checksums, config).await
+}
+
+// Downloads a file that is shared with anyone who has the link, without an
+// account. The name and size come from the response since the metadata needs
+// authentication
+pub async fn download_public(config: &Config) -> Result<(), Error> {
+    let http_config = hub_helper::http_config();
+    let client = hub::http_client(&http_config);
+
+    let response = get_public_file(&client, &config.file_id).await?;
+
+    let file = google_drive3::api::File {
+        id: Some(config.file_id.clone()),
+        name: attachment_file_name(response.headers()),
+        size: response
+            .headers()
+            .get(hyper::header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok()),
+        ..google_drive3::api::File::default()
+    };
+
+    err_if_file_exists(&file, config)?;
+    err_if_not_enough_space_for(&file, config)?;
+
+    // There is no checksum without the metadata
+    save_download(response.into_body(), &file, Checksums::default(), config).await
+}
+
+// Follows the redirects to the content and confirms the warning page that is
+// shown instead of files too large to be virus scanned
+async fn get_public_file(
+    client: &hub::HttpClient,
+    file_id: &str,
+) -> Result<hyper::Response<hyper::Body>, Error> {
+    let mut url = drive_url::download_url(file_id);
+    let mut confirmed = false;
+
+    for _ in 0..MAX_PUBLIC_REDIRECTS {
+        let request = hyper::Request::get(&url)
+            .body(hyper::Body::empty())
+            .map_err(|err| Error::PublicDownload(hub::RequestError::Build(err)))?;
+
+        let response = client
+            .request(request)
+            .await
+            .map_err(|err| Error::PublicDownload(hub::RequestError::Http(err)))?;
+
+        let status = response.status();
+
+        if status.is_redirection() {
+            url = response
+                .headers()
+                .get(hyper::header::LOCATION)
+                .and_then(|value| value.to_str().ok())
+                .ok_or(Error::NotPublic(file_id.to_string()))?
+                .to_string();
+            continue;
+        }
+
+        if matches!(
+            status,
+            hyper::StatusCode::UNAUTHORIZED
+                | hyper::StatusCode::FORBIDDEN
+                | hyper::StatusCode::NOT_FOUND
+        ) {
+            return Err(Error::NotPublic(file_id.to_string()));
+        }
+
+        if !status.is_success() {
+            let body = hyper::body::to_bytes(response.into_body())
+                .await
+                .unwrap_or_default();
+            return Err(Error::PublicDownload(hub::RequestError::Status(
+                status,
+                String::from_utf8_lossy(&body).to_string(),
+            )));
+        }
+
+        let is_html = response
+            .headers()
+            .get(hyper::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/html"));
+
+        if !is_html {
+            return Ok(response);
+        }
+
+        // Any other page, i.e. a sign in page or a Google document, means the
+        // file can't be downloaded without an account
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .map_err(|err| Error::PublicDownload(hub::RequestError::Http(err)))?;
+        let html = String::from_utf8_lossy(&body);
+
+        match drive_url::confirm_from_warning_page(&html) {
+            Some(confirm) if !confirmed => {
+                url = drive_url::confirmed_download_url(file_id, &confirm);
+                confirmed = true;
+            }
+
+            _ => return Err(Error::NotPublic(file_id.to_string())),
+        }
+    }
+
+    Err(Error::NotPublic(file_id.to_string()))
+}
+
+// Drive sends the name both as `filename` and as the utf-8 `filename*`
+fn attachment_file_name(headers: &hyper::HeaderMap) -> Option<String> {
+    let disposition = headers
+        .get(hyper::header::CONTENT_DISPOSITION)?
+        .to_str()
+        .ok()?;
+
+    let params: Vec<(&str, &str)> = disposition
+        .split(';')
+        .filter_map(|param| param.trim().split_once('='))
+        .collect();
+
+    let extended = params
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case("filename*"))
+        .and_then(|(_, value)| value.split_once("''"))
+        .and_then(|(_, encoded)| percent_decode_str(encoded).decode_utf8().ok())
+        .map(|name| name.to_string());
+
+    let plain = params
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case("filename"))
+        .map(|(_, value)| value.trim_matches('"').to_string());
+
+    extended.or(plain).filter(|name| !name.is_empty())
+}
+
+fn err_if_not_enough_space_for(
+    file: &google_drive3::api::File,
+    config: &Config,
+) -> Result<(), Error> {
+    if config.destination == Destination::Stdout {
+        return Ok(());
+    }
+
+    let size = file.size.unwrap_or_default().max(0) as u64;
+    let mut root_paths = vec![config.canonical_destination_root()?];
+    root_paths.extend(config.canonical_mirror_roots()?);
+    for root_path in &root_paths {
+        err_if_not_enough_space(root_path, size, config)?;
+    }
+    if let Some(temp_dir) = &config.temp_dir {
+        let needed = size * root_paths.len() as u64;
+        err_if_not_enough_space(temp_dir, needed, config)?;
+    }
+
+    Ok(())
+}
+
+async fn save_download(
+    body: hyper::Body,
+    file: &google_drive3::api::File,
+    checksums: Checksums,
+    config: &Config,
+) -> Result<(), Error> {
     match &config.destination {
         Destination::Stdout => {
             // fmt
@@ -120,10 +389,21 @@ pub async fn download_regular(
         _ => {
             let file_name = file.name.clone().ok_or(Error::MissingFileName)?;
             let root_path = config.canonical_destination_root()?;
-            let abs_file_path = root_path.join(&file_name);
+            let safe_name = file_name::sanitize(&file_name, &config.name_replacement);
+            let mut file_paths = vec![root_path.join(&safe_name)];
+            for mirror_root in config.canonical_mirror_roots()? {
+                file_paths.push(mirror_root.join(&safe_name));
+            }
 
             println!("Downloading {}", file_name);
-            save_body_to_file(body, &abs_file_path, file.md5_checksum.clone()).await?;
+            save_body_to_files(
+                body,
+                &file_paths,
+                checksums,
+                config.verify_on_disk,
+                config.temp_dir.as_ref(),
+            )
+            .await?;
             println!("Successfully downloaded {}", file_name);
         }
     }
@@ -136,7 +416,7 @@ pub async fn download_directory(
     file: &google_drive3::api::File,
     config: &Config,
 ) -> Result<(), Error> {
-    let tree = FileTreeDrive::from_file(&hub, &file)
+    let tree = FileTreeDrive::from_file(&hub, &file, &config.filter)
         .await
         .map_err(Error::CreateFileTree)?;
 
@@ -149,31 +429,73 @@ pub async fn download_directory(
         human_bytes(tree_info.total_file_size as f64)
     );
 
-    let root_path = config.canonical_destination_root()?;
+    let mut root_paths = vec![config.canonical_destination_root()?];
+    root_paths.extend(config.canonical_mirror_roots()?);
 
-    for folder in &tree.folders() {
-        let folder_path = folder.relative_path();
-        let abs_folder_path = root_path.join(&folder_path);
+    let folders = tree.folders();
+    let files = local_file_paths(&folders, config);
 
-        println!("Creating directory {}", folder_path.display());
-        fs::create_dir_all(&abs_folder_path)
-            .map_err(|err| Error::CreateDirectory(abs_folder_path, err))?;
+    for root_path in &root_paths {
+        let missing_size = files
+            .iter()
+            .filter(|(file, file_path)| !local_file_has_size(&root_path.join(file_path), file.size))
+            .map(|(file, _)| file.size)
+            .sum();
+        err_if_not_enough_space(root_path, missing_size, config)?;
+    }
 
-        for file in folder.files() {
-            let file_path = file.relative_path();
-            let abs_file_path = root_path.join(&file_path);
+    // Files are downloaded one at a time, so the temp dir only has to hold the largest
+    if let Some(temp_dir) = &config.temp_dir {
+        let largest_size = files
+            .iter()
+            .map(|(file, _)| file.size)
+            .max()
+            .unwrap_or_default();
+        err_if_not_enough_space(temp_dir, largest_size * root_paths.len() as u64, config)?;
+    }
 
-            if local_file_is_identical(&abs_file_path, &file) {
-                continue;
-            }
+    for folder in &folders {
+        let folder_path = folder.relative_path(&config.name_replacement);
+        let folder_path = match config.layout.folder_path(&folder_path) {
+            Some(folder_path) => folder_path,
+            None => continue,
+        };
 
-            let body = download_file(&hub, &file.drive_id)
-                .await
-                .map_err(Error::DownloadFile)?;
+        println!("Creating directory {}", folder_path.display());
+        for root_path in &root_paths {
+            let abs_folder_path = root_path.join(&folder_path);
+            fs::create_dir_all(&abs_folder_path)
+                .map_err(|err| Error::CreateDirectory(abs_folder_path, err))?;
+        }
+    }
+
+    for (file, file_path) in &files {
+        let abs_file_paths: Vec<PathBuf> = root_paths
+            .iter()
+            .map(|root_path| root_path.join(file_path))
+            .collect();
 
-            println!("Downloading file '{}'", file_path.display());
-            save_body_to_file(body, &abs_file_path, file.md5.clone()).await?;
+        if abs_file_paths
+            .iter()
+            .all(|path| local_file_is_identical(path, file))
+        {
+            continue;
         }
+
+        let checksums = checksums_of(hub, &file.drive_id, file.md5.clone()).await;
+        let body = download_file(&hub, &file.drive_id)
+            .await
+            .map_err(Error::DownloadFile)?;
+
+        println!("Downloading file '{}'", file_path.display());
+        save_body_to_files(
+            body,
+            &abs_file_paths,
+            checksums,
+            config.verify_on_disk,
+            config.temp_dir.as_ref(),
+        )
+        .await?;
     }
 
     println!(
@@ -186,6 +508,56 @@ pub async fn download_directory(
     Ok(())
 }
 
+// Where each file goes relative to the destination. Flattening or stripping
+// folders can give files the same path, later ones get a number like "name (2).ext"
+fn local_file_paths(
+    folders: &[file_tree_drive::Folder],
+    config: &Config,
+) -> Vec<(file_tree_drive::File, PathBuf)> {
+    let mut used_paths = HashSet::new();
+
+    folders
+        .iter()
+        .flat_map(|folder| folder.files())
+        .map(|file| {
+            let path = config
+                .layout
+                .file_path(&file.relative_path(&config.name_replacement));
+            let path = unique_path(path, &mut used_paths);
+            (file, path)
+        })
+        .collect()
+}
+
+// Paths are compared case-insensitively, some filesystems don't tell them apart
+fn unique_path(path: PathBuf, used_paths: &mut HashSet<String>) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+
+    let mut candidate = path.clone();
+    let mut number = 1;
+
+    while !used_paths.insert(candidate.to_string_lossy().to_lowercase()) {
+        number += 1;
+        candidate = path.with_file_name(format!("{} ({}){}", stem, number, extension));
+    }
+
+    candidate
+}
+
+// Sha1 and sha256 are checked when Drive has them, the md5 is enough otherwise
+async fn checksums_of(hub: &Hub, file_id: &str, md5: Option<String>) -> Checksums {
+    files::info::get_checksums(hub, file_id, false)
+        .await
+        .unwrap_or_else(|_| Checksums::md5(md5))
+}
+
 pub async fn download_file(hub: &Hub, file_id: &str) -> Result<hyper::Body, google_drive3::Error> {
     let (response, _) = hub
         .files()
@@ -193,6 +565,7 @@ pub async fn download_file(hub: &Hub, file_id: &str) -> Result<hyper::Body, goog
         .supports_all_drives(true)
         .param("alt", "media")
         .add_scope(google_drive3::api::Scope::Full)
+        .delegate(&mut StatsDelegate)
         .doit()
         .await?;
 
@@ -207,7 +580,15 @@ pub enum Error {
     MissingFileName,
     FileExists(PathBuf),
     IsDirectory(String),
-    Md5Mismatch { expected: String, actual: String },
+    Md5Mismatch {
+        expected: String,
+        actual: String,
+    },
+    ChecksumMismatch {
+        algorithm: String,
+        expected: String,
+        actual: String,
+    },
     CreateFile(io::Error),
     CreateDirectory(PathBuf, io::Error),
     CopyFile(io::Error),
@@ -221,6 +602,23 @@ pub enum Error {
     MissingShortcutTarget,
     IsShortcut(String),
     StdoutNotValidDestination,
+    InvalidNameReplacement(String),
+    SyncFile(io::Error),
+    ReadBack(PathBuf, io::Error),
+    DiskMd5Mismatch {
+        path: PathBuf,
+        stream: String,
+        disk: String,
+    },
+    NotEnoughSpace {
+        path: PathBuf,
+        needed: u64,
+        available: u64,
+    },
+    ReadAnswer(io::Error),
+    TempDirNotADirectory(PathBuf),
+    PublicDownload(hub::RequestError),
+    NotPublic(String),
 }
 
 impl error::Error for Error {}
@@ -250,6 +648,17 @@ impl Display for Error {
                     expected, actual
                 )
             }
+            Error::ChecksumMismatch {
+                algorithm,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{} mismatch, expected: {}, actual: {}",
+                algorithm.to_uppercase(),
+                expected,
+                actual
+            ),
             Error::CreateFile(err) => write!(f, "Failed to create file: {}", err),
             Error::CreateDirectory(path, err) => write!(
                 f,
@@ -288,34 +697,209 @@ impl Display for Error {
                 f,
                 "Stdout is not a valid destination for this combination of options"
             ),
+            Error::InvalidNameReplacement(replacement) => write!(
+                f,
+                "'{}' can't be used as name replacement, it contains characters that aren't valid in file names",
+                replacement
+            ),
+            Error::SyncFile(err) => write!(f, "Failed to flush file to disk: {}", err),
+            Error::ReadBack(path, err) => write!(
+                f,
+                "Failed to read back '{}': {}",
+                path.display(),
+                err
+            ),
+            Error::DiskMd5Mismatch { path, stream, disk } => write!(
+                f,
+                "'{}' is corrupt on disk, md5 of stream: {}, md5 on disk: {}",
+                path.display(),
+                stream,
+                disk
+            ),
+            Error::NotEnoughSpace {
+                path,
+                needed,
+                available,
+            } => write!(
+                f,
+                "Not enough free space in '{}', the download needs {} and {} is available. Use --skip-space-check to download anyway",
+                path.display(),
+                human_bytes(*needed as f64),
+                human_bytes(*available as f64)
+            ),
+            Error::ReadAnswer(err) => write!(f, "Failed to read answer: {}", err),
+            Error::TempDirNotADirectory(path) => {
+                write!(f, "Temp dir '{}' is not a directory", path.display())
+            }
+            Error::PublicDownload(err) => write!(f, "Failed to download file: {}", err),
+            Error::NotPublic(file_id) => write!(
+                f,
+                "File {} is not shared with anyone who has the link, download it without --no-auth",
+                file_id
+            ),
         }
     }
 }
 
 // TODO: move to common
 pub async fn save_body_to_file(
-    mut body: hyper::Body,
+    body: hyper::Body,
     file_path: &PathBuf,
     expected_md5: Option<String>,
 ) -> Result<(), Error> {
-    // Create temporary file
-    let tmp_file_path = file_path.with_extension("incomplete");
-    let file = File::create(&tmp_file_path).map_err(Error::CreateFile)?;
+    save_body_to_files(
+        body,
+        &[file_path.clone()],
+        Checksums::md5(expected_md5),
+        false,
+        None,
+    )
+    .await
+}
+
+// Writes the stream to all the paths at once, it is only read from Drive once.
+// With `verify_on_disk` the files are read back after they are renamed, to catch
+// corruption on the way to the disk that the md5 of the stream can't see.
+// With `temp_dir` the incomplete files are written there instead of next to
+// the destination, i.e. on a local disk when downloading to a network mount
+pub async fn save_body_to_files(
+    mut body: hyper::Body,
+    file_paths: &[PathBuf],
+    expected: Checksums,
+    verify_on_disk: bool,
+    temp_dir: Option<&PathBuf>,
+) -> Result<(), Error> {
+    // Long paths need a prefix on Windows
+    let file_paths: Vec<PathBuf> = file_paths
+        .iter()
+        .map(|path| local_path::extended(path))
+        .collect();
+
+    // Create temporary files
+    let (tmp_file_paths, files): (Vec<PathBuf>, Vec<File>) = file_paths
+        .iter()
+        .map(|path| create_temp_file(path, temp_dir))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Error::CreateFile)?
+        .into_iter()
+        .unzip();
 
-    // Wrap file in writer that calculates md5
-    let mut writer = Md5Writer::new(file);
+    // Wrap files in writer that calculates the checksums
+    let mut writer = HashWriter::new(TeeWriter::new(files));
 
-    // Read chunks from stream and write to file
+    // Read chunks from stream and write to files
     while let Some(chunk_result) = body.next().await {
         let chunk = chunk_result.map_err(Error::ReadChunk)?;
         writer.write_all(&chunk).map_err(Error::WriteChunk)?;
     }
 
-    // Check md5
-    err_if_md5_mismatch(expected_md5, writer.md5())?;
+    // Check the checksums Drive has
+    let hashes = writer.hashes();
+    err_if_checksum_mismatch(&expected, &hashes)?;
+    let stream_md5 = hashes.md5;
+
+    // Rename temporary files to final files
+    for (tmp_file_path, file_path) in tmp_file_paths.iter().zip(&file_paths) {
+        if verify_on_disk {
+            sync_file(tmp_file_path).map_err(Error::SyncFile)?;
+        }
+        match fs::rename(tmp_file_path, file_path) {
+            Ok(()) => {}
+            Err(_) if temp_dir.is_some() => {
+                copy_into_place(tmp_file_path, file_path, verify_on_disk)?;
+            }
+            Err(err) => return Err(Error::RenameFile(err)),
+        }
+    }
+
+    if verify_on_disk {
+        for file_path in &file_paths {
+            verify_file_on_disk(file_path, &stream_md5)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Files in the temp dir get a unique name, the copies for mirrors would
+// otherwise share one
+fn create_temp_file(
+    path: &PathBuf,
+    temp_dir: Option<&PathBuf>,
+) -> Result<(PathBuf, File), io::Error> {
+    match temp_dir {
+        Some(temp_dir) => {
+            let file_name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let (file, tmp_file_path) = tempfile::Builder::new()
+                .prefix(&format!("{}.", file_name))
+                .suffix(".incomplete")
+                .tempfile_in(temp_dir)?
+                .keep()
+                .map_err(|err| err.error)?;
+            Ok((tmp_file_path, file))
+        }
+
+        None => {
+            let tmp_file_path = path.with_extension("incomplete");
+            let file = File::create(&tmp_file_path)?;
+            Ok((tmp_file_path, file))
+        }
+    }
+}
+
+// A file in a temp dir on another filesystem can't be renamed to the
+// destination. It is copied next to it first, so the destination is still
+// only replaced by a rename and never left half written
+fn copy_into_place(
+    tmp_file_path: &PathBuf,
+    file_path: &PathBuf,
+    verify_on_disk: bool,
+) -> Result<(), Error> {
+    let staged_file_path = file_path.with_extension("incomplete");
+    fs::copy(tmp_file_path, &staged_file_path).map_err(Error::CopyFile)?;
+    if verify_on_disk {
+        sync_file(&staged_file_path).map_err(Error::SyncFile)?;
+    }
+    fs::rename(&staged_file_path, file_path).map_err(Error::RenameFile)?;
+
+    if let Err(err) = fs::remove_file(tmp_file_path) {
+        eprintln!(
+            "Warning: Failed to remove temporary file '{}': {}",
+            tmp_file_path.display(),
+            err
+        );
+    }
+
+    Ok(())
+}
 
-    // Rename temporary file to final file
-    fs::rename(&tmp_file_path, &file_path).map_err(Error::RenameFile)
+fn sync_file(path: &PathBuf) -> Result<(), io::Error> {
+    File::open(path)?.sync_all()
+}
+
+fn verify_file_on_disk(path: &PathBuf, stream_md5: &str) -> Result<(), Error> {
+    let disk_md5 =
+        hash_writer::md5_of_file(path).map_err(|err| Error::ReadBack(path.clone(), err))?;
+
+    println!(
+        "Verified '{}', md5 of stream: {}, md5 on disk: {}",
+        path.display(),
+        stream_md5,
+        disk_md5
+    );
+
+    if disk_md5 == stream_md5 {
+        Ok(())
+    } else {
+        Err(Error::DiskMd5Mismatch {
+            path: path.clone(),
+            stream: stream_md5.to_string(),
+            disk: disk_md5,
+        })
+    }
 }
 
 // TODO: move to common
@@ -333,6 +917,14 @@ pub async fn save_body_to_stdout(mut body: hyper::Body) -> Result<(), Error> {
 
 fn err_if_file_exists(file: &google_drive3::api::File, config: &Config) -> Result<(), Error> {
     let file_name = file.name.clone().ok_or(Error::MissingFileName)?;
+    let file_name = file_name::sanitize(&file_name, &config.name_replacement);
+
+    for mirror in &config.mirrors {
+        let path = mirror.join(&file_name);
+        if path.exists() && config.existing_file_action == ExistingFileAction::Abort {
+            return Err(Error::FileExists(path));
+        }
+    }
 
     let file_path = match &config.destination {
         Destination::CurrentDir => Some(PathBuf::from(".").join(file_name)),
@@ -356,6 +948,84 @@ fn err_if_file_exists(file: &google_drive3::api::File, config: &Config) -> Resul
     }
 }
 
+fn err_if_invalid_name_replacement(config: &Config) -> Result<(), Error> {
+    if file_name::is_valid_replacement(&config.name_replacement) {
+        Ok(())
+    } else {
+        Err(Error::InvalidNameReplacement(
+            config.name_replacement.clone(),
+        ))
+    }
+}
+
+fn err_if_invalid_temp_dir(config: &Config) -> Result<(), Error> {
+    match &config.temp_dir {
+        Some(path) if !path.is_dir() => Err(Error::TempDirNotADirectory(path.clone())),
+        _ => Ok(()),
+    }
+}
+
+fn err_if_mirrors_with_stdout(config: &Config) -> Result<(), Error> {
+    if config.destination == Destination::Stdout && !config.mirrors.is_empty() {
+        Err(Error::StdoutNotValidDestination)
+    } else {
+        Ok(())
+    }
+}
+
+// Files that are already there with the same size are most likely skipped, so
+// only the rest is counted. When the terminal is interactive the user can choose
+// to download anyway, i.e. when other files will be deleted in the meantime
+fn err_if_not_enough_space(root_path: &PathBuf, needed: u64, config: &Config) -> Result<(), Error> {
+    if !config.check_free_space || needed == 0 {
+        return Ok(());
+    }
+
+    let available = match fs2::available_space(root_path) {
+        Ok(available) => available,
+        Err(err) => {
+            eprintln!(
+                "Warning: Failed to get free space of '{}': {}",
+                root_path.display(),
+                err
+            );
+            return Ok(());
+        }
+    };
+
+    if needed <= available {
+        return Ok(());
+    }
+
+    let err = Error::NotEnoughSpace {
+        path: root_path.clone(),
+        needed,
+        available,
+    };
+
+    if !io::stdin().is_terminal() {
+        return Err(err);
+    }
+
+    print!(
+        "The download needs {} but only {} is free in '{}', download anyway? [y/N] ",
+        human_bytes(needed as f64),
+        human_bytes(available as f64),
+        root_path.display()
+    );
+    let _ = io::stdout().flush();
+
+    let mut input = String::new();
+    io::stdin()
+        .read_line(&mut input)
+        .map_err(Error::ReadAnswer)?;
+
+    match input.trim() {
+        "y" | "yes" => Ok(()),
+        _ => Err(err),
+    }
+}
+
 fn err_if_directory(file: &google_drive3::api::File, config: &Config) -> Result<(), Error> {
     if drive_file::is_directory(file) && !config.download_directories {
         let name = file
@@ -390,19 +1060,24 @@ fn err_if_shortcut_target_is_missing(target_id: &Option<String>) -> Result<(), E
     }
 }
 
-fn err_if_md5_mismatch(expected: Option<String>, actual: String) -> Result<(), Error> {
-    let is_matching = expected.clone().map(|md5| md5 == actual).unwrap_or(true);
-
-    if is_matching {
-        Ok(())
-    } else {
-        Err(Error::Md5Mismatch {
-            expected: expected.unwrap_or_default(),
+fn err_if_checksum_mismatch(expected: &Checksums, actual: &Hashes) -> Result<(), Error> {
+    match expected.mismatch(actual) {
+        None => Ok(()),
+        Some(("md5", expected, actual)) => Err(Error::Md5Mismatch { expected, actual }),
+        Some((algorithm, expected, actual)) => Err(Error::ChecksumMismatch {
+            algorithm: algorithm.to_string(),
+            expected,
             actual,
-        })
+        }),
     }
 }
 
+fn local_file_has_size(path: &PathBuf, size: u64) -> bool {
+    fs::metadata(path)
+        .map(|metadata| metadata.len() == size)
+        .unwrap_or(false)
+}
+
 fn local_file_is_identical(path: &PathBuf, file: &file_tree_drive::File) -> bool {
     if path.exists() {
         let file_md5 = compute_md5_from_path(path).unwrap_or_else(|err| {
@@ -0,0 +1,240 @@
+use crate::common::drive_url;
+use crate::common::hub_helper;
+use crate::files::download;
+use crate::hub::Hub;
+use futures::stream;
+use futures::stream::StreamExt;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+use std::path::PathBuf;
+
+const STDIN_PATH: &str = "-";
+const STDIN_RETRY_PATH: &str = "download-failed.txt";
+
+// Downloads every file of a list of ids, for bulk exports. The ids that failed
+// are written to a retry file, which is a list for the next run
+pub struct Config {
+    // `-` reads the list from stdin
+    pub list_path: PathBuf,
+    // None writes the retry file next to the list
+    pub retry_path: Option<PathBuf>,
+    pub parallel: usize,
+    // Used for each id of the list, its file_id is replaced
+    pub download: download::Config,
+}
+
+pub async fn download_list(config: Config) -> Result<(), Error> {
+    download::err_if_invalid_config(&config.download).map_err(Error::Config)?;
+
+    let ids = read_ids(&config.list_path)?;
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+
+    let failed_ids = download_ids(&hub, &ids, &config).await;
+
+    println!(
+        "Downloaded {} of {} files",
+        ids.len() - failed_ids.len(),
+        ids.len()
+    );
+
+    if failed_ids.is_empty() {
+        return Ok(());
+    }
+
+    let retry_path = config
+        .retry_path
+        .clone()
+        .unwrap_or_else(|| default_retry_path(&config.list_path));
+    let mut retry_list = failed_ids.join("\n");
+    retry_list.push('\n');
+    fs::write(&retry_path, retry_list)
+        .map_err(|err| Error::WriteRetryFile(retry_path.clone(), err))?;
+
+    Err(Error::Failed(failed_ids.len(), retry_path))
+}
+
+// Downloads the files concurrently and returns the ids that failed, in the
+// order of the list. A failed file doesn't stop the others
+pub async fn download_ids(hub: &Hub, ids: &[String], config: &Config) -> Vec<String> {
+    let total = ids.len();
+    let mut done = 0;
+    let mut failed = vec![];
+
+    let mut downloads = stream::iter(ids.iter().enumerate())
+        .map(|(index, id)| {
+            let download_config = download::Config {
+                file_id: id.clone(),
+                ..config.download.clone()
+            };
+            async move {
+                let result = download::download_with_hub(hub, download_config).await;
+                (index, result)
+            }
+        })
+        .buffer_unordered(config.parallel.max(1));
+
+    while let Some((index, result)) = downloads.next().await {
+        done += 1;
+        match result {
+            Ok(()) => println!("[{}/{}] Downloaded {}", done, total, ids[index]),
+            Err(err) => {
+                eprintln!(
+                    "[{}/{}] Error: Failed to download {}: {}",
+                    done, total, ids[index], err
+                );
+                failed.push(index);
+            }
+        }
+    }
+
+    failed.sort();
+    failed.into_iter().map(|index| ids[index].clone()).collect()
+}
+
+fn read_ids(list_path: &Path) -> Result<Vec<String>, Error> {
+    let mut text = String::new();
+    let result = if list_path == Path::new(STDIN_PATH) {
+        io::stdin().read_to_string(&mut text)
+    } else {
+        fs::File::open(list_path).and_then(|mut file| file.read_to_string(&mut text))
+    };
+    result.map_err(|err| Error::ReadList(list_path.to_path_buf(), err))?;
+
+    parse_ids(&text)
+}
+
+// One id or Drive url per line. Empty lines and lines starting with # are
+// skipped, so a retry file can be edited by hand
+fn parse_ids(text: &str) -> Result<Vec<String>, Error> {
+    text.lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(line_number, line)| {
+            drive_url::parse_id(line).map_err(|err| Error::InvalidId(line_number, err))
+        })
+        .collect()
+}
+
+fn default_retry_path(list_path: &Path) -> PathBuf {
+    if list_path == Path::new(STDIN_PATH) {
+        return PathBuf::from(STDIN_RETRY_PATH);
+    }
+
+    let mut retry_path = list_path.as_os_str().to_owned();
+    retry_path.push(".failed");
+    PathBuf::from(retry_path)
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    Config(download::Error),
+    ReadList(PathBuf, io::Error),
+    InvalidId(usize, String),
+    WriteRetryFile(PathBuf, io::Error),
+    Failed(usize, PathBuf),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::Config(err) => write!(f, "{}", err),
+            Error::ReadList(path, err) => {
+                write!(f, "Failed to read list '{}': {}", path.display(), err)
+            }
+            Error::InvalidId(line_number, err) => write!(f, "Line {}: {}", line_number, err),
+            Error::WriteRetryFile(path, err) => {
+                write!(f, "Failed to write '{}': {}", path.display(), err)
+            }
+            Error::Failed(count, retry_path) => write!(
+                f,
+                "{} files failed to download, retry them with --from-list {}",
+                count,
+                retry_path.display()
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::file_filter::FileFilter;
+    use crate::common::file_name;
+    use crate::test_util::{fixture, MockServer};
+
+    const FILE_ID: &str = "1ZyXwVuTsRqPoNmLkJiHgFeDcBa";
+
+    #[test]
+    fn ids_and_urls_are_read_from_the_list() {
+        let text = format!(
+            "# exported on monday\n{}\n\nhttps://drive.google.com/file/d/{}/view\n",
+            FILE_ID, FILE_ID
+        );
+
+        let ids = parse_ids(&text).unwrap();
+
+        assert_eq!(ids, vec![FILE_ID, FILE_ID]);
+        assert!(matches!(
+            parse_ids("not an id!"),
+            Err(Error::InvalidId(1, _))
+        ));
+    }
+
+    #[tokio::test]
+    async fn failed_ids_are_returned_for_the_retry_file() {
+        let server = MockServer::start().await;
+        let path = format!("/drive/v3/files/{}", FILE_ID);
+        server.respond_fixture("GET", &path, "file_metadata.json");
+        server.respond(
+            "GET",
+            &format!("{}?alt=media", path),
+            200,
+            fixture("file_content.txt"),
+        );
+        server.respond(
+            "GET",
+            "/drive/v3/files/1MiSsInGfIlE",
+            404,
+            fixture("not_found.json"),
+        );
+        let hub = server.hub().await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            list_path: PathBuf::from(STDIN_PATH),
+            retry_path: None,
+            parallel: 2,
+            download: download::Config {
+                file_id: String::new(),
+                existing_file_action: download::ExistingFileAction::Abort,
+                follow_shortcuts: false,
+                download_directories: false,
+                destination: download::Destination::Path(dir.path().to_path_buf()),
+                filter: FileFilter::default(),
+                name_replacement: file_name::DEFAULT_REPLACEMENT.to_string(),
+                mirrors: vec![],
+                verify_on_disk: false,
+                check_free_space: false,
+                temp_dir: None,
+                layout: download::Layout::default(),
+                no_auth: false,
+            },
+        };
+        let ids = vec!["1MiSsInGfIlE".to_string(), FILE_ID.to_string()];
+
+        let failed = download_ids(&hub, &ids, &config).await;
+
+        assert_eq!(failed, vec!["1MiSsInGfIlE"]);
+        assert!(dir.path().join("notes.txt").exists());
+    }
+}
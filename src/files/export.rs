@@ -1,3 +1,4 @@
+use crate::common::api_stats::StatsDelegate;
 use mime::Mime;
 
 use crate::common::drive_file;
@@ -75,6 +76,7 @@ pub async fn export_file(
         .files()
         .export(file_id, &mime_type.to_string())
         .add_scope(google_drive3::api::Scope::Full)
+        .delegate(&mut StatsDelegate)
         .doit()
         .await?;
 
@@ -0,0 +1,223 @@
+use crate::common::drive_file;
+use crate::common::drive_file::DocType;
+use crate::common::drive_file::FileExtension;
+use crate::common::file_name;
+use crate::common::hub_helper;
+use crate::files;
+use crate::files::list;
+use crate::files::list::ListQuery;
+use crate::hub::Hub;
+use futures::stream;
+use futures::stream::StreamExt;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub folder_id: String,
+    pub format: FileExtension,
+    pub destination: PathBuf,
+    pub overwrite: bool,
+    pub parallel: usize,
+}
+
+// A Google document found under the exported folder
+struct Doc {
+    id: String,
+    name: String,
+    doc_type: DocType,
+    file_path: PathBuf,
+}
+
+pub async fn export_all(config: Config) -> Result<(), Error> {
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+
+    let folder = files::info::get_file(&hub, &config.folder_id)
+        .await
+        .map_err(Error::GetFile)?;
+
+    if !drive_file::is_directory(&folder) {
+        return Err(Error::NotADirectory(folder.name.unwrap_or_default()));
+    }
+
+    let root_path = config.destination.join(file_name::sanitize(
+        &folder.name.unwrap_or_default(),
+        file_name::DEFAULT_REPLACEMENT,
+    ));
+
+    let docs = find_docs(&hub, &config.folder_id, root_path, &config.format).await?;
+
+    let mut skipped = 0;
+    let mut exports = vec![];
+
+    for doc in docs {
+        if !doc.doc_type.can_export_to(&config.format) {
+            println!(
+                "Skipping '{}', a {} can't be exported to {}",
+                doc.name, doc.doc_type, config.format
+            );
+            skipped += 1;
+        } else if doc.file_path.exists() && !config.overwrite {
+            println!(
+                "Skipping '{}', {} already exists",
+                doc.name,
+                doc.file_path.display()
+            );
+            skipped += 1;
+        } else {
+            exports.push(doc);
+        }
+    }
+
+    let mime_type = config
+        .format
+        .get_export_mime()
+        .ok_or(Error::GetFileExtensionMime(config.format.clone()))?;
+
+    let results: Vec<Result<(), Error>> = stream::iter(exports)
+        .map(|doc| export_doc(&hub, doc, &mime_type))
+        .buffer_unordered(config.parallel.max(1))
+        .collect()
+        .await;
+
+    let mut exported = 0;
+    let mut failed = 0;
+
+    for result in results {
+        match result {
+            Ok(()) => exported += 1,
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                failed += 1;
+            }
+        }
+    }
+
+    println!(
+        "Exported {} file(s) to {}, skipped {}",
+        exported,
+        config.destination.display(),
+        skipped
+    );
+
+    if failed > 0 {
+        Err(Error::Failed(failed))
+    } else {
+        Ok(())
+    }
+}
+
+// Walks the folder and returns the Google documents in it, with the path they
+// are exported to. Binary files are left out, use `files download --recursive` for those
+async fn find_docs(
+    hub: &Hub,
+    folder_id: &str,
+    root_path: PathBuf,
+    format: &FileExtension,
+) -> Result<Vec<Doc>, Error> {
+    let mut docs = vec![];
+    let mut folders = vec![(folder_id.to_string(), root_path)];
+
+    while let Some((folder_id, path)) = folders.pop() {
+        let files = list::list_files(
+            hub,
+            &list::ListFilesConfig {
+                query: ListQuery::FilesInFolder { folder_id },
+                order_by: Default::default(),
+                scope: Default::default(),
+                max_files: usize::MAX,
+            },
+        )
+        .await
+        .map_err(Error::ListFiles)?;
+
+        for file in files {
+            let id = file.id.clone().unwrap_or_default();
+            let name = file.name.clone().unwrap_or_default();
+            let safe_name = file_name::sanitize(&name, file_name::DEFAULT_REPLACEMENT);
+
+            if drive_file::is_directory(&file) {
+                folders.push((id, path.join(safe_name)));
+            } else if let Some(doc_type) =
+                file.mime_type.as_deref().and_then(DocType::from_mime_type)
+            {
+                docs.push(Doc {
+                    id,
+                    name,
+                    doc_type,
+                    file_path: path.join(format!("{}.{}", safe_name, format)),
+                });
+            }
+        }
+    }
+
+    Ok(docs)
+}
+
+async fn export_doc(hub: &Hub, doc: Doc, mime_type: &mime::Mime) -> Result<(), Error> {
+    if let Some(dir) = doc.file_path.parent() {
+        fs::create_dir_all(dir).map_err(|err| Error::CreateDirectory(dir.to_path_buf(), err))?;
+    }
+
+    let body = files::export::export_file(hub, &doc.id, mime_type)
+        .await
+        .map_err(|err| Error::ExportFile(doc.name.clone(), err))?;
+
+    files::download::save_body_to_file(body, &doc.file_path, None)
+        .await
+        .map_err(|err| Error::SaveFile(doc.file_path.clone(), err))?;
+
+    println!("Exported '{}' to {}", doc.name, doc.file_path.display());
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    GetFile(google_drive3::Error),
+    NotADirectory(String),
+    ListFiles(list::Error),
+    GetFileExtensionMime(FileExtension),
+    CreateDirectory(PathBuf, io::Error),
+    ExportFile(String, google_drive3::Error),
+    SaveFile(PathBuf, files::download::Error),
+    Failed(usize),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::GetFile(err) => write!(f, "Failed to get file: {}", err),
+            Error::NotADirectory(name) => write!(f, "'{}' is not a directory", name),
+            Error::ListFiles(err) => write!(f, "{}", err),
+            Error::GetFileExtensionMime(extension) => write!(
+                f,
+                "Failed to get mime type from file extension: {}",
+                extension
+            ),
+            Error::CreateDirectory(path, err) => {
+                write!(
+                    f,
+                    "Failed to create directory '{}': {}",
+                    path.display(),
+                    err
+                )
+            }
+            Error::ExportFile(name, err) => {
+                write!(f, "Failed to export '{}': {}", name, err)
+            }
+            Error::SaveFile(path, err) => {
+                write!(f, "Failed to save '{}': {}", path.display(), err)
+            }
+            Error::Failed(count) => write!(f, "{} file(s) failed to export", count),
+        }
+    }
+}
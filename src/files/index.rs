@@ -0,0 +1,474 @@
+use crate::app_config;
+use crate::app_config::AppConfig;
+use crate::common::api_stats::StatsDelegate;
+use crate::common::drive_file;
+use crate::common::hub_helper;
+use crate::files;
+use crate::files::list;
+use crate::files::list::ListFilesConfig;
+use crate::files::list::ListQuery;
+use crate::hub::Hub;
+use google_drive3::chrono;
+use google_drive3::chrono::DateTime;
+use rusqlite::params;
+use rusqlite::Connection;
+use rusqlite::OpenFlags;
+use rusqlite::OptionalExtension;
+use rusqlite::Transaction;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+const DB_NAME: &str = "index.sqlite";
+const MAX_CHANGES_PAGE_SIZE: i32 = 1000;
+const CHANGE_FIELDS: &str = "changes(fileId,removed,file(id,name,md5Checksum,mimeType,size,createdTime,modifiedTime,parents,description,trashed)),nextPageToken,newStartPageToken";
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS files (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    mime_type TEXT,
+    size INTEGER,
+    md5 TEXT,
+    created_time TEXT,
+    modified_time TEXT,
+    parent_id TEXT,
+    description TEXT
+);
+CREATE INDEX IF NOT EXISTS files_name ON files (name);
+CREATE INDEX IF NOT EXISTS files_parent_id ON files (parent_id);
+CREATE TABLE IF NOT EXISTS state (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+";
+
+pub struct Config {
+    pub db_path: Option<PathBuf>,
+    pub folder_id: Option<String>,
+    pub full: bool,
+}
+
+// A file as it is stored in the index
+#[derive(Debug, Clone)]
+pub struct IndexedFile {
+    pub id: String,
+    pub name: String,
+    pub mime_type: Option<String>,
+    pub size: Option<i64>,
+    pub parent_name: Option<String>,
+}
+
+impl IndexedFile {
+    pub fn is_directory(&self) -> bool {
+        self.mime_type.as_deref() == Some(drive_file::MIME_TYPE_DRIVE_FOLDER)
+    }
+}
+
+impl From<IndexedFile> for google_drive3::api::File {
+    fn from(file: IndexedFile) -> Self {
+        google_drive3::api::File {
+            id: Some(file.id),
+            name: Some(file.name),
+            mime_type: file.mime_type,
+            size: file.size,
+            ..google_drive3::api::File::default()
+        }
+    }
+}
+
+// The first run crawls the drive or folder, later runs only apply what the
+// Changes API reports since the previous run
+pub async fn index(config: Config) -> Result<(), Error> {
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+
+    let db_path = match config.db_path {
+        Some(path) => path,
+        None => default_db_path()?,
+    };
+    if let Some(dir) = db_path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(|err| Error::CreateDirectory(dir.to_path_buf(), err))?;
+    }
+
+    let mut conn = Connection::open(&db_path).map_err(|err| Error::Open(db_path.clone(), err))?;
+    conn.execute_batch(SCHEMA).map_err(Error::Database)?;
+
+    let page_token = get_state(&conn, "page_token")?;
+    let indexed_folder = get_state(&conn, "folder_id")?;
+
+    match page_token {
+        Some(page_token) if !config.full && indexed_folder == config.folder_id => {
+            let (changes, new_page_token) = list_changes(&hub, &page_token).await?;
+
+            let tx = conn.transaction().map_err(Error::Database)?;
+            let applied = apply_changes(&tx, &changes, config.folder_id.as_deref())?;
+            set_state(&tx, "page_token", Some(&new_page_token))?;
+            set_state(&tx, "updated_time", Some(&chrono::Utc::now().to_rfc3339()))?;
+            tx.commit().map_err(Error::Database)?;
+
+            println!("Applied {} change(s) to {}", applied, db_path.display());
+        }
+
+        _ => {
+            // The token is taken before the crawl, so changes made during it
+            // are picked up by the next run
+            let page_token = get_start_page_token(&hub).await?;
+            let files = crawl(&hub, config.folder_id.as_deref()).await?;
+
+            let tx = conn.transaction().map_err(Error::Database)?;
+            tx.execute("DELETE FROM files", [])
+                .map_err(Error::Database)?;
+            for file in &files {
+                upsert_file(&tx, file)?;
+            }
+            set_state(&tx, "page_token", Some(&page_token))?;
+            set_state(&tx, "folder_id", config.folder_id.as_deref())?;
+            set_state(&tx, "updated_time", Some(&chrono::Utc::now().to_rfc3339()))?;
+            tx.commit().map_err(Error::Database)?;
+
+            println!("Indexed {} file(s) into {}", files.len(), db_path.display());
+        }
+    }
+
+    Ok(())
+}
+
+// The index of the current account, used when --db is not given
+pub fn default_db_path() -> Result<PathBuf, Error> {
+    let app_cfg = AppConfig::load_current_account().map_err(Error::AppConfig)?;
+    Ok(app_cfg.account_base_path().join(DB_NAME))
+}
+
+// Files whose name contains the query, case-insensitive for ascii
+pub fn search(db_path: &PathBuf, query: &str, limit: usize) -> Result<Vec<IndexedFile>, Error> {
+    let conn = open_read_only(db_path)?;
+
+    let pattern = format!(
+        "%{}%",
+        query
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    );
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT f.id, f.name, f.mime_type, f.size, p.name
+             FROM files f LEFT JOIN files p ON p.id = f.parent_id
+             WHERE f.name LIKE ?1 ESCAPE '\\'
+             ORDER BY f.name
+             LIMIT ?2",
+        )
+        .map_err(Error::Database)?;
+
+    let files = stmt
+        .query_map(params![pattern, limit as i64], |row| {
+            Ok(IndexedFile {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                mime_type: row.get(2)?,
+                size: row.get(3)?,
+                parent_name: row.get(4)?,
+            })
+        })
+        .map_err(Error::Database)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Error::Database)?;
+
+    Ok(files)
+}
+
+// When the index was last written, results older than that may be out of date
+pub fn updated_time(db_path: &PathBuf) -> Result<Option<DateTime<chrono::Utc>>, Error> {
+    let conn = open_read_only(db_path)?;
+    let updated_time = get_state(&conn, "updated_time")?;
+
+    Ok(updated_time
+        .and_then(|time| DateTime::parse_from_rfc3339(&time).ok())
+        .map(|time| time.with_timezone(&chrono::Utc)))
+}
+
+fn open_read_only(db_path: &PathBuf) -> Result<Connection, Error> {
+    if !db_path.exists() {
+        return Err(Error::NoIndex(db_path.clone()));
+    }
+
+    Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|err| Error::Open(db_path.clone(), err))
+}
+
+async fn crawl(hub: &Hub, folder_id: Option<&str>) -> Result<Vec<google_drive3::api::File>, Error> {
+    let folder_id = match folder_id {
+        Some(folder_id) => folder_id,
+        None => {
+            return list::list_files(
+                hub,
+                &ListFilesConfig {
+                    query: ListQuery::Custom("trashed = false".to_string()),
+                    order_by: Default::default(),
+                    scope: Default::default(),
+                    max_files: usize::MAX,
+                },
+            )
+            .await
+            .map_err(Error::ListFiles);
+        }
+    };
+
+    let folder = files::info::get_file(hub, folder_id)
+        .await
+        .map_err(Error::GetFile)?;
+    if !drive_file::is_directory(&folder) {
+        return Err(Error::NotADirectory(folder.name.unwrap_or_default()));
+    }
+
+    let mut collected = vec![];
+    let mut folders = vec![folder_id.to_string()];
+
+    while let Some(folder_id) = folders.pop() {
+        let files = list::list_files(
+            hub,
+            &ListFilesConfig {
+                query: ListQuery::FilesInFolder { folder_id },
+                order_by: Default::default(),
+                scope: Default::default(),
+                max_files: usize::MAX,
+            },
+        )
+        .await
+        .map_err(Error::ListFiles)?;
+
+        for file in files {
+            if drive_file::is_directory(&file) {
+                folders.push(file.id.clone().unwrap_or_default());
+            }
+            collected.push(file);
+        }
+    }
+
+    Ok(collected)
+}
+
+pub async fn get_start_page_token(hub: &Hub) -> Result<String, Error> {
+    let (_, token) = hub
+        .changes()
+        .get_start_page_token()
+        .supports_all_drives(true)
+        .add_scope(google_drive3::api::Scope::Full)
+        .delegate(&mut StatsDelegate)
+        .doit()
+        .await
+        .map_err(Error::GetStartPageToken)?;
+
+    Ok(token.start_page_token.unwrap_or_default())
+}
+
+// Returns the changes since `page_token` and the token for the next run
+pub async fn list_changes(
+    hub: &Hub,
+    page_token: &str,
+) -> Result<(Vec<google_drive3::api::Change>, String), Error> {
+    let mut collected = vec![];
+    let mut page_token = page_token.to_string();
+
+    loop {
+        let (_, change_list) = hub
+            .changes()
+            .list(&page_token)
+            .page_size(MAX_CHANGES_PAGE_SIZE)
+            .include_removed(true)
+            .supports_all_drives(true)
+            .include_items_from_all_drives(true)
+            .param("fields", CHANGE_FIELDS)
+            .add_scope(google_drive3::api::Scope::Full)
+            .delegate(&mut StatsDelegate)
+            .doit()
+            .await
+            .map_err(Error::ListChanges)?;
+
+        if let Some(mut changes) = change_list.changes {
+            collected.append(&mut changes);
+        }
+
+        match (
+            change_list.next_page_token,
+            change_list.new_start_page_token,
+        ) {
+            (Some(next_page_token), _) => page_token = next_page_token,
+            (None, Some(new_start_page_token)) => return Ok((collected, new_start_page_token)),
+            (None, None) => return Ok((collected, page_token)),
+        }
+    }
+}
+
+// Changes come oldest first, so a new folder is indexed before the files in
+// it. When only a folder is indexed, files are kept if their parent is indexed
+fn apply_changes(
+    tx: &Transaction,
+    changes: &[google_drive3::api::Change],
+    folder_id: Option<&str>,
+) -> Result<usize, Error> {
+    let mut applied = 0;
+
+    for change in changes {
+        let file_id = match &change.file_id {
+            Some(file_id) => file_id,
+            None => continue,
+        };
+
+        let file = change
+            .file
+            .as_ref()
+            .filter(|_| change.removed != Some(true))
+            .filter(|file| file.trashed != Some(true));
+
+        let keep = match (file, folder_id) {
+            (None, _) => false,
+            (Some(_), None) => true,
+            (Some(file), Some(folder_id)) => match first_parent(file) {
+                Some(parent_id) => parent_id == folder_id || is_indexed(tx, &parent_id)?,
+                None => false,
+            },
+        };
+
+        match file {
+            Some(file) if keep => upsert_file(tx, file)?,
+            // Removing a folder also removes what was indexed under it, Drive
+            // doesn't always report a change for each of them
+            _ => delete_tree(tx, file_id)?,
+        }
+
+        applied += 1;
+    }
+
+    Ok(applied)
+}
+
+fn upsert_file(tx: &Transaction, file: &google_drive3::api::File) -> Result<(), Error> {
+    tx.prepare_cached(
+        "INSERT OR REPLACE INTO files
+         (id, name, mime_type, size, md5, created_time, modified_time, parent_id, description)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+    )
+    .and_then(|mut stmt| {
+        stmt.execute(params![
+            file.id,
+            file.name.clone().unwrap_or_default(),
+            file.mime_type,
+            file.size,
+            file.md5_checksum,
+            file.created_time.map(|time| time.to_rfc3339()),
+            file.modified_time.map(|time| time.to_rfc3339()),
+            first_parent(file),
+            file.description,
+        ])
+    })
+    .map_err(Error::Database)?;
+
+    Ok(())
+}
+
+fn delete_tree(tx: &Transaction, file_id: &str) -> Result<(), Error> {
+    tx.execute(
+        "WITH RECURSIVE tree(id) AS (
+             SELECT ?1
+             UNION SELECT files.id FROM files JOIN tree ON files.parent_id = tree.id
+         )
+         DELETE FROM files WHERE id IN tree",
+        params![file_id],
+    )
+    .map_err(Error::Database)?;
+
+    Ok(())
+}
+
+fn is_indexed(tx: &Transaction, file_id: &str) -> Result<bool, Error> {
+    tx.query_row(
+        "SELECT 1 FROM files WHERE id = ?1",
+        params![file_id],
+        |_| Ok(()),
+    )
+    .optional()
+    .map(|found| found.is_some())
+    .map_err(Error::Database)
+}
+
+fn first_parent(file: &google_drive3::api::File) -> Option<String> {
+    file.parents
+        .as_ref()
+        .and_then(|parents| parents.first().cloned())
+}
+
+fn get_state(conn: &Connection, key: &str) -> Result<Option<String>, Error> {
+    conn.query_row(
+        "SELECT value FROM state WHERE key = ?1",
+        params![key],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(Error::Database)
+}
+
+fn set_state(tx: &Transaction, key: &str, value: Option<&str>) -> Result<(), Error> {
+    match value {
+        Some(value) => tx.execute(
+            "INSERT OR REPLACE INTO state (key, value) VALUES (?1, ?2)",
+            params![key, value],
+        ),
+        None => tx.execute("DELETE FROM state WHERE key = ?1", params![key]),
+    }
+    .map_err(Error::Database)?;
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    AppConfig(app_config::Error),
+    CreateDirectory(PathBuf, io::Error),
+    Open(PathBuf, rusqlite::Error),
+    Database(rusqlite::Error),
+    GetFile(google_drive3::Error),
+    NotADirectory(String),
+    ListFiles(list::Error),
+    GetStartPageToken(google_drive3::Error),
+    ListChanges(google_drive3::Error),
+    NoIndex(PathBuf),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::AppConfig(err) => write!(f, "{}", err),
+            Error::CreateDirectory(path, err) => write!(
+                f,
+                "Failed to create directory '{}': {}",
+                path.display(),
+                err
+            ),
+            Error::Open(path, err) => {
+                write!(f, "Failed to open index '{}': {}", path.display(), err)
+            }
+            Error::Database(err) => write!(f, "Index database error: {}", err),
+            Error::GetFile(err) => write!(f, "Failed to get file: {}", err),
+            Error::NotADirectory(name) => write!(f, "'{}' is not a directory", name),
+            Error::ListFiles(err) => write!(f, "{}", err),
+            Error::GetStartPageToken(err) => {
+                write!(f, "Failed to get start page token: {}", err)
+            }
+            Error::ListChanges(err) => write!(f, "Failed to list changes: {}", err),
+            Error::NoIndex(path) => write!(
+                f,
+                "No index at '{}', run `gdrive files index` first",
+                path.display()
+            ),
+        }
+    }
+}
@@ -1,33 +1,108 @@
+use crate::app_config::NumberFormat;
+use crate::common::api_cache;
+use crate::common::api_stats::StatsDelegate;
 use google_drive3::chrono;
 use google_drive3::chrono::DateTime;
-use human_bytes::human_bytes;
 
+use crate::common::drive_file;
+use crate::common::hash_writer::Checksums;
 use crate::common::hub_helper;
+use crate::common::number_format;
+use crate::hub;
 use crate::hub::Hub;
+use std::collections::HashMap;
 use std::error;
 use std::fmt::Display;
 use std::fmt::Formatter;
 
+const DETAIL_FIELDS: &str = "id,name,size,quotaBytesUsed,createdTime,modifiedTime,md5Checksum,mimeType,parents,shared,description,webContentLink,webViewLink,shortcutDetails(targetId,targetMimeType),owners(displayName,emailAddress),permissions(type,role,emailAddress,domain),capabilities(canEdit,canShare,canComment,canDownload),exportLinks";
+
 pub struct Config {
     pub file_id: String,
     pub size_in_bytes: bool,
+    pub output: Output,
+    pub use_cache: bool,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Output {
+    Fields,
+    Json,
+    // Only the value of the named field, for scripting
+    Field(String),
 }
 
 pub async fn info(config: Config) -> Result<(), Error> {
     let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
 
-    let file = get_file(&hub, &config.file_id)
+    let file = get_file_details(&hub, &config.file_id, config.use_cache)
         .await
         .map_err(Error::GetFile)?;
+    let path = get_path(&hub, &file, config.use_cache).await;
+
+    let checksums = if drive_file::is_binary(&file) {
+        get_checksums(&hub, &config.file_id, config.use_cache)
+            .await
+            .map_err(Error::GetChecksums)?
+    } else {
+        Checksums::default()
+    };
 
-    let fields = prepare_fields(
+    if config.output == Output::Json {
+        let mut value = serde_json::to_value(&file).map_err(Error::Serialize)?;
+        if let Some(object) = value.as_object_mut() {
+            object.insert(String::from("path"), serde_json::Value::String(path));
+            for (key, checksum) in [
+                ("sha1Checksum", &checksums.sha1_checksum),
+                ("sha256Checksum", &checksums.sha256_checksum),
+            ] {
+                if let Some(checksum) = checksum {
+                    object.insert(key.to_string(), serde_json::Value::String(checksum.clone()));
+                }
+            }
+        }
+        let json = serde_json::to_string_pretty(&value).map_err(Error::Serialize)?;
+        println!("{}", json);
+        return Ok(());
+    }
+
+    let mut fields = prepare_fields(
         &file,
         &DisplayConfig {
             size_in_bytes: config.size_in_bytes,
+            ..DisplayConfig::default()
         },
     );
+    let md5_index = fields
+        .iter()
+        .position(|field| field.name == "MD5")
+        .map_or(fields.len(), |index| index + 1);
+    fields.splice(
+        md5_index..md5_index,
+        [
+            Field {
+                name: String::from("SHA1"),
+                value: checksums.sha1_checksum,
+            },
+            Field {
+                name: String::from("SHA256"),
+                value: checksums.sha256_checksum,
+            },
+        ],
+    );
+    fields.extend(prepare_detail_fields(&file, path));
 
-    print_fields(&fields);
+    if let Output::Field(name) = config.output {
+        let field = fields
+            .iter()
+            .find(|field| field.name.eq_ignore_ascii_case(&name))
+            .ok_or_else(|| Error::UnknownField(name, field_names(&fields)))?;
+        if let Some(value) = &field.value {
+            println!("{}", value);
+        }
+    } else {
+        print_fields(&fields);
+    }
 
     Ok(())
 }
@@ -42,12 +117,67 @@ pub async fn get_file(
         .param("fields", "id,name,size,createdTime,modifiedTime,md5Checksum,mimeType,parents,shared,description,webContentLink,webViewLink,shortcutDetails(targetId,targetMimeType)")
         .supports_all_drives(true)
         .add_scope(google_drive3::api::Scope::Full)
+        .delegate(&mut StatsDelegate)
         .doit()
         .await?;
 
     Ok(file)
 }
 
+// Fetched through the api cache, `files info` is often run in loops
+pub async fn get_file_details(
+    hub: &Hub,
+    file_id: &str,
+    use_cache: bool,
+) -> Result<google_drive3::api::File, hub::RequestError> {
+    api_cache::get_json(hub, &details_url(file_id), use_cache).await
+}
+
+pub fn details_url(file_id: &str) -> String {
+    hub::metadata_url(file_id, DETAIL_FIELDS)
+}
+
+// The generated api predates sha1Checksum and sha256Checksum, so the
+// checksums are fetched without it
+pub async fn get_checksums(
+    hub: &Hub,
+    file_id: &str,
+    use_cache: bool,
+) -> Result<Checksums, hub::RequestError> {
+    let url = hub::metadata_url(file_id, "md5Checksum,sha1Checksum,sha256Checksum");
+    api_cache::get_json(hub, &url, use_cache).await
+}
+
+// Folder names from the top down to the file, i.e. My Drive/Photos/cat.jpg.
+// Stops at the first parent that isn't accessible
+async fn get_path(hub: &Hub, file: &google_drive3::api::File, use_cache: bool) -> String {
+    let mut names = vec![file.name.clone().unwrap_or_default()];
+    let mut parent_id = first_parent(file);
+
+    while let Some(id) = parent_id {
+        let url = hub::metadata_url(&id, "name,parents");
+        match api_cache::get_json::<google_drive3::api::File>(hub, &url, use_cache).await {
+            Ok(folder) => {
+                names.push(folder.name.clone().unwrap_or_default());
+                parent_id = first_parent(&folder);
+            }
+            Err(_) => {
+                names.push(String::from("..."));
+                break;
+            }
+        }
+    }
+
+    names.reverse();
+    names.join("/")
+}
+
+fn first_parent(file: &google_drive3::api::File) -> Option<String> {
+    file.parents
+        .as_ref()
+        .and_then(|parents| parents.first().cloned())
+}
+
 pub fn print_fields(fields: &Vec<Field>) {
     for field in fields {
         if let Some(value) = &field.value {
@@ -56,9 +186,20 @@ pub fn print_fields(fields: &Vec<Field>) {
     }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct DisplayConfig {
     pub size_in_bytes: bool,
+    pub number_format: NumberFormat,
+}
+
+// Uses the number format from the display settings
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        DisplayConfig {
+            size_in_bytes: false,
+            number_format: number_format::current().clone(),
+        }
+    }
 }
 
 pub struct Field {
@@ -84,6 +225,14 @@ pub fn prepare_fields(file: &google_drive3::api::File, config: &DisplayConfig) -
             name: String::from("Size"),
             value: file.size.map(|bytes| format_bytes(bytes, config)),
         },
+        // Storage the file uses, Google documents use none and trashed files
+        // still count
+        Field {
+            name: String::from("Quota"),
+            value: file
+                .quota_bytes_used
+                .map(|bytes| format_bytes(bytes, config)),
+        },
         Field {
             name: String::from("Created"),
             value: file.created_time.map(format_date_time),
@@ -96,6 +245,10 @@ pub fn prepare_fields(file: &google_drive3::api::File, config: &DisplayConfig) -
             name: String::from("MD5"),
             value: file.md5_checksum.clone(),
         },
+        Field {
+            name: String::from("Description"),
+            value: file.description.clone().filter(|d| !d.is_empty()),
+        },
         Field {
             name: String::from("Shared"),
             value: file.shared.map(format_bool),
@@ -111,6 +264,100 @@ pub fn prepare_fields(file: &google_drive3::api::File, config: &DisplayConfig) -
     ]
 }
 
+fn prepare_detail_fields(file: &google_drive3::api::File, path: String) -> Vec<Field> {
+    let capabilities = file.capabilities.as_ref();
+
+    vec![
+        Field {
+            name: String::from("Path"),
+            value: Some(path),
+        },
+        Field {
+            name: String::from("Owners"),
+            value: file
+                .owners
+                .as_ref()
+                .map(|owners| format_list(&owners.iter().map(format_user).collect())),
+        },
+        Field {
+            name: String::from("Permissions"),
+            value: file.permissions.as_ref().map(|permissions| {
+                format_list(&permissions.iter().map(format_permission).collect())
+            }),
+        },
+        Field {
+            name: String::from("CanEdit"),
+            value: capabilities.and_then(|c| c.can_edit).map(format_bool),
+        },
+        Field {
+            name: String::from("CanShare"),
+            value: capabilities.and_then(|c| c.can_share).map(format_bool),
+        },
+        Field {
+            name: String::from("CanComment"),
+            value: capabilities.and_then(|c| c.can_comment).map(format_bool),
+        },
+        Field {
+            name: String::from("CanDownload"),
+            value: capabilities.and_then(|c| c.can_download).map(format_bool),
+        },
+        Field {
+            name: String::from("ShortcutTarget"),
+            value: file
+                .shortcut_details
+                .as_ref()
+                .and_then(|details| details.target_id.clone()),
+        },
+        Field {
+            name: String::from("ExportLinks"),
+            value: file.export_links.as_ref().map(format_export_links),
+        },
+    ]
+}
+
+fn field_names(fields: &[Field]) -> String {
+    fields
+        .iter()
+        .map(|field| field.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn format_user(user: &google_drive3::api::User) -> String {
+    match (&user.display_name, &user.email_address) {
+        (Some(name), Some(email)) => format!("{} <{}>", name, email),
+        (Some(name), None) => name.clone(),
+        (None, Some(email)) => email.clone(),
+        (None, None) => String::from("Unknown"),
+    }
+}
+
+// i.e. `user:alice@example.com (writer)` or `anyone (reader)`
+fn format_permission(permission: &google_drive3::api::Permission) -> String {
+    let kind = permission.type_.clone().unwrap_or_default();
+    let role = permission.role.clone().unwrap_or_default();
+    let target = permission
+        .email_address
+        .as_ref()
+        .or(permission.domain.as_ref());
+
+    match target {
+        Some(target) => format!("{}:{} ({})", kind, target, role),
+        None => format!("{} ({})", kind, role),
+    }
+}
+
+// One export format per line, sorted by mime type
+fn format_export_links(links: &HashMap<String, String>) -> String {
+    let mut links: Vec<_> = links.iter().collect();
+    links.sort();
+    links
+        .iter()
+        .map(|(mime_type, url)| format!("{} {}", mime_type, url))
+        .collect::<Vec<_>>()
+        .join("\n  ")
+}
+
 // TODO: move to common
 pub fn format_bool(b: bool) -> String {
     if b {
@@ -128,10 +375,15 @@ pub fn format_bytes(bytes: i64, config: &DisplayConfig) -> String {
     if config.size_in_bytes {
         bytes.to_string()
     } else {
-        human_bytes(bytes as f64)
+        number_format::format_bytes(bytes.max(0) as u64, &config.number_format)
     }
 }
 
+// Groups digits by thousands, i.e. 1204 -> 1,204
+pub fn format_count(count: u64) -> String {
+    number_format::format_count(count, number_format::current())
+}
+
 pub fn format_date_time(utc_time: DateTime<chrono::Utc>) -> String {
     let local_time: DateTime<chrono::Local> = DateTime::from(utc_time);
     local_time.format("%Y-%m-%d %H:%M:%S").to_string()
@@ -140,7 +392,10 @@ pub fn format_date_time(utc_time: DateTime<chrono::Utc>) -> String {
 #[derive(Debug)]
 pub enum Error {
     Hub(hub_helper::Error),
-    GetFile(google_drive3::Error),
+    GetFile(hub::RequestError),
+    Serialize(serde_json::Error),
+    UnknownField(String, String),
+    GetChecksums(hub::RequestError),
 }
 
 impl error::Error for Error {}
@@ -150,6 +405,11 @@ impl Display for Error {
         match self {
             Error::Hub(err) => write!(f, "{}", err),
             Error::GetFile(err) => write!(f, "Failed getting file: {}", err),
+            Error::Serialize(err) => write!(f, "Failed to serialize file: {}", err),
+            Error::GetChecksums(err) => write!(f, "Failed to get checksums: {}", err),
+            Error::UnknownField(name, valid) => {
+                write!(f, "Unknown field '{}', valid fields are: {}", name, valid)
+            }
         }
     }
 }
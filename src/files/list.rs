@@ -1,9 +1,14 @@
+use crate::common::api_cache;
+use crate::common::api_stats::StatsDelegate;
 use crate::common::drive_file;
+use crate::common::drive_query::Query;
+use crate::common::file_filter::FileFilter;
 use crate::common::hub_helper;
 use crate::common::table;
 use crate::common::table::Table;
 use crate::files;
 use crate::files::info::DisplayConfig;
+use crate::hub;
 use crate::hub::Hub;
 use std::cmp::min;
 use std::error;
@@ -12,31 +17,53 @@ use std::fmt::Display;
 use std::fmt::Formatter;
 use std::io;
 use std::str::FromStr;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
 
 const MAX_PAGE_SIZE: usize = 1000;
+const LIST_FIELDS: &str = "files(id,name,md5Checksum,mimeType,size,createdTime,modifiedTime,parents,description,trashed,quotaBytesUsed,capabilities(canRename,canTrash,canUntrash)),nextPageToken";
 
 pub struct Config {
     pub query: ListQuery,
     pub order_by: ListSortOrder,
+    pub scope: ListScope,
     pub max_files: usize,
     pub skip_header: bool,
     pub truncate_name: bool,
     pub field_separator: String,
+    pub filter: FileFilter,
+    pub use_cache: bool,
 }
 
 pub async fn list(config: Config) -> Result<(), Error> {
     let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
-    let files = list_files(
+
+    // Drive can't filter by size or missing parents, so all files are listed and filtered here
+    let orphaned = matches!(config.query, ListQuery::Orphaned);
+    let max_files = if config.filter.is_empty() && !orphaned {
+        config.max_files
+    } else {
+        usize::MAX
+    };
+
+    let files: Vec<google_drive3::api::File> = list_files_cached(
         &hub,
         &ListFilesConfig {
             query: config.query.clone(),
             order_by: config.order_by.clone(),
-            max_files: config.max_files,
+            scope: config.scope.clone(),
+            max_files,
         },
+        config.use_cache,
     )
-    .await?;
+    .await?
+    .into_iter()
+    .filter(|file| config.filter.matches_drive_file(file))
+    .filter(|file| !orphaned || is_orphaned(file))
+    .take(config.max_files)
+    .collect();
 
-    let mut values: Vec<[String; 5]> = vec![];
+    let mut values: Vec<[String; 6]> = vec![];
 
     for file in files {
         let file_type = simplified_file_type(&file);
@@ -52,11 +79,15 @@ pub async fn list(config: Config) -> Result<(), Error> {
             file.created_time
                 .map(files::info::format_date_time)
                 .unwrap_or_default(),
+            file.quota_bytes_used
+                .map(|bytes| files::info::format_bytes(bytes, &DisplayConfig::default()))
+                .unwrap_or_default(),
         ])
     }
 
+    // Quota is last so scripts that cut the other columns keep working
     let table = Table {
-        header: ["Id", "Name", "Type", "Size", "Created"],
+        header: ["Id", "Name", "Type", "Size", "Created", "Quota"],
         values,
     };
 
@@ -75,17 +106,32 @@ pub async fn list(config: Config) -> Result<(), Error> {
 pub struct ListFilesConfig {
     pub query: ListQuery,
     pub order_by: ListSortOrder,
+    pub scope: ListScope,
     pub max_files: usize,
 }
 
 pub async fn list_files(
     hub: &Hub,
     config: &ListFilesConfig,
+) -> Result<Vec<google_drive3::api::File>, Error> {
+    list_files_until_cancelled(hub, config, &AtomicBool::new(false)).await
+}
+
+// Stops before the next page once cancel is set, for listings in the
+// background that are no longer needed
+pub async fn list_files_until_cancelled(
+    hub: &Hub,
+    config: &ListFilesConfig,
+    cancel: &AtomicBool,
 ) -> Result<Vec<google_drive3::api::File>, Error> {
     let mut collected_files: Vec<google_drive3::api::File> = vec![];
     let mut next_page_token: Option<String> = None;
 
     loop {
+        if cancel.load(Ordering::SeqCst) {
+            return Err(Error::Cancelled);
+        }
+
         let max_files = config.max_files - collected_files.len();
         let page_size = min(MAX_PAGE_SIZE, max_files);
 
@@ -95,17 +141,23 @@ pub async fn list_files(
             req = req.page_token(&token);
         }
 
+        if let Some(corpora) = &config.scope.corpora {
+            req = req.corpora(corpora.name());
+        }
+
+        if let Some(drive_id) = config.scope.drive_id() {
+            req = req.drive_id(drive_id);
+        }
+
         let (_, file_list) = req
             .page_size(page_size as i32)
             .q(&config.query.to_string())
             .order_by(&config.order_by.to_string())
             .add_scope(google_drive3::api::Scope::Full)
             .supports_all_drives(true)
-            .include_items_from_all_drives(true)
-            .param(
-                "fields",
-                "files(id,name,md5Checksum,mimeType,size,createdTime,parents),nextPageToken",
-            )
+            .include_items_from_all_drives(config.scope.include_items_from_all_drives())
+            .param("fields", LIST_FIELDS)
+            .delegate(&mut StatsDelegate)
             .doit()
             .await
             .map_err(Error::ListFiles)?;
@@ -125,6 +177,154 @@ pub async fn list_files(
     Ok(collected_files[0..max_files].to_vec())
 }
 
+// Like list_files, with the pages fetched through the api cache. Only used by
+// `files list`, the other commands need a listing that is up to date
+async fn list_files_cached(
+    hub: &Hub,
+    config: &ListFilesConfig,
+    use_cache: bool,
+) -> Result<Vec<google_drive3::api::File>, Error> {
+    let mut collected_files: Vec<google_drive3::api::File> = vec![];
+    let mut next_page_token: Option<String> = None;
+
+    loop {
+        let max_files = config.max_files - collected_files.len();
+        let page_size = min(MAX_PAGE_SIZE, max_files).to_string();
+        let query = config.query.to_string();
+        let order_by = config.order_by.to_string();
+
+        let include_items_from_all_drives =
+            config.scope.include_items_from_all_drives().to_string();
+
+        let mut params = vec![
+            ("pageSize", page_size.as_str()),
+            ("q", query.as_str()),
+            ("orderBy", order_by.as_str()),
+            ("supportsAllDrives", "true"),
+            (
+                "includeItemsFromAllDrives",
+                include_items_from_all_drives.as_str(),
+            ),
+            ("fields", LIST_FIELDS),
+        ];
+        if let Some(corpora) = &config.scope.corpora {
+            params.push(("corpora", corpora.name()));
+        }
+        if let Some(drive_id) = config.scope.drive_id() {
+            params.push(("driveId", drive_id));
+        }
+        if let Some(token) = &next_page_token {
+            params.push(("pageToken", token));
+        }
+
+        let file_list: google_drive3::api::FileList =
+            api_cache::get_json(hub, &hub::list_url(&params), use_cache)
+                .await
+                .map_err(Error::ListFilesRequest)?;
+
+        if let Some(mut files) = file_list.files {
+            collected_files.append(&mut files);
+        }
+
+        next_page_token = file_list.next_page_token;
+
+        if collected_files.len() >= config.max_files || next_page_token.is_none() {
+            break;
+        }
+    }
+
+    let max_files = min(config.max_files, collected_files.len());
+    Ok(collected_files[0..max_files].to_vec())
+}
+
+// The collections of files a query is run against. Without corpora Drive
+// searches the user corpus, where files of shared drives only show up when they
+// were shared with the user directly
+#[derive(Debug, Clone)]
+pub struct ListScope {
+    pub corpora: Option<Corpora>,
+    pub include_items_from_all_drives: bool,
+}
+
+impl Default for ListScope {
+    fn default() -> Self {
+        ListScope {
+            corpora: None,
+            include_items_from_all_drives: true,
+        }
+    }
+}
+
+impl ListScope {
+    pub fn drive(drive_id: &str) -> ListScope {
+        ListScope {
+            corpora: Some(Corpora::Drive {
+                drive_id: drive_id.to_string(),
+            }),
+            ..ListScope::default()
+        }
+    }
+
+    // Drive rejects the shared drive corpora without shared drive items
+    fn include_items_from_all_drives(&self) -> bool {
+        self.include_items_from_all_drives
+            || matches!(
+                self.corpora,
+                Some(Corpora::Drive { .. }) | Some(Corpora::AllDrives)
+            )
+    }
+
+    fn drive_id(&self) -> Option<&str> {
+        match &self.corpora {
+            Some(Corpora::Drive { drive_id }) => Some(drive_id),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Corpora {
+    // Files the user owns or that have been shared with them
+    User,
+    // Files shared with the user's domain
+    Domain,
+    // The files of one shared drive
+    Drive { drive_id: String },
+    // Everything the user can see, Drive may return incomplete results
+    AllDrives,
+}
+
+impl Corpora {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Corpora::User => "user",
+            Corpora::Domain => "domain",
+            Corpora::Drive { .. } => "drive",
+            Corpora::AllDrives => "allDrives",
+        }
+    }
+}
+
+// The shared drive corpus takes the id of the drive, so it is not parsed here
+impl FromStr for Corpora {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "user" => Ok(Corpora::User),
+            "domain" => Ok(Corpora::Domain),
+            "all-drives" => Ok(Corpora::AllDrives),
+            "drive" => {
+                Err("Use --drive <DRIVE_ID> to list the files of a shared drive".to_string())
+            }
+            _ => Err(format!(
+                "'{}' is not a valid corpora, valid corpora are: user, domain, all-drives",
+                s
+            )),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub enum ListQuery {
     #[default]
@@ -135,6 +335,22 @@ pub enum ListQuery {
     FilesInFolder {
         folder_id: String,
     },
+    // Also the files in the folder that are in the trash
+    FilesInFolderWithTrashed {
+        folder_id: String,
+    },
+    // Files anywhere whose name contains the pattern
+    NameContains {
+        pattern: String,
+    },
+    // Files owned by `me` or an email address, in a folder or anywhere
+    OwnedBy {
+        owner: String,
+        folder_id: Option<String>,
+    },
+    // Files of the current user that have no parents. Drive can't query for
+    // this, so the results have to be filtered with is_orphaned
+    Orphaned,
     Custom(String),
     None,
 }
@@ -155,15 +371,35 @@ impl Display for ListQuery {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match self {
             ListQuery::RootNotTrashed => {
-                write!(f, "'root' in parents and trashed = false")
+                write!(f, "{}", Query::folder("root").not_trashed())
             }
 
             ListQuery::FilesOnDrive { drive_id } => {
-                write!(f, "'{}' in parents and trashed = false", drive_id)
+                write!(f, "{}", Query::folder(drive_id).not_trashed())
             }
 
             ListQuery::FilesInFolder { folder_id } => {
-                write!(f, "'{}' in parents and trashed = false", folder_id)
+                write!(f, "{}", Query::folder(folder_id).not_trashed())
+            }
+
+            ListQuery::FilesInFolderWithTrashed { folder_id } => {
+                write!(f, "{}", Query::folder(folder_id))
+            }
+
+            ListQuery::NameContains { pattern } => {
+                write!(f, "{}", Query::new().name_contains(pattern).not_trashed())
+            }
+
+            ListQuery::OwnedBy { owner, folder_id } => {
+                let query = match folder_id {
+                    Some(folder_id) => Query::folder(folder_id),
+                    None => Query::new(),
+                };
+                write!(f, "{}", query.owned_by(owner).not_trashed())
+            }
+
+            ListQuery::Orphaned => {
+                write!(f, "{}", Query::new().owned_by("me").not_trashed())
             }
 
             ListQuery::Custom(query) => {
@@ -177,10 +413,21 @@ impl Display for ListQuery {
     }
 }
 
+impl From<Query> for ListQuery {
+    fn from(query: Query) -> Self {
+        if query.is_empty() {
+            ListQuery::None
+        } else {
+            ListQuery::Custom(query.to_string())
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub enum ListSortOrder {
     #[default]
     FolderModifiedName,
+    QuotaUsed,
     Custom(String),
 }
 
@@ -203,6 +450,10 @@ impl fmt::Display for ListSortOrder {
                 write!(f, "folder,modifiedTime desc,name")
             }
 
+            ListSortOrder::QuotaUsed => {
+                write!(f, "quotaBytesUsed desc,name")
+            }
+
             ListSortOrder::Custom(query) => {
                 write!(f, "{}", query)
             }
@@ -210,10 +461,42 @@ impl fmt::Display for ListSortOrder {
     }
 }
 
+// Shorthands for --sort, --order-by takes any order Drive supports
+#[derive(Debug, Clone, Copy)]
+pub enum SortBy {
+    // Storage used, largest first. Google documents use none, trashed files
+    // still count
+    Quota,
+}
+
+impl SortBy {
+    pub fn order(&self) -> ListSortOrder {
+        match self {
+            SortBy::Quota => ListSortOrder::QuotaUsed,
+        }
+    }
+}
+
+impl FromStr for SortBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "quota" => Ok(SortBy::Quota),
+            _ => Err(format!(
+                "'{}' is not a valid sort, valid sorts are: quota",
+                s
+            )),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Error {
     Hub(hub_helper::Error),
     ListFiles(google_drive3::Error),
+    ListFilesRequest(hub::RequestError),
+    Cancelled,
 }
 
 impl error::Error for Error {}
@@ -223,11 +506,20 @@ impl Display for Error {
         match self {
             Error::Hub(e) => write!(f, "{}", e),
             Error::ListFiles(e) => write!(f, "Failed to list files: {}", e),
+            Error::ListFilesRequest(e) => write!(f, "Failed to list files: {}", e),
+            Error::Cancelled => write!(f, "Listing cancelled"),
         }
     }
 }
 
-fn simplified_file_type(file: &google_drive3::api::File) -> String {
+pub fn is_orphaned(file: &google_drive3::api::File) -> bool {
+    file.parents
+        .as_ref()
+        .map(|parents| parents.is_empty())
+        .unwrap_or(true)
+}
+
+pub fn simplified_file_type(file: &google_drive3::api::File) -> String {
     if drive_file::is_directory(file) {
         String::from("folder")
     } else if drive_file::is_binary(file) {
@@ -268,3 +560,50 @@ fn truncate_middle(s: &str, max_length: usize) -> String {
 
     vec![head, tail].join("…")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_contains_escapes_pattern() {
+        let query = ListQuery::NameContains {
+            pattern: "it's \\ here".to_string(),
+        };
+        assert_eq!(
+            query.to_string(),
+            "name contains 'it\\'s \\\\ here' and trashed = false"
+        );
+    }
+
+    #[test]
+    fn owned_by_escapes_owner() {
+        let query = ListQuery::OwnedBy {
+            owner: "o'brien@example.com".to_string(),
+            folder_id: Some("folderId".to_string()),
+        };
+        assert_eq!(
+            query.to_string(),
+            "'folderId' in parents and 'o\\'brien@example.com' in owners and trashed = false"
+        );
+    }
+
+    #[test]
+    fn root_query_is_unchanged() {
+        assert_eq!(
+            ListQuery::RootNotTrashed.to_string(),
+            "'root' in parents and trashed = false"
+        );
+    }
+
+    #[test]
+    fn quota_sort_puts_the_largest_first() {
+        let order = "quota".parse::<SortBy>().unwrap().order();
+        assert_eq!(order.to_string(), "quotaBytesUsed desc,name");
+    }
+
+    #[test]
+    fn empty_query_builds_no_query() {
+        assert!(matches!(ListQuery::from(Query::new()), ListQuery::None));
+    }
+}
@@ -0,0 +1,111 @@
+use crate::common::delegate::UploadDelegateConfig;
+use crate::common::drive_file;
+use crate::common::hub_helper;
+use crate::files;
+use crate::files::info::DisplayConfig;
+use crate::files::update::PatchFile;
+use google_drive3::chrono;
+use std::error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub file_id: String,
+    pub description: Option<String>,
+    pub folder_color: Option<FolderColor>,
+    pub viewed: bool,
+}
+
+pub async fn set(config: Config) -> Result<(), Error> {
+    if config.description.is_none() && config.folder_color.is_none() && !config.viewed {
+        return Err(Error::NothingToSet);
+    }
+
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+    let delegate_config = UploadDelegateConfig::default();
+
+    let file = files::info::get_file(&hub, &config.file_id)
+        .await
+        .map_err(Error::GetFile)?;
+
+    if config.folder_color.is_some() && !drive_file::is_directory(&file) {
+        return Err(Error::NotADirectory);
+    }
+
+    let mut patch_file = PatchFile::new(config.file_id);
+
+    if let Some(description) = &config.description {
+        patch_file = patch_file.with_description(description);
+    }
+
+    if let Some(color) = &config.folder_color {
+        patch_file = patch_file.with_folder_color(&color.to_string());
+    }
+
+    if config.viewed {
+        patch_file = patch_file.with_viewed_by_me_time(chrono::Utc::now());
+    }
+
+    println!("Updating metadata of '{}'", file.name.unwrap_or_default());
+
+    let file = files::update::update_metadata(&hub, delegate_config, patch_file)
+        .await
+        .map_err(Error::Update)?;
+
+    let fields = files::info::prepare_fields(&file, &DisplayConfig::default());
+    files::info::print_fields(&fields);
+
+    Ok(())
+}
+
+// Folder color as #rrggbb. Drive picks the closest color from its own palette
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FolderColor(String);
+
+impl FromStr for FolderColor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        if hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            Ok(FolderColor(format!("#{}", hex.to_lowercase())))
+        } else {
+            Err(format!("'{}' is not a color, use #rrggbb i.e. #fad165", s))
+        }
+    }
+}
+
+impl Display for FolderColor {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    GetFile(google_drive3::Error),
+    NothingToSet,
+    NotADirectory,
+    Update(google_drive3::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::GetFile(err) => write!(f, "Failed to get file: {}", err),
+            Error::NothingToSet => write!(
+                f,
+                "Nothing to update, use --description, --folder-color or --viewed"
+            ),
+            Error::NotADirectory => write!(f, "Only folders have a color"),
+            Error::Update(err) => write!(f, "Failed to update metadata: {}", err),
+        }
+    }
+}
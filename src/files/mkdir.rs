@@ -2,8 +2,10 @@ use crate::common::delegate::UploadDelegate;
 use crate::common::delegate::UploadDelegateConfig;
 use crate::common::drive_file::MIME_TYPE_DRIVE_FOLDER;
 use crate::common::empty_file::EmptyFile;
+use crate::common::file_name;
 use crate::common::hub_helper;
 use crate::hub::Hub;
+use google_drive3::chrono;
 use std::error;
 use std::fmt::Display;
 use std::fmt::Formatter;
@@ -17,6 +19,11 @@ pub struct Config {
 }
 
 pub async fn mkdir(config: Config) -> Result<(), Error> {
+    // The name is a template, see file_name::render_template
+    let name = file_name::render_template(&config.name, None, chrono::Local::now())
+        .map_err(Error::Name)?;
+    let config = Config { name, ..config };
+
     let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
     let delegate_config = UploadDelegateConfig::default();
 
@@ -72,6 +79,7 @@ pub async fn create_directory(
 pub enum Error {
     Hub(hub_helper::Error),
     CreateDirectory(google_drive3::Error),
+    Name(String),
 }
 
 impl error::Error for Error {}
@@ -83,6 +91,7 @@ impl Display for Error {
             Error::CreateDirectory(err) => {
                 write!(f, "Failed to create directory on drive: {}", err)
             }
+            Error::Name(err) => write!(f, "Invalid name: {}", err),
         }
     }
 }
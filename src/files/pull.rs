@@ -0,0 +1,327 @@
+use crate::common::file_filter::FileFilter;
+use crate::common::hash_writer;
+use crate::common::hub_helper;
+use crate::files::download;
+use crate::hub::Hub;
+use crate::sync::plan::Action;
+use crate::sync::scan;
+use crate::sync::scan::LocalFile;
+use crate::sync::scan::RemoteTree;
+use std::collections::HashMap;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+// A one-way mirror of a Drive folder. Unlike `sync run --direction download`
+// nothing is remembered between runs, a local file is compared with Drive by
+// size and md5 every time
+pub struct Config {
+    pub folder_id: String,
+    pub local_path: PathBuf,
+    // Delete local files that are not on Drive
+    pub delete: bool,
+    pub filter: FileFilter,
+}
+
+pub async fn pull(config: Config) -> Result<(), Error> {
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+
+    if config.local_path.exists() && !config.local_path.is_dir() {
+        return Err(Error::NotADirectory(config.local_path.clone()));
+    }
+    fs::create_dir_all(&config.local_path)
+        .map_err(|err| Error::CreateDirectory(config.local_path.clone(), err))?;
+
+    println!("Comparing '{}' with Drive", config.local_path.display());
+    let mut local = scan::list_local(&config.local_path, &config.filter).map_err(Error::Scan)?;
+    let mut remote = scan::scan_remote(&hub, &config.folder_id, &config.filter)
+        .await
+        .map_err(Error::Scan)?;
+    scan::drop_excluded(&mut local, &mut remote);
+
+    let actions = plan(&local.files, &remote, config.delete, |file| {
+        hash_writer::md5_of_file(&file.path).map_err(|err| Error::Hash(file.path.clone(), err))
+    })?;
+
+    let local_only = local
+        .files
+        .keys()
+        .filter(|key| !remote.files.contains_key(*key))
+        .count();
+
+    if actions.is_empty() {
+        println!("Everything is up to date");
+    }
+
+    for action in actions {
+        match action {
+            Action::Download { key, file_id } => {
+                println!("Downloading '{}'", key);
+                let expected_md5 = remote.files.get(&key).map(|file| file.md5.clone());
+                let path = local_path(&config, &key)?;
+                download_file(&hub, &path, &file_id, expected_md5).await?;
+            }
+
+            Action::DeleteLocal { key } => {
+                println!("Deleting '{}', it is not on Drive", key);
+                let path = local_path(&config, &key)?;
+                fs::remove_file(&path).map_err(|err| Error::Remove(path, err))?;
+            }
+
+            // plan only downloads and deletes
+            _ => {}
+        }
+    }
+
+    if !config.delete && local_only > 0 {
+        println!(
+            "{} local files are not on Drive, use --delete to delete them",
+            local_only
+        );
+    }
+
+    Ok(())
+}
+
+// Keys come from Drive names, scan_remote already skips names like `..` but a
+// path outside `local_path` is never written or deleted
+fn local_path(config: &Config, key: &str) -> Result<PathBuf, Error> {
+    scan::local_path(&config.local_path, key).ok_or_else(|| Error::UnsafePath(key.to_string()))
+}
+
+// Files missing locally or different from Drive are downloaded, in the order
+// of their path. Local files are only hashed when their size matches Drive,
+// a different size is enough to tell they changed
+fn plan<F>(
+    local: &HashMap<String, LocalFile>,
+    remote: &RemoteTree,
+    delete: bool,
+    md5_of: F,
+) -> Result<Vec<Action>, Error>
+where
+    F: Fn(&LocalFile) -> Result<String, Error>,
+{
+    let mut keys: Vec<&String> = remote.files.keys().collect();
+    keys.sort();
+
+    let mut actions = vec![];
+    for key in keys {
+        let remote_file = &remote.files[key];
+        let changed = match local.get(key) {
+            None => true,
+            Some(local_file) if local_file.size != remote_file.size => true,
+            Some(local_file) => md5_of(local_file)? != remote_file.md5,
+        };
+
+        if changed {
+            actions.push(Action::Download {
+                key: key.clone(),
+                file_id: remote_file.id.clone(),
+            });
+        }
+    }
+
+    if delete {
+        let mut deleted: Vec<&String> = local
+            .keys()
+            .filter(|key| !remote.files.contains_key(*key))
+            .collect();
+        deleted.sort();
+
+        for key in deleted {
+            actions.push(Action::DeleteLocal { key: key.clone() });
+        }
+    }
+
+    Ok(actions)
+}
+
+async fn download_file(
+    hub: &Hub,
+    path: &PathBuf,
+    file_id: &str,
+    expected_md5: Option<String>,
+) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| Error::CreateDirectory(dir.to_path_buf(), err))?;
+    }
+
+    let body = download::download_file(hub, file_id)
+        .await
+        .map_err(Error::DownloadFile)?;
+    download::save_body_to_file(body, path, expected_md5)
+        .await
+        .map_err(Error::Download)
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    NotADirectory(PathBuf),
+    CreateDirectory(PathBuf, io::Error),
+    Scan(scan::Error),
+    Hash(PathBuf, io::Error),
+    DownloadFile(google_drive3::Error),
+    Download(download::Error),
+    Remove(PathBuf, io::Error),
+    UnsafePath(String),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::NotADirectory(path) => write!(f, "'{}' is not a directory", path.display()),
+            Error::CreateDirectory(path, err) => {
+                write!(
+                    f,
+                    "Failed to create directory '{}': {}",
+                    path.display(),
+                    err
+                )
+            }
+            Error::Scan(err) => write!(f, "{}", err),
+            Error::Hash(path, err) => write!(f, "Failed to hash '{}': {}", path.display(), err),
+            Error::DownloadFile(err) => write!(f, "Failed to download file: {}", err),
+            Error::Download(err) => write!(f, "{}", err),
+            Error::Remove(path, err) => {
+                write!(f, "Failed to delete '{}': {}", path.display(), err)
+            }
+            Error::UnsafePath(key) => {
+                write!(f, "'{}' is not a path inside the local directory", key)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::scan::RemoteFile;
+    use crate::test_util::MockServer;
+
+    fn local_file(size: u64, md5: &str) -> LocalFile {
+        LocalFile {
+            path: PathBuf::from(md5),
+            size,
+            modified: 0,
+            md5: md5.to_string(),
+        }
+    }
+
+    fn remote_file(id: &str, size: u64, md5: &str) -> RemoteFile {
+        RemoteFile {
+            id: id.to_string(),
+            md5: md5.to_string(),
+            size,
+            modified: 0,
+        }
+    }
+
+    #[test]
+    fn only_missing_and_changed_files_are_downloaded() {
+        let local = HashMap::from([
+            ("same.txt".to_string(), local_file(3, "aaa")),
+            ("edited.txt".to_string(), local_file(3, "bbb")),
+            ("grown.txt".to_string(), local_file(3, "ccc")),
+            ("local-only.txt".to_string(), local_file(3, "ddd")),
+        ]);
+        let remote = RemoteTree {
+            files: HashMap::from([
+                ("same.txt".to_string(), remote_file("1", 3, "aaa")),
+                ("edited.txt".to_string(), remote_file("2", 3, "xxx")),
+                ("grown.txt".to_string(), remote_file("3", 4, "yyy")),
+                ("dir/new.txt".to_string(), remote_file("4", 1, "zzz")),
+            ]),
+            ..RemoteTree::default()
+        };
+
+        let hashed = std::cell::RefCell::new(vec![]);
+        let actions = plan(&local, &remote, true, |file| {
+            hashed.borrow_mut().push(file.md5.clone());
+            Ok(file.md5.clone())
+        })
+        .unwrap();
+
+        let download = |key: &str, file_id: &str| Action::Download {
+            key: key.to_string(),
+            file_id: file_id.to_string(),
+        };
+        assert_eq!(
+            actions,
+            vec![
+                download("dir/new.txt", "4"),
+                download("edited.txt", "2"),
+                download("grown.txt", "3"),
+                Action::DeleteLocal {
+                    key: "local-only.txt".to_string()
+                },
+            ]
+        );
+
+        // The grown file has another size, it isn't hashed
+        let mut hashed = hashed.into_inner();
+        hashed.sort();
+        assert_eq!(hashed, vec!["aaa", "bbb"]);
+    }
+
+    #[test]
+    fn local_files_are_kept_without_delete() {
+        let local = HashMap::from([("local-only.txt".to_string(), local_file(3, "ddd"))]);
+
+        let actions = plan(&local, &RemoteTree::default(), false, |file| {
+            Ok(file.md5.clone())
+        })
+        .unwrap();
+
+        assert!(actions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn drive_names_that_leave_the_local_directory_are_not_pulled() {
+        let server = MockServer::start().await;
+        server.respond_json(
+            "GET",
+            "/drive/v3/files",
+            &serde_json::json!({"files": [
+                {"id": "1DoTdOt", "name": "..", "mimeType": "application/vnd.google-apps.folder"},
+                {"id": "1SlAsH", "name": "a/b", "mimeType": "text/plain", "md5Checksum": "aaa"},
+                {"id": "1NoTeS", "name": "notes.txt", "mimeType": "text/plain", "md5Checksum": "bbb"}
+            ]}),
+        );
+
+        let remote = scan::scan_remote(&server.hub().await, "1FoLdEr", &FileFilter::default())
+            .await
+            .unwrap();
+        let actions = plan(&HashMap::new(), &remote, true, |file| Ok(file.md5.clone())).unwrap();
+
+        assert_eq!(
+            actions,
+            vec![Action::Download {
+                key: "notes.txt".to_string(),
+                file_id: "1NoTeS".to_string()
+            }]
+        );
+        assert!(remote.excluded.contains(".."));
+        assert!(remote.excluded.contains("a/b"));
+
+        let config = Config {
+            folder_id: "1FoLdEr".to_string(),
+            local_path: PathBuf::from("pulled"),
+            delete: true,
+            filter: FileFilter::default(),
+        };
+        for key in ["..", "../x", "/etc/x", ""] {
+            assert!(local_path(&config, key).is_err(), "{}", key);
+        }
+        assert_eq!(
+            local_path(&config, "a/b").unwrap(),
+            PathBuf::from("pulled/a/b")
+        );
+    }
+}
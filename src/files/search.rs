@@ -0,0 +1,119 @@
+use crate::common::hub_helper;
+use crate::common::table;
+use crate::common::table::Table;
+use crate::files;
+use crate::files::index;
+use crate::files::info::DisplayConfig;
+use crate::files::list;
+use crate::files::list::ListFilesConfig;
+use crate::files::list::ListQuery;
+use crate::files::list::ListScope;
+use crate::files::list::ListSortOrder;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::io;
+use std::path::PathBuf;
+
+pub struct Config {
+    pub pattern: String,
+    pub offline: bool,
+    pub db_path: Option<PathBuf>,
+    pub max_files: usize,
+    pub skip_header: bool,
+    pub field_separator: String,
+}
+
+pub async fn search(config: Config) -> Result<(), Error> {
+    let files = if config.offline {
+        search_index(&config)?
+    } else {
+        let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+        list::list_files(
+            &hub,
+            &ListFilesConfig {
+                query: ListQuery::NameContains {
+                    pattern: config.pattern.clone(),
+                },
+                order_by: ListSortOrder::default(),
+                scope: ListScope::default(),
+                max_files: config.max_files,
+            },
+        )
+        .await
+        .map_err(Error::ListFiles)?
+    };
+
+    let mut values: Vec<[String; 4]> = vec![];
+
+    for file in files {
+        let file_type = list::simplified_file_type(&file);
+
+        values.push([
+            file.id.unwrap_or_default(),
+            file.name.unwrap_or_default(),
+            file_type,
+            file.size
+                .map(|bytes| files::info::format_bytes(bytes, &DisplayConfig::default()))
+                .unwrap_or_default(),
+        ])
+    }
+
+    let table = Table {
+        header: ["Id", "Name", "Type", "Size"],
+        values,
+    };
+
+    let _ = table::write(
+        io::stdout(),
+        table,
+        &table::DisplayConfig {
+            skip_header: config.skip_header,
+            separator: config.field_separator,
+        },
+    );
+
+    Ok(())
+}
+
+// The note goes to stderr so the results can still be piped
+fn search_index(config: &Config) -> Result<Vec<google_drive3::api::File>, Error> {
+    let db_path = match &config.db_path {
+        Some(path) => path.clone(),
+        None => index::default_db_path().map_err(Error::Index)?,
+    };
+
+    let files = index::search(&db_path, &config.pattern, config.max_files).map_err(Error::Index)?;
+
+    match index::updated_time(&db_path).map_err(Error::Index)? {
+        Some(time) => eprintln!(
+            "Results are from the local index updated {}, they may be out of date",
+            files::info::format_date_time(time)
+        ),
+        None => eprintln!("Results are from the local index, they may be out of date"),
+    }
+
+    Ok(files
+        .into_iter()
+        .map(google_drive3::api::File::from)
+        .collect())
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    ListFiles(list::Error),
+    Index(index::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::ListFiles(err) => write!(f, "{}", err),
+            Error::Index(err) => write!(f, "{}", err),
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use crate::common::delegate::UploadDelegate;
+use crate::common::delegate::UploadDelegateConfig;
+use crate::common::drive_file::MIME_TYPE_DRIVE_SHORTCUT;
+use crate::common::empty_file::EmptyFile;
+use crate::common::hub_helper;
+use crate::files;
+use crate::hub::Hub;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub id: Option<String>,
+    pub name: String,
+    pub parents: Option<Vec<String>>,
+    pub target_id: String,
+}
+
+pub struct ShortcutConfig {
+    pub target_id: String,
+    pub parent: Option<String>,
+    pub name: Option<String>,
+    pub print_only_id: bool,
+}
+
+pub async fn shortcut(config: ShortcutConfig) -> Result<(), Error> {
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+    let delegate_config = UploadDelegateConfig::default();
+
+    let target = files::info::get_file(&hub, &config.target_id)
+        .await
+        .map_err(Error::GetTarget)?;
+
+    let name = config
+        .name
+        .unwrap_or_else(|| target.name.unwrap_or_default());
+
+    let file = create_shortcut(
+        &hub,
+        &Config {
+            id: None,
+            name: name.clone(),
+            parents: config.parent.map(|parent| vec![parent]),
+            target_id: config.target_id,
+        },
+        delegate_config,
+    )
+    .await
+    .map_err(Error::CreateShortcut)?;
+
+    if config.print_only_id {
+        print!("{}", file.id.unwrap_or_default())
+    } else {
+        println!(
+            "Created shortcut '{}' with id: {}",
+            name,
+            file.id.unwrap_or_default()
+        );
+    }
+
+    Ok(())
+}
+
+pub async fn create_shortcut(
+    hub: &Hub,
+    config: &Config,
+    delegate_config: UploadDelegateConfig,
+) -> Result<google_drive3::api::File, google_drive3::Error> {
+    let dst_file = google_drive3::api::File {
+        id: config.id.clone(),
+        name: Some(config.name.clone()),
+        parents: config.parents.clone(),
+        mime_type: Some(MIME_TYPE_DRIVE_SHORTCUT.to_string()),
+        shortcut_details: Some(google_drive3::api::FileShortcutDetails {
+            target_id: Some(config.target_id.clone()),
+            ..google_drive3::api::FileShortcutDetails::default()
+        }),
+        ..google_drive3::api::File::default()
+    };
+
+    let mut delegate = UploadDelegate::new(delegate_config);
+
+    let req = hub
+        .files()
+        .create(dst_file)
+        .param(
+            "fields",
+            "id,name,mimeType,parents,shortcutDetails(targetId,targetMimeType)",
+        )
+        .add_scope(google_drive3::api::Scope::Full)
+        .delegate(&mut delegate)
+        .supports_all_drives(true);
+
+    let empty_file = EmptyFile();
+    let mime_type: mime::Mime = MIME_TYPE_DRIVE_SHORTCUT.parse().unwrap();
+
+    let (_, file) = req.upload(empty_file, mime_type).await?;
+
+    Ok(file)
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    GetTarget(google_drive3::Error),
+    CreateShortcut(google_drive3::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::GetTarget(err) => write!(f, "Failed to get shortcut target: {}", err),
+            Error::CreateShortcut(err) => write!(f, "Failed to create shortcut: {}", err),
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use crate::common::api_stats::StatsDelegate;
+use crate::common::deletion_journal;
+use crate::common::hub_helper;
+use crate::common::path_resolver;
+use crate::files::delete;
+use crate::files::delete::Operation;
+use crate::hub::Hub;
+use google_drive3::hyper;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+pub async fn undelete_last() -> Result<(), Error> {
+    let mut entry = last_trashed()?;
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+
+    // A file that was deleted for good or restored another way since is
+    // recorded as such, so the run goes on with the one trashed before it
+    loop {
+        let status = trash_status(&hub, &entry.file_id)
+            .await
+            .map_err(|err| Error::GetFile(entry.name.clone(), err))?;
+
+        let operation = match status {
+            TrashStatus::Trashed => {
+                let restore = delete::apply_and_record(
+                    &hub,
+                    &entry.file_id,
+                    &entry.name,
+                    &entry.parents,
+                    Operation::Untrash,
+                );
+                match restore.await {
+                    Ok(journal_error) => {
+                        if let Some(err) = journal_error {
+                            eprintln!("Warning: {}", err);
+                        }
+                        print_restored(&hub, &entry).await;
+                        return Ok(());
+                    }
+                    Err(err) if is_not_found(&err) => Operation::DeletePermanent,
+                    Err(err) => return Err(Error::Untrash(entry.name.clone(), err)),
+                }
+            }
+            TrashStatus::Restored => Operation::Untrash,
+            TrashStatus::Gone => Operation::DeletePermanent,
+        };
+
+        let reason = match operation {
+            Operation::Untrash => "was already restored",
+            _ => "is no longer in the trash",
+        };
+        eprintln!(
+            "Warning: '{}' {}, trying the file trashed before it",
+            entry.name, reason
+        );
+        // Without the record the same file would come up again
+        record(&entry, operation).map_err(Error::Journal)?;
+        entry = last_trashed()?;
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum TrashStatus {
+    Trashed,
+    // Restored since, i.e. in the web UI
+    Restored,
+    Gone,
+}
+
+async fn trash_status(hub: &Hub, file_id: &str) -> Result<TrashStatus, google_drive3::Error> {
+    let result = hub
+        .files()
+        .get(file_id)
+        .param("fields", "trashed")
+        .supports_all_drives(true)
+        .add_scope(google_drive3::api::Scope::Full)
+        .delegate(&mut StatsDelegate)
+        .doit()
+        .await;
+
+    match result {
+        Ok((_, file)) if file.trashed == Some(true) => Ok(TrashStatus::Trashed),
+        Ok(_) => Ok(TrashStatus::Restored),
+        Err(err) if is_not_found(&err) => Ok(TrashStatus::Gone),
+        Err(err) => Err(err),
+    }
+}
+
+fn last_trashed() -> Result<deletion_journal::Entry, Error> {
+    deletion_journal::last_trashed()
+        .map_err(Error::Journal)?
+        .ok_or(Error::NothingToRestore)
+}
+
+fn record(
+    entry: &deletion_journal::Entry,
+    operation: Operation,
+) -> Result<(), deletion_journal::Error> {
+    let entry =
+        deletion_journal::Entry::new(&entry.file_id, &entry.name, &entry.parents, operation);
+    deletion_journal::record(&entry)
+}
+
+// Names the folder the file is back in, when it can still be found
+async fn print_restored(hub: &Hub, entry: &deletion_journal::Entry) {
+    let file = google_drive3::api::File {
+        parents: Some(entry.parents.clone()),
+        ..Default::default()
+    };
+    let folder = match path_resolver::ancestors(hub, &file).await {
+        Ok(ancestors) if !ancestors.is_empty() => ancestors
+            .iter()
+            .map(|folder| folder.name.clone().unwrap_or_default())
+            .collect::<Vec<_>>()
+            .join("/"),
+        _ => String::new(),
+    };
+
+    if folder.is_empty() {
+        println!(
+            "Restored '{}' (id: {}, trashed at {})",
+            entry.name, entry.file_id, entry.time
+        );
+    } else {
+        println!(
+            "Restored '{}' to '{}' (id: {}, trashed at {})",
+            entry.name, folder, entry.file_id, entry.time
+        );
+    }
+}
+
+fn is_not_found(err: &google_drive3::Error) -> bool {
+    match err {
+        google_drive3::Error::BadRequest(body) => {
+            body.pointer("/error/code").and_then(|code| code.as_u64()) == Some(404)
+        }
+        google_drive3::Error::Failure(response) => {
+            response.status() == hyper::StatusCode::NOT_FOUND
+        }
+        _ => false,
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    Journal(deletion_journal::Error),
+    NothingToRestore,
+    GetFile(String, google_drive3::Error),
+    Untrash(String, google_drive3::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::Journal(err) => write!(f, "{}", err),
+            Error::NothingToRestore => {
+                write!(f, "No trashed files found in the deletion journal")
+            }
+            Error::GetFile(name, err) => {
+                write!(f, "Failed to check if '{}' is still trashed: {}", name, err)
+            }
+            Error::Untrash(name, err) => {
+                write!(f, "Failed to restore '{}' from trash: {}", name, err)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{fixture, MockServer};
+
+    #[tokio::test]
+    async fn files_restored_elsewhere_are_not_trashed() {
+        let server = MockServer::start().await;
+        let path = "/drive/v3/files/1ZyXwVuTsRqPoNmLkJiHgFeDcBa";
+        let hub = server.hub().await;
+
+        server.respond_json("GET", path, &serde_json::json!({"trashed": true}));
+        let status = trash_status(&hub, "1ZyXwVuTsRqPoNmLkJiHgFeDcBa").await;
+        assert_eq!(status.unwrap(), TrashStatus::Trashed);
+
+        server.respond_json("GET", path, &serde_json::json!({"trashed": false}));
+        let status = trash_status(&hub, "1ZyXwVuTsRqPoNmLkJiHgFeDcBa").await;
+        assert_eq!(status.unwrap(), TrashStatus::Restored);
+
+        server.respond("GET", path, 404, fixture("not_found.json"));
+        let status = trash_status(&hub, "1ZyXwVuTsRqPoNmLkJiHgFeDcBa").await;
+        assert_eq!(status.unwrap(), TrashStatus::Gone);
+        let fields = server.requests()[0].query_param("fields");
+        assert_eq!(fields.as_deref(), Some("trashed"));
+    }
+}
@@ -0,0 +1,110 @@
+use crate::common::api_stats::StatsDelegate;
+use crate::common::delegate::UploadDelegateConfig;
+use crate::common::hub_helper;
+use crate::hub::Hub;
+use crate::permissions;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+pub struct Config {
+    pub file_id: String,
+}
+
+pub async fn unshare_me(config: Config) -> Result<(), Error> {
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+
+    let name = remove_my_access(&hub, &config.file_id).await?;
+    println!("Removed your access to '{}'", name);
+
+    Ok(())
+}
+
+// Deletes the permission of the current user, so a file shared with them is
+// gone from their Drive. Deleting the file would need the owner. Access through
+// a group, the domain or a link is not the user's own permission and stays.
+// Returns the name of the file
+pub async fn remove_my_access(hub: &Hub, file_id: &str) -> Result<String, Error> {
+    let (_, file) = hub
+        .files()
+        .get(file_id)
+        .param("fields", "name,ownedByMe")
+        .supports_all_drives(true)
+        .add_scope(google_drive3::api::Scope::Full)
+        .delegate(&mut StatsDelegate)
+        .doit()
+        .await
+        .map_err(Error::GetFile)?;
+
+    let name = file.name.unwrap_or_default();
+    if file.owned_by_me == Some(true) {
+        return Err(Error::OwnedByMe(name));
+    }
+
+    let permission_id = my_permission_id(hub).await?;
+    permissions::revoke::delete_permission(
+        hub,
+        UploadDelegateConfig::default(),
+        file_id,
+        &permission_id,
+    )
+    .await
+    .map_err(Error::DeletePermission)?;
+
+    Ok(name)
+}
+
+async fn my_permission_id(hub: &Hub) -> Result<String, Error> {
+    let (_, about) = hub
+        .about()
+        .get()
+        .param("fields", "user(permissionId)")
+        .add_scope(google_drive3::api::Scope::Full)
+        .delegate(&mut StatsDelegate)
+        .doit()
+        .await
+        .map_err(Error::GetUser)?;
+
+    about
+        .user
+        .and_then(|user| user.permission_id)
+        .ok_or(Error::MissingPermissionId)
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    GetFile(google_drive3::Error),
+    OwnedByMe(String),
+    GetUser(google_drive3::Error),
+    MissingPermissionId,
+    DeletePermission(google_drive3::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::GetFile(err) => write!(f, "Failed to get file: {}", err),
+            Error::OwnedByMe(name) => {
+                write!(f, "You own '{}', delete it instead", name)
+            }
+            Error::GetUser(err) => write!(f, "Failed to get the current user: {}", err),
+            Error::MissingPermissionId => {
+                write!(
+                    f,
+                    "Drive didn't return the permission id of the current user"
+                )
+            }
+            Error::DeletePermission(err) => {
+                write!(
+                    f,
+                    "Failed to remove your access, access through a group, domain or link can't be removed: {}",
+                    err
+                )
+            }
+        }
+    }
+}
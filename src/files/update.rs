@@ -1,22 +1,25 @@
-use crate::common::delegate::BackoffConfig;
 use crate::common::delegate::ChunkSize;
+use crate::common::delegate::PrintObserver;
 use crate::common::delegate::UploadDelegate;
 use crate::common::delegate::UploadDelegateConfig;
 use crate::common::file_info;
 use crate::common::file_info::FileInfo;
 use crate::common::file_helper;
+use crate::common::hash_writer;
 use crate::common::hub_helper;
+use crate::config::settings;
 use crate::files;
 use crate::files::info;
 use crate::files::info::DisplayConfig;
 use crate::hub::Hub;
+use google_drive3::chrono;
+use google_drive3::chrono::DateTime;
 use mime::Mime;
 use std::error;
 use std::fmt::Display;
 use std::fmt::Formatter;
 use std::io;
 use std::path::PathBuf;
-use std::time::Duration;
 
 pub struct Config {
     pub file_id: String,
@@ -25,6 +28,7 @@ pub struct Config {
     pub chunk_size: ChunkSize,
     pub print_chunk_errors: bool,
     pub print_chunk_info: bool,
+    pub skip_unchanged: bool,
 }
 
 pub async fn update(config: Config) -> Result<(), Error> {
@@ -32,13 +36,9 @@ pub async fn update(config: Config) -> Result<(), Error> {
 
     let delegate_config = UploadDelegateConfig {
         chunk_size: config.chunk_size,
-        backoff_config: BackoffConfig {
-            max_retries: 20,
-            min_sleep: Duration::from_secs(1),
-            max_sleep: Duration::from_secs(60),
-        },
-        print_chunk_errors: config.print_chunk_errors,
-        print_chunk_info: config.print_chunk_info,
+        backoff_config: settings::current().backoff(),
+        observer: PrintObserver::new(config.print_chunk_info, config.print_chunk_errors),
+        ..UploadDelegateConfig::default()
     };
 
     let (file, file_path) = file_helper::open_file(&config.file_path)
@@ -59,6 +59,16 @@ pub async fn update(config: Config) -> Result<(), Error> {
     )
     .map_err(Error::FileInfo)?;
 
+    // Drive can't patch part of a file, so a changed file is always uploaded whole
+    if config.skip_unchanged {
+        let md5 = hash_writer::md5_of_file(&file_path)
+            .map_err(|err| Error::Hash(file_path.clone(), err))?;
+        if drive_file.md5_checksum.as_deref() == Some(md5.as_str()) {
+            println!("{} is unchanged, skipping upload", file_path.display());
+            return Ok(());
+        }
+    }
+
     let reader = std::io::BufReader::new(file);
 
     println!(
@@ -94,6 +104,7 @@ where
         ..google_drive3::api::File::default()
     };
 
+    let resumable = delegate_config.use_resumable_upload(file_info.size);
     let mut delegate = UploadDelegate::new(delegate_config);
 
     let req = hub
@@ -104,7 +115,7 @@ where
         .delegate(&mut delegate)
         .supports_all_drives(true);
 
-    let (_, file) = if file_info.size > 0 {
+    let (_, file) = if resumable {
         req.upload_resumable(src_file, file_info.mime_type).await?
     } else {
         req.upload(src_file, file_info.mime_type).await?
@@ -137,6 +148,7 @@ pub enum Error {
     Hub(hub_helper::Error),
     FileInfo(file_info::Error),
     OpenFile(PathBuf, io::Error),
+    Hash(PathBuf, io::Error),
     GetFile(google_drive3::Error),
     Update(google_drive3::Error),
 }
@@ -151,6 +163,9 @@ impl Display for Error {
             Error::OpenFile(path, err) => {
                 write!(f, "Failed to open file '{}': {}", path.display(), err)
             }
+            Error::Hash(path, err) => {
+                write!(f, "Failed to hash file '{}': {}", path.display(), err)
+            }
             Error::GetFile(err) => write!(f, "Failed to get file: {}", err),
             Error::Update(err) => write!(f, "Failed to update file: {}", err),
         }
@@ -181,6 +196,46 @@ impl PatchFile {
         }
     }
 
+    pub fn with_trashed(&self, trashed: bool) -> Self {
+        Self {
+            file: google_drive3::api::File {
+                trashed: Some(trashed),
+                ..self.file.clone()
+            },
+            ..self.clone()
+        }
+    }
+
+    pub fn with_description(&self, description: &str) -> Self {
+        Self {
+            file: google_drive3::api::File {
+                description: Some(description.to_string()),
+                ..self.file.clone()
+            },
+            ..self.clone()
+        }
+    }
+
+    pub fn with_folder_color(&self, color: &str) -> Self {
+        Self {
+            file: google_drive3::api::File {
+                folder_color_rgb: Some(color.to_string()),
+                ..self.file.clone()
+            },
+            ..self.clone()
+        }
+    }
+
+    pub fn with_viewed_by_me_time(&self, time: DateTime<chrono::Utc>) -> Self {
+        Self {
+            file: google_drive3::api::File {
+                viewed_by_me_time: Some(time),
+                ..self.file.clone()
+            },
+            ..self.clone()
+        }
+    }
+
     pub fn id(&self) -> String {
         self.id.clone()
     }
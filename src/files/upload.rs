@@ -1,51 +1,85 @@
-use crate::common::delegate::BackoffConfig;
 use crate::common::delegate::ChunkSize;
+use crate::common::delegate::PrintObserver;
 use crate::common::delegate::UploadDelegate;
 use crate::common::delegate::UploadDelegateConfig;
+use crate::common::delegate::MAX_STALL_RETRIES;
+use crate::common::drive_file::MIME_TYPE_DRIVE_DOCUMENT;
 use crate::common::file_info;
 use crate::common::file_info::FileInfo;
+use crate::common::file_name;
 use crate::common::file_tree;
-use crate::common::file_tree::FileTree;
+use crate::common::file_tree::Entry;
+use crate::common::file_tree::Folder;
+use crate::common::file_tree::TreeInfo;
 use crate::common::file_helper;
 use crate::common::hub_helper;
 use crate::common::id_gen::IdGen;
+use crate::config::settings;
 use crate::files;
+use crate::files::delete::Operation;
 use crate::files::info::DisplayConfig;
 use crate::files::mkdir;
+use crate::files::upload_defaults;
+use crate::files::upload_defaults::FolderDefaults;
 use crate::hub::Hub;
+use futures::stream;
+use google_drive3::chrono;
+use futures::stream::StreamExt;
+use futures::stream::TryStreamExt;
 use human_bytes::human_bytes;
 use mime::Mime;
+use std::collections::BTreeMap;
 use std::error;
 use std::fmt::Display;
 use std::fmt::Formatter;
 use std::fs;
 use std::io;
 use std::path::PathBuf;
-use std::time::Duration;
+use tokio::sync::mpsc;
+
+// Folders created at the same time during directory uploads
+pub const MKDIR_PARALLELISM: usize = 8;
 
 pub struct Config {
     pub file_path: Option<PathBuf>,
+    // Template for the name on Drive, see file_name::render_template
+    pub name: Option<String>,
     pub mime_type: Option<Mime>,
     pub parents: Option<Vec<String>>,
-    pub chunk_size: ChunkSize,
+    // None uses the upload defaults of the parent or chunk-size in config.toml
+    pub chunk_size: Option<ChunkSize>,
     pub print_chunk_errors: bool,
     pub print_chunk_info: bool,
     pub upload_directories: bool,
+    // Upload dot files and hidden folders of a directory, i.e. for a backup of $HOME
+    pub include_hidden: bool,
     pub print_only_id: bool,
+    pub ocr: bool,
+    pub ocr_language: Option<String>,
+    // Delete what a directory upload created when it fails or is cancelled
+    pub rollback_on_failure: bool,
+    pub media: MediaOptions,
+}
+
+// Options of files.create for media workflows. They are only sent when set, so
+// plain uploads make the same request as before
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MediaOptions {
+    // Drive indexes the content as text for search, i.e. subtitles or
+    // transcripts with an extension it doesn't know
+    pub indexable_text: bool,
+    // The uploaded revision is never deleted when newer ones are uploaded.
+    // Drive keeps at most 200 revisions of a file forever
+    pub keep_forever: bool,
 }
 
 pub async fn upload(config: Config) -> Result<(), Error> {
     let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+    let defaults = get_folder_defaults(&hub, &config.parents).await;
 
     let delegate_config = UploadDelegateConfig {
-        chunk_size: config.chunk_size.clone(),
-        backoff_config: BackoffConfig {
-            max_retries: 100000,
-            min_sleep: Duration::from_secs(1),
-            max_sleep: Duration::from_secs(60),
-        },
-        print_chunk_errors: config.print_chunk_errors,
-        print_chunk_info: config.print_chunk_info,
+        observer: PrintObserver::new(config.print_chunk_info, config.print_chunk_errors),
+        ..delegate_config(config.chunk_size.clone(), &defaults)
     };
 
     match &config.file_path {
@@ -53,9 +87,9 @@ pub async fn upload(config: Config) -> Result<(), Error> {
             err_if_directory(&path, &config)?;
 
             if path.is_dir() {
-                upload_directory(&hub, &config, delegate_config).await?;
+                upload_directory(&hub, &config, &defaults, delegate_config).await?;
             } else {
-                upload_regular(&hub, &config, delegate_config).await?;
+                upload_regular(&hub, &config, &defaults, delegate_config).await?;
             }
         },
         None => {
@@ -65,41 +99,109 @@ pub async fn upload(config: Config) -> Result<(), Error> {
             upload_regular(&hub, &Config {
                 file_path: Some(tmp_file.as_ref().to_path_buf()),
                 ..config
-            }, delegate_config).await?;
+            }, &defaults, delegate_config).await?;
         }
     };
 
     Ok(())
 }
 
+// Defaults of the folder the upload goes to. They are optional, so a folder
+// that can't be read only gets a warning and the upload fails later if it
+// really doesn't exist
+pub async fn get_folder_defaults(hub: &Hub, parents: &Option<Vec<String>>) -> FolderDefaults {
+    let parent = match parents.as_ref().and_then(|parents| parents.first()) {
+        Some(parent) => parent,
+        None => return FolderDefaults::default(),
+    };
+
+    match upload_defaults::get(hub, parent).await {
+        Ok(defaults) => defaults,
+        Err(err) => {
+            eprintln!(
+                "Warning: Failed to get the upload defaults of folder '{}': {}",
+                parent, err
+            );
+            FolderDefaults::default()
+        }
+    }
+}
+
+// The chunk size is the given one, the one of the folder defaults or chunk-size
+// in config.toml. Uploads retry for a long time, they can take hours
+pub fn delegate_config(
+    chunk_size: Option<ChunkSize>,
+    defaults: &FolderDefaults,
+) -> UploadDelegateConfig {
+    let chunk_size = chunk_size
+        .or_else(|| defaults.chunk_size.clone())
+        .unwrap_or_else(|| settings::current().chunk_size());
+
+    UploadDelegateConfig {
+        chunk_size,
+        backoff_config: settings::current().backoff(),
+        ..UploadDelegateConfig::default()
+    }
+}
+
 pub async fn upload_regular(
     hub: &Hub,
     config: &Config,
+    defaults: &FolderDefaults,
     delegate_config: UploadDelegateConfig,
 ) -> Result<(), Error> {
     let file_path = config.file_path.as_ref().unwrap();
     let file = fs::File::open(file_path)
         .map_err(|err| Error::OpenFile(file_path.clone(), err))?;
 
-    let file_info = FileInfo::from_file(
+    let mut file_info = FileInfo::from_file(
         &file,
         &file_info::Config {
             file_path: file_path.clone(),
-            mime_type: config.mime_type.clone(),
+            mime_type: mime_type(config, defaults, file_path),
             parents: config.parents.clone(),
         },
     )
     .map_err(Error::FileInfo)?;
 
+    if let Some(template) = &config.name {
+        let now = chrono::Local::now();
+        file_info.name = file_name::render_template(template, Some(&file_info.name), now)
+            .map_err(Error::Name)?;
+    }
+
     let reader = std::io::BufReader::new(file);
 
-    if !config.print_only_id {
-        println!("Uploading {}", file_path.display());
-    }
+    let file = if config.ocr {
+        err_if_not_ocr_supported(&file_info.mime_type)?;
 
-    let file = upload_file(&hub, reader, None, file_info, delegate_config)
+        if !config.print_only_id {
+            println!("Uploading {} as a Google document", file_path.display());
+        }
+
+        let ocr_language = config.ocr_language.as_deref();
+        upload_file_as_doc(
+            hub,
+            reader,
+            file_info,
+            ocr_language,
+            config.media,
+            delegate_config,
+        )
         .await
-        .map_err(Error::Upload)?;
+        .map_err(Error::Upload)?
+    } else {
+        if !config.print_only_id {
+            match &config.name {
+                Some(_) => println!("Uploading {} as '{}'", file_path.display(), file_info.name),
+                None => println!("Uploading {}", file_path.display()),
+            }
+        }
+
+        upload_file_with_options(&hub, reader, None, file_info, config.media, delegate_config)
+            .await
+            .map_err(Error::Upload)?
+    };
 
     if config.print_only_id {
         print!("{}", file.id.unwrap_or_default())
@@ -115,87 +217,152 @@ pub async fn upload_regular(
 pub async fn upload_directory(
     hub: &Hub,
     config: &Config,
+    defaults: &FolderDefaults,
     delegate_config: UploadDelegateConfig,
 ) -> Result<(), Error> {
     let mut ids = IdGen::new(hub, &delegate_config);
-    let tree = FileTree::from_path(config.file_path.as_ref().unwrap(), &mut ids)
-        .await
-        .map_err(Error::CreateFileTree)?;
-
-    let tree_info = tree.info();
-
-    if !config.print_only_id {
-        println!(
-            "Found {} files in {} directories with a total size of {}",
-            tree_info.file_count,
-            tree_info.folder_count,
-            human_bytes(tree_info.total_file_size as f64)
-        );
-    }
-
-    for folder in &tree.folders() {
-        let folder_parents = folder
-            .parent
-            .as_ref()
-            .map(|p| vec![p.drive_id.clone()])
-            .or_else(|| config.parents.clone());
-
-        if !config.print_only_id {
-            println!(
-                "Creating directory '{}' with id: {}",
-                folder.relative_path().display(),
-                folder.drive_id
-            );
-        }
-
-        let drive_folder = mkdir::create_directory(
-            hub,
-            &mkdir::Config {
-                id: Some(folder.drive_id.clone()),
-                name: folder.name.clone(),
-                parents: folder_parents,
-                print_only_id: false,
-            },
-            delegate_config.clone(),
-        )
-        .await
-        .map_err(Error::Mkdir)?;
+    let (sender, receiver) = mpsc::channel(file_tree::WALK_BUFFER);
+
+    // Files are uploaded as they are found, the walk is never more than
+    // WALK_BUFFER entries ahead of the uploads
+    let walk = file_tree::walk(
+        config.file_path.as_ref().unwrap(),
+        config.include_hidden,
+        &mut ids,
+        sender,
+        &mut |_| {},
+    );
+
+    let mut tree_info = TreeInfo {
+        file_count: 0,
+        folder_count: 0,
+        total_file_size: 0,
+    };
 
-        if config.print_only_id {
-            println!("{}: {}", folder.relative_path().display(), folder.drive_id);
-        }
+    // Everything that is uploaded ends up in the folder created for the root
+    let mut root_folder_id = None;
 
-        let folder_id = drive_folder.id.ok_or(Error::DriveFolderMissingId)?;
-        let parents = Some(vec![folder_id.clone()]);
+    // The receiver is moved into the upload, so a failed upload drops it and
+    // makes the walk stop
+    let upload = async {
+        let mut receiver = receiver;
 
-        for file in folder.files() {
-            let os_file = fs::File::open(&file.path)
-                .map_err(|err| Error::OpenFile(config.file_path.as_ref().unwrap().clone(), err))?;
+        loop {
+            let entries = file_tree::recv_batch(&mut receiver).await;
+            if entries.is_empty() {
+                break;
+            }
 
-            let file_info = file.info(parents.clone());
+            let mut folders = vec![];
+            let mut files = vec![];
+            for entry in entries {
+                match entry {
+                    Entry::Folder(folder) => folders.push(folder),
+                    Entry::File(file) => files.push(file),
+                }
+            }
 
             if !config.print_only_id {
-                println!(
-                    "Uploading file '{}' with id: {}",
-                    file.relative_path().display(),
-                    file.drive_id
-                );
+                for folder in &folders {
+                    println!(
+                        "Creating directory '{}' with id: {}",
+                        folder.relative_path().display(),
+                        folder.drive_id
+                    );
+                }
             }
 
-            upload_file(
+            create_tree_directories(
                 hub,
-                os_file,
-                Some(file.drive_id.clone()),
-                file_info,
-                delegate_config.clone(),
+                &folders,
+                &config.parents,
+                &delegate_config,
+                &mut root_folder_id,
             )
             .await
-            .map_err(Error::Upload)?;
+            .map_err(Error::Mkdir)?;
+
+            for folder in &folders {
+                if config.print_only_id {
+                    println!("{}: {}", folder.relative_path().display(), folder.drive_id);
+                }
+            }
+
+            tree_info.folder_count += folders.len() as u128;
+
+            for file in files {
+                let os_file = fs::File::open(&file.path)
+                    .map_err(|err| Error::OpenFile(file.path.clone(), err))?;
+
+                let mut file_info = file.info(Some(vec![file.parent.drive_id.clone()]));
+                if let Some(mime_type) = defaults.mime_type(&file.path) {
+                    file_info.mime_type = mime_type;
+                }
+
+                if !config.print_only_id {
+                    println!(
+                        "Uploading file '{}' with id: {}",
+                        file.relative_path().display(),
+                        file.drive_id
+                    );
+                }
+
+                upload_file_with_options(
+                    hub,
+                    os_file,
+                    Some(file.drive_id.clone()),
+                    file_info,
+                    config.media,
+                    delegate_config.clone(),
+                )
+                .await
+                .map_err(Error::Upload)?;
+
+                if config.print_only_id {
+                    println!("{}: {}", file.relative_path().display(), file.drive_id);
+                }
+
+                tree_info.file_count += 1;
+                tree_info.total_file_size += file.size as u128;
+            }
+        }
+
+        Ok::<(), Error>(())
+    };
+
+    let transfer = async {
+        let (walk_result, upload_result) = tokio::join!(walk, upload);
+        upload_result?;
+        walk_result.map_err(Error::CreateFileTree)
+    };
+
+    // Ctrl-C ends the process right away, it is only caught when there is
+    // something to roll back
+    let result = if config.rollback_on_failure {
+        tokio::select! {
+            result = transfer => result,
+            Ok(()) = tokio::signal::ctrl_c() => Err(Error::Cancelled),
+        }
+    } else {
+        transfer.await
+    };
 
-            if config.print_only_id {
-                println!("{}: {}", file.relative_path().display(), file.drive_id);
+    if let Err(err) = result {
+        match root_folder_id {
+            Some(folder_id) if config.rollback_on_failure => {
+                if !config.print_only_id {
+                    println!("Rolling back, deleting directory with id: {}", folder_id);
+                }
+                if let Err(rollback_err) = rollback(hub, &folder_id).await {
+                    eprintln!(
+                        "Warning: Failed to delete the partially uploaded directory '{}': {}",
+                        folder_id, rollback_err
+                    );
+                }
             }
+            _ => {}
         }
+        return Err(err);
     }
 
     if !config.print_only_id {
@@ -210,6 +377,89 @@ pub async fn upload_directory(
     Ok(())
 }
 
+// --mime wins over the folder defaults. OCR needs the mime type of the file
+// itself, the defaults are not used for it
+fn mime_type(config: &Config, defaults: &FolderDefaults, file_path: &PathBuf) -> Option<Mime> {
+    if config.mime_type.is_some() || config.ocr {
+        return config.mime_type.clone();
+    }
+
+    defaults.mime_type(file_path)
+}
+
+// Creates the folders of a walked tree like create_directories. The root of the
+// tree is created on its own first, and `root_id` is set once it exists, so a
+// failed upload knows what to roll back
+pub async fn create_tree_directories(
+    hub: &Hub,
+    folders: &[Folder],
+    parents: &Option<Vec<String>>,
+    delegate_config: &UploadDelegateConfig,
+    root_id: &mut Option<String>,
+) -> Result<(), google_drive3::Error> {
+    let (roots, folders): (Vec<Folder>, Vec<Folder>) = folders
+        .iter()
+        .cloned()
+        .partition(|folder| folder.parent.is_none());
+
+    if let Some(root) = roots.first() {
+        create_directories(hub, &roots, parents, delegate_config).await?;
+        *root_id = Some(root.drive_id.clone());
+    }
+
+    create_directories(hub, &folders, parents, delegate_config).await
+}
+
+// Deletes the folder created for a directory upload with everything that was
+// uploaded into it. It skips the trash, the files are still on disk
+pub async fn rollback(hub: &Hub, root_id: &str) -> Result<(), google_drive3::Error> {
+    files::delete::apply(hub, root_id, Operation::DeletePermanent).await
+}
+
+// Creates the folders, folders at the same depth are created concurrently.
+// A folder's parent is either earlier in `folders` or already created
+pub async fn create_directories(
+    hub: &Hub,
+    folders: &[Folder],
+    parents: &Option<Vec<String>>,
+    delegate_config: &UploadDelegateConfig,
+) -> Result<(), google_drive3::Error> {
+    let mut levels: BTreeMap<usize, Vec<&Folder>> = BTreeMap::new();
+    for folder in folders {
+        levels
+            .entry(folder.ancestor_count())
+            .or_default()
+            .push(folder);
+    }
+
+    for level in levels.into_values() {
+        stream::iter(level)
+            .map(|folder| {
+                let folder_parents = folder
+                    .parent
+                    .as_ref()
+                    .map(|p| vec![p.drive_id.clone()])
+                    .or_else(|| parents.clone());
+
+                let mkdir_config = mkdir::Config {
+                    id: Some(folder.drive_id.clone()),
+                    name: folder.name.clone(),
+                    parents: folder_parents,
+                    print_only_id: false,
+                };
+
+                async move {
+                    mkdir::create_directory(hub, &mkdir_config, delegate_config.clone()).await
+                }
+            })
+            .buffer_unordered(MKDIR_PARALLELISM)
+            .try_collect::<Vec<_>>()
+            .await?;
+    }
+
+    Ok(())
+}
+
 pub async fn upload_file<RS>(
     hub: &Hub,
     src_file: RS,
@@ -217,6 +467,28 @@ pub async fn upload_file<RS>(
     file_info: FileInfo,
     delegate_config: UploadDelegateConfig,
 ) -> Result<google_drive3::api::File, google_drive3::Error>
+where
+    RS: google_drive3::client::ReadSeek,
+{
+    upload_file_with_options(
+        hub,
+        src_file,
+        file_id,
+        file_info,
+        MediaOptions::default(),
+        delegate_config,
+    )
+    .await
+}
+
+pub async fn upload_file_with_options<RS>(
+    hub: &Hub,
+    src_file: RS,
+    file_id: Option<String>,
+    file_info: FileInfo,
+    options: MediaOptions,
+    delegate_config: UploadDelegateConfig,
+) -> Result<google_drive3::api::File, google_drive3::Error>
 where
     RS: google_drive3::client::ReadSeek,
 {
@@ -228,10 +500,76 @@ where
         ..google_drive3::api::File::default()
     };
 
-    let chunk_size_bytes = delegate_config.chunk_size.in_bytes();
+    let resumable = delegate_config.use_resumable_upload(file_info.size);
     let mut delegate = UploadDelegate::new(delegate_config);
+    let stall_watch = delegate.stall_watch();
+    let mut src_file = src_file;
+    let mut stalls = 0;
+
+    loop {
+        let mut req = hub
+            .files()
+            .create(dst_file.clone())
+            .param("fields", "id,name,size,createdTime,modifiedTime,md5Checksum,mimeType,parents,shared,description,webContentLink,webViewLink")
+            .add_scope(google_drive3::api::Scope::Full)
+            .delegate(&mut delegate)
+            .supports_all_drives(true);
+
+        if options.indexable_text {
+            req = req.use_content_as_indexable_text(true);
+        }
+        if options.keep_forever {
+            req = req.keep_revision_forever(true);
+        }
+
+        if !resumable {
+            let (_, file) = req.upload(src_file, file_info.mime_type).await?;
+            return Ok(file);
+        }
 
-    let req = hub
+        // The delegate keeps the upload url, so a restarted upload continues
+        // from the last acknowledged chunk
+        let upload = req.upload_resumable(&mut src_file, file_info.mime_type.clone());
+        match stall_watch.watch(upload).await {
+            Some(result) => {
+                let (_, file) = result?;
+                return Ok(file);
+            }
+            None if stalls < MAX_STALL_RETRIES => stalls += 1,
+            None => {
+                return Err(google_drive3::Error::Io(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "upload stalled",
+                )))
+            }
+        }
+    }
+}
+
+// Drive converts images and PDFs to a document with the recognized text.
+// `ocr_language` is an ISO 639-1 code like `en` or `tr` that helps the recognition
+pub async fn upload_file_as_doc<RS>(
+    hub: &Hub,
+    src_file: RS,
+    file_info: FileInfo,
+    ocr_language: Option<&str>,
+    options: MediaOptions,
+    delegate_config: UploadDelegateConfig,
+) -> Result<google_drive3::api::File, google_drive3::Error>
+where
+    RS: google_drive3::client::ReadSeek,
+{
+    let dst_file = google_drive3::api::File {
+        name: Some(file_info.name),
+        mime_type: Some(MIME_TYPE_DRIVE_DOCUMENT.to_string()),
+        parents: file_info.parents,
+        ..google_drive3::api::File::default()
+    };
+
+    let resumable = delegate_config.use_resumable_upload(file_info.size);
+    let mut delegate = UploadDelegate::new(delegate_config);
+
+    let mut req = hub
         .files()
         .create(dst_file)
         .param("fields", "id,name,size,createdTime,modifiedTime,md5Checksum,mimeType,parents,shared,description,webContentLink,webViewLink")
@@ -239,7 +577,18 @@ where
         .delegate(&mut delegate)
         .supports_all_drives(true);
 
-    let (_, file) = if file_info.size > chunk_size_bytes {
+    if let Some(language) = ocr_language {
+        req = req.ocr_language(language);
+    }
+    if options.indexable_text {
+        req = req.use_content_as_indexable_text(true);
+    }
+    if options.keep_forever {
+        req = req.keep_revision_forever(true);
+    }
+
+    // The media keeps its own mime type so Drive knows what to convert from
+    let (_, file) = if resumable {
         req.upload_resumable(src_file, file_info.mime_type).await?
     } else {
         req.upload(src_file, file_info.mime_type).await?
@@ -258,6 +607,9 @@ pub enum Error {
     DriveFolderMissingId,
     CreateFileTree(file_tree::Error),
     Mkdir(google_drive3::Error),
+    OcrNotSupported(Mime),
+    Name(String),
+    Cancelled,
 }
 
 impl error::Error for Error {}
@@ -279,10 +631,25 @@ impl Display for Error {
             Error::DriveFolderMissingId => write!(f, "Folder created on drive does not have an id"),
             Error::CreateFileTree(err) => write!(f, "Failed to create file tree: {}", err),
             Error::Mkdir(err) => write!(f, "Failed to create directory: {}", err),
+            Error::OcrNotSupported(mime) => write!(
+                f,
+                "OCR is only supported for images and PDFs, the file is of type '{}'",
+                mime
+            ),
+            Error::Name(err) => write!(f, "Invalid --name: {}", err),
+            Error::Cancelled => write!(f, "Upload cancelled"),
         }
     }
 }
 
+fn err_if_not_ocr_supported(mime_type: &Mime) -> Result<(), Error> {
+    if mime_type.type_() == mime::IMAGE || mime_type == &mime::APPLICATION_PDF {
+        Ok(())
+    } else {
+        Err(Error::OcrNotSupported(mime_type.clone()))
+    }
+}
+
 fn err_if_directory(path: &PathBuf, config: &Config) -> Result<(), Error> {
     if path.is_dir() && !config.upload_directories {
         Err(Error::IsDirectory(path.clone()))
@@ -0,0 +1,396 @@
+use crate::common::api_stats::StatsDelegate;
+use crate::common::delegate::ChunkSize;
+use crate::common::drive_file;
+use crate::common::drive_file::DocType;
+use crate::common::hub_helper;
+use crate::files;
+use crate::hub;
+use crate::hub::Hub;
+use mime::Mime;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::path::Path;
+use std::str::FromStr;
+
+// Upload defaults of a folder are kept in its appProperties, so uploads to the
+// folder behave the same for everyone on the team. Drive only shows
+// appProperties to the OAuth client that wrote them, uploads with another
+// client id don't see the defaults
+const KEY_PREFIX: &str = "gdrive.upload.";
+const KEY_CONVERT: &str = "gdrive.upload.convert";
+const KEY_CHUNK_SIZE: &str = "gdrive.upload.chunkSize";
+const KEY_MIME_PREFIX: &str = "gdrive.upload.mime.";
+
+#[derive(Clone, Debug, Default)]
+pub struct FolderDefaults {
+    pub convert: Option<bool>,
+    pub chunk_size: Option<ChunkSize>,
+    // Extension without the dot -> mime type
+    pub mime_types: BTreeMap<String, Mime>,
+}
+
+impl FolderDefaults {
+    // Values that don't parse are ignored, the upload uses its own default
+    // instead of failing
+    pub fn from_app_properties(properties: &HashMap<String, String>) -> FolderDefaults {
+        let mut defaults = FolderDefaults::default();
+
+        for (key, value) in properties {
+            if key == KEY_CONVERT {
+                defaults.convert = value.parse().ok();
+            } else if key == KEY_CHUNK_SIZE {
+                defaults.chunk_size = value.parse().ok();
+            } else if let Some(extension) = key.strip_prefix(KEY_MIME_PREFIX) {
+                if let Ok(mime) = value.parse() {
+                    defaults.mime_types.insert(extension.to_string(), mime);
+                }
+            }
+        }
+
+        defaults
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.convert.is_none() && self.chunk_size.is_none() && self.mime_types.is_empty()
+    }
+
+    // Mime type to upload the file as when --mime isn't given. With convert
+    // files Drive can import are uploaded as the matching Google document
+    pub fn mime_type(&self, path: &Path) -> Option<Mime> {
+        if self.convert == Some(true) {
+            let doc_mime = DocType::from_file_path(&path.to_path_buf()).and_then(|doc| doc.mime());
+            if doc_mime.is_some() {
+                return doc_mime;
+            }
+        }
+
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        self.mime_types.get(&extension).cloned()
+    }
+}
+
+pub async fn get(hub: &Hub, folder_id: &str) -> Result<FolderDefaults, google_drive3::Error> {
+    let properties = get_app_properties(hub, folder_id).await?;
+    Ok(FolderDefaults::from_app_properties(&properties))
+}
+
+pub struct Config {
+    pub folder_id: String,
+    pub convert: Option<bool>,
+    pub chunk_size: Option<ChunkSize>,
+    pub mime_types: Vec<MimeOverride>,
+    // Remove all defaults before setting the given ones
+    pub clear: bool,
+}
+
+impl Config {
+    fn has_changes(&self) -> bool {
+        self.clear
+            || self.convert.is_some()
+            || self.chunk_size.is_some()
+            || !self.mime_types.is_empty()
+    }
+}
+
+// Prints the upload defaults of a folder, or changes them when options are given
+pub async fn upload_defaults(config: Config) -> Result<(), Error> {
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+
+    let file = files::info::get_file(&hub, &config.folder_id)
+        .await
+        .map_err(Error::GetFile)?;
+
+    if !drive_file::is_directory(&file) {
+        return Err(Error::NotADirectory);
+    }
+
+    let properties = get_app_properties(&hub, &config.folder_id)
+        .await
+        .map_err(Error::GetDefaults)?;
+
+    if !config.has_changes() {
+        print_defaults(&FolderDefaults::from_app_properties(&properties));
+        return Ok(());
+    }
+
+    println!(
+        "Updating upload defaults of '{}'",
+        file.name.unwrap_or_default()
+    );
+
+    let changes = changes(&properties, &config);
+    let properties = patch_app_properties(&hub, &config.folder_id, &changes)
+        .await
+        .map_err(Error::Update)?;
+
+    print_defaults(&FolderDefaults::from_app_properties(&properties));
+
+    Ok(())
+}
+
+// New value of each changed key, None removes the key
+fn changes(
+    properties: &HashMap<String, String>,
+    config: &Config,
+) -> BTreeMap<String, Option<String>> {
+    let mut changes = BTreeMap::new();
+
+    if config.clear {
+        for key in properties.keys() {
+            if key.starts_with(KEY_PREFIX) {
+                changes.insert(key.clone(), None);
+            }
+        }
+    }
+
+    if let Some(convert) = config.convert {
+        changes.insert(KEY_CONVERT.to_string(), Some(convert.to_string()));
+    }
+
+    if let Some(chunk_size) = &config.chunk_size {
+        changes.insert(KEY_CHUNK_SIZE.to_string(), Some(chunk_size.to_string()));
+    }
+
+    for mime_override in &config.mime_types {
+        let key = format!("{}{}", KEY_MIME_PREFIX, mime_override.extension);
+        let value = mime_override
+            .mime_type
+            .as_ref()
+            .map(|mime| mime.to_string());
+        changes.insert(key, value);
+    }
+
+    changes
+}
+
+fn print_defaults(defaults: &FolderDefaults) {
+    if defaults.is_empty() {
+        println!("No upload defaults");
+        return;
+    }
+
+    if let Some(convert) = defaults.convert {
+        println!("Convert: {}", convert);
+    }
+
+    if let Some(chunk_size) = &defaults.chunk_size {
+        println!("Chunk size: {}", chunk_size);
+    }
+
+    for (extension, mime_type) in &defaults.mime_types {
+        println!("Mime type of .{}: {}", extension, mime_type);
+    }
+}
+
+async fn get_app_properties(
+    hub: &Hub,
+    folder_id: &str,
+) -> Result<HashMap<String, String>, google_drive3::Error> {
+    let (_, file) = hub
+        .files()
+        .get(folder_id)
+        .param("fields", "appProperties")
+        .supports_all_drives(true)
+        .add_scope(google_drive3::api::Scope::Full)
+        .delegate(&mut StatsDelegate)
+        .doit()
+        .await?;
+
+    Ok(file.app_properties.unwrap_or_default())
+}
+
+// The generated api leaves out keys without a value, but Drive only removes a
+// property that is set to null, so the patch is sent without it
+async fn patch_app_properties(
+    hub: &Hub,
+    folder_id: &str,
+    changes: &BTreeMap<String, Option<String>>,
+) -> Result<HashMap<String, String>, hub::RequestError> {
+    let body = json!({ "appProperties": changes });
+    let request = hyper::Request::patch(hub::metadata_url(folder_id, "appProperties"))
+        .header(hyper::header::CONTENT_TYPE, "application/json");
+
+    let response = hub
+        .send(request, hyper::Body::from(body.to_string()))
+        .await?;
+    let status = response.status();
+    let body = hyper::body::to_bytes(response.into_body())
+        .await
+        .map_err(hub::RequestError::Http)?;
+
+    if !status.is_success() {
+        let body = String::from_utf8_lossy(&body).to_string();
+        return Err(hub::RequestError::Status(status, body));
+    }
+
+    let file: google_drive3::api::File =
+        serde_json::from_slice(&body).map_err(hub::RequestError::Parse)?;
+    Ok(file.app_properties.unwrap_or_default())
+}
+
+// `ext=mime/type` for --mime, an empty mime type removes the default
+#[derive(Clone, Debug)]
+pub struct MimeOverride {
+    pub extension: String,
+    pub mime_type: Option<Mime>,
+}
+
+impl FromStr for MimeOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (extension, mime_type) = s.split_once('=').ok_or(format!(
+            "'{}' is not ext=mime/type, i.e. md=text/markdown",
+            s
+        ))?;
+
+        let extension = extension.trim_start_matches('.').to_lowercase();
+        if extension.is_empty() {
+            return Err(format!("Missing file extension in '{}'", s));
+        }
+
+        let mime_type = if mime_type.is_empty() {
+            None
+        } else {
+            let mime = mime_type
+                .parse()
+                .map_err(|_| format!("'{}' is not a mime type", mime_type))?;
+            Some(mime)
+        };
+
+        Ok(MimeOverride {
+            extension,
+            mime_type,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    GetFile(google_drive3::Error),
+    NotADirectory,
+    GetDefaults(google_drive3::Error),
+    Update(hub::RequestError),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::GetFile(err) => write!(f, "Failed to get file: {}", err),
+            Error::NotADirectory => write!(f, "Upload defaults can only be set on folders"),
+            Error::GetDefaults(err) => write!(f, "Failed to get upload defaults: {}", err),
+            Error::Update(err) => write!(f, "Failed to update upload defaults: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockServer;
+
+    #[test]
+    fn defaults_are_read_from_app_properties() {
+        let properties = HashMap::from([
+            (KEY_CONVERT.to_string(), "true".to_string()),
+            (KEY_CHUNK_SIZE.to_string(), "64".to_string()),
+            (
+                "gdrive.upload.mime.md".to_string(),
+                "text/markdown".to_string(),
+            ),
+            (
+                "gdrive.upload.mime.log".to_string(),
+                "not a mime".to_string(),
+            ),
+            ("otherApp".to_string(), "true".to_string()),
+        ]);
+
+        let defaults = FolderDefaults::from_app_properties(&properties);
+
+        assert_eq!(defaults.convert, Some(true));
+        assert_eq!(
+            defaults.chunk_size.map(|size| size.to_string()),
+            Some("64".to_string())
+        );
+        assert_eq!(defaults.mime_types.len(), 1);
+        assert_eq!(
+            defaults.mime_type(Path::new("notes.MD")),
+            Some("text/markdown".parse().unwrap())
+        );
+        assert_eq!(
+            defaults.mime_type(Path::new("report.docx")),
+            DocType::Document.mime()
+        );
+        assert_eq!(defaults.mime_type(Path::new("photo.jpg")), None);
+    }
+
+    #[test]
+    fn clear_removes_only_upload_defaults() {
+        let properties = HashMap::from([
+            (KEY_CONVERT.to_string(), "true".to_string()),
+            ("otherApp".to_string(), "true".to_string()),
+        ]);
+        let config = Config {
+            folder_id: "folder".to_string(),
+            convert: None,
+            chunk_size: None,
+            mime_types: vec![
+                "md=text/markdown".parse().unwrap(),
+                ".TXT=".parse().unwrap(),
+            ],
+            clear: true,
+        };
+
+        let changes = changes(&properties, &config);
+
+        assert_eq!(
+            changes,
+            BTreeMap::from([
+                (KEY_CONVERT.to_string(), None),
+                (
+                    "gdrive.upload.mime.md".to_string(),
+                    Some("text/markdown".to_string())
+                ),
+                ("gdrive.upload.mime.txt".to_string(), None),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn removed_defaults_are_sent_as_null() {
+        let server = MockServer::start().await;
+        server.respond_json(
+            "PATCH",
+            "/drive/v3/files/folder",
+            &json!({"appProperties": {"gdrive.upload.chunkSize": "64"}}),
+        );
+        let hub = server.hub().await;
+
+        let changes = BTreeMap::from([
+            (KEY_CONVERT.to_string(), None),
+            (KEY_CHUNK_SIZE.to_string(), Some("64".to_string())),
+        ]);
+        let properties = patch_app_properties(&hub, "folder", &changes)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            properties.get(KEY_CHUNK_SIZE).map(String::as_str),
+            Some("64")
+        );
+        let body: serde_json::Value =
+            serde_json::from_str(&server.requests()[0].body_text()).unwrap();
+        assert_eq!(
+            body,
+            json!({"appProperties": {"gdrive.upload.convert": null, "gdrive.upload.chunkSize": "64"}})
+        );
+    }
+}
@@ -0,0 +1,317 @@
+use crate::common::delegate::ChunkSize;
+use crate::common::delegate::UploadDelegateConfig;
+use crate::common::file_info;
+use crate::common::file_info::FileInfo;
+use crate::common::hub_helper;
+use crate::files::upload;
+use crate::files::upload_defaults::FolderDefaults;
+use crate::hub::Hub;
+use futures::stream;
+use futures::stream::StreamExt;
+use mime::Mime;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs;
+use std::io;
+use std::io::BufRead;
+use std::path::Path;
+use std::path::PathBuf;
+
+const STDIN_PATH: &str = "-";
+const STDIN_REPORT_PATH: &str = "upload-report.csv";
+
+// Uploads every file named in a list, i.e. the output of `find`. The list is
+// read while the files are uploaded, so it can have millions of lines. Each
+// file gets a row in the report as soon as its upload is done
+pub struct Config {
+    // `-` reads the list from stdin
+    pub list_path: PathBuf,
+    // Paths are separated by NUL instead of newlines, for `find -print0`
+    pub null_separated: bool,
+    pub parents: Option<Vec<String>>,
+    pub mime_type: Option<Mime>,
+    pub chunk_size: Option<ChunkSize>,
+    pub media: upload::MediaOptions,
+    // None writes the report next to the list
+    pub report_path: Option<PathBuf>,
+    pub parallel: usize,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub uploaded: usize,
+    pub failed: usize,
+}
+
+pub async fn upload_list(config: Config) -> Result<(), Error> {
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+    let defaults = upload::get_folder_defaults(&hub, &config.parents).await;
+    let delegate_config = upload::delegate_config(config.chunk_size.clone(), &defaults);
+
+    let report_path = config
+        .report_path
+        .clone()
+        .unwrap_or_else(|| default_report_path(&config.list_path));
+    let report = csv::Writer::from_path(&report_path)
+        .map_err(|err| Error::CreateReport(report_path.clone(), err))?;
+
+    let summary = if config.list_path == Path::new(STDIN_PATH) {
+        let list = io::stdin().lock();
+        upload_entries(&hub, list, &config, &defaults, delegate_config, report).await
+    } else {
+        let file = fs::File::open(&config.list_path)
+            .map_err(|err| Error::OpenList(config.list_path.clone(), err))?;
+        let list = io::BufReader::new(file);
+        upload_entries(&hub, list, &config, &defaults, delegate_config, report).await
+    }?;
+
+    println!(
+        "Uploaded {} files, {} failed, see {} for details",
+        summary.uploaded,
+        summary.failed,
+        report_path.display()
+    );
+
+    if summary.failed > 0 {
+        Err(Error::Failed(summary.failed))
+    } else {
+        Ok(())
+    }
+}
+
+// A failed file doesn't stop the others, it is only counted
+pub async fn upload_entries<R: BufRead, W: io::Write>(
+    hub: &Hub,
+    list: R,
+    config: &Config,
+    defaults: &FolderDefaults,
+    delegate_config: UploadDelegateConfig,
+    mut report: csv::Writer<W>,
+) -> Result<Summary, Error> {
+    write_row(&mut report, ["path", "status", "id", "error"])?;
+
+    let separator = if config.null_separated { b'\0' } else { b'\n' };
+    let mut read_error = None;
+    let mut summary = Summary::default();
+
+    {
+        let paths = list
+            .split(separator)
+            .map_while(|entry| match entry {
+                Ok(entry) => Some(entry),
+                Err(err) => {
+                    read_error = Some(err);
+                    None
+                }
+            })
+            .filter_map(|entry| entry_path(&entry, config.null_separated));
+
+        let mut uploads = stream::iter(paths)
+            .map(|path| {
+                let delegate_config = delegate_config.clone();
+                async move {
+                    let result = upload_path(hub, &path, config, defaults, delegate_config).await;
+                    (path, result)
+                }
+            })
+            .buffer_unordered(config.parallel.max(1));
+
+        while let Some((path, result)) = uploads.next().await {
+            let path_text = path.display().to_string();
+            match result {
+                Ok(file_id) => {
+                    println!("Uploaded '{}' with id: {}", path_text, file_id);
+                    write_row(&mut report, [&path_text, "uploaded", &file_id, ""])?;
+                    summary.uploaded += 1;
+                }
+                Err(err) => {
+                    eprintln!("Error: Failed to upload '{}': {}", path_text, err);
+                    write_row(&mut report, [&path_text, "failed", "", &err.to_string()])?;
+                    summary.failed += 1;
+                }
+            }
+        }
+    }
+
+    match read_error {
+        Some(err) => Err(Error::ReadList(err)),
+        None => Ok(summary),
+    }
+}
+
+// Rows are flushed right away, so an interrupted upload still has a report of
+// what was done
+fn write_row<W: io::Write>(report: &mut csv::Writer<W>, row: [&str; 4]) -> Result<(), Error> {
+    report
+        .write_record(row)
+        .and_then(|()| report.flush().map_err(csv::Error::from))
+        .map_err(Error::WriteReport)
+}
+
+async fn upload_path(
+    hub: &Hub,
+    path: &Path,
+    config: &Config,
+    defaults: &FolderDefaults,
+    delegate_config: UploadDelegateConfig,
+) -> Result<String, upload::Error> {
+    if path.is_dir() {
+        return Err(upload::Error::IsDirectory(path.to_path_buf()));
+    }
+
+    let file =
+        fs::File::open(path).map_err(|err| upload::Error::OpenFile(path.to_path_buf(), err))?;
+    let file_info = FileInfo::from_file(
+        &file,
+        &file_info::Config {
+            file_path: path.to_path_buf(),
+            mime_type: config
+                .mime_type
+                .clone()
+                .or_else(|| defaults.mime_type(path)),
+            parents: config.parents.clone(),
+        },
+    )
+    .map_err(upload::Error::FileInfo)?;
+
+    let reader = io::BufReader::new(file);
+    let file = upload::upload_file_with_options(
+        hub,
+        reader,
+        None,
+        file_info,
+        config.media,
+        delegate_config,
+    )
+    .await
+    .map_err(upload::Error::Upload)?;
+
+    Ok(file.id.unwrap_or_default())
+}
+
+// Empty lines are skipped, and lists written on Windows end lines with \r\n
+fn entry_path(entry: &[u8], null_separated: bool) -> Option<PathBuf> {
+    let entry = String::from_utf8_lossy(entry);
+    let entry = if null_separated {
+        entry.as_ref()
+    } else {
+        entry.trim_end_matches('\r')
+    };
+
+    if entry.is_empty() {
+        None
+    } else {
+        Some(PathBuf::from(entry))
+    }
+}
+
+fn default_report_path(list_path: &Path) -> PathBuf {
+    if list_path == Path::new(STDIN_PATH) {
+        return PathBuf::from(STDIN_REPORT_PATH);
+    }
+
+    let mut report_path = list_path.as_os_str().to_owned();
+    report_path.push(".report.csv");
+    PathBuf::from(report_path)
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    OpenList(PathBuf, io::Error),
+    ReadList(io::Error),
+    CreateReport(PathBuf, csv::Error),
+    WriteReport(csv::Error),
+    Failed(usize),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::OpenList(path, err) => {
+                write!(f, "Failed to open list '{}': {}", path.display(), err)
+            }
+            Error::ReadList(err) => write!(f, "Failed to read list: {}", err),
+            Error::CreateReport(path, err) => {
+                write!(f, "Failed to create report '{}': {}", path.display(), err)
+            }
+            Error::WriteReport(err) => write!(f, "Failed to write report: {}", err),
+            Error::Failed(count) => write!(f, "{} files failed to upload", count),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockServer;
+
+    #[test]
+    fn entries_are_split_like_find_prints_them() {
+        assert_eq!(
+            entry_path(b"photos/cat.jpg\r", false),
+            Some(PathBuf::from("photos/cat.jpg"))
+        );
+        assert_eq!(entry_path(b"", false), None);
+        assert_eq!(
+            entry_path(b"name\nwith newline", true),
+            Some(PathBuf::from("name\nwith newline"))
+        );
+        assert_eq!(
+            default_report_path(Path::new("files.txt")),
+            PathBuf::from("files.txt.report.csv")
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_files_are_reported_and_the_rest_uploaded() {
+        let server = MockServer::start().await;
+        server.respond_fixture("POST", "/upload/drive/v3/files", "uploaded_file.json");
+        let hub = server.hub().await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let existing = dir.path().join("notes.txt");
+        fs::write(&existing, "hello").unwrap();
+        let missing = dir.path().join("missing.txt");
+        let list = format!("{}\n\n{}\n", existing.display(), missing.display());
+
+        let config = Config {
+            list_path: PathBuf::from(STDIN_PATH),
+            null_separated: false,
+            parents: None,
+            mime_type: None,
+            chunk_size: None,
+            media: upload::MediaOptions::default(),
+            report_path: None,
+            parallel: 1,
+        };
+        let mut report = vec![];
+        let summary = upload_entries(
+            &hub,
+            list.as_bytes(),
+            &config,
+            &FolderDefaults::default(),
+            UploadDelegateConfig::default(),
+            csv::Writer::from_writer(&mut report),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            summary,
+            Summary {
+                uploaded: 1,
+                failed: 1
+            }
+        );
+        let report = String::from_utf8(report).unwrap();
+        let rows: Vec<&str> = report.lines().collect();
+        assert_eq!(rows[0], "path,status,id,error");
+        assert!(rows[1].starts_with(&format!("{},uploaded,", existing.display())));
+        assert!(rows[2].starts_with(&format!("{},failed,,", missing.display())));
+    }
+}
@@ -0,0 +1,126 @@
+use crate::common::drive_file;
+use crate::common::drive_url;
+use crate::common::hub_helper;
+use crate::files;
+use crate::hub;
+use crate::hub::Hub;
+use google_drive3::hyper;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+// Drive doesn't virus scan files larger than this, their download link shows a
+// warning page instead of the file
+const VIRUS_SCAN_LIMIT: i64 = 100 * 1024 * 1024;
+
+// Value the warning page has used for its confirmation since it moved to
+// drive.usercontent.google.com
+const DEFAULT_CONFIRM: &str = "t";
+
+pub struct Config {
+    pub file_id: String,
+    pub direct: bool,
+}
+
+pub async fn url(config: Config) -> Result<(), Error> {
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+
+    let file = files::info::get_file(&hub, &config.file_id)
+        .await
+        .map_err(Error::GetFile)?;
+
+    let url = if config.direct {
+        direct_url(&hub, &file).await?
+    } else {
+        file.web_view_link
+            .unwrap_or_else(|| drive_url::view_url(&config.file_id))
+    };
+
+    println!("{}", url);
+
+    Ok(())
+}
+
+// Url that downloads the file with i.e. wget or curl. Only works for files that
+// are shared with anyone who has the link
+pub async fn direct_url(hub: &Hub, file: &google_drive3::api::File) -> Result<String, Error> {
+    let file_id = file.id.clone().unwrap_or_default();
+
+    if drive_file::is_directory(file) {
+        return Err(Error::IsDirectory);
+    }
+
+    if !drive_file::is_binary(file) {
+        return Err(Error::NotBinary(file.mime_type.clone().unwrap_or_default()));
+    }
+
+    if file.size.unwrap_or_default() <= VIRUS_SCAN_LIMIT {
+        return Ok(drive_url::download_url(&file_id));
+    }
+
+    let confirm = match find_confirm(hub, &file_id).await {
+        Ok(Some(confirm)) => confirm,
+        Ok(None) => String::from(DEFAULT_CONFIRM),
+        Err(err) => {
+            eprintln!(
+                "Warning: Failed to get the download confirmation, using the default: {}",
+                err
+            );
+            String::from(DEFAULT_CONFIRM)
+        }
+    };
+
+    Ok(drive_url::confirmed_download_url(&file_id, &confirm))
+}
+
+// Requests the download link the way someone without an account would and reads
+// the confirmation from the warning page
+async fn find_confirm(hub: &Hub, file_id: &str) -> Result<Option<String>, hub::RequestError> {
+    let url = drive_url::download_url(file_id);
+    let response = hub
+        .send_anonymous(hyper::Request::get(&url), hyper::Body::empty())
+        .await?;
+
+    let is_html = response
+        .headers()
+        .get(hyper::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/html"));
+
+    // Anything else is the file itself or a redirect to it
+    if !response.status().is_success() || !is_html {
+        return Ok(None);
+    }
+
+    let body = hyper::body::to_bytes(response.into_body())
+        .await
+        .map_err(hub::RequestError::Http)?;
+
+    let html = String::from_utf8_lossy(&body);
+    Ok(drive_url::confirm_from_warning_page(&html))
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    GetFile(google_drive3::Error),
+    IsDirectory,
+    NotBinary(String),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::GetFile(err) => write!(f, "Failed to get file: {}", err),
+            Error::IsDirectory => write!(f, "Folders have no direct download link"),
+            Error::NotBinary(mime) => write!(
+                f,
+                "Files of type '{}' have no direct download link, use files export",
+                mime
+            ),
+        }
+    }
+}
@@ -1,4 +1,8 @@
 use crate::app_config;
+use crate::app_config::HttpConfig;
+use crate::app_config::IpFamily;
+use crate::common::api_stats;
+use crate::common::read_timeout::ReadTimeoutConnector;
 use google_drive3::hyper;
 use google_drive3::hyper::client::HttpConnector;
 use google_drive3::hyper_rustls::HttpsConnector;
@@ -7,39 +11,308 @@ use google_drive3::oauth2;
 use google_drive3::oauth2::authenticator::Authenticator;
 use google_drive3::oauth2::authenticator_delegate::InstalledFlowDelegate;
 use google_drive3::DriveHub;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
 use std::future::Future;
 use std::io;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
 use std::ops::Deref;
 use std::path::PathBuf;
 use std::pin::Pin;
+use std::time::Duration;
 
 pub struct HubConfig {
     pub secret: oauth2::ApplicationSecret,
     pub tokens_path: PathBuf,
 }
 
-pub struct Hub(DriveHub<HttpsConnector<HttpConnector>>);
+const ROOT_URL: &str = "https://www.googleapis.com/";
+const DRIVE_FILES_URL: &str = "https://www.googleapis.com/drive/v3/files";
+const FULL_SCOPE: &str = "https://www.googleapis.com/auth/drive";
+
+type Connector = ReadTimeoutConnector<HttpsConnector<HttpConnector>>;
+pub type HttpClient = hyper::Client<Connector>;
+
+pub struct Hub {
+    drive: DriveHub<Connector>,
+    // Kept for requests the generated api can't express, i.e. ranged downloads
+    http_client: HttpClient,
+    auth: Authenticator<HttpsConnector<HttpConnector>>,
+    root_url: String,
+}
 
 impl Deref for Hub {
-    type Target = DriveHub<HttpsConnector<HttpConnector>>;
+    type Target = DriveHub<Connector>;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.drive
     }
 }
 
 impl Hub {
-    pub async fn new(auth: Auth) -> Hub {
-        let connector = HttpsConnectorBuilder::new()
-            .with_native_roots()
-            .https_or_http()
-            .enable_http1()
-            .enable_http2()
-            .build();
+    pub async fn new(auth: Auth, config: &HttpConfig) -> Hub {
+        Hub::with_root_url(auth, config, ROOT_URL)
+    }
+
+    // Sends the requests for googleapis.com to another server instead, i.e. a
+    // mock server in tests. The url ends with a slash
+    pub fn with_root_url(auth: Auth, config: &HttpConfig, root_url: &str) -> Hub {
+        let http_client = http_client(config);
+        let mut drive = google_drive3::DriveHub::new(http_client.clone(), auth.0.clone());
+        drive.root_url(root_url.to_string());
+        drive.base_url(format!("{}drive/v3/", root_url));
+
+        Hub {
+            drive,
+            http_client,
+            auth: auth.0,
+            root_url: root_url.to_string(),
+        }
+    }
+
+    pub async fn access_token(&self) -> Result<String, RequestError> {
+        self.access_token_for(FULL_SCOPE).await
+    }
+
+    // Tokens are stored per scope, so the first request for a scope other than
+    // the Drive scope asks the user for permission again
+    pub async fn access_token_for(&self, scope: &str) -> Result<String, RequestError> {
+        let token = self
+            .auth
+            .token(&[scope])
+            .await
+            .map_err(RequestError::Token)?;
+        token
+            .token()
+            .map(|token| token.to_string())
+            .ok_or(RequestError::MissingToken)
+    }
+
+    // Sends the request with the account's bearer token added
+    pub async fn send(
+        &self,
+        request: hyper::http::request::Builder,
+        body: hyper::Body,
+    ) -> Result<hyper::Response<hyper::Body>, RequestError> {
+        self.send_with_scope(request, body, FULL_SCOPE).await
+    }
+
+    pub async fn send_with_scope(
+        &self,
+        request: hyper::http::request::Builder,
+        body: hyper::Body,
+        scope: &str,
+    ) -> Result<hyper::Response<hyper::Body>, RequestError> {
+        let token = self.access_token_for(scope).await?;
+        api_stats::record(&request_endpoint(&request));
+        let request = self
+            .redirect(request)
+            .header(hyper::header::AUTHORIZATION, format!("Bearer {}", token))
+            .body(body)
+            .map_err(RequestError::Build)?;
+        self.http_client
+            .request(request)
+            .await
+            .map_err(RequestError::Http)
+    }
+
+    // Sends the request without a token, i.e. to see what someone who only has
+    // the link gets
+    pub async fn send_anonymous(
+        &self,
+        request: hyper::http::request::Builder,
+        body: hyper::Body,
+    ) -> Result<hyper::Response<hyper::Body>, RequestError> {
+        let request = self
+            .redirect(request)
+            .body(body)
+            .map_err(RequestError::Build)?;
+        self.http_client
+            .request(request)
+            .await
+            .map_err(RequestError::Http)
+    }
+
+    fn redirect(&self, request: hyper::http::request::Builder) -> hyper::http::request::Builder {
+        if self.root_url == ROOT_URL {
+            return request;
+        }
+
+        let path = request
+            .uri_ref()
+            .and_then(|uri| uri.to_string().strip_prefix(ROOT_URL).map(String::from));
+        match path {
+            Some(path) => request.uri(format!("{}{}", self.root_url, path)),
+            None => request,
+        }
+    }
+
+    // Downloads `length` bytes of the file content starting at `offset`
+    pub async fn get_media_range(
+        &self,
+        file_id: &str,
+        offset: u64,
+        length: u64,
+    ) -> Result<hyper::body::Bytes, RequestError> {
+        if length == 0 {
+            return Ok(hyper::body::Bytes::new());
+        }
+
+        let request = hyper::Request::get(media_url(file_id)).header(
+            hyper::header::RANGE,
+            format!("bytes={}-{}", offset, offset + length - 1),
+        );
+        let response = self.send(request, hyper::Body::empty()).await?;
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .map_err(RequestError::Http)?;
+
+        if status.is_success() {
+            Ok(body)
+        } else if status == hyper::StatusCode::RANGE_NOT_SATISFIABLE {
+            // Reading past the end of the file
+            Ok(hyper::body::Bytes::new())
+        } else {
+            Err(RequestError::Status(
+                status,
+                String::from_utf8_lossy(&body).to_string(),
+            ))
+        }
+    }
+
+    // Streams the file content from `offset` to the end, i.e. to continue a
+    // download after its connection was dropped
+    pub async fn get_media_from(
+        &self,
+        file_id: &str,
+        offset: u64,
+    ) -> Result<hyper::Body, RequestError> {
+        let request = hyper::Request::get(media_url(file_id))
+            .header(hyper::header::RANGE, format!("bytes={}-", offset));
+        let response = self.send(request, hyper::Body::empty()).await?;
+        let status = response.status();
+
+        // Anything but the requested range would be written at the wrong offset
+        if status == hyper::StatusCode::PARTIAL_CONTENT {
+            return Ok(response.into_body());
+        }
+
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .map_err(RequestError::Http)?;
+        Err(RequestError::Status(
+            status,
+            String::from_utf8_lossy(&body).to_string(),
+        ))
+    }
+}
 
-        let http_client = hyper::Client::builder().build(connector);
+// Client without credentials, the hub adds the token to its requests. Also
+// used on its own to download publicly shared files
+pub fn http_client(config: &HttpConfig) -> HttpClient {
+    let mut http = HttpConnector::new();
+    http.enforce_http(false);
+    http.set_connect_timeout(config.connect_timeout.map(Duration::from_secs));
+    http.set_keepalive(config.keep_alive.map(Duration::from_secs));
 
-        Hub(google_drive3::DriveHub::new(http_client, auth.0))
+    // Binding to the unspecified address of a family makes hyper skip
+    // the resolved addresses of the other family
+    match config.ip_family {
+        Some(IpFamily::Ipv4) => http.set_local_address(Some(Ipv4Addr::UNSPECIFIED.into())),
+        Some(IpFamily::Ipv6) => http.set_local_address(Some(Ipv6Addr::UNSPECIFIED.into())),
+        None => {}
+    }
+
+    let builder = HttpsConnectorBuilder::new()
+        .with_native_roots()
+        .https_or_http()
+        .enable_http1();
+
+    // Some proxies and middleboxes reset long running http2 connections
+    let https = if config.http1_only {
+        builder.wrap_connector(http)
+    } else {
+        builder.enable_http2().wrap_connector(http)
+    };
+
+    let connector = ReadTimeoutConnector::new(https, config.read_timeout.map(Duration::from_secs));
+    hyper::Client::builder().build(connector)
+}
+
+// Requests outside the generated api don't have a method id, they are counted
+// by host instead, i.e. "GET www.googleapis.com"
+fn request_endpoint(request: &hyper::http::request::Builder) -> String {
+    let method = request
+        .method_ref()
+        .map(|method| method.to_string())
+        .unwrap_or_default();
+    let host = request
+        .uri_ref()
+        .and_then(|uri| uri.host())
+        .unwrap_or_default();
+    format!("{} {}", method, host)
+}
+
+pub fn metadata_url(file_id: &str, fields: &str) -> String {
+    format!(
+        "{}/{}?fields={}&supportsAllDrives=true",
+        DRIVE_FILES_URL, file_id, fields
+    )
+}
+
+pub fn list_url(params: &[(&str, &str)]) -> String {
+    let query = params
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, utf8_percent_encode(value, NON_ALPHANUMERIC)))
+        .collect::<Vec<_>>()
+        .join("&");
+    format!("{}?{}", DRIVE_FILES_URL, query)
+}
+
+pub fn permission_url(file_id: &str, permission_id: &str) -> String {
+    format!(
+        "{}/{}/permissions/{}?supportsAllDrives=true",
+        DRIVE_FILES_URL, file_id, permission_id
+    )
+}
+
+pub fn media_url(file_id: &str) -> String {
+    format!(
+        "{}/{}?alt=media&supportsAllDrives=true",
+        DRIVE_FILES_URL, file_id
+    )
+}
+
+#[derive(Debug)]
+pub enum RequestError {
+    Token(oauth2::Error),
+    MissingToken,
+    Build(hyper::http::Error),
+    Http(hyper::Error),
+    Status(hyper::StatusCode, String),
+    Parse(serde_json::Error),
+    Batch(String),
+}
+
+impl error::Error for RequestError {}
+
+impl Display for RequestError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequestError::Token(err) => write!(f, "Failed to get access token: {}", err),
+            RequestError::MissingToken => write!(f, "No access token available"),
+            RequestError::Build(err) => write!(f, "Failed to build request: {}", err),
+            RequestError::Http(err) => write!(f, "Request failed: {}", err),
+            RequestError::Status(status, body) => {
+                write!(f, "Request failed with status {}: {}", status, body)
+            }
+            RequestError::Parse(err) => write!(f, "Failed to parse response: {}", err),
+            RequestError::Batch(err) => write!(f, "Failed to read batch response: {}", err),
+        }
     }
 }
 
@@ -72,6 +345,35 @@ impl Auth {
 
         Ok(Auth(auth))
     }
+
+    // Tokens are only kept in memory, the key is all that is needed
+    pub async fn from_service_account_key(key_json: &str) -> Result<Auth, io::Error> {
+        let key = oauth2::parse_service_account_key(key_json)?;
+        let auth = oauth2::ServiceAccountAuthenticator::builder(key)
+            .build()
+            .await?;
+
+        Ok(Auth(auth))
+    }
+
+    // Uses a refresh token from an earlier consent, so no browser is needed
+    pub async fn from_refresh_token(
+        config: &app_config::Secret,
+        refresh_token: &str,
+    ) -> Result<Auth, io::Error> {
+        let secret = oauth2::authorized_user::AuthorizedUserSecret {
+            client_id: config.client_id.clone(),
+            client_secret: config.client_secret.clone(),
+            refresh_token: refresh_token.to_string(),
+            key_type: String::from("authorized_user"),
+        };
+
+        let auth = oauth2::AuthorizedUserAuthenticator::builder(secret)
+            .build()
+            .await?;
+
+        Ok(Auth(auth))
+    }
 }
 
 fn oauth2_secret(config: &app_config::Secret) -> oauth2::ApplicationSecret {
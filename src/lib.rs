@@ -0,0 +1,26 @@
+pub mod about;
+pub mod account;
+pub mod activity;
+pub mod app_config;
+pub mod backup;
+pub mod batch;
+pub mod client;
+pub mod common;
+pub mod config;
+pub mod drives;
+pub mod files;
+pub mod hub;
+pub mod migrate;
+#[cfg(feature = "mount")]
+pub mod mount;
+pub mod navigate;
+pub mod notify;
+pub mod permissions;
+pub mod publish;
+pub mod revisions;
+pub mod serve;
+pub mod sync;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+pub mod version;
+pub mod watch;
@@ -1,21 +1,46 @@
-pub mod about;
-pub mod account;
-pub mod app_config;
-pub mod common;
-pub mod drives;
-pub mod files;
-pub mod hub;
-pub mod navigate;
-pub mod permissions;
-pub mod version;
+use gdrive::about;
+use gdrive::account;
+use gdrive::activity;
+use gdrive::app_config;
+use gdrive::backup;
+use gdrive::batch;
+use gdrive::common;
+use gdrive::config;
+use gdrive::drives;
+use gdrive::files;
+use gdrive::migrate;
+#[cfg(feature = "mount")]
+use gdrive::mount;
+use gdrive::navigate;
+use gdrive::notify;
+use gdrive::permissions;
+use gdrive::publish;
+use gdrive::revisions;
+use gdrive::serve;
+use gdrive::sync;
+use gdrive::version;
+use gdrive::watch;
 
 use clap::{Parser, Subcommand};
+use common::alerts;
+use common::api_stats;
 use common::delegate::ChunkSize;
+use common::drive_file::FileExtension;
+use common::drive_url;
+use common::file_filter::ByteSize;
+use common::file_filter::FileFilter;
+use common::hub_helper;
 use common::permission;
+use files::list::Corpora;
 use files::list::ListQuery;
+use files::list::ListScope;
 use files::list::ListSortOrder;
+use files::list::SortBy;
+use google_drive3::chrono::DateTime;
+use google_drive3::chrono::Utc;
 use mime::Mime;
 use std::error::Error;
+use std::net::IpAddr;
 use std::path::PathBuf;
 
 #[derive(Parser)]
@@ -23,6 +48,42 @@ use std::path::PathBuf;
 struct Cli {
     #[command(subcommand)]
     command: Command,
+
+    /// Seconds to wait for a connection to be established. Overrides http.connect-timeout in config.toml
+    #[arg(long, global = true, value_name = "SECONDS")]
+    connect_timeout: Option<u64>,
+
+    /// Seconds without receiving any data before a connection is dropped and the request retried
+    #[arg(long, global = true, value_name = "SECONDS")]
+    read_timeout: Option<u64>,
+
+    /// Interval in seconds for TCP keep-alive probes on open connections
+    #[arg(long, global = true, value_name = "SECONDS")]
+    keep_alive: Option<u64>,
+
+    /// Use HTTP/1.1 only, for networks that break long running HTTP/2 connections
+    #[arg(long, global = true)]
+    http1: bool,
+
+    /// Only connect to Google Drive over IPv4
+    #[arg(long, global = true, conflicts_with = "ipv6")]
+    ipv4: bool,
+
+    /// Only connect to Google Drive over IPv6
+    #[arg(long, global = true)]
+    ipv6: bool,
+
+    /// Only print the id of created files, folders and shortcuts, i.e. to capture it in scripts
+    #[arg(long, global = true)]
+    print_only_id: bool,
+
+    /// Print the number of API calls made by endpoint when the command finishes, to stderr
+    #[arg(long, global = true)]
+    stats: bool,
+
+    /// Account to use instead of the current one, see `gdrive account list`
+    #[arg(long, global = true, value_name = "NAME")]
+    account: Option<String>,
 }
 
 #[derive(Subcommand)]
@@ -54,11 +115,283 @@ enum Command {
         command: PermissionCommand,
     },
 
+    /// Commands for managing file revisions
+    Revisions {
+        #[command(subcommand)]
+        command: RevisionCommand,
+    },
+
+    /// Commands for syncing local directories with Drive
+    Sync {
+        #[command(subcommand)]
+        command: SyncCommand,
+    },
+
     /// Print version information
     Version,
 
     /// Open interactive TUI
-    Navigate,
+    Navigate {
+        /// Replay the keys in a file without a terminal and print the status after each step and the final screen, i.e. to attach to a bug report
+        #[arg(long, value_name = "FILE")]
+        script: Option<PathBuf>,
+
+        /// Directory the upload picker opens in [default: the directory of the last upload, or the current directory]
+        #[arg(long, value_name = "DIRECTORY")]
+        start_dir: Option<PathBuf>,
+    },
+
+    /// Back up a directory as a snapshot, unchanged files are linked to the previous snapshot
+    Backup {
+        /// Directory to back up
+        path: PathBuf,
+
+        /// Folder the snapshots are created in
+        #[arg(long, value_name = "DIRECTORY_ID", value_parser = drive_url::parse_id)]
+        parent: String,
+
+        /// Upload every file instead of linking unchanged files
+        #[arg(long)]
+        full: bool,
+    },
+
+    /// Commands for running many operations from a plan file
+    Batch {
+        #[command(subcommand)]
+        command: BatchCommand,
+    },
+
+    /// Copy a folder and everything in it from one account to another, run it again to continue an interrupted migration
+    Migrate {
+        /// Account to copy from, see `gdrive account list`
+        #[arg(long, value_name = "ACCOUNT")]
+        from_account: String,
+
+        /// Account to copy to
+        #[arg(long, value_name = "ACCOUNT")]
+        to_account: String,
+
+        /// Folder to copy
+        #[arg(long, value_name = "DIRECTORY_ID", value_parser = drive_url::parse_id)]
+        folder: String,
+
+        /// Folder in the destination account to copy into [default: My Drive]
+        #[arg(long, value_name = "DIRECTORY_ID", value_parser = drive_url::parse_id)]
+        parent: Option<String>,
+    },
+
+    /// Upload a release artifact and print a link to download it
+    Publish {
+        /// Path of file to publish
+        file_path: PathBuf,
+
+        /// Folder to upload the file to
+        #[arg(long, value_name = "DIRECTORY_ID", value_parser = drive_url::parse_id)]
+        folder: Option<String>,
+
+        /// Share the file with anyone who has the link
+        #[arg(long)]
+        public: bool,
+
+        /// Print id, name, size, checksum and links as json instead of only the download link
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Download files as they are added to a Drive folder, files already in it are downloaded on the first run
+    Watch {
+        /// Folder to watch
+        #[arg(value_parser = drive_url::parse_id)]
+        folder_id: String,
+
+        /// Local directory to download new files to
+        #[arg(long, value_name = "DIRECTORY")]
+        download_to: PathBuf,
+
+        /// Seconds between checks for new files
+        #[arg(long, value_name = "SECONDS", default_value_t = 60)]
+        interval: u64,
+
+        /// Check for new files once and exit, i.e. to run from cron
+        #[arg(long)]
+        once: bool,
+    },
+
+    /// Show who created, edited, renamed, moved or shared a file, or anything in a folder
+    Activity {
+        /// Id of file or directory
+        #[arg(value_parser = drive_url::parse_id)]
+        file_id: String,
+
+        /// Max activities to show
+        #[arg(long, default_value_t = 50)]
+        max: usize,
+
+        /// Print the activities as json, as returned by the Drive Activity API
+        #[arg(long)]
+        json: bool,
+
+        /// Don't print header
+        #[arg(long)]
+        skip_header: bool,
+
+        /// Field separator
+        #[arg(long, default_value_t = String::from("\t"))]
+        field_separator: String,
+    },
+
+    /// Serve Drive files over HTTP
+    Serve {
+        /// Port to listen on
+        #[arg(long, default_value_t = 8080)]
+        port: u16,
+
+        /// Address to listen on, use 0.0.0.0 to make Drive available to other devices on the network
+        #[arg(long, default_value = "127.0.0.1")]
+        address: IpAddr,
+
+        /// Serve this folder instead of My Drive
+        #[arg(long, value_name = "DIRECTORY_ID", value_parser = drive_url::parse_id)]
+        root: Option<String>,
+
+        /// Serve transfer, error and API call counters for Prometheus on /metrics
+        #[arg(long)]
+        metrics: bool,
+
+        /// Reject uploads, files can only be read
+        #[arg(long)]
+        read_only: bool,
+    },
+
+    /// Commands for Drive push notifications, an alternative to polling for users with a public https endpoint
+    Notify {
+        #[command(subcommand)]
+        command: NotifyCommand,
+    },
+
+    /// Commands for the settings in config.toml
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+
+    /// Mount Drive as a filesystem (requires the `mount` feature)
+    #[cfg(feature = "mount")]
+    Mount {
+        /// Directory to mount on
+        mountpoint: PathBuf,
+
+        /// Mount this folder instead of My Drive
+        #[arg(long, value_name = "DIRECTORY_ID", value_parser = drive_url::parse_id)]
+        root: Option<String>,
+
+        /// Seconds to cache folder listings before asking Drive again
+        #[arg(long, value_name = "SECONDS", default_value_t = 60)]
+        cache_ttl: u64,
+
+        /// Size in MB of the chunks fetched from Drive when reading files
+        #[arg(long, value_name = "MB", default_value_t = 8)]
+        read_chunk_size: u64,
+
+        /// Allow changes through the mount, written files are uploaded when they are closed
+        #[arg(long)]
+        read_write: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum BatchCommand {
+    /// Run the upload, mkdir, move, permission and delete steps of a json or csv plan in order
+    Run {
+        /// Path of the plan, files ending in .csv are read as csv and anything else as json
+        plan: PathBuf,
+
+        /// Run the remaining steps when a step fails instead of stopping
+        #[arg(long)]
+        continue_on_error: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum NotifyCommand {
+    /// Ask Drive to post a notification to an https url when a file or folder changes, or anything when no id is given
+    Watch {
+        /// File or folder to watch [default: all changes]
+        #[arg(value_parser = drive_url::parse_id)]
+        file_id: Option<String>,
+
+        /// Public https url that receives the notifications, i.e. a proxy in front of `gdrive notify listen`
+        #[arg(long, value_name = "URL")]
+        address: String,
+
+        /// Seconds until the channel expires [default: chosen by Drive, at most a day for files and a week for changes]
+        #[arg(long, value_name = "SECONDS")]
+        ttl: Option<u64>,
+    },
+
+    /// List channels created with this account
+    List {
+        /// Don't print header
+        #[arg(long)]
+        skip_header: bool,
+
+        /// Field separator
+        #[arg(long, default_value_t = String::from("\t"))]
+        field_separator: String,
+    },
+
+    /// Stop a channel, Drive stops sending its notifications
+    Stop {
+        /// Channel id, see `gdrive notify list`
+        channel_id: String,
+    },
+
+    /// Receive notifications and run hooks for them
+    Listen {
+        /// Port to listen on
+        #[arg(long, default_value_t = 8086)]
+        port: u16,
+
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1")]
+        address: IpAddr,
+
+        /// Shell command to run for every notification, can be repeated. The notification is passed in GDRIVE_* environment variables
+        #[arg(long, value_name = "COMMAND")]
+        hook: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print the value of a setting, i.e. chunk-size or keymap.download
+    Get {
+        /// Setting, see `gdrive config list`
+        key: String,
+    },
+
+    /// Change a setting in config.toml, an empty value removes it. Keys of the keymap are separated by spaces
+    Set {
+        /// Setting, see `gdrive config list`
+        key: String,
+
+        /// New value
+        value: String,
+    },
+
+    /// List the settings, their values and whether they come from the defaults, config.toml, the environment or a flag
+    List {
+        /// Don't print header
+        #[arg(long)]
+        skip_header: bool,
+
+        /// Field separator
+        #[arg(long, default_value_t = String::from("\t"))]
+        field_separator: String,
+    },
+
+    /// Check the config, credentials, network and access to Drive, and print how to fix what is wrong
+    Doctor,
 }
 
 #[derive(Subcommand)]
@@ -109,6 +442,112 @@ enum DriveCommand {
         #[arg(long, default_value_t = String::from("\t"))]
         field_separator: String,
     },
+
+    /// Create a shared drive
+    Create {
+        /// Name of the drive
+        name: String,
+    },
+
+    /// Rename a shared drive
+    Rename {
+        /// Drive id
+        drive_id: String,
+
+        /// New name
+        name: String,
+
+        /// Act as a Workspace admin, for drives you are not a member of
+        #[arg(long)]
+        admin: bool,
+    },
+
+    /// Delete a shared drive, it has to be empty
+    Delete {
+        /// Drive id
+        drive_id: String,
+
+        /// Act as a Workspace admin, for drives you are not a member of
+        #[arg(long)]
+        admin: bool,
+    },
+
+    /// Hide a shared drive from your default view
+    Hide {
+        /// Drive id
+        drive_id: String,
+    },
+
+    /// Show a hidden shared drive in your default view again
+    Unhide {
+        /// Drive id
+        drive_id: String,
+    },
+
+    /// Commands for managing the members of a shared drive
+    Members {
+        #[command(subcommand)]
+        command: DriveMemberCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum DriveMemberCommand {
+    /// List members of a shared drive
+    List {
+        /// Drive id
+        drive_id: String,
+
+        /// Act as a Workspace admin, for drives you are not a member of
+        #[arg(long)]
+        admin: bool,
+
+        /// Don't print header
+        #[arg(long)]
+        skip_header: bool,
+
+        /// Field separator
+        #[arg(long, default_value_t = String::from("\t"))]
+        field_separator: String,
+    },
+
+    /// Add a user or group to a shared drive
+    Add {
+        /// Drive id
+        drive_id: String,
+
+        /// Email address of the user or group
+        email: String,
+
+        /// Role of the member. Allowed values are: organizer, fileOrganizer, writer, commenter, reader
+        #[arg(long, default_value_t = permission::Role::Writer)]
+        role: permission::Role,
+
+        /// Type of the member. Allowed values are: user, group
+        #[arg(long, default_value_t = permission::Type::User)]
+        type_: permission::Type,
+
+        /// Send the new member an email about the drive
+        #[arg(long)]
+        notify: bool,
+
+        /// Act as a Workspace admin, for drives you are not a member of
+        #[arg(long)]
+        admin: bool,
+    },
+
+    /// Remove a user or group from a shared drive
+    Remove {
+        /// Drive id
+        drive_id: String,
+
+        /// Email address of the user or group
+        email: String,
+
+        /// Act as a Workspace admin, for drives you are not a member of
+        #[arg(long)]
+        admin: bool,
+    },
 }
 
 #[derive(Subcommand)]
@@ -116,11 +555,24 @@ enum FileCommand {
     /// Print file info
     Info {
         /// File id
+        #[arg(value_parser = drive_url::parse_id)]
         file_id: String,
 
         /// Display size in bytes
         #[arg(long, default_value_t = false)]
         size_in_bytes: bool,
+
+        /// Print all metadata as json
+        #[arg(long, default_value_t = false)]
+        json: bool,
+
+        /// Print only the value of this field, i.e. Path or CanEdit
+        #[arg(long, value_name = "NAME", conflicts_with = "json")]
+        field: Option<String>,
+
+        /// Don't use or revalidate cached responses, fetch everything again
+        #[arg(long)]
+        no_cache: bool,
     },
 
     /// List files
@@ -137,14 +589,30 @@ enum FileCommand {
         #[arg(long, default_value_t = ListSortOrder::default())]
         order_by: ListSortOrder,
 
+        /// Sort by `quota`: the storage a file uses, largest first. Add --query 'trashed = true' to find trashed files that still use storage
+        #[arg(long, value_name = "quota", conflicts_with = "order_by")]
+        sort: Option<SortBy>,
+
         /// List files in a specific folder
-        #[arg(long, value_name = "DIRECTORY_ID")]
+        #[arg(long, value_name = "DIRECTORY_ID", value_parser = drive_url::parse_id)]
         parent: Option<String>,
 
-        /// List files on a shared drive
-        #[arg(long, value_name = "DRIVE_ID")]
+        /// List files on a shared drive. With --parent or --query only the shared drive is searched
+        #[arg(long, value_name = "DRIVE_ID", value_parser = drive_url::parse_id)]
         drive: Option<String>,
 
+        /// Collections to search: user (default), domain or all-drives. Use --drive for a single shared drive
+        #[arg(long, value_name = "CORPORA", conflicts_with = "drive")]
+        corpora: Option<Corpora>,
+
+        /// List files owned by `me` or by an email address, in the whole Drive unless --parent or --drive is given
+        #[arg(long, value_name = "me|EMAIL", conflicts_with = "query")]
+        owner: Option<String>,
+
+        /// List your files that have no parent folder, see `gdrive files adopt`
+        #[arg(long, conflicts_with_all = ["query", "parent", "drive", "owner"])]
+        orphaned: bool,
+
         /// Don't print header
         #[arg(long)]
         skip_header: bool,
@@ -156,12 +624,33 @@ enum FileCommand {
         /// Field separator
         #[arg(long, default_value_t = String::from("\t"))]
         field_separator: String,
+
+        /// Only include files of at least this size, i.e. 100K, 1.5M or 2G
+        #[arg(long, value_name = "SIZE")]
+        min_size: Option<ByteSize>,
+
+        /// Only include files of at most this size, i.e. 100K, 1.5M or 2G
+        #[arg(long, value_name = "SIZE")]
+        max_size: Option<ByteSize>,
+
+        /// Only include files of this mime type, i.e. image/png or video/*. Can be repeated
+        #[arg(long, value_name = "MIME_TYPE")]
+        mime_include: Vec<String>,
+
+        /// Exclude files of this mime type, i.e. image/png or video/*. Can be repeated
+        #[arg(long, value_name = "MIME_TYPE")]
+        mime_exclude: Vec<String>,
+
+        /// Don't use or revalidate cached responses, fetch everything again
+        #[arg(long)]
+        no_cache: bool,
     },
 
     /// Download file
     Download {
         /// File id
-        file_id: String,
+        #[arg(value_parser = drive_url::parse_id, required_unless_present = "from_list")]
+        file_id: Option<String>,
 
         /// Overwrite existing files and folders
         #[arg(long)]
@@ -176,12 +665,107 @@ enum FileCommand {
         recursive: bool,
 
         /// Path where the file/directory should be downloaded to
-        #[arg(long, value_name = "PATH")]
+        #[arg(long, visible_alias = "out", value_name = "PATH")]
         destination: Option<PathBuf>,
 
         /// Write file to stdout
         #[arg(long)]
         stdout: bool,
+
+        /// Replacement for characters in Drive names that aren't valid in local file names, like / and \
+        #[arg(long, value_name = "TEXT", default_value_t = String::from(common::file_name::DEFAULT_REPLACEMENT))]
+        name_replacement: String,
+
+        /// Only include files of at least this size, i.e. 100K, 1.5M or 2G
+        #[arg(long, value_name = "SIZE")]
+        min_size: Option<ByteSize>,
+
+        /// Only include files of at most this size, i.e. 100K, 1.5M or 2G
+        #[arg(long, value_name = "SIZE")]
+        max_size: Option<ByteSize>,
+
+        /// Only include files of this mime type, i.e. image/png or video/*. Can be repeated
+        #[arg(long, value_name = "MIME_TYPE")]
+        mime_include: Vec<String>,
+
+        /// Exclude files of this mime type, i.e. image/png or video/*. Can be repeated
+        #[arg(long, value_name = "MIME_TYPE")]
+        mime_exclude: Vec<String>,
+
+        /// Also write the download to this directory, i.e. a backup disk. Can be repeated
+        #[arg(long, value_name = "DIRECTORY")]
+        also: Vec<PathBuf>,
+
+        /// Read downloaded files back from disk and check that their md5 matches what was downloaded
+        #[arg(long, conflicts_with = "stdout")]
+        paranoid: bool,
+
+        /// Don't check that the destination has enough free space before downloading
+        #[arg(long)]
+        skip_space_check: bool,
+
+        /// Write incomplete downloads to this directory and move them into place when they are complete [default: next to the destination]
+        #[arg(long, value_name = "DIRECTORY", conflicts_with = "stdout")]
+        temp_dir: Option<PathBuf>,
+
+        /// Put all files of a recursive download directly in the destination instead of recreating the folders. Files with the same name get a number
+        #[arg(long, requires = "recursive")]
+        flatten: bool,
+
+        /// Leave out this many leading folders of a recursive download, 1 leaves out the downloaded folder itself
+        #[arg(
+            long,
+            value_name = "N",
+            requires = "recursive",
+            conflicts_with = "flatten"
+        )]
+        strip_prefix: Option<usize>,
+
+        /// Download a file shared with anyone who has the link, without an account
+        #[arg(long, conflicts_with_all = ["recursive", "follow_shortcuts"])]
+        no_auth: bool,
+
+        /// Download the files whose ids are listed in a file, one id or url per line. Use - to read the list from stdin
+        #[arg(long, value_name = "LIST_FILE", conflicts_with_all = ["file_id", "stdout", "no_auth"])]
+        from_list: Option<PathBuf>,
+
+        /// Write the ids that failed to download to this file, it can be passed to --from-list to retry them [default: <LIST_FILE>.failed, or download-failed.txt for stdin]
+        #[arg(long, value_name = "RETRY_FILE", requires = "from_list")]
+        retry_file: Option<PathBuf>,
+
+        /// Number of files of the list to download at the same time [default: concurrency in config.toml or 4]
+        #[arg(long, requires = "from_list")]
+        parallel: Option<usize>,
+    },
+
+    /// Download the files of a folder that are missing locally or differ from Drive, a one-way mirror without sync state
+    Pull {
+        /// Folder id
+        #[arg(value_parser = drive_url::parse_id)]
+        folder_id: String,
+
+        /// Local directory to mirror the folder to, it is created if it doesn't exist
+        local_path: PathBuf,
+
+        /// Delete local files that are not on Drive
+        #[arg(long)]
+        delete: bool,
+
+        /// Only include files of at least this size, i.e. 100K, 1.5M or 2G
+        #[arg(long, value_name = "SIZE")]
+        min_size: Option<ByteSize>,
+
+        /// Only include files of at most this size, i.e. 100K, 1.5M or 2G
+        #[arg(long, value_name = "SIZE")]
+        max_size: Option<ByteSize>,
+
+        /// Only include files of this mime type, i.e. image/png or video/*. Can be repeated
+        #[arg(long, value_name = "MIME_TYPE")]
+        mime_include: Vec<String>,
+
+        /// Exclude files of this mime type, i.e. image/png or video/*. Can be repeated
+        #[arg(long, value_name = "MIME_TYPE")]
+        mime_exclude: Vec<String>,
     },
 
     /// Upload file
@@ -189,21 +773,29 @@ enum FileCommand {
         /// Path of file to upload
         file_path: Option<PathBuf>,
 
-        /// Force mime type [default: auto-detect]
+        /// Name of the file on drive [default: local file name]. strftime sequences like %Y-%m-%d, {date}, {time}, {hostname}, {uuid} and {name} (the local file name) are replaced, i.e. backup-{hostname}-%Y-%m-%d.tar.gz. Use %% for a literal %
+        #[arg(long, conflicts_with = "recursive")]
+        name: Option<String>,
+
+        /// Force mime type [default: upload defaults of the parent or auto-detect]
         #[arg(long, value_name = "MIME_TYPE")]
         mime: Option<Mime>,
 
-        /// Upload to an existing directory
-        #[arg(long, value_name = "DIRECTORY_ID")]
+        /// Upload to an existing directory [default: default-parent in config.toml or My Drive]
+        #[arg(long, value_name = "DIRECTORY_ID", value_parser = drive_url::parse_id)]
         parent: Option<Vec<String>>,
 
         /// Upload directories. Note that this will always create a new directory on drive and will not update existing directories with the same name
         #[arg(long)]
         recursive: bool,
 
-        /// Set chunk size in MB, must be a power of two.
-        #[arg(long, value_name = "1|2|4|8|16|32|64|128|256|512|1024|4096|8192", default_value_t = ChunkSize::default())]
-        chunk_size: ChunkSize,
+        /// Also upload dot files and hidden directories of the directory, i.e. for a backup of $HOME
+        #[arg(long, requires = "recursive")]
+        hidden: bool,
+
+        /// Set chunk size in MB, must be a power of two [default: upload defaults of the parent, chunk-size in config.toml or 32]
+        #[arg(long, value_name = "1|2|4|8|16|32|64|128|256|512|1024|4096|8192")]
+        chunk_size: Option<ChunkSize>,
 
         /// Print errors occuring during chunk upload
         #[arg(long, value_name = "", default_value_t = false)]
@@ -213,14 +805,47 @@ enum FileCommand {
         #[arg(long, value_name = "", default_value_t = false)]
         print_chunk_info: bool,
 
-        /// Print only id of file/folder
-        #[arg(long, default_value_t = false)]
-        print_only_id: bool,
+        /// Convert an image or PDF to a Google document with the text recognized by OCR
+        #[arg(long, conflicts_with = "recursive")]
+        ocr: bool,
+
+        /// Language hint for OCR as an ISO 639-1 code, i.e. en or tr
+        #[arg(long, value_name = "LANGUAGE", requires = "ocr")]
+        ocr_lang: Option<String>,
+
+        /// Delete the directory created on drive and everything in it when a directory upload fails or is cancelled with Ctrl-C
+        #[arg(long, requires = "recursive")]
+        rollback_on_failure: bool,
+
+        /// Let Drive index the content as text for search, i.e. for subtitles or transcripts
+        #[arg(long)]
+        indexable_text: bool,
+
+        /// Keep the uploaded revision forever instead of letting Drive delete it when newer revisions are uploaded. At most 200 revisions of a file can be kept forever
+        #[arg(long)]
+        keep_forever: bool,
+
+        /// Upload the files listed in a file, one path per line. Use - to read the list from stdin, i.e. from find
+        #[arg(long, value_name = "LIST_FILE", conflicts_with_all = ["file_path", "name", "recursive", "ocr"])]
+        from_list: Option<PathBuf>,
+
+        /// The paths in the list are separated by NUL, like find -print0 writes them
+        #[arg(short = '0', long = "null", requires = "from_list")]
+        null_separated: bool,
+
+        /// Write the result of each file of the list as csv to this file [default: <LIST_FILE>.report.csv, or upload-report.csv for stdin]
+        #[arg(long, value_name = "REPORT_FILE", requires = "from_list")]
+        report: Option<PathBuf>,
+
+        /// Number of files of the list to upload at the same time [default: concurrency in config.toml or 4]
+        #[arg(long, requires = "from_list")]
+        parallel: Option<usize>,
     },
 
     /// Update file. This will create a new version of the file. The older versions will typically be kept for 30 days.
     Update {
         /// File id of the file you want ot update
+        #[arg(value_parser = drive_url::parse_id)]
         file_id: String,
 
         /// Path of file to upload
@@ -230,9 +855,9 @@ enum FileCommand {
         #[arg(long, value_name = "MIME_TYPE")]
         mime: Option<Mime>,
 
-        /// Set chunk size in MB, must be a power of two.
-        #[arg(long, value_name = "1|2|4|8|16|32|64|128|256|512|1024|4096|8192", default_value_t = ChunkSize::default())]
-        chunk_size: ChunkSize,
+        /// Set chunk size in MB, must be a power of two [default: chunk-size in config.toml or 32]
+        #[arg(long, value_name = "1|2|4|8|16|32|64|128|256|512|1024|4096|8192")]
+        chunk_size: Option<ChunkSize>,
 
         /// Print errors occuring during chunk upload
         #[arg(long, value_name = "", default_value_t = false)]
@@ -241,11 +866,16 @@ enum FileCommand {
         /// Print details about each chunk
         #[arg(long, value_name = "", default_value_t = false)]
         print_chunk_info: bool,
+
+        /// Skip the upload if the md5 of the content matches the file on Drive
+        #[arg(long)]
+        skip_unchanged: bool,
     },
 
-    /// Delete file
+    /// Permanently delete file, skipping the trash
     Delete {
         /// File id
+        #[arg(value_parser = drive_url::parse_id)]
         file_id: String,
 
         /// Delete directory and all it's content
@@ -253,23 +883,41 @@ enum FileCommand {
         recursive: bool,
     },
 
+    /// Move file to trash
+    Trash {
+        /// File id
+        #[arg(value_parser = drive_url::parse_id)]
+        file_id: String,
+
+        /// Trash directory and all it's content
+        #[arg(long)]
+        recursive: bool,
+    },
+
+    /// Restore file from trash
+    Untrash {
+        /// File id
+        #[arg(value_parser = drive_url::parse_id)]
+        file_id: String,
+    },
+
+    /// Restore the most recently trashed file that is still in the trash, according to the local deletion journal
+    UndeleteLast,
+
     /// Create directory
     Mkdir {
-        /// Name
+        /// Name. strftime sequences like %Y-%m-%d, {date}, {time}, {hostname} and {uuid} are replaced, use %% for a literal %
         name: String,
 
-        /// Create in an existing directory
-        #[arg(long, value_name = "DIRECTORY_ID")]
+        /// Create in an existing directory [default: default-parent in config.toml or My Drive]
+        #[arg(long, value_name = "DIRECTORY_ID", value_parser = drive_url::parse_id)]
         parent: Option<Vec<String>>,
-
-        /// Print only id of folder
-        #[arg(long, default_value_t = false)]
-        print_only_id: bool,
     },
 
     /// Rename file/directory
     Rename {
         /// Id of file or directory
+        #[arg(value_parser = drive_url::parse_id)]
         file_id: String,
 
         /// New name
@@ -279,39 +927,73 @@ enum FileCommand {
     /// Move file/directory
     Move {
         /// Id of file or directory to move
+        #[arg(value_parser = drive_url::parse_id)]
         file_id: String,
 
         /// Id of folder to move to
+        #[arg(value_parser = drive_url::parse_id)]
         folder_id: String,
     },
 
-    /// Copy file
-    Copy {
+    /// Add a file without parents to a folder, see `gdrive files list --orphaned`
+    Adopt {
+        /// Id of file or directory
+        #[arg(value_parser = drive_url::parse_id)]
+        file_id: String,
+
+        /// Id of folder to add the file to
+        #[arg(long, value_name = "DIRECTORY_ID", value_parser = drive_url::parse_id)]
+        parent: String,
+    },
+
+    /// Remove your own access to a file or folder that is shared with you
+    UnshareMe {
+        /// Id of file or directory
+        #[arg(value_parser = drive_url::parse_id)]
+        file_id: String,
+    },
+
+    /// Copy file
+    Copy {
         /// Id of file or directory to move
+        #[arg(value_parser = drive_url::parse_id)]
         file_id: String,
 
         /// Id of folder to copy to
+        #[arg(value_parser = drive_url::parse_id)]
         folder_id: String,
     },
 
+    /// Create a shortcut to a file or folder
+    Shortcut {
+        /// Id of file or folder the shortcut points to
+        #[arg(value_parser = drive_url::parse_id)]
+        target_id: String,
+
+        /// Create in an existing directory
+        #[arg(long, value_name = "DIRECTORY_ID", value_parser = drive_url::parse_id)]
+        parent: Option<String>,
+
+        /// Name of the shortcut [default: name of the target]
+        #[arg(long)]
+        name: Option<String>,
+    },
+
     /// Import file as a google document/spreadsheet/presentation.
     /// Example of file types that can be imported: doc, docx, odt, pdf, html, xls, xlsx, csv, ods, ppt, pptx, odp
     Import {
         /// Path to file
         file_path: PathBuf,
 
-        /// Upload to an existing directory
-        #[arg(long, value_name = "DIRECTORY_ID")]
+        /// Upload to an existing directory [default: default-parent in config.toml or My Drive]
+        #[arg(long, value_name = "DIRECTORY_ID", value_parser = drive_url::parse_id)]
         parent: Option<Vec<String>>,
-
-        /// Print only id of file
-        #[arg(long, default_value_t = false)]
-        print_only_id: bool,
     },
 
     /// Export google document to file
     Export {
         /// File id
+        #[arg(value_parser = drive_url::parse_id)]
         file_id: String,
 
         /// File path to export to. The file extension will determine the export format
@@ -321,6 +1003,198 @@ enum FileCommand {
         #[arg(long)]
         overwrite: bool,
     },
+
+    /// Convert between Google documents and other formats, i.e. docx to a Google document or the other way around. The original file is kept
+    Convert {
+        /// File id
+        #[arg(value_parser = drive_url::parse_id)]
+        file_id: String,
+
+        /// Format to convert to: gdoc, gsheet, gslides or a file type like docx, xlsx or pdf
+        #[arg(long)]
+        to: files::convert::Format,
+    },
+
+    /// Commands for changing file metadata
+    Meta {
+        #[command(subcommand)]
+        command: MetaCommand,
+    },
+
+    /// Export every Google document in a folder and its subfolders, keeping the folder structure
+    ExportAll {
+        /// Folder id
+        #[arg(value_parser = drive_url::parse_id)]
+        folder_id: String,
+
+        /// File type to export to, i.e. pdf, docx or odt. Documents that can't be exported to it are skipped
+        #[arg(long)]
+        format: FileExtension,
+
+        /// Directory to export to, the folder is created in it
+        #[arg(long, value_name = "DIRECTORY", default_value = ".")]
+        out: PathBuf,
+
+        /// Overwrite existing files, they are skipped by default
+        #[arg(long)]
+        overwrite: bool,
+
+        /// Number of documents to export at the same time [default: concurrency in config.toml or 4]
+        #[arg(long)]
+        parallel: Option<usize>,
+    },
+
+    /// Save the metadata of all files to a SQLite database for offline queries. Later runs only fetch what changed
+    Index {
+        /// Path of the database [default: index.sqlite in the account directory]
+        #[arg(long, value_name = "PATH")]
+        db: Option<PathBuf>,
+
+        /// Only index this folder and its subfolders
+        #[arg(long, value_name = "DIRECTORY_ID", value_parser = drive_url::parse_id)]
+        folder: Option<String>,
+
+        /// Crawl everything again instead of applying the changes since the last run
+        #[arg(long)]
+        full: bool,
+    },
+
+    /// Search files by name
+    Search {
+        /// Part of the file name
+        pattern: String,
+
+        /// Search the local index from `gdrive files index` instead of Drive. Results may be out of date
+        #[arg(long)]
+        offline: bool,
+
+        /// Path of the index [default: index.sqlite in the account directory]
+        #[arg(long, value_name = "PATH", requires = "offline")]
+        db: Option<PathBuf>,
+
+        /// Max files to list
+        #[arg(long, default_value_t = 30)]
+        max: usize,
+
+        /// Don't print header
+        #[arg(long)]
+        skip_header: bool,
+
+        /// Field separator
+        #[arg(long, default_value_t = String::from("\t"))]
+        field_separator: String,
+    },
+
+    /// Print a link to a file
+    Url {
+        /// File id
+        #[arg(value_parser = drive_url::parse_id)]
+        file_id: String,
+
+        /// Print a link that downloads the file with i.e. wget or curl, instead of opening it in Drive
+        #[arg(long)]
+        direct: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum MetaCommand {
+    /// Set the description, folder color or last viewed time of a file
+    Set {
+        /// Id of file or directory
+        #[arg(value_parser = drive_url::parse_id)]
+        file_id: String,
+
+        /// Description, use an empty string to remove it
+        #[arg(long)]
+        description: Option<String>,
+
+        /// Folder color as #rrggbb, Drive uses the closest color it supports
+        #[arg(long, value_name = "COLOR")]
+        folder_color: Option<files::meta::FolderColor>,
+
+        /// Mark the file as viewed by you now
+        #[arg(long)]
+        viewed: bool,
+    },
+
+    /// Print or change the defaults for uploads to a folder. They are kept in the folder's appProperties, which only gdrive with the same OAuth client sees
+    UploadDefaults {
+        /// Folder id
+        #[arg(value_parser = drive_url::parse_id)]
+        folder_id: String,
+
+        /// Convert files to Google documents when Drive can import them
+        #[arg(long, value_name = "true|false")]
+        convert: Option<bool>,
+
+        /// Chunk size in MB, must be a power of two
+        #[arg(long, value_name = "1|2|4|8|16|32|64|128|256|512|1024|4096|8192")]
+        chunk_size: Option<ChunkSize>,
+
+        /// Mime type for files with an extension as ext=mime/type, i.e. md=text/markdown. An empty mime type removes it. Can be repeated
+        #[arg(long, value_name = "EXT=MIME_TYPE")]
+        mime: Vec<files::upload_defaults::MimeOverride>,
+
+        /// Remove all upload defaults of the folder before setting the given ones
+        #[arg(long)]
+        clear: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum RevisionCommand {
+    /// List revisions of a file
+    List {
+        /// File id
+        #[arg(value_parser = drive_url::parse_id)]
+        file_id: String,
+
+        /// Don't print header
+        #[arg(long)]
+        skip_header: bool,
+
+        /// Field separator
+        #[arg(long, default_value_t = String::from("\t"))]
+        field_separator: String,
+    },
+
+    /// Keep a revision of a binary file forever, so Drive doesn't delete it when it gets old
+    Keep {
+        /// File id
+        #[arg(value_parser = drive_url::parse_id)]
+        file_id: String,
+
+        /// Revision id
+        revision_id: String,
+
+        /// Let Drive delete the revision again when it gets old
+        #[arg(long)]
+        off: bool,
+    },
+
+    /// Download a file as it was at a point in time
+    Download {
+        /// File id
+        #[arg(value_parser = drive_url::parse_id)]
+        file_id: String,
+
+        /// Download the newest revision from on or before this time, i.e. 2024-06-01 (end of the day), '2024-06-01 14:30' or 2024-06-01T14:30:00Z
+        #[arg(long, value_name = "TIME", value_parser = revisions::download::parse_time)]
+        at: DateTime<Utc>,
+
+        /// File type to export Google documents to, i.e. pdf or docx [default: pdf for documents, csv for spreadsheets]
+        #[arg(long)]
+        format: Option<FileExtension>,
+
+        /// Path to save the file to [default: the file name in the current directory]
+        #[arg(long)]
+        destination: Option<PathBuf>,
+
+        /// Overwrite existing file
+        #[arg(long)]
+        overwrite: bool,
+    },
 }
 
 #[derive(Subcommand)]
@@ -328,6 +1202,7 @@ enum PermissionCommand {
     /// Grant permission to file
     Share {
         /// File id
+        #[arg(value_parser = drive_url::parse_id)]
         file_id: String,
 
         /// The role granted by this permission. Allowed values are: owner, organizer, fileOrganizer, writer, commenter, reader
@@ -354,6 +1229,7 @@ enum PermissionCommand {
     /// List permissions for a file
     List {
         /// File id
+        #[arg(value_parser = drive_url::parse_id)]
         file_id: String,
 
         /// Don't print header
@@ -368,6 +1244,7 @@ enum PermissionCommand {
     /// Revoke permissions for a file. If no other options are specified, the 'anyone' permission will be revoked
     Revoke {
         /// File id
+        #[arg(value_parser = drive_url::parse_id)]
         file_id: String,
 
         /// Revoke all permissions (except owner)
@@ -380,9 +1257,87 @@ enum PermissionCommand {
     },
 }
 
+#[derive(Subcommand)]
+enum SyncCommand {
+    /// Sync a local directory with a Drive folder. Without arguments all pairs in gdrive-sync.toml are synced
+    Run {
+        /// Local directory
+        #[arg(requires = "folder_id")]
+        local_path: Option<PathBuf>,
+
+        /// Drive folder id
+        #[arg(requires = "local_path", value_parser = drive_url::parse_id)]
+        folder_id: Option<String>,
+
+        /// Sync the pairs in this config file instead of ./gdrive-sync.toml
+        #[arg(long, value_name = "PATH", conflicts_with = "local_path")]
+        config: Option<PathBuf>,
+
+        /// Which side changes are copied from. Valid values are: two-way, upload, download
+        #[arg(long, default_value_t = sync::plan::Direction::default())]
+        direction: sync::plan::Direction,
+
+        /// How to resolve files changed on both sides. Valid values are: newest, prompt. The other version is kept as a conflicted copy
+        #[arg(long, default_value_t = sync::run::ConflictPolicy::default())]
+        on_conflict: sync::run::ConflictPolicy,
+
+        /// Only include files of at least this size, i.e. 100K, 1.5M or 2G
+        #[arg(long, value_name = "SIZE")]
+        min_size: Option<ByteSize>,
+
+        /// Only include files of at most this size, i.e. 100K, 1.5M or 2G
+        #[arg(long, value_name = "SIZE")]
+        max_size: Option<ByteSize>,
+
+        /// Only include files of this mime type, i.e. image/png or video/*. Can be repeated
+        #[arg(long, value_name = "MIME_TYPE")]
+        mime_include: Vec<String>,
+
+        /// Exclude files of this mime type, i.e. image/png or video/*. Can be repeated
+        #[arg(long, value_name = "MIME_TYPE")]
+        mime_exclude: Vec<String>,
+    },
+
+    /// Show pending changes for directories that have been synced
+    Status,
+}
+
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
+    let print_only_id = cli.print_only_id;
+
+    let settings_result = config::settings::init(config::settings::Settings {
+        account: cli.account,
+        http: config::settings::HttpSettings {
+            connect_timeout: cli.connect_timeout,
+            read_timeout: cli.read_timeout,
+            keep_alive: cli.keep_alive,
+            http1_only: cli.http1.then_some(true),
+            ip_family: if cli.ipv4 {
+                Some(app_config::IpFamily::Ipv4)
+            } else if cli.ipv6 {
+                Some(app_config::IpFamily::Ipv6)
+            } else {
+                None
+            },
+        },
+        ..config::settings::Settings::default()
+    });
+    // The doctor reports what is wrong with the settings itself
+    if !matches!(
+        cli.command,
+        Command::Config {
+            command: ConfigCommand::Doctor
+        }
+    ) {
+        settings_result.unwrap_or_else(handle_error);
+    }
+    let settings = config::settings::current();
+
+    if cli.stats {
+        api_stats::enable_summary();
+    }
 
     match cli.command {
         Command::About => {
@@ -448,6 +1403,94 @@ async fn main() {
                 })
                 .await
                 .unwrap_or_else(handle_error),
+
+                DriveCommand::Create { name } => drives::create(drives::create::Config {
+                    name,
+                    print_only_id,
+                })
+                .await
+                .unwrap_or_else(handle_error),
+
+                DriveCommand::Rename {
+                    drive_id,
+                    name,
+                    admin,
+                } => drives::rename(drives::rename::Config {
+                    drive_id,
+                    name,
+                    use_domain_admin_access: admin,
+                })
+                .await
+                .unwrap_or_else(handle_error),
+
+                DriveCommand::Delete { drive_id, admin } => {
+                    drives::delete(drives::delete::Config {
+                        drive_id,
+                        use_domain_admin_access: admin,
+                    })
+                    .await
+                    .unwrap_or_else(handle_error)
+                }
+
+                DriveCommand::Hide { drive_id } => drives::hide(drives::hide::Config {
+                    drive_id,
+                    hidden: true,
+                })
+                .await
+                .unwrap_or_else(handle_error),
+
+                DriveCommand::Unhide { drive_id } => drives::hide(drives::hide::Config {
+                    drive_id,
+                    hidden: false,
+                })
+                .await
+                .unwrap_or_else(handle_error),
+
+                DriveCommand::Members { command } => match command {
+                    DriveMemberCommand::List {
+                        drive_id,
+                        admin,
+                        skip_header,
+                        field_separator,
+                    } => drives::members::list(drives::members::ListConfig {
+                        drive_id,
+                        use_domain_admin_access: admin,
+                        skip_header,
+                        field_separator,
+                    })
+                    .await
+                    .unwrap_or_else(handle_error),
+
+                    DriveMemberCommand::Add {
+                        drive_id,
+                        email,
+                        role,
+                        type_,
+                        notify,
+                        admin,
+                    } => drives::members::add(drives::members::AddConfig {
+                        drive_id,
+                        email,
+                        role,
+                        type_,
+                        notify,
+                        use_domain_admin_access: admin,
+                    })
+                    .await
+                    .unwrap_or_else(handle_error),
+
+                    DriveMemberCommand::Remove {
+                        drive_id,
+                        email,
+                        admin,
+                    } => drives::members::remove(drives::members::RemoveConfig {
+                        drive_id,
+                        email,
+                        use_domain_admin_access: admin,
+                    })
+                    .await
+                    .unwrap_or_else(handle_error),
+                },
             }
         }
 
@@ -456,11 +1499,21 @@ async fn main() {
                 FileCommand::Info {
                     file_id,
                     size_in_bytes,
+                    json,
+                    field,
+                    no_cache,
                 } => {
-                    // fmt
+                    let output = match field {
+                        Some(name) => files::info::Output::Field(name),
+                        None if json => files::info::Output::Json,
+                        None => files::info::Output::Fields,
+                    };
+
                     files::info(files::info::Config {
                         file_id,
                         size_in_bytes,
+                        output,
+                        use_cache: !no_cache,
                     })
                     .await
                     .unwrap_or_else(handle_error)
@@ -470,26 +1523,61 @@ async fn main() {
                     max,
                     query,
                     order_by,
+                    sort,
                     parent,
                     drive,
+                    corpora,
+                    owner,
+                    orphaned,
                     skip_header,
                     full_name,
                     field_separator,
+                    min_size,
+                    max_size,
+                    mime_include,
+                    mime_exclude,
+                    no_cache,
                 } => {
-                    let parent_query =
-                        parent.map(|folder_id| ListQuery::FilesInFolder { folder_id });
+                    let scope = match &drive {
+                        Some(drive_id) => ListScope::drive(drive_id),
+                        None => ListScope {
+                            corpora,
+                            ..ListScope::default()
+                        },
+                    };
 
-                    let drive_query = drive.map(|drive_id| ListQuery::FilesOnDrive { drive_id });
+                    let q = if orphaned {
+                        ListQuery::Orphaned
+                    } else if let Some(owner) = owner {
+                        ListQuery::OwnedBy {
+                            owner,
+                            folder_id: parent.or(drive),
+                        }
+                    } else {
+                        let parent_query =
+                            parent.map(|folder_id| ListQuery::FilesInFolder { folder_id });
+
+                        let drive_query =
+                            drive.map(|drive_id| ListQuery::FilesOnDrive { drive_id });
 
-                    let q = parent_query.or(drive_query).unwrap_or(query);
+                        parent_query.or(drive_query).unwrap_or(query)
+                    };
 
                     files::list(files::list::Config {
                         query: q,
-                        order_by,
+                        order_by: sort.map(|sort| sort.order()).unwrap_or(order_by),
+                        scope,
                         max_files: max,
                         skip_header,
                         truncate_name: !full_name,
                         field_separator,
+                        filter: FileFilter {
+                            min_size,
+                            max_size,
+                            mime_include,
+                            mime_exclude,
+                        },
+                        use_cache: !no_cache,
                     })
                     .await
                     .unwrap_or_else(handle_error)
@@ -502,6 +1590,21 @@ async fn main() {
                     recursive,
                     destination,
                     stdout,
+                    name_replacement,
+                    min_size,
+                    max_size,
+                    mime_include,
+                    mime_exclude,
+                    also,
+                    paranoid,
+                    skip_space_check,
+                    temp_dir,
+                    flatten,
+                    strip_prefix,
+                    no_auth,
+                    from_list,
+                    retry_file,
+                    parallel,
                 } => {
                     let existing_file_action = if overwrite {
                         files::download::ExistingFileAction::Overwrite
@@ -517,12 +1620,66 @@ async fn main() {
                         files::download::Destination::CurrentDir
                     };
 
-                    files::download(files::download::Config {
-                        file_id,
+                    let download_config = files::download::Config {
+                        file_id: file_id.unwrap_or_default(),
                         existing_file_action,
                         follow_shortcuts,
                         download_directories: recursive,
                         destination: dst,
+                        filter: FileFilter {
+                            min_size,
+                            max_size,
+                            mime_include,
+                            mime_exclude,
+                        },
+                        name_replacement,
+                        mirrors: also,
+                        verify_on_disk: paranoid,
+                        check_free_space: !skip_space_check,
+                        temp_dir,
+                        layout: files::download::Layout {
+                            flatten,
+                            strip_prefix: strip_prefix.unwrap_or_default(),
+                        },
+                        no_auth,
+                    };
+
+                    match from_list {
+                        Some(list_path) => files::download_list(files::download_list::Config {
+                            list_path,
+                            retry_path: retry_file,
+                            parallel: parallel.unwrap_or_else(|| settings.concurrency()),
+                            download: download_config,
+                        })
+                        .await
+                        .unwrap_or_else(handle_error),
+
+                        None => files::download(download_config)
+                            .await
+                            .unwrap_or_else(handle_error),
+                    }
+                }
+
+                FileCommand::Pull {
+                    folder_id,
+                    local_path,
+                    delete,
+                    min_size,
+                    max_size,
+                    mime_include,
+                    mime_exclude,
+                } => {
+                    // fmt
+                    files::pull(files::pull::Config {
+                        folder_id,
+                        local_path,
+                        delete,
+                        filter: FileFilter {
+                            min_size,
+                            max_size,
+                            mime_include,
+                            mime_exclude,
+                        },
                     })
                     .await
                     .unwrap_or_else(handle_error)
@@ -530,27 +1687,63 @@ async fn main() {
 
                 FileCommand::Upload {
                     file_path,
+                    name,
                     mime,
                     parent,
                     recursive,
+                    hidden,
                     chunk_size,
                     print_chunk_errors,
                     print_chunk_info,
-                    print_only_id,
+                    ocr,
+                    ocr_lang,
+                    rollback_on_failure,
+                    indexable_text,
+                    keep_forever,
+                    from_list,
+                    null_separated,
+                    report,
+                    parallel,
                 } => {
-                    // fmt
-                    files::upload(files::upload::Config {
-                        file_path,
-                        mime_type: mime,
-                        parents: parent,
-                        chunk_size,
-                        print_chunk_errors,
-                        print_chunk_info,
-                        upload_directories: recursive,
-                        print_only_id,
-                    })
-                    .await
-                    .unwrap_or_else(handle_error)
+                    let parents = parent.or_else(|| settings.default_parents());
+                    let media = files::upload::MediaOptions {
+                        indexable_text,
+                        keep_forever,
+                    };
+
+                    match from_list {
+                        Some(list_path) => files::upload_list(files::upload_list::Config {
+                            list_path,
+                            null_separated,
+                            parents,
+                            mime_type: mime,
+                            chunk_size,
+                            media,
+                            report_path: report,
+                            parallel: parallel.unwrap_or_else(|| settings.concurrency()),
+                        })
+                        .await
+                        .unwrap_or_else(handle_error),
+
+                        None => files::upload(files::upload::Config {
+                            file_path,
+                            name,
+                            mime_type: mime,
+                            parents,
+                            chunk_size,
+                            print_chunk_errors,
+                            print_chunk_info,
+                            upload_directories: recursive,
+                            include_hidden: hidden,
+                            print_only_id,
+                            ocr,
+                            ocr_language: ocr_lang,
+                            rollback_on_failure,
+                            media,
+                        })
+                        .await
+                        .unwrap_or_else(handle_error),
+                    }
                 }
 
                 FileCommand::Update {
@@ -560,15 +1753,17 @@ async fn main() {
                     chunk_size,
                     print_chunk_errors,
                     print_chunk_info,
+                    skip_unchanged,
                 } => {
                     // fmt
                     files::update(files::update::Config {
                         file_id,
                         file_path,
                         mime_type: mime,
-                        chunk_size,
+                        chunk_size: chunk_size.unwrap_or_else(|| settings.chunk_size()),
                         print_chunk_errors,
                         print_chunk_info,
+                        skip_unchanged,
                     })
                     .await
                     .unwrap_or_else(handle_error)
@@ -578,22 +1773,46 @@ async fn main() {
                     // fmt
                     files::delete(files::delete::Config {
                         file_id,
-                        delete_directories: recursive,
+                        operation: files::delete::Operation::DeletePermanent,
+                        recursive: recursive.into(),
                     })
                     .await
                     .unwrap_or_else(handle_error)
                 }
 
-                FileCommand::Mkdir {
-                    name,
-                    parent,
-                    print_only_id,
-                } => {
+                FileCommand::Trash { file_id, recursive } => {
+                    // fmt
+                    files::delete(files::delete::Config {
+                        file_id,
+                        operation: files::delete::Operation::Trash,
+                        recursive: recursive.into(),
+                    })
+                    .await
+                    .unwrap_or_else(handle_error)
+                }
+
+                FileCommand::Untrash { file_id } => {
+                    // fmt
+                    files::delete(files::delete::Config {
+                        file_id,
+                        operation: files::delete::Operation::Untrash,
+                        recursive: files::delete::Recursive::Confirmed,
+                    })
+                    .await
+                    .unwrap_or_else(handle_error)
+                }
+
+                FileCommand::UndeleteLast => {
+                    // fmt
+                    files::undelete_last().await.unwrap_or_else(handle_error)
+                }
+
+                FileCommand::Mkdir { name, parent } => {
                     // fmt
                     files::mkdir(files::mkdir::Config {
                         id: None,
                         name,
-                        parents: parent,
+                        parents: parent.or_else(|| settings.default_parents()),
                         print_only_id,
                     })
                     .await
@@ -607,6 +1826,13 @@ async fn main() {
                         .unwrap_or_else(handle_error)
                 }
 
+                FileCommand::UnshareMe { file_id } => {
+                    // fmt
+                    files::unshare_me(files::unshare_me::Config { file_id })
+                        .await
+                        .unwrap_or_else(handle_error)
+                }
+
                 FileCommand::Move { file_id, folder_id } => {
                     // fmt
                     files::mv(files::mv::Config {
@@ -617,25 +1843,48 @@ async fn main() {
                     .unwrap_or_else(handle_error)
                 }
 
+                FileCommand::Adopt { file_id, parent } => {
+                    // fmt
+                    files::adopt(files::adopt::Config {
+                        file_id,
+                        folder_id: parent,
+                    })
+                    .await
+                    .unwrap_or_else(handle_error)
+                }
+
                 FileCommand::Copy { file_id, folder_id } => {
                     // fmt
                     files::copy(files::copy::Config {
                         file_id,
                         to_folder_id: folder_id,
+                        print_only_id,
                     })
                     .await
                     .unwrap_or_else(handle_error)
                 }
 
-                FileCommand::Import {
-                    file_path,
+                FileCommand::Shortcut {
+                    target_id,
                     parent,
-                    print_only_id,
+                    name,
                 } => {
+                    // fmt
+                    files::shortcut(files::shortcut::ShortcutConfig {
+                        target_id,
+                        parent,
+                        name,
+                        print_only_id,
+                    })
+                    .await
+                    .unwrap_or_else(handle_error)
+                }
+
+                FileCommand::Import { file_path, parent } => {
                     // fmt
                     files::import(files::import::Config {
                         file_path,
-                        parents: parent,
+                        parents: parent.or_else(|| settings.default_parents()),
                         print_only_id,
                     })
                     .await
@@ -661,6 +1910,109 @@ async fn main() {
                     .await
                     .unwrap_or_else(handle_error)
                 }
+
+                FileCommand::Convert { file_id, to } => {
+                    // fmt
+                    files::convert(files::convert::Config { file_id, to })
+                        .await
+                        .unwrap_or_else(handle_error)
+                }
+
+                FileCommand::Meta { command } => match command {
+                    MetaCommand::Set {
+                        file_id,
+                        description,
+                        folder_color,
+                        viewed,
+                    } => {
+                        // fmt
+                        files::meta::set(files::meta::Config {
+                            file_id,
+                            description,
+                            folder_color,
+                            viewed,
+                        })
+                        .await
+                        .unwrap_or_else(handle_error)
+                    }
+
+                    MetaCommand::UploadDefaults {
+                        folder_id,
+                        convert,
+                        chunk_size,
+                        mime,
+                        clear,
+                    } => {
+                        // fmt
+                        files::upload_defaults(files::upload_defaults::Config {
+                            folder_id,
+                            convert,
+                            chunk_size,
+                            mime_types: mime,
+                            clear,
+                        })
+                        .await
+                        .unwrap_or_else(handle_error)
+                    }
+                },
+
+                FileCommand::ExportAll {
+                    folder_id,
+                    format,
+                    out,
+                    overwrite,
+                    parallel,
+                } => {
+                    // fmt
+                    files::export_all(files::export_all::Config {
+                        folder_id,
+                        format,
+                        destination: out,
+                        overwrite,
+                        parallel: parallel.unwrap_or_else(|| settings.concurrency()),
+                    })
+                    .await
+                    .unwrap_or_else(handle_error)
+                }
+
+                FileCommand::Index { db, folder, full } => {
+                    // fmt
+                    files::index(files::index::Config {
+                        db_path: db,
+                        folder_id: folder,
+                        full,
+                    })
+                    .await
+                    .unwrap_or_else(handle_error)
+                }
+
+                FileCommand::Search {
+                    pattern,
+                    offline,
+                    db,
+                    max,
+                    skip_header,
+                    field_separator,
+                } => {
+                    // fmt
+                    files::search(files::search::Config {
+                        pattern,
+                        offline,
+                        db_path: db,
+                        max_files: max,
+                        skip_header,
+                        field_separator,
+                    })
+                    .await
+                    .unwrap_or_else(handle_error)
+                }
+
+                FileCommand::Url { file_id, direct } => {
+                    // fmt
+                    files::url(files::url::Config { file_id, direct })
+                        .await
+                        .unwrap_or_else(handle_error)
+                }
             }
         }
 
@@ -718,9 +2070,336 @@ async fn main() {
             }
         }
 
-        Command::Navigate => {
+        Command::Revisions { command } => {
+            match command {
+                RevisionCommand::List {
+                    file_id,
+                    skip_header,
+                    field_separator,
+                } => {
+                    // fmt
+                    revisions::list(revisions::list::Config {
+                        file_id,
+                        skip_header,
+                        field_separator,
+                    })
+                    .await
+                    .unwrap_or_else(handle_error)
+                }
+
+                RevisionCommand::Keep {
+                    file_id,
+                    revision_id,
+                    off,
+                } => {
+                    // fmt
+                    revisions::keep(revisions::keep::Config {
+                        file_id,
+                        revision_id,
+                        keep_forever: !off,
+                    })
+                    .await
+                    .unwrap_or_else(handle_error)
+                }
+
+                RevisionCommand::Download {
+                    file_id,
+                    at,
+                    format,
+                    destination,
+                    overwrite,
+                } => {
+                    // fmt
+                    revisions::download(revisions::download::Config {
+                        file_id,
+                        at,
+                        format,
+                        destination,
+                        overwrite,
+                    })
+                    .await
+                    .unwrap_or_else(handle_error)
+                }
+            }
+        }
+
+        Command::Navigate { script, start_dir } => {
+            // fmt
+            navigate::navigate(navigate::Config { script, start_dir })
+                .await
+                .unwrap_or_else(handle_error)
+        }
+
+        Command::Sync { command } => {
+            match command {
+                SyncCommand::Run {
+                    local_path,
+                    folder_id,
+                    config,
+                    direction,
+                    on_conflict,
+                    min_size,
+                    max_size,
+                    mime_include,
+                    mime_exclude,
+                } => {
+                    if let (Some(local_path), Some(folder_id)) = (local_path, folder_id) {
+                        let what = format!("Sync of '{}'", local_path.display());
+                        let result = sync::run(sync::run::Config {
+                            local_path,
+                            folder_id,
+                            on_conflict,
+                            direction,
+                            filter: FileFilter {
+                                min_size,
+                                max_size,
+                                mime_include,
+                                mime_exclude,
+                            },
+                        })
+                        .await;
+                        alerts::report(&what, result)
+                            .await
+                            .unwrap_or_else(handle_error)
+                    } else {
+                        let config_path =
+                            config.unwrap_or_else(|| PathBuf::from(sync::config::DEFAULT_PATH));
+                        sync::run_all(&config_path)
+                            .await
+                            .unwrap_or_else(handle_error)
+                    }
+                }
+
+                SyncCommand::Status => {
+                    // fmt
+                    sync::status().await.unwrap_or_else(handle_error)
+                }
+            }
+        }
+
+        Command::Backup { path, parent, full } => {
+            let what = format!("Backup of '{}'", path.display());
+            let result = backup::backup(backup::Config {
+                path,
+                parent_id: parent,
+                full,
+            })
+            .await;
+            alerts::report(&what, result)
+                .await
+                .unwrap_or_else(handle_error)
+        }
+
+        Command::Batch { command } => {
+            match command {
+                BatchCommand::Run {
+                    plan,
+                    continue_on_error,
+                } => {
+                    // fmt
+                    batch::run(batch::Config {
+                        plan_path: plan,
+                        continue_on_error,
+                    })
+                    .await
+                    .unwrap_or_else(handle_error)
+                }
+            }
+        }
+
+        Command::Migrate {
+            from_account,
+            to_account,
+            folder,
+            parent,
+        } => {
+            // fmt
+            migrate::migrate(migrate::Config {
+                from_account,
+                to_account,
+                folder_id: folder,
+                parent_id: parent,
+            })
+            .await
+            .unwrap_or_else(handle_error)
+        }
+
+        Command::Publish {
+            file_path,
+            folder,
+            public,
+            json,
+        } => {
+            // fmt
+            publish::publish(publish::Config {
+                file_path,
+                parent_id: folder,
+                public,
+                json,
+            })
+            .await
+            .unwrap_or_else(handle_error)
+        }
+
+        Command::Watch {
+            folder_id,
+            download_to,
+            interval,
+            once,
+        } => {
+            // fmt
+            watch::watch(watch::Config {
+                folder_id,
+                download_to,
+                interval: std::time::Duration::from_secs(interval.max(1)),
+                once,
+            })
+            .await
+            .unwrap_or_else(handle_error)
+        }
+
+        Command::Activity {
+            file_id,
+            max,
+            json,
+            skip_header,
+            field_separator,
+        } => {
+            // fmt
+            activity::activity(activity::Config {
+                file_id,
+                max_activities: max,
+                json,
+                skip_header,
+                field_separator,
+            })
+            .await
+            .unwrap_or_else(handle_error)
+        }
+
+        Command::Serve {
+            port,
+            address,
+            root,
+            metrics,
+            read_only,
+        } => {
+            // fmt
+            serve::serve(serve::Config {
+                address,
+                port,
+                root_id: root,
+                metrics,
+                read_only,
+            })
+            .await
+            .unwrap_or_else(handle_error)
+        }
+
+        Command::Notify { command } => {
+            // fmt
+            match command {
+                NotifyCommand::Watch {
+                    file_id,
+                    address,
+                    ttl,
+                } => {
+                    // fmt
+                    notify::watch(notify::watch::Config {
+                        file_id,
+                        address,
+                        ttl: ttl.map(std::time::Duration::from_secs),
+                        print_only_id,
+                    })
+                    .await
+                    .unwrap_or_else(handle_error)
+                }
+
+                NotifyCommand::List {
+                    skip_header,
+                    field_separator,
+                } => {
+                    // fmt
+                    notify::list(notify::list::Config {
+                        skip_header,
+                        field_separator,
+                    })
+                    .unwrap_or_else(handle_error)
+                }
+
+                NotifyCommand::Stop { channel_id } => {
+                    // fmt
+                    notify::stop(notify::stop::Config { channel_id })
+                        .await
+                        .unwrap_or_else(handle_error)
+                }
+
+                NotifyCommand::Listen {
+                    port,
+                    address,
+                    hook,
+                } => {
+                    // fmt
+                    notify::listen(notify::listen::Config {
+                        address,
+                        port,
+                        hooks: hook,
+                    })
+                    .await
+                    .unwrap_or_else(handle_error)
+                }
+            }
+        }
+
+        Command::Config { command } => {
             // fmt
-            navigate::navigate().await.unwrap_or_else(handle_error)
+            match command {
+                ConfigCommand::Get { key } => {
+                    // fmt
+                    config::get(config::get::Config { key }).unwrap_or_else(handle_error)
+                }
+
+                ConfigCommand::Set { key, value } => {
+                    // fmt
+                    config::set(config::set::Config { key, value }).unwrap_or_else(handle_error)
+                }
+
+                ConfigCommand::List {
+                    skip_header,
+                    field_separator,
+                } => {
+                    // fmt
+                    config::list(config::list::Config {
+                        skip_header,
+                        field_separator,
+                    })
+                }
+
+                ConfigCommand::Doctor => {
+                    // fmt
+                    config::doctor().await.unwrap_or_else(handle_error)
+                }
+            }
+        }
+
+        #[cfg(feature = "mount")]
+        Command::Mount {
+            mountpoint,
+            root,
+            cache_ttl,
+            read_chunk_size,
+            read_write,
+        } => {
+            // fmt
+            mount::mount(mount::Config {
+                mountpoint,
+                root_id: root,
+                cache_ttl: std::time::Duration::from_secs(cache_ttl),
+                read_chunk_size_mb: read_chunk_size.max(1),
+                read_write,
+            })
+            .await
+            .unwrap_or_else(handle_error)
         }
 
         Command::Version => {
@@ -728,9 +2407,15 @@ async fn main() {
             version::version()
         }
     }
+
+    api_stats::finish();
 }
 
 fn handle_error(err: impl Error) {
     eprintln!("Error: {}", err);
+    if let Some(exhausted) = common::quota::exhausted() {
+        eprintln!("{}", exhausted);
+    }
+    api_stats::finish();
     std::process::exit(1);
 }
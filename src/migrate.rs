@@ -0,0 +1,559 @@
+use crate::app_config;
+use crate::app_config::AppConfig;
+use crate::common::api_stats::StatsDelegate;
+use crate::common::delegate::UploadDelegate;
+use crate::common::delegate::UploadDelegateConfig;
+use crate::common::drive_file;
+use crate::common::file_info::FileInfo;
+use crate::common::hub_helper;
+use crate::config::settings;
+use crate::files;
+use crate::files::list;
+use crate::files::list::ListFilesConfig;
+use crate::files::list::ListQuery;
+use crate::files::mkdir;
+use crate::files::upload;
+use crate::hub::Hub;
+use crate::permissions::revoke;
+use futures::stream::StreamExt;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs;
+use std::io;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+const STATE_DIR_NAME: &str = "migrations";
+
+pub struct Config {
+    pub from_account: String,
+    pub to_account: String,
+    pub folder_id: String,
+    // Folder in the destination account, My Drive if not set
+    pub parent_id: Option<String>,
+}
+
+// What has been copied so far, so an interrupted migration continues where it
+// stopped. Kept in the destination account's directory
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct State {
+    // Id in the source account to the id of the copy in the destination account
+    copied: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+struct Item {
+    id: String,
+    name: String,
+    path: String,
+    mime_type: mime::Mime,
+    size: u64,
+    // None for the migrated folder itself
+    parent_id: Option<String>,
+    is_folder: bool,
+    is_binary: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Method {
+    Created,
+    Copied,
+    Streamed,
+}
+
+impl Display for Method {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Method::Created => write!(f, "Created"),
+            Method::Copied => write!(f, "Copied"),
+            Method::Streamed => write!(f, "Streamed"),
+        }
+    }
+}
+
+// Copies a folder tree from one account to another. The source folder is shared
+// with the destination account for the duration of the migration, so files are
+// copied by Drive and owned by the destination account. Files that can't be
+// copied that way are downloaded and uploaded again
+pub async fn migrate(config: Config) -> Result<(), Error> {
+    err_if_same_account(&config)?;
+    err_if_unknown_account(&config.from_account)?;
+    err_if_unknown_account(&config.to_account)?;
+
+    let from_hub = hub_helper::get_hub_for_account(&config.from_account)
+        .await
+        .map_err(Error::Hub)?;
+    let to_hub = hub_helper::get_hub_for_account(&config.to_account)
+        .await
+        .map_err(Error::Hub)?;
+
+    let root = files::info::get_file(&from_hub, &config.folder_id)
+        .await
+        .map_err(Error::GetFile)?;
+
+    if !drive_file::is_directory(&root) {
+        return Err(Error::NotADirectory);
+    }
+
+    let state_path = state_path(&config)?;
+    let mut state = load_state(&state_path)?;
+
+    let items = collect_items(&from_hub, &root).await?;
+    let file_count = items.iter().filter(|item| !item.is_folder).count();
+    let done_count = items
+        .iter()
+        .filter(|item| state.copied.contains_key(&item.id))
+        .count();
+
+    println!(
+        "Found {} files in {} folders, {} already migrated",
+        file_count,
+        items.len() - file_count,
+        done_count
+    );
+
+    let to_email = account_email(&to_hub).await?;
+    let permission_id = match share_with(&from_hub, &config.folder_id, &to_email).await {
+        Ok(permission_id) => Some(permission_id),
+        Err(err) => {
+            eprintln!(
+                "Warning: Failed to share the folder with {}, all files will be streamed: {}",
+                to_email, err
+            );
+            None
+        }
+    };
+
+    let delegate_config = UploadDelegateConfig {
+        backoff_config: settings::current().backoff(),
+        ..UploadDelegateConfig::default()
+    };
+
+    let total = items.len();
+    let mut failed = 0;
+
+    for (index, item) in items.iter().enumerate() {
+        if state.copied.contains_key(&item.id) {
+            continue;
+        }
+
+        let parent_id = match &item.parent_id {
+            Some(id) => state.copied.get(id).cloned(),
+            None => config.parent_id.clone(),
+        };
+
+        // The parent failed, its content is tried again on the next run
+        if item.parent_id.is_some() && parent_id.is_none() {
+            failed += 1;
+            continue;
+        }
+
+        let prefix = format!("[{}/{}]", index + 1, total);
+        let shared = permission_id.is_some();
+
+        match migrate_item(
+            &from_hub,
+            &to_hub,
+            item,
+            parent_id,
+            shared,
+            &delegate_config,
+        )
+        .await
+        {
+            Ok((new_id, method)) => {
+                println!("{} {} '{}'", prefix, method, item.path);
+                state.copied.insert(item.id.clone(), new_id);
+                save_state(&state_path, &state)?;
+            }
+
+            Err(err) => {
+                failed += 1;
+                println!("{} Failed '{}': {}", prefix, item.path, err);
+            }
+        }
+    }
+
+    if let Some(permission_id) = permission_id {
+        let result = revoke::delete_permission(
+            &from_hub,
+            delegate_config.clone(),
+            &config.folder_id,
+            &permission_id,
+        )
+        .await;
+
+        if let Err(err) = result {
+            eprintln!(
+                "Warning: Failed to remove the share with {}, remove it with `gdrive permissions revoke`: {}",
+                to_email, err
+            );
+        }
+    }
+
+    if failed > 0 {
+        return Err(Error::ItemsFailed(failed));
+    }
+
+    println!("Migrated {} files and folders", total);
+
+    Ok(())
+}
+
+async fn migrate_item(
+    from_hub: &Hub,
+    to_hub: &Hub,
+    item: &Item,
+    parent_id: Option<String>,
+    shared: bool,
+    delegate_config: &UploadDelegateConfig,
+) -> Result<(String, Method), ItemError> {
+    let parents = parent_id.map(|id| vec![id]);
+
+    if item.is_folder {
+        let folder = mkdir::create_directory(
+            to_hub,
+            &mkdir::Config {
+                id: None,
+                name: item.name.clone(),
+                parents,
+                print_only_id: false,
+            },
+            delegate_config.clone(),
+        )
+        .await
+        .map_err(ItemError::Mkdir)?;
+
+        return Ok((folder.id.unwrap_or_default(), Method::Created));
+    }
+
+    // Google documents have no content of their own to stream
+    if shared {
+        match copy_file(to_hub, item, parents.clone(), delegate_config).await {
+            Ok(file) => return Ok((file.id.unwrap_or_default(), Method::Copied)),
+            Err(err) if !item.is_binary => return Err(err),
+            Err(_) => {}
+        }
+    } else if !item.is_binary {
+        return Err(ItemError::NotShared);
+    }
+
+    let file = stream_file(from_hub, to_hub, item, parents, delegate_config).await?;
+    Ok((file.id.unwrap_or_default(), Method::Streamed))
+}
+
+// The copy is made by the destination account, so it owns it
+async fn copy_file(
+    to_hub: &Hub,
+    item: &Item,
+    parents: Option<Vec<String>>,
+    delegate_config: &UploadDelegateConfig,
+) -> Result<google_drive3::api::File, ItemError> {
+    let mut delegate = UploadDelegate::new(delegate_config.clone());
+
+    let file = google_drive3::api::File {
+        name: Some(item.name.clone()),
+        parents,
+        ..google_drive3::api::File::default()
+    };
+
+    let (_, file) = to_hub
+        .files()
+        .copy(file, &item.id)
+        .param("fields", "id")
+        .add_scope(google_drive3::api::Scope::Full)
+        .delegate(&mut delegate)
+        .supports_all_drives(true)
+        .doit()
+        .await
+        .map_err(ItemError::Copy)?;
+
+    Ok(file)
+}
+
+// The uploader needs to seek, so the content is spooled to a temporary file
+async fn stream_file(
+    from_hub: &Hub,
+    to_hub: &Hub,
+    item: &Item,
+    parents: Option<Vec<String>>,
+    delegate_config: &UploadDelegateConfig,
+) -> Result<google_drive3::api::File, ItemError> {
+    let mut body = files::download::download_file(from_hub, &item.id)
+        .await
+        .map_err(ItemError::Download)?;
+
+    let mut tmp_file = tempfile::tempfile().map_err(ItemError::TempFile)?;
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk.map_err(ItemError::ReadChunk)?;
+        tmp_file.write_all(&chunk).map_err(ItemError::TempFile)?;
+    }
+    tmp_file
+        .seek(SeekFrom::Start(0))
+        .map_err(ItemError::TempFile)?;
+
+    let file_info = FileInfo {
+        name: item.name.clone(),
+        mime_type: item.mime_type.clone(),
+        parents,
+        size: item.size,
+    };
+
+    upload::upload_file(to_hub, tmp_file, None, file_info, delegate_config.clone())
+        .await
+        .map_err(ItemError::Upload)
+}
+
+// Folders come before their content
+async fn collect_items(hub: &Hub, root: &google_drive3::api::File) -> Result<Vec<Item>, Error> {
+    let root_item = to_item(root, None, "");
+    let mut items = vec![root_item.clone()];
+    let mut queue = VecDeque::from([root_item]);
+
+    while let Some(folder) = queue.pop_front() {
+        let children = list::list_files(
+            hub,
+            &ListFilesConfig {
+                query: ListQuery::FilesInFolder {
+                    folder_id: folder.id.clone(),
+                },
+                order_by: Default::default(),
+                scope: Default::default(),
+                max_files: usize::MAX,
+            },
+        )
+        .await
+        .map_err(Error::ListFiles)?;
+
+        for child in children {
+            // Shortcuts would point into the source account
+            if drive_file::is_shortcut(&child) {
+                continue;
+            }
+
+            let item = to_item(&child, Some(&folder.id), &folder.path);
+            if item.is_folder {
+                queue.push_back(item.clone());
+            }
+            items.push(item);
+        }
+    }
+
+    Ok(items)
+}
+
+fn to_item(file: &google_drive3::api::File, parent_id: Option<&String>, parent_path: &str) -> Item {
+    let name = file.name.clone().unwrap_or_default();
+    let path = if parent_path.is_empty() {
+        name.clone()
+    } else {
+        format!("{}/{}", parent_path, name)
+    };
+
+    Item {
+        id: file.id.clone().unwrap_or_default(),
+        name,
+        path,
+        mime_type: file
+            .mime_type
+            .as_deref()
+            .and_then(|mime| mime.parse().ok())
+            .unwrap_or(mime::APPLICATION_OCTET_STREAM),
+        size: file.size.unwrap_or_default().max(0) as u64,
+        parent_id: parent_id.cloned(),
+        is_folder: drive_file::is_directory(file),
+        is_binary: drive_file::is_binary(file),
+    }
+}
+
+async fn account_email(hub: &Hub) -> Result<String, Error> {
+    let (_, about) = hub
+        .about()
+        .get()
+        .param("fields", "user")
+        .delegate(&mut StatsDelegate)
+        .doit()
+        .await
+        .map_err(Error::About)?;
+
+    about
+        .user
+        .and_then(|user| user.email_address)
+        .ok_or(Error::MissingEmail)
+}
+
+// No notification, the destination account is the same person
+async fn share_with(
+    hub: &Hub,
+    folder_id: &str,
+    email: &str,
+) -> Result<String, google_drive3::Error> {
+    let permission = google_drive3::api::Permission {
+        role: Some(String::from("reader")),
+        type_: Some(String::from("user")),
+        email_address: Some(email.to_string()),
+        ..google_drive3::api::Permission::default()
+    };
+
+    let (_, permission) = hub
+        .permissions()
+        .create(permission, folder_id)
+        .param("fields", "id")
+        .send_notification_email(false)
+        .add_scope(google_drive3::api::Scope::Full)
+        .supports_all_drives(true)
+        .delegate(&mut StatsDelegate)
+        .doit()
+        .await?;
+
+    Ok(permission.id.unwrap_or_default())
+}
+
+fn state_path(config: &Config) -> Result<PathBuf, Error> {
+    let key = md5::compute(format!(
+        "{}\n{}\n{}\n{}",
+        config.from_account,
+        config.to_account,
+        config.folder_id,
+        config.parent_id.clone().unwrap_or_default()
+    ));
+
+    let app_cfg = AppConfig::load_account(&config.to_account).map_err(Error::AppConfig)?;
+    Ok(app_cfg
+        .account_base_path()
+        .join(STATE_DIR_NAME)
+        .join(format!("{:x}.json", key)))
+}
+
+fn load_state(path: &Path) -> Result<State, Error> {
+    if !path.exists() {
+        return Ok(State::default());
+    }
+
+    let content =
+        fs::read_to_string(path).map_err(|err| Error::ReadState(path.to_path_buf(), err))?;
+    serde_json::from_str(&content).map_err(Error::ParseState)
+}
+
+fn save_state(path: &Path, state: &State) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| Error::WriteState(dir.to_path_buf(), err))?;
+    }
+
+    let content = serde_json::to_string(state).map_err(Error::SerializeState)?;
+    fs::write(path, content).map_err(|err| Error::WriteState(path.to_path_buf(), err))
+}
+
+fn err_if_same_account(config: &Config) -> Result<(), Error> {
+    if config.from_account == config.to_account {
+        return Err(Error::SameAccount);
+    }
+
+    Ok(())
+}
+
+fn err_if_unknown_account(name: &str) -> Result<(), Error> {
+    let accounts = app_config::list_accounts().map_err(Error::AppConfig)?;
+    if !accounts.iter().any(|account| account == name) {
+        return Err(Error::UnknownAccount(name.to_string()));
+    }
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum ItemError {
+    Mkdir(google_drive3::Error),
+    NotShared,
+    Copy(google_drive3::Error),
+    Download(google_drive3::Error),
+    ReadChunk(google_drive3::hyper::Error),
+    TempFile(io::Error),
+    Upload(google_drive3::Error),
+}
+
+impl error::Error for ItemError {}
+
+impl Display for ItemError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            ItemError::Mkdir(err) => write!(f, "Failed to create folder: {}", err),
+            ItemError::NotShared => write!(
+                f,
+                "Google documents can only be copied when the folder is shared"
+            ),
+            ItemError::Copy(err) => write!(f, "Failed to copy file: {}", err),
+            ItemError::Download(err) => write!(f, "Failed to download file: {}", err),
+            ItemError::ReadChunk(err) => write!(f, "Failed to read from stream: {}", err),
+            ItemError::TempFile(err) => write!(f, "Failed to write temporary file: {}", err),
+            ItemError::Upload(err) => write!(f, "Failed to upload file: {}", err),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    AppConfig(app_config::Error),
+    SameAccount,
+    UnknownAccount(String),
+    GetFile(google_drive3::Error),
+    NotADirectory,
+    ListFiles(list::Error),
+    About(google_drive3::Error),
+    MissingEmail,
+    ReadState(PathBuf, io::Error),
+    ParseState(serde_json::Error),
+    SerializeState(serde_json::Error),
+    WriteState(PathBuf, io::Error),
+    ItemsFailed(usize),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::AppConfig(err) => write!(f, "{}", err),
+            Error::SameAccount => write!(f, "The accounts to migrate from and to are the same"),
+            Error::UnknownAccount(name) => {
+                write!(f, "Account '{}' not found, see `gdrive account list`", name)
+            }
+            Error::GetFile(err) => write!(f, "Failed to get folder: {}", err),
+            Error::NotADirectory => write!(f, "Only folders can be migrated"),
+            Error::ListFiles(err) => write!(f, "{}", err),
+            Error::About(err) => write!(f, "Failed to get destination account: {}", err),
+            Error::MissingEmail => write!(f, "The destination account has no email address"),
+            Error::ReadState(path, err) => write!(
+                f,
+                "Failed to read migration state '{}': {}",
+                path.display(),
+                err
+            ),
+            Error::ParseState(err) => write!(f, "Failed to parse migration state: {}", err),
+            Error::SerializeState(err) => {
+                write!(f, "Failed to serialize migration state: {}", err)
+            }
+            Error::WriteState(path, err) => write!(
+                f,
+                "Failed to write migration state '{}': {}",
+                path.display(),
+                err
+            ),
+            Error::ItemsFailed(count) => write!(
+                f,
+                "{} files and folders were not migrated, run the same command again to retry them",
+                count
+            ),
+        }
+    }
+}
@@ -0,0 +1,93 @@
+pub mod fs;
+pub mod inodes;
+
+use crate::common::hub_helper;
+use fuser::MountOption;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::runtime::Handle;
+
+pub struct Config {
+    pub mountpoint: PathBuf,
+    pub root_id: Option<String>,
+    pub cache_ttl: Duration,
+    pub read_chunk_size_mb: u64,
+    // Read-only unless asked for, a mistake in a shell can't change Drive then
+    pub read_write: bool,
+}
+
+pub async fn mount(config: Config) -> Result<(), Error> {
+    err_if_not_directory(&config.mountpoint)?;
+
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+    let drive_fs = fs::DriveFs::new(
+        hub,
+        Handle::current(),
+        fs::Config {
+            root_id: config.root_id.unwrap_or_else(|| "root".to_string()),
+            cache_ttl: config.cache_ttl,
+            read_chunk_size: config.read_chunk_size_mb * 1024 * 1024,
+            read_only: !config.read_write,
+        },
+    );
+
+    let mut options = vec![
+        MountOption::FSName("gdrive".to_string()),
+        MountOption::Subtype("gdrive".to_string()),
+    ];
+    if !config.read_write {
+        options.push(MountOption::RO);
+    }
+
+    // The filesystem runs on its own thread and is unmounted when the session is dropped
+    let session = fuser::spawn_mount2(drive_fs, &config.mountpoint, &options)
+        .map_err(|err| Error::Mount(config.mountpoint.clone(), err))?;
+
+    println!(
+        "Mounted Drive at '{}', press Ctrl-C to unmount",
+        config.mountpoint.display()
+    );
+
+    tokio::signal::ctrl_c().await.map_err(Error::Signal)?;
+    println!("Unmounting '{}'", config.mountpoint.display());
+    drop(session);
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    NotADirectory(PathBuf),
+    Mount(PathBuf, io::Error),
+    Signal(io::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::NotADirectory(path) => {
+                write!(f, "Mountpoint '{}' is not a directory", path.display())
+            }
+            Error::Mount(path, err) => {
+                write!(f, "Failed to mount at '{}': {}", path.display(), err)
+            }
+            Error::Signal(err) => write!(f, "Failed to wait for Ctrl-C: {}", err),
+        }
+    }
+}
+
+fn err_if_not_directory(path: &PathBuf) -> Result<(), Error> {
+    if !path.is_dir() {
+        Err(Error::NotADirectory(path.clone()))
+    } else {
+        Ok(())
+    }
+}
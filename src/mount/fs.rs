@@ -0,0 +1,963 @@
+use crate::app_config::AppConfig;
+use crate::common::delegate::UploadDelegateConfig;
+use crate::common::file_info::FileInfo;
+use crate::files;
+use crate::files::delete::Operation;
+use crate::files::list::{self, ListFilesConfig, ListQuery, ListScope, ListSortOrder};
+use crate::files::mkdir;
+use crate::files::mv;
+use crate::files::update::PatchFile;
+use crate::files::upload;
+use crate::hub::Hub;
+use crate::mount::inodes::{InodeTable, Node, NodeKind};
+use fuser::{
+    FileAttr, FileType, Filesystem, ReplyAttr, ReplyCreate, ReplyData, ReplyDirectory, ReplyEmpty,
+    ReplyEntry, ReplyOpen, ReplyWrite, Request, TimeOrNow,
+};
+use futures::StreamExt;
+use google_drive3::chrono::Local;
+use hyper::body::Bytes;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::io::{Seek, SeekFrom, Write};
+use std::os::unix::fs::FileExt;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use tokio::runtime::Handle;
+
+const ATTR_TTL: Duration = Duration::from_secs(1);
+const BLOCK_SIZE: u32 = 512;
+const SPOOL_DIR_NAME: &str = "mount-spool";
+
+pub struct Config {
+    pub root_id: String,
+    pub cache_ttl: Duration,
+    pub read_chunk_size: u64,
+    pub read_only: bool,
+}
+
+struct ReadBuffer {
+    offset: u64,
+    data: Bytes,
+}
+
+impl ReadBuffer {
+    fn slice(&self, offset: u64, size: u64) -> Option<Bytes> {
+        let end = self.offset + self.data.len() as u64;
+        if offset < self.offset || offset + size > end {
+            return None;
+        }
+        let start = (offset - self.offset) as usize;
+        Some(self.data.slice(start..start + size as usize))
+    }
+}
+
+// Local copy of a file opened for writing, uploaded when the file is flushed
+struct WriteBuffer {
+    file: std::fs::File,
+    dirty: bool,
+    open_handles: u32,
+}
+
+pub struct DriveFs {
+    hub: Hub,
+    handle: Handle,
+    inodes: InodeTable,
+    read_buffers: HashMap<u64, ReadBuffer>,
+    write_buffers: HashMap<u64, WriteBuffer>,
+    read_chunk_size: u64,
+    read_only: bool,
+    uid: u32,
+    gid: u32,
+}
+
+impl DriveFs {
+    pub fn new(hub: Hub, handle: Handle, config: Config) -> Self {
+        Self {
+            hub,
+            handle,
+            inodes: InodeTable::new(&config.root_id, config.cache_ttl),
+            read_buffers: HashMap::new(),
+            write_buffers: HashMap::new(),
+            read_chunk_size: config.read_chunk_size,
+            read_only: config.read_only,
+            uid: unsafe { libc::getuid() },
+            gid: unsafe { libc::getgid() },
+        }
+    }
+
+    fn children(&mut self, ino: u64) -> Result<Vec<u64>, list::Error> {
+        if let Some(children) = self.inodes.cached_children(ino) {
+            return Ok(children);
+        }
+
+        let folder_id = match self.inodes.get(ino) {
+            Some(node) => node.id.clone(),
+            None => return Ok(vec![]),
+        };
+
+        let files = self.handle.block_on(list::list_files(
+            &self.hub,
+            &ListFilesConfig {
+                query: ListQuery::FilesInFolder { folder_id },
+                order_by: ListSortOrder::default(),
+                scope: ListScope::default(),
+                max_files: usize::MAX,
+            },
+        ))?;
+
+        Ok(self.inodes.set_children(ino, files))
+    }
+
+    fn find_child(&mut self, parent: u64, name: &OsStr) -> Result<Option<u64>, i32> {
+        let children = self.children(parent).map_err(|err| {
+            eprintln!("Failed to list folder: {}", err);
+            libc::EIO
+        })?;
+
+        Ok(children.into_iter().find(|ino| {
+            self.inodes
+                .get(*ino)
+                .map(|node| OsStr::new(&node.name) == name)
+                .unwrap_or(false)
+        }))
+    }
+
+    fn attr(&self, ino: u64, node: &Node) -> FileAttr {
+        let (kind, perm, nlink) = match (node.kind, self.read_only) {
+            (NodeKind::Directory, true) => (FileType::Directory, 0o555, 2),
+            (NodeKind::Directory, false) => (FileType::Directory, 0o755, 2),
+            (NodeKind::File, true) => (FileType::RegularFile, 0o444, 1),
+            (NodeKind::File, false) => (FileType::RegularFile, 0o644, 1),
+        };
+
+        FileAttr {
+            ino,
+            size: node.size,
+            blocks: node.size.div_ceil(BLOCK_SIZE as u64),
+            atime: node.modified,
+            mtime: node.modified,
+            ctime: node.modified,
+            crtime: node.modified,
+            kind,
+            perm,
+            nlink,
+            uid: self.uid,
+            gid: self.gid,
+            rdev: 0,
+            blksize: BLOCK_SIZE,
+            flags: 0,
+        }
+    }
+
+    fn attr_of(&self, ino: u64) -> Option<FileAttr> {
+        self.inodes.get(ino).map(|node| self.attr(ino, node))
+    }
+
+    // Serves reads from the last fetched chunk, fetching a new chunk starting at
+    // `offset` when the range isn't buffered. Sequential readers hit the buffer
+    // for most calls since the kernel reads in much smaller pieces
+    fn read_range(&mut self, ino: u64, offset: u64, size: u64) -> Result<Bytes, String> {
+        if let Some(buffer) = self.write_buffers.get(&ino) {
+            let mut data = vec![0; size as usize];
+            let count = buffer
+                .file
+                .read_at(&mut data, offset)
+                .map_err(|err| err.to_string())?;
+            data.truncate(count);
+            return Ok(Bytes::from(data));
+        }
+
+        let node = self.inodes.get(ino).cloned().ok_or("Unknown inode")?;
+        if offset >= node.size {
+            return Ok(Bytes::new());
+        }
+        let size = size.min(node.size - offset);
+
+        if let Some(data) = self
+            .read_buffers
+            .get(&ino)
+            .and_then(|buffer| buffer.slice(offset, size))
+        {
+            return Ok(data);
+        }
+
+        let length = size.max(self.read_chunk_size).min(node.size - offset);
+        let data = self
+            .handle
+            .block_on(self.hub.get_media_range(&node.id, offset, length))
+            .map_err(|err| err.to_string())?;
+
+        let buffer = ReadBuffer { offset, data };
+        let result = buffer
+            .slice(offset, size.min(buffer.data.len() as u64))
+            .unwrap_or_default();
+        self.read_buffers.insert(ino, buffer);
+        Ok(result)
+    }
+
+    // Opens the local copy used for writes, downloading the current content
+    // unless the file is truncated or hasn't been uploaded yet
+    fn open_write_buffer(&mut self, ino: u64, truncate: bool) -> Result<(), String> {
+        if let Some(buffer) = self.write_buffers.get_mut(&ino) {
+            buffer.open_handles += 1;
+            if truncate {
+                buffer.file.set_len(0).map_err(|err| err.to_string())?;
+                buffer.dirty = true;
+            }
+            return Ok(());
+        }
+
+        let node = self.inodes.get(ino).cloned().ok_or("Unknown inode")?;
+        let mut file = tempfile::tempfile().map_err(|err| err.to_string())?;
+
+        if !truncate && !node.id.is_empty() && node.size > 0 {
+            let hub = &self.hub;
+            self.handle.block_on(async {
+                let mut body = files::download::download_file(hub, &node.id)
+                    .await
+                    .map_err(|err| err.to_string())?;
+                while let Some(chunk) = body.next().await {
+                    let chunk = chunk.map_err(|err| err.to_string())?;
+                    file.write_all(&chunk).map_err(|err| err.to_string())?;
+                }
+                Ok::<(), String>(())
+            })?;
+        }
+
+        self.read_buffers.remove(&ino);
+        self.write_buffers.insert(
+            ino,
+            WriteBuffer {
+                file,
+                dirty: truncate || node.id.is_empty(),
+                open_handles: 1,
+            },
+        );
+        Ok(())
+    }
+
+    // Uploads the local copy if it changed, creating the Drive file for new files
+    fn upload_if_dirty(&mut self, ino: u64) -> Result<(), String> {
+        let buffer = match self.write_buffers.get_mut(&ino) {
+            Some(buffer) if buffer.dirty => buffer,
+            _ => return Ok(()),
+        };
+        let node = self.inodes.get(ino).cloned().ok_or("Unknown inode")?;
+        let parent_id = self
+            .inodes
+            .get(node.parent)
+            .map(|parent| parent.id.clone())
+            .ok_or("Unknown parent")?;
+
+        let mut reader = buffer.file.try_clone().map_err(|err| err.to_string())?;
+        let size = reader
+            .seek(SeekFrom::End(0))
+            .map_err(|err| err.to_string())?;
+        reader
+            .seek(SeekFrom::Start(0))
+            .map_err(|err| err.to_string())?;
+
+        let file_info = FileInfo {
+            name: node.name.clone(),
+            mime_type: mime_guess::from_path(&node.name).first_or_octet_stream(),
+            parents: Some(vec![parent_id]),
+            size,
+        };
+        let delegate_config = UploadDelegateConfig::default();
+
+        let uploaded = if node.id.is_empty() {
+            self.handle.block_on(upload::upload_file(
+                &self.hub,
+                reader,
+                None,
+                file_info,
+                delegate_config,
+            ))
+        } else {
+            self.handle.block_on(files::update::update_file(
+                &self.hub,
+                reader,
+                &node.id,
+                file_info,
+                delegate_config,
+            ))
+        }
+        .map_err(|err| err.to_string())?;
+
+        buffer.dirty = false;
+        if node.id.is_empty() {
+            if let Some(id) = uploaded.id {
+                self.inodes.set_id(ino, id);
+            }
+        }
+        if let Some(node) = self.inodes.get_mut(ino) {
+            node.size = size;
+            node.modified = SystemTime::now();
+        }
+        Ok(())
+    }
+
+    // Keeps the content of a file that couldn't be uploaded in the spool folder so
+    // it isn't lost. A file that never reached Drive is removed from the mount,
+    // for others the folder is listed again to show what Drive has
+    fn spool(&mut self, ino: u64) {
+        self.read_buffers.remove(&ino);
+        let mut buffer = match self.write_buffers.remove(&ino) {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        let node = match self.inodes.get(ino).cloned() {
+            Some(node) => node,
+            None => return,
+        };
+
+        match save_to_spool(&mut buffer.file, &node.name) {
+            Ok(path) => eprintln!(
+                "Warning: '{}' was not uploaded, its content was saved to {}",
+                node.name,
+                path.display()
+            ),
+            Err(err) => eprintln!(
+                "Warning: '{}' was not uploaded and could not be saved locally: {}",
+                node.name, err
+            ),
+        }
+
+        if node.id.is_empty() {
+            self.inodes.remove_child(node.parent, ino);
+        } else {
+            self.inodes.invalidate(node.parent);
+        }
+    }
+
+    fn trash(&mut self, parent: u64, name: &OsStr, kind: NodeKind) -> Result<(), i32> {
+        let ino = self.find_child(parent, name)?.ok_or(libc::ENOENT)?;
+        let node = self.inodes.get(ino).cloned().ok_or(libc::ENOENT)?;
+
+        match (kind, node.kind) {
+            (NodeKind::File, NodeKind::Directory) => return Err(libc::EISDIR),
+            (NodeKind::Directory, NodeKind::File) => return Err(libc::ENOTDIR),
+            (NodeKind::Directory, NodeKind::Directory) => {
+                let children = self.children(ino).map_err(|_| libc::EIO)?;
+                if !children.is_empty() {
+                    return Err(libc::ENOTEMPTY);
+                }
+            }
+            (NodeKind::File, NodeKind::File) => {}
+        }
+
+        // Deleting through the mount moves the file to trash so it can be recovered
+        if !node.id.is_empty() {
+            let parents: Vec<String> = self
+                .inodes
+                .get(parent)
+                .map(|n| n.id.clone())
+                .into_iter()
+                .collect();
+            let journal_error = self
+                .handle
+                .block_on(files::delete::apply_and_record(
+                    &self.hub,
+                    &node.id,
+                    &node.name,
+                    &parents,
+                    Operation::Trash,
+                ))
+                .map_err(|err| {
+                    eprintln!("Failed to trash '{}': {}", node.name, err);
+                    libc::EIO
+                })?;
+            if let Some(err) = journal_error {
+                eprintln!("Warning: {}", err);
+            }
+        }
+
+        self.write_buffers.remove(&ino);
+        self.read_buffers.remove(&ino);
+        self.inodes.remove_child(parent, ino);
+        Ok(())
+    }
+
+    fn rename_node(
+        &mut self,
+        parent: u64,
+        name: &OsStr,
+        new_parent: u64,
+        new_name: &OsStr,
+    ) -> Result<(), i32> {
+        let ino = self.find_child(parent, name)?.ok_or(libc::ENOENT)?;
+        let node = self.inodes.get(ino).cloned().ok_or(libc::ENOENT)?;
+        let new_name = new_name.to_str().ok_or(libc::EINVAL)?.to_string();
+
+        if let Some(existing) = self.find_child(new_parent, OsStr::new(&new_name))? {
+            if existing != ino {
+                let target = self.inodes.get(existing).cloned().ok_or(libc::ENOENT)?;
+                if node.kind == NodeKind::File
+                    && target.kind == NodeKind::File
+                    && !target.id.is_empty()
+                {
+                    return self.replace_content(parent, ino, existing);
+                }
+                self.trash(new_parent, OsStr::new(&new_name), target.kind)?;
+            }
+        }
+
+        if !node.id.is_empty() {
+            if node.name != new_name {
+                let patch_file = PatchFile::new(node.id.clone()).with_name(&new_name);
+                self.handle
+                    .block_on(files::update::update_metadata(
+                        &self.hub,
+                        UploadDelegateConfig::default(),
+                        patch_file,
+                    ))
+                    .map_err(|err| {
+                        eprintln!("Failed to rename '{}': {}", node.name, err);
+                        libc::EIO
+                    })?;
+            }
+
+            if parent != new_parent {
+                let old_parent_id = self.inodes.get(parent).map(|n| n.id.clone());
+                let new_parent_id = self.inodes.get(new_parent).map(|n| n.id.clone());
+                let config = mv::ChangeParentConfig {
+                    file_id: node.id.clone(),
+                    old_parent_id: old_parent_id.ok_or(libc::ENOENT)?,
+                    new_parent_id: new_parent_id.ok_or(libc::ENOENT)?,
+                };
+                self.handle
+                    .block_on(mv::change_parent(
+                        &self.hub,
+                        UploadDelegateConfig::default(),
+                        &config,
+                    ))
+                    .map_err(|err| {
+                        eprintln!("Failed to move '{}': {}", node.name, err);
+                        libc::EIO
+                    })?;
+            }
+        }
+
+        self.inodes.move_child(ino, new_parent, &new_name);
+        Ok(())
+    }
+
+    // Editors save by writing a temporary file and renaming it over the original.
+    // The content is uploaded as a new revision of the original so its id, sharing
+    // and history are kept, and the temporary file is deleted
+    fn replace_content(&mut self, parent: u64, ino: u64, target: u64) -> Result<(), i32> {
+        let node = self.inodes.get(ino).cloned().ok_or(libc::ENOENT)?;
+
+        if !self.write_buffers.contains_key(&ino) {
+            self.open_write_buffer(ino, false).map_err(|err| {
+                eprintln!("Failed to read '{}': {}", node.name, err);
+                libc::EIO
+            })?;
+            if let Some(buffer) = self.write_buffers.get_mut(&ino) {
+                buffer.open_handles = 0;
+            }
+        }
+        let mut buffer = self.write_buffers.remove(&ino).ok_or(libc::EIO)?;
+        buffer.dirty = true;
+        // Handles still open on the original keep writing to the new content
+        buffer.open_handles = self
+            .write_buffers
+            .remove(&target)
+            .map(|old| old.open_handles)
+            .unwrap_or(0);
+        let open_handles = buffer.open_handles;
+        self.write_buffers.insert(target, buffer);
+        self.read_buffers.remove(&ino);
+        self.read_buffers.remove(&target);
+        self.inodes.remove_child(parent, ino);
+
+        if let Err(err) = self.upload_if_dirty(target) {
+            eprintln!("Failed to upload file: {}", err);
+            if open_handles == 0 {
+                self.spool(target);
+            }
+            return Err(libc::EIO);
+        }
+        if open_handles == 0 {
+            self.write_buffers.remove(&target);
+        }
+
+        if !node.id.is_empty() {
+            let deleted = self.handle.block_on(files::delete::apply(
+                &self.hub,
+                &node.id,
+                Operation::DeletePermanent,
+            ));
+            if let Err(err) = deleted {
+                eprintln!("Warning: Failed to delete '{}': {}", node.name, err);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn save_to_spool(file: &mut std::fs::File, name: &str) -> Result<PathBuf, String> {
+    let dir = AppConfig::default_base_path()
+        .map_err(|err| err.to_string())?
+        .join(SPOOL_DIR_NAME);
+    std::fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+
+    let file_name = format!(
+        "{}-{}",
+        Local::now().format("%Y%m%d-%H%M%S"),
+        name.replace('/', "_")
+    );
+    let path = dir.join(file_name);
+    let mut spool_file = std::fs::File::create(&path).map_err(|err| err.to_string())?;
+    file.seek(SeekFrom::Start(0))
+        .map_err(|err| err.to_string())?;
+    std::io::copy(file, &mut spool_file).map_err(|err| err.to_string())?;
+    Ok(path)
+}
+
+impl Filesystem for DriveFs {
+    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        match self.find_child(parent, name) {
+            Ok(Some(ino)) => match self.attr_of(ino) {
+                Some(attr) => reply.entry(&ATTR_TTL, &attr, 0),
+                None => reply.error(libc::ENOENT),
+            },
+            Ok(None) => reply.error(libc::ENOENT),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn getattr(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyAttr) {
+        match self.attr_of(ino) {
+            Some(attr) => reply.attr(&ATTR_TTL, &attr),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    // Only truncation is supported, everything else is accepted but ignored
+    fn setattr(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _mode: Option<u32>,
+        _uid: Option<u32>,
+        _gid: Option<u32>,
+        size: Option<u64>,
+        _atime: Option<TimeOrNow>,
+        _mtime: Option<TimeOrNow>,
+        _ctime: Option<SystemTime>,
+        _fh: Option<u64>,
+        _crtime: Option<SystemTime>,
+        _chgtime: Option<SystemTime>,
+        _bkuptime: Option<SystemTime>,
+        _flags: Option<u32>,
+        reply: ReplyAttr,
+    ) {
+        if let Some(size) = size {
+            if self.read_only {
+                reply.error(libc::EROFS);
+                return;
+            }
+
+            let opened_here = !self.write_buffers.contains_key(&ino);
+            let result = self.open_write_buffer(ino, size == 0).and_then(|_| {
+                match self.write_buffers.get_mut(&ino) {
+                    Some(buffer) => {
+                        buffer.dirty = true;
+                        buffer.file.set_len(size).map_err(|err| err.to_string())
+                    }
+                    None => Ok(()),
+                }
+            });
+            if let Err(err) = result {
+                eprintln!("Failed to truncate file: {}", err);
+                reply.error(libc::EIO);
+                return;
+            }
+            if let Some(node) = self.inodes.get_mut(ino) {
+                node.size = size;
+            }
+
+            // truncate(2) on a closed file has no flush to upload on, so do it now
+            if opened_here {
+                if let Err(err) = self.upload_if_dirty(ino) {
+                    eprintln!("Failed to upload file: {}", err);
+                    self.spool(ino);
+                    reply.error(libc::EIO);
+                    return;
+                }
+                self.write_buffers.remove(&ino);
+            } else if let Some(buffer) = self.write_buffers.get_mut(&ino) {
+                buffer.open_handles -= 1;
+            }
+        }
+
+        match self.attr_of(ino) {
+            Some(attr) => reply.attr(&ATTR_TTL, &attr),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        let parent = match self.inodes.get(ino) {
+            Some(node) if node.kind == NodeKind::Directory => node.parent,
+            Some(_) => {
+                reply.error(libc::ENOTDIR);
+                return;
+            }
+            None => {
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+
+        let children = match self.children(ino) {
+            Ok(children) => children,
+            Err(err) => {
+                eprintln!("Failed to list folder: {}", err);
+                reply.error(libc::EIO);
+                return;
+            }
+        };
+
+        let mut entries = vec![
+            (ino, FileType::Directory, ".".to_string()),
+            (parent, FileType::Directory, "..".to_string()),
+        ];
+        for child in children {
+            if let Some(node) = self.inodes.get(child) {
+                let kind = match node.kind {
+                    NodeKind::Directory => FileType::Directory,
+                    NodeKind::File => FileType::RegularFile,
+                };
+                entries.push((child, kind, node.name.clone()));
+            }
+        }
+
+        for (index, (entry_ino, kind, name)) in
+            entries.into_iter().enumerate().skip(offset as usize)
+        {
+            // The offset passed back to us is the index of the next entry
+            if reply.add(entry_ino, (index + 1) as i64, kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+
+    fn mkdir(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        _mode: u32,
+        _umask: u32,
+        reply: ReplyEntry,
+    ) {
+        if self.read_only {
+            reply.error(libc::EROFS);
+            return;
+        }
+        let name = match name.to_str() {
+            Some(name) => name.to_string(),
+            None => {
+                reply.error(libc::EINVAL);
+                return;
+            }
+        };
+        let parent_id = match self.inodes.get(parent) {
+            Some(node) => node.id.clone(),
+            None => {
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+
+        let config = mkdir::Config {
+            id: None,
+            name: name.clone(),
+            parents: Some(vec![parent_id]),
+            print_only_id: false,
+        };
+        let created = self.handle.block_on(mkdir::create_directory(
+            &self.hub,
+            &config,
+            UploadDelegateConfig::default(),
+        ));
+
+        match created {
+            Ok(folder) => {
+                let ino = self.inodes.add_child(
+                    parent,
+                    Node {
+                        id: folder.id.unwrap_or_default(),
+                        name,
+                        parent,
+                        kind: NodeKind::Directory,
+                        size: 0,
+                        modified: SystemTime::now(),
+                    },
+                );
+                match self.attr_of(ino) {
+                    Some(attr) => reply.entry(&ATTR_TTL, &attr, 0),
+                    None => reply.error(libc::EIO),
+                }
+            }
+            Err(err) => {
+                eprintln!("Failed to create folder '{}': {}", name, err);
+                reply.error(libc::EIO);
+            }
+        }
+    }
+
+    fn unlink(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        if self.read_only {
+            reply.error(libc::EROFS);
+            return;
+        }
+        match self.trash(parent, name, NodeKind::File) {
+            Ok(()) => reply.ok(),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn rmdir(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        if self.read_only {
+            reply.error(libc::EROFS);
+            return;
+        }
+        match self.trash(parent, name, NodeKind::Directory) {
+            Ok(()) => reply.ok(),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn rename(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        newparent: u64,
+        newname: &OsStr,
+        _flags: u32,
+        reply: ReplyEmpty,
+    ) {
+        if self.read_only {
+            reply.error(libc::EROFS);
+            return;
+        }
+        match self.rename_node(parent, name, newparent, newname) {
+            Ok(()) => reply.ok(),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn create(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        _mode: u32,
+        _umask: u32,
+        flags: i32,
+        reply: ReplyCreate,
+    ) {
+        if self.read_only {
+            reply.error(libc::EROFS);
+            return;
+        }
+        let name = match name.to_str() {
+            Some(name) => name.to_string(),
+            None => {
+                reply.error(libc::EINVAL);
+                return;
+            }
+        };
+
+        // The Drive file is created by the first upload, until then it only exists locally
+        let ino = self.inodes.add_child(
+            parent,
+            Node {
+                id: String::new(),
+                name,
+                parent,
+                kind: NodeKind::File,
+                size: 0,
+                modified: SystemTime::now(),
+            },
+        );
+
+        if let Err(err) = self.open_write_buffer(ino, true) {
+            eprintln!("Failed to create file: {}", err);
+            self.inodes.remove_child(parent, ino);
+            reply.error(libc::EIO);
+            return;
+        }
+
+        match self.attr_of(ino) {
+            Some(attr) => reply.created(&ATTR_TTL, &attr, 0, 0, flags as u32),
+            None => reply.error(libc::EIO),
+        }
+    }
+
+    fn open(&mut self, _req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
+        let kind = match self.inodes.get(ino) {
+            Some(node) => node.kind,
+            None => {
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+        if kind == NodeKind::Directory {
+            reply.error(libc::EISDIR);
+            return;
+        }
+
+        if flags & libc::O_ACCMODE != libc::O_RDONLY {
+            if self.read_only {
+                reply.error(libc::EROFS);
+                return;
+            }
+            if let Err(err) = self.open_write_buffer(ino, flags & libc::O_TRUNC != 0) {
+                eprintln!("Failed to open file for writing: {}", err);
+                reply.error(libc::EIO);
+                return;
+            }
+        }
+
+        reply.opened(0, 0)
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        match self.read_range(ino, offset.max(0) as u64, size as u64) {
+            Ok(data) => reply.data(&data),
+            Err(err) => {
+                eprintln!("Failed to read file: {}", err);
+                reply.error(libc::EIO);
+            }
+        }
+    }
+
+    fn write(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        data: &[u8],
+        _write_flags: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyWrite,
+    ) {
+        let buffer = match self.write_buffers.get_mut(&ino) {
+            Some(buffer) => buffer,
+            None => {
+                reply.error(libc::EBADF);
+                return;
+            }
+        };
+
+        let offset = offset.max(0) as u64;
+        if let Err(err) = buffer.file.write_all_at(data, offset) {
+            eprintln!("Failed to write file: {}", err);
+            reply.error(libc::EIO);
+            return;
+        }
+        buffer.dirty = true;
+
+        if let Some(node) = self.inodes.get_mut(ino) {
+            node.size = node.size.max(offset + data.len() as u64);
+            node.modified = SystemTime::now();
+        }
+        reply.written(data.len() as u32);
+    }
+
+    // Called on every close(), uploading here lets the error reach the caller
+    fn flush(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        _lock_owner: u64,
+        reply: ReplyEmpty,
+    ) {
+        match self.upload_if_dirty(ino) {
+            Ok(()) => reply.ok(),
+            Err(err) => {
+                eprintln!("Failed to upload file: {}", err);
+                reply.error(libc::EIO);
+            }
+        }
+    }
+
+    fn fsync(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        _datasync: bool,
+        reply: ReplyEmpty,
+    ) {
+        match self.upload_if_dirty(ino) {
+            Ok(()) => reply.ok(),
+            Err(err) => {
+                eprintln!("Failed to upload file: {}", err);
+                reply.error(libc::EIO);
+            }
+        }
+    }
+
+    fn release(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        _flush: bool,
+        reply: ReplyEmpty,
+    ) {
+        self.read_buffers.remove(&ino);
+
+        let last_handle = match self.write_buffers.get_mut(&ino) {
+            Some(buffer) => {
+                buffer.open_handles = buffer.open_handles.saturating_sub(1);
+                buffer.open_handles == 0
+            }
+            None => false,
+        };
+        // flush already returned the error to the caller, a second failure here
+        // keeps the content locally instead of dropping it
+        if last_handle {
+            match self.upload_if_dirty(ino) {
+                Ok(()) => {
+                    self.write_buffers.remove(&ino);
+                }
+                Err(err) => {
+                    eprintln!("Failed to upload file: {}", err);
+                    self.spool(ino);
+                }
+            }
+        }
+        reply.ok();
+    }
+}
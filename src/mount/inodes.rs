@@ -0,0 +1,192 @@
+use crate::common::drive_file;
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime};
+
+pub const ROOT_INO: u64 = 1;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum NodeKind {
+    Directory,
+    File,
+}
+
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub id: String,
+    pub name: String,
+    pub parent: u64,
+    pub kind: NodeKind,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+struct Listing {
+    fetched_at: Instant,
+    children: Vec<u64>,
+}
+
+// Maps Drive files to inode numbers and caches folder listings for `ttl`
+pub struct InodeTable {
+    nodes: HashMap<u64, Node>,
+    inodes_by_id: HashMap<String, u64>,
+    listings: HashMap<u64, Listing>,
+    next_ino: u64,
+    ttl: Duration,
+}
+
+impl InodeTable {
+    pub fn new(root_id: &str, ttl: Duration) -> Self {
+        let root = Node {
+            id: root_id.to_string(),
+            name: String::new(),
+            parent: ROOT_INO,
+            kind: NodeKind::Directory,
+            size: 0,
+            modified: SystemTime::now(),
+        };
+
+        let mut table = Self {
+            nodes: HashMap::new(),
+            inodes_by_id: HashMap::new(),
+            listings: HashMap::new(),
+            next_ino: ROOT_INO + 1,
+            ttl,
+        };
+        table.inodes_by_id.insert(root.id.clone(), ROOT_INO);
+        table.nodes.insert(ROOT_INO, root);
+        table
+    }
+
+    pub fn get(&self, ino: u64) -> Option<&Node> {
+        self.nodes.get(&ino)
+    }
+
+    // Children of a folder if they were listed within the ttl
+    pub fn cached_children(&self, ino: u64) -> Option<Vec<u64>> {
+        self.listings
+            .get(&ino)
+            .filter(|listing| listing.fetched_at.elapsed() < self.ttl)
+            .map(|listing| listing.children.clone())
+    }
+
+    pub fn set_children(&mut self, parent: u64, files: Vec<google_drive3::api::File>) -> Vec<u64> {
+        let mut children: Vec<u64> = files
+            .iter()
+            .filter_map(|file| self.upsert(parent, file))
+            .collect();
+
+        // Files created through the mount that haven't been uploaded yet
+        let pending = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.parent == parent && node.id.is_empty())
+            .map(|(ino, _)| *ino);
+        children.extend(pending);
+
+        self.listings.insert(
+            parent,
+            Listing {
+                fetched_at: Instant::now(),
+                children: children.clone(),
+            },
+        );
+        children
+    }
+
+    pub fn get_mut(&mut self, ino: u64) -> Option<&mut Node> {
+        self.nodes.get_mut(&ino)
+    }
+
+    // Adds a node created through the mount, before Drive has been asked again.
+    // The id may be empty for files that haven't been uploaded yet
+    pub fn add_child(&mut self, parent: u64, node: Node) -> u64 {
+        let ino = self.next_ino;
+        self.next_ino += 1;
+        if !node.id.is_empty() {
+            self.inodes_by_id.insert(node.id.clone(), ino);
+        }
+        self.nodes.insert(ino, node);
+        if let Some(listing) = self.listings.get_mut(&parent) {
+            listing.children.push(ino);
+        }
+        ino
+    }
+
+    pub fn remove_child(&mut self, parent: u64, ino: u64) {
+        if let Some(listing) = self.listings.get_mut(&parent) {
+            listing.children.retain(|child| *child != ino);
+        }
+        if let Some(node) = self.nodes.remove(&ino) {
+            self.inodes_by_id.remove(&node.id);
+        }
+    }
+
+    pub fn move_child(&mut self, ino: u64, new_parent: u64, new_name: &str) {
+        let old_parent = match self.nodes.get_mut(&ino) {
+            Some(node) => {
+                let old_parent = node.parent;
+                node.parent = new_parent;
+                node.name = new_name.to_string();
+                old_parent
+            }
+            None => return,
+        };
+        if old_parent != new_parent {
+            if let Some(listing) = self.listings.get_mut(&old_parent) {
+                listing.children.retain(|child| *child != ino);
+            }
+            if let Some(listing) = self.listings.get_mut(&new_parent) {
+                listing.children.push(ino);
+            }
+        }
+    }
+
+    // Makes the next lookup in the folder ask Drive again
+    pub fn invalidate(&mut self, parent: u64) {
+        self.listings.remove(&parent);
+    }
+
+    pub fn set_id(&mut self, ino: u64, id: String) {
+        if let Some(node) = self.nodes.get_mut(&ino) {
+            self.inodes_by_id.remove(&node.id);
+            node.id = id.clone();
+            self.inodes_by_id.insert(id, ino);
+        }
+    }
+
+    // Google docs have no binary content to read, so they are left out of the mount
+    fn upsert(&mut self, parent: u64, file: &google_drive3::api::File) -> Option<u64> {
+        let kind = if drive_file::is_directory(file) {
+            NodeKind::Directory
+        } else if drive_file::is_binary(file) {
+            NodeKind::File
+        } else {
+            return None;
+        };
+
+        let id = file.id.clone()?;
+        let node = Node {
+            id: id.clone(),
+            name: file.name.clone().unwrap_or_default(),
+            parent,
+            kind,
+            size: file.size.unwrap_or(0).max(0) as u64,
+            modified: file
+                .modified_time
+                .map(SystemTime::from)
+                .unwrap_or(SystemTime::UNIX_EPOCH),
+        };
+
+        let ino = match self.inodes_by_id.get(&id) {
+            Some(ino) => *ino,
+            None => {
+                let ino = self.next_ino;
+                self.next_ino += 1;
+                self.inodes_by_id.insert(id, ino);
+                ino
+            }
+        };
+        self.nodes.insert(ino, node);
+        Some(ino)
+    }
+}
@@ -1,93 +1,299 @@
-use crate::common::delegate::{BackoffConfig, ChunkSize, UploadDelegateConfig};
+use crate::app_config;
+use crate::app_config::AppConfig;
+use crate::common::delegate::Chunk;
+use crate::common::delegate::UploadDelegateConfig;
+use crate::common::delegate::UploadObserver;
+use crate::common::deletion_journal;
 use crate::common::drive_file;
+use crate::common::drive_url;
 use crate::common::file_info;
+use crate::common::file_name;
 use crate::common::file_tree;
+use crate::common::hash_writer::HashWriter;
 use crate::common::hub_helper;
 use crate::common::id_gen::IdGen;
-use crate::common::md5_writer::Md5Writer;
+use crate::common::local_path;
+use crate::common::number_format;
+use crate::common::path_resolver;
+use crate::common::permission;
+use crate::config::settings;
+use crate::drives;
 use crate::files;
+use crate::files::delete::{Descendants, Operation};
 use crate::files::info::DisplayConfig;
-use crate::files::list::{ListFilesConfig, ListQuery, ListSortOrder};
+use crate::files::list::{ListFilesConfig, ListQuery, ListScope, ListSortOrder};
 use crate::files::mkdir;
+use crate::files::mv;
+use crate::files::update::PatchFile;
 use crate::files::upload;
 use crate::hub::Hub;
-use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crate::permissions;
+use crate::sync;
+use base64::Engine;
+use crossterm::event::{
+    self, DisableBracketedPaste, EnableBracketedPaste, Event, KeyCode, KeyEvent, KeyModifiers,
+};
 use crossterm::execute;
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
 use futures::StreamExt;
-use human_bytes::human_bytes;
+use google_drive3::chrono::DateTime;
+use google_drive3::chrono::Local;
+use google_drive3::chrono::Utc;
 use ratatui::backend::CrosstermBackend;
 use ratatui::layout::{Constraint, Direction, Layout};
 use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span};
 use ratatui::widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph};
 use ratatui::Terminal;
+use std::collections::{HashMap, VecDeque};
 use std::error;
 use std::fmt::{Display, Formatter};
 use std::io;
 use std::io::Write;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::runtime::Handle;
 
-const HELP_LABELS: [(&str, &str); 7] = [
-    ("Enter/→  : open", "open"),
-    ("←/b  : back", "back"),
-    ("d  : download", "download"),
-    ("u  : upload menu", "upload"),
-    ("x  : delete", "delete"),
-    ("r  : refresh", "refresh"),
-    ("q  : quit", "quit"),
+mod control;
+pub mod keymap;
+mod line_editor;
+mod local_dirs;
+mod palette;
+mod script;
+mod undo;
+
+use keymap::{Action, Keymap};
+use line_editor::LineEditor;
+use local_dirs::LocalDirs;
+use palette::Palette;
+use undo::{UndoEntry, UndoHistory};
+
+const FOOTER_ACTIONS: [Action; 9] = [
+    Action::Open,
+    Action::Back,
+    Action::Download,
+    Action::Upload,
+    Action::Delete,
+    Action::Refresh,
+    Action::Palette,
+    Action::Help,
+    Action::Quit,
 ];
 
-pub async fn navigate() -> Result<(), Error> {
+const MIN_WIDTH: u16 = 60;
+const SEARCH_LIMIT: usize = 100;
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(3);
+const MIN_HEIGHT: u16 = 12;
+
+// The error of a transfer that was cancelled, it is reported as cancelled and
+// not as failed
+const CANCELLED: &str = "Cancelled";
+
+// How often a paused transfer checks whether it can go on
+const PAUSE_INTERVAL: Duration = Duration::from_millis(200);
+
+pub struct Config {
+    // Replays the keys in the file without a terminal, see script.rs
+    pub script: Option<PathBuf>,
+    // Where the upload picker opens, instead of the directory of the last session
+    pub start_dir: Option<PathBuf>,
+}
+
+pub async fn navigate(config: Config) -> Result<(), Error> {
+    if let Some(start_dir) = &config.start_dir {
+        if !start_dir.is_dir() {
+            return Err(Error::StartDir(start_dir.clone()));
+        }
+    }
+
+    let steps = match &config.script {
+        Some(path) => {
+            let content = std::fs::read_to_string(path)
+                .map_err(|err| Error::ReadScript(path.clone(), err))?;
+            Some(script::parse(&content).map_err(Error::ParseScript)?)
+        }
+        None => None,
+    };
+
     let handle = Handle::current();
-    let result = tokio::task::spawn_blocking(move || run_app(handle)).await;
+    let start_dir = config.start_dir;
+    let result = tokio::task::spawn_blocking(move || match steps {
+        Some(steps) => run_script(handle, &steps, start_dir),
+        None => run_app(handle, start_dir),
+    })
+    .await;
     match result {
         Ok(inner) => inner,
         Err(err) => Err(Error::Join(err)),
     }
 }
 
-fn run_app(handle: Handle) -> Result<(), Error> {
+fn run_app(handle: Handle, start_dir: Option<PathBuf>) -> Result<(), Error> {
+    // Opened before the alternate screen, so a warning can be read
+    let (calls_sender, calls) = mpsc::channel();
+    let control_socket = open_control_socket(&handle, calls_sender);
+
     enable_raw_mode().map_err(Error::Io)?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen).map_err(Error::Io)?;
+    // Not supported by legacy Windows consoles, pasting is then delivered as key events
+    let _ = execute!(stdout, EnableBracketedPaste);
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend).map_err(Error::Io)?;
 
-    let result = run_loop(&mut terminal, handle);
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let interrupt_listener = handle.spawn(listen_for_interrupt(interrupted.clone()));
+
+    let result =
+        with_panic_hook(|| run_loop(&mut terminal, handle, &interrupted, &calls, start_dir));
+
+    interrupt_listener.abort();
+    drop(control_socket);
 
     disable_raw_mode().map_err(Error::Io)?;
+    let _ = execute!(terminal.backend_mut(), DisableBracketedPaste);
     execute!(terminal.backend_mut(), LeaveAlternateScreen).map_err(Error::Io)?;
     terminal.show_cursor().map_err(Error::Io)?;
 
     result
 }
 
-fn run_loop(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, handle: Handle) -> Result<(), Error> {
+// Restores the terminal before the previous hook prints the panic message,
+// otherwise the message is lost in the alternate screen and the shell is left in
+// raw mode. The previous hook is put back when `run` returns
+fn with_panic_hook<T>(run: impl FnOnce() -> T) -> T {
+    let ui_thread = std::thread::current().id();
+    let previous_hook = Arc::new(std::panic::take_hook());
+    let hook = previous_hook.clone();
+    std::panic::set_hook(Box::new(move |info| {
+        if std::thread::current().id() == ui_thread {
+            restore_terminal();
+        }
+        hook(info);
+    }));
+
+    let result = run();
+
+    std::panic::set_hook(Box::new(move |info| previous_hook(info)));
+    result
+}
+
+fn restore_terminal() {
+    let _ = disable_raw_mode();
+    let _ = execute!(io::stdout(), DisableBracketedPaste);
+    let _ = execute!(io::stdout(), LeaveAlternateScreen, crossterm::cursor::Show);
+}
+
+// Raw mode turns Ctrl-C into a key event, so this only fires for signals sent
+// from outside the terminal (i.e. `kill -INT`)
+async fn listen_for_interrupt(interrupted: Arc<AtomicBool>) {
+    while tokio::signal::ctrl_c().await.is_ok() {
+        interrupted.store(true, Ordering::SeqCst);
+    }
+}
+
+#[cfg(unix)]
+fn open_control_socket(
+    handle: &Handle,
+    calls: mpsc::Sender<control::Call>,
+) -> Option<control::Socket> {
+    let path = match AppConfig::load_current_account() {
+        Ok(app_cfg) => app_cfg.account_base_path().join(control::SOCKET_NAME),
+        // I.e. a service account from the environment, there is no account directory
+        Err(_) => return None,
+    };
+
+    match control::open(handle, &path, calls) {
+        Ok(socket) => Some(socket),
+        Err(err) => {
+            eprintln!(
+                "Warning: Failed to open control socket '{}': {}",
+                path.display(),
+                err
+            );
+            None
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn open_control_socket(_handle: &Handle, _calls: mpsc::Sender<control::Call>) -> Option<()> {
+    None
+}
+
+// Scripts don't read or change the directories remembered by the sessions
+fn run_script(
+    handle: Handle,
+    steps: &[script::Step],
+    start_dir: Option<PathBuf>,
+) -> Result<(), Error> {
+    let hub = handle
+        .block_on(hub_helper::shared_hub())
+        .map_err(Error::Hub)?;
+    let mut app = App::new(hub);
+    app.local_dirs.upload_dir = start_dir;
+    app.reload(&handle)?;
+    script::run(&mut app, steps, &handle)
+}
+
+fn run_loop(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    handle: Handle,
+    interrupted: &AtomicBool,
+    calls: &mpsc::Receiver<control::Call>,
+    start_dir: Option<PathBuf>,
+) -> Result<(), Error> {
     let hub = handle
-        .block_on(hub_helper::get_hub())
+        .block_on(hub_helper::shared_hub())
         .map_err(Error::Hub)?;
     let mut app = App::new(hub);
+    app.local_dirs_path = local_dirs::default_path();
+    if let Some(path) = &app.local_dirs_path {
+        app.local_dirs = local_dirs::load(path);
+    }
+    if start_dir.is_some() {
+        app.local_dirs.upload_dir = start_dir;
+    }
     app.reload(&handle)?;
 
     loop {
         app.tick();
-        terminal.draw(|frame| draw_ui(frame, &app)).map_err(Error::Io)?;
+        while let Ok(call) = calls.try_recv() {
+            control::answer(&mut app, &handle, call);
+        }
+        terminal
+            .draw(|frame| {
+                draw_ui(frame, &app);
+                if !settings::current().colors() {
+                    remove_colors(frame.buffer_mut());
+                }
+            })
+            .map_err(Error::Io)?;
 
         if app.should_exit() {
             break;
         }
 
+        if interrupted.swap(false, Ordering::SeqCst) && app.interrupt() {
+            break;
+        }
+
         if !event::poll(Duration::from_millis(250)).map_err(Error::Io)? {
             continue;
         }
 
-        if let Event::Key(key) = event::read().map_err(Error::Io)? {
-            if handle_key_event(&mut app, key, &handle)? {
-                break;
+        match event::read().map_err(Error::Io)? {
+            Event::Resize(_, _) => {
+                // Redraw with the new size right away instead of waiting for the next event
+                terminal.autoresize().map_err(Error::Io)?;
+            }
+            event => {
+                if handle_event(&mut app, event, &handle)? {
+                    break;
+                }
             }
         }
     }
@@ -95,51 +301,322 @@ fn run_loop(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, handle: Handl
     Ok(())
 }
 
+// Applies an input event to the app, without touching the terminal. Returns
+// true when the app should exit
+fn handle_event(app: &mut App, event: Event, handle: &Handle) -> Result<bool, Error> {
+    match event {
+        Event::Key(key) => handle_key_event(app, key, handle),
+        Event::Paste(text) => {
+            handle_paste(app, &text);
+            Ok(false)
+        }
+        _ => Ok(false),
+    }
+}
+
 fn handle_key_event(app: &mut App, key: KeyEvent, handle: &Handle) -> Result<bool, Error> {
+    if is_ctrl_c(&key) {
+        return Ok(app.interrupt());
+    }
+
     match app.input_mode {
         InputMode::Normal => handle_normal_key(app, key, handle),
-        InputMode::DownloadDestination => handle_input_key(app, key, handle),
+        InputMode::DownloadDestination
+        | InputMode::Rename
+        | InputMode::NewFolder
+        | InputMode::ShareWith
+        | InputMode::GoTo
+        | InputMode::Search => handle_input_key(app, key, handle),
         InputMode::UploadPicker => handle_upload_picker_key(app, key, handle),
         InputMode::DeleteConfirm => handle_delete_confirm_key(app, key, handle),
         InputMode::QuitConfirm => handle_quit_confirm_key(app, key),
+        InputMode::CreateDestinationConfirm => handle_create_destination_key(app, key, handle),
+        InputMode::Help => handle_help_key(app, key),
+        InputMode::Palette => handle_palette_key(app, key, handle),
+        InputMode::Select => handle_select_key(app, key, handle),
+        InputMode::Transfers => handle_transfers_key(app, key, handle),
+    }
+}
+
+// Pasted text is inserted into the current prompt as a whole. Prompts are a
+// single line, so line breaks are dropped
+fn handle_paste(app: &mut App, text: &str) {
+    let text: String = text.chars().filter(|ch| *ch != '\n' && *ch != '\r').collect();
+
+    match app.input_mode {
+        InputMode::DownloadDestination
+        | InputMode::Rename
+        | InputMode::NewFolder
+        | InputMode::ShareWith
+        | InputMode::GoTo
+        | InputMode::Search => app.input.insert_str(&text),
+        InputMode::Palette => {
+            if let Some(palette) = app.palette.as_mut() {
+                text.chars().for_each(|ch| palette.push(ch));
+            }
+        }
+        InputMode::Normal
+        | InputMode::UploadPicker
+        | InputMode::DeleteConfirm
+        | InputMode::QuitConfirm
+        | InputMode::CreateDestinationConfirm
+        | InputMode::Help
+        | InputMode::Select
+        | InputMode::Transfers => {}
     }
 }
 
+fn is_ctrl_c(key: &KeyEvent) -> bool {
+    key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL)
+}
+
 fn handle_normal_key(app: &mut App, key: KeyEvent, handle: &Handle) -> Result<bool, Error> {
-    match key.code {
-        KeyCode::Char('q') => {
+    match app.keymap.action_for(&key) {
+        Some(action) => run_action(app, action, handle),
+        None => Ok(false),
+    }
+}
+
+fn run_action(app: &mut App, action: Action, handle: &Handle) -> Result<bool, Error> {
+    if action != Action::RemoveAccess {
+        app.pending_remove_access = None;
+    }
+
+    match action {
+        Action::Quit => {
             if app.can_quit() {
                 return Ok(true);
             }
             app.start_quit_confirm();
         }
-        KeyCode::Char('r') => {
+        Action::Refresh => {
             app.reload(handle)?;
         }
-        KeyCode::Char('b') | KeyCode::Left => {
+        Action::Back => {
             app.go_back(handle)?;
         }
-        KeyCode::Char('d') => {
+        Action::Download => {
             app.start_input(InputMode::DownloadDestination, "Download destination (dir)");
         }
-        KeyCode::Char('u') => {
+        Action::Upload => {
             app.start_upload_picker();
         }
-        KeyCode::Char('x') => {
-            app.start_delete_confirm();
-        }
-        KeyCode::Delete => {
-            app.start_delete_confirm();
+        Action::Delete => {
+            app.start_delete_confirm(handle);
         }
-        KeyCode::Up => {
+        Action::Up => {
             app.select_previous();
         }
-        KeyCode::Down => {
+        Action::Down => {
             app.select_next();
         }
-        KeyCode::Enter | KeyCode::Right => {
+        Action::Open => {
             app.open_selected(handle)?;
         }
+        Action::Rename => {
+            app.start_rename();
+        }
+        Action::Mkdir => {
+            app.start_input(InputMode::NewFolder, "New folder name");
+        }
+        Action::Move => {
+            app.move_marked_or_mark(handle)?;
+        }
+        Action::Share => {
+            app.start_share();
+        }
+        Action::Sort => {
+            app.cycle_sort_order();
+        }
+        Action::ShowTrashed => {
+            app.toggle_show_trashed(handle)?;
+        }
+        Action::Restore => {
+            app.restore_selected(handle)?;
+        }
+        Action::RemoveAccess => {
+            app.remove_access_selected(handle)?;
+        }
+        Action::SwitchDrive => {
+            app.start_drive_select(handle);
+        }
+        Action::SwitchAccount => {
+            app.start_account_select();
+        }
+        Action::Undo => {
+            app.undo_last(handle)?;
+        }
+        Action::GoTo => {
+            app.start_input(InputMode::GoTo, "Go to Drive url or id");
+        }
+        Action::Search => {
+            app.start_input(InputMode::Search, "Search files by name");
+        }
+        Action::Transfers => {
+            app.transfers_selected = 0;
+            app.input_mode = InputMode::Transfers;
+        }
+        Action::CancelTransfer => {
+            app.cancel_selected_item_transfer();
+        }
+        Action::PauseTransfer => {
+            app.toggle_pause_selected_item_transfer();
+        }
+        Action::Palette => {
+            app.palette = Some(Palette::new());
+            app.input_mode = InputMode::Palette;
+        }
+        Action::Help => {
+            app.input_mode = InputMode::Help;
+        }
+    }
+
+    Ok(false)
+}
+
+fn handle_help_key(app: &mut App, key: KeyEvent) -> Result<bool, Error> {
+    let closes = matches!(key.code, KeyCode::Esc | KeyCode::Char('q') | KeyCode::Enter)
+        || app.keymap.action_for(&key) == Some(Action::Help);
+    if closes {
+        app.input_mode = InputMode::Normal;
+    }
+    Ok(false)
+}
+
+fn handle_palette_key(app: &mut App, key: KeyEvent, handle: &Handle) -> Result<bool, Error> {
+    let palette = match app.palette.as_mut() {
+        Some(palette) => palette,
+        None => {
+            app.input_mode = InputMode::Normal;
+            return Ok(false);
+        }
+    };
+
+    match key.code {
+        KeyCode::Esc => {
+            app.palette = None;
+            app.input_mode = InputMode::Normal;
+        }
+        KeyCode::Up => {
+            palette.select_previous();
+        }
+        KeyCode::Down | KeyCode::Tab => {
+            palette.select_next();
+        }
+        KeyCode::Backspace => {
+            palette.pop();
+        }
+        KeyCode::Enter => {
+            let action = palette.selected_action();
+            app.palette = None;
+            app.input_mode = InputMode::Normal;
+            if let Some(action) = action {
+                return run_action(app, action, handle);
+            }
+        }
+        KeyCode::Char(ch) => {
+            if !key.modifiers.contains(KeyModifiers::CONTROL) {
+                palette.push(ch);
+            }
+        }
+        _ => {}
+    }
+
+    Ok(false)
+}
+
+fn handle_select_key(app: &mut App, key: KeyEvent, handle: &Handle) -> Result<bool, Error> {
+    let select = match app.select.as_mut() {
+        Some(select) => select,
+        None => {
+            app.input_mode = InputMode::Normal;
+            return Ok(false);
+        }
+    };
+
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') => {
+            app.select = None;
+            app.input_mode = InputMode::Normal;
+            app.status = "Cancelled".to_string();
+        }
+        KeyCode::Up => {
+            if !select.options.is_empty() {
+                if select.selected == 0 {
+                    select.selected = select.options.len() - 1;
+                } else {
+                    select.selected -= 1;
+                }
+            }
+        }
+        KeyCode::Down => {
+            if !select.options.is_empty() {
+                select.selected = (select.selected + 1) % select.options.len();
+            }
+        }
+        KeyCode::Char('p') if select.kind == SelectKind::SearchResult => {
+            let option = select.options.get(select.selected).cloned();
+            app.select = None;
+            app.input_mode = InputMode::Normal;
+
+            if let Some(option) = option {
+                if let Err(err) = app.open_search_result_folder(handle, option) {
+                    app.status = format!("Error: {}", err);
+                }
+            }
+        }
+        KeyCode::Enter => {
+            let kind = select.kind;
+            let option = select.options.get(select.selected).cloned();
+            app.select = None;
+            app.input_mode = InputMode::Normal;
+
+            if let Some(option) = option {
+                let result = match kind {
+                    SelectKind::Drive => app.switch_drive(handle, option),
+                    SelectKind::Account => app.switch_account(handle, option),
+                    SelectKind::SearchResult => app.open_search_result(handle, option),
+                };
+                if let Err(err) = result {
+                    app.status = format!("Error: {}", err);
+                }
+            }
+        }
+        _ => {}
+    }
+
+    Ok(false)
+}
+
+fn handle_transfers_key(app: &mut App, key: KeyEvent, handle: &Handle) -> Result<bool, Error> {
+    let count = app.running_transfers().len() + app.finished_transfers.len();
+
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('h') => {
+            app.input_mode = InputMode::Normal;
+        }
+        KeyCode::Up => {
+            if count > 0 {
+                app.transfers_selected = (app.transfers_selected + count - 1) % count;
+            }
+        }
+        KeyCode::Down => {
+            if count > 0 {
+                app.transfers_selected = (app.transfers_selected + 1) % count;
+            }
+        }
+        KeyCode::Char('r') => {
+            app.retry_selected_transfer(handle)?;
+        }
+        KeyCode::Char('c') => {
+            app.copy_selected_transfer_error();
+        }
+        KeyCode::Char('x') => {
+            app.cancel_selected_transfer();
+        }
+        KeyCode::Char('p') => {
+            app.toggle_pause_selected_transfer();
+        }
         _ => {}
     }
 
@@ -152,7 +629,7 @@ fn handle_input_key(app: &mut App, key: KeyEvent, handle: &Handle) -> Result<boo
             app.cancel_input("Cancelled");
         }
         KeyCode::Enter => {
-            let input = app.input.clone();
+            let input = app.input.as_str().to_string();
             let mode = app.input_mode;
             app.input.clear();
             app.input_mode = InputMode::Normal;
@@ -160,29 +637,96 @@ fn handle_input_key(app: &mut App, key: KeyEvent, handle: &Handle) -> Result<boo
             match mode {
                 InputMode::DownloadDestination => {
                     let destination = if input.trim().is_empty() {
-                        None
+                        app.local_dirs.download_dir.clone()
                     } else {
-                        Some(PathBuf::from(input.trim()))
+                        let destination = PathBuf::from(input.trim());
+                        // Relative to the directory of this session
+                        let destination = std::env::current_dir()
+                            .map(|dir| dir.join(&destination))
+                            .unwrap_or(destination);
+                        Some(destination)
                     };
-                    if let Err(err) = app.download_selected(handle, destination) {
+                    if let Err(err) = app.download_to(handle, destination) {
                         app.status = format!("Error: {}", err);
-                    } else {
+                    } else if app.input_mode == InputMode::Normal {
                         app.status = "Download completed".to_string();
                     }
                 }
-                InputMode::Normal | InputMode::UploadPicker | InputMode::DeleteConfirm | InputMode::QuitConfirm => {}
+                InputMode::Rename => {
+                    let name = input.trim();
+                    match app.prompt_item.take() {
+                        Some(item) if !name.is_empty() => {
+                            match app.rename_item(handle, &item, name) {
+                                Ok(()) => {
+                                    app.reload(handle)?;
+                                    app.status = format!("Renamed '{}' to '{}'", item.name, name);
+                                }
+                                Err(err) => app.status = format!("Rename failed: {}", err),
+                            }
+                        }
+                        _ => app.status = "Rename cancelled".to_string(),
+                    }
+                }
+                InputMode::NewFolder => {
+                    let name = input.trim();
+                    if name.is_empty() {
+                        app.status = "Cancelled".to_string();
+                    } else {
+                        match app.create_folder(handle, name) {
+                            Ok(()) => {
+                                app.reload(handle)?;
+                                app.status = format!("Created folder '{}'", name);
+                            }
+                            Err(err) => app.status = format!("Create folder failed: {}", err),
+                        }
+                    }
+                }
+                InputMode::ShareWith => {
+                    let email = input.trim();
+                    let email = if email.is_empty() {
+                        None
+                    } else {
+                        Some(email.to_string())
+                    };
+                    if let Some(item) = app.prompt_item.take() {
+                        let target = email
+                            .clone()
+                            .unwrap_or_else(|| "anyone with the link".to_string());
+                        match app.share_item(handle, &item, email) {
+                            Ok(()) => app.status = format!("Shared '{}' with {}", item.name, target),
+                            Err(err) => app.status = format!("Share failed: {}", err),
+                        }
+                    }
+                }
+                InputMode::GoTo => {
+                    let target = input.trim();
+                    if target.is_empty() {
+                        app.status = "Cancelled".to_string();
+                    } else {
+                        app.go_to(handle, target)?;
+                    }
+                }
+                InputMode::Search => {
+                    let query = input.trim();
+                    if query.is_empty() {
+                        app.status = "Cancelled".to_string();
+                    } else {
+                        app.start_search_results(handle, query);
+                    }
+                }
+                InputMode::Normal
+                | InputMode::UploadPicker
+                | InputMode::DeleteConfirm
+                | InputMode::QuitConfirm
+                | InputMode::CreateDestinationConfirm
+                | InputMode::Help
+                | InputMode::Palette
+                | InputMode::Select => {}
             }
         }
-        KeyCode::Backspace => {
-            app.input.pop();
-        }
-        KeyCode::Char(ch) => {
-            if key.modifiers.contains(KeyModifiers::CONTROL) {
-                return Ok(false);
-            }
-            app.input.push(ch);
+        _ => {
+            app.input.handle_key(key);
         }
-        _ => {}
     }
 
     Ok(false)
@@ -201,24 +745,68 @@ fn handle_upload_picker_key(
         }
     };
 
+    // While the filter is typed, keys go to it. Up and down still move
+    if picker.filtering {
+        match key.code {
+            KeyCode::Esc => {
+                picker.filter.clear();
+                picker.filtering = false;
+                picker.apply_view();
+                return Ok(false);
+            }
+            KeyCode::Enter => {
+                picker.filtering = false;
+                return Ok(false);
+            }
+            KeyCode::Backspace => {
+                picker.filter.pop();
+                picker.apply_view();
+                return Ok(false);
+            }
+            KeyCode::Char(ch) => {
+                picker.filter.push(ch);
+                picker.apply_view();
+                return Ok(false);
+            }
+            _ => {}
+        }
+    }
+
     match key.code {
         KeyCode::Esc | KeyCode::Char('q') => {
             app.cancel_input("Upload cancelled");
         }
         KeyCode::Char('b') | KeyCode::Left => {
-            if let Some(parent) = picker.current_dir.parent().map(|p| p.to_path_buf()) {
-                picker.current_dir = parent;
-                picker.reload().map_err(Error::Io)?;
+            if let Some(parent) = picker_parent(&picker.current_dir) {
+                picker.open(parent).map_err(Error::Io)?;
             }
         }
         KeyCode::Right => {
             if let Some(entry) = picker.entries.get(picker.selected) {
                 if entry.is_dir {
-                    picker.current_dir = entry.path.clone();
-                    picker.reload().map_err(Error::Io)?;
+                    let path = entry.path.clone();
+                    picker.open(path).map_err(Error::Io)?;
                 }
             }
         }
+        KeyCode::Char('/') => {
+            picker.filtering = true;
+        }
+        KeyCode::Char('o') => {
+            picker.sort = picker.sort.next();
+            picker.apply_view();
+            app.status = format!("Sorted by {}", picker.sort.label());
+        }
+        KeyCode::Char('.') => {
+            picker.show_hidden = !picker.show_hidden;
+            picker.reload().map_err(Error::Io)?;
+            app.show_hidden = picker.show_hidden;
+            app.status = if app.show_hidden {
+                "Showing hidden files, folder uploads include them".to_string()
+            } else {
+                "Hiding hidden files".to_string()
+            };
+        }
         KeyCode::Up => {
             if !picker.entries.is_empty() {
                 if picker.selected == 0 {
@@ -236,8 +824,8 @@ fn handle_upload_picker_key(
         KeyCode::Enter => {
             if let Some(entry) = picker.entries.get(picker.selected) {
                 if entry.is_parent {
-                    picker.current_dir = entry.path.clone();
-                    picker.reload().map_err(Error::Io)?;
+                    let path = entry.path.clone();
+                    picker.open(path).map_err(Error::Io)?;
                 } else {
                     picker.selected_path = Some(entry.path.clone());
                     app.status = format!("Selected {}", entry.name);
@@ -251,9 +839,12 @@ fn handle_upload_picker_key(
                 .or_else(|| picker.entries.get(picker.selected).map(|e| e.path.clone()));
             match selection {
                 Some(path) => {
+                    let upload_dir = picker.current_dir.clone();
+                    app.remember_local_dirs(|dirs| dirs.upload_dir = Some(upload_dir));
                     app.input_mode = InputMode::Normal;
                     app.upload_picker = None;
-                    app.start_upload_job(handle, path)?;
+                    let parent_id = app.current_folder_id.clone();
+                    app.start_upload_job(handle, path, parent_id)?;
                 }
                 None => {
                     app.status = "No selection to upload".to_string();
@@ -274,18 +865,22 @@ fn handle_delete_confirm_key(
     match key.code {
         KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('n') | KeyCode::Char('N') => {
             app.pending_delete = None;
+            app.delete_scan = None;
             app.input_mode = InputMode::Normal;
             app.status = "Delete cancelled".to_string();
         }
         KeyCode::Char('y') | KeyCode::Char('Y') => {
             if let Some(item) = app.pending_delete.clone() {
                 app.pending_delete = None;
+                app.delete_scan = None;
                 app.input_mode = InputMode::Normal;
-                if let Err(err) = app.delete_item(handle, item) {
-                    app.status = format!("Delete failed: {}", err);
-                } else {
-                    app.status = "Delete completed".to_string();
-                    app.reload(handle)?;
+                match app.trash_item(handle, item) {
+                    Ok(journal_error) => {
+                        app.reload(handle)?;
+                        let status = "Moved to trash (U to undo)".to_string();
+                        app.status = with_journal_warning(status, journal_error);
+                    }
+                    Err(err) => app.status = format!("Delete failed: {}", err),
                 }
             } else {
                 app.input_mode = InputMode::Normal;
@@ -313,7 +908,44 @@ fn handle_quit_confirm_key(app: &mut App, key: KeyEvent) -> Result<bool, Error>
     Ok(false)
 }
 
+fn handle_create_destination_key(
+    app: &mut App,
+    key: KeyEvent,
+    handle: &Handle,
+) -> Result<bool, Error> {
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('n') | KeyCode::Char('N') => {
+            app.pending_destination = None;
+            app.input_mode = InputMode::Normal;
+            app.status = "Download cancelled".to_string();
+        }
+        KeyCode::Char('y') | KeyCode::Char('Y') => {
+            app.input_mode = InputMode::Normal;
+            if let Some(destination) = app.pending_destination.take() {
+                match std::fs::create_dir_all(&destination) {
+                    Ok(()) => {
+                        if let Err(err) = app.download_to(handle, Some(destination)) {
+                            app.status = format!("Error: {}", err);
+                        }
+                    }
+                    Err(err) => {
+                        app.status =
+                            format!("Failed to create '{}': {}", destination.display(), err);
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(false)
+}
+
 fn draw_ui(frame: &mut ratatui::Frame<'_>, app: &App) {
+    if frame.size().width < MIN_WIDTH || frame.size().height < MIN_HEIGHT {
+        draw_too_small(frame);
+        return;
+    }
+
     let layout = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
@@ -323,16 +955,44 @@ fn draw_ui(frame: &mut ratatui::Frame<'_>, app: &App) {
         ])
         .split(frame.size());
 
-    let header = Paragraph::new(Line::from(vec![
+    let mut header_spans = vec![
         Span::raw("Folder: "),
         Span::styled(
             app.current_folder_name.as_str(),
             Style::default().add_modifier(Modifier::BOLD),
         ),
-    ]));
+        Span::raw(format!("  Sort: {}", app.sort_order.label())),
+    ];
+    if app.show_trashed {
+        header_spans.push(Span::styled(
+            "  Showing trash",
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    if let Some(local_path) = app
+        .current_folder_id
+        .as_ref()
+        .and_then(|id| app.sync_pairs.get(id))
+    {
+        header_spans.push(Span::styled(
+            format!("  Synced with {}", local_path.display()),
+            Style::default().fg(Color::Green),
+        ));
+    }
+    if let Some(item) = &app.pending_move {
+        header_spans.push(Span::styled(
+            format!(
+                "  Moving: {} ({} to drop here)",
+                item.name,
+                app.keymap.format_keys(Action::Move)
+            ),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    let header = Paragraph::new(Line::from(header_spans));
     frame.render_widget(header, layout[0]);
 
-    let items: Vec<ListItem> = app
+    let mut items: Vec<ListItem> = app
         .items
         .iter()
         .map(|item| {
@@ -347,17 +1007,49 @@ fn draw_ui(frame: &mut ratatui::Frame<'_>, app: &App) {
             } else {
                 item.name.clone()
             };
-            ListItem::new(Line::from(label))
+            // Trashed items are shown with `show-trashed` and can be restored
+            let mut spans = if item.trashed {
+                let style = Style::default()
+                    .fg(Color::DarkGray)
+                    .add_modifier(Modifier::CROSSED_OUT);
+                vec![Span::styled(label, style)]
+            } else {
+                vec![Span::raw(label)]
+            };
+            if let Some(badge) = app.transfer_badge(item) {
+                spans.push(Span::raw("  "));
+                spans.push(badge_span(&badge));
+            }
+            ListItem::new(Line::from(spans))
         })
         .collect();
 
+    // An upload only shows up in the listing when it is done, until then it
+    // gets a row after the items that can't be selected
+    if let Some(name) = app.pending_upload_name() {
+        if let Some(badge) = app.upload_badge() {
+            items.push(ListItem::new(Line::from(vec![
+                Span::styled(name, Style::default().fg(Color::DarkGray)),
+                Span::raw("  "),
+                badge_span(&badge),
+            ])));
+        }
+    }
+
+    let mut list_block = Block::default()
+        .borders(Borders::ALL)
+        .title("Drive")
+        .border_style(Style::default().fg(Color::LightBlue));
+    if let Some(description) = app
+        .items
+        .get(app.selected)
+        .and_then(|item| item.description.as_ref())
+    {
+        list_block = list_block.title_bottom(format!(" {} ", description.replace('\n', " ")));
+    }
+
     let list = List::new(items)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title("Drive")
-                .border_style(Style::default().fg(Color::LightBlue)),
-        )
+        .block(list_block)
         .highlight_style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD));
 
     let mut state = ListState::default();
@@ -370,33 +1062,31 @@ fn draw_ui(frame: &mut ratatui::Frame<'_>, app: &App) {
         InputMode::Normal => {
             let status = app.render_status();
             let mut spans = vec![Span::raw(status), Span::raw(" | ")];
-            for (index, (label, tag)) in HELP_LABELS.iter().enumerate() {
-                let color = match *tag {
-                    "open" => Color::Cyan,
-                    "back" => Color::Magenta,
-                    "download" => Color::Yellow,
-                    "upload" => Color::Green,
-                    "delete" => Color::Red,
-                    "refresh" => Color::Blue,
-                    "quit" => Color::Red,
-                    _ => Color::White,
-                };
+            for (index, action) in FOOTER_ACTIONS.iter().enumerate() {
                 if index > 0 {
                     spans.push(Span::raw("  "));
                 }
-                spans.push(Span::styled(*label, Style::default().fg(color)));
+                spans.push(Span::styled(
+                    format!("{}  : {}", app.keymap.format_keys(*action), action.name()),
+                    Style::default().fg(action_color(*action)),
+                ));
             }
             Line::from(spans)
         }
         InputMode::DownloadDestination => {
-            let current_dir = std::env::current_dir()
-                .ok()
+            let current_dir = app
+                .local_dirs
+                .download_dir
+                .clone()
+                .or_else(|| std::env::current_dir().ok())
                 .map(|path| path.display().to_string())
                 .unwrap_or_else(|| "<unknown>".to_string());
-            Line::from(vec![
-                Span::raw(format!("Download to dir (empty = {}): ", current_dir)),
-                Span::styled(app.input.as_str(), Style::default().add_modifier(Modifier::BOLD)),
-            ])
+            let mut spans = vec![Span::raw(format!(
+                "Download to dir (empty = {}): ",
+                current_dir
+            ))];
+            spans.extend(input_spans(&app.input));
+            Line::from(spans)
         }
         InputMode::UploadPicker => {
             let selected = app
@@ -415,6 +1105,19 @@ fn draw_ui(frame: &mut ratatui::Frame<'_>, app: &App) {
                 Span::raw("  "),
                 Span::styled("u: upload", Style::default().fg(Color::Green)),
                 Span::raw("  "),
+                Span::styled("/: filter", Style::default().fg(Color::Gray)),
+                Span::raw("  "),
+                Span::styled("o: sort", Style::default().fg(Color::Gray)),
+                Span::raw("  "),
+                Span::styled(
+                    if app.show_hidden {
+                        ".: hide hidden"
+                    } else {
+                        ".: show hidden"
+                    },
+                    Style::default().fg(Color::Gray),
+                ),
+                Span::raw("  "),
                 Span::styled("Esc: cancel", Style::default().fg(Color::Red)),
                 Span::raw(" | Selected: "),
                 Span::styled(selected, Style::default().add_modifier(Modifier::BOLD)),
@@ -436,6 +1139,39 @@ fn draw_ui(frame: &mut ratatui::Frame<'_>, app: &App) {
         }
         InputMode::DeleteConfirm => Line::from(vec![Span::raw("Confirm delete...")]),
         InputMode::QuitConfirm => Line::from(vec![Span::raw("Confirm quit...")]),
+        InputMode::CreateDestinationConfirm => Line::from(vec![
+            Span::raw(format!("{}  ", app.status)),
+            Span::styled("[y] Create", Style::default().fg(Color::Green)),
+            Span::raw("  "),
+            Span::styled("[n] Cancel", Style::default().fg(Color::Red)),
+        ]),
+        InputMode::Rename
+        | InputMode::NewFolder
+        | InputMode::ShareWith
+        | InputMode::GoTo
+        | InputMode::Search => {
+            let mut spans = vec![Span::raw(format!("{}: ", app.status))];
+            spans.extend(input_spans(&app.input));
+            Line::from(spans)
+        }
+        InputMode::Help => Line::from(vec![Span::raw("Esc/q: close help")]),
+        InputMode::Select
+            if app
+                .select
+                .as_ref()
+                .map(|select| select.kind == SelectKind::SearchResult)
+                .unwrap_or(false) =>
+        {
+            Line::from(vec![Span::raw(
+                "↑/↓: select  Enter: open  p: open containing folder  Esc: cancel",
+            )])
+        }
+        InputMode::Palette | InputMode::Select => {
+            Line::from(vec![Span::raw("↑/↓: select  Enter: run  Esc: cancel")])
+        }
+        InputMode::Transfers => Line::from(vec![Span::raw(
+            "↑/↓: select  p: pause/resume  x: cancel  r: retry failed  c: copy error  Esc/h: close",
+        )]),
     };
 
     let footer = Paragraph::new(footer_text);
@@ -450,6 +1186,316 @@ fn draw_ui(frame: &mut ratatui::Frame<'_>, app: &App) {
     if app.input_mode == InputMode::QuitConfirm {
         draw_quit_confirm(frame, app);
     }
+    if app.input_mode == InputMode::Help {
+        draw_help(frame, app);
+    }
+    if app.input_mode == InputMode::Palette {
+        draw_palette(frame, app);
+    }
+    if app.input_mode == InputMode::Select {
+        draw_select(frame, app);
+    }
+    if app.input_mode == InputMode::Transfers {
+        draw_transfers(frame, app);
+    }
+}
+
+fn badge_span(badge: &TransferBadge) -> Span<'static> {
+    let (label, color) = match badge {
+        TransferBadge::Uploading(percent) => (format!("↑ {}%", percent), Color::Cyan),
+        TransferBadge::Downloading(percent) => (format!("↓ {}%", percent), Color::Cyan),
+        TransferBadge::Paused(percent) => (format!("paused {}%", percent), Color::Yellow),
+        TransferBadge::Done => ("✓ done".to_string(), Color::Green),
+        TransferBadge::Failed => ("✗ failed".to_string(), Color::Red),
+    };
+    Span::styled(label, Style::default().fg(color))
+}
+
+fn action_color(action: Action) -> Color {
+    match action {
+        Action::Open => Color::Cyan,
+        Action::Back => Color::Magenta,
+        Action::Download => Color::Yellow,
+        Action::Upload => Color::Green,
+        Action::Delete | Action::RemoveAccess | Action::CancelTransfer => Color::Red,
+        Action::Refresh => Color::Blue,
+        Action::Quit => Color::Red,
+        Action::Rename | Action::Mkdir | Action::Move | Action::Share | Action::PauseTransfer => {
+            Color::Yellow
+        }
+        Action::Undo | Action::Restore => Color::Green,
+        Action::Palette => Color::Cyan,
+        Action::Up
+        | Action::Down
+        | Action::Sort
+        | Action::ShowTrashed
+        | Action::SwitchDrive
+        | Action::SwitchAccount
+        | Action::GoTo
+        | Action::Search
+        | Action::Help => Color::White,
+    }
+}
+
+fn draw_help(frame: &mut ratatui::Frame<'_>, app: &App) {
+    let area = centered_rect(90, 90, frame.size());
+    frame.render_widget(Clear, area);
+
+    // Generated from the keymap so remapped keys show up as they are bound
+    let key_width = Action::ALL
+        .iter()
+        .map(|action| app.keymap.format_keys(*action).chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let mut lines: Vec<Line> = Action::ALL
+        .iter()
+        .map(|action| {
+            let keys = app.keymap.format_keys(*action);
+            let keys = if keys.is_empty() {
+                "(unbound)".to_string()
+            } else {
+                keys
+            };
+            Line::from(vec![
+                Span::styled(
+                    format!("{:width$}  ", keys, width = key_width),
+                    Style::default()
+                        .fg(action_color(*action))
+                        .add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(action.description()),
+            ])
+        })
+        .collect();
+
+    lines.push(Line::from(""));
+    lines.push(Line::from(vec![
+        Span::styled(
+            format!("{:width$}  ", "Ctrl-c", width = key_width),
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+        Span::raw("Cancel the current prompt or quit"),
+    ]));
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        "Press Esc or q to close",
+        Style::default().fg(Color::DarkGray),
+    )));
+
+    let paragraph = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Help")
+                .border_style(Style::default().fg(Color::LightBlue)),
+        )
+        .wrap(ratatui::widgets::Wrap { trim: false });
+    frame.render_widget(paragraph, area);
+}
+
+fn draw_too_small(frame: &mut ratatui::Frame<'_>) {
+    let size = frame.size();
+    let lines = vec![
+        Line::from(Span::styled(
+            "Terminal too small",
+            Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+        )),
+        Line::from(format!("Current size: {}x{}", size.width, size.height)),
+        Line::from(format!("Minimum size: {}x{}", MIN_WIDTH, MIN_HEIGHT)),
+        Line::from("Resize the window or press q to quit"),
+    ];
+    let paragraph = Paragraph::new(lines)
+        .alignment(ratatui::layout::Alignment::Center)
+        .wrap(ratatui::widgets::Wrap { trim: true });
+    frame.render_widget(paragraph, size);
+}
+
+fn draw_palette(frame: &mut ratatui::Frame<'_>, app: &App) {
+    let palette = match &app.palette {
+        Some(palette) => palette,
+        None => return,
+    };
+    let area = centered_rect(70, 60, frame.size());
+    frame.render_widget(Clear, area);
+
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(1)])
+        .split(area);
+
+    let input = Paragraph::new(Line::from(vec![
+        Span::raw(": "),
+        Span::styled(palette.query.as_str(), Style::default().add_modifier(Modifier::BOLD)),
+    ]))
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Command")
+            .border_style(Style::default().fg(Color::LightBlue)),
+    );
+    frame.render_widget(input, layout[0]);
+
+    let matches = palette.matches();
+    let items: Vec<ListItem> = matches
+        .iter()
+        .map(|action| {
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("{:<16}", action.name()),
+                    Style::default().fg(action_color(*action)),
+                ),
+                Span::raw(action.description()),
+                Span::styled(
+                    format!("  {}", app.keymap.format_keys(*action)),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ]))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::LightBlue)),
+        )
+        .highlight_style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD));
+
+    let mut state = ListState::default();
+    if !matches.is_empty() {
+        state.select(Some(palette.selected));
+    }
+    frame.render_stateful_widget(list, layout[1], &mut state);
+}
+
+fn draw_select(frame: &mut ratatui::Frame<'_>, app: &App) {
+    let select = match &app.select {
+        Some(select) => select,
+        None => return,
+    };
+    let area = centered_rect(60, 50, frame.size());
+    frame.render_widget(Clear, area);
+
+    let items: Vec<ListItem> = select
+        .options
+        .iter()
+        .map(|option| ListItem::new(Line::from(option.label.clone())))
+        .collect();
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(select.title.as_str())
+                .border_style(Style::default().fg(Color::LightBlue)),
+        )
+        .highlight_style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD));
+
+    let mut state = ListState::default();
+    if !select.options.is_empty() {
+        state.select(Some(select.selected));
+    }
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_transfers(frame: &mut ratatui::Frame<'_>, app: &App) {
+    let area = centered_rect(80, 60, frame.size());
+    frame.render_widget(Clear, area);
+
+    // Running transfers are listed first, they can be cancelled with x
+    let running = app.running_transfers();
+    let mut items: Vec<ListItem> = running
+        .iter()
+        .map(|transfer| {
+            let state = if transfer.cancel.load(Ordering::SeqCst) {
+                Span::styled("cancelling", Style::default().fg(Color::Red))
+            } else {
+                badge_span(&transfer.badge)
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled("running ", Style::default().fg(Color::DarkGray)),
+                Span::raw(format!("  {} {}  ", transfer.arrow(), transfer.target.name)),
+                state,
+            ]))
+        })
+        .collect();
+
+    items.extend(app.finished_transfers.iter().rev().map(|transfer| {
+        let direction = match transfer.kind {
+            TransferKind::Upload { .. } => "↑",
+            TransferKind::Download { .. } => "↓",
+        };
+        let badge = if transfer.error.is_some() {
+            TransferBadge::Failed
+        } else {
+            TransferBadge::Done
+        };
+        ListItem::new(Line::from(vec![
+            Span::styled(
+                transfer.finished_at.format("%H:%M:%S").to_string(),
+                Style::default().fg(Color::DarkGray),
+            ),
+            Span::raw(format!("  {} {}  ", direction, transfer.target.name)),
+            badge_span(&badge),
+        ]))
+    }));
+
+    let has_transfers = !items.is_empty();
+    if !has_transfers {
+        items.push(ListItem::new(Line::from("No transfers yet")));
+    }
+
+    let mut block = Block::default()
+        .borders(Borders::ALL)
+        .title("Transfers")
+        .border_style(Style::default().fg(Color::LightBlue));
+    if let Some(error) = app
+        .selected_transfer()
+        .and_then(|transfer| transfer.error.as_ref())
+    {
+        block = block.title_bottom(format!(" {} ", error.replace('\n', " ")));
+    }
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD));
+
+    let mut state = ListState::default();
+    if has_transfers {
+        state.select(Some(app.transfers_selected));
+    }
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+// Input text with the cursor drawn as a reversed cell
+fn input_spans(input: &LineEditor) -> Vec<Span<'_>> {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let (before, current, after) = input.split_at_cursor();
+    let current = if current.is_empty() { " " } else { current };
+    vec![
+        Span::styled(before, bold),
+        Span::styled(current, bold.add_modifier(Modifier::REVERSED)),
+        Span::styled(after, bold),
+    ]
+}
+
+// I.e. ` sorted by size, filter: "raw" (3 of 120) `
+fn upload_picker_view_label(picker: &UploadPicker) -> String {
+    let mut label = format!(" sorted by {}", picker.sort.label());
+    if picker.filtering || !picker.filter.is_empty() {
+        let cursor = if picker.filtering { "_" } else { "" };
+        let count = |entries: &[LocalEntry]| entries.iter().filter(|e| !e.is_parent).count();
+        label.push_str(&format!(
+            ", filter: \"{}{}\" ({} of {})",
+            picker.filter,
+            cursor,
+            count(&picker.entries),
+            count(&picker.all_entries)
+        ));
+    }
+    label.push(' ');
+    label
 }
 
 fn draw_upload_picker(frame: &mut ratatui::Frame<'_>, app: &App) {
@@ -470,17 +1516,33 @@ fn draw_upload_picker(frame: &mut ratatui::Frame<'_>, app: &App) {
             } else {
                 entry.name.clone()
             };
-            ListItem::new(Line::from(label))
+            let mut spans = vec![Span::raw(label)];
+            if let Some(details) = local_entry_details(entry) {
+                spans.push(Span::styled(
+                    format!("  ({})", details),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+            if entry.is_hidden {
+                ListItem::new(Line::from(spans)).style(Style::default().fg(Color::DarkGray))
+            } else {
+                ListItem::new(Line::from(spans))
+            }
         })
         .collect();
 
     let list = List::new(entries)
         .block(
             Block::default()
-                .title(format!(
-                    "Upload from {}",
-                    picker.current_dir.display()
-                ))
+                .title(if picker.current_dir.as_os_str().is_empty() {
+                    "Upload from drives".to_string()
+                } else {
+                    format!(
+                        "Upload from {}",
+                        local_path::simplified(&picker.current_dir).display()
+                    )
+                })
+                .title_bottom(upload_picker_view_label(picker))
                 .borders(Borders::ALL)
                 .border_style(Style::default().fg(Color::LightBlue)),
         )
@@ -498,24 +1560,36 @@ fn draw_delete_confirm(frame: &mut ratatui::Frame<'_>, app: &App) {
         Some(item) => item,
         None => return,
     };
-    let area = centered_rect(50, 30, frame.size());
+    let area = centered_rect(60, 40, frame.size());
     frame.render_widget(Clear, area);
-    let lines = vec![
-        Line::from(vec![
-            Span::raw("Delete "),
-            Span::styled(
-                item.name.as_str(),
-                Style::default().add_modifier(Modifier::BOLD),
-            ),
-            Span::raw("?"),
-        ]),
-        Line::from("Are you sure?"),
-        Line::from(vec![
-            Span::styled("[y] Yes", Style::default().fg(Color::Red)),
-            Span::raw("  "),
-            Span::styled("[n] No", Style::default().fg(Color::Green)),
-        ]),
-    ];
+    let mut lines = vec![Line::from(vec![
+        Span::raw("Move "),
+        Span::styled(
+            item.name.as_str(),
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+        Span::raw(" to trash?"),
+    ])];
+
+    if let Some(scan) = &app.delete_scan {
+        let line = match scan.state.lock().map(|scan| scan.clone()) {
+            Ok(DescendantScan::Counting) => Line::from("Counting files..."),
+            Ok(DescendantScan::Done(descendants)) => Line::from(Span::styled(
+                format_blast_radius(&descendants),
+                Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+            )),
+            Ok(DescendantScan::Failed(err)) => Line::from(format!("Could not count files: {}", err)),
+            Err(_) => Line::from("Could not count files"),
+        };
+        lines.push(line);
+    }
+
+    lines.push(Line::from("It can be restored with U until you quit"));
+    lines.push(Line::from(vec![
+        Span::styled("[y] Yes", Style::default().fg(Color::Red)),
+        Span::raw("  "),
+        Span::styled("[n] No", Style::default().fg(Color::Green)),
+    ]));
 
     let block = Block::default()
         .title("Confirm Delete")
@@ -526,6 +1600,24 @@ fn draw_delete_confirm(frame: &mut ratatui::Frame<'_>, app: &App) {
     frame.render_widget(paragraph, area);
 }
 
+fn format_blast_radius(descendants: &Descendants) -> String {
+    let size = files::info::format_bytes(descendants.bytes as i64, &DisplayConfig::default());
+    if descendants.folders > 0 {
+        format!(
+            "This will remove {} files and {} folders ({})",
+            files::info::format_count(descendants.files),
+            files::info::format_count(descendants.folders),
+            size
+        )
+    } else {
+        format!(
+            "This will remove {} files ({})",
+            files::info::format_count(descendants.files),
+            size
+        )
+    }
+}
+
 fn draw_quit_confirm(frame: &mut ratatui::Frame<'_>, app: &App) {
     if !app.has_active_transfer() {
         return;
@@ -574,9 +1666,90 @@ struct DriveItem {
     name: String,
     is_folder: bool,
     size: Option<i64>,
+    created_time: Option<DateTime<Utc>>,
+    parents: Vec<String>,
+    description: Option<String>,
+    trashed: bool,
+    capabilities: ItemCapabilities,
     is_parent: bool,
 }
 
+// What the user may do with an item. Files shared with the user can often only
+// be viewed, so the actions are refused up front instead of failing with a 403
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+struct ItemCapabilities {
+    can_rename: bool,
+    can_trash: bool,
+    can_untrash: bool,
+}
+
+impl ItemCapabilities {
+    // Capabilities Drive didn't send are allowed, Drive still has the last word
+    fn from_file(file: &google_drive3::api::File) -> ItemCapabilities {
+        let capabilities = file.capabilities.as_ref();
+        let allowed = |flag: Option<bool>| flag.unwrap_or(true);
+
+        ItemCapabilities {
+            can_rename: allowed(capabilities.and_then(|c| c.can_rename)),
+            can_trash: allowed(capabilities.and_then(|c| c.can_trash)),
+            can_untrash: allowed(capabilities.and_then(|c| c.can_untrash)),
+        }
+    }
+
+    fn all() -> ItemCapabilities {
+        ItemCapabilities {
+            can_rename: true,
+            can_trash: true,
+            can_untrash: true,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum SortOrder {
+    Name,
+    Created,
+    Size,
+}
+
+impl SortOrder {
+    fn next(&self) -> SortOrder {
+        match self {
+            SortOrder::Name => SortOrder::Created,
+            SortOrder::Created => SortOrder::Size,
+            SortOrder::Size => SortOrder::Name,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            SortOrder::Name => "name",
+            SortOrder::Created => "created",
+            SortOrder::Size => "size",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum SelectKind {
+    Drive,
+    Account,
+    SearchResult,
+}
+
+#[derive(Debug, Clone)]
+struct SelectOption {
+    label: String,
+    value: Option<String>,
+}
+
+struct SelectPopup {
+    title: String,
+    kind: SelectKind,
+    options: Vec<SelectOption>,
+    selected: usize,
+}
+
 #[derive(Debug, Clone)]
 struct FolderState {
     id: Option<String>,
@@ -589,13 +1762,52 @@ struct LocalEntry {
     path: PathBuf,
     is_dir: bool,
     is_parent: bool,
+    is_hidden: bool,
+    // Size of a file, or the number of items in a directory
+    size: Option<u64>,
+    item_count: Option<u64>,
+    modified: Option<std::time::SystemTime>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum PickerSort {
+    Name,
+    Size,
+    Modified,
+}
+
+impl PickerSort {
+    fn next(&self) -> PickerSort {
+        match self {
+            PickerSort::Name => PickerSort::Size,
+            PickerSort::Size => PickerSort::Modified,
+            PickerSort::Modified => PickerSort::Name,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            PickerSort::Name => "name",
+            PickerSort::Size => "size",
+            PickerSort::Modified => "modified",
+        }
+    }
 }
 
 struct UploadPicker {
     current_dir: PathBuf,
+    // Everything in current_dir, `entries` are the ones that match the filter
+    all_entries: Vec<LocalEntry>,
     entries: Vec<LocalEntry>,
     selected: usize,
     selected_path: Option<PathBuf>,
+    // Dot files and hidden folders are listed, dimmed
+    show_hidden: bool,
+    sort: PickerSort,
+    // Only names that contain it are listed, ignoring case
+    filter: String,
+    // Set while the filter is typed
+    filtering: bool,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -605,10 +1817,22 @@ enum InputMode {
     UploadPicker,
     DeleteConfirm,
     QuitConfirm,
+    // The download destination doesn't exist, y creates it
+    CreateDestinationConfirm,
+    Help,
+    Rename,
+    NewFolder,
+    ShareWith,
+    GoTo,
+    Search,
+    Palette,
+    Select,
+    Transfers,
 }
 
 struct App {
-    hub: Hub,
+    // Cloned into the background jobs, which reuse its connections and token
+    hub: Arc<Hub>,
     items: Vec<DriveItem>,
     selected: usize,
     folder_stack: Vec<FolderState>,
@@ -616,18 +1840,47 @@ struct App {
     current_folder_name: String,
     status: String,
     input_mode: InputMode,
-    input: String,
+    input: LineEditor,
+    keymap: Keymap,
+    palette: Option<Palette>,
+    select: Option<SelectPopup>,
+    prompt_item: Option<DriveItem>,
+    pending_move: Option<DriveItem>,
+    // Id of the item whose access is removed when the action is pressed again
+    pending_remove_access: Option<String>,
+    sort_order: SortOrder,
+    // Trashed items of the folder are listed too
+    show_trashed: bool,
+    history: UndoHistory,
     download_job: Option<DownloadJob>,
     upload_picker: Option<UploadPicker>,
+    // Set with . in the upload picker and kept for the session. Folder uploads
+    // include hidden files while it is set
+    show_hidden: bool,
+    // Where the upload picker opens and where downloads go when no directory
+    // is entered. Saved to local_dirs_path, which is only set for a session
+    // in the terminal
+    local_dirs: LocalDirs,
+    local_dirs_path: Option<PathBuf>,
+    // A download destination that doesn't exist yet, waiting for y to create it
+    pending_destination: Option<PathBuf>,
     upload_job: Option<UploadJob>,
+    // Uploads queued through the control socket, started one after the other
+    upload_queue: VecDeque<QueuedUpload>,
     blink_on: bool,
     last_blink: Instant,
     pending_delete: Option<DriveItem>,
+    delete_scan: Option<DeleteScan>,
+    sync_pairs: HashMap<String, PathBuf>,
+    finished_transfers: Vec<FinishedTransfer>,
+    // Index in the transfers panel, which shows the running transfers and then
+    // the finished ones, latest first
+    transfers_selected: usize,
     exit_requested: bool,
 }
 
 impl App {
-    fn new(hub: Hub) -> Self {
+    fn new(hub: Arc<Hub>) -> Self {
         Self {
             hub,
             items: Vec::new(),
@@ -637,13 +1890,31 @@ impl App {
             current_folder_name: "root".to_string(),
             status: "Ready".to_string(),
             input_mode: InputMode::Normal,
-            input: String::new(),
+            input: LineEditor::default(),
+            keymap: settings::current().keymap(),
+            palette: None,
+            select: None,
+            prompt_item: None,
+            pending_move: None,
+            pending_remove_access: None,
+            sort_order: SortOrder::Name,
+            show_trashed: false,
+            history: UndoHistory::default(),
             download_job: None,
             upload_picker: None,
+            show_hidden: false,
+            local_dirs: LocalDirs::default(),
+            local_dirs_path: None,
+            pending_destination: None,
             upload_job: None,
+            upload_queue: VecDeque::new(),
             blink_on: true,
             last_blink: Instant::now(),
             pending_delete: None,
+            delete_scan: None,
+            sync_pairs: load_sync_pairs(),
+            finished_transfers: Vec::new(),
+            transfers_selected: 0,
             exit_requested: false,
         }
     }
@@ -659,11 +1930,18 @@ impl App {
         self.input.clear();
         self.status = status.to_string();
         self.upload_picker = None;
+        self.prompt_item = None;
     }
 
     fn start_upload_picker(&mut self) {
-        let current_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
-        match UploadPicker::from_dir(current_dir) {
+        let current_dir = self
+            .local_dirs
+            .upload_dir
+            .clone()
+            .filter(|dir| dir.is_dir())
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_else(|| PathBuf::from("."));
+        match UploadPicker::from_dir(current_dir, self.show_hidden) {
             Ok(picker) => {
                 self.upload_picker = Some(picker);
                 self.input_mode = InputMode::UploadPicker;
@@ -675,18 +1953,74 @@ impl App {
         }
     }
 
+    // Only a convenience, a failed save doesn't stop the transfer it is for
+    fn remember_local_dirs(&mut self, update: impl FnOnce(&mut LocalDirs)) {
+        update(&mut self.local_dirs);
+        if let Some(path) = &self.local_dirs_path {
+            let _ = local_dirs::save(path, &self.local_dirs);
+        }
+    }
+
     fn start_quit_confirm(&mut self) {
         self.input_mode = InputMode::QuitConfirm;
         self.status = "Confirm quit".to_string();
     }
 
+    // Ctrl-C backs out of the current prompt, otherwise it behaves like quit.
+    // Returns true if the app can exit right away
+    fn interrupt(&mut self) -> bool {
+        match self.input_mode {
+            InputMode::Normal => {
+                if self.can_quit() {
+                    return true;
+                }
+                self.start_quit_confirm();
+            }
+            InputMode::QuitConfirm => {
+                self.input_mode = InputMode::Normal;
+                self.request_exit();
+            }
+            InputMode::DeleteConfirm => {
+                self.pending_delete = None;
+                self.delete_scan = None;
+                self.input_mode = InputMode::Normal;
+                self.status = "Delete cancelled".to_string();
+            }
+            InputMode::CreateDestinationConfirm => {
+                self.pending_destination = None;
+                self.input_mode = InputMode::Normal;
+                self.status = "Download cancelled".to_string();
+            }
+            InputMode::DownloadDestination
+            | InputMode::UploadPicker
+            | InputMode::Rename
+            | InputMode::NewFolder
+            | InputMode::ShareWith
+            | InputMode::GoTo
+            | InputMode::Search => {
+                self.cancel_input("Cancelled");
+            }
+            InputMode::Help | InputMode::Transfers => {
+                self.input_mode = InputMode::Normal;
+            }
+            InputMode::Palette => {
+                self.palette = None;
+                self.input_mode = InputMode::Normal;
+            }
+            InputMode::Select => {
+                self.select = None;
+                self.input_mode = InputMode::Normal;
+                self.status = "Cancelled".to_string();
+            }
+        }
+        false
+    }
+
     fn request_exit(&mut self) {
         self.exit_requested = true;
-        if let Some(job) = &self.upload_job {
-            job.cancel.store(true, Ordering::SeqCst);
-        }
-        if let Some(job) = &self.download_job {
-            job.cancel.store(true, Ordering::SeqCst);
+        self.upload_queue.clear();
+        for transfer in self.running_transfers() {
+            transfer.cancel();
         }
     }
 
@@ -702,7 +2036,7 @@ impl App {
         self.exit_requested && self.can_quit()
     }
 
-    fn start_delete_confirm(&mut self) {
+    fn start_delete_confirm(&mut self, handle: &Handle) {
         let item = match self.items.get(self.selected) {
             Some(item) => item.clone(),
             None => {
@@ -718,7 +2052,24 @@ impl App {
             self.status = "Missing file id".to_string();
             return;
         }
-        self.pending_delete = Some(item);
+        if !item.capabilities.can_trash {
+            self.status = format!(
+                "Cannot delete '{}', you don't own it. Press {} to remove your access instead",
+                item.name,
+                self.keymap.format_keys(Action::RemoveAccess)
+            );
+            return;
+        }
+        self.delete_scan = if item.is_folder {
+            Some(start_descendant_scan(
+                handle,
+                self.hub.clone(),
+                item.id.clone(),
+            ))
+        } else {
+            None
+        };
+        self.pending_delete = Some(item);
         self.input_mode = InputMode::DeleteConfirm;
         self.status = "Confirm delete".to_string();
     }
@@ -742,9 +2093,13 @@ impl App {
     }
 
     fn reload(&mut self, handle: &Handle) -> Result<(), Error> {
-        let query = match self.current_folder_id.clone() {
-            Some(folder_id) => ListQuery::FilesInFolder { folder_id },
-            None => ListQuery::RootNotTrashed,
+        let query = match (self.current_folder_id.clone(), self.show_trashed) {
+            (Some(folder_id), true) => ListQuery::FilesInFolderWithTrashed { folder_id },
+            (None, true) => ListQuery::FilesInFolderWithTrashed {
+                folder_id: "root".to_string(),
+            },
+            (Some(folder_id), false) => ListQuery::FilesInFolder { folder_id },
+            (None, false) => ListQuery::RootNotTrashed,
         };
         let files = handle
             .block_on(files::list::list_files(
@@ -752,6 +2107,7 @@ impl App {
                 &ListFilesConfig {
                     query,
                     order_by: ListSortOrder::default(),
+                    scope: ListScope::default(),
                     max_files: 1000,
                 },
             ))
@@ -764,6 +2120,11 @@ impl App {
                 name: file.name.clone().unwrap_or_else(|| "<unnamed>".to_string()),
                 is_folder: drive_file::is_directory(&file),
                 size: file.size,
+                created_time: file.created_time,
+                parents: file.parents.clone().unwrap_or_default(),
+                description: file.description.clone().filter(|d| !d.is_empty()),
+                trashed: file.trashed.unwrap_or(false),
+                capabilities: ItemCapabilities::from_file(&file),
                 is_parent: false,
             })
             .collect();
@@ -772,17 +2133,399 @@ impl App {
             name: "/..".to_string(),
             is_folder: true,
             size: None,
+            created_time: None,
+            parents: vec![],
+            description: None,
+            trashed: false,
+            capabilities: ItemCapabilities::all(),
             is_parent: true,
         });
+        self.sort_items();
+        self.selected = 0;
+        self.status = "Ready".to_string();
+        Ok(())
+    }
+
+    fn sort_items(&mut self) {
+        let sort_order = self.sort_order;
         self.items.sort_by(|a, b| match (a.is_folder, b.is_folder) {
             _ if a.is_parent && !b.is_parent => std::cmp::Ordering::Less,
             _ if b.is_parent && !a.is_parent => std::cmp::Ordering::Greater,
             (true, false) => std::cmp::Ordering::Less,
             (false, true) => std::cmp::Ordering::Greater,
-            _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            _ => {
+                let by_name = a.name.to_lowercase().cmp(&b.name.to_lowercase());
+                match sort_order {
+                    SortOrder::Name => by_name,
+                    SortOrder::Created => b.created_time.cmp(&a.created_time).then(by_name),
+                    SortOrder::Size => b.size.cmp(&a.size).then(by_name),
+                }
+            }
         });
-        self.selected = 0;
-        self.status = "Ready".to_string();
+    }
+
+    fn toggle_show_trashed(&mut self, handle: &Handle) -> Result<(), Error> {
+        self.show_trashed = !self.show_trashed;
+        self.reload_keeping_selection(handle)?;
+        self.status = if self.show_trashed {
+            format!(
+                "Showing trashed items, {} restores the selected one",
+                self.keymap.format_keys(Action::Restore)
+            )
+        } else {
+            "Hiding trashed items".to_string()
+        };
+        Ok(())
+    }
+
+    fn restore_selected(&mut self, handle: &Handle) -> Result<(), Error> {
+        let item = match self.items.get(self.selected) {
+            Some(item) if item.trashed => item.clone(),
+            _ => {
+                self.status = "The selected item is not in the trash".to_string();
+                return Ok(());
+            }
+        };
+        if !item.capabilities.can_untrash {
+            self.status = format!("Cannot restore '{}', only its owner can", item.name);
+            return Ok(());
+        }
+
+        let restore = files::delete::apply_and_record(
+            &self.hub,
+            &item.id,
+            &item.name,
+            &item.parents,
+            Operation::Untrash,
+        );
+        let journal_error = handle.block_on(restore).map_err(Error::Trash)?;
+
+        self.reload_keeping_selection(handle)?;
+        let status = format!("Restored '{}'", item.name);
+        self.status = with_journal_warning(status, journal_error);
+        Ok(())
+    }
+
+    // Access can only be given back by the owner, so the first press asks for
+    // a second one on the same item
+    fn remove_access_selected(&mut self, handle: &Handle) -> Result<(), Error> {
+        let item = match self.selected_item() {
+            Some(item) => item,
+            None => return Ok(()),
+        };
+
+        if self.pending_remove_access.take() != Some(item.id.clone()) {
+            self.status = format!(
+                "Press {} again to remove your access to '{}'",
+                self.keymap.format_keys(Action::RemoveAccess),
+                item.name
+            );
+            self.pending_remove_access = Some(item.id);
+            return Ok(());
+        }
+
+        let remove = files::unshare_me::remove_my_access(&self.hub, &item.id);
+        match handle.block_on(remove) {
+            Ok(_) => {
+                self.reload_keeping_selection(handle)?;
+                self.status = format!("Removed your access to '{}'", item.name);
+            }
+            Err(err) => {
+                self.status = format!("Remove access failed: {}", err);
+            }
+        }
+        Ok(())
+    }
+
+    fn reload_keeping_selection(&mut self, handle: &Handle) -> Result<(), Error> {
+        let selected_id = self.items.get(self.selected).map(|item| item.id.clone());
+        self.reload(handle)?;
+        self.selected = selected_id
+            .and_then(|id| self.items.iter().position(|item| item.id == id))
+            .unwrap_or(0);
+        Ok(())
+    }
+
+    fn cycle_sort_order(&mut self) {
+        let selected_id = self.items.get(self.selected).map(|item| item.id.clone());
+        self.sort_order = self.sort_order.next();
+        self.sort_items();
+        self.selected = selected_id
+            .and_then(|id| self.items.iter().position(|item| item.id == id))
+            .unwrap_or(0);
+        self.status = format!("Sorted by {}", self.sort_order.label());
+    }
+
+    // Returns the selected item unless it's the parent entry, setting the status otherwise
+    fn selected_item(&mut self) -> Option<DriveItem> {
+        match self.items.get(self.selected) {
+            Some(item) if item.is_parent => {
+                self.status = "Select a file or folder first".to_string();
+                None
+            }
+            Some(item) => Some(item.clone()),
+            None => {
+                self.status = "No selection".to_string();
+                None
+            }
+        }
+    }
+
+    fn start_rename(&mut self) {
+        if let Some(item) = self.selected_item() {
+            if !item.capabilities.can_rename {
+                self.status = format!("Cannot rename '{}', you can only view it", item.name);
+                return;
+            }
+            self.start_input(InputMode::Rename, &format!("Rename '{}' to", item.name));
+            self.input.set(&item.name);
+            self.prompt_item = Some(item);
+        }
+    }
+
+    fn start_share(&mut self) {
+        if let Some(item) = self.selected_item() {
+            self.start_input(
+                InputMode::ShareWith,
+                "Share with email (empty = anyone with the link)",
+            );
+            self.prompt_item = Some(item);
+        }
+    }
+
+    fn rename_item(&mut self, handle: &Handle, item: &DriveItem, name: &str) -> Result<(), Error> {
+        let patch_file = PatchFile::new(item.id.clone()).with_name(name);
+        handle
+            .block_on(files::update::update_metadata(
+                &self.hub,
+                UploadDelegateConfig::default(),
+                patch_file,
+            ))
+            .map_err(Error::Rename)?;
+        self.history.push(UndoEntry::Rename {
+            file_id: item.id.clone(),
+            old_name: item.name.clone(),
+            new_name: name.to_string(),
+        });
+        Ok(())
+    }
+
+    fn create_folder(&mut self, handle: &Handle, name: &str) -> Result<(), Error> {
+        let config = mkdir::Config {
+            id: None,
+            name: name.to_string(),
+            parents: self.current_folder_id.clone().map(|id| vec![id]),
+            print_only_id: false,
+        };
+        handle
+            .block_on(mkdir::create_directory(
+                &self.hub,
+                &config,
+                UploadDelegateConfig::default(),
+            ))
+            .map_err(Error::Mkdir)?;
+        Ok(())
+    }
+
+    fn share_item(
+        &mut self,
+        handle: &Handle,
+        item: &DriveItem,
+        email: Option<String>,
+    ) -> Result<(), Error> {
+        let type_ = if email.is_some() {
+            permission::Type::User
+        } else {
+            permission::Type::Anyone
+        };
+        let config = permissions::share::Config {
+            file_id: item.id.clone(),
+            role: permission::Role::Reader,
+            type_,
+            discoverable: false,
+            email,
+            domain: None,
+        };
+        handle
+            .block_on(permissions::share::create_permission(
+                &self.hub,
+                UploadDelegateConfig::default(),
+                &config,
+            ))
+            .map_err(Error::Share)?;
+        Ok(())
+    }
+
+    // The first press marks the selected item, the second press moves it into the
+    // folder that is open at that point
+    fn move_marked_or_mark(&mut self, handle: &Handle) -> Result<(), Error> {
+        let item = match self.pending_move.take() {
+            Some(item) => item,
+            None => {
+                if let Some(item) = self.selected_item() {
+                    self.status = format!(
+                        "Marked '{}', open the destination folder and press m again",
+                        item.name
+                    );
+                    self.pending_move = Some(item);
+                }
+                return Ok(());
+            }
+        };
+
+        let new_parent_id = self
+            .current_folder_id
+            .clone()
+            .unwrap_or_else(|| "root".to_string());
+        let old_parent_id = match &item.parents[..] {
+            [parent_id] => parent_id.clone(),
+            [] => {
+                self.status = "Cannot move an item without parents".to_string();
+                return Ok(());
+            }
+            _ => {
+                self.status = "Cannot move an item with multiple parents".to_string();
+                return Ok(());
+            }
+        };
+        if old_parent_id == new_parent_id || item.id == new_parent_id {
+            self.status = "Move cancelled".to_string();
+            return Ok(());
+        }
+
+        let config = mv::ChangeParentConfig {
+            file_id: item.id.clone(),
+            old_parent_id,
+            new_parent_id,
+        };
+        match handle.block_on(mv::change_parent(
+            &self.hub,
+            UploadDelegateConfig::default(),
+            &config,
+        )) {
+            Ok(_) => {
+                self.history.push(UndoEntry::Move {
+                    file_id: config.file_id,
+                    name: item.name.clone(),
+                    old_parent_id: config.old_parent_id,
+                    new_parent_id: config.new_parent_id,
+                });
+                self.reload(handle)?;
+                self.status = format!("Moved '{}' to '{}'", item.name, self.current_folder_name);
+            }
+            Err(err) => {
+                self.status = format!("Move failed: {}", Error::Move(err));
+            }
+        }
+        Ok(())
+    }
+
+    fn start_drive_select(&mut self, handle: &Handle) {
+        let drives = handle.block_on(drives::list::list_drives(
+            &self.hub,
+            UploadDelegateConfig::default(),
+        ));
+        let drives = match drives {
+            Ok(drives) => drives,
+            Err(err) => {
+                self.status = format!("Error: {}", Error::ListDrives(err));
+                return;
+            }
+        };
+
+        let mut options = vec![SelectOption {
+            label: "My Drive".to_string(),
+            value: None,
+        }];
+        options.extend(drives.into_iter().map(|drive| SelectOption {
+            label: drive.name.unwrap_or_default(),
+            value: drive.id,
+        }));
+
+        self.select = Some(SelectPopup {
+            title: "Switch drive".to_string(),
+            kind: SelectKind::Drive,
+            options,
+            selected: 0,
+        });
+        self.input_mode = InputMode::Select;
+    }
+
+    fn switch_drive(&mut self, handle: &Handle, option: SelectOption) -> Result<(), Error> {
+        self.folder_stack.clear();
+        self.pending_move = None;
+        self.current_folder_id = option.value;
+        self.current_folder_name = option.label;
+        self.reload(handle)
+    }
+
+    fn start_account_select(&mut self) {
+        if self.has_active_transfer() {
+            self.status = "Wait for active transfers before switching account".to_string();
+            return;
+        }
+        let accounts = match app_config::list_accounts() {
+            Ok(accounts) => accounts,
+            Err(err) => {
+                self.status = format!("Error: {}", err);
+                return;
+            }
+        };
+        let current = AppConfig::load_current_account()
+            .map(|config| config.account.name)
+            .unwrap_or_default();
+
+        let options: Vec<SelectOption> = accounts
+            .into_iter()
+            .map(|name| SelectOption {
+                label: if name == current {
+                    format!("{} (current)", name)
+                } else {
+                    name.clone()
+                },
+                value: Some(name),
+            })
+            .collect();
+        let selected = options
+            .iter()
+            .position(|option| option.value.as_deref() == Some(current.as_str()))
+            .unwrap_or(0);
+
+        self.select = Some(SelectPopup {
+            title: "Switch account".to_string(),
+            kind: SelectKind::Account,
+            options,
+            selected,
+        });
+        self.input_mode = InputMode::Select;
+    }
+
+    // Switches the current account like `gdrive account switch`, since transfers
+    // started from the TUI load the hub of the current account
+    fn switch_account(&mut self, handle: &Handle, option: SelectOption) -> Result<(), Error> {
+        let account_name = match option.value {
+            Some(name) => name,
+            None => return Ok(()),
+        };
+        let app_cfg = AppConfig::load_account(&account_name).map_err(Error::AppConfig)?;
+        app_config::switch_account(&app_cfg).map_err(Error::AppConfig)?;
+        // Built for the account by name, the account setting would otherwise keep
+        // the hub on the pinned account
+        let hub = handle
+            .block_on(hub_helper::get_hub_for_account(&account_name))
+            .map_err(Error::Hub)?;
+        self.hub = Arc::new(hub);
+        hub_helper::set_shared_hub(self.hub.clone());
+        self.sync_pairs = load_sync_pairs();
+        // The ids in the history belong to the previous account
+        self.history.clear();
+
+        self.folder_stack.clear();
+        self.pending_move = None;
+        self.current_folder_id = None;
+        self.current_folder_name = "root".to_string();
+        self.reload(handle)?;
+        self.status = format!("Switched to account '{}'", account_name);
         Ok(())
     }
 
@@ -806,22 +2549,320 @@ impl App {
             return Ok(());
         }
 
+        self.open_folder(handle, item.id, item.name)
+    }
+
+    fn open_folder(&mut self, handle: &Handle, id: String, name: String) -> Result<(), Error> {
         let previous = FolderState {
             id: self.current_folder_id.clone(),
             name: self.current_folder_name.clone(),
         };
         self.folder_stack.push(previous);
-        self.current_folder_id = Some(item.id);
-        self.current_folder_name = item.name;
+        self.current_folder_id = Some(id);
+        self.current_folder_name = name;
         self.reload(handle)
     }
 
-    fn delete_item(&mut self, handle: &Handle, item: DriveItem) -> Result<(), Error> {
-        let config = files::delete::Config {
+    // Folders are opened, files are selected in their parent folder so they
+    // can be downloaded right away
+    fn go_to(&mut self, handle: &Handle, input: &str) -> Result<(), Error> {
+        let id = match drive_url::extract_id(input) {
+            Some(id) => id,
+            None => {
+                self.status = format!("'{}' is not a Drive url or id", input);
+                return Ok(());
+            }
+        };
+        let file = match handle.block_on(files::info::get_file(&self.hub, &id)) {
+            Ok(file) => file,
+            Err(err) => {
+                self.status = format!("Go to failed: {}", err);
+                return Ok(());
+            }
+        };
+        let name = file.name.clone().unwrap_or_else(|| "<unnamed>".to_string());
+
+        if drive_file::is_directory(&file) {
+            return self.open_folder(handle, id, name);
+        }
+
+        self.open_containing_folder(handle, &file)
+    }
+
+    // Opens the folder that contains the file with the file selected. The
+    // folders above it are looked up, so going back walks up its path instead
+    // of returning to where the user came from
+    fn open_containing_folder(
+        &mut self,
+        handle: &Handle,
+        file: &google_drive3::api::File,
+    ) -> Result<(), Error> {
+        let id = file.id.clone().unwrap_or_default();
+        let name = file.name.clone().unwrap_or_else(|| "<unnamed>".to_string());
+
+        let ancestors = match handle.block_on(path_resolver::ancestors(&self.hub, file)) {
+            Ok(ancestors) => ancestors,
+            Err(err) => {
+                self.status = format!("Failed to find the folder of '{}': {}", name, err);
+                return Ok(());
+            }
+        };
+        let (parent, above) = match ancestors.split_last() {
+            Some(split) => split,
+            None => {
+                self.status = format!("'{}' is not in a folder you have access to", name);
+                return Ok(());
+            }
+        };
+
+        let mut folder_stack: Vec<FolderState> = above.iter().map(folder_state).collect();
+        // Folders whose top isn't a drive root, i.e. shared with the user, go
+        // back to My Drive at the end
+        let starts_at_root = ancestors
+            .first()
+            .map(|top| top.parents.is_none())
+            .unwrap_or(false);
+        if !starts_at_root {
+            folder_stack.insert(0, my_drive_root());
+        }
+
+        let parent = folder_state(parent);
+        let path: Vec<&str> = folder_stack
+            .iter()
+            .chain(std::iter::once(&parent))
+            .filter(|folder| folder.id.is_some())
+            .map(|folder| folder.name.as_str())
+            .collect();
+        let path = format!("/{}", path.join("/"));
+
+        self.folder_stack = folder_stack;
+        self.pending_move = None;
+        self.current_folder_id = parent.id;
+        self.current_folder_name = parent.name;
+        self.reload(handle)?;
+
+        match self.items.iter().position(|item| item.id == id) {
+            Some(index) => {
+                self.selected = index;
+                self.status = format!(
+                    "Selected '{}' in {}, press {} to download it",
+                    name,
+                    path,
+                    self.keymap.format_keys(Action::Download)
+                );
+            }
+            None => self.status = format!("Opened {}, the folder of '{}'", path, name),
+        }
+        Ok(())
+    }
+
+    // Searches Drive, and falls back to the index written by `gdrive files index`
+    // when Drive fails or doesn't answer within SEARCH_TIMEOUT
+    fn start_search_results(&mut self, handle: &Handle, query: &str) {
+        let drive_search = handle.block_on(tokio::time::timeout(
+            SEARCH_TIMEOUT,
+            files::list::list_files(
+                &self.hub,
+                &ListFilesConfig {
+                    query: ListQuery::NameContains {
+                        pattern: query.to_string(),
+                    },
+                    order_by: ListSortOrder::default(),
+                    scope: ListScope::default(),
+                    max_files: SEARCH_LIMIT,
+                },
+            ),
+        ));
+
+        let drive_error = match drive_search {
+            Ok(Ok(files)) => {
+                let options = files
+                    .into_iter()
+                    .map(|file| SelectOption {
+                        label: search_result_label(
+                            file.name.clone().unwrap_or_default(),
+                            drive_file::is_directory(&file),
+                            None,
+                        ),
+                        value: file.id,
+                    })
+                    .collect();
+                let title = format!("Search results for '{}'", query);
+                self.show_search_results(query, title, options);
+                return;
+            }
+            Ok(Err(err)) => err.to_string(),
+            Err(_) => format!("no answer within {} seconds", SEARCH_TIMEOUT.as_secs()),
+        };
+
+        let index_search = files::index::default_db_path().and_then(|db_path| {
+            let results = files::index::search(&db_path, query, SEARCH_LIMIT)?;
+            let updated_time = files::index::updated_time(&db_path)?;
+            Ok((results, updated_time))
+        });
+        let (results, updated_time) = match index_search {
+            Ok(search) => search,
+            Err(err) => {
+                self.status = format!("Search failed: {}. {}", drive_error, err);
+                return;
+            }
+        };
+
+        let options = results
+            .into_iter()
+            .map(|file| SelectOption {
+                label: search_result_label(
+                    file.name.clone(),
+                    file.is_directory(),
+                    file.parent_name,
+                ),
+                value: Some(file.id),
+            })
+            .collect();
+        let updated = updated_time
+            .map(|time| format!("updated {}, ", files::info::format_date_time(time)))
+            .unwrap_or_default();
+        let title = format!(
+            "Search results for '{}' from the local index ({}may be out of date)",
+            query, updated
+        );
+        self.show_search_results(query, title, options);
+        if self.input_mode == InputMode::Select {
+            self.status = format!(
+                "Drive search failed ({}), showing the local index",
+                drive_error
+            );
+        }
+    }
+
+    fn show_search_results(&mut self, query: &str, title: String, options: Vec<SelectOption>) {
+        if options.is_empty() {
+            self.status = format!("No files named like '{}'", query);
+            return;
+        }
+
+        self.select = Some(SelectPopup {
+            title,
+            kind: SelectKind::SearchResult,
+            options,
+            selected: 0,
+        });
+        self.input_mode = InputMode::Select;
+    }
+
+    fn open_search_result(&mut self, handle: &Handle, option: SelectOption) -> Result<(), Error> {
+        match option.value {
+            Some(id) => self.go_to(handle, &id),
+            None => Ok(()),
+        }
+    }
+
+    // Unlike opening a result, folders are selected in their parent too
+    fn open_search_result_folder(
+        &mut self,
+        handle: &Handle,
+        option: SelectOption,
+    ) -> Result<(), Error> {
+        let id = match option.value {
+            Some(id) => id,
+            None => return Ok(()),
+        };
+        match handle.block_on(files::info::get_file(&self.hub, &id)) {
+            Ok(file) => self.open_containing_folder(handle, &file),
+            Err(err) => {
+                self.status = format!("Failed to open folder: {}", err);
+                Ok(())
+            }
+        }
+    }
+
+    fn trash_item(
+        &mut self,
+        handle: &Handle,
+        item: DriveItem,
+    ) -> Result<Option<deletion_journal::Error>, Error> {
+        let journal_error = handle
+            .block_on(files::delete::apply_and_record(
+                &self.hub,
+                &item.id,
+                &item.name,
+                &item.parents,
+                Operation::Trash,
+            ))
+            .map_err(Error::Trash)?;
+        self.history.push(UndoEntry::Trash {
             file_id: item.id,
-            delete_directories: item.is_folder,
+            name: item.name,
+        });
+        Ok(journal_error)
+    }
+
+    fn undo_last(&mut self, handle: &Handle) -> Result<(), Error> {
+        // Only removed once it worked, a failed undo can be tried again
+        let entry = match self.history.last() {
+            Some(entry) => entry.clone(),
+            None => {
+                self.status = "Nothing to undo".to_string();
+                return Ok(());
+            }
         };
-        handle.block_on(files::delete(config)).map_err(Error::Delete)
+
+        let result = match &entry {
+            UndoEntry::Trash { file_id, name } => handle
+                .block_on(files::delete::apply_and_record(
+                    &self.hub,
+                    file_id,
+                    name,
+                    &[],
+                    Operation::Untrash,
+                ))
+                .map_err(Error::Trash),
+            UndoEntry::Move {
+                file_id,
+                old_parent_id,
+                new_parent_id,
+                ..
+            } => {
+                let config = mv::ChangeParentConfig {
+                    file_id: file_id.clone(),
+                    old_parent_id: new_parent_id.clone(),
+                    new_parent_id: old_parent_id.clone(),
+                };
+                handle
+                    .block_on(mv::change_parent(
+                        &self.hub,
+                        UploadDelegateConfig::default(),
+                        &config,
+                    ))
+                    .map(|_| None)
+                    .map_err(Error::Move)
+            }
+            UndoEntry::Rename {
+                file_id, old_name, ..
+            } => {
+                let patch_file = PatchFile::new(file_id.clone()).with_name(old_name);
+                handle
+                    .block_on(files::update::update_metadata(
+                        &self.hub,
+                        UploadDelegateConfig::default(),
+                        patch_file,
+                    ))
+                    .map(|_| None)
+                    .map_err(Error::Rename)
+            }
+        };
+
+        match result {
+            Ok(journal_error) => {
+                self.history.pop();
+                self.reload(handle)?;
+                self.status = with_journal_warning(entry.describe(), journal_error);
+            }
+            Err(err) => {
+                self.status = format!("Undo failed: {}", err);
+            }
+        }
+        Ok(())
     }
 
     fn go_back(&mut self, handle: &Handle) -> Result<(), Error> {
@@ -837,15 +2878,26 @@ impl App {
         self.reload(handle)
     }
 
+    // A destination that doesn't exist is created after asking, instead of
+    // failing the download
+    fn download_to(&mut self, handle: &Handle, destination: Option<PathBuf>) -> Result<(), Error> {
+        if let Some(dir) = &destination {
+            if !dir.exists() {
+                self.pending_destination = Some(dir.clone());
+                self.input_mode = InputMode::CreateDestinationConfirm;
+                self.status = format!("'{}' does not exist, create it?", dir.display());
+                return Ok(());
+            }
+            self.remember_local_dirs(|dirs| dirs.download_dir = Some(dir.clone()));
+        }
+        self.download_selected(handle, destination)
+    }
+
     fn download_selected(
         &mut self,
         handle: &Handle,
         destination: Option<PathBuf>,
     ) -> Result<(), Error> {
-        if self.download_job.is_some() {
-            self.status = "Download already in progress".to_string();
-            return Ok(());
-        }
         let item = match self.items.get(self.selected) {
             Some(item) => item.clone(),
             None => {
@@ -862,17 +2914,47 @@ impl App {
             return Ok(());
         }
 
-        let progress = DownloadProgress::new(item.name.clone());
+        let target = TransferTarget {
+            file_id: Some(item.id.clone()),
+            parent_id: self.current_folder_id.clone(),
+            name: item.name.clone(),
+        };
+        self.start_download_job(handle, target, destination)
+    }
+
+    fn start_download_job(
+        &mut self,
+        handle: &Handle,
+        target: TransferTarget,
+        destination: Option<PathBuf>,
+    ) -> Result<(), Error> {
+        if self.download_job.is_some() {
+            self.status = "Download already in progress".to_string();
+            return Ok(());
+        }
+
+        let progress = DownloadProgress::new(target.name.clone());
         let shared_progress = std::sync::Arc::new(std::sync::Mutex::new(progress));
         let cancel = std::sync::Arc::new(AtomicBool::new(false));
         let progress_ref = shared_progress.clone();
-        let file_id = item.id.clone();
-        let destination = destination.clone();
+        let file_id = target.file_id.clone().unwrap_or_default();
+        let kind = TransferKind::Download {
+            destination: destination.clone(),
+        };
+        let paused = std::sync::Arc::new(AtomicBool::new(false));
         let handle = handle.clone();
+        let hub = self.hub.clone();
         let cancel_ref = cancel.clone();
+        let paused_ref = paused.clone();
         let join_handle = std::thread::spawn(move || {
-            let result =
-                handle.block_on(download_with_progress(file_id, destination, progress_ref.clone(), cancel_ref));
+            let result = handle.block_on(download_with_progress(
+                hub,
+                file_id,
+                destination,
+                progress_ref.clone(),
+                cancel_ref,
+                paused_ref,
+            ));
             if let Ok(mut progress) = progress_ref.lock() {
                 progress.done = true;
                 if let Err(err) = result {
@@ -882,30 +2964,61 @@ impl App {
         });
 
         self.download_job = Some(DownloadJob {
+            target,
+            kind,
             progress: shared_progress,
             handle: Some(join_handle),
             cancel,
+            paused,
         });
         self.status = "Download started".to_string();
         Ok(())
     }
 
-    fn start_upload_job(&mut self, handle: &Handle, path: PathBuf) -> Result<(), Error> {
+    // The parent is passed in, a retried upload goes to the folder it was
+    // started in
+    fn start_upload_job(
+        &mut self,
+        handle: &Handle,
+        path: PathBuf,
+        parent_id: Option<String>,
+    ) -> Result<(), Error> {
         if self.upload_job.is_some() {
             self.status = "Upload already in progress".to_string();
             return Ok(());
         }
 
-        let parents = self.current_folder_id.clone().map(|id| vec![id]);
+        let parents = parent_id.clone().map(|id| vec![id]);
+        let kind = TransferKind::Upload { path: path.clone() };
+        let target = TransferTarget {
+            file_id: None,
+            parent_id,
+            name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        };
         let progress = UploadProgress::new();
         let shared_progress = std::sync::Arc::new(std::sync::Mutex::new(progress));
         let cancel = std::sync::Arc::new(AtomicBool::new(false));
         let progress_ref = shared_progress.clone();
+        let paused = std::sync::Arc::new(AtomicBool::new(false));
         let handle = handle.clone();
+        let hub = self.hub.clone();
         let cancel_ref = cancel.clone();
+        let paused_ref = paused.clone();
+        let include_hidden = self.show_hidden;
+        // The upload runs on a thread of its own, a paused upload blocks it
         let join_handle = std::thread::spawn(move || {
-            let result =
-                handle.block_on(upload_with_progress(path, parents, progress_ref.clone(), cancel_ref));
+            let result = handle.block_on(upload_with_progress(
+                hub,
+                path,
+                parents,
+                include_hidden,
+                progress_ref.clone(),
+                cancel_ref,
+                paused_ref,
+            ));
             if let Ok(mut progress) = progress_ref.lock() {
                 progress.done = true;
                 if let Err(err) = result {
@@ -915,9 +3028,12 @@ impl App {
         });
 
         self.upload_job = Some(UploadJob {
+            target,
+            kind,
             progress: shared_progress,
             handle: Some(join_handle),
             cancel,
+            paused,
         });
         self.status = "Upload started".to_string();
         Ok(())
@@ -941,12 +3057,20 @@ impl App {
                     let _ = handle.join();
                 }
                 if let Ok(progress) = job.progress.lock() {
-                    if let Some(error) = progress.error.clone() {
+                    if progress.error.as_deref() == Some(CANCELLED) {
+                        self.status = format!("Upload of {} cancelled", job.target.name);
+                    } else if let Some(error) = progress.error.clone() {
                         self.status = format!("Upload failed: {}", error);
                     } else {
                         self.status = "Upload completed".to_string();
                         refresh_needed = true;
                     }
+                    self.finished_transfers.push(FinishedTransfer {
+                        target: job.target.clone(),
+                        kind: job.kind.clone(),
+                        error: progress.error.clone(),
+                        finished_at: Local::now(),
+                    });
                 }
                 self.upload_job = None;
                 if refresh_needed {
@@ -968,20 +3092,268 @@ impl App {
                     let _ = handle.join();
                 }
                 if let Ok(progress) = job.progress.lock() {
-                    if let Some(error) = progress.error.clone() {
+                    if progress.error.as_deref() == Some(CANCELLED) {
+                        self.status = format!("Download of {} cancelled", job.target.name);
+                    } else if let Some(error) = progress.error.clone() {
                         self.status = format!("Download failed: {}", error);
                     } else {
                         self.status = "Download completed".to_string();
                     }
+                    self.finished_transfers.push(FinishedTransfer {
+                        target: job.target.clone(),
+                        kind: job.kind.clone(),
+                        error: progress.error.clone(),
+                        finished_at: Local::now(),
+                    });
                 }
                 self.download_job = None;
             }
         }
+
+        if self.upload_job.is_none() {
+            if let Some(upload) = self.upload_queue.pop_front() {
+                if let Err(err) =
+                    self.start_upload_job(&Handle::current(), upload.path, upload.parent_id)
+                {
+                    self.status = format!("Upload failed: {}", err);
+                }
+            }
+        }
+    }
+
+    fn transfer_badge(&self, item: &DriveItem) -> Option<TransferBadge> {
+        if item.is_parent {
+            return None;
+        }
+        let folder_id = self.current_folder_id.as_ref();
+
+        if let Some(job) = &self.upload_job {
+            if job.target.matches(item, folder_id) {
+                return self.upload_badge();
+            }
+        }
+
+        if let Some(job) = &self.download_job {
+            if job.target.matches(item, folder_id) {
+                return self.download_badge();
+            }
+        }
+
+        // The latest transfer of the item decides, a retried upload can
+        // succeed after it failed
+        self.finished_transfers
+            .iter()
+            .rev()
+            .find(|transfer| transfer.target.matches(item, folder_id))
+            .map(|transfer| {
+                if transfer.error.is_some() {
+                    TransferBadge::Failed
+                } else {
+                    TransferBadge::Done
+                }
+            })
+    }
+
+    // Folders are counted in files, single files in bytes
+    fn upload_badge(&self) -> Option<TransferBadge> {
+        let job = self.upload_job.as_ref()?;
+        let progress = job.progress.lock().ok()?;
+        let done = match progress.total_files {
+            Some(total_files) if total_files > 1 => percent(progress.done_files, Some(total_files)),
+            _ => percent(progress.current_bytes, progress.total_bytes),
+        };
+        if job.paused.load(Ordering::SeqCst) {
+            return Some(TransferBadge::Paused(done));
+        }
+        Some(TransferBadge::Uploading(done))
+    }
+
+    fn download_badge(&self) -> Option<TransferBadge> {
+        let job = self.download_job.as_ref()?;
+        let progress = job.progress.lock().ok()?;
+        let done = percent(progress.current_bytes, progress.total_bytes);
+        if job.paused.load(Ordering::SeqCst) {
+            return Some(TransferBadge::Paused(done));
+        }
+        Some(TransferBadge::Downloading(done))
+    }
+
+    // The name of an upload to the current folder that isn't listed yet
+    fn pending_upload_name(&self) -> Option<String> {
+        let job = self.upload_job.as_ref()?;
+        let in_current_folder = job.target.parent_id == self.current_folder_id;
+        let listed = self.items.iter().any(|item| {
+            !item.is_parent && job.target.matches(item, self.current_folder_id.as_ref())
+        });
+        if in_current_folder && !listed {
+            Some(job.target.name.clone())
+        } else {
+            None
+        }
+    }
+
+    // The upload is listed before the download
+    fn running_transfers(&self) -> Vec<RunningTransfer<'_>> {
+        let mut running = Vec::new();
+        if let Some(job) = &self.upload_job {
+            running.push(RunningTransfer {
+                is_upload: true,
+                target: &job.target,
+                badge: self.upload_badge().unwrap_or(TransferBadge::Uploading(0)),
+                cancel: &job.cancel,
+                paused: &job.paused,
+            });
+        }
+        if let Some(job) = &self.download_job {
+            running.push(RunningTransfer {
+                is_upload: false,
+                target: &job.target,
+                badge: self
+                    .download_badge()
+                    .unwrap_or(TransferBadge::Downloading(0)),
+                cancel: &job.cancel,
+                paused: &job.paused,
+            });
+        }
+        running
+    }
+
+    fn selected_transfer(&self) -> Option<&FinishedTransfer> {
+        let index = self
+            .transfers_selected
+            .checked_sub(self.running_transfers().len())?;
+        self.finished_transfers.iter().rev().nth(index)
+    }
+
+    fn cancel_selected_transfer(&mut self) {
+        let status = match self.running_transfers().get(self.transfers_selected) {
+            Some(transfer) => transfer.cancel(),
+            None => "Only running transfers can be cancelled".to_string(),
+        };
+        self.status = status;
+    }
+
+    fn toggle_pause_selected_transfer(&mut self) {
+        let status = match self.running_transfers().get(self.transfers_selected) {
+            Some(transfer) => transfer.toggle_pause(),
+            None => "Only running transfers can be paused".to_string(),
+        };
+        self.status = status;
+    }
+
+    // The transfer of the selected item, or the only one that is running, so
+    // it can be controlled without leaving the folder
+    fn selected_item_transfer(&self) -> Result<RunningTransfer<'_>, &'static str> {
+        let mut running = self.running_transfers();
+        let folder_id = self.current_folder_id.as_ref();
+        let index = self
+            .items
+            .get(self.selected)
+            .filter(|item| !item.is_parent)
+            .and_then(|item| {
+                running
+                    .iter()
+                    .position(|transfer| transfer.target.matches(item, folder_id))
+            });
+
+        match (index, running.len()) {
+            (Some(index), _) => Ok(running.swap_remove(index)),
+            (None, 1) => Ok(running.swap_remove(0)),
+            (None, 0) => Err("No transfer is running"),
+            (None, _) => Err("Select the item of the transfer, or use the transfers panel"),
+        }
+    }
+
+    fn cancel_selected_item_transfer(&mut self) {
+        let status = match self.selected_item_transfer() {
+            Ok(transfer) => transfer.cancel(),
+            Err(status) => status.to_string(),
+        };
+        self.status = status;
+    }
+
+    fn toggle_pause_selected_item_transfer(&mut self) {
+        let status = match self.selected_item_transfer() {
+            Ok(transfer) => transfer.toggle_pause(),
+            Err(status) => status.to_string(),
+        };
+        self.status = status;
+    }
+
+    fn retry_selected_transfer(&mut self, handle: &Handle) -> Result<(), Error> {
+        let transfer = match self.selected_transfer() {
+            Some(transfer) if transfer.error.is_some() => transfer.clone(),
+            Some(_) => {
+                self.status = "Only failed transfers can be retried".to_string();
+                return Ok(());
+            }
+            None => return Ok(()),
+        };
+
+        self.input_mode = InputMode::Normal;
+        match transfer.kind {
+            TransferKind::Upload { path } => {
+                self.start_upload_job(handle, path, transfer.target.parent_id)
+            }
+            TransferKind::Download { destination } => {
+                self.start_download_job(handle, transfer.target, destination)
+            }
+        }
+    }
+
+    // Copied with an OSC 52 escape sequence, which most terminals support and
+    // which also works over ssh
+    fn copy_selected_transfer_error(&mut self) {
+        let error = match self
+            .selected_transfer()
+            .and_then(|transfer| transfer.error.clone())
+        {
+            Some(error) => error,
+            None => {
+                self.status = "The selected transfer has no error".to_string();
+                return;
+            }
+        };
+
+        let encoded = base64::engine::general_purpose::STANDARD.encode(error);
+        let mut stdout = io::stdout();
+        let result = write!(stdout, "\x1b]52;c;{}\x07", encoded).and_then(|_| stdout.flush());
+        self.status = match result {
+            Ok(()) => "Copied error to the clipboard".to_string(),
+            Err(err) => format!("Failed to copy error: {}", err),
+        };
     }
 
     fn render_status(&self) -> String {
         if let Some(job) = &self.upload_job {
+            if job.cancel.load(Ordering::SeqCst) {
+                return format!("Cancelling upload of {}...", job.target.name);
+            }
+            if job.paused.load(Ordering::SeqCst) {
+                return format!("Upload of {} paused", job.target.name);
+            }
             if let Ok(progress) = job.progress.lock() {
+                // Uploads start before the scan is done, the total is shown
+                // with a + until it is known
+                if let (Some(scanned), None) = (progress.scanned, &progress.current_file) {
+                    return format!(
+                        "Scanning... {} files",
+                        files::info::format_count(scanned.files)
+                    );
+                }
+                if progress.stalled.load(Ordering::SeqCst) {
+                    return format!(
+                        "Upload of {} stalled, retrying...",
+                        progress.current_file.as_deref().unwrap_or("<unknown>")
+                    );
+                }
+                if let Some(delay) = progress.retrying {
+                    return format!(
+                        "Upload of {} failed, retrying in {}s",
+                        progress.current_file.as_deref().unwrap_or("<unknown>"),
+                        delay.as_secs().max(1)
+                    );
+                }
                 if let Some(total_files) = progress.total_files {
                     let current = progress
                         .current_file
@@ -991,24 +3363,23 @@ impl App {
                         format!(
                             "{} ({}/{})",
                             current,
-                            human_bytes(progress.current_bytes as f64),
-                            human_bytes(total_bytes as f64)
+                            format_size(progress.current_bytes),
+                            format_size(total_bytes)
                         )
                     } else {
                         current
                     };
+                    let more = if progress.scanned.is_some() { "+" } else { "" };
                     return format!(
-                        "Uploading {} [{}/{}]",
-                        file_info,
-                        progress.done_files,
-                        total_files
+                        "Uploading {} [{}/{}{}]",
+                        file_info, progress.done_files, total_files, more
                     );
                 }
                 if let Some(total_bytes) = progress.total_bytes {
                     return format!(
                         "Uploading ({}/{})",
-                        human_bytes(progress.current_bytes as f64),
-                        human_bytes(total_bytes as f64)
+                        format_size(progress.current_bytes),
+                        format_size(total_bytes)
                     );
                 }
                 return "Uploading...".to_string();
@@ -1016,6 +3387,12 @@ impl App {
         }
 
         if let Some(job) = &self.download_job {
+            if job.cancel.load(Ordering::SeqCst) {
+                return format!("Cancelling download of {}...", job.target.name);
+            }
+            if job.paused.load(Ordering::SeqCst) {
+                return format!("Download of {} paused", job.target.name);
+            }
             if let Ok(progress) = job.progress.lock() {
                 let total = progress.total_bytes;
                 let current = progress.current_bytes;
@@ -1023,11 +3400,11 @@ impl App {
                     return format!(
                         "Downloading {} ({}/{})",
                         progress.file_name,
-                        human_bytes(current as f64),
-                        human_bytes(total_bytes as f64)
+                        format_size(current),
+                        format_size(total_bytes)
                     );
                 }
-                return format!("Downloading {} ({})", progress.file_name, human_bytes(current as f64));
+                return format!("Downloading {} ({})", progress.file_name, format_size(current));
             }
         }
         self.status.clone()
@@ -1035,32 +3412,137 @@ impl App {
 }
 
 impl UploadPicker {
-    fn from_dir(path: PathBuf) -> Result<Self, io::Error> {
-        let entries = list_local_entries(&path)?;
-        Ok(Self {
+    fn from_dir(path: PathBuf, show_hidden: bool) -> Result<Self, io::Error> {
+        let mut picker = Self {
             current_dir: path,
-            entries,
+            all_entries: vec![],
+            entries: vec![],
             selected: 0,
             selected_path: None,
-        })
+            show_hidden,
+            sort: PickerSort::Name,
+            filter: String::new(),
+            filtering: false,
+        };
+        picker.reload()?;
+        Ok(picker)
+    }
+
+    // The filter is for one directory, it is cleared when another one is opened
+    fn open(&mut self, path: PathBuf) -> Result<(), io::Error> {
+        self.current_dir = path;
+        self.filter.clear();
+        self.filtering = false;
+        self.reload()
     }
 
     fn reload(&mut self) -> Result<(), io::Error> {
-        self.entries = list_local_entries(&self.current_dir)?;
-        self.selected = 0;
+        self.all_entries = list_local_entries(&self.current_dir, self.show_hidden)?;
+        self.apply_view();
         Ok(())
     }
+
+    // Sorts and filters the entries again, after the sort or the filter changed
+    fn apply_view(&mut self) {
+        let filter = self.filter.to_lowercase();
+        self.entries = self
+            .all_entries
+            .iter()
+            .filter(|entry| entry.is_parent || entry.name.to_lowercase().contains(&filter))
+            .cloned()
+            .collect();
+        sort_local_entries(&mut self.entries, self.sort);
+        self.selected = 0;
+    }
+}
+
+// Above a drive root on Windows is the list of drives, represented by an empty path
+// Set with colors = false in config.toml, for terminals with themes that make
+// some of the colors unreadable. Bold text still marks the selection
+fn remove_colors(buffer: &mut ratatui::buffer::Buffer) {
+    for cell in buffer.content.iter_mut() {
+        cell.fg = Color::Reset;
+        cell.bg = Color::Reset;
+    }
+}
+
+// Transfer progress, in the unit of the display settings like the size column
+fn format_size(bytes: u64) -> String {
+    number_format::format_bytes(bytes, number_format::current())
+}
+
+// The root of My Drive is shown as root, like when navigate starts
+fn my_drive_root() -> FolderState {
+    FolderState {
+        id: None,
+        name: "root".to_string(),
+    }
+}
+
+// Folders without parents are drive roots, shared drives keep their id since
+// that is how they are listed
+fn folder_state(folder: &google_drive3::api::File) -> FolderState {
+    if folder.parents.is_none() && folder.drive_id.is_none() {
+        return my_drive_root();
+    }
+
+    FolderState {
+        id: folder.id.clone(),
+        name: folder
+            .name
+            .clone()
+            .unwrap_or_else(|| "<unnamed>".to_string()),
+    }
+}
+
+fn search_result_label(name: String, is_folder: bool, parent_name: Option<String>) -> String {
+    let name = if is_folder {
+        format!("[DIR] {}", name)
+    } else {
+        name
+    };
+    match parent_name {
+        Some(parent_name) => format!("{}  (in {})", name, parent_name),
+        None => name,
+    }
 }
 
-fn list_local_entries(path: &PathBuf) -> Result<Vec<LocalEntry>, io::Error> {
+fn picker_parent(path: &PathBuf) -> Option<PathBuf> {
+    local_path::parent(path)
+        .or_else(|| (cfg!(windows) && !path.as_os_str().is_empty()).then(PathBuf::new))
+}
+
+fn list_local_entries(path: &PathBuf, show_hidden: bool) -> Result<Vec<LocalEntry>, io::Error> {
+    if path.as_os_str().is_empty() {
+        let drives = local_path::drive_roots()
+            .into_iter()
+            .map(|root| LocalEntry {
+                name: root.display().to_string(),
+                path: root,
+                is_dir: true,
+                is_parent: false,
+                is_hidden: false,
+                size: None,
+                item_count: None,
+                modified: None,
+            })
+            .collect();
+        return Ok(drives);
+    }
+
     let mut entries: Vec<LocalEntry> = vec![];
-    let parent_path = path.parent().map(|p| p.to_path_buf()).unwrap_or_else(|| path.clone());
-    entries.push(LocalEntry {
-        name: "/..".to_string(),
-        path: parent_path,
-        is_dir: true,
-        is_parent: true,
-    });
+    if let Some(parent_path) = picker_parent(path) {
+        entries.push(LocalEntry {
+            name: "/..".to_string(),
+            path: parent_path,
+            is_dir: true,
+            is_parent: true,
+            is_hidden: false,
+            size: None,
+            item_count: None,
+            modified: None,
+        });
+    }
 
     for entry in std::fs::read_dir(path)? {
         let entry = entry?;
@@ -1069,68 +3551,324 @@ fn list_local_entries(path: &PathBuf) -> Result<Vec<LocalEntry>, io::Error> {
             .file_name()
             .to_string_lossy()
             .to_string();
-        let is_dir = entry_path.is_dir();
-        if entry_path.is_file() || is_dir {
-            entries.push(LocalEntry {
-                name,
-                path: entry_path,
-                is_dir,
-                is_parent: false,
-            });
+        let is_hidden = local_path::is_hidden(&entry_path);
+        if is_hidden && !show_hidden {
+            continue;
+        }
+        // Follows symlinks, broken ones are left out
+        let metadata = match std::fs::metadata(&entry_path) {
+            Ok(metadata) if metadata.is_file() || metadata.is_dir() => metadata,
+            _ => continue,
+        };
+        let is_dir = metadata.is_dir();
+        entries.push(LocalEntry {
+            name,
+            size: (!is_dir).then(|| metadata.len()),
+            item_count: if is_dir {
+                count_local_items(&entry_path, show_hidden)
+            } else {
+                None
+            },
+            modified: metadata.modified().ok(),
+            path: entry_path,
+            is_dir,
+            is_parent: false,
+            is_hidden,
+        });
+    }
+
+    sort_local_entries(&mut entries, PickerSort::Name);
+    Ok(entries)
+}
+
+// Directories that can't be read have no count
+fn count_local_items(path: &std::path::Path, show_hidden: bool) -> Option<u64> {
+    let entries = std::fs::read_dir(path).ok()?;
+    let count = entries
+        .flatten()
+        .filter(|entry| show_hidden || !local_path::is_hidden(&entry.path()))
+        .count();
+    Some(count as u64)
+}
+
+// The parent entry comes first and directories before files, like in the Drive
+// listing. Sizes and times are largest and newest first
+fn sort_local_entries(entries: &mut [LocalEntry], sort: PickerSort) {
+    entries.sort_by(|a, b| match (a.is_dir, b.is_dir) {
+        _ if a.is_parent && !b.is_parent => std::cmp::Ordering::Less,
+        _ if b.is_parent && !a.is_parent => std::cmp::Ordering::Greater,
+        (true, false) => std::cmp::Ordering::Less,
+        (false, true) => std::cmp::Ordering::Greater,
+        _ => {
+            let by_name = a.name.to_lowercase().cmp(&b.name.to_lowercase());
+            match sort {
+                PickerSort::Name => by_name,
+                PickerSort::Size => b
+                    .size
+                    .or(b.item_count)
+                    .cmp(&a.size.or(a.item_count))
+                    .then(by_name),
+                PickerSort::Modified => b.modified.cmp(&a.modified).then(by_name),
+            }
         }
+    });
+}
+
+// I.e. `12 items` for a directory and `1.2 MB, 2024-03-09 14:30:00` for a file
+fn local_entry_details(entry: &LocalEntry) -> Option<String> {
+    let mut details = vec![];
+    if let Some(count) = entry.item_count {
+        let unit = if count == 1 { "item" } else { "items" };
+        details.push(format!("{} {}", files::info::format_count(count), unit));
+    }
+    if let Some(size) = entry.size {
+        details.push(format_size(size));
+    }
+    if let Some(modified) = entry.modified {
+        let modified: DateTime<Utc> = DateTime::from(modified);
+        details.push(files::info::format_date_time(modified));
+    }
+
+    if details.is_empty() {
+        None
+    } else {
+        Some(details.join(", "))
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Hub(hub_helper::Error),
+    List(files::list::Error),
+    Download(files::download::Error),
+    Trash(google_drive3::Error),
+    Upload(files::upload::Error),
+    Rename(google_drive3::Error),
+    Mkdir(google_drive3::Error),
+    Move(google_drive3::Error),
+    Share(google_drive3::Error),
+    ListDrives(google_drive3::Error),
+    AppConfig(app_config::Error),
+    Join(tokio::task::JoinError),
+    ReadScript(PathBuf, io::Error),
+    ParseScript(String),
+    StartDir(PathBuf),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::List(err) => write!(f, "{}", err),
+            Error::Download(err) => write!(f, "{}", err),
+            Error::Trash(err) => write!(f, "Failed to update trash state: {}", err),
+            Error::Upload(err) => write!(f, "{}", err),
+            Error::Rename(err) => write!(f, "Failed to rename: {}", err),
+            Error::Mkdir(err) => write!(f, "Failed to create folder: {}", err),
+            Error::Move(err) => write!(f, "Failed to move: {}", err),
+            Error::Share(err) => write!(f, "Failed to share: {}", err),
+            Error::ListDrives(err) => write!(f, "Failed to list drives: {}", err),
+            Error::AppConfig(err) => write!(f, "{}", err),
+            Error::Join(err) => write!(f, "{}", err),
+            Error::ReadScript(path, err) => {
+                write!(f, "Failed to read script '{}': {}", path.display(), err)
+            }
+            Error::ParseScript(err) => write!(f, "Invalid script: {}", err),
+            Error::StartDir(path) => write!(f, "'{}' is not a directory", path.display()),
+        }
+    }
+}
+
+// A journal that couldn't be written is shown in the status line, the TUI has
+// no stderr to warn on
+fn with_journal_warning(status: String, journal_error: Option<deletion_journal::Error>) -> String {
+    match journal_error {
+        Some(err) => format!("{} (Warning: {})", status, err),
+        None => status,
+    }
+}
+
+// Drive folders synced with a local directory, by folder id. Like the journal this
+// is only informational, so a broken sync state just leaves the header plain
+fn load_sync_pairs() -> HashMap<String, PathBuf> {
+    sync::state::pairs()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|state| (state.folder_id, state.local_path))
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+enum DescendantScan {
+    Counting,
+    Done(Descendants),
+    Failed(String),
+}
+
+// A running or finished count for the delete dialog. Dropping it stops the
+// count before its next page, i.e. when the dialog is closed
+struct DeleteScan {
+    state: Arc<std::sync::Mutex<DescendantScan>>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Drop for DeleteScan {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::SeqCst);
+    }
+}
+
+// Counts the folder content in the background so the confirm dialog opens right away
+fn start_descendant_scan(handle: &Handle, hub: Arc<Hub>, folder_id: String) -> DeleteScan {
+    let state = Arc::new(std::sync::Mutex::new(DescendantScan::Counting));
+    let cancel = Arc::new(AtomicBool::new(false));
+    let state_ref = state.clone();
+    let cancel_ref = cancel.clone();
+    let handle = handle.clone();
+    std::thread::spawn(move || {
+        let result = handle
+            .block_on(files::delete::count_descendants(
+                &hub,
+                &folder_id,
+                &cancel_ref,
+            ))
+            .map_err(|err| err.to_string());
+        if let Ok(mut state) = state_ref.lock() {
+            *state = match result {
+                Ok(descendants) => DescendantScan::Done(descendants),
+                Err(err) => DescendantScan::Failed(err),
+            };
+        }
+    });
+    DeleteScan { state, cancel }
+}
+
+// What a transfer is about, so its progress can be shown next to the item.
+// Uploads don't have a file id until they are done and are matched by name
+#[derive(Debug, Clone)]
+struct TransferTarget {
+    file_id: Option<String>,
+    parent_id: Option<String>,
+    name: String,
+}
+
+impl TransferTarget {
+    fn matches(&self, item: &DriveItem, folder_id: Option<&String>) -> bool {
+        match &self.file_id {
+            Some(file_id) => &item.id == file_id,
+            None => self.parent_id.as_ref() == folder_id && self.name == item.name,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TransferBadge {
+    Uploading(u8),
+    Downloading(u8),
+    Paused(u8),
+    Done,
+    Failed,
+}
+
+// What is needed to start a transfer again
+#[derive(Debug, Clone)]
+enum TransferKind {
+    Upload { path: PathBuf },
+    Download { destination: Option<PathBuf> },
+}
+
+// Kept for the session, so the rows keep their badge and errors can be read in
+// the transfers panel after the status line has moved on
+#[derive(Debug, Clone)]
+struct FinishedTransfer {
+    target: TransferTarget,
+    kind: TransferKind,
+    error: Option<String>,
+    finished_at: DateTime<Local>,
+}
+
+// A transfer that is still running, as listed in the transfers panel
+struct RunningTransfer<'a> {
+    is_upload: bool,
+    target: &'a TransferTarget,
+    badge: TransferBadge,
+    cancel: &'a std::sync::Arc<AtomicBool>,
+    paused: &'a std::sync::Arc<AtomicBool>,
+}
+
+impl RunningTransfer<'_> {
+    fn arrow(&self) -> &'static str {
+        if self.is_upload {
+            "↑"
+        } else {
+            "↓"
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        if self.is_upload {
+            "upload"
+        } else {
+            "download"
+        }
+    }
+
+    // The job stops at its next chunk and cleans up after itself, a paused job
+    // is woken up for it. Returns the status to show until it has stopped
+    fn cancel(&self) -> String {
+        self.cancel.store(true, Ordering::SeqCst);
+        self.paused.store(false, Ordering::SeqCst);
+        format!("Cancelling {} of {}...", self.kind(), self.target.name)
+    }
+
+    fn toggle_pause(&self) -> String {
+        self.set_paused(!self.paused.load(Ordering::SeqCst))
     }
 
-    entries.sort_by(|a, b| {
-        if a.is_parent && !b.is_parent {
-            return std::cmp::Ordering::Less;
-        }
-        if b.is_parent && !a.is_parent {
-            return std::cmp::Ordering::Greater;
-        }
-        match (a.is_dir, b.is_dir) {
-            (true, false) => std::cmp::Ordering::Less,
-            (false, true) => std::cmp::Ordering::Greater,
-            _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+    // Uploads stop before their next chunk or file, downloads stop reading
+    // the response and keep the connection
+    fn set_paused(&self, paused: bool) -> String {
+        if self.cancel.load(Ordering::SeqCst) {
+            return format!("The {} is being cancelled", self.kind());
         }
-    });
-
-    Ok(entries)
+        self.paused.store(paused, Ordering::SeqCst);
+        let action = if paused { "Paused" } else { "Resumed" };
+        format!("{} {} of {}", action, self.kind(), self.target.name)
+    }
 }
 
-#[derive(Debug)]
-pub enum Error {
-    Io(io::Error),
-    Hub(hub_helper::Error),
-    List(files::list::Error),
-    Download(files::download::Error),
-    Delete(files::delete::Error),
-    Upload(files::upload::Error),
-    Join(tokio::task::JoinError),
+struct QueuedUpload {
+    path: PathBuf,
+    parent_id: Option<String>,
 }
 
-impl error::Error for Error {}
-
-impl Display for Error {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Error::Io(err) => write!(f, "{}", err),
-            Error::Hub(err) => write!(f, "{}", err),
-            Error::List(err) => write!(f, "{}", err),
-            Error::Download(err) => write!(f, "{}", err),
-            Error::Delete(err) => write!(f, "{}", err),
-            Error::Upload(err) => write!(f, "{}", err),
-            Error::Join(err) => write!(f, "{}", err),
-        }
+fn percent(current: u64, total: Option<u64>) -> u8 {
+    match total {
+        Some(total) if total > 0 => (current.min(total) * 100 / total) as u8,
+        _ => 0,
     }
 }
 
 struct UploadJob {
+    target: TransferTarget,
+    kind: TransferKind,
     progress: std::sync::Arc<std::sync::Mutex<UploadProgress>>,
     handle: Option<std::thread::JoinHandle<()>>,
     cancel: std::sync::Arc<AtomicBool>,
+    paused: std::sync::Arc<AtomicBool>,
 }
 
 struct UploadProgress {
+    // Set while a directory is still being scanned
+    scanned: Option<file_tree::ScanProgress>,
+    // Set by the upload delegate while a stalled upload is restarted
+    stalled: std::sync::Arc<AtomicBool>,
+    // Set by the upload observer while a failed chunk waits for its retry
+    retrying: Option<Duration>,
     current_file: Option<String>,
     current_bytes: u64,
     total_bytes: Option<u64>,
@@ -1143,6 +3881,9 @@ struct UploadProgress {
 impl UploadProgress {
     fn new() -> Self {
         Self {
+            scanned: None,
+            stalled: std::sync::Arc::new(AtomicBool::new(false)),
+            retrying: None,
             current_file: None,
             current_bytes: 0,
             total_bytes: None,
@@ -1155,9 +3896,12 @@ impl UploadProgress {
 }
 
 struct DownloadJob {
+    target: TransferTarget,
+    kind: TransferKind,
     progress: std::sync::Arc<std::sync::Mutex<DownloadProgress>>,
     handle: Option<std::thread::JoinHandle<()>>,
     cancel: std::sync::Arc<AtomicBool>,
+    paused: std::sync::Arc<AtomicBool>,
 }
 
 struct DownloadProgress {
@@ -1205,7 +3949,7 @@ impl<R> ProgressReader<R> {
 impl<R: std::io::Read + std::io::Seek> std::io::Read for ProgressReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         if self.cancel.load(Ordering::SeqCst) {
-            return Err(std::io::Error::new(std::io::ErrorKind::Interrupted, "Cancelled"));
+            return Err(std::io::Error::new(std::io::ErrorKind::Interrupted, CANCELLED));
         }
         let count = self.inner.read(buf)?;
         if count > 0 {
@@ -1221,7 +3965,7 @@ impl<R: std::io::Read + std::io::Seek> std::io::Read for ProgressReader<R> {
 impl<R: std::io::Read + std::io::Seek> std::io::Seek for ProgressReader<R> {
     fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
         if self.cancel.load(Ordering::SeqCst) {
-            return Err(std::io::Error::new(std::io::ErrorKind::Interrupted, "Cancelled"));
+            return Err(std::io::Error::new(std::io::ErrorKind::Interrupted, CANCELLED));
         }
         let new_pos = self.inner.seek(pos)?;
         self.position = new_pos;
@@ -1233,12 +3977,13 @@ impl<R: std::io::Read + std::io::Seek> std::io::Seek for ProgressReader<R> {
 }
 
 async fn download_with_progress(
+    hub: Arc<Hub>,
     file_id: String,
     destination: Option<PathBuf>,
     progress: std::sync::Arc<std::sync::Mutex<DownloadProgress>>,
     cancel: std::sync::Arc<AtomicBool>,
+    paused: std::sync::Arc<AtomicBool>,
 ) -> Result<(), String> {
-    let hub = hub_helper::get_hub().await.map_err(|err| err.to_string())?;
     let file = files::info::get_file(&hub, &file_id)
         .await
         .map_err(|err| err.to_string())?;
@@ -1278,7 +4023,10 @@ async fn download_with_progress(
             .map_err(|err| format!("Failed to canonicalize destination: {}", err))?,
     };
 
-    let file_path = root_path.join(&file_name);
+    // Drive names can contain path separators or characters the platform
+    // doesn't allow, the file is always saved directly in the destination
+    let safe_name = file_name::sanitize(&file_name, file_name::DEFAULT_REPLACEMENT);
+    let file_path = root_path.join(&safe_name);
     if file_path.exists() {
         return Err(format!(
             "File '{}' already exists, delete it or use a different destination",
@@ -1289,43 +4037,92 @@ async fn download_with_progress(
     let body = files::download::download_file(&hub, &file_id)
         .await
         .map_err(|err| err.to_string())?;
+    if cancel.load(Ordering::SeqCst) {
+        return Err(CANCELLED.to_string());
+    }
 
-    save_body_to_file_with_progress(
-        body,
-        &file_path,
-        file.md5_checksum.clone(),
+    let download = Download {
+        hub: &hub,
+        file_id: &file_id,
         progress,
         cancel,
-    )
-        .await
+        paused,
+    };
+    save_body_to_file_with_progress(&download, body, &file_path, file.md5_checksum.clone()).await
+}
+
+// What a download needs besides the response, to show its progress and to
+// continue after a pause
+struct Download<'a> {
+    hub: &'a Hub,
+    file_id: &'a str,
+    progress: std::sync::Arc<std::sync::Mutex<DownloadProgress>>,
+    cancel: std::sync::Arc<AtomicBool>,
+    paused: std::sync::Arc<AtomicBool>,
 }
 
+// The file is written next to the destination and renamed when it is
+// complete, a cancelled or failed download removes it
 async fn save_body_to_file_with_progress(
-    mut body: hyper::Body,
+    download: &Download<'_>,
+    body: hyper::Body,
     file_path: &PathBuf,
     expected_md5: Option<String>,
-    progress: std::sync::Arc<std::sync::Mutex<DownloadProgress>>,
-    cancel: std::sync::Arc<AtomicBool>,
 ) -> Result<(), String> {
     let tmp_file_path = file_path.with_extension("incomplete");
-    let file = std::fs::File::create(&tmp_file_path).map_err(|err| err.to_string())?;
-    let mut writer = Md5Writer::new(file);
+    let result = write_body_with_progress(download, body, &tmp_file_path, expected_md5)
+        .await
+        .and_then(|_| std::fs::rename(&tmp_file_path, file_path).map_err(|err| err.to_string()));
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_file_path);
+    }
+    result
+}
+
+async fn write_body_with_progress(
+    download: &Download<'_>,
+    mut body: hyper::Body,
+    tmp_file_path: &PathBuf,
+    expected_md5: Option<String>,
+) -> Result<(), String> {
+    let file = std::fs::File::create(tmp_file_path).map_err(|err| err.to_string())?;
+    let mut writer = HashWriter::new(file);
     let mut total_written: u64 = 0;
+    let mut resumed = false;
 
-    while let Some(chunk_result) = body.next().await {
-        if cancel.load(Ordering::SeqCst) {
-            let _ = std::fs::remove_file(&tmp_file_path);
-            return Err("Cancelled".to_string());
+    loop {
+        // The response isn't read while paused. When the server has dropped
+        // the connection in the meantime the rest is requested again
+        resumed |= wait_while_paused(&download.paused, &download.cancel).await;
+        if download.cancel.load(Ordering::SeqCst) {
+            return Err(CANCELLED.to_string());
         }
-        let chunk = chunk_result.map_err(|err| err.to_string())?;
+
+        let chunk = match body.next().await {
+            Some(Ok(chunk)) => chunk,
+            Some(Err(_)) if resumed => {
+                body = download
+                    .hub
+                    .get_media_from(download.file_id, total_written)
+                    .await
+                    .map_err(|err| err.to_string())?;
+                resumed = false;
+                continue;
+            }
+            Some(Err(err)) => return Err(err.to_string()),
+            None => break,
+        };
+        resumed = false;
+
         writer.write_all(&chunk).map_err(|err| err.to_string())?;
         total_written = total_written.saturating_add(chunk.len() as u64);
-        if let Ok(mut progress) = progress.lock() {
+        if let Ok(mut progress) = download.progress.lock() {
             progress.current_bytes = total_written;
         }
     }
 
-    let actual_md5 = writer.md5();
+    let actual_md5 = writer.hashes().md5;
     if let Some(expected) = expected_md5 {
         if expected != actual_md5 {
             return Err(format!(
@@ -1335,25 +4132,67 @@ async fn save_body_to_file_with_progress(
         }
     }
 
-    std::fs::rename(&tmp_file_path, &file_path).map_err(|err| err.to_string())
+    Ok(())
+}
+
+// Shows retries of failed chunks in the status line
+struct TransferObserver {
+    progress: std::sync::Arc<std::sync::Mutex<UploadProgress>>,
+}
+
+impl TransferObserver {
+    fn set_retrying(&self, retrying: Option<Duration>) {
+        if let Ok(mut progress) = self.progress.lock() {
+            progress.retrying = retrying;
+        }
+    }
+}
+
+impl UploadObserver for TransferObserver {
+    fn chunk_started(&self, _chunk: &Chunk, _retry: bool) {
+        self.set_retrying(None);
+    }
+
+    fn chunk_completed(&self, _chunk: &Chunk) {
+        self.set_retrying(None);
+    }
+
+    fn retry_scheduled(&self, _attempt: u32, delay: Duration) {
+        self.set_retrying(Some(delay));
+    }
+}
+
+// Returns true when the transfer was paused
+async fn wait_while_paused(paused: &AtomicBool, cancel: &AtomicBool) -> bool {
+    let mut waited = false;
+    while paused.load(Ordering::SeqCst) && !cancel.load(Ordering::SeqCst) {
+        waited = true;
+        tokio::time::sleep(PAUSE_INTERVAL).await;
+    }
+    waited
 }
 
 async fn upload_with_progress(
+    hub: Arc<Hub>,
     path: PathBuf,
     parents: Option<Vec<String>>,
+    include_hidden: bool,
     progress: std::sync::Arc<std::sync::Mutex<UploadProgress>>,
     cancel: std::sync::Arc<AtomicBool>,
+    paused: std::sync::Arc<AtomicBool>,
 ) -> Result<(), String> {
-    let hub = hub_helper::get_hub().await.map_err(|err| err.to_string())?;
     let delegate_config = UploadDelegateConfig {
-        chunk_size: ChunkSize::default(),
-        backoff_config: BackoffConfig {
-            max_retries: 100000,
-            min_sleep: Duration::from_secs(1),
-            max_sleep: Duration::from_secs(60),
-        },
-        print_chunk_errors: false,
-        print_chunk_info: false,
+        chunk_size: settings::current().chunk_size(),
+        backoff_config: settings::current().backoff(),
+        observer: Some(Arc::new(TransferObserver {
+            progress: progress.clone(),
+        })),
+        stalled: progress
+            .lock()
+            .ok()
+            .map(|progress| progress.stalled.clone()),
+        paused: Some(paused.clone()),
+        ..UploadDelegateConfig::default()
     };
 
     if path.is_dir() {
@@ -1361,9 +4200,11 @@ async fn upload_with_progress(
             &hub,
             path,
             parents,
+            include_hidden,
             delegate_config,
             progress,
             cancel,
+            paused,
         )
         .await
     } else {
@@ -1388,7 +4229,7 @@ async fn upload_single_file_with_progress(
     cancel: std::sync::Arc<AtomicBool>,
 ) -> Result<(), String> {
     if cancel.load(Ordering::SeqCst) {
-        return Err("Cancelled".to_string());
+        return Err(CANCELLED.to_string());
     }
     let file = std::fs::File::open(&path).map_err(|err| err.to_string())?;
     let file_info = file_info::FileInfo::from_file(
@@ -1409,10 +4250,12 @@ async fn upload_single_file_with_progress(
         progress.total_files = Some(1);
     }
 
-    let reader = ProgressReader::new(file, progress.clone(), cancel);
+    // Nothing is created on Drive until the upload is complete, an unfinished
+    // resumable session expires on its own
+    let reader = ProgressReader::new(file, progress.clone(), cancel.clone());
     upload::upload_file(hub, reader, None, file_info, delegate_config)
         .await
-        .map_err(|err| err.to_string())?;
+        .map_err(|err| upload_error(err, &cancel))?;
 
     if let Ok(mut progress) = progress.lock() {
         progress.done_files = 1;
@@ -1421,83 +4264,750 @@ async fn upload_single_file_with_progress(
     Ok(())
 }
 
+// Small files are sent in one request, a paused upload waits between files
+// and between the chunks of large files
 async fn upload_directory_with_progress(
     hub: &Hub,
     path: PathBuf,
     parents: Option<Vec<String>>,
+    include_hidden: bool,
     delegate_config: UploadDelegateConfig,
     progress: std::sync::Arc<std::sync::Mutex<UploadProgress>>,
     cancel: std::sync::Arc<AtomicBool>,
+    paused: std::sync::Arc<AtomicBool>,
 ) -> Result<(), String> {
     if cancel.load(Ordering::SeqCst) {
-        return Err("Cancelled".to_string());
+        return Err(CANCELLED.to_string());
     }
     let mut ids = IdGen::new(hub, &delegate_config);
-    let tree = file_tree::FileTree::from_path(&path, &mut ids)
-        .await
-        .map_err(|err| err.to_string())?;
+    let mut root_folder_id = None;
+    let (sender, receiver) = tokio::sync::mpsc::channel(file_tree::WALK_BUFFER);
+    let scan_progress = progress.clone();
 
-    let tree_info = tree.info();
-    if let Ok(mut progress) = progress.lock() {
-        progress.total_files = Some(tree_info.file_count as u64);
-        progress.done_files = 0;
-    }
+    let walk = async {
+        let result = file_tree::walk(&path, include_hidden, &mut ids, sender, &mut |scan| {
+            if let Ok(mut progress) = scan_progress.lock() {
+                progress.scanned = Some(scan);
+                progress.total_files = Some(scan.files);
+            }
+        })
+        .await;
 
-    for folder in tree.folders() {
-        if cancel.load(Ordering::SeqCst) {
-            return Err("Cancelled".to_string());
+        if let Ok(mut progress) = progress.lock() {
+            progress.scanned = None;
         }
-        let folder_parents = folder
-            .parent
-            .as_ref()
-            .map(|p| vec![p.drive_id.clone()])
-            .or_else(|| parents.clone());
-
-        let drive_folder = mkdir::create_directory(
-            hub,
-            &mkdir::Config {
-                id: Some(folder.drive_id.clone()),
-                name: folder.name.clone(),
-                parents: folder_parents,
-                print_only_id: false,
-            },
-            delegate_config.clone(),
-        )
-        .await
-        .map_err(|err| err.to_string())?;
+        result.map_err(|err| err.to_string())
+    };
 
-        let folder_id = drive_folder.id.ok_or("Folder created on drive has no id")?;
-        let file_parents = Some(vec![folder_id.clone()]);
+    let upload = async {
+        let mut receiver = receiver;
 
-        for file in folder.files() {
-            if cancel.load(Ordering::SeqCst) {
-                return Err("Cancelled".to_string());
+        loop {
+            let entries = file_tree::recv_batch(&mut receiver).await;
+            if entries.is_empty() {
+                break;
             }
-            if let Ok(mut progress) = progress.lock() {
-                progress.current_file = Some(file.relative_path().display().to_string());
-                progress.total_bytes = Some(file.size);
-                progress.current_bytes = 0;
+            if cancel.load(Ordering::SeqCst) {
+                return Err(CANCELLED.to_string());
             }
 
-            let os_file = std::fs::File::open(&file.path).map_err(|err| err.to_string())?;
-            let reader = ProgressReader::new(os_file, progress.clone(), cancel.clone());
-            let file_info = file.info(file_parents.clone());
+            let mut folders = vec![];
+            let mut files = vec![];
+            for entry in entries {
+                match entry {
+                    file_tree::Entry::Folder(folder) => folders.push(folder),
+                    file_tree::Entry::File(file) => files.push(file),
+                }
+            }
 
-            upload::upload_file(
+            upload::create_tree_directories(
                 hub,
-                reader,
-                Some(file.drive_id.clone()),
-                file_info,
-                delegate_config.clone(),
+                &folders,
+                &parents,
+                &delegate_config,
+                &mut root_folder_id,
             )
             .await
             .map_err(|err| err.to_string())?;
 
-            if let Ok(mut progress) = progress.lock() {
-                progress.done_files = progress.done_files.saturating_add(1);
+            for file in files {
+                wait_while_paused(&paused, &cancel).await;
+                if cancel.load(Ordering::SeqCst) {
+                    return Err(CANCELLED.to_string());
+                }
+                if let Ok(mut progress) = progress.lock() {
+                    progress.current_file = Some(file.relative_path().display().to_string());
+                    progress.total_bytes = Some(file.size);
+                    progress.current_bytes = 0;
+                    progress.retrying = None;
+                }
+
+                let os_file = std::fs::File::open(&file.path).map_err(|err| err.to_string())?;
+                let reader = ProgressReader::new(os_file, progress.clone(), cancel.clone());
+                let file_info = file.info(Some(vec![file.parent.drive_id.clone()]));
+
+                upload::upload_file(
+                    hub,
+                    reader,
+                    Some(file.drive_id.clone()),
+                    file_info,
+                    delegate_config.clone(),
+                )
+                .await
+                .map_err(|err| upload_error(err, &cancel))?;
+
+                if let Ok(mut progress) = progress.lock() {
+                    progress.done_files = progress.done_files.saturating_add(1);
+                }
             }
         }
+
+        Ok(())
+    };
+
+    // A failed upload drops the receiver, which stops the walk
+    let (walk_result, upload_result) = tokio::join!(walk, upload);
+
+    // Everything that was uploaded is in the folder created for the directory
+    if matches!(&upload_result, Err(err) if err == CANCELLED) {
+        if let Some(folder_id) = root_folder_id {
+            upload::rollback(hub, &folder_id).await.map_err(|err| {
+                format!("Cancelled, but failed to delete the uploaded part: {}", err)
+            })?;
+        }
     }
 
-    Ok(())
+    upload_result?;
+    walk_result
+}
+
+// The upload reads the file through a ProgressReader, which fails when the
+// upload is cancelled
+fn upload_error(err: google_drive3::Error, cancel: &AtomicBool) -> String {
+    if cancel.load(Ordering::SeqCst) {
+        CANCELLED.to_string()
+    } else {
+        err.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{fixture, MockServer};
+    use tokio::runtime::Runtime;
+
+    // The app blocks on the runtime like it does on the ui thread, the mock
+    // server runs on the runtime's workers
+    fn app_with_server(runtime: &Runtime) -> (App, MockServer) {
+        let server = runtime.block_on(MockServer::start());
+        let hub = runtime.block_on(server.hub());
+        (App::new(Arc::new(hub)), server)
+    }
+
+    fn press(app: &mut App, runtime: &Runtime, keys: &str) -> bool {
+        keys.split_whitespace().any(|key| {
+            let key: keymap::Key = key.parse().unwrap();
+            let event = Event::Key(KeyEvent::new(key.code, key.modifiers));
+            handle_event(app, event, runtime.handle()).unwrap()
+        })
+    }
+
+    // Folders, the parent entry and notes.txt, with the default keys
+    fn loaded_app(runtime: &Runtime) -> (App, MockServer) {
+        let (mut app, server) = app_with_server(runtime);
+        app.keymap = Keymap::default();
+        server.respond_fixture("GET", "/drive/v3/files", "files_list.json");
+        app.reload(runtime.handle()).unwrap();
+        (app, server)
+    }
+
+    fn active_upload() -> UploadJob {
+        UploadJob {
+            target: TransferTarget {
+                file_id: None,
+                parent_id: None,
+                name: "big.iso".to_string(),
+            },
+            kind: TransferKind::Upload {
+                path: PathBuf::from("big.iso"),
+            },
+            progress: Arc::new(std::sync::Mutex::new(UploadProgress::new())),
+            handle: None,
+            cancel: Arc::new(AtomicBool::new(false)),
+            paused: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    #[test]
+    fn reload_lists_folders_first() {
+        let runtime = Runtime::new().unwrap();
+        let (mut app, server) = app_with_server(&runtime);
+        server.respond_fixture("GET", "/drive/v3/files", "files_list.json");
+
+        app.reload(runtime.handle()).unwrap();
+
+        let names: Vec<&str> = app.items.iter().map(|item| item.name.as_str()).collect();
+        assert_eq!(names, vec!["/..", "Photos", "notes.txt"]);
+        assert_eq!(app.selected, 0);
+        assert_eq!(
+            server.requests()[0].query_param("q").as_deref(),
+            Some("'root' in parents and trashed = false")
+        );
+    }
+
+    #[test]
+    fn delete_can_be_cancelled() {
+        let runtime = Runtime::new().unwrap();
+        let (mut app, server) = loaded_app(&runtime);
+
+        press(&mut app, &runtime, "Down Down x");
+        assert_eq!(app.input_mode, InputMode::DeleteConfirm);
+        assert_eq!(
+            app.pending_delete.as_ref().map(|item| item.name.as_str()),
+            Some("notes.txt")
+        );
+
+        press(&mut app, &runtime, "n");
+        assert_eq!(app.input_mode, InputMode::Normal);
+        assert!(app.pending_delete.is_none());
+        assert_eq!(app.status, "Delete cancelled");
+        let methods: Vec<String> = server.requests().into_iter().map(|r| r.method).collect();
+        assert_eq!(methods, vec!["GET"]);
+    }
+
+    #[test]
+    fn confirmed_delete_trashes_file() {
+        let runtime = Runtime::new().unwrap();
+        let (mut app, server) = loaded_app(&runtime);
+        server.respond_fixture(
+            "PATCH",
+            "/drive/v3/files/1ZyXwVuTsRqPoNmLkJiHgFeDcBa",
+            "trashed_file.json",
+        );
+
+        press(&mut app, &runtime, "Down Down x y");
+
+        assert_eq!(app.input_mode, InputMode::Normal);
+        assert_eq!(app.status, "Moved to trash (U to undo)");
+        let patch = server
+            .requests()
+            .into_iter()
+            .find(|request| request.method == "PATCH")
+            .unwrap();
+        assert!(patch.body_text().contains("\"trashed\":true"));
+    }
+
+    #[test]
+    fn trashed_items_are_shown_and_restored() {
+        let runtime = Runtime::new().unwrap();
+        let (mut app, server) = loaded_app(&runtime);
+        server.respond_json(
+            "GET",
+            "/drive/v3/files",
+            &serde_json::json!({"files": [
+                {"id": "1TrAsHeDfIlE", "name": "old.txt", "mimeType": "text/plain", "trashed": true}
+            ]}),
+        );
+        server.respond_fixture("PATCH", "/drive/v3/files/1TrAsHeDfIlE", "trashed_file.json");
+
+        press(&mut app, &runtime, "t");
+        let query = server.requests().last().unwrap().query_param("q");
+        assert_eq!(query.as_deref(), Some("'root' in parents"));
+        assert!(app.items[1].trashed);
+
+        press(&mut app, &runtime, "Down T");
+
+        assert_eq!(app.status, "Restored 'old.txt'");
+        let patch = server
+            .requests()
+            .into_iter()
+            .find(|request| request.method == "PATCH")
+            .unwrap();
+        assert!(patch.body_text().contains("\"trashed\":false"));
+    }
+
+    #[test]
+    fn files_that_cannot_be_trashed_are_not_deleted() {
+        let runtime = Runtime::new().unwrap();
+        let (mut app, server) = app_with_server(&runtime);
+        server.respond_json(
+            "GET",
+            "/drive/v3/files",
+            &serde_json::json!({"files": [{
+                "id": "1ShArEdFiLe",
+                "name": "shared.txt",
+                "mimeType": "text/plain",
+                "capabilities": {"canRename": false, "canTrash": false}
+            }]}),
+        );
+        app.reload(runtime.handle()).unwrap();
+
+        press(&mut app, &runtime, "Down x");
+
+        assert_eq!(app.input_mode, InputMode::Normal);
+        assert_eq!(
+            app.status,
+            "Cannot delete 'shared.txt', you don't own it. Press X to remove your access instead"
+        );
+        let methods: Vec<String> = server.requests().into_iter().map(|r| r.method).collect();
+        assert_eq!(methods, vec!["GET"]);
+    }
+
+    #[test]
+    fn access_is_removed_on_the_second_press() {
+        let runtime = Runtime::new().unwrap();
+        let (mut app, server) = loaded_app(&runtime);
+        server.respond_json(
+            "GET",
+            "/drive/v3/files/1ZyXwVuTsRqPoNmLkJiHgFeDcBa",
+            &serde_json::json!({"name": "notes.txt", "ownedByMe": false}),
+        );
+        server.respond_json(
+            "GET",
+            "/drive/v3/about",
+            &serde_json::json!({"user": {"permissionId": "0123456789"}}),
+        );
+        server.respond(
+            "DELETE",
+            "/drive/v3/files/1ZyXwVuTsRqPoNmLkJiHgFeDcBa/permissions/0123456789",
+            204,
+            "",
+        );
+
+        press(&mut app, &runtime, "Down Down X");
+        assert_eq!(
+            app.status,
+            "Press X again to remove your access to 'notes.txt'"
+        );
+        assert!(!server.requests().iter().any(|r| r.method == "DELETE"));
+
+        press(&mut app, &runtime, "X");
+
+        assert_eq!(app.status, "Removed your access to 'notes.txt'");
+        assert!(server.requests().iter().any(|r| r.method == "DELETE"));
+    }
+
+    #[test]
+    fn parent_entry_is_not_deleted() {
+        let runtime = Runtime::new().unwrap();
+        let (mut app, _server) = loaded_app(&runtime);
+
+        press(&mut app, &runtime, "x");
+
+        assert_eq!(app.input_mode, InputMode::Normal);
+        assert_eq!(app.status, "Cannot delete parent entry");
+    }
+
+    #[test]
+    fn upload_picker_opens_and_cancels() {
+        let runtime = Runtime::new().unwrap();
+        let (mut app, _server) = loaded_app(&runtime);
+
+        press(&mut app, &runtime, "u");
+        assert_eq!(app.input_mode, InputMode::UploadPicker);
+        assert!(app.upload_picker.is_some());
+
+        press(&mut app, &runtime, "Esc");
+        assert_eq!(app.input_mode, InputMode::Normal);
+        assert!(app.upload_picker.is_none());
+        assert_eq!(app.status, "Upload cancelled");
+    }
+
+    #[test]
+    fn upload_picker_toggles_hidden_files() {
+        let runtime = Runtime::new().unwrap();
+        let (mut app, _server) = loaded_app(&runtime);
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(".bashrc"), "").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "").unwrap();
+        app.upload_picker = Some(UploadPicker::from_dir(dir.path().to_path_buf(), false).unwrap());
+        app.input_mode = InputMode::UploadPicker;
+        let names = |app: &App| -> Vec<String> {
+            let picker = app.upload_picker.as_ref().unwrap();
+            picker
+                .entries
+                .iter()
+                .filter(|entry| !entry.is_parent)
+                .map(|entry| entry.name.clone())
+                .collect()
+        };
+
+        assert_eq!(names(&app), vec!["notes.txt"]);
+
+        press(&mut app, &runtime, ".");
+        assert_eq!(names(&app), vec![".bashrc", "notes.txt"]);
+        assert!(app.show_hidden);
+    }
+
+    #[test]
+    fn upload_picker_sorts_and_filters() {
+        let runtime = Runtime::new().unwrap();
+        let (mut app, _server) = loaded_app(&runtime);
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("raw")).unwrap();
+        std::fs::write(dir.path().join("raw").join("a.cr2"), "").unwrap();
+        std::fs::write(dir.path().join("big.jpg"), "0123456789").unwrap();
+        std::fs::write(dir.path().join("small.jpg"), "0").unwrap();
+        app.upload_picker = Some(UploadPicker::from_dir(dir.path().to_path_buf(), false).unwrap());
+        app.input_mode = InputMode::UploadPicker;
+        let names = |app: &App| -> Vec<String> {
+            let picker = app.upload_picker.as_ref().unwrap();
+            picker
+                .entries
+                .iter()
+                .filter(|entry| !entry.is_parent)
+                .map(|entry| entry.name.clone())
+                .collect()
+        };
+
+        press(&mut app, &runtime, "o");
+        assert_eq!(names(&app), vec!["raw", "big.jpg", "small.jpg"]);
+        let picker = app.upload_picker.as_ref().unwrap();
+        assert_eq!(picker.entries[1].item_count, Some(1));
+        assert_eq!(picker.entries[2].size, Some(10));
+
+        press(&mut app, &runtime, "/ s m Enter");
+        assert_eq!(names(&app), vec!["small.jpg"]);
+        assert_eq!(app.input_mode, InputMode::UploadPicker);
+    }
+
+    #[test]
+    fn missing_download_destination_asks_to_create_it() {
+        let runtime = Runtime::new().unwrap();
+        let (mut app, _server) = loaded_app(&runtime);
+        let dir = tempfile::tempdir().unwrap();
+        let destination = dir.path().join("exports");
+
+        press(&mut app, &runtime, "Down Down d");
+        app.input.insert_str(&destination.display().to_string());
+        press(&mut app, &runtime, "Enter");
+
+        assert_eq!(app.input_mode, InputMode::CreateDestinationConfirm);
+        assert_eq!(app.pending_destination.as_ref(), Some(&destination));
+        assert!(app.download_job.is_none());
+
+        press(&mut app, &runtime, "n");
+        assert_eq!(app.input_mode, InputMode::Normal);
+        assert_eq!(app.status, "Download cancelled");
+        assert!(!destination.exists());
+    }
+
+    #[test]
+    fn failed_undo_stays_in_the_history() {
+        let runtime = Runtime::new().unwrap();
+        let (mut app, server) = loaded_app(&runtime);
+        server.respond(
+            "PATCH",
+            "/drive/v3/files/1ZyXwVuTsRqPoNmLkJiHgFeDcBa",
+            404,
+            fixture("not_found.json"),
+        );
+        app.history.push(UndoEntry::Trash {
+            file_id: "1ZyXwVuTsRqPoNmLkJiHgFeDcBa".to_string(),
+            name: "notes.txt".to_string(),
+        });
+
+        press(&mut app, &runtime, "U");
+
+        assert!(app.status.starts_with("Undo failed"));
+        assert!(app.history.last().is_some());
+    }
+
+    #[test]
+    fn download_job_uses_the_hub_of_the_app() {
+        let runtime = Runtime::new().unwrap();
+        let (mut app, server) = loaded_app(&runtime);
+        let path = "/drive/v3/files/1ZyXwVuTsRqPoNmLkJiHgFeDcBa";
+        server.respond_fixture("GET", path, "file_metadata.json");
+        server.respond(
+            "GET",
+            &format!("{}?alt=media", path),
+            200,
+            fixture("file_content.txt"),
+        );
+        let dir = tempfile::tempdir().unwrap();
+
+        press(&mut app, &runtime, "Down Down d");
+        app.input.insert_str(&dir.path().display().to_string());
+        press(&mut app, &runtime, "Enter");
+
+        let job = app.download_job.as_mut().unwrap();
+        job.handle.take().unwrap().join().unwrap();
+        assert_eq!(job.progress.lock().unwrap().error, None);
+        assert!(dir.path().join("notes.txt").exists());
+    }
+
+    #[test]
+    fn quit_without_transfers_exits() {
+        let runtime = Runtime::new().unwrap();
+        let (mut app, _server) = loaded_app(&runtime);
+
+        assert!(press(&mut app, &runtime, "q"));
+    }
+
+    #[test]
+    fn quit_with_active_transfer_asks_first() {
+        let runtime = Runtime::new().unwrap();
+        let (mut app, _server) = loaded_app(&runtime);
+        app.upload_job = Some(active_upload());
+
+        assert!(!press(&mut app, &runtime, "q"));
+        assert_eq!(app.input_mode, InputMode::QuitConfirm);
+
+        press(&mut app, &runtime, "n");
+        assert_eq!(app.input_mode, InputMode::Normal);
+        assert_eq!(app.status, "Quit cancelled");
+        assert!(!app.exit_requested);
+
+        press(&mut app, &runtime, "q y");
+        assert!(app.exit_requested);
+        let cancel = &app.upload_job.as_ref().unwrap().cancel;
+        assert!(cancel.load(Ordering::SeqCst));
+        // Waits for the upload to stop before exiting
+        assert!(!app.should_exit());
+    }
+
+    #[test]
+    fn escape_cancels_the_running_upload() {
+        let runtime = Runtime::new().unwrap();
+        let (mut app, _server) = loaded_app(&runtime);
+        app.upload_job = Some(active_upload());
+
+        press(&mut app, &runtime, "Esc");
+        let job = app.upload_job.as_ref().unwrap();
+        assert!(job.cancel.load(Ordering::SeqCst));
+        assert_eq!(app.status, "Cancelling upload of big.iso...");
+        assert_eq!(app.render_status(), "Cancelling upload of big.iso...");
+
+        // The job stops with this error when it sees the flag
+        if let Ok(mut progress) = job.progress.lock() {
+            progress.done = true;
+            progress.error = Some(CANCELLED.to_string());
+        }
+        app.tick();
+
+        assert!(app.upload_job.is_none());
+        assert_eq!(app.status, "Upload of big.iso cancelled");
+        assert_eq!(app.finished_transfers[0].error.as_deref(), Some(CANCELLED));
+    }
+
+    #[test]
+    fn transfers_panel_cancels_only_running_transfers() {
+        let runtime = Runtime::new().unwrap();
+        let (mut app, _server) = loaded_app(&runtime);
+        app.upload_job = Some(active_upload());
+        app.finished_transfers.push(FinishedTransfer {
+            target: TransferTarget {
+                file_id: Some("1ZyXwVuTsRqPoNmLkJiHgFeDcBa".to_string()),
+                parent_id: None,
+                name: "notes.txt".to_string(),
+            },
+            kind: TransferKind::Download { destination: None },
+            error: Some("Connection reset".to_string()),
+            finished_at: Local::now(),
+        });
+
+        press(&mut app, &runtime, "h Down x");
+        let cancel = app.upload_job.as_ref().unwrap().cancel.clone();
+        assert_eq!(app.status, "Only running transfers can be cancelled");
+        assert!(!cancel.load(Ordering::SeqCst));
+
+        press(&mut app, &runtime, "Up x");
+        assert_eq!(app.input_mode, InputMode::Transfers);
+        assert!(cancel.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn escape_without_transfers_does_nothing() {
+        let runtime = Runtime::new().unwrap();
+        let (mut app, _server) = loaded_app(&runtime);
+
+        press(&mut app, &runtime, "Esc");
+
+        assert_eq!(app.input_mode, InputMode::Normal);
+        assert_eq!(app.status, "No transfer is running");
+    }
+
+    #[test]
+    fn control_requests_pause_and_queue_behind_the_upload() {
+        let runtime = Runtime::new().unwrap();
+        let (mut app, _server) = loaded_app(&runtime);
+        app.upload_job = Some(active_upload());
+        let mut request = |line: &str| {
+            let request = control::parse(line).unwrap();
+            control::handle_request(&mut app, runtime.handle(), &request)
+        };
+
+        let paused = request(r#"{"jsonrpc": "2.0", "id": 1, "method": "pause"}"#).unwrap();
+        assert_eq!(paused["status"], "Paused upload of big.iso");
+        let status = request(r#"{"jsonrpc": "2.0", "id": 2, "method": "status"}"#).unwrap();
+        assert_eq!(status["transfers"][0]["state"], "paused");
+        assert_eq!(status["transfers"][0]["kind"], "upload");
+
+        let dir = tempfile::tempdir().unwrap();
+        let line = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "queue_upload",
+            "params": {"path": dir.path()},
+        });
+        let queued = request(&line.to_string()).unwrap();
+        assert_eq!(queued["position"], 1);
+        let resumed = request(
+            r#"{"jsonrpc": "2.0", "id": 4, "method": "resume", "params": {"transfer": "download"}}"#,
+        );
+        assert!(resumed.is_err());
+
+        let queued_status = format!("Queued upload of {}", dir.path().display());
+        assert_eq!(app.status, queued_status);
+        assert_eq!(app.upload_queue[0].parent_id, None);
+        let job = app.upload_job.as_ref().unwrap();
+        assert!(job.paused.load(Ordering::SeqCst));
+    }
+
+    fn download<'a>(hub: &'a Hub, cancel: bool, paused: bool) -> Download<'a> {
+        Download {
+            hub,
+            file_id: "1ZyXwVuTsRqPoNmLkJiHgFeDcBa",
+            progress: Arc::new(std::sync::Mutex::new(DownloadProgress::new(
+                "notes.txt".to_string(),
+            ))),
+            cancel: Arc::new(AtomicBool::new(cancel)),
+            paused: Arc::new(AtomicBool::new(paused)),
+        }
+    }
+
+    #[tokio::test]
+    async fn cancelled_download_removes_incomplete_file() {
+        let server = MockServer::start().await;
+        let hub = server.hub().await;
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("notes.txt");
+
+        let result = save_body_to_file_with_progress(
+            &download(&hub, true, false),
+            hyper::Body::from("hello from drive\n"),
+            &file_path,
+            None,
+        )
+        .await;
+
+        assert_eq!(result, Err(CANCELLED.to_string()));
+        assert!(!file_path.exists());
+        assert!(!file_path.with_extension("incomplete").exists());
+    }
+
+    #[test]
+    fn pause_toggles_and_cancel_wakes_the_job() {
+        let runtime = Runtime::new().unwrap();
+        let (mut app, _server) = loaded_app(&runtime);
+        app.upload_job = Some(active_upload());
+
+        press(&mut app, &runtime, "p");
+        assert_eq!(app.status, "Paused upload of big.iso");
+        assert_eq!(app.render_status(), "Upload of big.iso paused");
+        assert_eq!(app.upload_badge(), Some(TransferBadge::Paused(0)));
+
+        press(&mut app, &runtime, "h p");
+        assert_eq!(app.status, "Resumed upload of big.iso");
+
+        press(&mut app, &runtime, "p x");
+        let job = app.upload_job.as_ref().unwrap();
+        assert!(job.cancel.load(Ordering::SeqCst));
+        assert!(!job.paused.load(Ordering::SeqCst));
+    }
+
+    // The connection of the paused download is gone, the rest of the file is
+    // requested from where it stopped
+    #[tokio::test]
+    async fn paused_download_continues_with_a_range() {
+        let server = MockServer::start().await;
+        let path = "/drive/v3/files/1ZyXwVuTsRqPoNmLkJiHgFeDcBa";
+        server.respond(
+            "GET",
+            &format!("{}?alt=media", path),
+            206,
+            fixture("file_content.txt"),
+        );
+        let hub = server.hub().await;
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("notes.txt");
+
+        let (sender, body) = hyper::Body::channel();
+        sender.abort();
+        let download = download(&hub, false, true);
+        let paused = download.paused.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            paused.store(false, Ordering::SeqCst);
+        });
+
+        save_body_to_file_with_progress(
+            &download,
+            body,
+            &file_path,
+            Some("1a104aeed98e8c1ec57e45fbb082717c".to_string()),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&file_path).unwrap(),
+            "hello from drive\n"
+        );
+        assert_eq!(server.requests()[0].headers["range"], "bytes=0-");
+    }
+
+    #[tokio::test]
+    async fn download_stays_in_the_destination() {
+        let server = MockServer::start().await;
+        let path = "/drive/v3/files/1ZyXwVuTsRqPoNmLkJiHgFeDcBa";
+        server.respond_json(
+            "GET",
+            path,
+            &serde_json::json!({
+                "id": "1ZyXwVuTsRqPoNmLkJiHgFeDcBa",
+                "name": "../../notes.txt",
+                "mimeType": "text/plain"
+            }),
+        );
+        server.respond(
+            "GET",
+            &format!("{}?alt=media", path),
+            200,
+            fixture("file_content.txt"),
+        );
+        let dir = tempfile::tempdir().unwrap();
+
+        download_with_progress(
+            Arc::new(server.hub().await),
+            "1ZyXwVuTsRqPoNmLkJiHgFeDcBa".to_string(),
+            Some(dir.path().to_path_buf()),
+            Arc::new(std::sync::Mutex::new(DownloadProgress::new(String::new()))),
+            Arc::new(AtomicBool::new(false)),
+            Arc::new(AtomicBool::new(false)),
+        )
+        .await
+        .unwrap();
+
+        let names: Vec<String> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(names, vec![file_name::sanitize("../../notes.txt", "_")]);
+    }
+
+    #[test]
+    fn script_renders_screen() {
+        let runtime = Runtime::new().unwrap();
+        let (mut app, _server) = loaded_app(&runtime);
+
+        press(&mut app, &runtime, "Down");
+        let screen = script::render(&app).unwrap();
+
+        assert!(screen.contains("Photos"));
+        assert!(screen.contains("notes.txt"));
+        assert_eq!(screen.lines().count(), script::SCREEN_HEIGHT as usize);
+    }
 }
@@ -0,0 +1,433 @@
+// A unix socket in the account directory that lets scripts and status bar
+// widgets query and control the transfers of a running `gdrive navigate`.
+// Requests are JSON-RPC 2.0 objects, one per line, and each gets a response
+// line, requests without an id don't:
+//
+//     $ echo '{"jsonrpc": "2.0", "id": 1, "method": "status"}' | nc -U navigate.sock
+//     {"id":1,"jsonrpc":"2.0","result":{"finished":[],"queued":[],"transfers":[...]}}
+//
+// Methods:
+//     status
+//     pause, resume, cancel       {"transfer": "upload" | "download"}, optional
+//                                 when only one transfer is running
+//     queue_upload                {"path": "/absolute/path", "parent": "<folder id>"},
+//                                 the parent defaults to the folder that is shown
+use super::{App, QueuedUpload, RunningTransfer, TransferBadge, TransferKind};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use tokio::runtime::Handle;
+use tokio::sync::oneshot;
+
+#[cfg(unix)]
+use std::path::Path;
+#[cfg(unix)]
+use std::sync::mpsc;
+#[cfg(unix)]
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+
+pub const SOCKET_NAME: &str = "navigate.sock";
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+// Requests that are valid but can't be done right now, i.e. pausing without a transfer
+const REJECTED: i64 = -32000;
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> RpcError {
+        RpcError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+// A request waiting for the ui thread, which owns the app. The reply is the
+// whole response
+pub struct Call {
+    request: Request,
+    reply: oneshot::Sender<Value>,
+}
+
+pub fn parse(line: &str) -> Result<Request, RpcError> {
+    let value: Value =
+        serde_json::from_str(line).map_err(|err| RpcError::new(PARSE_ERROR, err.to_string()))?;
+    if value.get("jsonrpc") != Some(&json!("2.0")) {
+        return Err(RpcError::new(INVALID_REQUEST, "jsonrpc must be \"2.0\""));
+    }
+    serde_json::from_value(value).map_err(|err| RpcError::new(INVALID_REQUEST, err.to_string()))
+}
+
+pub fn response(id: Option<Value>, result: Result<Value, RpcError>) -> Value {
+    let id = id.unwrap_or(Value::Null);
+    match result {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err(err) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": {"code": err.code, "message": err.message},
+        }),
+    }
+}
+
+// Called from the ui loop for each request that came in since the last round
+pub fn answer(app: &mut App, handle: &Handle, call: Call) {
+    let result = handle_request(app, handle, &call.request);
+    let _ = call.reply.send(response(call.request.id, result));
+}
+
+pub fn handle_request(
+    app: &mut App,
+    handle: &Handle,
+    request: &Request,
+) -> Result<Value, RpcError> {
+    match request.method.as_str() {
+        "status" => Ok(status(app)),
+        "pause" => control_transfer(app, &request.params, |transfer| transfer.set_paused(true)),
+        "resume" => control_transfer(app, &request.params, |transfer| transfer.set_paused(false)),
+        "cancel" => control_transfer(app, &request.params, |transfer| transfer.cancel()),
+        "queue_upload" => queue_upload(app, handle, &request.params),
+        method => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("Unknown method '{}'", method),
+        )),
+    }
+}
+
+fn status(app: &App) -> Value {
+    let transfers: Vec<Value> = app
+        .running_transfers()
+        .iter()
+        .map(|transfer| {
+            let percent = match transfer.badge {
+                TransferBadge::Uploading(percent)
+                | TransferBadge::Downloading(percent)
+                | TransferBadge::Paused(percent) => percent,
+                TransferBadge::Done | TransferBadge::Failed => 100,
+            };
+            let state = if transfer.cancel.load(Ordering::SeqCst) {
+                "cancelling"
+            } else if transfer.paused.load(Ordering::SeqCst) {
+                "paused"
+            } else {
+                "running"
+            };
+            json!({
+                "kind": transfer.kind(),
+                "name": transfer.target.name,
+                "state": state,
+                "percent": percent,
+            })
+        })
+        .collect();
+
+    let queued: Vec<Value> = app
+        .upload_queue
+        .iter()
+        .map(
+            |upload| json!({"path": upload.path.display().to_string(), "parent": upload.parent_id}),
+        )
+        .collect();
+
+    let finished: Vec<Value> = app
+        .finished_transfers
+        .iter()
+        .map(|transfer| {
+            let kind = match transfer.kind {
+                TransferKind::Upload { .. } => "upload",
+                TransferKind::Download { .. } => "download",
+            };
+            json!({
+                "kind": kind,
+                "name": transfer.target.name,
+                "error": transfer.error,
+                "finished_at": transfer.finished_at.to_rfc3339(),
+            })
+        })
+        .collect();
+
+    json!({"transfers": transfers, "queued": queued, "finished": finished})
+}
+
+// The transfer is picked by its kind, which can be left out when only one is
+// running. The status of the action is shown in the app as well
+fn control_transfer(
+    app: &mut App,
+    params: &Value,
+    action: impl Fn(&RunningTransfer<'_>) -> String,
+) -> Result<Value, RpcError> {
+    let kind = optional_string(params, "transfer")?;
+    let status = {
+        let running = app.running_transfers();
+        let transfer = match kind.as_deref() {
+            Some(kind @ ("upload" | "download")) => running
+                .iter()
+                .find(|transfer| transfer.kind() == kind)
+                .ok_or_else(|| RpcError::new(REJECTED, format!("No {} is running", kind)))?,
+            Some(kind) => {
+                return Err(RpcError::new(
+                    INVALID_PARAMS,
+                    format!("Unknown transfer '{}', expected upload or download", kind),
+                ))
+            }
+            None => match running.as_slice() {
+                [transfer] => transfer,
+                [] => return Err(RpcError::new(REJECTED, "No transfer is running")),
+                _ => return Err(RpcError::new(
+                    INVALID_PARAMS,
+                    "Several transfers are running, pass \"transfer\": \"upload\" or \"download\"",
+                )),
+            },
+        };
+        action(transfer)
+    };
+
+    app.status = status.clone();
+    Ok(json!({ "status": status }))
+}
+
+// Starts the upload right away when none is running, otherwise it starts when
+// the ones before it are done. The position is 0 for a started upload
+fn queue_upload(app: &mut App, handle: &Handle, params: &Value) -> Result<Value, RpcError> {
+    let path = optional_string(params, "path")?
+        .map(PathBuf::from)
+        .ok_or_else(|| RpcError::new(INVALID_PARAMS, "Missing \"path\""))?;
+    // The app doesn't run in the directory of the caller
+    if !path.is_absolute() {
+        return Err(RpcError::new(INVALID_PARAMS, "The path must be absolute"));
+    }
+    if !path.exists() {
+        return Err(RpcError::new(
+            INVALID_PARAMS,
+            format!("'{}' does not exist", path.display()),
+        ));
+    }
+    let parent_id = optional_string(params, "parent")?.or_else(|| app.current_folder_id.clone());
+
+    let upload = QueuedUpload { path, parent_id };
+    if app.upload_job.is_none() && app.upload_queue.is_empty() {
+        app.start_upload_job(handle, upload.path, upload.parent_id)
+            .map_err(|err| RpcError::new(REJECTED, err.to_string()))?;
+        return Ok(json!({"status": app.status, "position": 0}));
+    }
+
+    app.status = format!("Queued upload of {}", upload.path.display());
+    app.upload_queue.push_back(upload);
+    Ok(json!({"status": app.status, "position": app.upload_queue.len()}))
+}
+
+fn optional_string(params: &Value, name: &str) -> Result<Option<String>, RpcError> {
+    match params.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(value)) => Ok(Some(value.clone())),
+        Some(_) => Err(RpcError::new(
+            INVALID_PARAMS,
+            format!("\"{}\" must be a string", name),
+        )),
+    }
+}
+
+// Listens while it is alive and removes the socket file when it is dropped
+#[cfg(unix)]
+pub struct Socket {
+    path: PathBuf,
+    server: tokio::task::JoinHandle<()>,
+}
+
+#[cfg(unix)]
+impl Drop for Socket {
+    fn drop(&mut self) {
+        self.server.abort();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+// A socket that is left over from a crashed app is replaced, one that still
+// answers belongs to another app and is kept. The socket is bound in a private
+// directory and only moved into place once it is 0600, so other users can't
+// connect in between
+#[cfg(unix)]
+pub fn open(handle: &Handle, path: &Path, calls: mpsc::Sender<Call>) -> Result<Socket, String> {
+    if std::os::unix::net::UnixStream::connect(path).is_ok() {
+        return Err("another gdrive navigate is using it".to_string());
+    }
+    if path.exists() {
+        std::fs::remove_file(path).map_err(|err| err.to_string())?;
+    }
+
+    let parent = path.parent().unwrap_or(Path::new("."));
+    let private_dir = tempfile::Builder::new()
+        .prefix(".navigate-")
+        .tempdir_in(parent)
+        .map_err(|err| err.to_string())?;
+    let private_path = private_dir.path().join(SOCKET_NAME);
+
+    let _guard = handle.enter();
+    let listener = UnixListener::bind(&private_path).map_err(|err| err.to_string())?;
+    crate::app_config::set_file_permissions(&private_path).map_err(|err| err.to_string())?;
+    std::fs::rename(&private_path, path).map_err(|err| err.to_string())?;
+    let server = handle.spawn(serve(listener, calls));
+
+    Ok(Socket {
+        path: path.to_path_buf(),
+        server,
+    })
+}
+
+#[cfg(unix)]
+async fn serve(listener: UnixListener, calls: mpsc::Sender<Call>) {
+    while let Ok((stream, _)) = listener.accept().await {
+        tokio::spawn(serve_connection(stream, calls.clone()));
+    }
+}
+
+#[cfg(unix)]
+async fn serve_connection(stream: UnixStream, calls: mpsc::Sender<Call>) -> std::io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match parse(&line) {
+            Ok(request) => {
+                let notification = request.id.is_none();
+                let (reply, replied) = oneshot::channel();
+                // Both fail when the app has exited
+                if calls.send(Call { request, reply }).is_err() {
+                    break;
+                }
+                match replied.await {
+                    Ok(_) if notification => None,
+                    Ok(response) => Some(response),
+                    Err(_) => break,
+                }
+            }
+            // Answered even though the id is unknown
+            Err(err) => Some(response(None, Err(err))),
+        };
+
+        if let Some(response) = response {
+            writer
+                .write_all(format!("{}\n", response).as_bytes())
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reports_json_rpc_errors() {
+        assert_eq!(parse("{").unwrap_err().code, PARSE_ERROR);
+        assert_eq!(
+            parse(r#"{"jsonrpc": "2.0", "id": 1}"#).unwrap_err().code,
+            INVALID_REQUEST
+        );
+        assert_eq!(
+            parse(r#"{"jsonrpc": "1.0", "id": 1, "method": "status"}"#)
+                .unwrap_err()
+                .code,
+            INVALID_REQUEST
+        );
+        assert_eq!(
+            parse(r#"{"id": 1, "method": "status"}"#).unwrap_err().code,
+            INVALID_REQUEST
+        );
+
+        let request = parse(r#"{"jsonrpc": "2.0", "id": 7, "method": "status"}"#).unwrap();
+        assert_eq!(request.id, Some(json!(7)));
+        assert_eq!(request.method, "status");
+        assert_eq!(request.params, Value::Null);
+    }
+
+    #[test]
+    fn errors_are_json_rpc_responses() {
+        let response = response(
+            Some(json!("a")),
+            Err(RpcError::new(METHOD_NOT_FOUND, "Unknown method 'x'")),
+        );
+        assert_eq!(
+            response,
+            json!({
+                "jsonrpc": "2.0",
+                "id": "a",
+                "error": {"code": -32601, "message": "Unknown method 'x'"},
+            })
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn socket_answers_each_line_and_replaces_stale_file() {
+        use std::io::{BufRead, Write};
+        use std::os::unix::fs::PermissionsExt;
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SOCKET_NAME);
+        std::fs::write(&path, "").unwrap();
+
+        let (sender, calls) = mpsc::channel();
+        let socket = open(runtime.handle(), &path, sender).unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        // Only the socket is left, the private directory it was bound in is gone
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        // The test plays the ui thread
+        let ui = std::thread::spawn(move || {
+            for call in calls.iter().take(2) {
+                let result = Ok(json!(call.request.method));
+                let _ = call.reply.send(response(call.request.id, result));
+            }
+        });
+
+        let mut stream = std::os::unix::net::UnixStream::connect(&path).unwrap();
+        stream
+            .write_all(
+                b"{\"jsonrpc\": \"2.0\", \"method\": \"pause\"}\nnot json\n{\"jsonrpc\": \"2.0\", \"id\": 2, \"method\": \"status\"}\n",
+            )
+            .unwrap();
+        let mut lines = std::io::BufReader::new(stream).lines();
+        let parse_error: Value = serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+        let status: Value = serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+        ui.join().unwrap();
+
+        assert_eq!(parse_error["error"]["code"], PARSE_ERROR);
+        assert_eq!(
+            status,
+            json!({"jsonrpc": "2.0", "id": 2, "result": "status"})
+        );
+
+        drop(socket);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,352 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Action {
+    Up,
+    Down,
+    Open,
+    Back,
+    Download,
+    Upload,
+    Delete,
+    Refresh,
+    Rename,
+    Mkdir,
+    Move,
+    Share,
+    Sort,
+    ShowTrashed,
+    Restore,
+    RemoveAccess,
+    SwitchDrive,
+    SwitchAccount,
+    Undo,
+    GoTo,
+    Search,
+    Transfers,
+    CancelTransfer,
+    PauseTransfer,
+    Palette,
+    Help,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: [Action; 27] = [
+        Action::Up,
+        Action::Down,
+        Action::Open,
+        Action::Back,
+        Action::Download,
+        Action::Upload,
+        Action::Delete,
+        Action::Refresh,
+        Action::Rename,
+        Action::Mkdir,
+        Action::Move,
+        Action::Share,
+        Action::Sort,
+        Action::ShowTrashed,
+        Action::Restore,
+        Action::RemoveAccess,
+        Action::SwitchDrive,
+        Action::SwitchAccount,
+        Action::Undo,
+        Action::GoTo,
+        Action::Search,
+        Action::Transfers,
+        Action::CancelTransfer,
+        Action::PauseTransfer,
+        Action::Palette,
+        Action::Help,
+        Action::Quit,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::Open => "open",
+            Action::Back => "back",
+            Action::Download => "download",
+            Action::Upload => "upload",
+            Action::Delete => "delete",
+            Action::Refresh => "refresh",
+            Action::Rename => "rename",
+            Action::Mkdir => "mkdir",
+            Action::Move => "move",
+            Action::Share => "share",
+            Action::Sort => "sort",
+            Action::ShowTrashed => "show-trashed",
+            Action::Restore => "restore",
+            Action::RemoveAccess => "remove-access",
+            Action::SwitchDrive => "switch-drive",
+            Action::SwitchAccount => "switch-account",
+            Action::Undo => "undo",
+            Action::GoTo => "go-to",
+            Action::Search => "search",
+            Action::Transfers => "transfers",
+            Action::CancelTransfer => "cancel-transfer",
+            Action::PauseTransfer => "pause-transfer",
+            Action::Palette => "palette",
+            Action::Help => "help",
+            Action::Quit => "quit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|action| action.name() == name)
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::Up => "Move selection up",
+            Action::Down => "Move selection down",
+            Action::Open => "Open selected folder",
+            Action::Back => "Go back to the previous folder",
+            Action::Download => "Download selected file",
+            Action::Upload => "Open the upload picker",
+            Action::Delete => "Move selected item to trash",
+            Action::Refresh => "Reload the current folder",
+            Action::Rename => "Rename selected item",
+            Action::Mkdir => "Create a folder in the current folder",
+            Action::Move => "Mark selected item for move, press again in the destination folder",
+            Action::Share => "Share selected item with a user or anyone with the link",
+            Action::Sort => "Cycle sort order (name, created, size)",
+            Action::ShowTrashed => "Show or hide trashed items in the current folder",
+            Action::Restore => "Restore the selected trashed item",
+            Action::RemoveAccess => "Remove your access to the selected shared item",
+            Action::SwitchDrive => "Switch between My Drive and shared drives",
+            Action::SwitchAccount => "Switch to another account",
+            Action::Undo => "Undo the last trash, move or rename",
+            Action::GoTo => "Jump to a file or folder by pasting its Drive url or id",
+            Action::Search => "Search files by name, with the local index as fallback",
+            Action::Transfers => "Show transfers, pause or cancel running ones, retry failed ones",
+            Action::CancelTransfer => "Cancel the transfer of the selected item",
+            Action::PauseTransfer => "Pause or resume the transfer of the selected item",
+            Action::Palette => "Open the command palette",
+            Action::Help => "Show this help",
+            Action::Quit => "Quit",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Key {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl Key {
+    pub fn plain(code: KeyCode) -> Key {
+        Key {
+            code,
+            modifiers: KeyModifiers::NONE,
+        }
+    }
+
+    pub fn char(ch: char) -> Key {
+        Key::plain(KeyCode::Char(ch))
+    }
+
+    pub fn matches(&self, event: &KeyEvent) -> bool {
+        // Terminals disagree on whether shifted characters like '?' carry SHIFT,
+        // so it is ignored for character keys
+        let ignored = match event.code {
+            KeyCode::Char(_) => KeyModifiers::SHIFT,
+            _ => KeyModifiers::NONE,
+        };
+
+        self.code == event.code
+            && self.modifiers.difference(ignored) == event.modifiers.difference(ignored)
+    }
+}
+
+impl Display for Key {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt-")?;
+        }
+
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(ch) => write!(f, "{}", ch),
+            KeyCode::Enter => write!(f, "Enter"),
+            KeyCode::Esc => write!(f, "Esc"),
+            KeyCode::Tab => write!(f, "Tab"),
+            KeyCode::Backspace => write!(f, "Backspace"),
+            KeyCode::Delete => write!(f, "Del"),
+            KeyCode::Home => write!(f, "Home"),
+            KeyCode::End => write!(f, "End"),
+            KeyCode::PageUp => write!(f, "PgUp"),
+            KeyCode::PageDown => write!(f, "PgDn"),
+            KeyCode::Up => write!(f, "↑"),
+            KeyCode::Down => write!(f, "↓"),
+            KeyCode::Left => write!(f, "←"),
+            KeyCode::Right => write!(f, "→"),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            other => write!(f, "{:?}", other),
+        }
+    }
+}
+
+// Parses keys as they are shown in the help, i.e. "d", "Ctrl-d", "PgDn" or "F5"
+impl FromStr for Key {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+        loop {
+            if let Some(stripped) = rest.strip_prefix("Ctrl-") {
+                modifiers |= KeyModifiers::CONTROL;
+                rest = stripped;
+            } else if let Some(stripped) = rest.strip_prefix("Alt-") {
+                modifiers |= KeyModifiers::ALT;
+                rest = stripped;
+            } else {
+                break;
+            }
+        }
+
+        let code = match rest {
+            "Space" => KeyCode::Char(' '),
+            "Enter" => KeyCode::Enter,
+            "Esc" => KeyCode::Esc,
+            "Tab" => KeyCode::Tab,
+            "Backspace" => KeyCode::Backspace,
+            "Del" => KeyCode::Delete,
+            "Home" => KeyCode::Home,
+            "End" => KeyCode::End,
+            "PgUp" => KeyCode::PageUp,
+            "PgDn" => KeyCode::PageDown,
+            "Up" | "↑" => KeyCode::Up,
+            "Down" | "↓" => KeyCode::Down,
+            "Left" | "←" => KeyCode::Left,
+            "Right" | "→" => KeyCode::Right,
+            _ => {
+                let mut chars = rest.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(ch), None) => KeyCode::Char(ch),
+                    (Some('F'), Some(_)) => match rest[1..].parse::<u8>() {
+                        Ok(n) if (1..=12).contains(&n) => KeyCode::F(n),
+                        _ => return Err(format!("Unknown key '{}'", s)),
+                    },
+                    _ => return Err(format!("Unknown key '{}'", s)),
+                }
+            }
+        };
+
+        Ok(Key { code, modifiers })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct KeyBinding {
+    pub key: Key,
+    pub action: Action,
+}
+
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: Vec<KeyBinding>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = vec![
+            (Key::plain(KeyCode::Up), Action::Up),
+            (Key::plain(KeyCode::Down), Action::Down),
+            (Key::plain(KeyCode::Enter), Action::Open),
+            (Key::plain(KeyCode::Right), Action::Open),
+            (Key::plain(KeyCode::Left), Action::Back),
+            (Key::char('b'), Action::Back),
+            (Key::char('d'), Action::Download),
+            (Key::char('u'), Action::Upload),
+            (Key::char('x'), Action::Delete),
+            (Key::plain(KeyCode::Delete), Action::Delete),
+            (Key::char('r'), Action::Refresh),
+            (Key::char('R'), Action::Rename),
+            (Key::char('N'), Action::Mkdir),
+            (Key::char('m'), Action::Move),
+            (Key::char('s'), Action::Share),
+            (Key::char('o'), Action::Sort),
+            (Key::char('t'), Action::ShowTrashed),
+            (Key::char('T'), Action::Restore),
+            (Key::char('X'), Action::RemoveAccess),
+            (Key::char('D'), Action::SwitchDrive),
+            (Key::char('A'), Action::SwitchAccount),
+            (Key::char('U'), Action::Undo),
+            (Key::char('G'), Action::GoTo),
+            (Key::char('/'), Action::Search),
+            (Key::char('h'), Action::Transfers),
+            (Key::plain(KeyCode::Esc), Action::CancelTransfer),
+            (Key::char('p'), Action::PauseTransfer),
+            (Key::char(':'), Action::Palette),
+            (Key::char('?'), Action::Help),
+            (Key::char('q'), Action::Quit),
+        ];
+
+        Keymap {
+            bindings: bindings
+                .into_iter()
+                .map(|(key, action)| KeyBinding { key, action })
+                .collect(),
+        }
+    }
+}
+
+impl Keymap {
+    // Keys by action name from the config file, they replace the default keys
+    // of the action
+    pub fn with_overrides(overrides: &BTreeMap<String, Vec<String>>) -> Result<Keymap, String> {
+        let mut keymap = Keymap::default();
+        for (name, keys) in overrides {
+            let action =
+                Action::from_name(name).ok_or_else(|| format!("Unknown action '{}'", name))?;
+            let keys = keys
+                .iter()
+                .map(|key| key.parse())
+                .collect::<Result<Vec<Key>, String>>()?;
+            keymap.bind(action, keys);
+        }
+        Ok(keymap)
+    }
+
+    pub fn bind(&mut self, action: Action, keys: Vec<Key>) {
+        // A key triggers a single action, rebinding it takes it from the other one
+        self.bindings
+            .retain(|binding| binding.action != action && !keys.contains(&binding.key));
+        self.bindings
+            .extend(keys.into_iter().map(|key| KeyBinding { key, action }));
+    }
+
+    pub fn action_for(&self, event: &KeyEvent) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|binding| binding.key.matches(event))
+            .map(|binding| binding.action)
+    }
+
+    pub fn keys_for(&self, action: Action) -> Vec<Key> {
+        self.bindings
+            .iter()
+            .filter(|binding| binding.action == action)
+            .map(|binding| binding.key)
+            .collect()
+    }
+
+    pub fn format_keys(&self, action: Action) -> String {
+        self.keys_for(action)
+            .iter()
+            .map(|key| key.to_string())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
@@ -0,0 +1,117 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use unicode_segmentation::UnicodeSegmentation;
+
+// Single line text input. The cursor is a byte offset that always sits on a
+// grapheme boundary, so combined characters and emoji are edited as one
+#[derive(Debug, Default)]
+pub struct LineEditor {
+    text: String,
+    cursor: usize,
+}
+
+impl LineEditor {
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    // Text before the cursor, the grapheme under it and the text after it
+    pub fn split_at_cursor(&self) -> (&str, &str, &str) {
+        let (before, rest) = self.text.split_at(self.cursor);
+        let current_len = rest.graphemes(true).next().map(str::len).unwrap_or(0);
+        let (current, after) = rest.split_at(current_len);
+        (before, current, after)
+    }
+
+    pub fn set(&mut self, text: &str) {
+        self.text = text.to_string();
+        self.cursor = self.text.len();
+    }
+
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.cursor = 0;
+    }
+
+    pub fn insert_str(&mut self, s: &str) {
+        self.text.insert_str(self.cursor, s);
+        self.cursor += s.len();
+    }
+
+    // Returns false for keys that aren't editing keys
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+
+        match key.code {
+            KeyCode::Left if ctrl || alt => self.cursor = self.previous_word_boundary(),
+            KeyCode::Right if ctrl || alt => self.cursor = self.next_word_boundary(),
+            KeyCode::Left => self.cursor = self.previous_grapheme_boundary(),
+            KeyCode::Right => self.cursor = self.next_grapheme_boundary(),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::Char('a') if ctrl => self.cursor = 0,
+            KeyCode::End => self.cursor = self.text.len(),
+            KeyCode::Char('e') if ctrl => self.cursor = self.text.len(),
+            KeyCode::Backspace if ctrl || alt => self.delete_back_to(self.previous_word_boundary()),
+            KeyCode::Char('w') if ctrl => self.delete_back_to(self.previous_word_boundary()),
+            KeyCode::Char('u') if ctrl => self.delete_back_to(0),
+            KeyCode::Char('k') if ctrl => self.text.truncate(self.cursor),
+            KeyCode::Backspace => self.delete_back_to(self.previous_grapheme_boundary()),
+            KeyCode::Delete => {
+                let end = self.next_grapheme_boundary();
+                self.text.replace_range(self.cursor..end, "");
+            }
+            // AltGr is reported as Ctrl+Alt on Windows
+            KeyCode::Char(ch) if !ctrl || alt => {
+                self.text.insert(self.cursor, ch);
+                self.cursor += ch.len_utf8();
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    fn delete_back_to(&mut self, start: usize) {
+        self.text.replace_range(start..self.cursor, "");
+        self.cursor = start;
+    }
+
+    fn previous_grapheme_boundary(&self) -> usize {
+        self.text[..self.cursor]
+            .grapheme_indices(true)
+            .next_back()
+            .map(|(i, _)| i)
+            .unwrap_or(0)
+    }
+
+    fn next_grapheme_boundary(&self) -> usize {
+        self.text[self.cursor..]
+            .graphemes(true)
+            .next()
+            .map(|g| self.cursor + g.len())
+            .unwrap_or(self.cursor)
+    }
+
+    // Start of the word before the cursor, skipping whitespace and punctuation
+    // like the / in paths
+    fn previous_word_boundary(&self) -> usize {
+        self.text[..self.cursor]
+            .split_word_bound_indices()
+            .rev()
+            .find(|(_, word)| is_word(word))
+            .map(|(i, _)| i)
+            .unwrap_or(0)
+    }
+
+    fn next_word_boundary(&self) -> usize {
+        self.text[self.cursor..]
+            .split_word_bound_indices()
+            .find(|(_, word)| is_word(word))
+            .map(|(i, word)| self.cursor + i + word.len())
+            .unwrap_or(self.text.len())
+    }
+}
+
+fn is_word(s: &str) -> bool {
+    s.chars().any(char::is_alphanumeric)
+}
@@ -0,0 +1,62 @@
+use crate::app_config::AppConfig;
+use serde::Deserialize;
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+const LOCAL_DIRS_FILE_NAME: &str = "navigate.json";
+
+// The local directories the last session uploaded from and downloaded to, so
+// the next one starts there instead of in the current directory
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LocalDirs {
+    pub upload_dir: Option<PathBuf>,
+    pub download_dir: Option<PathBuf>,
+}
+
+// Shared by all accounts, the directories are on this machine
+pub fn default_path() -> Option<PathBuf> {
+    AppConfig::default_base_path()
+        .ok()
+        .map(|base_path| base_path.join(LOCAL_DIRS_FILE_NAME))
+}
+
+// A missing or broken file is the same as a first session
+pub fn load(path: &Path) -> LocalDirs {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn save(path: &Path, dirs: &LocalDirs) -> Result<(), io::Error> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let content = serde_json::to_string_pretty(dirs)?;
+    fs::write(path, content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dirs_are_kept_between_sessions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(LOCAL_DIRS_FILE_NAME);
+        assert_eq!(load(&path), LocalDirs::default());
+
+        let dirs = LocalDirs {
+            upload_dir: Some(PathBuf::from("/home/me/projects/site/dist")),
+            download_dir: None,
+        };
+        save(&path, &dirs).unwrap();
+
+        assert_eq!(load(&path), dirs);
+        fs::write(&path, "not json").unwrap();
+        assert_eq!(load(&path), LocalDirs::default());
+    }
+}
@@ -0,0 +1,105 @@
+use super::keymap::Action;
+
+pub struct Palette {
+    pub query: String,
+    pub selected: usize,
+}
+
+impl Palette {
+    pub fn new() -> Self {
+        Self {
+            query: String::new(),
+            selected: 0,
+        }
+    }
+
+    // Actions matching the query, best match first
+    pub fn matches(&self) -> Vec<Action> {
+        let mut scored: Vec<(i64, Action)> = Action::ALL
+            .iter()
+            .filter(|action| **action != Action::Palette)
+            .filter_map(|action| {
+                let name_score = fuzzy_score(&self.query, action.name());
+                let description_score = fuzzy_score(&self.query, action.description());
+                // Prefer hits on the short name over hits somewhere in the description
+                let score = match (name_score, description_score) {
+                    (Some(name), _) => Some(name + 1000),
+                    (None, Some(description)) => Some(description),
+                    (None, None) => None,
+                };
+                score.map(|score| (score, *action))
+            })
+            .collect();
+
+        // Stable sort keeps keymap order for equal scores
+        scored.sort_by(|a, b| b.0.cmp(&a.0));
+        scored.into_iter().map(|(_, action)| action).collect()
+    }
+
+    pub fn selected_action(&self) -> Option<Action> {
+        self.matches().get(self.selected).copied()
+    }
+
+    pub fn select_next(&mut self) {
+        let count = self.matches().len();
+        if count > 0 {
+            self.selected = (self.selected + 1) % count;
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        let count = self.matches().len();
+        if count > 0 {
+            self.selected = if self.selected == 0 {
+                count - 1
+            } else {
+                self.selected - 1
+            };
+        }
+    }
+
+    pub fn push(&mut self, ch: char) {
+        self.query.push(ch);
+        self.selected = 0;
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.selected = 0;
+    }
+}
+
+// Case-insensitive subsequence match. Consecutive characters and characters at the
+// start of a word score higher, so "mk" ranks "mkdir" above "Mark selected item..."
+fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    if query.is_empty() {
+        return Some(0);
+    }
+
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    let mut score: i64 = 0;
+    let mut position = 0;
+    let mut previous_match: Option<usize> = None;
+
+    for query_char in query.to_lowercase().chars() {
+        let index = (position..candidate.len()).find(|i| candidate[*i] == query_char)?;
+
+        score += 1;
+        if previous_match
+            .map(|prev| prev + 1 == index)
+            .unwrap_or(false)
+        {
+            score += 5;
+        }
+        if index == 0 || !candidate[index - 1].is_alphanumeric() {
+            score += 3;
+        }
+        // Penalize matches that skip over a lot of the candidate
+        score -= (index - position) as i64;
+
+        previous_match = Some(index);
+        position = index + 1;
+    }
+
+    Some(score)
+}
@@ -0,0 +1,203 @@
+use super::keymap::Key;
+use super::{draw_ui, handle_event, App, Error};
+use crossterm::event::{Event, KeyEvent};
+use ratatui::backend::TestBackend;
+use ratatui::text::Span;
+use ratatui::Terminal;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::runtime::Handle;
+
+// Size of the screen that is printed, large enough for all popups
+pub const SCREEN_WIDTH: u16 = 100;
+pub const SCREEN_HEIGHT: u16 = 30;
+
+const WAIT_TIMEOUT: Duration = Duration::from_secs(300);
+const WAIT_INTERVAL: Duration = Duration::from_millis(100);
+
+// A line of a script. Keys are named like in the keymap of config.toml,
+// several keys on a line are separated by spaces:
+//
+//     # Trash the second file
+//     Down
+//     x y
+//     paste some text
+//     wait
+//     screen
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    Keys(Vec<Key>),
+    Paste(String),
+    // Until the running transfers are finished
+    Wait,
+    Screen,
+}
+
+impl Display for Step {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Step::Keys(keys) => {
+                let names: Vec<String> = keys.iter().map(|key| key.to_string()).collect();
+                write!(f, "{}", names.join(" "))
+            }
+            Step::Paste(text) => write!(f, "paste {}", text),
+            Step::Wait => write!(f, "wait"),
+            Step::Screen => write!(f, "screen"),
+        }
+    }
+}
+
+pub fn parse(content: &str) -> Result<Vec<Step>, String> {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(index, line)| parse_line(line).map_err(|err| format!("Line {}: {}", index + 1, err)))
+        .collect()
+}
+
+fn parse_line(line: &str) -> Result<Step, String> {
+    if let Some(text) = line.strip_prefix("paste ") {
+        return Ok(Step::Paste(text.to_string()));
+    }
+
+    match line.trim() {
+        "wait" => Ok(Step::Wait),
+        "screen" => Ok(Step::Screen),
+        keys => keys
+            .split_whitespace()
+            .map(|key| key.parse::<Key>())
+            .collect::<Result<Vec<_>, _>>()
+            .map(Step::Keys),
+    }
+}
+
+// Runs the steps against the app without a terminal and prints the status after
+// each step and the screen at the end, so a bug report can show what happened
+pub fn run(app: &mut App, steps: &[Step], handle: &Handle) -> Result<(), Error> {
+    for step in steps {
+        let exited = run_step(app, step, handle)?;
+        app.tick();
+
+        println!("> {}", step);
+        println!("  {}", app.status);
+
+        if *step == Step::Screen {
+            print!("{}", render(app)?);
+        }
+
+        if exited || app.should_exit() {
+            println!("App exited");
+            return Ok(());
+        }
+    }
+
+    print!("{}", render(app)?);
+    Ok(())
+}
+
+// Returns true when the app exited
+fn run_step(app: &mut App, step: &Step, handle: &Handle) -> Result<bool, Error> {
+    match step {
+        Step::Keys(keys) => {
+            for key in keys {
+                let event = Event::Key(KeyEvent::new(key.code, key.modifiers));
+                if handle_event(app, event, handle)? {
+                    return Ok(true);
+                }
+            }
+        }
+        Step::Paste(text) => {
+            handle_event(app, Event::Paste(text.clone()), handle)?;
+        }
+        Step::Wait => wait_for_transfers(app),
+        Step::Screen => {}
+    }
+
+    Ok(false)
+}
+
+fn wait_for_transfers(app: &mut App) {
+    let started = Instant::now();
+    while app.has_active_transfer() && started.elapsed() < WAIT_TIMEOUT {
+        thread::sleep(WAIT_INTERVAL);
+        app.tick();
+    }
+}
+
+// The screen as text, without colors
+pub fn render(app: &App) -> Result<String, Error> {
+    let backend = TestBackend::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+    let mut terminal = Terminal::new(backend).map_err(Error::Io)?;
+    terminal
+        .draw(|frame| draw_ui(frame, app))
+        .map_err(Error::Io)?;
+
+    let buffer = terminal.backend().buffer();
+    let mut screen = String::new();
+    for cells in buffer.content.chunks(buffer.area.width as usize) {
+        // Cells after a wide character are hidden by it
+        let mut skip = 0;
+        let mut line = String::new();
+        for cell in cells {
+            if skip == 0 {
+                line.push_str(cell.symbol());
+            }
+            skip = skip.max(Span::raw(cell.symbol()).width()).saturating_sub(1);
+        }
+        screen.push_str(line.trim_end());
+        screen.push('\n');
+    }
+
+    Ok(screen)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::{KeyCode, KeyModifiers};
+
+    #[test]
+    fn parse_keys_and_commands() {
+        let steps =
+            parse("# Trash the second file\n\nDown Down\nx y\npaste a b\nwait\nscreen\n").unwrap();
+
+        assert_eq!(
+            steps,
+            vec![
+                Step::Keys(vec![Key::plain(KeyCode::Down), Key::plain(KeyCode::Down)]),
+                Step::Keys(vec![Key::char('x'), Key::char('y')]),
+                Step::Paste("a b".to_string()),
+                Step::Wait,
+                Step::Screen,
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_modifiers() {
+        let steps = parse("Ctrl-c").unwrap();
+        assert_eq!(
+            steps,
+            vec![Step::Keys(vec![Key {
+                code: KeyCode::Char('c'),
+                modifiers: KeyModifiers::CONTROL,
+            }])]
+        );
+    }
+
+    #[test]
+    fn parse_reports_line_of_unknown_key() {
+        let err = parse("# comment\nDown\nShift-x\n").unwrap_err();
+        assert_eq!(err, "Line 3: Unknown key 'Shift-x'");
+    }
+
+    #[test]
+    fn steps_display_like_they_are_written() {
+        let steps = parse("Enter Space\npaste text\n").unwrap();
+        let lines: Vec<String> = steps.iter().map(|step| step.to_string()).collect();
+        assert_eq!(lines, vec!["Enter Space", "paste text"]);
+    }
+}
@@ -0,0 +1,59 @@
+const MAX_ENTRIES: usize = 20;
+
+// Inverse information for a destructive action taken in this session
+#[derive(Debug, Clone)]
+pub enum UndoEntry {
+    Trash {
+        file_id: String,
+        name: String,
+    },
+    Move {
+        file_id: String,
+        name: String,
+        old_parent_id: String,
+        new_parent_id: String,
+    },
+    Rename {
+        file_id: String,
+        old_name: String,
+        new_name: String,
+    },
+}
+
+impl UndoEntry {
+    pub fn describe(&self) -> String {
+        match self {
+            UndoEntry::Trash { name, .. } => format!("Restored '{}' from trash", name),
+            UndoEntry::Move { name, .. } => format!("Moved '{}' back", name),
+            UndoEntry::Rename {
+                old_name, new_name, ..
+            } => format!("Renamed '{}' back to '{}'", new_name, old_name),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct UndoHistory {
+    entries: Vec<UndoEntry>,
+}
+
+impl UndoHistory {
+    pub fn push(&mut self, entry: UndoEntry) {
+        if self.entries.len() == MAX_ENTRIES {
+            self.entries.remove(0);
+        }
+        self.entries.push(entry);
+    }
+
+    pub fn last(&self) -> Option<&UndoEntry> {
+        self.entries.last()
+    }
+
+    pub fn pop(&mut self) -> Option<UndoEntry> {
+        self.entries.pop()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
@@ -0,0 +1,10 @@
+pub mod channels;
+pub mod list;
+pub mod listen;
+pub mod stop;
+pub mod watch;
+
+pub use list::list;
+pub use listen::listen;
+pub use stop::stop;
+pub use watch::watch;
@@ -0,0 +1,109 @@
+use crate::app_config;
+use crate::app_config::AppConfig;
+use google_drive3::chrono;
+use google_drive3::chrono::TimeZone;
+use serde::Deserialize;
+use serde::Serialize;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+const CHANNELS_FILE_NAME: &str = "channels.json";
+
+// A notification channel registered with Drive. Drive needs the resource id to
+// stop a channel and only returns it when the channel is created, so it is kept
+// in the account directory together with the token that callbacks are checked against
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Channel {
+    pub id: String,
+    pub resource_id: String,
+    pub token: String,
+    pub address: String,
+    // None for a channel that watches all changes
+    pub file_id: Option<String>,
+    // Milliseconds since the epoch
+    pub expiration: Option<i64>,
+}
+
+impl Channel {
+    pub fn expiration_time(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.expiration
+            .and_then(|millis| chrono::Utc.timestamp_millis_opt(millis).single())
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expiration_time()
+            .map(|time| time < chrono::Utc::now())
+            .unwrap_or(false)
+    }
+}
+
+pub fn load() -> Result<Vec<Channel>, Error> {
+    let path = channels_path()?;
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+
+    let content = fs::read_to_string(&path).map_err(|err| Error::Read(path.clone(), err))?;
+    serde_json::from_str(&content).map_err(Error::Deserialize)
+}
+
+pub fn add(channel: Channel) -> Result<(), Error> {
+    let mut channels = load()?;
+    channels.push(channel);
+    save(&channels)
+}
+
+pub fn remove(channel_id: &str) -> Result<(), Error> {
+    let channels: Vec<Channel> = load()?
+        .into_iter()
+        .filter(|channel| channel.id != channel_id)
+        .collect();
+    save(&channels)
+}
+
+pub fn find(channel_id: &str) -> Result<Option<Channel>, Error> {
+    Ok(load()?.into_iter().find(|channel| channel.id == channel_id))
+}
+
+fn save(channels: &[Channel]) -> Result<(), Error> {
+    let path = channels_path()?;
+    let content = serde_json::to_string_pretty(channels).map_err(Error::Serialize)?;
+    fs::write(&path, content).map_err(|err| Error::Write(path.clone(), err))?;
+    app_config::set_file_permissions(&path).map_err(|err| Error::Write(path, err))
+}
+
+fn channels_path() -> Result<PathBuf, Error> {
+    let app_cfg = AppConfig::load_current_account().map_err(Error::AppConfig)?;
+    Ok(app_cfg.account_base_path().join(CHANNELS_FILE_NAME))
+}
+
+#[derive(Debug)]
+pub enum Error {
+    AppConfig(app_config::Error),
+    Serialize(serde_json::Error),
+    Deserialize(serde_json::Error),
+    Read(PathBuf, io::Error),
+    Write(PathBuf, io::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::AppConfig(err) => write!(f, "{}", err),
+            Error::Serialize(err) => write!(f, "Failed to serialize channels: {}", err),
+            Error::Deserialize(err) => write!(f, "Failed to parse channels: {}", err),
+            Error::Read(path, err) => {
+                write!(f, "Failed to read channels '{}': {}", path.display(), err)
+            }
+            Error::Write(path, err) => {
+                write!(f, "Failed to write channels '{}': {}", path.display(), err)
+            }
+        }
+    }
+}
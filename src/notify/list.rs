@@ -0,0 +1,69 @@
+use crate::common::table;
+use crate::common::table::Table;
+use crate::files;
+use crate::notify::channels;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::io;
+
+pub struct Config {
+    pub skip_header: bool,
+    pub field_separator: String,
+}
+
+pub fn list(config: Config) -> Result<(), Error> {
+    let channels = channels::load().map_err(Error::Channels)?;
+
+    let values: Vec<[String; 4]> = channels
+        .iter()
+        .map(|channel| {
+            let expires = match channel.expiration_time() {
+                Some(_) if channel.is_expired() => String::from("expired"),
+                Some(time) => files::info::format_date_time(time),
+                None => String::new(),
+            };
+
+            [
+                channel.id.clone(),
+                channel
+                    .file_id
+                    .clone()
+                    .unwrap_or_else(|| String::from("all changes")),
+                channel.address.clone(),
+                expires,
+            ]
+        })
+        .collect();
+
+    let table = Table {
+        header: ["Id", "Watching", "Address", "Expires"],
+        values,
+    };
+
+    let _ = table::write(
+        io::stdout(),
+        table,
+        &table::DisplayConfig {
+            skip_header: config.skip_header,
+            separator: config.field_separator,
+        },
+    );
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Channels(channels::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Channels(err) => write!(f, "{}", err),
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use crate::notify::channels;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use std::convert::Infallible;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+pub struct Config {
+    pub address: IpAddr,
+    pub port: u16,
+    // Shell commands run for every notification, one after another
+    pub hooks: Vec<String>,
+}
+
+// What Drive sends in the headers of a notification, the body is empty
+#[derive(Debug, Clone)]
+struct Notification {
+    channel_id: String,
+    token: String,
+    // sync when the channel was created, otherwise i.e. update, trash or change
+    resource_state: String,
+    resource_id: String,
+    message_number: String,
+    // What was updated, i.e. content or parents
+    changed: String,
+    // Empty for channels that watch all changes
+    file_id: String,
+}
+
+impl Notification {
+    fn from_request(req: &Request<Body>) -> Notification {
+        let header = |name: &str| {
+            req.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_string()
+        };
+
+        Notification {
+            channel_id: header("x-goog-channel-id"),
+            token: header("x-goog-channel-token"),
+            resource_state: header("x-goog-resource-state"),
+            resource_id: header("x-goog-resource-id"),
+            message_number: header("x-goog-message-number"),
+            changed: header("x-goog-changed"),
+            file_id: String::new(),
+        }
+    }
+}
+
+// Receives the notifications of channels created with `notify watch`. The
+// server only speaks http, it is meant to run behind a proxy that terminates
+// https at the address of the channels
+pub async fn listen(config: Config) -> Result<(), Error> {
+    let hooks = Arc::new(config.hooks);
+    let address = SocketAddr::new(config.address, config.port);
+
+    let make_service = make_service_fn(move |_conn| {
+        let hooks = hooks.clone();
+
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let hooks = hooks.clone();
+                async move { Ok::<_, Infallible>(handle(hooks, req)) }
+            }))
+        }
+    });
+
+    let server = Server::try_bind(&address)
+        .map_err(|err| Error::Bind(address, err))?
+        .serve(make_service);
+
+    println!(
+        "Listening for notifications on http://{}, press Ctrl-C to stop",
+        address
+    );
+
+    server
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await
+        .map_err(Error::Server)
+}
+
+// Drive retries notifications that don't get a success response, so hooks run
+// in the background and the response is sent right away
+fn handle(hooks: Arc<Vec<String>>, req: Request<Body>) -> Response<Body> {
+    if req.method() != Method::POST {
+        return status_response(StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    let mut notification = Notification::from_request(&req);
+
+    match channels::find(&notification.channel_id) {
+        Ok(Some(channel)) if channel.token == notification.token => {
+            notification.file_id = channel.file_id.unwrap_or_default();
+        }
+
+        Ok(_) => {
+            eprintln!(
+                "Warning: Ignoring notification for unknown channel '{}'",
+                notification.channel_id
+            );
+            return status_response(StatusCode::FORBIDDEN);
+        }
+
+        Err(err) => {
+            eprintln!("Warning: {}", err);
+            return status_response(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    if notification.resource_state == "sync" {
+        println!("Channel {} is ready", notification.channel_id);
+        return status_response(StatusCode::OK);
+    }
+
+    println!(
+        "Channel {}: {} {}",
+        notification.channel_id, notification.resource_state, notification.changed
+    );
+
+    tokio::spawn(run_hooks(hooks, notification));
+
+    status_response(StatusCode::OK)
+}
+
+// The notification is passed to the hooks in environment variables
+async fn run_hooks(hooks: Arc<Vec<String>>, notification: Notification) {
+    for hook in hooks.iter() {
+        let (shell, flag) = if cfg!(windows) {
+            ("cmd", "/C")
+        } else {
+            ("sh", "-c")
+        };
+
+        let status = tokio::process::Command::new(shell)
+            .arg(flag)
+            .arg(hook)
+            .env("GDRIVE_CHANNEL_ID", &notification.channel_id)
+            .env("GDRIVE_RESOURCE_STATE", &notification.resource_state)
+            .env("GDRIVE_RESOURCE_ID", &notification.resource_id)
+            .env("GDRIVE_MESSAGE_NUMBER", &notification.message_number)
+            .env("GDRIVE_CHANGED", &notification.changed)
+            .env("GDRIVE_FILE_ID", &notification.file_id)
+            .status()
+            .await;
+
+        match status {
+            Ok(status) if status.success() => {}
+            Ok(status) => eprintln!("Warning: Hook '{}' failed: {}", hook, status),
+            Err(err) => eprintln!("Warning: Failed to run hook '{}': {}", hook, err),
+        }
+    }
+}
+
+fn status_response(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Bind(SocketAddr, hyper::Error),
+    Server(hyper::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Bind(address, err) => write!(f, "Failed to listen on {}: {}", address, err),
+            Error::Server(err) => write!(f, "Server failed: {}", err),
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use crate::common::api_stats::StatsDelegate;
+use crate::common::hub_helper;
+use crate::notify::channels;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+pub struct Config {
+    pub channel_id: String,
+}
+
+// Expired channels are only removed from the local list, Drive has already
+// stopped them
+pub async fn stop(config: Config) -> Result<(), Error> {
+    let channel = channels::find(&config.channel_id)
+        .map_err(Error::Channels)?
+        .ok_or_else(|| Error::UnknownChannel(config.channel_id.clone()))?;
+
+    if !channel.is_expired() {
+        let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+
+        let request = google_drive3::api::Channel {
+            id: Some(channel.id.clone()),
+            resource_id: Some(channel.resource_id.clone()),
+            ..google_drive3::api::Channel::default()
+        };
+
+        hub.channels()
+            .stop(request)
+            .add_scope(google_drive3::api::Scope::Full)
+            .delegate(&mut StatsDelegate)
+            .doit()
+            .await
+            .map_err(Error::Stop)?;
+    }
+
+    channels::remove(&channel.id).map_err(Error::Channels)?;
+
+    println!("Stopped channel {}", channel.id);
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    Channels(channels::Error),
+    UnknownChannel(String),
+    Stop(google_drive3::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::Channels(err) => write!(f, "{}", err),
+            Error::UnknownChannel(id) => write!(
+                f,
+                "Channel '{}' was not created with this account, see `gdrive notify list`",
+                id
+            ),
+            Error::Stop(err) => write!(f, "Failed to stop channel: {}", err),
+        }
+    }
+}
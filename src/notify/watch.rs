@@ -0,0 +1,149 @@
+use crate::common::api_stats::StatsDelegate;
+use crate::common::hub_helper;
+use crate::files;
+use crate::files::index;
+use crate::hub::Hub;
+use crate::notify::channels;
+use crate::notify::channels::Channel;
+use google_drive3::chrono;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::time::Duration;
+
+pub struct Config {
+    // Watch a single file or folder, all changes in the account if not set
+    pub file_id: Option<String>,
+    // Public https url that Drive posts notifications to
+    pub address: String,
+    // Drive picks the expiration if not set, channels can't be renewed
+    pub ttl: Option<Duration>,
+    pub print_only_id: bool,
+}
+
+pub async fn watch(config: Config) -> Result<(), Error> {
+    err_if_not_https(&config.address)?;
+
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+
+    let request = google_drive3::api::Channel {
+        id: Some(uuid::Uuid::new_v4().to_string()),
+        type_: Some(String::from("web_hook")),
+        address: Some(config.address.clone()),
+        // Sent back with every notification, `notify listen` ignores callbacks
+        // without a known token
+        token: Some(uuid::Uuid::new_v4().simple().to_string()),
+        expiration: config
+            .ttl
+            .map(|ttl| chrono::Utc::now().timestamp_millis() + ttl.as_millis() as i64),
+        ..google_drive3::api::Channel::default()
+    };
+
+    let channel = match &config.file_id {
+        Some(file_id) => watch_file(&hub, file_id, request).await?,
+        None => watch_changes(&hub, request).await?,
+    };
+
+    let channel = Channel {
+        id: channel.id.unwrap_or_default(),
+        resource_id: channel.resource_id.unwrap_or_default(),
+        token: channel.token.unwrap_or_default(),
+        address: config.address,
+        file_id: config.file_id,
+        expiration: channel.expiration,
+    };
+
+    channels::add(channel.clone()).map_err(Error::Channels)?;
+
+    if config.print_only_id {
+        println!("{}", channel.id);
+    } else {
+        println!("Created channel {}", channel.id);
+        if let Some(time) = channel.expiration_time() {
+            println!(
+                "Expires {}, create a new channel before then",
+                files::info::format_date_time(time)
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn watch_file(
+    hub: &Hub,
+    file_id: &str,
+    request: google_drive3::api::Channel,
+) -> Result<google_drive3::api::Channel, Error> {
+    let (_, channel) = hub
+        .files()
+        .watch(request, file_id)
+        .supports_all_drives(true)
+        .add_scope(google_drive3::api::Scope::Full)
+        .delegate(&mut StatsDelegate)
+        .doit()
+        .await
+        .map_err(Error::Watch)?;
+
+    Ok(channel)
+}
+
+// Notifications only say that something changed, the changes since the token
+// taken here are listed with the Changes api
+async fn watch_changes(
+    hub: &Hub,
+    request: google_drive3::api::Channel,
+) -> Result<google_drive3::api::Channel, Error> {
+    let page_token = index::get_start_page_token(hub)
+        .await
+        .map_err(Error::PageToken)?;
+
+    let (_, channel) = hub
+        .changes()
+        .watch(request, &page_token)
+        .supports_all_drives(true)
+        .include_items_from_all_drives(true)
+        .add_scope(google_drive3::api::Scope::Full)
+        .delegate(&mut StatsDelegate)
+        .doit()
+        .await
+        .map_err(Error::Watch)?;
+
+    Ok(channel)
+}
+
+// Drive only delivers notifications over https
+fn err_if_not_https(address: &str) -> Result<(), Error> {
+    if !address.starts_with("https://") {
+        return Err(Error::NotHttps(address.to_string()));
+    }
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    NotHttps(String),
+    PageToken(index::Error),
+    Watch(google_drive3::Error),
+    Channels(channels::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::NotHttps(address) => write!(
+                f,
+                "'{}' is not an https url, Drive only sends notifications over https",
+                address
+            ),
+            Error::PageToken(err) => write!(f, "{}", err),
+            Error::Watch(err) => write!(f, "Failed to create channel: {}", err),
+            Error::Channels(err) => write!(f, "{}", err),
+        }
+    }
+}
@@ -1,8 +1,11 @@
+use crate::common::batch_request;
 use crate::common::delegate::UploadDelegate;
 use crate::common::delegate::UploadDelegateConfig;
 use crate::common::hub_helper;
 use crate::common::permission;
+use crate::config::settings;
 use crate::files;
+use crate::hub;
 use crate::hub::Hub;
 use crate::permissions;
 use std::error;
@@ -30,25 +33,42 @@ pub async fn revoke(config: Config) -> Result<(), Error> {
 
     let delete_list = config.action.get_matching_permissions(permissions)?;
 
-    for permission in delete_list {
-        if let Err(_) = print_revoke_details(&file, &permission) {
+    for permission in &delete_list {
+        if let Err(_) = print_revoke_details(&file, permission) {
             println!(
                 "Revoking permission with id: '{}'",
                 permission.id.clone().unwrap_or_default()
             );
         }
-
-        delete_permission(
-            &hub,
-            delegate_config.clone(),
-            &config.file_id,
-            &permission.id.clone().unwrap_or_default(),
-        )
-        .await
-        .map_err(|err| Error::DeletePermission(permission.clone(), err))?;
     }
 
-    Ok(())
+    // Revoking everything of a widely shared file is many deletes, they are
+    // sent in one batch. Deletes that failed are all reported, not just the first
+    let requests: Vec<batch_request::Request> = delete_list
+        .iter()
+        .map(|permission| {
+            batch_request::Request::delete(&hub::permission_url(
+                &config.file_id,
+                &permission.id.clone().unwrap_or_default(),
+            ))
+        })
+        .collect();
+    let responses =
+        batch_request::send_with_retries(&hub, &requests, settings::current().backoff())
+            .await
+            .map_err(Error::Batch)?;
+
+    let failed: Vec<(google_drive3::api::Permission, hub::RequestError)> = delete_list
+        .into_iter()
+        .zip(responses)
+        .filter_map(|(permission, response)| response.check().err().map(|err| (permission, err)))
+        .collect();
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::DeletePermissions(failed))
+    }
 }
 
 pub async fn delete_permission(
@@ -79,7 +99,8 @@ pub enum Error {
     Hub(hub_helper::Error),
     GetFile(google_drive3::Error),
     ListPermissions(google_drive3::Error),
-    DeletePermission(google_drive3::api::Permission, google_drive3::Error),
+    DeletePermissions(Vec<(google_drive3::api::Permission, hub::RequestError)>),
+    Batch(hub::RequestError),
     PermissionNotFound(String),
     UnknownPermissionType(String),
     UnknownPermissionRole(String),
@@ -97,13 +118,16 @@ impl Display for Error {
             Error::ListPermissions(err) => {
                 write!(f, "Failed to list permissions: {}", err)
             }
-            Error::DeletePermission(permission, err) => {
-                write!(
-                    f,
-                    "Failed to delete permission '{}': {}",
-                    permission.clone().id.unwrap_or_default(),
-                    err
-                )
+            Error::DeletePermissions(failed) => {
+                write!(f, "Failed to delete {} permissions:", failed.len())?;
+                for (permission, err) in failed {
+                    let id = permission.id.clone().unwrap_or_default();
+                    write!(f, "\n  '{}': {}", id, err)?;
+                }
+                Ok(())
+            }
+            Error::Batch(err) => {
+                write!(f, "Failed to revoke permissions: {}", err)
             }
             Error::PermissionNotFound(id) => {
                 write!(f, "Permission '{}' not found", id)
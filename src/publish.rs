@@ -0,0 +1,139 @@
+use crate::common::delegate::UploadDelegateConfig;
+use crate::common::file_info;
+use crate::common::file_info::FileInfo;
+use crate::common::hub_helper;
+use crate::common::permission;
+use crate::config::settings;
+use crate::files::upload;
+use crate::files::url;
+use crate::permissions::share;
+use serde_json::json;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+pub struct Config {
+    pub file_path: PathBuf,
+    pub parent_id: Option<String>,
+    pub public: bool,
+    pub json: bool,
+}
+
+// Uploads a release artifact and prints the link to download it. Progress goes
+// to stderr so the link can be captured by the pipeline
+pub async fn publish(config: Config) -> Result<(), Error> {
+    if config.file_path.is_dir() {
+        return Err(Error::IsDirectory(config.file_path.clone()));
+    }
+
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+
+    let delegate_config = UploadDelegateConfig {
+        backoff_config: settings::current().backoff(),
+        ..UploadDelegateConfig::default()
+    };
+
+    let file = fs::File::open(&config.file_path)
+        .map_err(|err| Error::OpenFile(config.file_path.clone(), err))?;
+
+    let file_info = FileInfo::from_file(
+        &file,
+        &file_info::Config {
+            file_path: config.file_path.clone(),
+            mime_type: None,
+            parents: config.parent_id.clone().map(|id| vec![id]),
+        },
+    )
+    .map_err(Error::FileInfo)?;
+
+    eprintln!("Uploading {}", config.file_path.display());
+
+    let reader = io::BufReader::new(file);
+    let file = upload::upload_file(&hub, reader, None, file_info, delegate_config.clone())
+        .await
+        .map_err(Error::Upload)?;
+
+    let file_id = file.id.clone().unwrap_or_default();
+
+    if config.public {
+        eprintln!("Sharing with anyone who has the link");
+
+        share::create_permission(
+            &hub,
+            delegate_config,
+            &share::Config {
+                file_id: file_id.clone(),
+                role: permission::Role::Reader,
+                type_: permission::Type::Anyone,
+                discoverable: false,
+                email: None,
+                domain: None,
+            },
+        )
+        .await
+        .map_err(|err| Error::Share(file_id.clone(), err))?;
+    }
+
+    let download_url = url::direct_url(&hub, &file).await.map_err(Error::Url)?;
+
+    if config.json {
+        let value = json!({
+            "id": file_id,
+            "name": file.name.unwrap_or_default(),
+            "size": file.size,
+            "md5Checksum": file.md5_checksum,
+            "public": config.public,
+            "webViewLink": file.web_view_link,
+            "downloadUrl": download_url,
+        });
+
+        let json = serde_json::to_string_pretty(&value).map_err(Error::Serialize)?;
+        println!("{}", json);
+    } else {
+        println!("{}", download_url);
+    }
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    IsDirectory(PathBuf),
+    OpenFile(PathBuf, io::Error),
+    FileInfo(file_info::Error),
+    Upload(google_drive3::Error),
+    Share(String, google_drive3::Error),
+    Url(url::Error),
+    Serialize(serde_json::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::IsDirectory(path) => write!(
+                f,
+                "'{}' is a directory, only single files can be published",
+                path.display()
+            ),
+            Error::OpenFile(path, err) => {
+                write!(f, "Failed to open file '{}': {}", path.display(), err)
+            }
+            Error::FileInfo(err) => write!(f, "{}", err),
+            Error::Upload(err) => write!(f, "Failed to upload file: {}", err),
+            Error::Share(file_id, err) => write!(
+                f,
+                "Uploaded file {} but failed to share it: {}",
+                file_id, err
+            ),
+            Error::Url(err) => write!(f, "{}", err),
+            Error::Serialize(err) => write!(f, "Failed to serialize file: {}", err),
+        }
+    }
+}
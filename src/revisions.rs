@@ -0,0 +1,7 @@
+pub mod download;
+pub mod keep;
+pub mod list;
+
+pub use download::download;
+pub use keep::keep;
+pub use list::list;
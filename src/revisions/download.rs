@@ -0,0 +1,258 @@
+use crate::common::api_stats::StatsDelegate;
+use crate::common::drive_file;
+use crate::common::drive_file::DocType;
+use crate::common::drive_file::FileExtension;
+use crate::common::file_name;
+use crate::common::hub_helper;
+use crate::files;
+use crate::hub;
+use crate::hub::Hub;
+use crate::revisions::list;
+use google_drive3::chrono;
+use google_drive3::chrono::DateTime;
+use google_drive3::chrono::TimeZone;
+use google_drive3::hyper;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::path::PathBuf;
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub file_id: String,
+    pub at: DateTime<chrono::Utc>,
+    pub format: Option<FileExtension>,
+    pub destination: Option<PathBuf>,
+    pub overwrite: bool,
+}
+
+// Downloads the file as it was at the given time, i.e. the newest revision
+// that was made on or before it
+pub async fn download(config: Config) -> Result<(), Error> {
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+
+    let file = files::info::get_file(&hub, &config.file_id)
+        .await
+        .map_err(Error::GetFile)?;
+
+    let name = file_name::sanitize(
+        &file.name.clone().unwrap_or_default(),
+        file_name::DEFAULT_REPLACEMENT,
+    );
+
+    let revisions = list::list_revisions(&hub, &config.file_id)
+        .await
+        .map_err(Error::ListRevisions)?;
+
+    let revision = revisions
+        .into_iter()
+        .filter(|revision| revision.modified_time.is_some_and(|time| time <= config.at))
+        .max_by_key(|revision| revision.modified_time)
+        .ok_or(Error::NoRevision(config.at))?;
+
+    let revision_id = revision.id.clone().unwrap_or_default();
+    let modified = revision
+        .modified_time
+        .map(files::info::format_date_time)
+        .unwrap_or_default();
+
+    let (body, file_name) = if drive_file::is_binary(&file) {
+        let body = download_revision(&hub, &config.file_id, &revision_id)
+            .await
+            .map_err(Error::Download)?;
+        (body, name)
+    } else {
+        let doc_type = file
+            .mime_type
+            .as_deref()
+            .and_then(DocType::from_mime_type)
+            .ok_or(Error::UnsupportedMime(
+                file.mime_type.clone().unwrap_or_default(),
+            ))?;
+
+        let extension = config
+            .format
+            .clone()
+            .unwrap_or_else(|| doc_type.default_export_type());
+
+        if !doc_type.can_export_to(&extension) {
+            return Err(Error::UnsupportedExport(doc_type));
+        }
+
+        let body = export_revision(&hub, &revision, &extension).await?;
+        (body, format!("{}.{}", name, extension))
+    };
+
+    let file_path = config
+        .destination
+        .unwrap_or_else(|| PathBuf::from(&file_name));
+
+    if file_path.exists() && !config.overwrite {
+        return Err(Error::FileExists(file_path));
+    }
+
+    println!(
+        "Downloading revision {} from {} to {}",
+        revision_id,
+        modified,
+        file_path.display()
+    );
+
+    files::download::save_body_to_file(body, &file_path, revision.md5_checksum.clone())
+        .await
+        .map_err(Error::SaveFile)?;
+
+    println!("Successfully downloaded {}", file_path.display());
+
+    Ok(())
+}
+
+pub async fn download_revision(
+    hub: &Hub,
+    file_id: &str,
+    revision_id: &str,
+) -> Result<hyper::Body, google_drive3::Error> {
+    let (response, _) = hub
+        .revisions()
+        .get(file_id, revision_id)
+        .param("alt", "media")
+        .add_scope(google_drive3::api::Scope::Full)
+        .delegate(&mut StatsDelegate)
+        .doit()
+        .await?;
+
+    Ok(response.into_body())
+}
+
+// Google documents have no content of their own, each revision has links to
+// export it instead
+async fn export_revision(
+    hub: &Hub,
+    revision: &google_drive3::api::Revision,
+    extension: &FileExtension,
+) -> Result<hyper::Body, Error> {
+    let mime_type = extension
+        .get_export_mime()
+        .ok_or(Error::GetFileExtensionMime(extension.clone()))?;
+
+    let url = revision
+        .export_links
+        .as_ref()
+        .and_then(|links| links.get(&mime_type.to_string()))
+        .ok_or(Error::MissingExportLink(mime_type.to_string()))?;
+
+    let response = hub
+        .send(hyper::Request::get(url), hyper::Body::empty())
+        .await
+        .map_err(Error::Export)?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .unwrap_or_default();
+        return Err(Error::Export(hub::RequestError::Status(
+            status,
+            String::from_utf8_lossy(&body).to_string(),
+        )));
+    }
+
+    Ok(response.into_body())
+}
+
+// Accepts a date, which means the end of that day, a date and time in local
+// time, or an RFC 3339 timestamp
+pub fn parse_time(s: &str) -> Result<DateTime<chrono::Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Ok(time.with_timezone(&chrono::Utc));
+    }
+
+    let naive = chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M"))
+        .ok()
+        .or_else(|| {
+            chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(23, 59, 59))
+        });
+
+    naive
+        .and_then(|naive| chrono::Local.from_local_datetime(&naive).earliest())
+        .map(|time| time.with_timezone(&chrono::Utc))
+        .ok_or_else(|| {
+            format!(
+                "'{}' is not a valid time, use i.e. 2024-06-01, '2024-06-01 14:30' or 2024-06-01T14:30:00Z",
+                s
+            )
+        })
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    GetFile(google_drive3::Error),
+    ListRevisions(google_drive3::Error),
+    NoRevision(DateTime<chrono::Utc>),
+    UnsupportedMime(String),
+    UnsupportedExport(DocType),
+    GetFileExtensionMime(FileExtension),
+    MissingExportLink(String),
+    FileExists(PathBuf),
+    Download(google_drive3::Error),
+    Export(hub::RequestError),
+    SaveFile(files::download::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::GetFile(err) => write!(f, "Failed to get file: {}", err),
+            Error::ListRevisions(err) => write!(f, "Failed to list revisions: {}", err),
+            Error::NoRevision(time) => write!(
+                f,
+                "The file has no revision from before {}",
+                files::info::format_date_time(*time)
+            ),
+            Error::UnsupportedMime(mime) => {
+                write!(
+                    f,
+                    "Revisions of files of type '{}' can't be downloaded",
+                    mime
+                )
+            }
+            Error::UnsupportedExport(doc_type) => {
+                let supported_types = doc_type
+                    .supported_export_types()
+                    .iter()
+                    .map(|ext| ext.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                write!(
+                    f,
+                    "A {} can't be downloaded as this file type, supported file types are: {}",
+                    doc_type, supported_types
+                )
+            }
+            Error::GetFileExtensionMime(extension) => write!(
+                f,
+                "Failed to get mime type from file extension: {}",
+                extension
+            ),
+            Error::MissingExportLink(mime) => {
+                write!(f, "The revision can't be exported as '{}'", mime)
+            }
+            Error::FileExists(path) => write!(
+                f,
+                "File '{}' already exists, use --overwrite to overwrite it",
+                path.display()
+            ),
+            Error::Download(err) => write!(f, "Failed to download revision: {}", err),
+            Error::Export(err) => write!(f, "Failed to export revision: {}", err),
+            Error::SaveFile(err) => write!(f, "Failed to save file: {}", err),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use crate::common::api_stats::StatsDelegate;
+use crate::common::drive_file;
+use crate::common::hub_helper;
+use crate::files;
+use crate::hub::Hub;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub file_id: String,
+    pub revision_id: String,
+    pub keep_forever: bool,
+}
+
+// Drive deletes old revisions of binary files after 30 days or 100 revisions,
+// unless they are marked to be kept forever
+pub async fn keep(config: Config) -> Result<(), Error> {
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+
+    let file = files::info::get_file(&hub, &config.file_id)
+        .await
+        .map_err(Error::GetFile)?;
+
+    if !drive_file::is_binary(&file) {
+        return Err(Error::NotBinary);
+    }
+
+    let revision = set_keep_forever(
+        &hub,
+        &config.file_id,
+        &config.revision_id,
+        config.keep_forever,
+    )
+    .await
+    .map_err(Error::Update)?;
+
+    let revision_id = revision.id.unwrap_or(config.revision_id);
+    if revision.keep_forever.unwrap_or(config.keep_forever) {
+        println!(
+            "Revision {} of '{}' will be kept forever",
+            revision_id,
+            file.name.unwrap_or_default()
+        );
+    } else {
+        println!(
+            "Revision {} of '{}' will be deleted by Drive when it is no longer recent",
+            revision_id,
+            file.name.unwrap_or_default()
+        );
+    }
+
+    Ok(())
+}
+
+pub async fn set_keep_forever(
+    hub: &Hub,
+    file_id: &str,
+    revision_id: &str,
+    keep_forever: bool,
+) -> Result<google_drive3::api::Revision, google_drive3::Error> {
+    let revision = google_drive3::api::Revision {
+        keep_forever: Some(keep_forever),
+        ..google_drive3::api::Revision::default()
+    };
+
+    let (_, revision) = hub
+        .revisions()
+        .update(revision, file_id, revision_id)
+        .param("fields", "id,modifiedTime,keepForever")
+        .add_scope(google_drive3::api::Scope::Full)
+        .delegate(&mut StatsDelegate)
+        .doit()
+        .await?;
+
+    Ok(revision)
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    GetFile(google_drive3::Error),
+    NotBinary,
+    Update(google_drive3::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::GetFile(err) => {
+                write!(f, "Failed to get file: {}", err)
+            }
+            Error::NotBinary => write!(
+                f,
+                "Only revisions of binary files can be kept forever, Google documents keep their revisions"
+            ),
+            Error::Update(err) => {
+                write!(f, "Failed to update revision: {}", err)
+            }
+        }
+    }
+}
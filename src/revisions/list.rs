@@ -0,0 +1,133 @@
+use crate::common::api_stats::StatsDelegate;
+use crate::common::hub_helper;
+use crate::common::table;
+use crate::common::table::Table;
+use crate::files;
+use crate::files::info::DisplayConfig;
+use crate::hub::Hub;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::io;
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub file_id: String,
+    pub skip_header: bool,
+    pub field_separator: String,
+}
+
+pub async fn list(config: Config) -> Result<(), Error> {
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+
+    files::info::get_file(&hub, &config.file_id)
+        .await
+        .map_err(Error::GetFile)?;
+
+    let revisions = list_revisions(&hub, &config.file_id)
+        .await
+        .map_err(Error::ListRevisions)?;
+
+    let mut values: Vec<[String; 5]> = vec![];
+
+    for revision in revisions {
+        values.push([
+            revision.id.unwrap_or_default(),
+            revision
+                .modified_time
+                .map(files::info::format_date_time)
+                .unwrap_or_default(),
+            revision
+                .size
+                .map(|bytes| files::info::format_bytes(bytes, &DisplayConfig::default()))
+                .unwrap_or_default(),
+            revision
+                .keep_forever
+                .map(files::info::format_bool)
+                .unwrap_or_default(),
+            revision
+                .last_modifying_user
+                .and_then(|user| user.email_address.or(user.display_name))
+                .unwrap_or_default(),
+        ])
+    }
+
+    let table = Table {
+        header: ["Id", "Modified", "Size", "KeepForever", "ModifiedBy"],
+        values,
+    };
+
+    let _ = table::write(
+        io::stdout(),
+        table,
+        &table::DisplayConfig {
+            skip_header: config.skip_header,
+            separator: config.field_separator,
+        },
+    );
+
+    Ok(())
+}
+
+// Oldest revision first
+pub async fn list_revisions(
+    hub: &Hub,
+    file_id: &str,
+) -> Result<Vec<google_drive3::api::Revision>, google_drive3::Error> {
+    let mut collected_revisions = vec![];
+    let mut next_page_token: Option<String> = None;
+
+    loop {
+        let mut req = hub.revisions().list(file_id);
+
+        if let Some(token) = next_page_token {
+            req = req.page_token(&token);
+        }
+
+        let (_, revision_list) = req
+            .page_size(1000)
+            .param(
+                "fields",
+                "revisions(id,mimeType,modifiedTime,size,md5Checksum,keepForever,exportLinks,lastModifyingUser(displayName,emailAddress)),nextPageToken",
+            )
+            .add_scope(google_drive3::api::Scope::Full)
+            .delegate(&mut StatsDelegate)
+            .doit()
+            .await?;
+
+        if let Some(mut revisions) = revision_list.revisions {
+            collected_revisions.append(&mut revisions);
+        }
+
+        next_page_token = revision_list.next_page_token;
+
+        if next_page_token.is_none() {
+            break;
+        }
+    }
+
+    Ok(collected_revisions)
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    GetFile(google_drive3::Error),
+    ListRevisions(google_drive3::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::GetFile(err) => {
+                write!(f, "Failed to get file: {}", err)
+            }
+            Error::ListRevisions(err) => {
+                write!(f, "Failed to list revisions: {}", err)
+            }
+        }
+    }
+}
@@ -0,0 +1,578 @@
+use crate::common::delegate::UploadDelegateConfig;
+use crate::common::drive_file;
+use crate::common::file_info::FileInfo;
+use crate::common::hub_helper;
+use crate::common::path_resolver;
+use crate::files;
+use crate::hub;
+use crate::hub::Hub;
+use futures::StreamExt;
+use hyper::header;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use std::convert::Infallible;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::io;
+use std::io::{Seek, SeekFrom, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+mod metrics;
+
+use metrics::Metrics;
+
+// Files are addressed by id with `/?id=<FILE_ID>`, a query can't clash with a file name
+const ID_PARAM: &str = "id";
+const METRICS_PATH: &str = "/metrics";
+const MY_DRIVE_ID: &str = "root";
+
+pub struct Config {
+    pub address: IpAddr,
+    pub port: u16,
+    pub root_id: Option<String>,
+    // Serves counters for Prometheus on /metrics, which hides a file of that
+    // name in the root folder
+    pub metrics: bool,
+    pub read_only: bool,
+}
+
+pub async fn serve(config: Config) -> Result<(), Error> {
+    let hub = Arc::new(hub_helper::get_hub().await.map_err(Error::Hub)?);
+    let root_id = Arc::new(config.root_id.unwrap_or_else(|| MY_DRIVE_ID.to_string()));
+    let address = SocketAddr::new(config.address, config.port);
+    let metrics = config.metrics.then(|| Arc::new(Metrics::default()));
+    let read_only = config.read_only;
+
+    let make_service = make_service_fn(move |_conn| {
+        let hub = hub.clone();
+        let root_id = root_id.clone();
+        let metrics = metrics.clone();
+
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let hub = hub.clone();
+                let root_id = root_id.clone();
+                let metrics = metrics.clone();
+                async move { Ok::<_, Infallible>(handle(&hub, &root_id, read_only, metrics, req).await) }
+            }))
+        }
+    });
+
+    let server = Server::try_bind(&address)
+        .map_err(|err| Error::Bind(address, err))?
+        .serve(make_service);
+
+    println!("Serving Drive on http://{}, press Ctrl-C to stop", address);
+    if read_only {
+        println!("Uploads are disabled");
+    }
+    if config.metrics {
+        println!("Metrics on http://{}{}", address, METRICS_PATH);
+    }
+
+    server
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await
+        .map_err(Error::Server)
+}
+
+async fn handle(
+    hub: &Hub,
+    root_id: &str,
+    read_only: bool,
+    metrics: Option<Arc<Metrics>>,
+    req: Request<Body>,
+) -> Response<Body> {
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+
+    if let Some(metrics) = &metrics {
+        if method == Method::GET && path == METRICS_PATH {
+            let mut response = text_response(StatusCode::OK, metrics.render());
+            response.headers_mut().insert(
+                header::CONTENT_TYPE,
+                header::HeaderValue::from_static("text/plain; version=0.0.4; charset=utf-8"),
+            );
+            return response;
+        }
+    }
+
+    let response = match route(hub, root_id, read_only, metrics.as_ref(), req).await {
+        Ok(response) => response,
+        Err(err) => {
+            if let Some(metrics) = &metrics {
+                metrics.error(err.reason());
+            }
+            text_response(err.status(), format!("{}\n", err))
+        }
+    };
+
+    if let Some(metrics) = &metrics {
+        metrics.request(method.as_str(), response.status().as_u16());
+    }
+    println!("{} {} {}", method, path, response.status().as_u16());
+    response
+}
+
+async fn route(
+    hub: &Hub,
+    root_id: &str,
+    read_only: bool,
+    metrics: Option<&Arc<Metrics>>,
+    req: Request<Body>,
+) -> Result<Response<Body>, RequestError> {
+    let path = percent_decode_str(req.uri().path().trim_start_matches('/'))
+        .decode_utf8()
+        .map_err(|_| RequestError::InvalidPath)?
+        .to_string();
+    let file_id = id_param(req.uri().query().unwrap_or_default());
+
+    match *req.method() {
+        Method::GET | Method::HEAD => {
+            let file = match &file_id {
+                Some(file_id) => get_file_in_root(hub, root_id, file_id).await?,
+                None => path_resolver::resolve(hub, root_id, &path)
+                    .await
+                    .map_err(RequestError::List)?
+                    .ok_or_else(|| RequestError::NotFound(path.clone()))?,
+            };
+            let by_id = file_id.is_some();
+            let name = file_id.unwrap_or(path);
+
+            if drive_file::is_directory(&file) {
+                list_folder(hub, &file, &name, by_id).await
+            } else if drive_file::is_binary(&file) {
+                stream_file(hub, &file, metrics, req).await
+            } else {
+                Err(RequestError::NotBinary(name))
+            }
+        }
+
+        Method::PUT if read_only => Err(RequestError::ReadOnly),
+
+        Method::PUT => upload(hub, root_id, &path, file_id.as_deref(), metrics, req).await,
+
+        _ => Err(RequestError::MethodNotAllowed),
+    }
+}
+
+fn id_param(query: &str) -> Option<String> {
+    query.split('&').find_map(|pair| {
+        let (name, value) = pair.split_once('=')?;
+        (name == ID_PARAM && !value.is_empty())
+            .then(|| percent_decode_str(value).decode_utf8_lossy().to_string())
+    })
+}
+
+// Without the check any file in the account could be read or overwritten by
+// its id when only a folder is served
+async fn get_file_in_root(
+    hub: &Hub,
+    root_id: &str,
+    file_id: &str,
+) -> Result<google_drive3::api::File, RequestError> {
+    let not_found = || RequestError::NotFound(file_id.to_string());
+    let file = files::info::get_file(hub, file_id)
+        .await
+        .map_err(|_| not_found())?;
+
+    if root_id == MY_DRIVE_ID || file.id.as_deref() == Some(root_id) {
+        return Ok(file);
+    }
+
+    let ancestors = path_resolver::ancestors(hub, &file)
+        .await
+        .map_err(|_| not_found())?;
+    if ancestors
+        .iter()
+        .any(|folder| folder.id.as_deref() == Some(root_id))
+    {
+        Ok(file)
+    } else {
+        Err(not_found())
+    }
+}
+
+// Forwards the request to Drive so range requests are answered by Drive itself,
+// the body is streamed through without buffering
+async fn stream_file(
+    hub: &Hub,
+    file: &google_drive3::api::File,
+    metrics: Option<&Arc<Metrics>>,
+    req: Request<Body>,
+) -> Result<Response<Body>, RequestError> {
+    let file_id = file.id.clone().unwrap_or_default();
+    let mime_type = file
+        .mime_type
+        .clone()
+        .unwrap_or_else(|| mime::APPLICATION_OCTET_STREAM.to_string());
+
+    if req.method() == Method::HEAD {
+        let size = file.size.unwrap_or(0).max(0);
+        return Response::builder()
+            .header(header::CONTENT_TYPE, mime_type)
+            .header(header::CONTENT_LENGTH, size)
+            .header(header::ACCEPT_RANGES, "bytes")
+            .body(Body::empty())
+            .map_err(RequestError::Response);
+    }
+
+    let mut drive_request = hyper::Request::get(hub::media_url(&file_id));
+    if let Some(range) = req.headers().get(header::RANGE) {
+        drive_request = drive_request.header(header::RANGE, range.clone());
+    }
+
+    let drive_response = hub
+        .send(drive_request, Body::empty())
+        .await
+        .map_err(RequestError::Drive)?;
+
+    let status = drive_response.status();
+    if !status.is_success() && status != StatusCode::RANGE_NOT_SATISFIABLE {
+        let body = hyper::body::to_bytes(drive_response.into_body())
+            .await
+            .unwrap_or_default();
+        return Err(RequestError::Drive(hub::RequestError::Status(
+            status,
+            String::from_utf8_lossy(&body).to_string(),
+        )));
+    }
+
+    let mut response = Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, mime_type)
+        .header(header::ACCEPT_RANGES, "bytes");
+    for name in [header::CONTENT_LENGTH, header::CONTENT_RANGE] {
+        if let Some(value) = drive_response.headers().get(&name) {
+            response = response.header(name, value.clone());
+        }
+    }
+
+    let body = match metrics {
+        Some(metrics) => {
+            metrics.download();
+            let metrics = metrics.clone();
+            Body::wrap_stream(drive_response.into_body().inspect(move |chunk| {
+                if let Ok(chunk) = chunk {
+                    metrics.downloaded(chunk.len() as u64);
+                }
+            }))
+        }
+        None => drive_response.into_body(),
+    };
+
+    response.body(body).map_err(RequestError::Response)
+}
+
+// Plain html index, which is what media players expect when browsing over http
+async fn list_folder(
+    hub: &Hub,
+    folder: &google_drive3::api::File,
+    path: &str,
+    by_id: bool,
+) -> Result<Response<Body>, RequestError> {
+    let folder_id = folder.id.clone().unwrap_or_default();
+    let children = files::list::list_files(
+        hub,
+        &files::list::ListFilesConfig {
+            query: files::list::ListQuery::FilesInFolder { folder_id },
+            order_by: files::list::ListSortOrder::default(),
+            scope: files::list::ListScope::default(),
+            max_files: usize::MAX,
+        },
+    )
+    .await
+    .map_err(RequestError::List)?;
+
+    let base_path: String = path_resolver::components(path)
+        .map(|name| format!("/{}", utf8_percent_encode(name, NON_ALPHANUMERIC)))
+        .collect();
+
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{0}</title></head><body>\n<h1>{0}</h1>\n<ul>\n",
+        escape_html(folder.name.as_deref().unwrap_or("/"))
+    );
+    for child in children {
+        let name = child.name.clone().unwrap_or_default();
+        let suffix = if drive_file::is_directory(&child) {
+            "/"
+        } else {
+            ""
+        };
+        let href = if by_id {
+            format!("/?{}={}", ID_PARAM, child.id.clone().unwrap_or_default())
+        } else {
+            format!(
+                "{}/{}",
+                base_path,
+                utf8_percent_encode(&name, NON_ALPHANUMERIC)
+            )
+        };
+        html.push_str(&format!(
+            "<li><a href=\"{}\">{}{}</a></li>\n",
+            href,
+            escape_html(&name),
+            suffix
+        ));
+    }
+    html.push_str("</ul>\n</body></html>\n");
+
+    Response::builder()
+        .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+        .body(Body::from(html))
+        .map_err(RequestError::Response)
+}
+
+// Uploads the request body to the path, replacing the content of an existing file
+// with the same name. `/?id=<FILE_ID>` updates the content of that file
+async fn upload(
+    hub: &Hub,
+    root_id: &str,
+    path: &str,
+    file_id: Option<&str>,
+    metrics: Option<&Arc<Metrics>>,
+    req: Request<Body>,
+) -> Result<Response<Body>, RequestError> {
+    let (existing, name, parent_id) = match file_id {
+        Some(file_id) => {
+            let file = get_file_in_root(hub, root_id, file_id).await?;
+            let name = file.name.clone().unwrap_or_default();
+            (Some(file), name, None)
+        }
+
+        None => {
+            let components: Vec<&str> = path_resolver::components(path).collect();
+            let (name, parent_components) =
+                components.split_last().ok_or(RequestError::InvalidPath)?;
+            let parent = path_resolver::resolve(hub, root_id, &parent_components.join("/"))
+                .await
+                .map_err(RequestError::List)?
+                .filter(drive_file::is_directory)
+                .ok_or_else(|| RequestError::NotFound(parent_components.join("/")))?;
+            let parent_id = parent.id.unwrap_or_default();
+            let existing = path_resolver::find_child(hub, &parent_id, name)
+                .await
+                .map_err(RequestError::List)?;
+            (existing, name.to_string(), Some(parent_id))
+        }
+    };
+
+    if let Some(file) = &existing {
+        if drive_file::is_directory(file) || !drive_file::is_binary(file) {
+            return Err(RequestError::NotBinary(path.to_string()));
+        }
+    }
+
+    let mime_type = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<mime::Mime>().ok())
+        .unwrap_or_else(|| mime_guess::from_path(&name).first_or_octet_stream());
+
+    // The uploader needs to seek, so the body is spooled to a temporary file first
+    let mut tmp_file = tempfile::tempfile().map_err(RequestError::Io)?;
+    let mut body = req.into_body();
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk.map_err(RequestError::Body)?;
+        tmp_file.write_all(&chunk).map_err(RequestError::Io)?;
+    }
+    let size = tmp_file.seek(SeekFrom::End(0)).map_err(RequestError::Io)?;
+    tmp_file
+        .seek(SeekFrom::Start(0))
+        .map_err(RequestError::Io)?;
+
+    let file_info = FileInfo {
+        name,
+        mime_type,
+        parents: parent_id.map(|id| vec![id]),
+        size,
+    };
+
+    let (status, file) = match existing {
+        Some(file) => {
+            let file_id = file.id.unwrap_or_default();
+            let file = files::update::update_file(
+                hub,
+                tmp_file,
+                &file_id,
+                file_info,
+                UploadDelegateConfig::default(),
+            )
+            .await
+            .map_err(RequestError::Upload)?;
+            (StatusCode::OK, file)
+        }
+
+        None => {
+            let file = files::upload::upload_file(
+                hub,
+                tmp_file,
+                None,
+                file_info,
+                UploadDelegateConfig::default(),
+            )
+            .await
+            .map_err(RequestError::Upload)?;
+            (StatusCode::CREATED, file)
+        }
+    };
+
+    if let Some(metrics) = metrics {
+        metrics.upload(size);
+    }
+
+    Ok(text_response(
+        status,
+        format!("{}\n", file.id.unwrap_or_default()),
+    ))
+}
+
+fn text_response(status: StatusCode, text: String) -> Response<Body> {
+    let mut response = Response::new(Body::from(text));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("text/plain; charset=utf-8"),
+    );
+    response
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    Bind(SocketAddr, hyper::Error),
+    Server(hyper::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::Bind(address, err) => write!(f, "Failed to listen on {}: {}", address, err),
+            Error::Server(err) => write!(f, "Server failed: {}", err),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum RequestError {
+    InvalidPath,
+    NotFound(String),
+    NotBinary(String),
+    MethodNotAllowed,
+    ReadOnly,
+    List(files::list::Error),
+    Drive(hub::RequestError),
+    Upload(google_drive3::Error),
+    Body(hyper::Error),
+    Io(io::Error),
+    Response(hyper::http::Error),
+}
+
+impl RequestError {
+    // The label of the error in the metrics
+    fn reason(&self) -> &'static str {
+        match self {
+            RequestError::InvalidPath => "invalid_path",
+            RequestError::NotFound(_) => "not_found",
+            RequestError::NotBinary(_) => "not_binary",
+            RequestError::MethodNotAllowed => "method_not_allowed",
+            RequestError::ReadOnly => "read_only",
+            RequestError::List(_) | RequestError::Drive(_) | RequestError::Upload(_) => "drive",
+            RequestError::Body(_) => "request_body",
+            RequestError::Io(_) | RequestError::Response(_) => "internal",
+        }
+    }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            RequestError::InvalidPath => StatusCode::BAD_REQUEST,
+            RequestError::NotFound(_) => StatusCode::NOT_FOUND,
+            RequestError::NotBinary(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            RequestError::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            RequestError::ReadOnly => StatusCode::FORBIDDEN,
+            RequestError::Body(_) => StatusCode::BAD_REQUEST,
+            RequestError::List(_) | RequestError::Drive(_) | RequestError::Upload(_) => {
+                StatusCode::BAD_GATEWAY
+            }
+            RequestError::Io(_) | RequestError::Response(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl Display for RequestError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequestError::InvalidPath => write!(f, "Invalid path"),
+            RequestError::NotFound(path) => write!(f, "'{}' not found", path),
+            RequestError::NotBinary(path) => write!(
+                f,
+                "'{}' is not a regular file, Google Docs have to be exported",
+                path
+            ),
+            RequestError::MethodNotAllowed => write!(f, "Only GET, HEAD and PUT are supported"),
+            RequestError::ReadOnly => write!(f, "Uploads are disabled, the server is read-only"),
+            RequestError::List(err) => write!(f, "{}", err),
+            RequestError::Drive(err) => write!(f, "{}", err),
+            RequestError::Upload(err) => write!(f, "Failed to upload file: {}", err),
+            RequestError::Body(err) => write!(f, "Failed to read request body: {}", err),
+            RequestError::Io(err) => write!(f, "{}", err),
+            RequestError::Response(err) => write!(f, "Failed to build response: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockServer;
+
+    #[test]
+    fn id_param_is_read_from_the_query() {
+        assert_eq!(id_param("id=1AbC"), Some("1AbC".to_string()));
+        assert_eq!(id_param("x=1&id=1AbC"), Some("1AbC".to_string()));
+        assert_eq!(id_param("id="), None);
+        assert_eq!(id_param(""), None);
+    }
+
+    #[tokio::test]
+    async fn files_outside_the_served_folder_are_not_found_by_id() {
+        let server = MockServer::start().await;
+        server.respond_fixture(
+            "GET",
+            "/drive/v3/files/1ZyXwVuTsRqPoNmLkJiHgFeDcBa",
+            "file_metadata.json",
+        );
+        server.respond_json(
+            "GET",
+            "/drive/v3/files/0AAbCdEfGhIjKUk9PVA",
+            &serde_json::json!({"id": "0AAbCdEfGhIjKUk9PVA", "name": "Shared"}),
+        );
+        let hub = server.hub().await;
+
+        let inside = get_file_in_root(&hub, "0AAbCdEfGhIjKUk9PVA", "1ZyXwVuTsRqPoNmLkJiHgFeDcBa");
+        assert!(inside.await.is_ok());
+
+        let outside = get_file_in_root(
+            &hub,
+            "1AbCdEfGhIjKlMnOpQrStUvWxYz",
+            "1ZyXwVuTsRqPoNmLkJiHgFeDcBa",
+        );
+        assert!(matches!(outside.await, Err(RequestError::NotFound(_))));
+    }
+}
@@ -0,0 +1,193 @@
+use crate::common::api_stats;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+// Counters of a running `gdrive serve`, rendered in the Prometheus text format
+// for `GET /metrics`. They start at zero with the process like Prometheus
+// expects from counters
+#[derive(Default)]
+pub struct Metrics {
+    // (method, status) -> count
+    requests: Mutex<BTreeMap<(String, u16), u64>>,
+    // Reason -> count
+    errors: Mutex<BTreeMap<&'static str, u64>>,
+    downloads: AtomicU64,
+    uploads: AtomicU64,
+    downloaded_bytes: AtomicU64,
+    uploaded_bytes: AtomicU64,
+}
+
+impl Metrics {
+    pub fn request(&self, method: &str, status: u16) {
+        if let Ok(mut requests) = self.requests.lock() {
+            *requests.entry((method.to_string(), status)).or_default() += 1;
+        }
+    }
+
+    pub fn error(&self, reason: &'static str) {
+        if let Ok(mut errors) = self.errors.lock() {
+            *errors.entry(reason).or_default() += 1;
+        }
+    }
+
+    pub fn download(&self) {
+        self.downloads.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Counted while the body is streamed, a client that stops reading only
+    // adds what it got
+    pub fn downloaded(&self, bytes: u64) {
+        self.downloaded_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn upload(&self, bytes: u64) {
+        self.uploads.fetch_add(1, Ordering::Relaxed);
+        self.uploaded_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn render(&self) -> String {
+        let requests = self
+            .requests
+            .lock()
+            .map(|requests| requests.clone())
+            .unwrap_or_default();
+        let errors = self
+            .errors
+            .lock()
+            .map(|errors| errors.clone())
+            .unwrap_or_default();
+
+        let mut out = String::new();
+        write_header(
+            &mut out,
+            "gdrive_serve_requests_total",
+            "Requests answered, by method and status",
+        );
+        for ((method, status), count) in requests {
+            let _ = writeln!(
+                out,
+                "gdrive_serve_requests_total{{method=\"{}\",status=\"{}\"}} {}",
+                method, status, count
+            );
+        }
+
+        write_header(
+            &mut out,
+            "gdrive_serve_errors_total",
+            "Requests that failed, by reason",
+        );
+        for (reason, count) in errors {
+            let _ = writeln!(
+                out,
+                "gdrive_serve_errors_total{{reason=\"{}\"}} {}",
+                reason, count
+            );
+        }
+
+        write_header(
+            &mut out,
+            "gdrive_transfers_total",
+            "Files streamed from or uploaded to Drive",
+        );
+        write_directions(
+            &mut out,
+            "gdrive_transfers_total",
+            &self.downloads,
+            &self.uploads,
+        );
+
+        write_header(
+            &mut out,
+            "gdrive_transferred_bytes_total",
+            "Bytes streamed from or uploaded to Drive",
+        );
+        write_directions(
+            &mut out,
+            "gdrive_transferred_bytes_total",
+            &self.downloaded_bytes,
+            &self.uploaded_bytes,
+        );
+
+        write_header(
+            &mut out,
+            "gdrive_api_calls_total",
+            "Drive API calls, by endpoint, which count against the quota",
+        );
+        for (endpoint, count) in api_stats::calls() {
+            let _ = writeln!(
+                out,
+                "gdrive_api_calls_total{{endpoint=\"{}\"}} {}",
+                escape_label(&endpoint),
+                count
+            );
+        }
+
+        write_header(
+            &mut out,
+            "gdrive_api_retries_total",
+            "Drive API calls that were retried",
+        );
+        let _ = writeln!(out, "gdrive_api_retries_total {}", api_stats::retries());
+
+        out
+    }
+}
+
+fn write_header(out: &mut String, name: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} counter", name);
+}
+
+fn write_directions(out: &mut String, name: &str, download: &AtomicU64, upload: &AtomicU64) {
+    for (direction, value) in [("download", download), ("upload", upload)] {
+        let _ = writeln!(
+            out,
+            "{}{{direction=\"{}\"}} {}",
+            name,
+            direction,
+            value.load(Ordering::Relaxed)
+        );
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_groups_requests_and_directions() {
+        let metrics = Metrics::default();
+        metrics.request("GET", 200);
+        metrics.request("GET", 200);
+        metrics.request("PUT", 502);
+        metrics.error("drive");
+        metrics.download();
+        metrics.downloaded(10);
+        metrics.downloaded(5);
+        metrics.upload(7);
+
+        let text = metrics.render();
+
+        assert!(text.contains("# TYPE gdrive_serve_requests_total counter\n"));
+        assert!(text.contains("gdrive_serve_requests_total{method=\"GET\",status=\"200\"} 2\n"));
+        assert!(text.contains("gdrive_serve_requests_total{method=\"PUT\",status=\"502\"} 1\n"));
+        assert!(text.contains("gdrive_serve_errors_total{reason=\"drive\"} 1\n"));
+        assert!(text.contains("gdrive_transfers_total{direction=\"download\"} 1\n"));
+        assert!(text.contains("gdrive_transferred_bytes_total{direction=\"download\"} 15\n"));
+        assert!(text.contains("gdrive_transferred_bytes_total{direction=\"upload\"} 7\n"));
+    }
+
+    #[test]
+    fn labels_are_escaped() {
+        assert_eq!(escape_label("a\"b\\c"), "a\\\"b\\\\c");
+    }
+}
@@ -0,0 +1,10 @@
+pub mod config;
+pub mod plan;
+pub mod run;
+pub mod scan;
+pub mod state;
+pub mod status;
+
+pub use config::run_all;
+pub use run::run;
+pub use status::status;
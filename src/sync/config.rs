@@ -0,0 +1,201 @@
+use crate::common::alerts;
+use crate::common::file_filter::FileFilter;
+use crate::sync::plan::Direction;
+use crate::sync::run;
+use crate::sync::run::ConflictPolicy;
+use crate::sync::state;
+use crate::sync::state::State;
+use google_drive3::chrono;
+use serde::Deserialize;
+use std::error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+pub const DEFAULT_PATH: &str = "gdrive-sync.toml";
+
+const UNITS: [(&str, i64); 5] = [
+    ("w", 7 * 24 * 60 * 60),
+    ("d", 24 * 60 * 60),
+    ("h", 60 * 60),
+    ("m", 60),
+    ("s", 1),
+];
+
+// Sync pairs that are synced in order by `gdrive sync run`, i.e.
+//
+// [[pair]]
+// local = "/home/me/Photos"
+// remote = "<DIRECTORY_ID>"
+// direction = "upload"
+// schedule = "1d"
+// mime-exclude = ["video/*"]
+#[derive(Debug, Deserialize)]
+pub struct SyncConfig {
+    #[serde(default, rename = "pair")]
+    pub pairs: Vec<Pair>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Pair {
+    // Relative paths are relative to the config file
+    pub local: PathBuf,
+    pub remote: String,
+    #[serde(default)]
+    pub direction: Direction,
+    #[serde(default)]
+    pub on_conflict: ConflictPolicy,
+    // The pair is skipped until this long has passed since its last sync
+    pub schedule: Option<Schedule>,
+    #[serde(flatten)]
+    pub filter: FileFilter,
+}
+
+impl SyncConfig {
+    pub fn load(path: &Path) -> Result<SyncConfig, Error> {
+        let content =
+            fs::read_to_string(path).map_err(|err| Error::Read(path.to_path_buf(), err))?;
+        toml::from_str(&content).map_err(|err| Error::Parse(path.to_path_buf(), err))
+    }
+}
+
+pub async fn run_all(config_path: &Path) -> Result<(), Error> {
+    let config = SyncConfig::load(config_path)?;
+    if config.pairs.is_empty() {
+        return Err(Error::NoPairs(config_path.to_path_buf()));
+    }
+
+    let base_path = config_path.parent().unwrap_or(Path::new(""));
+    let mut failed = 0;
+
+    for pair in config.pairs {
+        let local_path = base_path.join(&pair.local);
+
+        if let Some(schedule) = pair.schedule {
+            let next_sync = last_synced(&local_path, &pair.remote)? + schedule.0;
+            let now = chrono::Utc::now().timestamp();
+            if now < next_sync {
+                println!(
+                    "Skipping '{}', it is not due for another {}",
+                    local_path.display(),
+                    Schedule(next_sync - now)
+                );
+                continue;
+            }
+        }
+
+        let result = run::run(run::Config {
+            local_path: local_path.clone(),
+            folder_id: pair.remote,
+            on_conflict: pair.on_conflict,
+            direction: pair.direction,
+            filter: pair.filter,
+        })
+        .await;
+
+        // A failing pair doesn't stop the others from syncing
+        if let Err(err) = result {
+            eprintln!("Error: Failed to sync '{}': {}", local_path.display(), err);
+            alerts::failure(&format!("Sync of '{}'", local_path.display()), &err).await;
+            failed += 1;
+        }
+        println!();
+    }
+    alerts::check_quota().await;
+
+    if failed > 0 {
+        Err(Error::Failed(failed))
+    } else {
+        Ok(())
+    }
+}
+
+fn last_synced(local_path: &Path, folder_id: &str) -> Result<i64, Error> {
+    // Missing directories have no state, run reports them as an error
+    let local_path = match local_path.canonicalize() {
+        Ok(path) => path,
+        Err(_) => return Ok(0),
+    };
+
+    let state_path = state::state_path(&local_path, folder_id).map_err(Error::State)?;
+    let state = State::load(&state_path).map_err(Error::State)?;
+    Ok(state.last_synced)
+}
+
+// Time between syncs in seconds, parsed from values like `30m`, `12h` or `1d`
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Schedule(pub i64);
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value = s.trim();
+        let seconds = UNITS.iter().find_map(|(suffix, multiplier)| {
+            value
+                .strip_suffix(suffix)
+                .and_then(|number| number.trim().parse::<i64>().ok())
+                .filter(|number| *number > 0)
+                .map(|number| number * multiplier)
+        });
+
+        seconds.map(Schedule).ok_or_else(|| {
+            format!(
+                "'{}' is not a valid schedule, use a number followed by s, m, h, d or w",
+                s
+            )
+        })
+    }
+}
+
+impl TryFrom<String> for Schedule {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl Display for Schedule {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let (suffix, multiplier) = UNITS
+            .iter()
+            .find(|(_, multiplier)| self.0 >= *multiplier)
+            .unwrap_or(&("s", 1));
+        write!(f, "{}{}", self.0 / multiplier, suffix)
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Read(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    NoPairs(PathBuf),
+    State(state::Error),
+    Failed(usize),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Read(path, err) => {
+                write!(f, "Failed to read '{}': {}", path.display(), err)
+            }
+            Error::Parse(path, err) => {
+                write!(f, "Failed to parse '{}': {}", path.display(), err)
+            }
+            Error::NoPairs(path) => write!(f, "No sync pairs in '{}'", path.display()),
+            Error::State(err) => write!(f, "{}", err),
+            Error::Failed(count) => write!(f, "{} sync pair(s) failed", count),
+        }
+    }
+}
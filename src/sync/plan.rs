@@ -0,0 +1,481 @@
+use crate::sync::scan::LocalFile;
+use crate::sync::scan::RemoteTree;
+use crate::sync::state::State;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Action {
+    Upload { key: String },
+    UpdateRemote { key: String, file_id: String },
+    Download { key: String, file_id: String },
+    DeleteLocal { key: String },
+    TrashRemote { key: String, file_id: String },
+    Conflict { key: String, file_id: String },
+    // Both sides agree, only the state needs updating
+    Record { key: String, file_id: String },
+    Forget { key: String },
+}
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Direction {
+    #[default]
+    TwoWay,
+    // Drive follows the local directory
+    Upload,
+    // The local directory follows Drive
+    Download,
+}
+
+impl Display for Direction {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Direction::TwoWay => write!(f, "two-way"),
+            Direction::Upload => write!(f, "upload"),
+            Direction::Download => write!(f, "download"),
+        }
+    }
+}
+
+impl FromStr for Direction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "two-way" => Ok(Direction::TwoWay),
+            "upload" => Ok(Direction::Upload),
+            "download" => Ok(Direction::Download),
+            _ => Err(format!(
+                "'{}' is not a valid direction, valid directions are: two-way, upload, download",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Summary {
+    pub uploads: usize,
+    pub downloads: usize,
+    pub deletions: usize,
+    pub conflicts: usize,
+}
+
+impl Summary {
+    pub fn from_actions(actions: &[Action]) -> Summary {
+        let mut summary = Summary::default();
+        for action in actions {
+            match action {
+                Action::Upload { .. } | Action::UpdateRemote { .. } => summary.uploads += 1,
+                Action::Download { .. } => summary.downloads += 1,
+                Action::DeleteLocal { .. } | Action::TrashRemote { .. } => summary.deletions += 1,
+                Action::Conflict { .. } => summary.conflicts += 1,
+                Action::Record { .. } | Action::Forget { .. } => {}
+            }
+        }
+        summary
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.uploads + self.downloads + self.deletions + self.conflicts == 0
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} to upload, {} to download, {} to delete, {} conflicts",
+            self.uploads, self.downloads, self.deletions, self.conflicts
+        )
+    }
+}
+
+// Compares both sides against the state from the last sync. A side has changed
+// if its md5 differs from the state, a side without the file has deleted it if
+// the state has it
+pub fn plan(local: &HashMap<String, LocalFile>, remote: &RemoteTree, state: &State) -> Vec<Action> {
+    let keys: BTreeSet<&String> = local
+        .keys()
+        .chain(remote.files.keys())
+        .chain(state.files.keys())
+        .collect();
+
+    keys.into_iter()
+        .filter_map(|key| {
+            let local_file = local.get(key);
+            let remote_file = remote.files.get(key);
+            let entry = state.files.get(key);
+            let key = key.clone();
+
+            match (local_file, remote_file, entry) {
+                (Some(local_file), Some(remote_file), entry) => {
+                    let file_id = remote_file.id.clone();
+                    if local_file.md5 == remote_file.md5 {
+                        let up_to_date = entry
+                            .map(|entry| {
+                                entry.file_id == remote_file.id
+                                    && entry.md5 == local_file.md5
+                                    && entry.local_modified == local_file.modified
+                                    && entry.remote_modified == remote_file.modified
+                            })
+                            .unwrap_or(false);
+                        return (!up_to_date).then_some(Action::Record { key, file_id });
+                    }
+
+                    let entry = match entry {
+                        Some(entry) => entry,
+                        None => return Some(Action::Conflict { key, file_id }),
+                    };
+                    let local_changed = local_file.md5 != entry.md5;
+                    let remote_changed = remote_file.md5 != entry.md5;
+
+                    match (local_changed, remote_changed) {
+                        (true, false) => Some(Action::UpdateRemote { key, file_id }),
+                        (false, true) => Some(Action::Download { key, file_id }),
+                        _ => Some(Action::Conflict { key, file_id }),
+                    }
+                }
+
+                (Some(_), None, None) => Some(Action::Upload { key }),
+
+                // Deleted on Drive, the local copy goes too unless it was edited since
+                (Some(local_file), None, Some(entry)) => {
+                    if local_file.md5 == entry.md5 {
+                        Some(Action::DeleteLocal { key })
+                    } else {
+                        Some(Action::Upload { key })
+                    }
+                }
+
+                (None, Some(remote_file), None) => Some(Action::Download {
+                    key,
+                    file_id: remote_file.id.clone(),
+                }),
+
+                // Deleted locally, the Drive file is trashed unless it was edited since
+                (None, Some(remote_file), Some(entry)) => {
+                    let file_id = remote_file.id.clone();
+                    if remote_file.md5 == entry.md5 {
+                        Some(Action::TrashRemote { key, file_id })
+                    } else {
+                        Some(Action::Download { key, file_id })
+                    }
+                }
+
+                (None, None, Some(_)) => Some(Action::Forget { key }),
+
+                (None, None, None) => None,
+            }
+        })
+        .collect()
+}
+
+// Turns a two-way plan into a one-way plan. The source side always wins, changes
+// on the other side are overwritten and files that only exist there are left alone
+pub fn restrict(
+    actions: Vec<Action>,
+    direction: Direction,
+    local: &HashMap<String, LocalFile>,
+) -> Vec<Action> {
+    actions
+        .into_iter()
+        .filter_map(|action| match (direction, action) {
+            (Direction::TwoWay, action) => Some(action),
+
+            (Direction::Upload, Action::Download { key, file_id })
+            | (Direction::Upload, Action::Conflict { key, file_id }) => local
+                .contains_key(&key)
+                .then_some(Action::UpdateRemote { key, file_id }),
+            (Direction::Upload, Action::DeleteLocal { key }) => Some(Action::Upload { key }),
+
+            (Direction::Download, Action::UpdateRemote { key, file_id })
+            | (Direction::Download, Action::Conflict { key, file_id })
+            | (Direction::Download, Action::TrashRemote { key, file_id }) => {
+                Some(Action::Download { key, file_id })
+            }
+            (Direction::Download, Action::Upload { .. }) => None,
+
+            (_, action) => Some(action),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::scan::RemoteFile;
+    use crate::sync::state::Entry;
+    use std::path::PathBuf;
+
+    const KEY: &str = "docs/notes.txt";
+    const FILE_ID: &str = "1ZyXwVuTsRqPoNmLkJiHgFeDcBa";
+
+    type Local = HashMap<String, LocalFile>;
+
+    fn local(md5: &str) -> Local {
+        let file = LocalFile {
+            path: PathBuf::from(KEY),
+            size: 4,
+            modified: 100,
+            md5: md5.to_string(),
+        };
+        HashMap::from([(KEY.to_string(), file)])
+    }
+
+    fn no_local() -> Local {
+        HashMap::new()
+    }
+
+    fn remote(md5: &str) -> RemoteTree {
+        let file = RemoteFile {
+            id: FILE_ID.to_string(),
+            md5: md5.to_string(),
+            size: 4,
+            modified: 100,
+        };
+        RemoteTree {
+            files: HashMap::from([(KEY.to_string(), file)]),
+            ..RemoteTree::default()
+        }
+    }
+
+    fn no_remote() -> RemoteTree {
+        RemoteTree::default()
+    }
+
+    fn state(md5: &str) -> State {
+        let entry = Entry {
+            file_id: FILE_ID.to_string(),
+            md5: md5.to_string(),
+            size: 4,
+            local_modified: 100,
+            remote_modified: 100,
+        };
+        State {
+            files: HashMap::from([(KEY.to_string(), entry)]),
+            ..State::default()
+        }
+    }
+
+    fn first_run() -> State {
+        State::default()
+    }
+
+    fn upload() -> Action {
+        Action::Upload {
+            key: KEY.to_string(),
+        }
+    }
+
+    fn update_remote() -> Action {
+        let (key, file_id) = (KEY.to_string(), FILE_ID.to_string());
+        Action::UpdateRemote { key, file_id }
+    }
+
+    fn download() -> Action {
+        let (key, file_id) = (KEY.to_string(), FILE_ID.to_string());
+        Action::Download { key, file_id }
+    }
+
+    fn delete_local() -> Action {
+        Action::DeleteLocal {
+            key: KEY.to_string(),
+        }
+    }
+
+    fn trash_remote() -> Action {
+        let (key, file_id) = (KEY.to_string(), FILE_ID.to_string());
+        Action::TrashRemote { key, file_id }
+    }
+
+    fn conflict() -> Action {
+        let (key, file_id) = (KEY.to_string(), FILE_ID.to_string());
+        Action::Conflict { key, file_id }
+    }
+
+    fn record() -> Action {
+        let (key, file_id) = (KEY.to_string(), FILE_ID.to_string());
+        Action::Record { key, file_id }
+    }
+
+    fn forget() -> Action {
+        Action::Forget {
+            key: KEY.to_string(),
+        }
+    }
+
+    #[test]
+    fn actions_follow_the_changes_since_the_last_sync() {
+        let cases = [
+            ("unchanged", local("a"), remote("a"), state("a"), None),
+            (
+                "edited on both sides",
+                local("b"),
+                remote("c"),
+                state("a"),
+                Some(conflict()),
+            ),
+            (
+                "same edit on both sides",
+                local("b"),
+                remote("b"),
+                state("a"),
+                Some(record()),
+            ),
+            (
+                "edited locally",
+                local("b"),
+                remote("a"),
+                state("a"),
+                Some(update_remote()),
+            ),
+            (
+                "edited on Drive",
+                local("a"),
+                remote("b"),
+                state("a"),
+                Some(download()),
+            ),
+            (
+                "deleted on Drive",
+                local("a"),
+                no_remote(),
+                state("a"),
+                Some(delete_local()),
+            ),
+            (
+                "deleted on Drive, edited locally",
+                local("b"),
+                no_remote(),
+                state("a"),
+                Some(upload()),
+            ),
+            (
+                "deleted locally",
+                no_local(),
+                remote("a"),
+                state("a"),
+                Some(trash_remote()),
+            ),
+            (
+                "deleted locally, edited on Drive",
+                no_local(),
+                remote("b"),
+                state("a"),
+                Some(download()),
+            ),
+            (
+                "deleted on both sides",
+                no_local(),
+                no_remote(),
+                state("a"),
+                Some(forget()),
+            ),
+            (
+                "first run, same content",
+                local("a"),
+                remote("a"),
+                first_run(),
+                Some(record()),
+            ),
+            (
+                "first run, different content",
+                local("a"),
+                remote("b"),
+                first_run(),
+                Some(conflict()),
+            ),
+            (
+                "first run, only local",
+                local("a"),
+                no_remote(),
+                first_run(),
+                Some(upload()),
+            ),
+            (
+                "first run, only on Drive",
+                no_local(),
+                remote("a"),
+                first_run(),
+                Some(download()),
+            ),
+        ];
+
+        for (name, local, remote, state, expected) in cases {
+            let actions = plan(&local, &remote, &state);
+            assert_eq!(actions, Vec::from_iter(expected), "{}", name);
+        }
+    }
+
+    #[test]
+    fn one_way_plans_let_the_source_side_win() {
+        let cases = [
+            (Direction::TwoWay, conflict(), local("a"), Some(conflict())),
+            (
+                Direction::Upload,
+                conflict(),
+                local("a"),
+                Some(update_remote()),
+            ),
+            (
+                Direction::Upload,
+                download(),
+                local("a"),
+                Some(update_remote()),
+            ),
+            // Only on Drive, an upload leaves it alone
+            (Direction::Upload, download(), no_local(), None),
+            (
+                Direction::Upload,
+                delete_local(),
+                local("a"),
+                Some(upload()),
+            ),
+            (
+                Direction::Upload,
+                trash_remote(),
+                no_local(),
+                Some(trash_remote()),
+            ),
+            (
+                Direction::Download,
+                conflict(),
+                local("a"),
+                Some(download()),
+            ),
+            (
+                Direction::Download,
+                update_remote(),
+                local("a"),
+                Some(download()),
+            ),
+            (
+                Direction::Download,
+                trash_remote(),
+                no_local(),
+                Some(download()),
+            ),
+            (Direction::Download, upload(), local("a"), None),
+            (
+                Direction::Download,
+                delete_local(),
+                local("a"),
+                Some(delete_local()),
+            ),
+        ];
+
+        for (direction, action, local, expected) in cases {
+            let name = format!("{:?} with {}", action, direction);
+            let actions = restrict(vec![action], direction, &local);
+            assert_eq!(actions, Vec::from_iter(expected), "{}", name);
+        }
+    }
+}
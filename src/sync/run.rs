@@ -0,0 +1,617 @@
+use crate::common::delegate::UploadDelegateConfig;
+use crate::common::file_filter::FileFilter;
+use crate::common::file_info::FileInfo;
+use crate::common::hash_writer;
+use crate::common::hub_helper;
+use crate::files;
+use crate::files::delete::Operation;
+use crate::files::download;
+use crate::files::mkdir;
+use crate::hub::Hub;
+use crate::sync::plan;
+use crate::sync::plan::Action;
+use crate::sync::plan::Direction;
+use crate::sync::scan;
+use crate::sync::scan::LocalFile;
+use crate::sync::scan::RemoteFile;
+use crate::sync::scan::RemoteTree;
+use crate::sync::state;
+use crate::sync::state::State;
+use async_recursion::async_recursion;
+use google_drive3::chrono;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::UNIX_EPOCH;
+
+pub struct Config {
+    pub local_path: PathBuf,
+    pub folder_id: String,
+    pub on_conflict: ConflictPolicy,
+    pub direction: Direction,
+    pub filter: FileFilter,
+}
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictPolicy {
+    #[default]
+    Newest,
+    Prompt,
+}
+
+impl Display for ConflictPolicy {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ConflictPolicy::Newest => write!(f, "newest"),
+            ConflictPolicy::Prompt => write!(f, "prompt"),
+        }
+    }
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "newest" => Ok(ConflictPolicy::Newest),
+            "prompt" => Ok(ConflictPolicy::Prompt),
+            _ => Err(format!(
+                "'{}' is not a valid conflict policy, valid policies are: newest, prompt",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Side {
+    Local,
+    Remote,
+}
+
+pub async fn run(config: Config) -> Result<(), Error> {
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+
+    if !config.local_path.is_dir() {
+        return Err(Error::NotADirectory(config.local_path.clone()));
+    }
+    let local_path = config
+        .local_path
+        .canonicalize()
+        .map_err(|err| Error::Canonicalize(config.local_path.clone(), err))?;
+
+    let state_path = state::state_path(&local_path, &config.folder_id).map_err(Error::State)?;
+    let mut state = State::load(&state_path).map_err(Error::State)?;
+    state.local_path = local_path.clone();
+    state.folder_id = config.folder_id.clone();
+    state.filter = config.filter.clone();
+    state.direction = config.direction;
+
+    println!("Comparing '{}' with Drive", local_path.display());
+    let mut local = scan::scan_local(&local_path, &state, &config.filter).map_err(Error::Scan)?;
+    let mut remote = scan::scan_remote(&hub, &config.folder_id, &config.filter)
+        .await
+        .map_err(Error::Scan)?;
+    scan::drop_excluded(&mut local, &mut remote);
+
+    let actions = plan::restrict(
+        plan::plan(&local.files, &remote, &state),
+        config.direction,
+        &local.files,
+    );
+    if actions.is_empty() {
+        state.last_synced = chrono::Utc::now().timestamp();
+        state.save(&state_path).map_err(Error::State)?;
+        println!("Everything is up to date");
+        return Ok(());
+    }
+
+    let mut syncer = Syncer {
+        hub: &hub,
+        root: local_path,
+        local: local.files,
+        remote,
+        state,
+        on_conflict: config.on_conflict,
+    };
+
+    // The state is saved even if an action fails, so the actions that did
+    // complete aren't mistaken for changes on the next run
+    let mut result = Ok(());
+    for action in actions {
+        result = syncer.apply(action).await;
+        if result.is_err() {
+            break;
+        }
+    }
+    if result.is_ok() {
+        syncer.state.last_synced = chrono::Utc::now().timestamp();
+    }
+    syncer.state.save(&state_path).map_err(Error::State)?;
+    result?;
+
+    println!("Sync complete");
+    Ok(())
+}
+
+struct Syncer<'a> {
+    hub: &'a Hub,
+    root: PathBuf,
+    local: HashMap<String, LocalFile>,
+    remote: RemoteTree,
+    state: State,
+    on_conflict: ConflictPolicy,
+}
+
+impl<'a> Syncer<'a> {
+    async fn apply(&mut self, action: Action) -> Result<(), Error> {
+        match action {
+            Action::Upload { key } => {
+                println!("Uploading '{}'", key);
+                self.upload(&key).await
+            }
+
+            Action::UpdateRemote { key, file_id } => {
+                println!("Updating '{}' on Drive", key);
+                self.update_remote(&key, &file_id).await
+            }
+
+            Action::Download { key, file_id } => {
+                println!("Downloading '{}'", key);
+                self.download(&key, &file_id).await
+            }
+
+            Action::DeleteLocal { key } => {
+                println!("Deleting '{}', it was deleted on Drive", key);
+                let path = self.local_path(&key)?;
+                fs::remove_file(&path).map_err(|err| Error::Remove(path, err))?;
+                self.state.files.remove(&key);
+                Ok(())
+            }
+
+            Action::TrashRemote { key, file_id } => {
+                println!("Trashing '{}' on Drive, it was deleted locally", key);
+                self.trash_remote(&key, &file_id).await
+            }
+
+            Action::Conflict { key, file_id } => self.resolve_conflict(&key, &file_id).await,
+
+            Action::Record { key, file_id } => {
+                let local_file = self.local_file(&key)?;
+                let remote_file = self.remote_file(&key)?;
+                self.record(&key, &file_id, &local_file, remote_file.modified);
+                Ok(())
+            }
+
+            Action::Forget { key } => {
+                self.state.files.remove(&key);
+                Ok(())
+            }
+        }
+    }
+
+    async fn upload(&mut self, key: &str) -> Result<(), Error> {
+        let local_file = self.local_file(key)?;
+        let parent_id = self.ensure_remote_folder(scan::parent_key(key)).await?;
+
+        let os_file = fs::File::open(&local_file.path)
+            .map_err(|err| Error::OpenFile(local_file.path.clone(), err))?;
+        let file = files::upload::upload_file(
+            self.hub,
+            os_file,
+            None,
+            file_info(key, &local_file, Some(vec![parent_id])),
+            UploadDelegateConfig::default(),
+        )
+        .await
+        .map_err(Error::Upload)?;
+
+        let file_id = file.id.clone().unwrap_or_default();
+        self.record(key, &file_id, &local_file, remote_modified(&file));
+        Ok(())
+    }
+
+    async fn update_remote(&mut self, key: &str, file_id: &str) -> Result<(), Error> {
+        let local_file = self.local_file(key)?;
+
+        let os_file = fs::File::open(&local_file.path)
+            .map_err(|err| Error::OpenFile(local_file.path.clone(), err))?;
+        let file = files::update::update_file(
+            self.hub,
+            os_file,
+            file_id,
+            file_info(key, &local_file, None),
+            UploadDelegateConfig::default(),
+        )
+        .await
+        .map_err(Error::Upload)?;
+
+        self.record(key, file_id, &local_file, remote_modified(&file));
+        Ok(())
+    }
+
+    async fn download(&mut self, key: &str, file_id: &str) -> Result<(), Error> {
+        let remote_file = self.remote_file(key)?;
+        let path = self.local_path(key)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|err| Error::CreateDirectory(dir.to_path_buf(), err))?;
+        }
+
+        let body = download::download_file(self.hub, file_id)
+            .await
+            .map_err(Error::DownloadFile)?;
+        download::save_body_to_file(body, &path, Some(remote_file.md5.clone()))
+            .await
+            .map_err(Error::Download)?;
+
+        let local_file = read_local_file(&path)?;
+        self.local.insert(key.to_string(), local_file.clone());
+        self.record(key, file_id, &local_file, remote_file.modified);
+        Ok(())
+    }
+
+    async fn trash_remote(&mut self, key: &str, file_id: &str) -> Result<(), Error> {
+        let parents: Vec<String> = self
+            .remote
+            .folders
+            .get(scan::parent_key(key))
+            .cloned()
+            .into_iter()
+            .collect();
+        let name = scan::file_name(key);
+        let journal_error =
+            files::delete::apply_and_record(self.hub, file_id, name, &parents, Operation::Trash)
+                .await
+                .map_err(Error::Trash)?;
+        if let Some(err) = journal_error {
+            eprintln!("Warning: {}", err);
+        }
+
+        self.state.files.remove(key);
+        Ok(())
+    }
+
+    // The losing version is kept next to the file as a conflicted copy on both
+    // sides, the winning version ends up at the original path
+    async fn resolve_conflict(&mut self, key: &str, file_id: &str) -> Result<(), Error> {
+        let local_file = self.local_file(key)?;
+        let remote_file = self.remote_file(key)?;
+
+        let winner = pick_winner(self.on_conflict, &local_file, &remote_file, || {
+            prompt_winner(key)
+        })
+        .map_err(Error::Prompt)?;
+
+        let copy_key = conflicted_copy_key(key);
+        let copy_path = self.local_path(&copy_key)?;
+        println!(
+            "Conflict in '{}', keeping the {} version and saving the other as '{}'",
+            key,
+            match winner {
+                Side::Local => "local",
+                Side::Remote => "Drive",
+            },
+            copy_key
+        );
+
+        match winner {
+            Side::Local => {
+                let body = download::download_file(self.hub, file_id)
+                    .await
+                    .map_err(Error::DownloadFile)?;
+                download::save_body_to_file(body, &copy_path, Some(remote_file.md5.clone()))
+                    .await
+                    .map_err(Error::Download)?;
+                self.local
+                    .insert(copy_key.clone(), read_local_file(&copy_path)?);
+
+                self.upload(&copy_key).await?;
+                self.update_remote(key, file_id).await
+            }
+
+            Side::Remote => {
+                fs::rename(&local_file.path, &copy_path)
+                    .map_err(|err| Error::Rename(local_file.path.clone(), err))?;
+                self.local
+                    .insert(copy_key.clone(), read_local_file(&copy_path)?);
+
+                self.upload(&copy_key).await?;
+                self.download(key, file_id).await
+            }
+        }
+    }
+
+    #[async_recursion]
+    async fn ensure_remote_folder(&mut self, key: &str) -> Result<String, Error> {
+        if let Some(folder_id) = self.remote.folders.get(key) {
+            return Ok(folder_id.clone());
+        }
+
+        let parent_id = self.ensure_remote_folder(scan::parent_key(key)).await?;
+        let folder = mkdir::create_directory(
+            self.hub,
+            &mkdir::Config {
+                id: None,
+                name: scan::file_name(key).to_string(),
+                parents: Some(vec![parent_id]),
+                print_only_id: false,
+            },
+            UploadDelegateConfig::default(),
+        )
+        .await
+        .map_err(Error::Mkdir)?;
+
+        let folder_id = folder.id.unwrap_or_default();
+        self.remote
+            .folders
+            .insert(key.to_string(), folder_id.clone());
+        Ok(folder_id)
+    }
+
+    fn record(&mut self, key: &str, file_id: &str, local_file: &LocalFile, remote_modified: i64) {
+        self.state.files.insert(
+            key.to_string(),
+            state::Entry {
+                file_id: file_id.to_string(),
+                md5: local_file.md5.clone(),
+                size: local_file.size,
+                local_modified: local_file.modified,
+                remote_modified,
+            },
+        );
+    }
+
+    // Checked before every local write or delete, the keys of Drive files come
+    // from names that can be anything
+    fn local_path(&self, key: &str) -> Result<PathBuf, Error> {
+        scan::local_path(&self.root, key).ok_or_else(|| Error::UnsafePath(key.to_string()))
+    }
+
+    fn local_file(&self, key: &str) -> Result<LocalFile, Error> {
+        self.local
+            .get(key)
+            .cloned()
+            .ok_or_else(|| Error::Missing(key.to_string()))
+    }
+
+    fn remote_file(&self, key: &str) -> Result<RemoteFile, Error> {
+        self.remote
+            .files
+            .get(key)
+            .cloned()
+            .ok_or_else(|| Error::Missing(key.to_string()))
+    }
+}
+
+fn file_info(key: &str, local_file: &LocalFile, parents: Option<Vec<String>>) -> FileInfo {
+    FileInfo {
+        name: scan::file_name(key).to_string(),
+        mime_type: mime_guess::from_path(&local_file.path).first_or_octet_stream(),
+        parents,
+        size: local_file.size,
+    }
+}
+
+fn remote_modified(file: &google_drive3::api::File) -> i64 {
+    file.modified_time.map(|time| time.timestamp()).unwrap_or(0)
+}
+
+fn read_local_file(path: &Path) -> Result<LocalFile, Error> {
+    let metadata = fs::metadata(path).map_err(|err| Error::OpenFile(path.to_path_buf(), err))?;
+    let md5 =
+        hash_writer::md5_of_file(path).map_err(|err| Error::OpenFile(path.to_path_buf(), err))?;
+
+    Ok(LocalFile {
+        path: path.to_path_buf(),
+        size: metadata.len(),
+        modified: metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0),
+        md5,
+    })
+}
+
+// "dir/report (conflicted copy 2024-01-31 12.00.00).txt" for "dir/report.txt"
+fn conflicted_copy_key(key: &str) -> String {
+    let name = Path::new(scan::file_name(key));
+    let stem = name
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = name
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    let time = chrono::Local::now().format("%Y-%m-%d %H.%M.%S");
+
+    scan::join_key(
+        scan::parent_key(key),
+        &format!("{} (conflicted copy {}){}", stem, time, extension),
+    )
+}
+
+// A tie goes to the local version
+fn pick_winner<F>(
+    policy: ConflictPolicy,
+    local_file: &LocalFile,
+    remote_file: &RemoteFile,
+    prompt: F,
+) -> Result<Side, io::Error>
+where
+    F: FnOnce() -> Result<Side, io::Error>,
+{
+    match policy {
+        ConflictPolicy::Newest if local_file.modified as i64 >= remote_file.modified => {
+            Ok(Side::Local)
+        }
+        ConflictPolicy::Newest => Ok(Side::Remote),
+        ConflictPolicy::Prompt => prompt(),
+    }
+}
+
+fn prompt_winner(key: &str) -> Result<Side, io::Error> {
+    loop {
+        print!(
+            "'{}' changed both locally and on Drive, keep [l]ocal or [d]rive version? ",
+            key
+        );
+        let _ = io::stdout().flush();
+
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "no answer given",
+            ));
+        }
+
+        match input.trim() {
+            "l" | "local" => return Ok(Side::Local),
+            "d" | "drive" => return Ok(Side::Remote),
+            _ => continue,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    NotADirectory(PathBuf),
+    Canonicalize(PathBuf, io::Error),
+    State(state::Error),
+    Scan(scan::Error),
+    Missing(String),
+    OpenFile(PathBuf, io::Error),
+    CreateDirectory(PathBuf, io::Error),
+    Remove(PathBuf, io::Error),
+    Rename(PathBuf, io::Error),
+    Upload(google_drive3::Error),
+    DownloadFile(google_drive3::Error),
+    Download(download::Error),
+    Trash(google_drive3::Error),
+    Mkdir(google_drive3::Error),
+    Prompt(io::Error),
+    UnsafePath(String),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::NotADirectory(path) => write!(f, "'{}' is not a directory", path.display()),
+            Error::Canonicalize(path, err) => {
+                write!(f, "Failed to resolve '{}': {}", path.display(), err)
+            }
+            Error::State(err) => write!(f, "{}", err),
+            Error::Scan(err) => write!(f, "{}", err),
+            Error::Missing(key) => write!(f, "'{}' disappeared during sync", key),
+            Error::OpenFile(path, err) => {
+                write!(f, "Failed to open file '{}': {}", path.display(), err)
+            }
+            Error::CreateDirectory(path, err) => {
+                write!(
+                    f,
+                    "Failed to create directory '{}': {}",
+                    path.display(),
+                    err
+                )
+            }
+            Error::Remove(path, err) => {
+                write!(f, "Failed to delete '{}': {}", path.display(), err)
+            }
+            Error::Rename(path, err) => {
+                write!(f, "Failed to rename '{}': {}", path.display(), err)
+            }
+            Error::Upload(err) => write!(f, "Failed to upload file: {}", err),
+            Error::DownloadFile(err) => write!(f, "Failed to download file: {}", err),
+            Error::Download(err) => write!(f, "{}", err),
+            Error::Trash(err) => write!(f, "Failed to trash file: {}", err),
+            Error::Mkdir(err) => write!(f, "Failed to create directory: {}", err),
+            Error::Prompt(err) => write!(f, "Failed to read answer: {}", err),
+            Error::UnsafePath(key) => {
+                write!(f, "'{}' is not a path inside the synced folder", key)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local_file(modified: u64) -> LocalFile {
+        LocalFile {
+            path: PathBuf::from("notes.txt"),
+            size: 4,
+            modified,
+            md5: String::from("local"),
+        }
+    }
+
+    fn remote_file(modified: i64) -> RemoteFile {
+        RemoteFile {
+            id: String::from("1ZyXwVuTsRqPoNmLkJiHgFeDcBa"),
+            md5: String::from("remote"),
+            size: 4,
+            modified,
+        }
+    }
+
+    fn unasked() -> Result<Side, io::Error> {
+        panic!("the newest policy doesn't prompt")
+    }
+
+    #[test]
+    fn newest_keeps_the_version_changed_last() {
+        let cases = [
+            (200, 100, Side::Local),
+            (100, 200, Side::Remote),
+            (100, 100, Side::Local),
+        ];
+
+        for (local_modified, remote_modified, expected) in cases {
+            let winner = pick_winner(
+                ConflictPolicy::Newest,
+                &local_file(local_modified),
+                &remote_file(remote_modified),
+                unasked,
+            );
+            assert_eq!(
+                winner.unwrap(),
+                expected,
+                "{} vs {}",
+                local_modified,
+                remote_modified
+            );
+        }
+    }
+
+    #[test]
+    fn prompt_keeps_the_answer() {
+        for answer in [Side::Local, Side::Remote] {
+            let winner = pick_winner(
+                ConflictPolicy::Prompt,
+                &local_file(200),
+                &remote_file(100),
+                || Ok(answer),
+            );
+            assert_eq!(winner.unwrap(), answer);
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use crate::common::drive_file;
+use crate::common::file_filter::FileFilter;
+use crate::common::file_name;
+use crate::common::hash_writer;
+use crate::files;
+use crate::files::list::{ListFilesConfig, ListQuery, ListScope, ListSortOrder};
+use crate::hub::Hub;
+use crate::sync::state::State;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs;
+use std::io;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+#[derive(Debug, Clone)]
+pub struct LocalFile {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: u64,
+    pub md5: String,
+}
+
+// Files that don't match the filter are left out and their keys kept in
+// `excluded`, see `drop_excluded`
+#[derive(Debug, Default)]
+pub struct LocalTree {
+    pub files: HashMap<String, LocalFile>,
+    pub excluded: HashSet<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RemoteFile {
+    pub id: String,
+    pub md5: String,
+    pub size: u64,
+    pub modified: i64,
+}
+
+#[derive(Debug, Default)]
+pub struct RemoteTree {
+    // Folder ids by relative path, the synced folder itself is the empty path
+    pub folders: HashMap<String, String>,
+    pub files: HashMap<String, RemoteFile>,
+    pub excluded: HashSet<String>,
+}
+
+// Files below `root` by relative path. Hashing is skipped for files whose size
+// and modification time match the state from the last sync
+pub fn scan_local(root: &Path, state: &State, filter: &FileFilter) -> Result<LocalTree, Error> {
+    let mut tree = list_local(root, filter)?;
+
+    for (key, file) in tree.files.iter_mut() {
+        file.md5 = match state.files.get(key) {
+            Some(entry) if entry.size == file.size && entry.local_modified == file.modified => {
+                entry.md5.clone()
+            }
+            _ => hash_writer::md5_of_file(&file.path)
+                .map_err(|err| Error::Hash(file.path.clone(), err))?,
+        };
+    }
+
+    Ok(tree)
+}
+
+// Like scan_local without hashing, the md5 of the files is left empty
+pub fn list_local(root: &Path, filter: &FileFilter) -> Result<LocalTree, Error> {
+    let mut tree = LocalTree::default();
+    let mut pending = vec![(root.to_path_buf(), String::new())];
+
+    while let Some((dir, prefix)) = pending.pop() {
+        let entries = fs::read_dir(&dir).map_err(|err| Error::ReadDir(dir.clone(), err))?;
+
+        for entry in entries {
+            let entry = entry.map_err(|err| Error::ReadDir(dir.clone(), err))?;
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().to_string();
+            let key = join_key(&prefix, &name);
+            let file_type = entry
+                .file_type()
+                .map_err(|err| Error::ReadDir(dir.clone(), err))?;
+
+            if file_type.is_dir() {
+                pending.push((path, key));
+            } else if file_type.is_file() {
+                let metadata = entry
+                    .metadata()
+                    .map_err(|err| Error::ReadDir(dir.clone(), err))?;
+                let size = metadata.len();
+                let modified = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                    .map(|duration| duration.as_secs())
+                    .unwrap_or(0);
+
+                let mime_type = mime_guess::from_path(&path).first_or_octet_stream();
+                if !filter.matches(size, mime_type.essence_str()) {
+                    tree.excluded.insert(key);
+                    continue;
+                }
+
+                tree.files.insert(
+                    key,
+                    LocalFile {
+                        path,
+                        size,
+                        modified,
+                        md5: String::new(),
+                    },
+                );
+            }
+        }
+    }
+
+    Ok(tree)
+}
+
+// Google Docs and shortcuts have no content to sync and are left out. Files
+// that share their name with another file in the same folder can't be told
+// apart locally, so the scan fails with a list of them to rename on Drive.
+// Names that aren't a single local path component, like `..` or `a/b`, are
+// excluded so they can't write outside the local folder
+pub async fn scan_remote(
+    hub: &Hub,
+    folder_id: &str,
+    filter: &FileFilter,
+) -> Result<RemoteTree, Error> {
+    let mut tree = RemoteTree::default();
+    tree.folders.insert(String::new(), folder_id.to_string());
+    let mut pending = vec![(folder_id.to_string(), String::new())];
+    let mut duplicates = vec![];
+
+    while let Some((current_id, prefix)) = pending.pop() {
+        let children = files::list::list_files(
+            hub,
+            &ListFilesConfig {
+                query: ListQuery::FilesInFolder {
+                    folder_id: current_id,
+                },
+                order_by: ListSortOrder::default(),
+                scope: ListScope::default(),
+                max_files: usize::MAX,
+            },
+        )
+        .await
+        .map_err(Error::List)?;
+
+        let synced = |child: &google_drive3::api::File| {
+            drive_file::is_directory(child) || drive_file::is_binary(child)
+        };
+        let duplicate_names = file_name::duplicate_names(
+            children
+                .iter()
+                .filter(|child| synced(child))
+                .filter_map(|child| child.name.as_deref()),
+        );
+
+        for child in children {
+            let (id, name) = match (child.id.clone(), child.name.clone()) {
+                (Some(id), Some(name)) => (id, name),
+                _ => continue,
+            };
+            let key = join_key(&prefix, &name);
+
+            if !is_safe_name(&name) {
+                if synced(&child) {
+                    eprintln!(
+                        "Warning: '{}' ({}) is skipped, its name can't be used as a local file name",
+                        key, id
+                    );
+                }
+                tree.excluded.insert(key);
+                continue;
+            }
+
+            if synced(&child) && file_name::is_duplicate(&duplicate_names, &name) {
+                duplicates.push(Duplicate { key, id });
+                continue;
+            }
+
+            if drive_file::is_directory(&child) {
+                tree.folders.insert(key.clone(), id.clone());
+                pending.push((id, key));
+            } else if drive_file::is_binary(&child) && !filter.matches_drive_file(&child) {
+                tree.excluded.insert(key);
+            } else if drive_file::is_binary(&child) {
+                tree.files.entry(key).or_insert(RemoteFile {
+                    id,
+                    md5: child.md5_checksum.clone().unwrap_or_default(),
+                    size: child.size.unwrap_or(0).max(0) as u64,
+                    modified: child
+                        .modified_time
+                        .map(|time| time.timestamp())
+                        .unwrap_or(0),
+                });
+            }
+        }
+    }
+
+    if !duplicates.is_empty() {
+        duplicates.sort_by(|a, b| a.key.cmp(&b.key));
+        return Err(Error::DuplicateNames(duplicates));
+    }
+
+    Ok(tree)
+}
+
+#[derive(Debug, Clone)]
+pub struct Duplicate {
+    pub key: String,
+    pub id: String,
+}
+
+// A file excluded on one side is dropped from the other side too, otherwise it
+// would look like it was deleted there
+pub fn drop_excluded(local: &mut LocalTree, remote: &mut RemoteTree) {
+    for key in &local.excluded {
+        remote.files.remove(key);
+    }
+    for key in &remote.excluded {
+        local.files.remove(key);
+    }
+}
+
+fn is_safe_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\'])
+}
+
+// The local path of `key`, None if it would point outside `root`
+pub fn local_path(root: &Path, key: &str) -> Option<PathBuf> {
+    let relative = Path::new(key);
+    let below_root = !key.is_empty()
+        && relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+    below_root.then(|| root.join(relative))
+}
+
+pub fn join_key(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", prefix, name)
+    }
+}
+
+// Relative path of the folder containing `key`, empty for the synced folder itself
+pub fn parent_key(key: &str) -> &str {
+    key.rsplit_once('/').map(|(parent, _)| parent).unwrap_or("")
+}
+
+pub fn file_name(key: &str) -> &str {
+    key.rsplit_once('/').map(|(_, name)| name).unwrap_or(key)
+}
+
+#[derive(Debug)]
+pub enum Error {
+    ReadDir(PathBuf, io::Error),
+    Hash(PathBuf, io::Error),
+    List(files::list::Error),
+    DuplicateNames(Vec<Duplicate>),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::ReadDir(path, err) => {
+                write!(f, "Failed to read directory '{}': {}", path.display(), err)
+            }
+            Error::Hash(path, err) => write!(f, "Failed to hash '{}': {}", path.display(), err),
+            Error::List(err) => write!(f, "{}", err),
+            Error::DuplicateNames(duplicates) => {
+                write!(
+                    f,
+                    "Drive has more than one file with the same name in a folder, rename them on Drive to sync:"
+                )?;
+                for duplicate in duplicates {
+                    write!(f, "\n  {} ({})", duplicate.key, duplicate.id)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
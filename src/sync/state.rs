@@ -0,0 +1,128 @@
+use crate::app_config;
+use crate::app_config::AppConfig;
+use crate::common::file_filter::FileFilter;
+use crate::sync::plan::Direction;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+const STATE_DIR_NAME: &str = "sync";
+
+// What both sides looked like after the last sync of a pair, keyed by the path
+// relative to the local directory. Changes are detected by comparing each side
+// against this, so a file changed on both sides since the last sync is a conflict
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct State {
+    #[serde(default)]
+    pub local_path: PathBuf,
+    #[serde(default)]
+    pub folder_id: String,
+    #[serde(default)]
+    pub filter: FileFilter,
+    #[serde(default)]
+    pub direction: Direction,
+    // Unix timestamp of the last sync that completed
+    #[serde(default)]
+    pub last_synced: i64,
+    pub files: HashMap<String, Entry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub file_id: String,
+    pub md5: String,
+    pub size: u64,
+    pub local_modified: u64,
+    pub remote_modified: i64,
+}
+
+impl State {
+    pub fn load(path: &Path) -> Result<State, Error> {
+        if !path.exists() {
+            return Ok(State::default());
+        }
+
+        let content =
+            fs::read_to_string(path).map_err(|err| Error::Read(path.to_path_buf(), err))?;
+        serde_json::from_str(&content).map_err(Error::Deserialize)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|err| Error::Write(dir.to_path_buf(), err))?;
+        }
+
+        let content = serde_json::to_string(self).map_err(Error::Serialize)?;
+        fs::write(path, content).map_err(|err| Error::Write(path.to_path_buf(), err))
+    }
+}
+
+// One state file per local directory and remote folder pair
+pub fn state_path(local_path: &Path, folder_id: &str) -> Result<PathBuf, Error> {
+    let key = md5::compute(format!("{}\n{}", local_path.display(), folder_id));
+    Ok(state_dir()?.join(format!("{:x}.json", key)))
+}
+
+// States of all pairs that have been synced with the current account
+pub fn pairs() -> Result<Vec<State>, Error> {
+    let dir = state_dir()?;
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let entries = fs::read_dir(&dir).map_err(|err| Error::Read(dir.clone(), err))?;
+    let mut states = vec![];
+    for entry in entries {
+        let path = entry.map_err(|err| Error::Read(dir.clone(), err))?.path();
+        if path.extension().map(|ext| ext == "json").unwrap_or(false) {
+            states.push(State::load(&path)?);
+        }
+    }
+
+    states.sort_by(|a, b| a.local_path.cmp(&b.local_path));
+    Ok(states)
+}
+
+fn state_dir() -> Result<PathBuf, Error> {
+    let app_cfg = AppConfig::load_current_account().map_err(Error::AppConfig)?;
+    Ok(app_cfg.account_base_path().join(STATE_DIR_NAME))
+}
+
+#[derive(Debug)]
+pub enum Error {
+    AppConfig(app_config::Error),
+    Serialize(serde_json::Error),
+    Deserialize(serde_json::Error),
+    Read(PathBuf, io::Error),
+    Write(PathBuf, io::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::AppConfig(err) => write!(f, "{}", err),
+            Error::Serialize(err) => write!(f, "Failed to serialize sync state: {}", err),
+            Error::Deserialize(err) => write!(f, "Failed to parse sync state: {}", err),
+            Error::Read(path, err) => {
+                write!(f, "Failed to read sync state '{}': {}", path.display(), err)
+            }
+            Error::Write(path, err) => {
+                write!(
+                    f,
+                    "Failed to write sync state '{}': {}",
+                    path.display(),
+                    err
+                )
+            }
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use crate::common::hub_helper;
+use crate::sync::plan;
+use crate::sync::plan::Direction;
+use crate::sync::plan::Summary;
+use crate::sync::scan;
+use crate::sync::state;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+pub async fn status() -> Result<(), Error> {
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+    let pairs = state::pairs().map_err(Error::State)?;
+
+    if pairs.is_empty() {
+        println!("No directories have been synced yet, use `gdrive sync run` to sync one");
+        return Ok(());
+    }
+
+    for state in pairs {
+        let arrow = match state.direction {
+            Direction::TwoWay => "<->",
+            Direction::Upload => "->",
+            Direction::Download => "<-",
+        };
+        println!(
+            "{} {} {}",
+            state.local_path.display(),
+            arrow,
+            state.folder_id
+        );
+
+        if !state.local_path.is_dir() {
+            println!("  Local directory is missing");
+            continue;
+        }
+
+        let mut local =
+            scan::scan_local(&state.local_path, &state, &state.filter).map_err(Error::Scan)?;
+        let mut remote = scan::scan_remote(&hub, &state.folder_id, &state.filter)
+            .await
+            .map_err(Error::Scan)?;
+        scan::drop_excluded(&mut local, &mut remote);
+        let actions = plan::restrict(
+            plan::plan(&local.files, &remote, &state),
+            state.direction,
+            &local.files,
+        );
+        let summary = Summary::from_actions(&actions);
+
+        if summary.is_empty() {
+            println!("  Up to date");
+        } else {
+            println!("  {}", summary);
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    State(state::Error),
+    Scan(scan::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::State(err) => write!(f, "{}", err),
+            Error::Scan(err) => write!(f, "{}", err),
+        }
+    }
+}
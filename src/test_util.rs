@@ -0,0 +1,295 @@
+// Helpers for testing code that talks to Drive without credentials or network.
+// MockServer answers with canned responses on localhost and its hub sends all
+// requests there, with a fake token that never expires:
+//
+//     let server = MockServer::start().await;
+//     server.respond_fixture("GET", "/drive/v3/files", "files_list.json");
+//     let files = list::list_files(&server.hub().await, &config).await?;
+//     assert_eq!(server.requests()[0].query_param("pageSize"), Some("100".into()));
+//
+// Enabled with the `test-util` feature
+use crate::app_config::HttpConfig;
+use crate::app_config::Secret;
+use crate::hub::Auth;
+use crate::hub::Hub;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server, StatusCode};
+use percent_encoding::percent_decode_str;
+use std::convert::Infallible;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use tempfile::TempDir;
+use tokio::sync::oneshot;
+
+pub const ACCESS_TOKEN: &str = "test-access-token";
+
+const FIXTURES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+// The stored token is only used for requests with scopes it has
+const SCOPES: [&str; 9] = [
+    "https://www.googleapis.com/auth/drive",
+    "https://www.googleapis.com/auth/drive.activity.readonly",
+    "https://www.googleapis.com/auth/drive.appdata",
+    "https://www.googleapis.com/auth/drive.file",
+    "https://www.googleapis.com/auth/drive.metadata",
+    "https://www.googleapis.com/auth/drive.metadata.readonly",
+    "https://www.googleapis.com/auth/drive.photos.readonly",
+    "https://www.googleapis.com/auth/drive.readonly",
+    "https://www.googleapis.com/auth/drive.scripts",
+];
+
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    pub headers: hyper::HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl RecordedRequest {
+    pub fn query_param(&self, name: &str) -> Option<String> {
+        self.query.as_deref()?.split('&').find_map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            if key == name {
+                Some(percent_decode_str(value).decode_utf8_lossy().to_string())
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn body_text(&self) -> String {
+        String::from_utf8_lossy(&self.body).to_string()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Route {
+    method: String,
+    path: String,
+    // Parameters the request must have, i.e. alt=media for downloads
+    params: Vec<(String, String)>,
+    status: StatusCode,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Route {
+    fn matches(&self, request: &RecordedRequest) -> bool {
+        self.method == request.method
+            && self.path == request.path
+            && self
+                .params
+                .iter()
+                .all(|(name, value)| request.query_param(name).as_deref() == Some(value.as_str()))
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    routes: Vec<Route>,
+    requests: Vec<RecordedRequest>,
+}
+
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    shutdown: Option<oneshot::Sender<()>>,
+    tokens_dir: TempDir,
+}
+
+impl MockServer {
+    pub async fn start() -> MockServer {
+        let state = Arc::new(Mutex::new(State::default()));
+
+        let service_state = state.clone();
+        let make_service = make_service_fn(move |_conn| {
+            let state = service_state.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let state = state.clone();
+                    async move { Ok::<_, Infallible>(handle(&state, request).await) }
+                }))
+            }
+        });
+
+        let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_service);
+        let addr = server.local_addr();
+        let (shutdown, stopped) = oneshot::channel::<()>();
+        tokio::spawn(server.with_graceful_shutdown(async {
+            let _ = stopped.await;
+        }));
+
+        MockServer {
+            addr,
+            state,
+            shutdown: Some(shutdown),
+            tokens_dir: tempfile::tempdir().expect("Failed to create tokens directory"),
+        }
+    }
+
+    // Ends with a slash like the googleapis.com root url
+    pub fn url(&self) -> String {
+        format!("http://{}/", self.addr)
+    }
+
+    pub async fn hub(&self) -> Hub {
+        let tokens_path = self.tokens_dir.path().join("tokens.json");
+        let tokens = serde_json::json!([{
+            "scopes": SCOPES,
+            "token": {
+                "access_token": ACCESS_TOKEN,
+                "refresh_token": null,
+                "expires_at": null,
+                "id_token": null,
+            },
+        }]);
+        fs::write(&tokens_path, tokens.to_string()).expect("Failed to write tokens");
+
+        let secret = Secret {
+            client_id: "test-client-id".to_string(),
+            client_secret: "test-client-secret".to_string(),
+        };
+        let auth = Auth::new(&secret, &tokens_path)
+            .await
+            .expect("Failed to create authenticator");
+
+        Hub::with_root_url(auth, &HttpConfig::default(), &self.url())
+    }
+
+    // Answers requests for the path. Parameters in the path have to be in the
+    // query of the request, others are ignored, i.e. "/drive/v3/files/id?alt=media".
+    // The route added last wins, so a test can change the answer for a later request
+    pub fn respond(&self, method: &str, path: &str, status: u16, body: impl Into<Vec<u8>>) {
+        self.respond_with_headers(method, path, status, &[], body)
+    }
+
+    pub fn respond_with_headers(
+        &self,
+        method: &str,
+        path: &str,
+        status: u16,
+        headers: &[(&str, &str)],
+        body: impl Into<Vec<u8>>,
+    ) {
+        let (path, query) = path.split_once('?').unwrap_or((path, ""));
+        let route = Route {
+            method: method.to_uppercase(),
+            path: path.to_string(),
+            params: query
+                .split('&')
+                .filter(|pair| !pair.is_empty())
+                .map(|pair| {
+                    let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                    (name.to_string(), value.to_string())
+                })
+                .collect(),
+            status: StatusCode::from_u16(status).expect("Invalid status code"),
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            body: body.into(),
+        };
+        self.state.lock().unwrap().routes.push(route);
+    }
+
+    pub fn respond_json(&self, method: &str, path: &str, body: &serde_json::Value) {
+        self.respond_with_headers(
+            method,
+            path,
+            200,
+            &[("content-type", "application/json")],
+            body.to_string(),
+        )
+    }
+
+    // Answers with a recorded response from tests/fixtures
+    pub fn respond_fixture(&self, method: &str, path: &str, name: &str) {
+        self.respond_with_headers(
+            method,
+            path,
+            200,
+            &[("content-type", "application/json")],
+            fixture(name),
+        )
+    }
+
+    // The requests received so far, in order
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+pub fn fixture(name: &str) -> String {
+    let path = PathBuf::from(FIXTURES_DIR).join(name);
+    fs::read_to_string(&path)
+        .unwrap_or_else(|err| panic!("Failed to read fixture '{}': {}", path.display(), err))
+}
+
+async fn handle(state: &Mutex<State>, request: Request<Body>) -> Response<Body> {
+    let (parts, body) = request.into_parts();
+    let body = hyper::body::to_bytes(body).await.unwrap_or_default();
+
+    let recorded = RecordedRequest {
+        method: parts.method.to_string(),
+        path: parts.uri.path().to_string(),
+        query: parts.uri.query().map(String::from),
+        headers: parts.headers,
+        body: body.to_vec(),
+    };
+
+    let mut state = state.lock().unwrap();
+    let route = state
+        .routes
+        .iter()
+        .rev()
+        .find(|route| route.matches(&recorded))
+        .cloned();
+    state.requests.push(recorded.clone());
+    drop(state);
+
+    match route {
+        Some(route) => route_response(route),
+        None => not_found(&recorded),
+    }
+}
+
+fn route_response(route: Route) -> Response<Body> {
+    let mut builder = Response::builder().status(route.status);
+    for (name, value) in &route.headers {
+        builder = builder.header(name, value);
+    }
+    builder
+        .body(Body::from(route.body))
+        .expect("Invalid mock response")
+}
+
+// Shaped like a Drive error so callers report it like one
+fn not_found(request: &RecordedRequest) -> Response<Body> {
+    let message = format!("No mock response for {} {}", request.method, request.path);
+    let body = serde_json::json!({
+        "error": {
+            "code": 404,
+            "message": message,
+        }
+    });
+
+    Response::builder()
+        .status(StatusCode::NOT_FOUND)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .expect("Invalid mock response")
+}
@@ -0,0 +1,351 @@
+use crate::app_config;
+use crate::app_config::AppConfig;
+use crate::common::drive_file;
+use crate::common::file_name;
+use crate::common::hub_helper;
+use crate::files;
+use crate::files::download;
+use crate::files::index;
+use crate::files::list;
+use crate::files::list::ListFilesConfig;
+use crate::files::list::ListQuery;
+use crate::files::list::ListScope;
+use crate::files::list::ListSortOrder;
+use crate::hub::Hub;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::error;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const STATE_DIR_NAME: &str = "watch";
+
+pub struct Config {
+    pub folder_id: String,
+    pub download_to: PathBuf,
+    pub interval: Duration,
+    // Check for new files once and exit, i.e. when run from cron
+    pub once: bool,
+}
+
+// Kept in the account directory, so a restarted watch neither downloads files
+// again nor misses the ones that were added while it was stopped
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct State {
+    // Changes api token of the last check, None before the first one
+    page_token: Option<String>,
+    // Files that have been downloaded or skipped
+    processed: HashSet<String>,
+}
+
+// Downloads files as they are added to a Drive folder. The files already in the
+// folder are downloaded on the first run, after that the Changes api is polled
+// for new files. Subfolders are not watched
+pub async fn watch(config: Config) -> Result<(), Error> {
+    err_if_not_directory(&config.download_to)?;
+
+    let hub = hub_helper::get_hub().await.map_err(Error::Hub)?;
+
+    let folder = files::info::get_file(&hub, &config.folder_id)
+        .await
+        .map_err(Error::GetFolder)?;
+
+    if !drive_file::is_directory(&folder) {
+        return Err(Error::NotADirectory(config.folder_id.clone()));
+    }
+
+    let state_path = state_path(&config)?;
+    let mut state = load_state(&state_path)?;
+
+    println!(
+        "Watching '{}' for new files, downloading to {}",
+        folder.name.unwrap_or_default(),
+        config.download_to.display()
+    );
+
+    loop {
+        match state.page_token {
+            None => download_existing_files(&hub, &config, &mut state, &state_path).await?,
+            Some(_) => check_for_new_files(&hub, &config, &mut state, &state_path).await?,
+        }
+
+        if config.once {
+            return Ok(());
+        }
+
+        tokio::time::sleep(config.interval).await;
+    }
+}
+
+// The token is taken before listing, files added in between show up in both
+// and are only downloaded once. Until every file has been downloaded there is
+// no token, and the next check lists the folder again
+async fn download_existing_files(
+    hub: &Hub,
+    config: &Config,
+    state: &mut State,
+    state_path: &Path,
+) -> Result<(), Error> {
+    let listed = async {
+        let page_token = index::get_start_page_token(hub)
+            .await
+            .map_err(Error::Changes)?;
+
+        let files = list::list_files(
+            hub,
+            &ListFilesConfig {
+                query: ListQuery::FilesInFolder {
+                    folder_id: config.folder_id.clone(),
+                },
+                order_by: ListSortOrder::default(),
+                scope: ListScope::default(),
+                max_files: usize::MAX,
+            },
+        )
+        .await
+        .map_err(Error::ListFiles)?;
+
+        Ok::<_, Error>((page_token, files))
+    };
+
+    let (page_token, files) = match listed.await {
+        Ok(result) => result,
+        Err(err) => {
+            eprintln!("Warning: Failed to list the folder: {}", err);
+            return Ok(());
+        }
+    };
+
+    let failed = process_files(hub, &files, config, state, state_path).await?;
+    if failed == 0 {
+        state.page_token = Some(page_token);
+        save_state(state_path, state)?;
+    }
+
+    Ok(())
+}
+
+async fn check_for_new_files(
+    hub: &Hub,
+    config: &Config,
+    state: &mut State,
+    state_path: &Path,
+) -> Result<(), Error> {
+    let page_token = state.page_token.clone().unwrap_or_default();
+
+    let (changes, new_page_token) = match index::list_changes(hub, &page_token).await {
+        Ok(result) => result,
+        Err(err) => {
+            // The next check tries again, a watch should survive a network hiccup
+            eprintln!("Warning: Failed to check for new files: {}", err);
+            return Ok(());
+        }
+    };
+
+    let files: Vec<google_drive3::api::File> = changes
+        .into_iter()
+        .filter(|change| change.removed != Some(true))
+        .filter_map(|change| change.file)
+        .filter(|file| file.trashed != Some(true))
+        .filter(|file| is_in_folder(file, &config.folder_id))
+        .collect();
+
+    let failed = process_files(hub, &files, config, state, state_path).await?;
+
+    // The same changes are listed again until every file has been downloaded,
+    // the processed ids keep the others from being downloaded twice
+    if failed == 0 {
+        state.page_token = Some(new_page_token);
+        save_state(state_path, state)?;
+    }
+
+    Ok(())
+}
+
+// Returns the number of files that failed to download
+async fn process_files(
+    hub: &Hub,
+    files: &[google_drive3::api::File],
+    config: &Config,
+    state: &mut State,
+    state_path: &Path,
+) -> Result<usize, Error> {
+    let mut failed = 0;
+
+    for file in files {
+        let file_id = file.id.clone().unwrap_or_default();
+        let name = file.name.clone().unwrap_or_default();
+
+        if state.processed.contains(&file_id)
+            || drive_file::is_directory(file)
+            || drive_file::is_shortcut(file)
+        {
+            continue;
+        }
+
+        if drive_file::is_binary(file) {
+            match download_file(hub, file, &config.download_to).await {
+                Ok(path) => println!("Downloaded '{}' to {}", name, path.display()),
+                Err(err) => {
+                    eprintln!("Warning: Failed to download '{}': {}", name, err);
+                    failed += 1;
+                    continue;
+                }
+            }
+        } else {
+            eprintln!(
+                "Warning: Skipping '{}', Google Docs can't be downloaded, use `gdrive files export`",
+                name
+            );
+        }
+
+        state.processed.insert(file_id);
+        save_state(state_path, state)?;
+    }
+
+    Ok(failed)
+}
+
+// Files that have the same name as an existing local file get a number, i.e.
+// "scan (2).pdf", nothing in the destination is overwritten
+async fn download_file(
+    hub: &Hub,
+    file: &google_drive3::api::File,
+    download_to: &Path,
+) -> Result<PathBuf, download::Error> {
+    let name = file_name::sanitize(
+        &file.name.clone().unwrap_or_default(),
+        file_name::DEFAULT_REPLACEMENT,
+    );
+    let path = free_path(&download_to.join(name));
+
+    let body = download::download_file(hub, &file.id.clone().unwrap_or_default())
+        .await
+        .map_err(download::Error::DownloadFile)?;
+
+    download::save_body_to_file(body, &path, file.md5_checksum.clone()).await?;
+
+    Ok(path)
+}
+
+fn free_path(path: &Path) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+
+    let mut candidate = path.to_path_buf();
+    let mut number = 1;
+
+    while candidate.exists() {
+        number += 1;
+        candidate = path.with_file_name(format!("{} ({}){}", stem, number, extension));
+    }
+
+    candidate
+}
+
+fn is_in_folder(file: &google_drive3::api::File, folder_id: &str) -> bool {
+    file.parents
+        .as_ref()
+        .map(|parents| parents.iter().any(|parent| parent == folder_id))
+        .unwrap_or(false)
+}
+
+fn err_if_not_directory(path: &PathBuf) -> Result<(), Error> {
+    if !path.is_dir() {
+        return Err(Error::DestinationNotADirectory(path.clone()));
+    }
+
+    Ok(())
+}
+
+// One state per folder and destination, the same folder can be watched into
+// several directories
+fn state_path(config: &Config) -> Result<PathBuf, Error> {
+    let download_to = config
+        .download_to
+        .canonicalize()
+        .map_err(|err| Error::DestinationNotFound(config.download_to.clone(), err))?;
+    let key = md5::compute(format!("{}\n{}", config.folder_id, download_to.display()));
+
+    let app_cfg = AppConfig::load_current_account().map_err(Error::AppConfig)?;
+    Ok(app_cfg
+        .account_base_path()
+        .join(STATE_DIR_NAME)
+        .join(format!("{:x}.json", key)))
+}
+
+fn load_state(path: &Path) -> Result<State, Error> {
+    if !path.exists() {
+        return Ok(State::default());
+    }
+
+    let content =
+        fs::read_to_string(path).map_err(|err| Error::ReadState(path.to_path_buf(), err))?;
+    serde_json::from_str(&content).map_err(Error::ParseState)
+}
+
+fn save_state(path: &Path, state: &State) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| Error::WriteState(dir.to_path_buf(), err))?;
+    }
+
+    let content = serde_json::to_string(state).map_err(Error::SerializeState)?;
+    fs::write(path, content).map_err(|err| Error::WriteState(path.to_path_buf(), err))
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Hub(hub_helper::Error),
+    AppConfig(app_config::Error),
+    GetFolder(google_drive3::Error),
+    NotADirectory(String),
+    DestinationNotADirectory(PathBuf),
+    DestinationNotFound(PathBuf, io::Error),
+    ListFiles(list::Error),
+    Changes(index::Error),
+    ReadState(PathBuf, io::Error),
+    ParseState(serde_json::Error),
+    SerializeState(serde_json::Error),
+    WriteState(PathBuf, io::Error),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Hub(err) => write!(f, "{}", err),
+            Error::AppConfig(err) => write!(f, "{}", err),
+            Error::GetFolder(err) => write!(f, "Failed to get folder: {}", err),
+            Error::NotADirectory(id) => write!(f, "'{}' is not a folder", id),
+            Error::DestinationNotADirectory(path) => {
+                write!(f, "'{}' is not a directory", path.display())
+            }
+            Error::DestinationNotFound(path, err) => {
+                write!(f, "Failed to find '{}': {}", path.display(), err)
+            }
+            Error::ListFiles(err) => write!(f, "Failed to list files: {}", err),
+            Error::Changes(err) => write!(f, "{}", err),
+            Error::ReadState(path, err) => {
+                write!(f, "Failed to read state '{}': {}", path.display(), err)
+            }
+            Error::ParseState(err) => write!(f, "Failed to parse watch state: {}", err),
+            Error::SerializeState(err) => write!(f, "Failed to serialize watch state: {}", err),
+            Error::WriteState(path, err) => {
+                write!(f, "Failed to write state '{}': {}", path.display(), err)
+            }
+        }
+    }
+}
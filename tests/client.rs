@@ -0,0 +1,230 @@
+use gdrive::client::{Client, Error, ListScope, Query};
+use gdrive::test_util::{fixture, MockServer, ACCESS_TOKEN};
+use std::fs;
+
+const FILE_ID: &str = "1ZyXwVuTsRqPoNmLkJiHgFeDcBa";
+
+async fn client(server: &MockServer) -> Client {
+    Client::from_hub(server.hub().await)
+}
+
+#[tokio::test]
+async fn list_sends_query_and_token() {
+    let server = MockServer::start().await;
+    server.respond_fixture("GET", "/drive/v3/files", "files_list.json");
+    let client = client(&server).await;
+
+    let files = client
+        .list(Query::folder("root").not_trashed(), 100)
+        .await
+        .unwrap();
+
+    let names: Vec<_> = files.iter().filter_map(|file| file.name.clone()).collect();
+    assert_eq!(names, vec!["Photos", "notes.txt"]);
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(
+        requests[0].query_param("q").as_deref(),
+        Some("'root' in parents and trashed = false")
+    );
+    assert_eq!(
+        requests[0].headers["authorization"],
+        format!("Bearer {}", ACCESS_TOKEN)
+    );
+}
+
+#[tokio::test]
+async fn list_in_searches_only_the_shared_drive() {
+    let server = MockServer::start().await;
+    server.respond_fixture("GET", "/drive/v3/files", "files_list.json");
+    let client = client(&server).await;
+
+    client
+        .list_in(Query::new().not_trashed(), ListScope::drive("driveId"), 100)
+        .await
+        .unwrap();
+
+    let request = &server.requests()[0];
+    assert_eq!(request.query_param("corpora").as_deref(), Some("drive"));
+    assert_eq!(request.query_param("driveId").as_deref(), Some("driveId"));
+    assert_eq!(
+        request.query_param("includeItemsFromAllDrives").as_deref(),
+        Some("true")
+    );
+}
+
+#[tokio::test]
+async fn info_reports_missing_file() {
+    let server = MockServer::start().await;
+    server.respond(
+        "GET",
+        "/drive/v3/files/missing",
+        404,
+        fixture("not_found.json"),
+    );
+    let client = client(&server).await;
+
+    let err = client.info("missing").await.unwrap_err();
+
+    assert!(matches!(err, Error::GetFile(_)));
+    assert!(err.to_string().contains("File not found"));
+}
+
+#[tokio::test]
+async fn upload_sends_content() {
+    let server = MockServer::start().await;
+    server.respond_fixture("POST", "/upload/drive/v3/files", "uploaded_file.json");
+    let client = client(&server).await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("report.txt");
+    fs::write(&path, fixture("file_content.txt")).unwrap();
+
+    let file = client
+        .upload(&path, Some(vec!["0AAbCdEfGhIjKUk9PVA".to_string()]))
+        .await
+        .unwrap();
+
+    assert_eq!(file.id.as_deref(), Some("1UpLoAdEdFiLeIdAbCdEfGhIjK"));
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    let body = requests[0].body_text();
+    assert!(body.contains("\"name\":\"report.txt\""));
+    assert!(body.contains("0AAbCdEfGhIjKUk9PVA"));
+    assert!(body.contains("hello from drive"));
+}
+
+#[tokio::test]
+async fn download_writes_file_into_directory() {
+    let server = MockServer::start().await;
+    let path = format!("/drive/v3/files/{}", FILE_ID);
+    server.respond_fixture("GET", &path, "file_metadata.json");
+    server.respond(
+        "GET",
+        &format!("{}?alt=media", path),
+        200,
+        fixture("file_content.txt"),
+    );
+    let client = client(&server).await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let saved = client.download(FILE_ID, dir.path()).await.unwrap();
+
+    assert_eq!(saved, dir.path().join("notes.txt"));
+    assert_eq!(fs::read_to_string(saved).unwrap(), "hello from drive\n");
+}
+
+#[tokio::test]
+async fn download_rejects_wrong_checksum() {
+    let server = MockServer::start().await;
+    let path = format!("/drive/v3/files/{}", FILE_ID);
+    server.respond_fixture("GET", &path, "file_metadata.json");
+    server.respond("GET", &format!("{}?alt=media", path), 200, "corrupted\n");
+    let client = client(&server).await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let err = client.download(FILE_ID, dir.path()).await.unwrap_err();
+
+    assert!(matches!(err, Error::Save(_)));
+    assert!(!dir.path().join("notes.txt").exists());
+}
+
+#[tokio::test]
+async fn trash_patches_file() {
+    let server = MockServer::start().await;
+    let path = format!("/drive/v3/files/{}", FILE_ID);
+    server.respond_fixture("GET", &path, "file_metadata.json");
+    server.respond_fixture("PATCH", &path, "trashed_file.json");
+    let client = client(&server).await;
+
+    client.trash(FILE_ID).await.unwrap();
+
+    // The name and parents are looked up first for the deletion journal
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].method, "GET");
+    assert!(requests[1].body_text().contains("\"trashed\":true"));
+}
+
+#[tokio::test]
+async fn delete_removes_file() {
+    let server = MockServer::start().await;
+    let path = format!("/drive/v3/files/{}", FILE_ID);
+    server.respond_fixture("GET", &path, "file_metadata.json");
+    server.respond("DELETE", &path, 204, "");
+    let client = client(&server).await;
+
+    client.delete(FILE_ID).await.unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[1].method, "DELETE");
+}
+
+#[tokio::test]
+async fn permissions_are_listed() {
+    let server = MockServer::start().await;
+    server.respond_fixture(
+        "GET",
+        &format!("/drive/v3/files/{}/permissions", FILE_ID),
+        "permissions_list.json",
+    );
+    let client = client(&server).await;
+
+    let permissions = client.permissions(FILE_ID).await.unwrap();
+
+    let roles: Vec<_> = permissions
+        .iter()
+        .filter_map(|permission| permission.role.clone())
+        .collect();
+    assert_eq!(roles, vec!["owner", "reader"]);
+}
+
+#[tokio::test]
+async fn info_many_sends_one_batch() {
+    let server = MockServer::start().await;
+    let batch_response = format!(
+        "--batch_abc\r\n\
+         Content-Type: application/http\r\n\
+         Content-ID: <response-item1>\r\n\r\n\
+         HTTP/1.1 404 Not Found\r\n\
+         Content-Type: application/json\r\n\r\n\
+         {}\r\n\
+         --batch_abc\r\n\
+         Content-Type: application/http\r\n\
+         Content-ID: <response-item0>\r\n\r\n\
+         HTTP/1.1 200 OK\r\n\
+         Content-Type: application/json\r\n\r\n\
+         {}\r\n\
+         --batch_abc--\r\n",
+        fixture("not_found.json").trim(),
+        fixture("file_metadata.json").trim()
+    );
+    server.respond_with_headers(
+        "POST",
+        "/batch/drive/v3",
+        200,
+        &[("content-type", "multipart/mixed; boundary=batch_abc")],
+        batch_response,
+    );
+    let client = client(&server).await;
+
+    let results = client
+        .info_many(&[FILE_ID.to_string(), "missing".to_string()])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        results[0].as_ref().unwrap().name.as_deref(),
+        Some("notes.txt")
+    );
+    assert!(matches!(results[1], Err(Error::GetFile(_))));
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    let body = requests[0].body_text();
+    assert!(body.contains(&format!("GET /drive/v3/files/{}?fields=", FILE_ID)));
+    assert!(body.contains("GET /drive/v3/files/missing?fields="));
+}
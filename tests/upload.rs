@@ -0,0 +1,136 @@
+// The walk fails on a broken symlink after the root folder is created, which
+// is the same for the upload as a failure halfway through the tree
+#![cfg(unix)]
+
+use gdrive::common::delegate::UploadDelegateConfig;
+use gdrive::common::file_info;
+use gdrive::common::file_info::FileInfo;
+use gdrive::files::upload;
+use gdrive::files::upload_defaults::FolderDefaults;
+use gdrive::test_util::MockServer;
+use std::os::unix::fs::symlink;
+use std::path::Path;
+use std::path::PathBuf;
+
+const ROOT_FOLDER_ID: &str = "1GeNeRaTeDiDfIrStAbCdEfGhIj";
+
+fn config(path: &Path, rollback_on_failure: bool) -> upload::Config {
+    upload::Config {
+        file_path: Some(path.to_path_buf()),
+        name: None,
+        mime_type: None,
+        parents: Some(vec!["0AAbCdEfGhIjKUk9PVA".to_string()]),
+        chunk_size: None,
+        print_chunk_errors: false,
+        print_chunk_info: false,
+        upload_directories: true,
+        include_hidden: false,
+        print_only_id: false,
+        ocr: false,
+        ocr_language: None,
+        rollback_on_failure,
+        media: upload::MediaOptions::default(),
+    }
+}
+
+async fn failing_upload(server: &MockServer, rollback_on_failure: bool) -> upload::Error {
+    server.respond_fixture("GET", "/drive/v3/files/generateIds", "generated_ids.json");
+    server.respond_fixture("POST", "/upload/drive/v3/files", "created_folder.json");
+    server.respond(
+        "DELETE",
+        &format!("/drive/v3/files/{}", ROOT_FOLDER_ID),
+        204,
+        "",
+    );
+    let hub = server.hub().await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("photos");
+    std::fs::create_dir(&path).unwrap();
+    symlink(PathBuf::from("missing.jpg"), path.join("broken.jpg")).unwrap();
+
+    upload::upload_directory(
+        &hub,
+        &config(&path, rollback_on_failure),
+        &FolderDefaults::default(),
+        UploadDelegateConfig::default(),
+    )
+    .await
+    .unwrap_err()
+}
+
+fn deleted_paths(server: &MockServer) -> Vec<String> {
+    server
+        .requests()
+        .into_iter()
+        .filter(|request| request.method == "DELETE")
+        .map(|request| request.path)
+        .collect()
+}
+
+#[tokio::test]
+async fn failed_directory_upload_is_rolled_back() {
+    let server = MockServer::start().await;
+
+    let err = failing_upload(&server, true).await;
+
+    assert!(matches!(err, upload::Error::CreateFileTree(_)));
+    assert_eq!(
+        deleted_paths(&server),
+        vec![format!("/drive/v3/files/{}", ROOT_FOLDER_ID)]
+    );
+}
+
+#[tokio::test]
+async fn failed_directory_upload_is_kept_without_rollback() {
+    let server = MockServer::start().await;
+
+    failing_upload(&server, false).await;
+
+    assert!(deleted_paths(&server).is_empty());
+}
+
+#[tokio::test]
+async fn media_options_are_sent_with_the_upload() {
+    let server = MockServer::start().await;
+    server.respond_fixture("POST", "/upload/drive/v3/files", "uploaded_file.json");
+    let hub = server.hub().await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("talk.vtt");
+    std::fs::write(&path, "WEBVTT").unwrap();
+    let file = std::fs::File::open(&path).unwrap();
+    let file_info = FileInfo::from_file(
+        &file,
+        &file_info::Config {
+            file_path: path.clone(),
+            mime_type: None,
+            parents: None,
+        },
+    )
+    .unwrap();
+
+    upload::upload_file_with_options(
+        &hub,
+        file,
+        None,
+        file_info,
+        upload::MediaOptions {
+            indexable_text: true,
+            keep_forever: true,
+        },
+        UploadDelegateConfig::default(),
+    )
+    .await
+    .unwrap();
+
+    let request = &server.requests()[0];
+    assert_eq!(
+        request.query_param("useContentAsIndexableText").as_deref(),
+        Some("true")
+    );
+    assert_eq!(
+        request.query_param("keepRevisionForever").as_deref(),
+        Some("true")
+    );
+}